@@ -0,0 +1,103 @@
+use std::io::sink;
+use std::time::Instant;
+
+use anyhow::Result;
+use binseq::SequencingRecordBuilder;
+use binseq::prelude::*;
+use binseq::utils::OnIncompatible;
+use binseq::vbq::{FileHeader, FileHeaderBuilder, MmapReader, WriterBuilder, convert_bitsize};
+use clap::Parser;
+
+#[derive(Parser)]
+struct Args {
+    /// Input VBQ path
+    #[clap(default_value = "./data/subset.vbq")]
+    input: String,
+}
+
+/// Header of the input with the other bitsize
+fn target_header(input: FileHeader) -> FileHeader {
+    let bits = match input.bits {
+        BitSize::Two => BitSize::Four,
+        BitSize::Four => BitSize::Two,
+    };
+    FileHeaderBuilder::new()
+        .block(input.block)
+        .compressed(input.compressed)
+        .flags(input.flags)
+        .qual(input.qual)
+        .paired(input.paired)
+        .headers(input.headers)
+        .bitsize(bits)
+        .build()
+}
+
+/// Converts by decoding every record to ASCII and encoding it again
+fn convert_decoded(input: &str, header: FileHeader) -> Result<usize> {
+    let mut reader = MmapReader::new(input)?;
+    let mut writer = WriterBuilder::default()
+        .header(header)
+        .policy(Policy::IgnoreSequence)
+        .build(sink())?;
+    let (mut sbuf, mut xbuf) = (Vec::new(), Vec::new());
+    let mut written = 0;
+    let mut block = reader.new_block();
+    while reader.read_block_into(&mut block)? {
+        for record in block.iter() {
+            sbuf.clear();
+            xbuf.clear();
+            record.decode_s(&mut sbuf)?;
+            let mut builder = SequencingRecordBuilder::default()
+                .s_seq(&sbuf)
+                .opt_flag(record.flag());
+            if header.qual {
+                builder = builder.s_qual(record.squal());
+            }
+            if header.headers {
+                builder = builder.s_header(record.sheader());
+            }
+            if record.is_paired() {
+                record.decode_x(&mut xbuf)?;
+                builder = builder.x_seq(&xbuf);
+                if header.qual {
+                    builder = builder.x_qual(record.xqual());
+                }
+                if header.headers {
+                    builder = builder.x_header(record.xheader());
+                }
+            }
+            written += usize::from(writer.push(builder.build()?)?);
+        }
+    }
+    writer.finish()?;
+    Ok(written)
+}
+
+/// Converts by transcoding the encoded words
+fn convert_packed(input: &str, header: FileHeader) -> Result<usize> {
+    let mut reader = MmapReader::new(input)?;
+    let mut writer = WriterBuilder::default().header(header).build(sink())?;
+    let stats = convert_bitsize(
+        &mut reader,
+        &mut writer,
+        OnIncompatible::Policy(Policy::IgnoreSequence),
+    )?;
+    writer.finish()?;
+    Ok(stats.written)
+}
+
+/// Compares bitsize conversion on encoded words against decode-then-encode
+fn main() -> Result<()> {
+    let args = Args::parse();
+    let header = target_header(MmapReader::new(&args.input)?.header());
+    println!("converting to {:?}", header.bits);
+
+    let start = Instant::now();
+    let written = convert_packed(&args.input, header)?;
+    println!("  packed: {written} records in {:?}", start.elapsed());
+
+    let start = Instant::now();
+    let written = convert_decoded(&args.input, header)?;
+    println!(" decoded: {written} records in {:?}", start.elapsed());
+    Ok(())
+}
@@ -0,0 +1,62 @@
+use binseq::processors::CountProcessor;
+use binseq::{BinseqReader, Executor, ParallelReader, Result};
+use std::time::Instant;
+
+/// Compares spawn-per-call parallel processing against a reusable `Executor`
+/// when processing many small record ranges in succession.
+fn main() -> Result<()> {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() < 2 {
+        eprintln!(
+            "Usage: {} <binseq_file> [num_threads] [range_size]",
+            args[0]
+        );
+        eprintln!("Example: {} data/subset.bq 4 1000", args[0]);
+        std::process::exit(1);
+    }
+
+    let file_path = &args[1];
+    let num_threads: usize = args.get(2).and_then(|s| s.parse().ok()).unwrap_or(4);
+    let range_size: usize = args.get(3).and_then(|s| s.parse().ok()).unwrap_or(1000);
+
+    let num_records = BinseqReader::new(file_path)?.num_records()?;
+    let ranges: Vec<_> = (0..num_records)
+        .step_by(range_size)
+        .map(|start| start..(start + range_size).min(num_records))
+        .collect();
+    println!(
+        "Processing {} ranges of up to {} records with {} threads",
+        ranges.len(),
+        range_size,
+        num_threads
+    );
+
+    // Spawn fresh threads for every call
+    let counter = CountProcessor::new();
+    let start = Instant::now();
+    for range in &ranges {
+        let reader = BinseqReader::new(file_path)?;
+        reader.process_parallel_range(counter.clone(), num_threads, range.clone())?;
+    }
+    println!(
+        "spawn-per-call: {} records in {:?}",
+        counter.count(),
+        start.elapsed()
+    );
+
+    // Reuse the same worker threads for every call
+    let executor = Executor::new(num_threads);
+    let counter = CountProcessor::new();
+    let start = Instant::now();
+    for range in &ranges {
+        let reader = BinseqReader::new(file_path)?;
+        reader.process_parallel_in(&executor, counter.clone(), range.clone())?;
+    }
+    println!(
+        "executor:       {} records in {:?}",
+        counter.count(),
+        start.elapsed()
+    );
+
+    Ok(())
+}
@@ -0,0 +1,89 @@
+use std::time::Instant;
+
+use anyhow::Result;
+use binseq::SequencingRecordBuilder;
+use binseq::prelude::*;
+use binseq::vbq::{FileHeader, FileHeaderBuilder, MmapReader, WriterBuilder};
+use clap::Parser;
+
+#[derive(Parser)]
+struct Args {
+    /// Number of paired records to write
+    #[clap(short, long, default_value_t = 1_000_000)]
+    num_records: usize,
+}
+
+/// Illumina-style headers, differing only in their tile and coordinates
+fn headers(idx: usize) -> (String, String) {
+    let id = format!(
+        "A00123:8:H7K2LDSX3:{}:{}:{}:{}",
+        1 + idx % 4,
+        1101 + (idx / 7) % 78,
+        1000 + (idx * 37) % 30_000,
+        1000 + (idx * 91) % 36_000
+    );
+    (
+        format!("{id} 1:N:0:ACGTACGT"),
+        format!("{id} 2:N:0:ACGTACGT"),
+    )
+}
+
+/// Writes the records to memory, returning the output
+fn write(header: FileHeader, num_records: usize) -> Result<Vec<u8>> {
+    let seq = b"ACGT".repeat(38);
+    let mut output = Vec::new();
+    let mut writer = WriterBuilder::default().header(header).build(&mut output)?;
+    for idx in 0..num_records {
+        let (sheader, xheader) = headers(idx);
+        let record = SequencingRecordBuilder::default()
+            .s_seq(&seq[idx % 4..])
+            .s_header(sheader.as_bytes())
+            .x_seq(&seq[idx % 3..])
+            .x_header(xheader.as_bytes())
+            .build()?;
+        writer.push(record)?;
+    }
+    writer.finish()?;
+    drop(writer);
+    Ok(output)
+}
+
+/// Reads all records, returning the total length of their headers
+fn read(bytes: Vec<u8>) -> Result<usize> {
+    let mut reader = MmapReader::from_bytes(bytes)?;
+    let mut block = reader.new_block();
+    let mut header_bytes = 0;
+    while reader.read_block_into(&mut block)? {
+        for record in block.iter() {
+            header_bytes += record.sheader().len() + record.xheader().len();
+        }
+    }
+    Ok(header_bytes)
+}
+
+/// Compares file sizes and throughput with and without per-block header prefixes
+fn main() -> Result<()> {
+    let args = Args::parse();
+    for compressed in [false, true] {
+        println!("compressed: {compressed}");
+        let header = FileHeaderBuilder::new()
+            .paired(true)
+            .headers(true)
+            .compressed(compressed)
+            .build();
+        for header_prefix in [false, true] {
+            let start = Instant::now();
+            let bytes = write(header.with_header_prefix(header_prefix), args.num_records)?;
+            let write_time = start.elapsed();
+            let size = bytes.len();
+
+            let start = Instant::now();
+            let header_bytes = read(bytes)?;
+            println!(
+                "  header_prefix: {header_prefix:5} => {size:>12} bytes, write {write_time:?}, read {:?} ({header_bytes} header bytes)",
+                start.elapsed()
+            );
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,138 @@
+use std::sync::Arc;
+use std::time::Instant;
+
+use anyhow::Result;
+use binseq::prelude::*;
+use clap::Parser;
+use parking_lot::Mutex;
+
+/// Strategy used to extract canonical k-mers
+#[derive(Clone, Copy)]
+enum Mode {
+    /// Roll k-mers directly over the encoded sequence
+    Packed,
+
+    /// Decode to ASCII first, then roll k-mers over the decoded bases
+    Decoded,
+}
+
+#[derive(Clone)]
+pub struct KmerSketcher {
+    mode: Mode,
+    k: usize,
+
+    // (thread) local variables
+    dbuf: Vec<u8>,
+    local_count: usize,
+    local_hash: u64,
+
+    // global variables
+    count: Arc<Mutex<usize>>,
+    hash: Arc<Mutex<u64>>,
+}
+impl KmerSketcher {
+    fn new(mode: Mode, k: usize) -> Self {
+        Self {
+            mode,
+            k,
+            dbuf: Vec::new(),
+            local_count: 0,
+            local_hash: 0,
+            count: Arc::new(Mutex::new(0)),
+            hash: Arc::new(Mutex::new(0)),
+        }
+    }
+
+    fn add(&mut self, kmer: u64) {
+        self.local_count += 1;
+        self.local_hash ^= kmer.wrapping_mul(0x9E37_79B9_7F4A_7C15);
+    }
+
+    /// Canonical k-mers of decoded ASCII bases with a rolling reverse complement
+    fn decoded_kmers(&mut self) {
+        let mask = if self.k == 32 {
+            u64::MAX
+        } else {
+            (1 << (2 * self.k)) - 1
+        };
+        let (mut fwd, mut rev, mut valid) = (0u64, 0u64, 0);
+        let dbuf = std::mem::take(&mut self.dbuf);
+        for &base in &dbuf {
+            let code = match base {
+                b'A' => 0,
+                b'C' => 1,
+                b'G' => 2,
+                b'T' => 3,
+                _ => {
+                    valid = 0;
+                    continue;
+                }
+            };
+            fwd = ((fwd << 2) | code) & mask;
+            rev = (rev >> 2) | ((3 ^ code) << (2 * (self.k - 1)));
+            valid += 1;
+            if valid >= self.k {
+                self.add(fwd.min(rev));
+            }
+        }
+        self.dbuf = dbuf;
+    }
+}
+impl ParallelProcessor for KmerSketcher {
+    fn process_record<R: binseq::BinseqRecord>(&mut self, record: R) -> binseq::Result<()> {
+        match self.mode {
+            Mode::Packed => {
+                for (_, kmer) in record.canonical_kmers(self.k) {
+                    self.add(kmer);
+                }
+            }
+            Mode::Decoded => {
+                self.dbuf.clear();
+                record.decode_s(&mut self.dbuf)?;
+                self.decoded_kmers();
+            }
+        }
+        Ok(())
+    }
+
+    fn on_batch_complete(&mut self) -> binseq::Result<()> {
+        *self.count.lock() += self.local_count;
+        *self.hash.lock() ^= self.local_hash;
+        self.local_count = 0;
+        self.local_hash = 0;
+        Ok(())
+    }
+}
+
+#[derive(Parser)]
+struct Args {
+    /// Input BINSEQ path
+    #[clap(default_value = "./data/subset.vbq")]
+    input: String,
+
+    /// K-mer size
+    #[clap(short, long, default_value_t = 21)]
+    k: usize,
+
+    /// Threads to use [0: auto]
+    #[clap(short = 'T', long, default_value_t = 1)]
+    threads: usize,
+}
+
+/// Compares canonical k-mer extraction on encoded sequences against decode-then-hash
+fn main() -> Result<()> {
+    let args = Args::parse();
+    for (name, mode) in [("packed", Mode::Packed), ("decoded", Mode::Decoded)] {
+        let sketcher = KmerSketcher::new(mode, args.k);
+        let reader = BinseqReader::new(&args.input)?;
+        let start = Instant::now();
+        reader.process_parallel(sketcher.clone(), args.threads)?;
+        println!(
+            "{name:>8}: {} k-mers (hash {:016x}) in {:?}",
+            sketcher.count.lock(),
+            sketcher.hash.lock(),
+            start.elapsed()
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,70 @@
+use std::time::{Duration, Instant};
+
+use binseq::prelude::*;
+use binseq::{Result, bq, vbq};
+
+/// A processor whose cost per record varies strongly across the file
+///
+/// Records in the first quarter of the file are much more expensive to process, so a fixed
+/// contiguous assignment leaves most threads idle while one thread finishes the slow chunk.
+#[derive(Clone)]
+struct SkewedProcessor {
+    slow_until: u64,
+}
+impl ParallelProcessor for SkewedProcessor {
+    fn process_record<R: BinseqRecord>(&mut self, record: R) -> Result<()> {
+        let cost = if record.index() < self.slow_until {
+            20
+        } else {
+            1
+        };
+        let start = Instant::now();
+        while start.elapsed() < Duration::from_micros(cost) {
+            std::hint::spin_loop();
+        }
+        Ok(())
+    }
+}
+
+fn main() -> Result<()> {
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() < 2 {
+        eprintln!("Usage: {} <bq_or_vbq_file> [num_threads]", args[0]);
+        eprintln!("Example: {} data/subset.vbq 4", args[0]);
+        std::process::exit(1);
+    }
+
+    let file_path = &args[1];
+    let num_threads: usize = args.get(2).and_then(|s| s.parse().ok()).unwrap_or(4);
+
+    let num_records = BinseqReader::new(file_path)?.num_records()?;
+    let processor = SkewedProcessor {
+        slow_until: num_records as u64 / 4,
+    };
+    println!("Processing {num_records} records with {num_threads} threads");
+
+    let start = Instant::now();
+    BinseqReader::new(file_path)?.process_parallel(processor.clone(), num_threads)?;
+    println!("fixed chunks:  {:?}", start.elapsed());
+
+    let start = Instant::now();
+    match BinseqReader::new(file_path)? {
+        BinseqReader::Bq(_) => bq::MmapReader::new(file_path)?.process_parallel_work_stealing(
+            processor,
+            num_threads,
+            0..num_records,
+        )?,
+        BinseqReader::Vbq(_) => vbq::MmapReader::new(file_path)?.process_parallel_work_stealing(
+            processor,
+            num_threads,
+            0..num_records,
+        )?,
+        BinseqReader::Cbq(_) | BinseqReader::PairedBq(_) => {
+            eprintln!("Work stealing is only available for BQ and VBQ files");
+            std::process::exit(1);
+        }
+    }
+    println!("work stealing: {:?}", start.elapsed());
+
+    Ok(())
+}
@@ -0,0 +1,18 @@
+#![no_main]
+
+use std::io::Cursor;
+
+use binseq::{BinseqRecord, bq};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let mut reader = bq::StreamReader::new(Cursor::new(data));
+    if reader.read_header().is_err() {
+        return;
+    }
+    let mut sbuf = Vec::new();
+    while let Some(Ok(record)) = reader.next_record() {
+        sbuf.clear();
+        let _ = record.decode_s(&mut sbuf);
+    }
+});
@@ -0,0 +1,30 @@
+#![no_main]
+
+use binseq::{BinseqRecord, vbq};
+use libfuzzer_sys::fuzz_target;
+
+/// Largest block size accepted from a fuzzed header (blocks are allocated up front)
+const MAX_BLOCK_SIZE: u64 = 1 << 24;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(mut reader) = vbq::MmapReader::from_bytes(data.to_vec()) else {
+        return;
+    };
+    if reader.header().block > MAX_BLOCK_SIZE {
+        return;
+    }
+    let _ = reader.num_records();
+    let _ = reader.validate_index();
+
+    let mut block = reader.new_block();
+    let (mut sbuf, mut xbuf) = (Vec::new(), Vec::new());
+    while let Ok(true) = reader.read_block_into(&mut block) {
+        for record in block.iter() {
+            sbuf.clear();
+            xbuf.clear();
+            let _ = record.decode_s(&mut sbuf);
+            let _ = record.decode_x(&mut xbuf);
+            let _ = (record.sheader(), record.squal(), record.xqual());
+        }
+    }
+});
@@ -0,0 +1,178 @@
+//! Cached random access to decoded BQ sequences
+//!
+//! Enabled with the `cache` feature.
+
+use std::num::NonZeroUsize;
+use std::sync::Arc;
+
+use lru::LruCache;
+
+use super::MmapReader;
+use crate::{BinseqRecord, Result};
+
+/// Random access to decoded sequences with an LRU cache keyed by record index
+///
+/// Workloads that repeatedly visit the same records (e.g. seed extension during alignment)
+/// otherwise decode the same sequence many times. Primary and extended sequences are cached
+/// separately, each holding up to `capacity` decoded sequences.
+///
+/// The slices returned by [`get_decoded_s`](Self::get_decoded_s) and
+/// [`get_decoded_x`](Self::get_decoded_x) point into the cache and borrow the batch mutably,
+/// so they must be copied (e.g. with `to_vec`) to be kept across further lookups.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::sync::Arc;
+/// use binseq::bq::{MmapReader, RandomAccessBatch};
+///
+/// let reader = Arc::new(MmapReader::new("./data/subset.bq").unwrap());
+/// let mut batch = RandomAccessBatch::new(reader, 128);
+///
+/// let first = batch.get_decoded_s(0).unwrap().to_vec();
+/// assert_eq!(batch.get_decoded_s(0).unwrap(), first.as_slice());
+/// assert_eq!(batch.hits(), 1);
+/// ```
+pub struct RandomAccessBatch {
+    /// Reader used to decode records missing from the cache
+    reader: Arc<MmapReader>,
+
+    /// Decoded primary sequences by record index
+    cache: LruCache<usize, Vec<u8>>,
+
+    /// Decoded extended sequences by record index
+    xcache: LruCache<usize, Vec<u8>>,
+
+    /// Number of lookups served from the cache
+    hits: usize,
+
+    /// Number of lookups that required decoding a record
+    misses: usize,
+}
+impl RandomAccessBatch {
+    /// Creates a new batch over `reader` caching up to `capacity` sequences of each kind
+    ///
+    /// A capacity of zero is treated as one.
+    #[must_use]
+    pub fn new(reader: Arc<MmapReader>, capacity: usize) -> Self {
+        let capacity = NonZeroUsize::new(capacity).unwrap_or(NonZeroUsize::MIN);
+        Self {
+            reader,
+            cache: LruCache::new(capacity),
+            xcache: LruCache::new(capacity),
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    /// Returns the decoded primary sequence of the record at `idx`
+    ///
+    /// The record is only decoded if it is not already cached.
+    pub fn get_decoded_s(&mut self, idx: usize) -> Result<&[u8]> {
+        let (reader, misses) = (&self.reader, &mut self.misses);
+        let found = self.cache.contains(&idx);
+        let seq = self.cache.try_get_or_insert(idx, || {
+            *misses += 1;
+            reader.get(idx)?.decode_s_alloc()
+        })?;
+        self.hits += usize::from(found);
+        Ok(seq.as_slice())
+    }
+
+    /// Returns the decoded extended sequence of the record at `idx`
+    ///
+    /// The sequence is empty if the file is not paired.
+    pub fn get_decoded_x(&mut self, idx: usize) -> Result<&[u8]> {
+        let (reader, misses) = (&self.reader, &mut self.misses);
+        let found = self.xcache.contains(&idx);
+        let seq = self.xcache.try_get_or_insert(idx, || {
+            *misses += 1;
+            reader.get(idx)?.decode_x_alloc()
+        })?;
+        self.hits += usize::from(found);
+        Ok(seq.as_slice())
+    }
+
+    /// Returns the underlying reader
+    #[must_use]
+    pub fn reader(&self) -> &Arc<MmapReader> {
+        &self.reader
+    }
+
+    /// Returns the number of lookups served from the cache
+    #[must_use]
+    pub fn hits(&self) -> usize {
+        self.hits
+    }
+
+    /// Returns the number of lookups that required decoding a record
+    #[must_use]
+    pub fn misses(&self) -> usize {
+        self.misses
+    }
+
+    /// Removes all cached sequences (hit and miss counts are kept)
+    pub fn clear(&mut self) {
+        self.cache.clear();
+        self.xcache.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_BQ_FILE: &str = "./data/subset.bq";
+
+    fn reader() -> Arc<MmapReader> {
+        Arc::new(MmapReader::new(TEST_BQ_FILE).unwrap())
+    }
+
+    #[test]
+    fn test_alternating_access_hits_cache() {
+        let reader = reader();
+        let expected: Vec<_> = (0..2)
+            .map(|idx| reader.get(idx).unwrap().decode_s_alloc().unwrap())
+            .collect();
+
+        let mut batch = RandomAccessBatch::new(reader, 2);
+        for (access, idx) in [0, 1, 0, 1].into_iter().enumerate() {
+            assert_eq!(batch.get_decoded_s(idx).unwrap(), expected[idx].as_slice());
+            if access == 2 {
+                // Second access to record 0 is served from the cache
+                assert_eq!((batch.hits(), batch.misses()), (1, 2));
+            }
+        }
+        assert_eq!((batch.hits(), batch.misses()), (2, 2));
+    }
+
+    #[test]
+    fn test_eviction() {
+        let mut batch = RandomAccessBatch::new(reader(), 1);
+        for idx in [0, 1, 0] {
+            batch.get_decoded_s(idx).unwrap();
+        }
+        assert_eq!((batch.hits(), batch.misses()), (0, 3));
+    }
+
+    #[test]
+    fn test_extended_sequences_cached_separately() {
+        let reader = reader();
+        let expected = reader.get(3).unwrap().decode_x_alloc().unwrap();
+        let mut batch = RandomAccessBatch::new(reader, 4);
+        batch.get_decoded_s(3).unwrap();
+        assert_eq!(batch.get_decoded_x(3).unwrap(), expected.as_slice());
+        assert_eq!(batch.get_decoded_x(3).unwrap(), expected.as_slice());
+        assert_eq!((batch.hits(), batch.misses()), (1, 2));
+    }
+
+    #[test]
+    fn test_out_of_range_is_not_cached() {
+        let reader = reader();
+        let num_records = reader.num_records();
+        let mut batch = RandomAccessBatch::new(reader, 4);
+        assert!(batch.get_decoded_s(num_records).is_err());
+        assert!(batch.get_decoded_s(num_records).is_err());
+        assert_eq!(batch.misses(), 2);
+    }
+}
@@ -0,0 +1,176 @@
+//! Converting BQ files between the 2-bit and 4-bit encodings
+//!
+//! The BQ counterpart of [`vbq::convert_bitsize`](crate::vbq::convert_bitsize).
+
+use std::io::Write;
+
+use super::{MmapReader, Writer};
+use crate::BinseqRecord;
+use crate::error::{HeaderError, Result};
+use crate::utils::{ConvertStats, OnIncompatible, Transcoded, Transcoder};
+
+/// Copies the records of `reader` to `writer`, converting them to the bitsize of the writer
+///
+/// The target bitsize is taken from the header of `writer`, whose sequence lengths must match
+/// the input. Flags are copied as-is.
+///
+/// Converting from 2-bit to 4-bit never fails. Converting from 4-bit to 2-bit fails for
+/// sequences with ambiguous bases (e.g. `N`), which are handled by `on_incompatible`.
+///
+/// # Errors
+///
+/// * `HeaderError::IncompatibleBqHeaders` - If the sequence lengths of the headers differ
+/// * `WriteError::InvalidNucleotideSequence` - If a sequence cannot be encoded with the target
+///   bitsize and `on_incompatible` is [`OnIncompatible::Error`] (or a breaking policy)
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use binseq::BitSize;
+/// use binseq::bq::{FileHeaderBuilder, MmapReader, WriterBuilder, convert_bitsize};
+/// use binseq::utils::OnIncompatible;
+/// use std::fs::File;
+///
+/// let reader = MmapReader::new("legacy.bq").unwrap();
+/// let input = reader.header();
+/// let header = FileHeaderBuilder::new()
+///     .slen(input.slen)
+///     .xlen(input.xlen)
+///     .flags(input.flags)
+///     .bitsize(BitSize::Two)
+///     .build()
+///     .unwrap();
+/// let mut writer = WriterBuilder::default()
+///     .header(header)
+///     .build(File::create("converted.bq").unwrap())
+///     .unwrap();
+///
+/// let stats = convert_bitsize(&reader, &mut writer, OnIncompatible::Error).unwrap();
+/// writer.flush().unwrap();
+/// println!("converted {} records", stats.written);
+/// ```
+pub fn convert_bitsize<W: Write>(
+    reader: &MmapReader,
+    writer: &mut Writer<W>,
+    on_incompatible: OnIncompatible,
+) -> Result<ConvertStats> {
+    let (input, output) = (reader.header(), writer.header());
+    if input.slen != output.slen || input.xlen != output.xlen {
+        return Err(HeaderError::IncompatibleBqHeaders(input, output).into());
+    }
+
+    let mut transcoder = Transcoder::new(input.bits, output.bits, on_incompatible);
+    let mut stats = ConvertStats::default();
+    for idx in 0..reader.num_records() {
+        let record = reader.get(idx)?;
+        stats.records += 1;
+        match transcoder.transcode(&record)? {
+            Transcoded::Skipped => {
+                stats.skipped += 1;
+                continue;
+            }
+            Transcoded::Corrected => stats.corrected += 1,
+            Transcoded::Converted => {}
+        }
+        if output.is_paired() {
+            writer.write_encoded_direct_paired(
+                record.flag(),
+                &transcoder.sbuf,
+                &transcoder.xbuf,
+            )?;
+        } else {
+            writer.write_encoded_direct(record.flag(), &transcoder.sbuf)?;
+        }
+        stats.written += 1;
+    }
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use bitnuc::BitSize;
+
+    use super::*;
+    use crate::Policy;
+    use crate::bq::{FileHeader, FileHeaderBuilder, WriterBuilder};
+    use crate::error::{Error, WriteError};
+    use crate::testing::{self, DatasetConfig, LengthDistribution, SyntheticRecord};
+
+    fn config(paired: bool) -> DatasetConfig {
+        DatasetConfig::new(LengthDistribution::Fixed(75))
+            .paired(paired)
+            .flags(true)
+    }
+
+    /// Converts an in-memory BQ file to the bitsize of `header`
+    fn convert(
+        input: Vec<u8>,
+        header: FileHeader,
+        on_incompatible: OnIncompatible,
+    ) -> Result<(ConvertStats, Vec<u8>)> {
+        let reader = MmapReader::from_bytes(input)?;
+        let mut writer = WriterBuilder::default().header(header).build(Vec::new())?;
+        let stats = convert_bitsize(&reader, &mut writer, on_incompatible)?;
+        writer.flush()?;
+        Ok((stats, writer.into_inner()))
+    }
+
+    #[test]
+    fn test_convert_round_trip() -> Result<()> {
+        for paired in [false, true] {
+            let config = config(paired);
+            let records = testing::random_dataset(200, &config);
+            let two = testing::write_bq(&records, config.bq_header(BitSize::Two)?)?;
+
+            let header = config.bq_header(BitSize::Four)?;
+            let (stats, four) = convert(two.clone(), header, OnIncompatible::Error)?;
+            assert_eq!(stats.written, 200);
+            assert_eq!(
+                MmapReader::from_bytes(four.clone())?.header().bits,
+                BitSize::Four
+            );
+            assert_eq!(testing::read_bq(four.clone(), &config)?, records);
+
+            let header = config.bq_header(BitSize::Two)?;
+            let (_, back) = convert(four, header, OnIncompatible::Error)?;
+            assert_eq!(back, two);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_convert_incompatible() -> Result<()> {
+        let config = config(false).ambiguous(0.005);
+        let records = testing::random_dataset(100, &config);
+        let four = testing::write_bq(&records, config.bq_header(BitSize::Four)?)?;
+        let has_n = |record: &SyntheticRecord| record.s_seq.contains(&b'N');
+        let ambiguous = records.iter().filter(|record| has_n(record)).count() as u64;
+        assert!(ambiguous > 0);
+
+        let two_bit = config.bq_header(BitSize::Two)?;
+        let err = convert(four.clone(), two_bit, OnIncompatible::Error);
+        assert!(matches!(
+            err,
+            Err(Error::WriteError(WriteError::InvalidNucleotideSequence(_)))
+        ));
+
+        let policy = OnIncompatible::Policy(Policy::IgnoreSequence);
+        let (stats, two) = convert(four.clone(), two_bit, policy)?;
+        assert_eq!(stats.skipped, ambiguous);
+        assert_eq!(stats.written, 100 - ambiguous);
+        let kept: Vec<_> = records
+            .iter()
+            .filter(|record| !has_n(record))
+            .cloned()
+            .collect();
+        assert_eq!(testing::read_bq(two, &config)?, kept);
+
+        let mismatched = FileHeaderBuilder::new().slen(76).build()?;
+        let err = convert(four, mismatched, OnIncompatible::Error);
+        assert!(matches!(
+            err,
+            Err(Error::HeaderError(HeaderError::IncompatibleBqHeaders(..)))
+        ));
+        Ok(())
+    }
+}
@@ -6,9 +6,14 @@
 
 use bitnuc::BitSize;
 use byteorder::{ByteOrder, LittleEndian};
+use std::fmt;
 use std::io::{Read, Write};
 
-use crate::error::{BuilderError, HeaderError, Result};
+use super::reader::RecordConfig;
+use crate::{
+    error::{BuilderError, HeaderError, Result},
+    magic,
+};
 
 /// Current magic number: "BSEQ" in ASCII (in little-endian byte order)
 ///
@@ -103,7 +108,7 @@ impl FileHeaderBuilder {
 ///
 /// The total size of this structure is 32 bytes, with a fixed layout to ensure
 /// consistent reading and writing across different platforms.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Clone, Copy, PartialEq, Eq)]
 pub struct FileHeader {
     /// Magic number to identify the file format
     ///
@@ -208,6 +213,62 @@ impl FileHeader {
         self.xlen > 0
     }
 
+    /// Checks if records of `other` can be combined with records of this header
+    ///
+    /// BQ records have a fixed layout, so files can only be merged or concatenated if their
+    /// sequence lengths, bit size and flag setting agree. See
+    /// [`compatibility_diff`](Self::compatibility_diff) for the differences.
+    #[must_use]
+    pub fn is_compatible_with(&self, other: &FileHeader) -> bool {
+        self.slen == other.slen
+            && self.xlen == other.xlen
+            && self.bits == other.bits
+            && self.flags == other.flags
+    }
+
+    /// Returns every reason records of `other` can not be combined with this header
+    ///
+    /// The list is empty iff [`is_compatible_with`](Self::is_compatible_with) holds. Each
+    /// reason holds the value of this header first.
+    #[must_use]
+    pub fn compatibility_diff(&self, other: &FileHeader) -> Vec<IncompatibilityReason> {
+        let mut reasons = Vec::new();
+        if self.slen != other.slen {
+            reasons.push(IncompatibilityReason::SlenMismatch(self.slen, other.slen));
+        }
+        if self.xlen != other.xlen {
+            reasons.push(IncompatibilityReason::XlenMismatch(self.xlen, other.xlen));
+        }
+        if self.bits != other.bits {
+            reasons.push(IncompatibilityReason::BitsMismatch(self.bits, other.bits));
+        }
+        if self.flags != other.flags {
+            reasons.push(IncompatibilityReason::FlagsMismatch(
+                self.flags,
+                other.flags,
+            ));
+        }
+        reasons
+    }
+
+    /// Returns the size in bytes of a single encoded record
+    ///
+    /// This includes the primary and extended sequence chunks as well as the
+    /// optional flag word.
+    #[must_use]
+    pub fn record_size_bytes(&self) -> usize {
+        RecordConfig::from_header(self).record_size_bytes()
+    }
+
+    /// Returns the exact size in bytes of a file containing `n_records` records
+    ///
+    /// This is the size of the file header plus `n_records` fixed-size records, and is
+    /// useful for pre-allocating space or validating that a write completed.
+    #[must_use]
+    pub fn expected_file_size(&self, n_records: u64) -> u64 {
+        SIZE_HEADER as u64 + n_records * self.record_size_bytes() as u64
+    }
+
     /// Parses a header from a fixed-size byte array
     ///
     /// This method validates the magic number and format version before constructing
@@ -225,13 +286,15 @@ impl FileHeader {
     /// # Errors
     ///
     /// Returns an error if:
-    /// * The magic number is incorrect
+    /// * The magic number is incorrect. If the buffer starts with the magic of another
+    ///   BINSEQ format or like e.g. a gzip or FASTQ file, the error names that format.
     /// * The format version is unsupported
     /// * The reserved bytes are invalid
     pub fn from_bytes(buffer: &[u8; SIZE_HEADER]) -> Result<Self> {
         let magic = LittleEndian::read_u32(&buffer[0..4]);
         if magic != MAGIC {
-            return Err(HeaderError::InvalidMagicNumber(magic).into());
+            return Err(magic::diagnose(buffer, "BQ file", 0)
+                .unwrap_or_else(|| HeaderError::InvalidMagicNumber(magic).into()));
         }
         let format = buffer[4];
         if format != FORMAT {
@@ -344,6 +407,49 @@ impl FileHeader {
     }
 }
 
+/// A difference between two BQ headers preventing their records from being combined
+///
+/// Returned by [`FileHeader::compatibility_diff`], with the value of the receiving header
+/// first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IncompatibilityReason {
+    /// The primary sequence lengths differ
+    SlenMismatch(u32, u32),
+    /// The extended sequence lengths differ
+    XlenMismatch(u32, u32),
+    /// The bit sizes differ
+    BitsMismatch(BitSize, BitSize),
+    /// Only one of the headers stores flags
+    FlagsMismatch(bool, bool),
+}
+
+impl fmt::Display for FileHeader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let bits = match self.bits {
+            BitSize::Two => 2,
+            BitSize::Four => 4,
+        };
+        write!(
+            f,
+            "BQ {{ version: {}, slen: {}, xlen: {}, bitsize: {bits}, flags: {} }}",
+            self.format, self.slen, self.xlen, self.flags,
+        )
+    }
+}
+
+impl fmt::Debug for FileHeader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FileHeader")
+            .field("magic", &format_args!("{:#010x}", self.magic))
+            .field("format", &self.format)
+            .field("slen", &self.slen)
+            .field("xlen", &self.xlen)
+            .field("bits", &self.bits)
+            .field("flags", &self.flags)
+            .finish_non_exhaustive()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -402,6 +508,37 @@ mod tests {
 
     // ==================== from_bytes Tests ====================
 
+    #[test]
+    fn test_from_bytes_other_formats() {
+        use crate::{Error, error::ReadError};
+
+        for (prefix, format) in [
+            (&b"\x1f\x8b\x08"[..], "gzip"),
+            (b"BAM\x01", "BAM"),
+            (b"@read_1", "FASTQ"),
+            (b">chr1", "FASTA"),
+        ] {
+            let mut buffer = [0u8; SIZE_HEADER];
+            buffer[..prefix.len()].copy_from_slice(prefix);
+            let result = FileHeader::from_bytes(&buffer);
+            assert!(
+                matches!(result, Err(Error::ReadError(ReadError::LooksLikeOtherFormat(f))) if f == format)
+            );
+        }
+
+        let mut buffer = [0u8; SIZE_HEADER];
+        buffer[..4].copy_from_slice(&crate::vbq::FILE_MAGIC);
+        let result = FileHeader::from_bytes(&buffer);
+        assert!(matches!(
+            result,
+            Err(Error::HeaderError(HeaderError::MismatchedFormat {
+                found: "VBQ file",
+                expected: "BQ file",
+                ..
+            }))
+        ));
+    }
+
     #[test]
     fn test_from_bytes_invalid_format_version() {
         let header = FileHeader::new(BitSize::Two, 32, false);
@@ -478,4 +615,124 @@ mod tests {
         let result = FileHeader::from_reader(&mut cursor);
         assert!(result.is_err());
     }
+
+    // ==================== Size Tests ====================
+
+    #[test]
+    fn test_record_size_bytes() {
+        assert_eq!(
+            FileHeader::new(BitSize::Two, 32, false).record_size_bytes(),
+            8
+        );
+        assert_eq!(
+            FileHeader::new(BitSize::Two, 33, false).record_size_bytes(),
+            16
+        );
+        assert_eq!(
+            FileHeader::new(BitSize::Four, 32, false).record_size_bytes(),
+            16
+        );
+        assert_eq!(
+            FileHeader::new(BitSize::Two, 32, true).record_size_bytes(),
+            16
+        );
+        assert_eq!(
+            FileHeader::new_extended(BitSize::Two, 32, 40, true).record_size_bytes(),
+            32
+        );
+    }
+
+    #[test]
+    fn test_expected_file_size_empty() {
+        let header = FileHeader::new(BitSize::Two, 32, false);
+        assert_eq!(header.expected_file_size(0), SIZE_HEADER as u64);
+    }
+
+    #[test]
+    fn test_expected_file_size_matches_written() -> Result<()> {
+        use crate::SequencingRecordBuilder;
+        use crate::bq::WriterBuilder;
+
+        let sseq = b"ACGTACGTACGTACGTACGTACGTACGTACGTACGTAC";
+        let xseq = b"TTTTGGGGCCCCAAAA";
+        for (bits, flags) in [
+            (BitSize::Two, false),
+            (BitSize::Two, true),
+            (BitSize::Four, false),
+            (BitSize::Four, true),
+        ] {
+            let header = FileHeaderBuilder::new()
+                .slen(sseq.len() as u32)
+                .xlen(xseq.len() as u32)
+                .bitsize(bits)
+                .flags(flags)
+                .build()?;
+            let mut writer = WriterBuilder::default().header(header).build(Vec::new())?;
+            let n_records = 37;
+            for idx in 0..n_records {
+                let record = SequencingRecordBuilder::default()
+                    .s_seq(sseq)
+                    .x_seq(xseq)
+                    .flag(idx)
+                    .build()?;
+                writer.push(record)?;
+            }
+            writer.flush()?;
+            let buffer = writer.into_inner();
+            assert_eq!(buffer.len() as u64, header.expected_file_size(n_records));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_display_and_debug() {
+        let header = FileHeader::new_extended(BitSize::Four, 28, 90, true);
+        assert_eq!(
+            header.to_string(),
+            "BQ { version: 1, slen: 28, xlen: 90, bitsize: 4, flags: true }"
+        );
+        let debug = format!("{header:?}");
+        assert!(debug.starts_with("FileHeader { magic: 0x51455342, format: 1, slen: 28"));
+        assert!(!debug.contains("reserved"));
+    }
+
+    #[test]
+    fn test_compatibility() {
+        let header = FileHeader::new_extended(BitSize::Two, 28, 90, true);
+        assert!(header.is_compatible_with(&header));
+        assert!(header.compatibility_diff(&header).is_empty());
+
+        // every combination of differing fields
+        for mask in 1..16u32 {
+            let other = FileHeader::new_extended(
+                if mask & 4 == 0 {
+                    BitSize::Two
+                } else {
+                    BitSize::Four
+                },
+                if mask & 1 == 0 { 28 } else { 32 },
+                if mask & 2 == 0 { 90 } else { 0 },
+                mask & 8 == 0,
+            );
+            let mut expected = Vec::new();
+            if mask & 1 != 0 {
+                expected.push(IncompatibilityReason::SlenMismatch(28, 32));
+            }
+            if mask & 2 != 0 {
+                expected.push(IncompatibilityReason::XlenMismatch(90, 0));
+            }
+            if mask & 4 != 0 {
+                expected.push(IncompatibilityReason::BitsMismatch(
+                    BitSize::Two,
+                    BitSize::Four,
+                ));
+            }
+            if mask & 8 != 0 {
+                expected.push(IncompatibilityReason::FlagsMismatch(true, false));
+            }
+            assert!(!header.is_compatible_with(&other));
+            assert!(!other.is_compatible_with(&header));
+            assert_eq!(header.compatibility_diff(&other), expected);
+        }
+    }
 }
@@ -0,0 +1,233 @@
+//! Interleaved mates in single-end BQ files
+//!
+//! Paired reads can be stored as consecutive single-end records (R1, R2, R1, R2, ...) instead
+//! of a paired file, with the mate of each record recoverable from its flag: the first mate has
+//! [`MATE1_BIT`] set and the second [`MATE2_BIT`]. Both mates must have the `slen` of the header.
+//!
+//! [`write_interleaved_pair`] writes a pair with this convention and [`InterleavedPairIter`]
+//! reads the pairs back, reporting records that are out of order.
+//!
+//! # Examples
+//!
+//! ```rust
+//! use binseq::bq::{FileHeaderBuilder, InterleavedPairIter, MmapReader, WriterBuilder};
+//! use binseq::bq::{MATE1_BIT, write_interleaved_pair};
+//! use binseq::BinseqRecord;
+//!
+//! let header = FileHeaderBuilder::new().slen(4).flags(true).build()?;
+//! let mut writer = WriterBuilder::default().header(header).build(Vec::new())?;
+//! write_interleaved_pair(&mut writer, 7, b"ACGT", b"TTGA")?;
+//!
+//! let reader = MmapReader::from_bytes(writer.into_inner())?;
+//! for pair in InterleavedPairIter::new(reader.iter_range(0, reader.num_records())) {
+//!     let (r1, r2) = pair?;
+//!     assert_eq!(r1.flag(), Some(7 | MATE1_BIT));
+//!     assert_eq!(r2.decode_s_alloc()?, b"TTGA");
+//! }
+//! # Ok::<(), binseq::Error>(())
+//! ```
+
+use std::io::Write;
+
+use super::Writer;
+use crate::{BinseqRecord, Result, error::ReadError};
+
+/// Flag bit marking the first mate of an interleaved pair
+pub const MATE1_BIT: u64 = 1 << 62;
+
+/// Flag bit marking the second mate of an interleaved pair
+pub const MATE2_BIT: u64 = 1 << 63;
+
+/// Both mate bits
+const MATE_MASK: u64 = MATE1_BIT | MATE2_BIT;
+
+/// Writes a pair of mates as two consecutive single-end records
+///
+/// The records are flagged `flag_base | MATE1_BIT` and `flag_base | MATE2_BIT` (any mate bits
+/// already set in `flag_base` are cleared). Both mates are encoded before anything is written,
+/// so either both records are written or neither: returns `Ok(false)` if a mate is skipped by
+/// the invalid nucleotide policy of the writer.
+///
+/// # Errors
+///
+/// * `WriteError::ConfigurationMismatch` - If the writer is paired or has no flags
+/// * `WriteError::SequenceTooShort`/`SequenceTooLong` - If a mate does not have the `slen` of
+///   the header
+pub fn write_interleaved_pair<W: Write>(
+    writer: &mut Writer<W>,
+    flag_base: u64,
+    r1: &[u8],
+    r2: &[u8],
+) -> Result<bool> {
+    let flag_base = flag_base & !MATE_MASK;
+    writer.write_mates([flag_base | MATE1_BIT, flag_base | MATE2_BIT], r1, r2)
+}
+
+/// Adapter yielding the pairs of an iterator over interleaved records
+///
+/// Each pair is checked to be a record with [`MATE1_BIT`] followed by a record with
+/// [`MATE2_BIT`]. A record with the wrong (or no) mate bit, or a first mate without its
+/// partner at the end of the iterator, is reported as [`ReadError::InterleaveDesync`], after
+/// which the adapter yields nothing. Errors of the underlying iterator are passed through.
+pub struct InterleavedPairIter<I> {
+    inner: I,
+    /// Position of the next record of `inner`
+    position: usize,
+    /// Set after an error, once the pairs can no longer be trusted
+    done: bool,
+}
+impl<I> InterleavedPairIter<I> {
+    /// Creates an adapter over an iterator of interleaved records
+    #[must_use]
+    pub fn new(inner: I) -> Self {
+        Self {
+            inner,
+            position: 0,
+            done: false,
+        }
+    }
+
+    /// Returns the underlying iterator
+    #[must_use]
+    pub fn into_inner(self) -> I {
+        self.inner
+    }
+
+    /// Checks that the record at the current position has exactly the `expected` mate bit
+    fn check_mate<R: BinseqRecord>(&self, record: &R, expected: u64) -> Result<()> {
+        let reason = match record.flag().map(|flag| flag & MATE_MASK) {
+            Some(bits) if bits == expected => return Ok(()),
+            None => "record has no flag",
+            Some(0) => "record has no mate bit",
+            Some(MATE_MASK) => "record has both mate bits",
+            Some(_) if expected == MATE1_BIT => "expected a first mate",
+            Some(_) => "expected a second mate",
+        };
+        Err(ReadError::InterleaveDesync {
+            record: self.position,
+            reason,
+        }
+        .into())
+    }
+
+    fn next_pair<R: BinseqRecord>(&mut self, r1: Result<R>) -> Result<(R, R)>
+    where
+        I: Iterator<Item = Result<R>>,
+    {
+        let r1 = r1?;
+        self.check_mate(&r1, MATE1_BIT)?;
+        self.position += 1;
+
+        let Some(r2) = self.inner.next() else {
+            return Err(ReadError::InterleaveDesync {
+                record: self.position - 1,
+                reason: "first mate without a partner at the end of the records",
+            }
+            .into());
+        };
+        let r2 = r2?;
+        self.check_mate(&r2, MATE2_BIT)?;
+        self.position += 1;
+        Ok((r1, r2))
+    }
+}
+impl<I, R> Iterator for InterleavedPairIter<I>
+where
+    I: Iterator<Item = Result<R>>,
+    R: BinseqRecord,
+{
+    type Item = Result<(R, R)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let r1 = self.inner.next()?;
+        let pair = self.next_pair(r1);
+        self.done = pair.is_err();
+        Some(pair)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Error;
+    use crate::bq::{FileHeaderBuilder, MmapReader, WriterBuilder};
+
+    fn new_writer() -> Result<Writer<Vec<u8>>> {
+        let header = FileHeaderBuilder::new().slen(8).flags(true).build()?;
+        WriterBuilder::default().header(header).build(Vec::new())
+    }
+
+    fn read_pairs(bytes: Vec<u8>) -> Result<Vec<Result<(u64, u64)>>> {
+        let reader = MmapReader::from_bytes(bytes)?;
+        Ok(
+            InterleavedPairIter::new(reader.iter_range(0, reader.num_records()))
+                .map(|pair| pair.map(|(r1, r2)| (r1.flag().unwrap(), r2.flag().unwrap())))
+                .collect(),
+        )
+    }
+
+    fn desync_record(pair: &Result<(u64, u64)>) -> Option<usize> {
+        match pair {
+            Err(Error::ReadError(ReadError::InterleaveDesync { record, .. })) => Some(*record),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_interleaved_roundtrip() -> Result<()> {
+        let mut writer = new_writer()?;
+        for idx in 0..10 {
+            assert!(write_interleaved_pair(
+                &mut writer,
+                idx | MATE2_BIT,
+                b"ACGTACGT",
+                b"TTTTGGGG"
+            )?);
+        }
+        // Pairs with a mate skipped by the policy are dropped as a whole
+        assert!(!write_interleaved_pair(
+            &mut writer,
+            10,
+            b"ACGTACGT",
+            b"NNNNGGGG"
+        )?);
+
+        let pairs = read_pairs(writer.into_inner())?;
+        assert_eq!(pairs.len(), 10);
+        for (idx, pair) in pairs.into_iter().enumerate() {
+            let idx = idx as u64;
+            assert_eq!(pair?, (idx | MATE1_BIT, idx | MATE2_BIT));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_interleaved_desync() -> Result<()> {
+        // Two consecutive first mates
+        let mut writer = new_writer()?;
+        write_interleaved_pair(&mut writer, 0, b"ACGTACGT", b"ACGTACGT")?;
+        writer.write_encoded_direct(Some(MATE1_BIT), &[0])?;
+        writer.write_encoded_direct(Some(MATE1_BIT), &[0])?;
+        let pairs = read_pairs(writer.into_inner())?;
+        assert_eq!(pairs.len(), 2);
+        assert!(pairs[0].is_ok());
+        assert_eq!(desync_record(&pairs[1]), Some(3));
+
+        // Odd trailing record
+        let mut writer = new_writer()?;
+        write_interleaved_pair(&mut writer, 0, b"ACGTACGT", b"ACGTACGT")?;
+        writer.write_encoded_direct(Some(MATE1_BIT), &[0])?;
+        let pairs = read_pairs(writer.into_inner())?;
+        assert_eq!(pairs.len(), 2);
+        assert_eq!(desync_record(&pairs[1]), Some(2));
+
+        // Unflagged header
+        let header = FileHeaderBuilder::new().slen(8).build()?;
+        let mut writer = WriterBuilder::default().header(header).build(Vec::new())?;
+        assert!(write_interleaved_pair(&mut writer, 0, b"ACGTACGT", b"ACGTACGT").is_err());
+        Ok(())
+    }
+}
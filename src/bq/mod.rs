@@ -237,10 +237,27 @@
 //!   - Processing state
 //!   - Count data
 
+#[cfg(feature = "cache")]
+mod cache;
+mod convert;
 mod header;
+mod interleave;
+mod paired;
 mod reader;
+mod sharded;
 mod writer;
 
-pub use header::{FILE_MAGIC, FileHeader, FileHeaderBuilder, SIZE_HEADER};
+#[cfg(feature = "cache")]
+pub use cache::RandomAccessBatch;
+pub use convert::convert_bitsize;
+pub use header::{FILE_MAGIC, FileHeader, FileHeaderBuilder, IncompatibilityReason, SIZE_HEADER};
+pub use interleave::{InterleavedPairIter, MATE1_BIT, MATE2_BIT, write_interleaved_pair};
+pub use paired::{PairedReader, PairedRecord};
 pub use reader::{MmapReader, RefRecord, StreamReader};
-pub use writer::{Encoder, StreamWriter, StreamWriterBuilder, Writer, WriterBuilder};
+pub use sharded::{ShardedWriter, ShardedWriterBuilder};
+#[cfg(feature = "flate2")]
+pub use writer::GzipStreamWriterBuilder;
+pub use writer::{
+    Encoder, LengthMismatchPolicy, StreamWriter, StreamWriterBuilder, TeeWriter, TeeWriterBuilder,
+    WriteStats, Writer, WriterBuilder,
+};
@@ -0,0 +1,346 @@
+//! Reading two single-end BQ files as one paired dataset
+//!
+//! Paired sequencing runs are commonly stored as separate R1 and R2 files. A
+//! [`PairedReader`] reads both files in lockstep so that each record pair is presented to a
+//! [`ParallelProcessor`] as a single paired record, without first merging the files.
+
+use std::ops::Range;
+use std::path::Path;
+use std::sync::Arc;
+
+use bitnuc::BitSize;
+
+use super::{MmapReader, RefRecord};
+use crate::{
+    BinseqRecord, Executor, ParallelProcessor, ParallelReader, ScratchBuffers,
+    error::{Result, WriteError},
+    executor::{self, Job},
+};
+
+/// A pair of records at the same index in the R1 and R2 files
+///
+/// The primary sequence is taken from R1 and the extended sequence from R2. The flag of
+/// the pair is the flag of the R1 record.
+#[derive(Clone, Copy)]
+pub struct PairedRecord<'a> {
+    /// Record from the R1 file
+    r1: RefRecord<'a>,
+
+    /// Record from the R2 file
+    r2: RefRecord<'a>,
+}
+impl<'a> PairedRecord<'a> {
+    /// Returns the record from the R1 file
+    #[must_use]
+    pub fn r1(&self) -> RefRecord<'a> {
+        self.r1
+    }
+
+    /// Returns the record from the R2 file
+    #[must_use]
+    pub fn r2(&self) -> RefRecord<'a> {
+        self.r2
+    }
+}
+impl BinseqRecord for PairedRecord<'_> {
+    fn bitsize(&self) -> BitSize {
+        self.r1.bitsize()
+    }
+    fn index(&self) -> u64 {
+        self.r1.index()
+    }
+    fn flag(&self) -> Option<u64> {
+        self.r1.flag()
+    }
+    fn sheader(&self) -> &[u8] {
+        self.r1.sheader()
+    }
+    fn xheader(&self) -> &[u8] {
+        self.r2.sheader()
+    }
+    fn slen(&self) -> u64 {
+        self.r1.slen()
+    }
+    fn xlen(&self) -> u64 {
+        self.r2.slen()
+    }
+    fn sbuf(&self) -> &[u64] {
+        self.r1.sbuf()
+    }
+    fn xbuf(&self) -> &[u64] {
+        self.r2.sbuf()
+    }
+    fn squal(&self) -> &[u8] {
+        self.r1.squal()
+    }
+    fn xqual(&self) -> &[u8] {
+        self.r2.squal()
+    }
+}
+
+/// A reader over two single-end BQ files holding the mates of a paired dataset
+///
+/// Record `i` of the R1 file is paired with record `i` of the R2 file.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use binseq::bq::PairedReader;
+///
+/// let reader = PairedReader::new("sample_R1.bq", "sample_R2.bq").unwrap();
+/// assert!(reader.num_records() > 0);
+/// ```
+pub struct PairedReader {
+    /// Reader over the R1 file
+    r1: MmapReader,
+
+    /// Reader over the R2 file
+    r2: MmapReader,
+}
+impl PairedReader {
+    /// Opens the R1 and R2 files of a paired dataset
+    ///
+    /// # Errors
+    ///
+    /// In addition to the errors of [`MmapReader::new`], see [`PairedReader::from_readers`].
+    pub fn new<P: AsRef<Path>, Q: AsRef<Path>>(r1: P, r2: Q) -> Result<Self> {
+        Self::from_readers(MmapReader::new(r1)?, MmapReader::new(r2)?)
+    }
+
+    /// Pairs two already opened readers
+    ///
+    /// # Errors
+    ///
+    /// * `WriteError::PairedFlagSet` - If either input is already paired
+    /// * `WriteError::IncompatibleBitsizes` - If the inputs use different bitsizes
+    /// * `WriteError::RecordCountMismatch` - If the inputs contain different numbers of records
+    pub fn from_readers(r1: MmapReader, r2: MmapReader) -> Result<Self> {
+        if r1.is_paired() || r2.is_paired() {
+            return Err(WriteError::PairedFlagSet.into());
+        }
+        let (b1, b2) = (r1.header().bits, r2.header().bits);
+        if b1 != b2 {
+            return Err(WriteError::IncompatibleBitsizes(b1, b2).into());
+        }
+        let (n1, n2) = (r1.num_records(), r2.num_records());
+        if n1 != n2 {
+            return Err(WriteError::RecordCountMismatch(n1, n2).into());
+        }
+        Ok(Self { r1, r2 })
+    }
+
+    /// Returns the number of record pairs
+    #[must_use]
+    pub fn num_records(&self) -> usize {
+        self.r1.num_records()
+    }
+
+    /// Returns the reader over the R1 file
+    #[must_use]
+    pub fn r1(&self) -> &MmapReader {
+        &self.r1
+    }
+
+    /// Returns the reader over the R2 file
+    #[must_use]
+    pub fn r2(&self) -> &MmapReader {
+        &self.r2
+    }
+
+    /// Sets the default quality score for records without quality information
+    pub fn set_default_quality_score(&mut self, score: u8) {
+        self.r1.set_default_quality_score(score);
+        self.r2.set_default_quality_score(score);
+    }
+
+    /// Sets the number of pairs per batch in parallel processing
+    ///
+    /// See [`MmapReader::set_batch_size`].
+    pub fn set_batch_size(&mut self, batch_size: usize) {
+        self.r1.set_batch_size(batch_size);
+        self.r2.set_batch_size(batch_size);
+    }
+
+    /// Returns the record pair at index `idx`
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the requested index is beyond the number of records
+    pub fn get(&self, idx: usize) -> Result<PairedRecord<'_>> {
+        Ok(PairedRecord {
+            r1: self.r1.get(idx)?,
+            r2: self.r2.get(idx)?,
+        })
+    }
+
+    /// Builds one job per thread, each processing a contiguous chunk of pairs in `range`
+    fn parallel_jobs<P: ParallelProcessor + Clone + 'static>(
+        self,
+        processor: &P,
+        num_threads: usize,
+        range: &Range<usize>,
+    ) -> Result<Vec<Job>> {
+        self.validate_range(self.num_records(), range)?;
+
+        let range_size = range.end - range.start;
+        let records_per_thread = range_size.div_ceil(num_threads);
+        let batch_size = self.r1.batch_size();
+        let reader = Arc::new(self);
+
+        let mut jobs: Vec<Job> = Vec::with_capacity(num_threads);
+        for tid in 0..num_threads {
+            let mut processor = processor.clone();
+            let reader = reader.clone();
+            let range = range.clone();
+            processor.set_tid(tid);
+
+            jobs.push(Box::new(move || -> Result<()> {
+                let start_idx = range.start + tid * records_per_thread;
+                let end_idx = (start_idx + records_per_thread).min(range.end);
+
+                let mut translater = itoa::Buffer::new();
+                let mut scratch = ScratchBuffers::default();
+                for batch_start in (start_idx..end_idx).step_by(batch_size) {
+                    let batch_end = (batch_start + batch_size).min(end_idx);
+                    for idx in batch_start..batch_end {
+                        let mut record = reader.get(idx)?;
+                        let id = translater.format(idx).as_bytes();
+                        record.r1.set_id(id);
+                        record.r2.set_id(id);
+                        crate::parallel::process_or_skip(&mut processor, record, &mut scratch)?;
+                    }
+                    processor.on_batch_complete()?;
+                }
+                processor.on_thread_complete()
+            }));
+        }
+        Ok(jobs)
+    }
+}
+impl ParallelReader for PairedReader {
+    fn process_parallel<P: ParallelProcessor + Clone + 'static>(
+        self,
+        processor: P,
+        num_threads: usize,
+    ) -> Result<()> {
+        let num_records = self.num_records();
+        if num_records == 0 {
+            // Nothing to process in empty files
+            return Ok(());
+        }
+        self.process_parallel_range(processor, num_threads, 0..num_records)
+    }
+
+    fn process_parallel_range<P: ParallelProcessor + Clone + 'static>(
+        self,
+        processor: P,
+        num_threads: usize,
+        range: Range<usize>,
+    ) -> Result<()> {
+        let num_threads = if num_threads == 0 {
+            num_cpus::get()
+        } else {
+            num_threads.min(num_cpus::get())
+        };
+        let jobs = self.parallel_jobs(&processor, num_threads, &range)?;
+        executor::spawn_and_join(jobs)
+    }
+
+    fn process_parallel_in<P: ParallelProcessor + Clone + 'static>(
+        self,
+        executor: &Executor,
+        processor: P,
+        range: Range<usize>,
+    ) -> Result<()> {
+        let jobs = self.parallel_jobs(&processor, executor.num_threads(), &range)?;
+        executor.run(jobs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::{
+        Error, SequencingRecordBuilder,
+        bq::{FileHeaderBuilder, WriterBuilder},
+    };
+
+    /// Writes single-end sequences of `slen` bases to an in-memory BQ file
+    fn single_end(seqs: &[Vec<u8>], slen: u32) -> Result<MmapReader> {
+        let header = FileHeaderBuilder::new().slen(slen).build()?;
+        let mut writer = WriterBuilder::default().header(header).build(Vec::new())?;
+        for seq in seqs {
+            let record = SequencingRecordBuilder::default().s_seq(seq).build()?;
+            assert!(writer.push(record)?);
+        }
+        writer.flush()?;
+        MmapReader::from_bytes(writer.into_inner())
+    }
+
+    fn sequences(n: usize, len: usize, seed: usize) -> Vec<Vec<u8>> {
+        (0..n)
+            .map(|i| (0..len).map(|j| b"ACGT"[(i * 7 + j * seed) % 4]).collect())
+            .collect()
+    }
+
+    #[derive(Clone, Default)]
+    struct PairCollector {
+        local: Vec<(u64, Vec<u8>, Vec<u8>)>,
+        all: Arc<Mutex<Vec<(u64, Vec<u8>, Vec<u8>)>>>,
+    }
+    impl ParallelProcessor for PairCollector {
+        fn process_record<R: BinseqRecord>(&mut self, record: R) -> Result<()> {
+            assert!(record.is_paired());
+            self.local.push((
+                record.index(),
+                record.decode_s_alloc()?,
+                record.decode_x_alloc()?,
+            ));
+            Ok(())
+        }
+        fn on_batch_complete(&mut self) -> Result<()> {
+            self.all.lock().unwrap().append(&mut self.local);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_paired_reader_processes_all_pairs() -> Result<()> {
+        let (s1, s2) = (sequences(3000, 50, 1), sequences(3000, 80, 3));
+        let paired = PairedReader::from_readers(single_end(&s1, 50)?, single_end(&s2, 80)?)?;
+        assert_eq!(paired.num_records(), 3000);
+
+        let processor = PairCollector::default();
+        paired.process_parallel(processor.clone(), 4)?;
+
+        let mut pairs = processor.all.lock().unwrap().clone();
+        pairs.sort_unstable_by_key(|(idx, _, _)| *idx);
+        assert_eq!(pairs.len(), 3000);
+        for (i, (idx, sseq, xseq)) in pairs.iter().enumerate() {
+            assert_eq!(*idx, i as u64);
+            assert_eq!(*sseq, s1[i]);
+            assert_eq!(*xseq, s2[i]);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_paired_reader_rejects_mismatched_inputs() -> Result<()> {
+        let seqs = sequences(10, 50, 1);
+        let result =
+            PairedReader::from_readers(single_end(&seqs, 50)?, single_end(&seqs[..9], 50)?);
+        assert!(matches!(
+            result,
+            Err(Error::WriteError(WriteError::RecordCountMismatch(10, 9)))
+        ));
+
+        let result = PairedReader::new("./data/subset.bq", "./data/subset.bq");
+        assert!(matches!(
+            result,
+            Err(Error::WriteError(WriteError::PairedFlagSet))
+        ));
+        Ok(())
+    }
+}
@@ -7,20 +7,26 @@
 //! It supports both sequential and parallel processing of records,
 //! with configurable record layouts for different sequence types.
 
+use std::collections::{HashMap, HashSet};
 use std::fs::File;
-use std::io::Read;
+use std::io::{Read, Write};
 use std::ops::Range;
 use std::path::Path;
 use std::sync::Arc;
 
 use bitnuc::BitSize;
 use bytemuck::cast_slice;
-use memmap2::Mmap;
+use byteorder::{ByteOrder, LittleEndian};
 
 use super::header::{FileHeader, SIZE_HEADER};
 use crate::{
-    BinseqRecord, DEFAULT_QUALITY_SCORE, Error, ParallelProcessor, ParallelReader,
+    BinseqRecord, CheckpointStore, DEFAULT_QUALITY_SCORE, Error, Executor, OwnedRecord,
+    ParallelProcessor, ParallelReader, ScratchBuffers,
+    checkpoint::run_resumable,
     error::{ReadError, Result},
+    executor::{self, Job},
+    record::debug_record,
+    source::ByteSource,
 };
 
 /// A reference to a binary sequence record in a memory-mapped file
@@ -86,6 +92,12 @@ impl<'a> RefRecord<'a> {
     }
 }
 
+impl std::fmt::Debug for RefRecord<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        debug_record(self, "RefRecord", f)
+    }
+}
+
 impl BinseqRecord for RefRecord<'_> {
     fn bitsize(&self) -> BitSize {
         self.config.bitsize
@@ -144,6 +156,8 @@ pub struct BatchRecord<'a> {
     buffer: &'a [u64],
     /// Decoded buffer slice
     dbuf: &'a [u8],
+    /// Layout of the decoded buffer slice
+    layout: BatchLayout,
     /// Record ID
     id: u64,
     /// The configuration that defines the layout and size of record components
@@ -209,25 +223,13 @@ impl BinseqRecord for BatchRecord<'_> {
     }
     /// Override this method since we can make use of block information
     fn sseq(&self) -> &[u8] {
-        let scalar = self.config.scalar();
-        let mut lbound = 0;
-        let mut rbound = self.config.slen();
-        if self.config.flags {
-            lbound += scalar;
-            rbound += scalar;
-        }
-        &self.dbuf[lbound..rbound]
+        let lbound = self.layout.soffset;
+        &self.dbuf[lbound..lbound + self.config.slen()]
     }
     /// Override this method since we can make use of block information
     fn xseq(&self) -> &[u8] {
-        let scalar = self.config.scalar();
-        let mut lbound = scalar * self.config.schunk();
-        let mut rbound = lbound + self.config.xlen();
-        if self.config.flags {
-            lbound += scalar;
-            rbound += scalar;
-        }
-        &self.dbuf[lbound..rbound]
+        let lbound = self.layout.xoffset;
+        &self.dbuf[lbound..lbound + self.config.xlen()]
     }
     fn squal(&self) -> &[u8] {
         &self.qbuf[..self.config.slen()]
@@ -374,6 +376,46 @@ impl RecordConfig {
     }
 }
 
+/// Layout of a record in the decoded buffer of a batch
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct BatchLayout {
+    /// Whether the encoded words of the batch are decoded at once
+    bulk: bool,
+    /// Number of decoded bytes per record
+    rsize: usize,
+    /// Offset of the primary sequence in a decoded record
+    soffset: usize,
+    /// Offset of the extended sequence in a decoded record
+    xoffset: usize,
+}
+impl BatchLayout {
+    fn new(config: &RecordConfig) -> Self {
+        match config.bitsize {
+            // Every 2-bit code is a nucleotide, so the whole batch (flags and padding
+            // included) can be decoded at once with `scalar` bases per word
+            BitSize::Two => {
+                let scalar = config.scalar();
+                let soffset = if config.flags { scalar } else { 0 };
+                let xoffset = soffset + config.schunk() * scalar;
+                Self {
+                    bulk: true,
+                    rsize: xoffset + config.xchunk() * scalar,
+                    soffset,
+                    xoffset,
+                }
+            }
+            // Flags and zeroed padding are not valid 4-bit codes, so only the bases of
+            // each sequence are decoded and packed back to back
+            BitSize::Four => Self {
+                bulk: false,
+                rsize: config.slen() + config.xlen(),
+                soffset: 0,
+                xoffset: config.slen(),
+            },
+        }
+    }
+}
+
 /// A memory-mapped reader for binary sequence files
 ///
 /// This reader provides efficient access to binary sequence files by memory-mapping
@@ -408,7 +450,7 @@ impl RecordConfig {
 /// ```
 pub struct MmapReader {
     /// Memory mapped file contents, wrapped in Arc for thread-safe sharing
-    mmap: Arc<Mmap>,
+    mmap: Arc<ByteSource>,
 
     /// Binary sequence file header containing format information
     header: FileHeader,
@@ -421,6 +463,9 @@ pub struct MmapReader {
 
     /// Default quality score for records without quality scores
     default_quality_score: u8,
+
+    /// Number of records per batch in parallel processing
+    batch_size: usize,
 }
 
 impl MmapReader {
@@ -452,9 +497,31 @@ impl MmapReader {
             return Err(ReadError::IncompatibleFile.into());
         }
 
-        // Safety: the file is open and won't be modified while mapped
-        let mmap = unsafe { Mmap::map(&file)? };
+        Self::from_source(ByteSource::map(&file)?)
+    }
+
+    /// Creates a new reader over the contents of a binary sequence file held in memory
+    ///
+    /// This behaves exactly like [`MmapReader::new`] but parses `data` directly instead of
+    /// memory-mapping a file, e.g. for file contents fetched over the network.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the header is invalid or the size of `data` doesn't match the
+    /// expected size based on the header.
+    pub fn from_bytes(data: Vec<u8>) -> Result<Self> {
+        Self::from_arc_bytes(data.into())
+    }
+
+    /// Creates a new reader over shared in-memory file contents
+    ///
+    /// See [`MmapReader::from_bytes`].
+    pub fn from_arc_bytes(data: Arc<[u8]>) -> Result<Self> {
+        Self::from_source(ByteSource::memory(data))
+    }
 
+    /// Creates a new reader over the bytes of a binary sequence file
+    fn from_source(mmap: ByteSource) -> Result<Self> {
         // Read header from mapped memory
         let header = FileHeader::from_buffer(&mmap)?;
 
@@ -475,6 +542,7 @@ impl MmapReader {
             config,
             qbuf,
             default_quality_score: DEFAULT_QUALITY_SCORE,
+            batch_size: BATCH_SIZE,
         })
     }
 
@@ -495,6 +563,11 @@ impl MmapReader {
         self.header
     }
 
+    /// Returns the raw contents of the file
+    pub(crate) fn as_bytes(&self) -> &[u8] {
+        &self.mmap
+    }
+
     /// Checks if the file has paired-records
     #[must_use]
     pub fn is_paired(&self) -> bool {
@@ -507,6 +580,32 @@ impl MmapReader {
         self.qbuf = self.build_qbuf();
     }
 
+    /// Sets the number of records per batch in parallel processing (default [`BATCH_SIZE`])
+    ///
+    /// Each thread decodes its records one batch at a time and calls
+    /// [`ParallelProcessor::on_batch_complete`] exactly once after every batch, including a
+    /// final partial batch. Larger batches mean fewer calls (e.g. fewer lock acquisitions by
+    /// processors flushing output per batch), smaller batches a finer cadence (e.g. for
+    /// progress reporting). A batch size of 0 is treated as 1.
+    ///
+    /// Resumable scans ([`process_parallel_resumable`](Self::process_parallel_resumable)) use
+    /// batches as checkpoint units, so a scan must be resumed with the same batch size.
+    pub fn set_batch_size(&mut self, batch_size: usize) {
+        self.batch_size = batch_size.max(1);
+    }
+
+    /// Returns the number of records per batch in parallel processing
+    #[must_use]
+    pub fn batch_size(&self) -> usize {
+        self.batch_size
+    }
+
+    /// Creates a decoding buffer sized for a batch of records
+    fn build_dbuf(&self) -> Vec<u8> {
+        let batch_records = self.batch_size.min(self.num_records());
+        Vec::with_capacity(batch_records * BatchLayout::new(&self.config).rsize)
+    }
+
     /// Creates a new quality score buffer
     #[must_use]
     pub fn build_qbuf(&self) -> Vec<u8> {
@@ -528,7 +627,7 @@ impl MmapReader {
     ///
     /// Returns an error if the requested index is beyond the number of records in the file
     pub fn get(&self, idx: usize) -> Result<RefRecord<'_>> {
-        if idx > self.num_records() {
+        if idx >= self.num_records() {
             return Err(ReadError::OutOfRange {
                 requested_index: idx,
                 max_index: self.num_records(),
@@ -563,6 +662,378 @@ impl MmapReader {
         let buffer = cast_slice(bytes);
         Ok(buffer)
     }
+
+    /// Copies the file (header and records) to `writer` without decoding any record
+    ///
+    /// Returns the number of bytes written.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use binseq::bq::MmapReader;
+    ///
+    /// let reader = MmapReader::new("./data/subset.bq").unwrap();
+    /// let mut copy = Vec::new();
+    /// let size = reader.write_to(&mut copy).unwrap();
+    /// assert_eq!(size, copy.len() as u64);
+    /// ```
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> Result<u64> {
+        writer.write_all(&self.mmap[..SIZE_HEADER])?;
+        writer.write_all(&self.mmap[SIZE_HEADER..])?;
+        Ok(self.mmap.len() as u64)
+    }
+
+    /// Copies the records with indices in `range` to `writer` without decoding them
+    ///
+    /// Only the records are written, without the file header. Write the header first
+    /// (see [`FileHeader::write_bytes`]) to produce a complete BQ file of the range.
+    ///
+    /// Returns the number of bytes written.
+    ///
+    /// # Errors
+    ///
+    /// * `ReadError::InvalidRange` - If the start of `range` is after its end
+    /// * `ReadError::OutOfRange` - If the end of `range` exceeds the number of records
+    pub fn write_records_range_to<W: Write>(
+        &self,
+        range: Range<usize>,
+        writer: &mut W,
+    ) -> Result<u64> {
+        if range.start > range.end {
+            return Err(ReadError::InvalidRange {
+                start: range.start,
+                end: range.end,
+            }
+            .into());
+        }
+        let buffer = self.get_buffer_slice(range)?;
+        let bytes: &[u8] = cast_slice(buffer);
+        writer.write_all(bytes)?;
+        Ok(bytes.len() as u64)
+    }
+
+    /// Returns an iterator over the records with indices in `start..end`
+    ///
+    /// Records are read in order directly from the memory map, which is lighter than
+    /// [`process_parallel_range`](ParallelReader::process_parallel_range) for single-threaded
+    /// use. An empty range yields no records.
+    ///
+    /// If `start > end` or `end` exceeds the number of records, the iterator yields a single
+    /// error.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use binseq::{BinseqRecord, bq::MmapReader};
+    ///
+    /// let reader = MmapReader::new("./data/subset.bq").unwrap();
+    /// for record in reader.iter_range(10, 20) {
+    ///     let record = record.unwrap();
+    ///     assert!((10..20).contains(&record.index()));
+    /// }
+    /// ```
+    pub fn iter_range(
+        &self,
+        start: usize,
+        end: usize,
+    ) -> impl Iterator<Item = Result<RefRecord<'_>>> {
+        let buffer = if start > end {
+            Err(ReadError::InvalidRange { start, end }.into())
+        } else {
+            self.get_buffer_slice(start..end)
+        };
+        let (error, buffer) = match buffer {
+            Ok(buffer) => (None, buffer),
+            Err(error) => (Some(error), &[][..]),
+        };
+        error.map(Err).into_iter().chain(
+            buffer
+                .chunks_exact(self.config.record_size_u64())
+                .zip(start as u64..)
+                .map(|(buffer, idx)| Ok(RefRecord::new(idx, buffer, &self.qbuf, self.config))),
+        )
+    }
+
+    /// Returns an iterator over the mates of paired records whose flag matches `pair_flag`
+    ///
+    /// A record is yielded when `flag & pair_flag != 0`, split into a `(primary, secondary)`
+    /// pair of single-end records: the primary is configured with the primary sequence
+    /// length and the secondary with the extended sequence length, so each decodes through
+    /// [`BinseqRecord::decode_s`]. Both mates keep the index of the original record. The flag
+    /// is only carried by the primary mate.
+    ///
+    /// Files without flags yield no records.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ReadError::NotPaired`] if the file does not contain paired records.
+    pub fn iter_flagged_pairs(
+        &self,
+        pair_flag: u64,
+    ) -> Result<impl Iterator<Item = Result<(RefRecord<'_>, RefRecord<'_>)>>> {
+        if !self.is_paired() {
+            return Err(ReadError::NotPaired.into());
+        }
+        let bitsize = self.config.bitsize;
+        let flags = self.config.flags;
+        let primary_config = RecordConfig::new(self.config.slen(), 0, bitsize, flags);
+        let secondary_config = RecordConfig::new(self.config.xlen(), 0, bitsize, false);
+        let split = primary_config.record_size_u64();
+        let records = self
+            .iter_range(0, self.num_records())
+            .filter(move |record| match record {
+                Ok(record) => record.flag().is_some_and(|flag| flag & pair_flag != 0),
+                Err(_) => true,
+            })
+            .map(move |record| {
+                let record = record?;
+                let (sbuf, xbuf) = record.buffer.split_at(split);
+                Ok((
+                    RefRecord::new(record.id, sbuf, record.qbuf, primary_config),
+                    RefRecord::new(record.id, xbuf, record.qbuf, secondary_config),
+                ))
+            });
+        Ok(records)
+    }
+
+    /// Returns an iterator over every `step`-th record, starting at index `start`
+    ///
+    /// Useful for systematic subsampling. Yields no records if `start` is beyond the last
+    /// record.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `step` is zero.
+    pub fn iter_with_step(
+        &self,
+        start: usize,
+        step: usize,
+    ) -> impl Iterator<Item = Result<RefRecord<'_>>> {
+        assert!(step > 0, "step must be positive");
+        (start..self.num_records())
+            .step_by(step)
+            .map(|idx| self.get(idx))
+    }
+
+    /// Returns owned copies of the first `n` records in the file
+    ///
+    /// If `n` exceeds the number of records in the file, all records are returned.
+    pub fn head(&self, n: usize) -> Result<Vec<OwnedRecord>> {
+        let n = n.min(self.num_records());
+        self.owned_records(0..n)
+    }
+
+    /// Returns owned copies of the last `n` records in the file
+    ///
+    /// Records are fixed-size, so only the requested records are touched.
+    /// If `n` exceeds the number of records in the file, all records are returned.
+    pub fn tail(&self, n: usize) -> Result<Vec<OwnedRecord>> {
+        let total = self.num_records();
+        self.owned_records(total.saturating_sub(n)..total)
+    }
+
+    /// Returns the flag of every record in file order
+    ///
+    /// Flags are read directly from the first word of each record, without touching the
+    /// sequence data. Files written without flags do not store them, so an empty `Vec` is
+    /// returned for them.
+    #[must_use]
+    pub fn record_flags_vec(&self) -> Vec<u64> {
+        if !self.header.flags {
+            return Vec::new();
+        }
+        let mut flags = vec![0; self.num_records()];
+        self.read_flags_into(0, &mut flags);
+        flags
+    }
+
+    /// Returns the flag of every record in file order, reading with `n_threads` threads
+    ///
+    /// See [`record_flags_vec`](Self::record_flags_vec). A thread count of zero uses all
+    /// available CPUs.
+    #[must_use]
+    pub fn record_flags_vec_parallel(&self, n_threads: usize) -> Vec<u64> {
+        if !self.header.flags {
+            return Vec::new();
+        }
+        let n_threads = if n_threads == 0 {
+            num_cpus::get()
+        } else {
+            n_threads.min(num_cpus::get())
+        };
+        let mut flags = vec![0; self.num_records()];
+        let chunk_size = flags.len().div_ceil(n_threads).max(1);
+        std::thread::scope(|scope| {
+            for (tid, chunk) in flags.chunks_mut(chunk_size).enumerate() {
+                scope.spawn(move || self.read_flags_into(tid * chunk_size, chunk));
+            }
+        });
+        flags
+    }
+
+    /// Divides the records of the file into `n_parts` contiguous ranges of nearly equal size
+    ///
+    /// Records have a fixed size, so equal record counts are equal byte sizes. The range
+    /// lengths differ by at most one; the ranges are disjoint, cover every record in order and
+    /// can be passed to [`ParallelReader::process_parallel_range`]. Fewer ranges are returned
+    /// if the file has fewer than `n_parts` records, and a single range if `n_parts` is zero.
+    /// An empty file yields the single empty range `0..0`.
+    #[must_use]
+    pub fn partition_by_records(&self, n_parts: usize) -> Vec<Range<usize>> {
+        split_evenly(self.num_records(), n_parts)
+    }
+
+    /// Returns the distinct flag values of the records
+    ///
+    /// Flags are read directly from the memory map in one pass over the records. Files
+    /// written without flags do not store them, so an empty set is returned for them.
+    #[must_use]
+    pub fn unique_flag_values(&self) -> HashSet<u64> {
+        self.flags().collect()
+    }
+
+    /// Returns the distinct flag values of the records after applying `mask` to each flag
+    ///
+    /// E.g. a mask of `0xFFFF` yields the distinct sample identifiers stored in the low 16
+    /// bits of the flags. See [`unique_flag_values`](Self::unique_flag_values).
+    #[must_use]
+    pub fn unique_flag_values_masked(&self, mask: u64) -> HashSet<u64> {
+        self.flags().map(|flag| flag & mask).collect()
+    }
+
+    /// Returns the number of records of each flag value
+    ///
+    /// See [`unique_flag_values`](Self::unique_flag_values). The counts add up to the number
+    /// of records for files with flags.
+    #[must_use]
+    pub fn flag_value_counts(&self) -> HashMap<u64, usize> {
+        let mut counts = HashMap::new();
+        for flag in self.flags() {
+            *counts.entry(flag).or_default() += 1;
+        }
+        counts
+    }
+
+    /// Returns an iterator over the flags of the records in file order
+    ///
+    /// The iterator is empty for files without flags.
+    fn flags(&self) -> impl Iterator<Item = u64> + '_ {
+        let rsize = self.config.record_size_bytes();
+        let n_records = if self.header.flags {
+            self.num_records()
+        } else {
+            0
+        };
+        (0..n_records).map(move |idx| {
+            let pos = SIZE_HEADER + idx * rsize;
+            LittleEndian::read_u64(&self.mmap[pos..pos + 8])
+        })
+    }
+
+    /// Reads the flags of the records starting at index `start` into `flags`
+    fn read_flags_into(&self, start: usize, flags: &mut [u64]) {
+        let rsize = self.config.record_size_bytes();
+        for (idx, flag) in (start..).zip(flags.iter_mut()) {
+            let pos = SIZE_HEADER + idx * rsize;
+            *flag = LittleEndian::read_u64(&self.mmap[pos..pos + 8]);
+        }
+    }
+
+    /// Collects owned copies of all records in the range
+    fn owned_records(&self, range: Range<usize>) -> Result<Vec<OwnedRecord>> {
+        range
+            .map(|idx| self.get(idx).map(OwnedRecord::from))
+            .collect()
+    }
+
+    /// Returns an iterator over the indices of records whose primary sequence contains `query`
+    ///
+    /// The query is encoded with the bitsize of the file and compared against the encoded
+    /// sequences in the memory map, so no records are decoded. A query of exactly `slen` bases
+    /// is a single equality check per record, while shorter queries are matched at every
+    /// offset by shifting across the packed words.
+    ///
+    /// A query longer than `slen` matches no records. The extended sequence is not searched.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the query cannot be encoded with the bitsize of the file
+    /// (e.g. it contains `N` in a 2-bit file).
+    pub fn search_sequence(&self, query: &[u8]) -> Result<impl Iterator<Item = usize> + '_> {
+        let mut encoded = Vec::new();
+        self.config.bitsize.encode(query, &mut encoded)?;
+
+        let slen = self.config.slen();
+        let qlen = query.len();
+        let num_records = if qlen > slen { 0 } else { self.num_records() };
+        let buffer = self.get_buffer_slice(0..num_records)?;
+
+        let rsize = self.config.record_size_u64();
+        let sstart = usize::from(self.config.flags);
+        let send = sstart + self.config.schunk();
+        let bits_per_base = match self.config.bitsize {
+            BitSize::Two => 2,
+            BitSize::Four => 4,
+        };
+
+        Ok(buffer
+            .chunks_exact(rsize)
+            .enumerate()
+            .filter_map(move |(idx, record)| {
+                let sbuf = &record[sstart..send];
+                let found = if qlen == slen {
+                    sbuf == encoded.as_slice()
+                } else {
+                    contains_encoded(sbuf, slen, &encoded, qlen, bits_per_base)
+                };
+                found.then_some(idx)
+            }))
+    }
+}
+
+/// Checks whether an encoded sequence of `slen` bases contains an encoded query of `qlen` bases
+///
+/// Every base offset is tested: offsets that are multiples of the bases per word compare
+/// whole words, and all other offsets compare words shifted across word boundaries.
+fn contains_encoded(
+    sbuf: &[u64],
+    slen: usize,
+    query: &[u64],
+    qlen: usize,
+    bits_per_base: usize,
+) -> bool {
+    if qlen > slen {
+        return false;
+    }
+    let query_bits = qlen * bits_per_base;
+    (0..=slen - qlen).any(|pos| {
+        let offset = pos * bits_per_base;
+        query.iter().enumerate().all(|(word_idx, &qword)| {
+            let start = word_idx * 64;
+            let n_bits = (query_bits - start).min(64);
+            read_bits(sbuf, offset + start, n_bits) == qword & low_mask(n_bits)
+        })
+    })
+}
+
+/// Reads up to 64 bits starting at `bit_offset` from a packed buffer
+fn read_bits(buffer: &[u64], bit_offset: usize, n_bits: usize) -> u64 {
+    let word = bit_offset / 64;
+    let shift = bit_offset % 64;
+    let mut value = buffer[word] >> shift;
+    if shift > 0 && shift + n_bits > 64 {
+        value |= buffer[word + 1] << (64 - shift);
+    }
+    value & low_mask(n_bits)
+}
+
+/// Returns a mask of the lowest `n_bits` bits
+fn low_mask(n_bits: usize) -> u64 {
+    if n_bits >= 64 {
+        u64::MAX
+    } else {
+        (1 << n_bits) - 1
+    }
 }
 
 /// A reader for streaming binary sequence data from any source that implements Read
@@ -609,6 +1080,19 @@ pub struct StreamReader<R: Read> {
     records_read: u64,
 }
 
+#[cfg(feature = "flate2")]
+impl StreamReader<flate2::read::MultiGzDecoder<File>> {
+    /// Opens a gzip-compressed BQ file for streaming
+    ///
+    /// The file is decompressed on the fly, e.g. for output written with
+    /// [`StreamWriterBuilder::gzip_output`](super::StreamWriterBuilder::gzip_output).
+    /// Concatenated gzip members are read as a single stream.
+    pub fn from_gzip<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let file = File::open(path)?;
+        Ok(Self::new(flate2::read::MultiGzDecoder::new(file)))
+    }
+}
+
 impl<R: Read> StreamReader<R> {
     /// Creates a new `StreamReader` with the default buffer size
     ///
@@ -827,7 +1311,8 @@ impl<R: Read> StreamReader<R> {
 /// Default batch size for parallel processing
 ///
 /// This constant defines how many records each thread processes at a time
-/// during parallel processing operations.
+/// during parallel processing operations. It can be changed per reader with
+/// [`MmapReader::set_batch_size`].
 pub const BATCH_SIZE: usize = 1024;
 
 /// Parallel processing implementation for memory-mapped readers
@@ -857,6 +1342,10 @@ impl ParallelReader for MmapReader {
         num_threads: usize,
     ) -> Result<()> {
         let num_records = self.num_records();
+        if num_records == 0 {
+            // Nothing to process in an empty file
+            return Ok(());
+        }
         self.process_parallel_range(processor, num_threads, 0..num_records)
     }
 
@@ -892,26 +1381,55 @@ impl ParallelReader for MmapReader {
         } else {
             num_threads.min(num_cpus::get())
         };
+        let jobs = self.parallel_jobs(&processor, num_threads, &range)?;
+        executor::spawn_and_join(jobs)
+    }
+
+    /// Process records in parallel within a specified range on a reusable [`Executor`]
+    ///
+    /// The range is split the same way as in
+    /// [`process_parallel_range`](ParallelReader::process_parallel_range), using one chunk per
+    /// worker thread of the executor.
+    fn process_parallel_in<P: ParallelProcessor + Clone + 'static>(
+        self,
+        executor: &Executor,
+        processor: P,
+        range: Range<usize>,
+    ) -> Result<()> {
+        let jobs = self.parallel_jobs(&processor, executor.num_threads(), &range)?;
+        executor.run(jobs)
+    }
+}
 
+impl MmapReader {
+    /// Builds one job per thread, each processing a contiguous chunk of records in `range`
+    fn parallel_jobs<P: ParallelProcessor + Clone + 'static>(
+        self,
+        processor: &P,
+        num_threads: usize,
+        range: &Range<usize>,
+    ) -> Result<Vec<Job>> {
         // Validate range
         let num_records = self.num_records();
-        self.validate_range(num_records, &range)?;
+        self.validate_range(num_records, range)?;
 
         // Calculate number of records for each thread within the range
         let range_size = range.end - range.start;
         let records_per_thread = range_size.div_ceil(num_threads);
 
         // Arc self
+        let batch_size = self.batch_size;
         let reader = Arc::new(self);
 
-        // Build thread handles
-        let mut handles = Vec::new();
+        // Build one job per thread
+        let mut jobs: Vec<Job> = Vec::with_capacity(num_threads);
         for tid in 0..num_threads {
             let mut processor = processor.clone();
             let reader = reader.clone();
+            let range = range.clone();
             processor.set_tid(tid);
 
-            let handle = std::thread::spawn(move || -> Result<()> {
+            jobs.push(Box::new(move || -> Result<()> {
                 let start_idx = range.start + tid * records_per_thread;
                 let end_idx = (start_idx + records_per_thread).min(range.end);
 
@@ -919,96 +1437,227 @@ impl ParallelReader for MmapReader {
                     return Ok(()); // No records for this thread
                 }
 
-                // create a reusable buffer for translating record IDs
-                let mut translater = itoa::Buffer::new();
-
                 // initialize a decoding buffer
-                let mut dbuf = Vec::new();
+                let mut dbuf = reader.build_dbuf();
 
                 // initialize a quality score buffer
                 let qbuf = reader.build_qbuf();
 
-                // calculate the size of a record in the cast u64 slice
-                let rsize_u64 = reader.config.record_size_bytes() / 8;
+                // initialize the scratch buffers of the processor
+                let mut scratch = ScratchBuffers::default();
+
+                // iterate over the range of indices one batch at a time
+                for range_start in (start_idx..end_idx).step_by(batch_size) {
+                    let range_end = (range_start + batch_size).min(end_idx);
+                    reader.process_batch(
+                        &mut processor,
+                        range_start..range_end,
+                        &mut dbuf,
+                        &qbuf,
+                        &mut scratch,
+                    )?;
+                }
 
-                // determine the required scalar size
-                let scalar = reader.config.scalar();
+                // process the thread
+                processor.on_thread_complete()?;
 
-                // calculate the size of a record in the batch decoded buffer
-                let mut dbuf_rsize = { (reader.config.schunk() + reader.config.xchunk()) * scalar };
-                if reader.config.flags {
-                    dbuf_rsize += scalar;
-                }
+                Ok(())
+            }));
+        }
 
-                // iterate over the range of indices
-                for range_start in (start_idx..end_idx).step_by(BATCH_SIZE) {
-                    let range_end = (range_start + BATCH_SIZE).min(end_idx);
+        Ok(jobs)
+    }
 
-                    // clear the decoded buffer
-                    dbuf.clear();
+    /// Process all records in parallel, skipping chunks completed by a previous scan
+    ///
+    /// The file is split into chunks of [`batch_size`](Self::batch_size) records, each of which is a unit of
+    /// work of the `checkpoint`: chunks marked as done are skipped, and every other chunk is
+    /// marked once [`ParallelProcessor::on_batch_complete`] succeeds for it. If the scan fails
+    /// or is interrupted, calling this method again with the same checkpoint resumes it.
+    /// Chunks are processed *at least once*, see [`CheckpointStore`].
+    ///
+    /// Pending chunks are split into contiguous groups, one per thread.
+    pub fn process_parallel_resumable<P: ParallelProcessor>(
+        self,
+        processor: P,
+        num_threads: usize,
+        checkpoint: &mut dyn CheckpointStore,
+    ) -> Result<()> {
+        let num_records = self.num_records();
+        let batch_size = self.batch_size;
+        run_resumable(
+            &processor,
+            num_threads,
+            num_records.div_ceil(batch_size),
+            checkpoint,
+            || {
+                (
+                    self.build_dbuf(),
+                    self.build_qbuf(),
+                    ScratchBuffers::default(),
+                )
+            },
+            |proc, (dbuf, qbuf, scratch), chunk| {
+                let start = chunk * batch_size;
+                let end = (start + batch_size).min(num_records);
+                self.process_batch(proc, start..end, dbuf, qbuf, scratch)
+            },
+        )
+    }
 
-                    // get the encoded buffer slice
-                    let ebuf = reader.get_buffer_slice(range_start..range_end)?;
+    /// Decodes the records in `range` at once and passes them to the processor as one batch
+    fn process_batch<P: ParallelProcessor>(
+        &self,
+        processor: &mut P,
+        range: Range<usize>,
+        dbuf: &mut Vec<u8>,
+        qbuf: &[u8],
+        scratch: &mut ScratchBuffers,
+    ) -> Result<()> {
+        // create a reusable buffer for translating record IDs
+        let mut translater = itoa::Buffer::new();
 
-                    // decode the entire buffer at once (with flags and extra bases)
-                    reader
-                        .config
-                        .bitsize
-                        .decode(ebuf, ebuf.len() * scalar, &mut dbuf)?;
-
-                    // iterate over each index in the range
-                    for (inner_idx, idx) in (range_start..range_end).enumerate() {
-                        // translate the index
-                        let id_str = translater.format(idx);
-
-                        // create the index buffer
-                        let mut header_buf = [0; 20];
-                        let header_len = id_str.len();
-                        header_buf[..header_len].copy_from_slice(id_str.as_bytes());
-
-                        // find the buffer starts
-                        let ebuf_start = inner_idx * rsize_u64;
-                        let dbuf_start = inner_idx * dbuf_rsize;
-
-                        // initialize the record
-                        let record = BatchRecord {
-                            buffer: &ebuf[ebuf_start..(ebuf_start + rsize_u64)],
-                            dbuf: &dbuf[dbuf_start..(dbuf_start + dbuf_rsize)],
-                            qbuf: &qbuf,
-                            id: idx as u64,
-                            config: reader.config,
-                            header_buf,
-                            header_len,
-                        };
-
-                        // process the record
-                        processor.process_record(record)?;
-                    }
+        // calculate the size of a record in the cast u64 slice
+        let rsize_u64 = self.config.record_size_bytes() / 8;
 
-                    // process the batch
-                    processor.on_batch_complete()?;
-                }
+        // determine the layout of a record in the batch decoded buffer
+        let layout = BatchLayout::new(&self.config);
+        let dbuf_rsize = layout.rsize;
 
-                // process the thread
-                processor.on_thread_complete()?;
+        // clear the decoded buffer
+        dbuf.clear();
 
-                Ok(())
-            });
+        // get the encoded buffer slice
+        let ebuf = self.get_buffer_slice(range.clone())?;
 
-            handles.push(handle);
+        if layout.bulk {
+            // decode the entire buffer at once (with flags and extra bases)
+            self.config
+                .bitsize
+                .decode(ebuf, ebuf.len() * self.config.scalar(), dbuf)?;
+        } else {
+            // decode the sequences of each record without their flags and padding
+            let sstart = usize::from(self.config.flags);
+            let xstart = sstart + self.config.schunk();
+            for record in ebuf.chunks_exact(rsize_u64) {
+                self.config
+                    .bitsize
+                    .decode(&record[sstart..xstart], self.config.slen(), dbuf)?;
+                if self.config.xlen > 0 {
+                    self.config
+                        .bitsize
+                        .decode(&record[xstart..], self.config.xlen(), dbuf)?;
+                }
+            }
         }
 
-        for handle in handles {
-            handle
-                .join()
-                .expect("Error joining handle (1)")
-                .expect("Error joining handle (2)");
+        // iterate over each index in the range
+        for (inner_idx, idx) in range.enumerate() {
+            // translate the index
+            let id_str = translater.format(idx);
+
+            // create the index buffer
+            let mut header_buf = [0; 20];
+            let header_len = id_str.len();
+            header_buf[..header_len].copy_from_slice(id_str.as_bytes());
+
+            // find the buffer starts
+            let ebuf_start = inner_idx * rsize_u64;
+            let dbuf_start = inner_idx * dbuf_rsize;
+
+            // initialize the record
+            let record = BatchRecord {
+                buffer: &ebuf[ebuf_start..(ebuf_start + rsize_u64)],
+                dbuf: &dbuf[dbuf_start..(dbuf_start + dbuf_rsize)],
+                layout,
+                qbuf,
+                id: idx as u64,
+                config: self.config,
+                header_buf,
+                header_len,
+            };
+
+            // process the record
+            crate::parallel::process_or_skip(processor, record, scratch)?;
         }
 
-        Ok(())
+        // process the batch
+        processor.on_batch_complete()
+    }
+
+    /// Process records in parallel within a specified range using work stealing
+    ///
+    /// Unlike [`process_parallel_range`](ParallelReader::process_parallel_range), which assigns
+    /// each thread a fixed contiguous chunk, the range is split into batches of
+    /// [`batch_size`](Self::batch_size) records that are loaded into a shared queue. Each thread pulls batches from the queue
+    /// and steals from other threads once it runs dry, which balances the load when the
+    /// processing time per record varies.
+    ///
+    /// Records within a batch are processed in order, but batches are not processed in any
+    /// particular order.
+    #[cfg(feature = "work-stealing")]
+    pub fn process_parallel_work_stealing<P: ParallelProcessor + Clone + 'static>(
+        self,
+        processor: P,
+        num_threads: usize,
+        range: Range<usize>,
+    ) -> Result<()> {
+        let num_threads = if num_threads == 0 {
+            num_cpus::get()
+        } else {
+            num_threads.min(num_cpus::get())
+        };
+        self.validate_range(self.num_records(), &range)?;
+
+        let batch_size = self.batch_size;
+        let batches = range.clone().step_by(batch_size);
+        let queues = crate::stealing::WorkQueue::build(batches, num_threads);
+        let reader = Arc::new(self);
+
+        let mut jobs: Vec<Job> = Vec::with_capacity(num_threads);
+        for (tid, queue) in queues.into_iter().enumerate() {
+            let mut processor = processor.clone();
+            let reader = reader.clone();
+            let end = range.end;
+            processor.set_tid(tid);
+
+            jobs.push(Box::new(move || -> Result<()> {
+                let mut dbuf = reader.build_dbuf();
+                let qbuf = reader.build_qbuf();
+                let mut scratch = ScratchBuffers::default();
+                while let Some(batch_start) = queue.next() {
+                    let batch_end = (batch_start + batch_size).min(end);
+                    reader.process_batch(
+                        &mut processor,
+                        batch_start..batch_end,
+                        &mut dbuf,
+                        &qbuf,
+                        &mut scratch,
+                    )?;
+                }
+                processor.on_thread_complete()
+            }));
+        }
+        executor::spawn_and_join(jobs)
     }
 }
 
+/// Splits `0..n_records` into at most `n_parts` non-empty ranges differing in length by at
+/// most one
+fn split_evenly(n_records: usize, n_parts: usize) -> Vec<Range<usize>> {
+    let n_parts = n_parts.clamp(1, n_records.max(1));
+    let (size, extra) = (n_records / n_parts, n_records % n_parts);
+    let mut start = 0;
+    (0..n_parts)
+        .map(|idx| {
+            let end = start + size + usize::from(idx < extra);
+            let range = start..end;
+            start = end;
+            range
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1057,6 +1706,91 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_ref_record_debug() {
+        let reader = MmapReader::new(TEST_BQ_FILE).unwrap();
+        let record = reader.get(0).unwrap();
+        assert_eq!(
+            format!("{record:?}"),
+            "RefRecord { index: 0, slen: 28, xlen: 90, flag: Some(0), sseq: \"CGGTATTGTTAGCGCC...\", xseq: \"ACGCGGTTAGCACGTA...\" }"
+        );
+    }
+
+    // ==================== Sequence Search Tests ====================
+
+    /// Writes single-end records of `slen` bases, embedding `query` at `offset` in the given records
+    fn write_search_file(
+        path: &Path,
+        bitsize: BitSize,
+        n_records: usize,
+        slen: usize,
+        query: &[u8],
+        hits: &[(usize, usize)],
+    ) -> Result<()> {
+        use crate::SequencingRecordBuilder;
+        use crate::bq::{FileHeaderBuilder, WriterBuilder};
+
+        let header = FileHeaderBuilder::new()
+            .slen(slen as u32)
+            .bitsize(bitsize)
+            .build()?;
+        let mut writer = WriterBuilder::default()
+            .header(header)
+            .build(File::create(path)?)?;
+        for idx in 0..n_records {
+            // The background never contains the query since it has no C bases
+            let mut seq: Vec<u8> = (0..slen).map(|i| b"AGT"[(i + idx) % 3]).collect();
+            if let Some(&(_, offset)) = hits.iter().find(|(hit, _)| *hit == idx) {
+                seq[offset..offset + query.len()].copy_from_slice(query);
+            }
+            let record = SequencingRecordBuilder::default().s_seq(&seq).build()?;
+            writer.push(record)?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_search_sequence() -> Result<()> {
+        let query = b"CCGTACCATTCACGCC";
+        // Offsets cover aligned positions and positions spanning a word boundary
+        let hits = [(50, 0), (200, 25), (700, 64)];
+        for bitsize in [BitSize::Two, BitSize::Four] {
+            let path = std::env::temp_dir().join("binseq_test_search_sequence.bq");
+            write_search_file(&path, bitsize, 1000, 100, query, &hits)?;
+
+            let reader = MmapReader::new(&path)?;
+            let found: Vec<_> = reader.search_sequence(query)?.collect();
+            assert_eq!(found, vec![50, 200, 700]);
+
+            // Every found record decodes to a sequence containing the query
+            for idx in found {
+                let seq = reader.get(idx)?.decode_s_alloc()?;
+                assert!(seq.windows(query.len()).any(|w| w == query));
+            }
+
+            std::fs::remove_file(&path)?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_search_sequence_full_length_and_too_long() -> Result<()> {
+        let path = std::env::temp_dir().join("binseq_test_search_sequence_full.bq");
+        let query = b"CCCCGGGGTTTTAAAACCCCGGGGTTTTAAAACCCCGGGG";
+        write_search_file(&path, BitSize::Two, 100, query.len(), query, &[(42, 0)])?;
+
+        let reader = MmapReader::new(&path)?;
+        assert_eq!(reader.search_sequence(query)?.collect::<Vec<_>>(), vec![42]);
+
+        let mut too_long = query.to_vec();
+        too_long.push(b'A');
+        assert_eq!(reader.search_sequence(&too_long)?.count(), 0);
+
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+
     // ==================== Record Access Tests ====================
 
     #[test]
@@ -1187,6 +1921,253 @@ mod tests {
         }
     }
 
+    #[cfg(feature = "work-stealing")]
+    #[derive(Clone, Default)]
+    struct IndexCollector {
+        indices: Arc<std::sync::Mutex<Vec<u64>>>,
+    }
+
+    #[cfg(feature = "work-stealing")]
+    impl ParallelProcessor for IndexCollector {
+        fn process_record<R: BinseqRecord>(&mut self, record: R) -> Result<()> {
+            self.indices.lock().unwrap().push(record.index());
+            Ok(())
+        }
+    }
+
+    #[cfg(feature = "work-stealing")]
+    #[test]
+    fn test_parallel_processing_work_stealing() {
+        let num_records = MmapReader::new(TEST_BQ_FILE).unwrap().num_records();
+        for range in [0..num_records, 10..num_records / 2] {
+            let reader = MmapReader::new(TEST_BQ_FILE).unwrap();
+            let collector = IndexCollector::default();
+            reader
+                .process_parallel_work_stealing(collector.clone(), 4, range.clone())
+                .unwrap();
+
+            // Every record is processed exactly once
+            let mut indices = collector.indices.lock().unwrap().clone();
+            indices.sort_unstable();
+            let expected: Vec<u64> = range.map(|idx| idx as u64).collect();
+            assert_eq!(indices, expected);
+        }
+    }
+
+    /// Index, flag and decoded sequences of a record
+    type DecodedRecord = (u64, Option<u64>, Vec<u8>, Vec<u8>);
+
+    #[derive(Clone, Default)]
+    struct DecodedCollector {
+        records: Arc<std::sync::Mutex<Vec<DecodedRecord>>>,
+    }
+
+    impl ParallelProcessor for DecodedCollector {
+        fn process_record<R: BinseqRecord>(&mut self, record: R) -> Result<()> {
+            let (mut sbuf, mut xbuf) = (Vec::new(), Vec::new());
+            record.decode_s(&mut sbuf)?;
+            record.decode_x(&mut xbuf)?;
+            assert_eq!((record.sseq(), record.xseq()), (&sbuf[..], &xbuf[..]));
+            self.records
+                .lock()
+                .unwrap()
+                .push((record.index(), record.flag(), sbuf, xbuf));
+            Ok(())
+        }
+    }
+
+    fn awkward_sequence(len: usize, seed: usize) -> Vec<u8> {
+        (0..len)
+            .map(|i| b"ACGT"[((i * 7 + seed * 13) % 4) ^ ((i / 3) % 4)])
+            .collect()
+    }
+
+    #[test]
+    fn test_batch_decode_awkward_lengths() -> Result<()> {
+        use crate::SequencingRecordBuilder;
+        use crate::bq::{FileHeaderBuilder, WriterBuilder};
+
+        let num_records = 2 * BATCH_SIZE + 17;
+        for bitsize in [BitSize::Two, BitSize::Four] {
+            for flags in [false, true] {
+                for (slen, xlen) in [(33, 17), (17, 33), (1, 1), (16, 0), (31, 65), (100, 15)] {
+                    let header = FileHeaderBuilder::new()
+                        .slen(slen as u32)
+                        .xlen(xlen as u32)
+                        .bitsize(bitsize)
+                        .flags(flags)
+                        .build()?;
+                    let mut writer = WriterBuilder::default().header(header).build(Vec::new())?;
+                    for idx in 0..num_records {
+                        let (s, x) = (awkward_sequence(slen, idx), awkward_sequence(xlen, idx + 1));
+                        let mut builder = SequencingRecordBuilder::default()
+                            .s_seq(&s)
+                            .flag(idx as u64 * 3);
+                        if xlen > 0 {
+                            builder = builder.x_seq(&x);
+                        }
+                        writer.push(builder.build()?)?;
+                    }
+                    let bytes = writer.into_inner();
+
+                    let collector = DecodedCollector::default();
+                    MmapReader::from_bytes(bytes.clone())?
+                        .process_parallel(collector.clone(), 3)?;
+                    let mut records = collector.records.lock().unwrap().clone();
+                    records.sort_unstable();
+                    assert_eq!(records.len(), num_records);
+
+                    let reader = MmapReader::from_bytes(bytes)?;
+                    for (idx, flag, sbuf, xbuf) in records {
+                        let record = reader.get(idx as usize)?;
+                        let context = format!("{bitsize:?} flags={flags} slen={slen} xlen={xlen}");
+                        assert_eq!(sbuf, record.decode_s_alloc()?, "{context}");
+                        assert_eq!(xbuf, record.decode_x_alloc()?, "{context}");
+                        assert_eq!(sbuf, awkward_sequence(slen, idx as usize), "{context}");
+                        assert_eq!(flag, record.flag(), "{context}");
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_iter_range_and_step() -> Result<()> {
+        use crate::SequencingRecordBuilder;
+        use crate::bq::{FileHeaderBuilder, WriterBuilder};
+
+        let header = FileHeaderBuilder::new().slen(40).flags(true).build()?;
+        let mut writer = WriterBuilder::default().header(header).build(Vec::new())?;
+        for idx in 0..1000 {
+            let seq = awkward_sequence(40, idx);
+            let record = SequencingRecordBuilder::default()
+                .s_seq(&seq)
+                .flag(idx as u64)
+                .build()?;
+            writer.push(record)?;
+        }
+        let reader = MmapReader::from_bytes(writer.into_inner())?;
+
+        let records = reader.iter_range(100, 200).collect::<Result<Vec<_>>>()?;
+        assert_eq!(records.len(), 100);
+        for (idx, record) in (100..).zip(&records) {
+            assert_eq!((record.index(), record.flag()), (idx, Some(idx)));
+            assert_eq!(record.decode_s_alloc()?, awkward_sequence(40, idx as usize));
+        }
+        assert_eq!(reader.iter_range(1000, 1000).count(), 0);
+
+        let records = reader.iter_with_step(0, 10).collect::<Result<Vec<_>>>()?;
+        assert_eq!(records.len(), 100);
+        assert!(
+            records
+                .iter()
+                .zip((0..).step_by(10))
+                .all(|(record, idx)| record.index() == idx)
+        );
+        assert_eq!(reader.iter_with_step(995, 3).count(), 2);
+        assert_eq!(reader.iter_with_step(1000, 1).count(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_iter_flagged_pairs() -> Result<()> {
+        use crate::SequencingRecordBuilder;
+        use crate::bq::{FileHeaderBuilder, WriterBuilder};
+
+        let header = FileHeaderBuilder::new()
+            .slen(40)
+            .xlen(70)
+            .flags(true)
+            .build()?;
+        let mut writer = WriterBuilder::default().header(header).build(Vec::new())?;
+        for idx in 0..50 {
+            let sseq = awkward_sequence(40, idx);
+            let xseq = awkward_sequence(70, idx + 1000);
+            let record = SequencingRecordBuilder::default()
+                .s_seq(&sseq)
+                .x_seq(&xseq)
+                .flag(idx as u64)
+                .build()?;
+            writer.push(record)?;
+        }
+        let reader = MmapReader::from_bytes(writer.into_inner())?;
+
+        // Every odd record has the lowest flag bit set
+        let pairs = reader.iter_flagged_pairs(1)?.collect::<Result<Vec<_>>>()?;
+        assert_eq!(pairs.len(), 25);
+        for ((primary, secondary), idx) in pairs.iter().zip((1..).step_by(2)) {
+            assert_eq!((primary.index(), secondary.index()), (idx, idx));
+            assert_eq!(primary.flag(), Some(idx));
+            assert_eq!((primary.slen(), primary.xlen()), (40, 0));
+            assert_eq!((secondary.slen(), secondary.xlen()), (70, 0));
+            assert!(!primary.is_paired() && !secondary.is_paired());
+            assert_eq!(
+                primary.decode_s_alloc()?,
+                awkward_sequence(40, idx as usize)
+            );
+            assert_eq!(
+                secondary.decode_s_alloc()?,
+                awkward_sequence(70, idx as usize + 1000)
+            );
+        }
+        assert_eq!(reader.iter_flagged_pairs(0)?.count(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_iter_flagged_pairs_not_paired() -> Result<()> {
+        use crate::bq::{FileHeaderBuilder, WriterBuilder};
+
+        let header = FileHeaderBuilder::new().slen(40).flags(true).build()?;
+        let writer = WriterBuilder::default().header(header).build(Vec::new())?;
+        let reader = MmapReader::from_bytes(writer.into_inner())?;
+        assert!(matches!(
+            reader.iter_flagged_pairs(1),
+            Err(Error::ReadError(ReadError::NotPaired))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_iter_range_invalid() {
+        let reader = MmapReader::new(TEST_BQ_FILE).unwrap();
+        let num_records = reader.num_records();
+
+        let mut iter = reader.iter_range(0, num_records + 1);
+        assert!(matches!(
+            iter.next(),
+            Some(Err(Error::ReadError(ReadError::OutOfRange { .. })))
+        ));
+        assert!(iter.next().is_none());
+
+        let mut iter = reader.iter_range(5, 2);
+        assert!(matches!(
+            iter.next(),
+            Some(Err(Error::ReadError(ReadError::InvalidRange {
+                start: 5,
+                end: 2
+            })))
+        ));
+        assert!(iter.next().is_none());
+    }
+
+    #[test]
+    fn test_batch_layout() {
+        let header = FileHeader::new_extended(BitSize::Two, 33, 17, true);
+        let layout = BatchLayout::new(&RecordConfig::from_header(&header));
+        assert!(layout.bulk);
+        assert_eq!(
+            (layout.soffset, layout.xoffset, layout.rsize),
+            (32, 96, 128)
+        );
+
+        let header = FileHeader::new_extended(BitSize::Four, 33, 17, true);
+        let layout = BatchLayout::new(&RecordConfig::from_header(&header));
+        assert!(!layout.bulk);
+        assert_eq!((layout.soffset, layout.xoffset, layout.rsize), (0, 33, 50));
+    }
+
     // ==================== RecordConfig Tests ====================
 
     #[test]
@@ -1343,6 +2324,61 @@ mod tests {
         assert!(slice.is_err());
     }
 
+    #[test]
+    fn test_write_to() -> Result<()> {
+        for paired in [false, true] {
+            let reader = MmapReader::from_bytes(build_stream_bytes(paired))?;
+            let mut copy = Vec::new();
+            let size = reader.write_to(&mut copy)?;
+            assert_eq!(size, copy.len() as u64);
+            assert_eq!(copy, reader.as_bytes());
+
+            let mut stream = StreamReader::new(std::io::Cursor::new(copy));
+            let mut count = 0;
+            while let Some(record) = stream.next_record() {
+                let record = record?;
+                let expected = reader.get(count)?;
+                assert_eq!(record.decode_s_alloc()?, expected.decode_s_alloc()?);
+                assert_eq!(record.decode_x_alloc()?, expected.decode_x_alloc()?);
+                count += 1;
+            }
+            assert_eq!(count, reader.num_records());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_records_range_to() -> Result<()> {
+        let reader = MmapReader::new(TEST_BQ_FILE)?;
+        let mut copy = Vec::new();
+        reader.header().write_bytes(&mut copy)?;
+        let size = reader.write_records_range_to(10..25, &mut copy)?;
+        assert_eq!(size as usize, copy.len() - SIZE_HEADER);
+
+        let range = MmapReader::from_bytes(copy)?;
+        assert_eq!(range.num_records(), 15);
+        for idx in 0..15 {
+            assert_eq!(range.get(idx)?.sbuf(), reader.get(idx + 10)?.sbuf());
+        }
+
+        let mut sink = Vec::new();
+        let (start, end) = (5, 2);
+        assert_eq!(reader.write_records_range_to(end..end, &mut sink)?, 0);
+        assert!(matches!(
+            reader.write_records_range_to(start..end, &mut sink),
+            Err(Error::ReadError(ReadError::InvalidRange {
+                start: 5,
+                end: 2
+            }))
+        ));
+        assert!(matches!(
+            reader.write_records_range_to(0..reader.num_records() + 1, &mut sink),
+            Err(Error::ReadError(ReadError::OutOfRange { .. }))
+        ));
+        assert!(sink.is_empty());
+        Ok(())
+    }
+
     // ==================== MmapReader Error Path Tests ====================
 
     #[test]
@@ -1522,4 +2558,274 @@ mod tests {
         let cursor = reader.into_inner();
         assert_eq!(cursor.into_inner(), data);
     }
+
+    // ==================== Flag Extraction Tests ====================
+
+    #[test]
+    fn test_record_flags_vec() -> Result<()> {
+        use crate::SequencingRecordBuilder;
+        use crate::bq::{FileHeaderBuilder, WriterBuilder};
+
+        let seq = [b'A'; 24];
+        for flags in [true, false] {
+            let header = FileHeaderBuilder::new().slen(24).flags(flags).build()?;
+            let mut writer = WriterBuilder::default().header(header).build(Vec::new())?;
+            for flag in 0..10_000 {
+                let record = SequencingRecordBuilder::default()
+                    .s_seq(&seq)
+                    .flag(flag)
+                    .build()?;
+                writer.push(record)?;
+            }
+            let reader = MmapReader::from_bytes(writer.into_inner())?;
+
+            let flags_vec = reader.record_flags_vec();
+            if flags {
+                assert_eq!(flags_vec.len(), 10_000);
+                assert!(flags_vec.iter().enumerate().all(|(i, &f)| f == i as u64));
+            } else {
+                assert!(flags_vec.is_empty());
+            }
+            for n_threads in [0, 1, 3] {
+                assert_eq!(reader.record_flags_vec_parallel(n_threads), flags_vec);
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_unique_flag_values() -> Result<()> {
+        use crate::SequencingRecordBuilder;
+        use crate::bq::{FileHeaderBuilder, WriterBuilder};
+
+        let seq = [b'C'; 30];
+        let values: Vec<u64> = (0..10).map(|i| (i << 16) | (i % 5)).collect();
+        for flags in [true, false] {
+            let header = FileHeaderBuilder::new().slen(30).flags(flags).build()?;
+            let mut writer = WriterBuilder::default().header(header).build(Vec::new())?;
+            for idx in 0..100 {
+                let record = SequencingRecordBuilder::default()
+                    .s_seq(&seq)
+                    .flag(values[idx % 10])
+                    .build()?;
+                writer.push(record)?;
+            }
+            let reader = MmapReader::from_bytes(writer.into_inner())?;
+
+            let unique = reader.unique_flag_values();
+            let counts = reader.flag_value_counts();
+            let masked = reader.unique_flag_values_masked(0xFFFF);
+            if flags {
+                assert_eq!(unique, values.iter().copied().collect());
+                assert_eq!(counts.len(), 10);
+                assert!(values.iter().all(|value| counts[value] == 10));
+                assert_eq!(masked, (0..5).collect());
+            } else {
+                assert!(unique.is_empty() && counts.is_empty() && masked.is_empty());
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_split_evenly_random_sizes() {
+        use rand::{Rng, SeedableRng, rngs::SmallRng};
+
+        let mut rng = SmallRng::seed_from_u64(7);
+        for _ in 0..500 {
+            let n_records = rng.random_range(0..2_000);
+            let n_parts = rng.random_range(0..64);
+            let ranges = split_evenly(n_records, n_parts);
+            assert_eq!(ranges.len(), n_parts.clamp(1, n_records.max(1)));
+            assert_eq!(ranges[0].start, 0);
+            assert_eq!(ranges.last().unwrap().end, n_records);
+            for pair in ranges.windows(2) {
+                assert_eq!(pair[0].end, pair[1].start);
+            }
+            let lengths: Vec<usize> = ranges.iter().map(ExactSizeIterator::len).collect();
+            assert!(lengths.iter().max().unwrap() - lengths.iter().min().unwrap() <= 1);
+            assert!(n_records == 0 || lengths.iter().all(|&len| len > 0));
+        }
+    }
+
+    #[test]
+    fn test_partition_by_records() -> Result<()> {
+        use crate::processors::CountProcessor;
+
+        let reader = MmapReader::new(TEST_BQ_FILE)?;
+        let n_records = reader.num_records();
+        assert_eq!(reader.partition_by_records(1), vec![0..n_records]);
+        assert_eq!(reader.partition_by_records(n_records + 10).len(), n_records);
+
+        let mut counted = 0;
+        for partition in reader.partition_by_records(5) {
+            let counter = CountProcessor::new();
+            MmapReader::new(TEST_BQ_FILE)?.process_parallel_range(
+                counter.clone(),
+                2,
+                partition.clone(),
+            )?;
+            assert_eq!(counter.count() as usize, partition.len());
+            counted += partition.len();
+        }
+        assert_eq!(counted, n_records);
+        Ok(())
+    }
+
+    // ==================== Head / Tail Tests ====================
+
+    #[test]
+    fn test_head_tail() {
+        let reader = MmapReader::new(TEST_BQ_FILE).unwrap();
+        let total = reader.num_records();
+
+        let head = reader.head(5).unwrap();
+        assert_eq!(head.len(), 5);
+        for (idx, record) in head.iter().enumerate() {
+            assert_eq!(record.index() as usize, idx);
+            assert_eq!(record.sbuf(), reader.get(idx).unwrap().sbuf());
+        }
+
+        let tail = reader.tail(5).unwrap();
+        assert_eq!(tail.len(), 5);
+        for (offset, record) in tail.iter().enumerate() {
+            assert_eq!(record.index() as usize, total - 5 + offset);
+        }
+    }
+
+    #[test]
+    fn test_head_tail_exceeding_total() {
+        let reader = MmapReader::new(TEST_BQ_FILE).unwrap();
+        let total = reader.num_records();
+        assert_eq!(reader.head(total + 10).unwrap().len(), total);
+        assert_eq!(reader.tail(total + 10).unwrap().len(), total);
+        assert!(reader.head(0).unwrap().is_empty());
+        assert!(reader.tail(0).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_empty_file() -> Result<()> {
+        use crate::bq::{FileHeaderBuilder, WriterBuilder};
+
+        let path = std::env::temp_dir().join("binseq_test_empty.bq");
+        let header = FileHeaderBuilder::new().slen(100).build()?;
+        let mut writer = WriterBuilder::default()
+            .header(header)
+            .build(File::create(&path)?)?;
+        writer.flush()?;
+        drop(writer);
+        assert_eq!(std::fs::metadata(&path)?.len(), SIZE_HEADER as u64);
+
+        let reader = MmapReader::new(&path)?;
+        assert_eq!(reader.num_records(), 0);
+        assert!(reader.get(0).is_err());
+
+        let count = Arc::new(std::sync::Mutex::new(0));
+        let processor = CountingProcessor {
+            count: count.clone(),
+        };
+        reader.process_parallel(processor, 2)?;
+        assert_eq!(*count.lock().unwrap(), 0);
+
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_zero_byte_file() {
+        use crate::error::HeaderError;
+
+        let path = std::env::temp_dir().join("binseq_test_zero_byte.bq");
+        std::fs::write(&path, []).unwrap();
+
+        let result = MmapReader::new(&path);
+        assert!(matches!(
+            result,
+            Err(Error::HeaderError(HeaderError::InvalidSize(0, SIZE_HEADER)))
+        ));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    /// Records the size of every batch passed to the processor
+    #[derive(Clone, Default)]
+    struct BatchCounter {
+        current: usize,
+        batches: Arc<std::sync::Mutex<Vec<usize>>>,
+    }
+
+    impl ParallelProcessor for BatchCounter {
+        fn process_record<R: BinseqRecord>(&mut self, _record: R) -> Result<()> {
+            self.current += 1;
+            Ok(())
+        }
+
+        fn on_batch_complete(&mut self) -> Result<()> {
+            self.batches.lock().unwrap().push(self.current);
+            self.current = 0;
+            Ok(())
+        }
+    }
+
+    fn batch_sizes(batch_size: usize, num_threads: usize) -> (usize, Vec<usize>) {
+        let mut reader = MmapReader::new(TEST_BQ_FILE).unwrap();
+        reader.set_batch_size(batch_size);
+        let num_records = reader.num_records();
+        let counter = BatchCounter::default();
+        reader
+            .process_parallel(counter.clone(), num_threads)
+            .unwrap();
+        let batches = counter.batches.lock().unwrap().clone();
+        (num_records, batches)
+    }
+
+    #[test]
+    fn test_batch_size_default() {
+        let reader = MmapReader::new(TEST_BQ_FILE).unwrap();
+        assert_eq!(reader.batch_size(), BATCH_SIZE);
+    }
+
+    #[test]
+    fn test_on_batch_complete_once_per_batch() {
+        for batch_size in [1, 7, 100, BATCH_SIZE, usize::MAX / 2] {
+            let (num_records, batches) = batch_sizes(batch_size, 1);
+            assert_eq!(
+                batches.len(),
+                num_records.div_ceil(batch_size),
+                "{batch_size}"
+            );
+            assert_eq!(batches.iter().sum::<usize>(), num_records);
+
+            // Every batch is full except the final partial batch
+            let (last, full) = batches.split_last().unwrap();
+            assert!(full.iter().all(|&n| n == batch_size));
+            assert!(*last > 0 && *last <= batch_size);
+        }
+    }
+
+    #[test]
+    fn test_on_batch_complete_multiple_threads() {
+        let (num_records, batches) = batch_sizes(100, 3);
+        assert_eq!(batches.iter().sum::<usize>(), num_records);
+        assert!(batches.iter().all(|&n| n > 0 && n <= 100));
+
+        // Each thread ends with at most one partial batch
+        let num_threads = 3.min(num_cpus::get());
+        let records_per_thread = num_records.div_ceil(num_threads);
+        let expected: usize = (0..num_threads)
+            .map(|tid| {
+                let start = (tid * records_per_thread).min(num_records);
+                let end = (start + records_per_thread).min(num_records);
+                (end - start).div_ceil(100)
+            })
+            .sum();
+        assert_eq!(batches.len(), expected);
+    }
+
+    #[test]
+    fn test_set_batch_size_zero() {
+        let mut reader = MmapReader::new(TEST_BQ_FILE).unwrap();
+        reader.set_batch_size(0);
+        assert_eq!(reader.batch_size(), 1);
+    }
 }
@@ -7,12 +7,14 @@
 //! - Efficient buffering and encoding
 //! - Headless mode for parallel writing
 
-use std::io::{BufWriter, Write};
+use std::io::{BufWriter, Cursor, Write};
 
 use byteorder::{LittleEndian, WriteBytesExt};
+#[cfg(feature = "flate2")]
+use flate2::write::GzEncoder;
 use rand::{SeedableRng, rngs::SmallRng};
 
-use super::FileHeader;
+use super::{FileHeader, StreamReader, reader::RecordConfig};
 use crate::{
     Policy, RNG_SEED, SequencingRecord,
     error::{Result, WriteError},
@@ -54,6 +56,104 @@ pub fn write_buffer<W: Write>(writer: &mut W, ebuf: &[u64]) -> Result<()> {
     Ok(())
 }
 
+/// Checks that a sequence has the length expected by the header
+fn check_sequence_length(expected: u32, seq: &[u8]) -> Result<()> {
+    let (expected, got) = (expected as usize, seq.len());
+    match got.cmp(&expected) {
+        std::cmp::Ordering::Less => Err(WriteError::SequenceTooShort { expected, got }.into()),
+        std::cmp::Ordering::Greater => Err(WriteError::SequenceTooLong { expected, got }.into()),
+        std::cmp::Ordering::Equal => Ok(()),
+    }
+}
+
+/// Checks that a pre-encoded sequence has the number of words expected by the header
+fn check_encoded_len(expected: usize, ebuf: &[u64]) -> Result<()> {
+    if ebuf.len() == expected {
+        Ok(())
+    } else {
+        Err(WriteError::EncodedLengthMismatch {
+            expected,
+            got: ebuf.len(),
+        }
+        .into())
+    }
+}
+
+/// Determines how a `Writer` handles records whose sequences do not have the lengths of the
+/// header
+///
+/// Only applies to [`Writer::push`]. Each sequence is compared to the length of its mate in
+/// the header (`slen` for the primary, `xlen` for the extended sequence).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LengthMismatchPolicy {
+    /// Return `WriteError::SequenceTooShort` or `WriteError::SequenceTooLong`
+    #[default]
+    Error,
+
+    /// Drop the record (counted in [`WriteStats::skipped`])
+    SkipAndCount,
+
+    /// Clip sequences that are too long and extend sequences that are too short with
+    /// `pad_base` (counted in [`WriteStats::truncated`] and [`WriteStats::padded`])
+    ///
+    /// The padded sequence is encoded like any other, so a `pad_base` other than `A`, `C`,
+    /// `G` or `T` is subject to the invalid nucleotide [`Policy`] of 2-bit files.
+    TruncatePad {
+        /// Nucleotide appended to sequences that are too short
+        pad_base: u8,
+    },
+}
+
+/// Counts of records affected by the length mismatch policy of a `Writer`
+///
+/// See [`Writer::write_stats`]. A paired record with one mate clipped and the other
+/// extended is counted as both truncated and padded.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WriteStats {
+    /// Number of records dropped with [`LengthMismatchPolicy::SkipAndCount`]
+    pub skipped: usize,
+
+    /// Number of records with a sequence clipped by [`LengthMismatchPolicy::TruncatePad`]
+    pub truncated: usize,
+
+    /// Number of records with a sequence extended by [`LengthMismatchPolicy::TruncatePad`]
+    pub padded: usize,
+}
+
+/// Outcome of fitting a sequence to the length of the header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Fit {
+    /// The sequence has the expected length
+    Exact,
+    /// The sequence was clipped into the adjustment buffer
+    Truncated,
+    /// The sequence was extended into the adjustment buffer
+    Padded,
+    /// The sequence has another length and the policy does not adjust it
+    Mismatch,
+}
+
+/// Fits `seq` to `len` nucleotides according to `policy`
+///
+/// Adjusted sequences are written to `buf`, which is left untouched otherwise.
+fn fit_sequence(seq: &[u8], len: usize, policy: LengthMismatchPolicy, buf: &mut Vec<u8>) -> Fit {
+    if seq.len() == len {
+        return Fit::Exact;
+    }
+    let LengthMismatchPolicy::TruncatePad { pad_base } = policy else {
+        return Fit::Mismatch;
+    };
+    buf.clear();
+    if seq.len() > len {
+        buf.extend_from_slice(&seq[..len]);
+        Fit::Truncated
+    } else {
+        buf.extend_from_slice(seq);
+        buf.resize(len, pad_base);
+        Fit::Padded
+    }
+}
+
 /// Encodes nucleotide sequences into a compact 2-bit binary format
 ///
 /// The `Encoder` handles the conversion of nucleotide sequences (A, C, G, T)
@@ -82,8 +182,11 @@ pub struct Encoder {
     policy: Policy,
 
     /// Random number generator for the `RandomDraw` policy
-    /// Seeded with `RNG_SEED` for reproducibility
+    /// Seeded with `RNG_SEED` by default for reproducibility
     rng: SmallRng,
+
+    /// Seed of the random number generator
+    seed: u64,
 }
 impl Encoder {
     /// Creates a new encoder with default invalid nucleotide policy
@@ -121,6 +224,24 @@ impl Encoder {
     /// ```
     #[must_use]
     pub fn with_policy(header: FileHeader, policy: Policy) -> Self {
+        Self::with_policy_seed(header, policy, RNG_SEED)
+    }
+
+    /// Creates a new encoder with a specific invalid nucleotide policy and random seed
+    ///
+    /// The seed initializes the random number generator of the `RandomDraw` policy
+    /// ([`with_policy`](Self::with_policy) uses [`RNG_SEED`]).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use binseq::bq::{FileHeaderBuilder, Encoder};
+    /// # use binseq::Policy;
+    /// let header = FileHeaderBuilder::new().slen(100).build().unwrap();
+    /// let encoder = Encoder::with_policy_seed(header, Policy::RandomDraw, 7);
+    /// ```
+    #[must_use]
+    pub fn with_policy_seed(header: FileHeader, policy: Policy, seed: u64) -> Self {
         Self {
             header,
             policy,
@@ -128,7 +249,8 @@ impl Encoder {
             xbuffer: Vec::default(),
             s_ibuf: Vec::default(),
             x_ibuf: Vec::default(),
-            rng: SmallRng::seed_from_u64(RNG_SEED),
+            rng: SmallRng::seed_from_u64(seed),
+            seed,
         }
     }
 
@@ -142,26 +264,18 @@ impl Encoder {
     ///
     /// Will return `None` if the sequence is invalid and the policy does not allow correction.
     pub fn encode_single(&mut self, primary: &[u8]) -> Result<Option<&[u64]>> {
-        if primary.len() != self.header.slen as usize {
-            return Err(WriteError::UnexpectedSequenceLength {
-                expected: self.header.slen,
-                got: primary.len(),
-            }
-            .into());
-        }
+        check_sequence_length(self.header.slen, primary)?;
 
         // Fill the buffer with the 2-bit representation of the nucleotides
         self.clear();
-        if self.header.bits.encode(primary, &mut self.sbuffer).is_err() {
-            self.clear();
-            if self
-                .policy
-                .handle(primary, &mut self.s_ibuf, &mut self.rng)?
-            {
-                self.header.bits.encode(&self.s_ibuf, &mut self.sbuffer)?;
-            } else {
-                return Ok(None);
-            }
+        if !self.policy.encode(
+            self.header.bits,
+            primary,
+            &mut self.s_ibuf,
+            &mut self.sbuffer,
+            &mut self.rng,
+        )? {
+            return Ok(None);
         }
 
         Ok(Some(&self.sbuffer))
@@ -175,47 +289,53 @@ impl Encoder {
         primary: &[u8],
         extended: &[u8],
     ) -> Result<Option<(&[u64], &[u64])>> {
-        if primary.len() != self.header.slen as usize {
-            return Err(WriteError::UnexpectedSequenceLength {
-                expected: self.header.slen,
-                got: primary.len(),
-            }
-            .into());
-        }
-        if extended.len() != self.header.xlen as usize {
-            return Err(WriteError::UnexpectedSequenceLength {
-                expected: self.header.xlen,
-                got: extended.len(),
-            }
-            .into());
-        }
+        check_sequence_length(self.header.slen, primary)?;
+        check_sequence_length(self.header.xlen, extended)?;
 
+        // Each mate is corrected on its own, so a valid mate is encoded only once
         self.clear();
-        if self.header.bits.encode(primary, &mut self.sbuffer).is_err()
-            || self
-                .header
-                .bits
-                .encode(extended, &mut self.xbuffer)
-                .is_err()
-        {
-            self.clear();
-            if self
-                .policy
-                .handle(primary, &mut self.s_ibuf, &mut self.rng)?
-                && self
-                    .policy
-                    .handle(extended, &mut self.x_ibuf, &mut self.rng)?
-            {
-                self.header.bits.encode(&self.s_ibuf, &mut self.sbuffer)?;
-                self.header.bits.encode(&self.x_ibuf, &mut self.xbuffer)?;
-            } else {
-                return Ok(None);
-            }
+        if !self.policy.encode(
+            self.header.bits,
+            primary,
+            &mut self.s_ibuf,
+            &mut self.sbuffer,
+            &mut self.rng,
+        )? || !self.policy.encode(
+            self.header.bits,
+            extended,
+            &mut self.x_ibuf,
+            &mut self.xbuffer,
+            &mut self.rng,
+        )? {
+            return Ok(None);
         }
 
         Ok(Some((&self.sbuffer, &self.xbuffer)))
     }
 
+    /// Encodes two mates of `slen` nucleotides into the primary and extended buffers
+    ///
+    /// Returns `false` if either mate is invalid and the policy does not allow correction.
+    fn encode_mates(&mut self, r1: &[u8], r2: &[u8]) -> Result<bool> {
+        check_sequence_length(self.header.slen, r1)?;
+        check_sequence_length(self.header.slen, r2)?;
+
+        self.clear();
+        Ok(self.policy.encode(
+            self.header.bits,
+            r1,
+            &mut self.s_ibuf,
+            &mut self.sbuffer,
+            &mut self.rng,
+        )? && self.policy.encode(
+            self.header.bits,
+            r2,
+            &mut self.x_ibuf,
+            &mut self.xbuffer,
+            &mut self.rng,
+        )?)
+    }
+
     /// Clear all buffers and reset the encoder.
     pub fn clear(&mut self) {
         self.sbuffer.clear();
@@ -254,6 +374,14 @@ pub struct WriterBuilder {
     policy: Option<Policy>,
     /// Optional headless mode for parallel writing scenarios
     headless: Option<bool>,
+    /// Optional strict mode (reject records with data the format cannot store)
+    strict_mode: Option<bool>,
+    /// Optional seed of the random number generator of the policy
+    policy_seed: Option<u64>,
+    /// Optional handling of records with sequences of the wrong length
+    length_mismatch_policy: Option<LengthMismatchPolicy>,
+    /// Optional inference of the sequence lengths from the first record
+    infer_lengths: Option<bool>,
 }
 impl WriterBuilder {
     #[must_use]
@@ -274,16 +402,73 @@ impl WriterBuilder {
         self
     }
 
+    /// Sets whether to reject records holding data the BQ format cannot store
+    ///
+    /// By default, quality scores, headers, and the extended sequence of a record written to
+    /// a single-end file are silently dropped. In strict mode, writing such a record returns
+    /// a [`WriteError::UnsupportedField`] or [`WriteError::PairedFlagNotSet`] instead.
+    #[must_use]
+    pub fn strict_mode(mut self, strict_mode: bool) -> Self {
+        self.strict_mode = Some(strict_mode);
+        self
+    }
+
+    /// Sets the seed of the random number generator used by [`Policy::RandomDraw`]
+    ///
+    /// Defaults to [`RNG_SEED`], so output is reproducible across runs. Writers in one
+    /// process built with the same seed draw the same substitutions.
+    #[must_use]
+    pub fn policy_seed(mut self, seed: u64) -> Self {
+        self.policy_seed = Some(seed);
+        self
+    }
+
+    /// Sets how records with sequences of the wrong length are handled by [`Writer::push`]
+    ///
+    /// Defaults to [`LengthMismatchPolicy::Error`].
+    #[must_use]
+    pub fn length_mismatch_policy(mut self, policy: LengthMismatchPolicy) -> Self {
+        self.length_mismatch_policy = Some(policy);
+        self
+    }
+
+    /// Sets whether the sequence lengths are taken from the first record pushed
+    ///
+    /// The file header is held back until the first record is pushed with
+    /// [`Writer::push`], which then fixes `slen` (and `xlen` for paired files) for the rest
+    /// of the file. The lengths of the configured header only tell single-end (`xlen == 0`)
+    /// from paired (`xlen > 0`) files. Since nothing is written before the first record, any
+    /// writer can be used, without seeking back to the header.
+    ///
+    /// Operations that need the lengths before the first record fail with
+    /// `WriteError::LengthsNotInferred`: building a headless writer, writing pre-encoded
+    /// records, ingesting other writers, and flushing a writer without records.
+    #[must_use]
+    pub fn infer_lengths(mut self, infer_lengths: bool) -> Self {
+        self.infer_lengths = Some(infer_lengths);
+        self
+    }
+
     pub fn build<W: Write>(self, inner: W) -> Result<Writer<W>> {
         let Some(header) = self.header else {
             return Err(WriteError::MissingHeader.into());
         };
-        Writer::new(
-            inner,
-            header,
-            self.policy.unwrap_or_default(),
-            self.headless.unwrap_or(false),
-        )
+        let headless = self.headless.unwrap_or(false);
+        let infer_lengths = self.infer_lengths.unwrap_or(false);
+        if infer_lengths && headless {
+            return Err(WriteError::LengthsNotInferred("headless writers have no header").into());
+        }
+        let policy = self.policy.unwrap_or_default();
+        // an inferred header is written with the first record
+        let mut writer = Writer::new(inner, header, policy, headless || infer_lengths)?;
+        writer.headless = headless;
+        writer.pending_header = infer_lengths;
+        if let Some(seed) = self.policy_seed {
+            writer.encoder = Encoder::with_policy_seed(header, policy, seed);
+        }
+        writer.strict_mode = self.strict_mode.unwrap_or(false);
+        writer.length_policy = self.length_mismatch_policy.unwrap_or_default();
+        Ok(writer)
     }
 }
 
@@ -311,6 +496,22 @@ pub struct Writer<W: Write> {
     /// Whether this writer is in headless mode
     /// When true, the header is not written to the output
     headless: bool,
+
+    /// Whether records with data the format cannot store are rejected
+    strict_mode: bool,
+
+    /// Whether the header is written once the first record fixes the sequence lengths
+    pending_header: bool,
+
+    /// Handling of records with sequences of the wrong length
+    length_policy: LengthMismatchPolicy,
+
+    /// Counts of records affected by the length mismatch policy
+    stats: WriteStats,
+
+    /// Buffers for sequences adjusted to the lengths of the header
+    s_adj: Vec<u8>,
+    x_adj: Vec<u8>,
 }
 impl<W: Write> Writer<W> {
     /// Creates a new `Writer` instance with specified configuration
@@ -354,6 +555,12 @@ impl<W: Write> Writer<W> {
             inner,
             encoder: Encoder::with_policy(header, policy),
             headless,
+            strict_mode: false,
+            pending_header: false,
+            length_policy: LengthMismatchPolicy::default(),
+            stats: WriteStats::default(),
+            s_adj: Vec::new(),
+            x_adj: Vec::new(),
         })
     }
 
@@ -363,15 +570,98 @@ impl<W: Write> Writer<W> {
     }
 
     /// Returns the header of the writer
+    ///
+    /// For writers inferring their lengths (see [`WriterBuilder::infer_lengths`]), the
+    /// lengths are only final once [`is_header_pending`](Self::is_header_pending) is `false`.
     pub fn header(&self) -> FileHeader {
         self.encoder.header
     }
 
+    /// Returns `true` if the header is held back until the first record fixes the lengths
+    ///
+    /// See [`WriterBuilder::infer_lengths`].
+    pub fn is_header_pending(&self) -> bool {
+        self.pending_header
+    }
+
+    /// Returns the handling of records with sequences of the wrong length
+    pub fn length_mismatch_policy(&self) -> LengthMismatchPolicy {
+        self.length_policy
+    }
+
+    /// Returns the counts of records affected by the length mismatch policy
+    ///
+    /// See [`WriterBuilder::length_mismatch_policy`].
+    pub fn write_stats(&self) -> WriteStats {
+        self.stats
+    }
+
+    /// Fails if the header is still waiting for the first record
+    fn check_header_written(&self, operation: &'static str) -> Result<()> {
+        if self.pending_header {
+            return Err(WriteError::LengthsNotInferred(operation).into());
+        }
+        Ok(())
+    }
+
+    /// Fixes the sequence lengths from the first record and writes the header
+    fn write_inferred_header(&mut self, record: &SequencingRecord) -> Result<()> {
+        let length = |seq: &[u8]| {
+            if seq.is_empty() {
+                return Err(WriteError::SequenceTooShort {
+                    expected: 1,
+                    got: 0,
+                });
+            }
+            u32::try_from(seq.len()).map_err(|_| WriteError::SequenceTooLong {
+                expected: u32::MAX as usize,
+                got: seq.len(),
+            })
+        };
+        let mut header = self.encoder.header;
+        header.slen = length(record.s_seq)?;
+        if header.is_paired() {
+            header.xlen = length(record.x_seq.unwrap_or_default())?;
+        }
+        header.write_bytes(&mut self.inner)?;
+        self.encoder.header = header;
+        self.pending_header = false;
+        Ok(())
+    }
+
     /// Returns the N-policy of the writer
     pub fn policy(&self) -> Policy {
         self.encoder.policy
     }
 
+    /// Returns the seed of the random number generator of the N-policy
+    ///
+    /// See [`WriterBuilder::policy_seed`].
+    pub fn policy_seed(&self) -> u64 {
+        self.encoder.seed
+    }
+
+    /// Returns `true` if the writer rejects records holding data it cannot store
+    ///
+    /// See [`WriterBuilder::strict_mode`].
+    pub fn is_strict(&self) -> bool {
+        self.strict_mode
+    }
+
+    /// Checks that the record only holds data the file can store
+    fn check_stored_fields(&self, record: &SequencingRecord) -> Result<()> {
+        if record.has_qualities() {
+            return Err(WriteError::UnsupportedField("quality").into());
+        }
+        if record.has_headers() {
+            return Err(WriteError::UnsupportedField("header").into());
+        }
+        if record.is_paired() && !self.encoder.header.is_paired() {
+            return Err(WriteError::PairedFlagNotSet.into());
+        }
+        Ok(())
+    }
+
     /// Writes a single record to the output
     ///
     /// This method encodes and writes a primary sequence along with an associated flag.
@@ -388,6 +678,7 @@ impl<W: Write> Writer<W> {
     /// * `Err(WriteError::FlagSet)` if the flag is set but no flag value is provided
     #[deprecated]
     pub fn write_record(&mut self, flag: Option<u64>, primary: &[u8]) -> Result<bool> {
+        self.check_header_written("use push to write the first record")?;
         let has_flag = self.encoder.header.flags;
         if let Some(sbuffer) = self.encoder.encode_single(primary)? {
             if has_flag {
@@ -420,6 +711,7 @@ impl<W: Write> Writer<W> {
         primary: &[u8],
         extended: &[u8],
     ) -> Result<bool> {
+        self.check_header_written("use push to write the first record")?;
         let has_flag = self.encoder.header.flags;
         if let Some((sbuffer, xbuffer)) = self.encoder.encode_paired(primary, extended)? {
             if has_flag {
@@ -470,11 +762,6 @@ impl<W: Write> Writer<W> {
     /// # }
     /// ```
     pub fn push(&mut self, record: SequencingRecord) -> Result<bool> {
-        let has_flag = self.encoder.header.flags;
-        if has_flag {
-            write_flag(&mut self.inner, record.flag().unwrap_or(0))?;
-        }
-
         // Check paired status - writer can require paired (record must have R2),
         // but if writer is single-end, we simply ignore any R2 data in the record.
         if self.encoder.header.is_paired() && !record.is_paired() {
@@ -486,23 +773,183 @@ impl<W: Write> Writer<W> {
             .into());
         }
 
-        if self.encoder.header.is_paired() {
-            if let Some((sbuffer, xbuffer)) = self
-                .encoder
-                .encode_paired(record.s_seq, record.x_seq.unwrap_or_default())?
-            {
+        // In strict mode, data the format cannot store is rejected instead of ignored
+        if self.strict_mode {
+            self.check_stored_fields(&record)?;
+        }
+
+        if self.pending_header {
+            self.write_inferred_header(&record)?;
+        }
+
+        // Adjust the sequences to the lengths of the header
+        let header = self.encoder.header;
+        let x_seq = record.x_seq.unwrap_or_default();
+        let s_fit = fit_sequence(
+            record.s_seq,
+            header.slen as usize,
+            self.length_policy,
+            &mut self.s_adj,
+        );
+        let x_fit = if header.is_paired() {
+            fit_sequence(
+                x_seq,
+                header.xlen as usize,
+                self.length_policy,
+                &mut self.x_adj,
+            )
+        } else {
+            Fit::Exact
+        };
+        if s_fit == Fit::Mismatch || x_fit == Fit::Mismatch {
+            if self.length_policy == LengthMismatchPolicy::SkipAndCount {
+                self.stats.skipped += 1;
+                return Ok(false);
+            }
+            check_sequence_length(header.slen, record.s_seq)?;
+            check_sequence_length(header.xlen, x_seq)?;
+        }
+        let truncated = s_fit == Fit::Truncated || x_fit == Fit::Truncated;
+        let padded = s_fit == Fit::Padded || x_fit == Fit::Padded;
+        let s_seq = if s_fit == Fit::Exact {
+            record.s_seq
+        } else {
+            &self.s_adj
+        };
+        let x_seq = if x_fit == Fit::Exact {
+            x_seq
+        } else {
+            &self.x_adj
+        };
+
+        // The flag is only written once the record is known to be encodable, so that
+        // skipped or rejected records leave no partial data behind
+        let flag = header.flags.then(|| record.flag().unwrap_or(0));
+        let written = if header.is_paired() {
+            if let Some((sbuffer, xbuffer)) = self.encoder.encode_paired(s_seq, x_seq)? {
+                if let Some(flag) = flag {
+                    write_flag(&mut self.inner, flag)?;
+                }
                 write_buffer(&mut self.inner, sbuffer)?;
                 write_buffer(&mut self.inner, xbuffer)?;
-                Ok(true)
+                true
             } else {
-                Ok(false)
+                false
+            }
+        } else if let Some(buffer) = self.encoder.encode_single(s_seq)? {
+            if let Some(flag) = flag {
+                write_flag(&mut self.inner, flag)?;
             }
-        } else if let Some(buffer) = self.encoder.encode_single(record.s_seq)? {
             write_buffer(&mut self.inner, buffer)?;
-            Ok(true)
+            true
         } else {
-            Ok(false)
+            false
+        };
+
+        // Adjusted records dropped by the nucleotide policy are not counted
+        if written {
+            self.stats.truncated += usize::from(truncated);
+            self.stats.padded += usize::from(padded);
         }
+        Ok(written)
+    }
+
+    /// Writes a single record from an already-encoded primary sequence
+    ///
+    /// The words of `sbuf` are written as-is, bypassing the encoder and therefore the invalid
+    /// nucleotide [`Policy`]. The caller must ensure that `sbuf` holds a sequence of exactly
+    /// `slen` nucleotides encoded with the bitsize of the header (e.g. by
+    /// [`BitSize::encode`](bitnuc::BitSize::encode)), otherwise the file will decode to
+    /// garbage. `flag` is written if the header has flags (defaulting to 0).
+    ///
+    /// # Errors
+    ///
+    /// * `WriteError::ConfigurationMismatch` - If the writer is configured for paired records
+    /// * `WriteError::EncodedLengthMismatch` - If `sbuf` does not have the number of words
+    ///   required by the header
+    pub fn write_encoded_direct(&mut self, flag: Option<u64>, sbuf: &[u64]) -> Result<()> {
+        self.check_header_written("cannot write encoded records before the first record")?;
+        if self.encoder.header.is_paired() {
+            return Err(WriteError::ConfigurationMismatch {
+                attribute: "paired",
+                expected: true,
+                actual: false,
+            }
+            .into());
+        }
+        let config = RecordConfig::from_header(&self.encoder.header);
+        check_encoded_len(config.schunk(), sbuf)?;
+        if self.encoder.header.flags {
+            write_flag(&mut self.inner, flag.unwrap_or(0))?;
+        }
+        write_buffer(&mut self.inner, sbuf)
+    }
+
+    /// Writes a paired record from already-encoded primary and extended sequences
+    ///
+    /// See [`write_encoded_direct`](Self::write_encoded_direct) for the requirements on the
+    /// encoded buffers.
+    ///
+    /// # Errors
+    ///
+    /// * `WriteError::ConfigurationMismatch` - If the writer is not configured for paired records
+    /// * `WriteError::EncodedLengthMismatch` - If either buffer does not have the number of
+    ///   words required by the header
+    pub fn write_encoded_direct_paired(
+        &mut self,
+        flag: Option<u64>,
+        sbuf: &[u64],
+        xbuf: &[u64],
+    ) -> Result<()> {
+        self.check_header_written("cannot write encoded records before the first record")?;
+        if !self.encoder.header.is_paired() {
+            return Err(WriteError::ConfigurationMismatch {
+                attribute: "paired",
+                expected: false,
+                actual: true,
+            }
+            .into());
+        }
+        let config = RecordConfig::from_header(&self.encoder.header);
+        check_encoded_len(config.schunk(), sbuf)?;
+        check_encoded_len(config.xchunk(), xbuf)?;
+        if self.encoder.header.flags {
+            write_flag(&mut self.inner, flag.unwrap_or(0))?;
+        }
+        write_buffer(&mut self.inner, sbuf)?;
+        write_buffer(&mut self.inner, xbuf)
+    }
+
+    /// Writes two mates as consecutive single-end records with their own flags
+    ///
+    /// Either both records are written or neither, so a mate skipped by the policy does not
+    /// leave its partner unpaired. See [`write_interleaved_pair`](super::write_interleaved_pair).
+    pub(crate) fn write_mates(&mut self, flags: [u64; 2], r1: &[u8], r2: &[u8]) -> Result<bool> {
+        self.check_header_written("cannot write interleaved mates before the first record")?;
+        if self.encoder.header.is_paired() {
+            return Err(WriteError::ConfigurationMismatch {
+                attribute: "paired",
+                expected: true,
+                actual: false,
+            }
+            .into());
+        }
+        if !self.encoder.header.flags {
+            return Err(WriteError::ConfigurationMismatch {
+                attribute: "flags",
+                expected: false,
+                actual: true,
+            }
+            .into());
+        }
+        if !self.encoder.encode_mates(r1, r2)? {
+            return Ok(false);
+        }
+        write_flag(&mut self.inner, flags[0])?;
+        write_buffer(&mut self.inner, &self.encoder.sbuffer)?;
+        write_flag(&mut self.inner, flags[1])?;
+        write_buffer(&mut self.inner, &self.encoder.xbuffer)?;
+        Ok(true)
     }
 
     /// Consumes the writer and returns the underlying writer
@@ -545,6 +992,7 @@ impl<W: Write> Writer<W> {
     /// * `Ok(())` - If the flush was successful
     /// * `Err(Error)` - If flushing failed
     pub fn flush(&mut self) -> Result<()> {
+        self.check_header_written("no record has been written")?;
         self.inner.flush()?;
         Ok(())
     }
@@ -592,6 +1040,7 @@ impl<W: Write> Writer<W> {
     /// * `Ok(())` - If the contents were successfully ingested
     /// * `Err(Error)` - If writing the contents failed
     pub fn ingest(&mut self, other: &mut Writer<Vec<u8>>) -> Result<()> {
+        self.check_header_written("cannot ingest records before the first record")?;
         let other_inner = other.by_ref();
         self.inner.write_all(other_inner)?;
         other_inner.clear();
@@ -599,6 +1048,149 @@ impl<W: Write> Writer<W> {
     }
 }
 
+impl<W: Write + Clone> Writer<W> {
+    /// Creates a copy of this writer with a fresh encoder
+    ///
+    /// The fork shares the header, policy (and its seed), length mismatch policy and mode of
+    /// this writer (including a header still waiting for the first record) and writes
+    /// to a clone of its underlying writer. Its encoder starts with empty buffers and a
+    /// freshly seeded random number generator, so it encodes records exactly like a newly
+    /// built writer.
+    ///
+    /// Nothing is written to the underlying writer when forking.
+    #[must_use]
+    pub fn fork(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            encoder: Encoder::with_policy_seed(
+                self.encoder.header,
+                self.encoder.policy,
+                self.encoder.seed,
+            ),
+            headless: self.headless,
+            strict_mode: self.strict_mode,
+            pending_header: self.pending_header,
+            length_policy: self.length_policy,
+            stats: WriteStats::default(),
+            s_adj: Vec::new(),
+            x_adj: Vec::new(),
+        }
+    }
+}
+
+/// A writer forwarding every record to two [`Writer`]s
+///
+/// This is useful to write a primary output and a copy of it (e.g. a backup) in a single
+/// pass. Both writers must share the same header; they are usually created by the same
+/// [`WriterBuilder`] configuration, or one is a [`fork`](Writer::fork) of the other.
+///
+/// Unlike [`Writer::ingest`], which combines the output of writers filled on different
+/// threads, both destinations are written simultaneously on the calling thread.
+///
+/// # Examples
+///
+/// ```
+/// # use binseq::bq::{FileHeaderBuilder, TeeWriterBuilder, WriterBuilder};
+/// # use binseq::{Result, SequencingRecordBuilder};
+/// # fn main() -> Result<()> {
+/// let header = FileHeaderBuilder::new().slen(8).build()?;
+/// let primary = WriterBuilder::default().header(header).build(Vec::new())?;
+/// let secondary = WriterBuilder::default().header(header).build(Vec::new())?;
+/// let mut tee = TeeWriterBuilder::new(primary, secondary).build()?;
+///
+/// tee.push(SequencingRecordBuilder::default().s_seq(b"ACGTACGT").build()?)?;
+///
+/// let (primary, secondary) = tee.into_parts();
+/// assert_eq!(primary.into_inner(), secondary.into_inner());
+/// # Ok(())
+/// # }
+/// ```
+pub struct TeeWriter<W1: Write, W2: Write> {
+    /// Writer receiving every record first
+    primary: Writer<W1>,
+
+    /// Writer receiving every record second
+    secondary: Writer<W2>,
+}
+impl<W1: Write, W2: Write> TeeWriter<W1, W2> {
+    /// Writes a record to both writers
+    ///
+    /// The record is written to the primary writer first. Writing stops at the first
+    /// error, so the secondary writer may be missing the record if the primary failed.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(true)` if the record was written to both writers
+    /// * `Ok(false)` if the record was skipped due to invalid nucleotides
+    /// * `Err(_)` if writing to either writer failed
+    pub fn push(&mut self, record: SequencingRecord) -> Result<bool> {
+        let written = self.primary.push(record)?;
+        let mirrored = self.secondary.push(record)?;
+        debug_assert_eq!(written, mirrored, "tee writers diverged");
+        Ok(written && mirrored)
+    }
+
+    /// Flushes both writers
+    pub fn flush(&mut self) -> Result<()> {
+        self.primary.flush()?;
+        self.secondary.flush()
+    }
+
+    /// Returns the header shared by both writers
+    pub fn header(&self) -> FileHeader {
+        self.primary.header()
+    }
+
+    /// Consumes the tee and returns the primary and secondary writers
+    pub fn into_parts(self) -> (Writer<W1>, Writer<W2>) {
+        (self.primary, self.secondary)
+    }
+}
+
+/// Builder for [`TeeWriter`] instances
+///
+/// # Examples
+///
+/// ```
+/// # use binseq::bq::{FileHeaderBuilder, TeeWriterBuilder, WriterBuilder};
+/// # use binseq::Result;
+/// # fn main() -> Result<()> {
+/// let header = FileHeaderBuilder::new().slen(100).build()?;
+/// let primary = WriterBuilder::default().header(header).build(Vec::new())?;
+/// let tee = TeeWriterBuilder::new(primary.fork(), primary).build()?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct TeeWriterBuilder<W1: Write, W2: Write> {
+    /// Writer receiving every record first
+    primary: Writer<W1>,
+
+    /// Writer receiving every record second
+    secondary: Writer<W2>,
+}
+impl<W1: Write, W2: Write> TeeWriterBuilder<W1, W2> {
+    /// Creates a builder for a tee over the `primary` and `secondary` writers
+    #[must_use]
+    pub fn new(primary: Writer<W1>, secondary: Writer<W2>) -> Self {
+        Self { primary, secondary }
+    }
+
+    /// Builds the tee
+    ///
+    /// # Errors
+    ///
+    /// * `WriteError::FormatMismatch` - If the writers do not share the same header
+    pub fn build(self) -> Result<TeeWriter<W1, W2>> {
+        if self.primary.header() != self.secondary.header() {
+            return Err(WriteError::FormatMismatch.into());
+        }
+        Ok(TeeWriter {
+            primary: self.primary,
+            secondary: self.secondary,
+        })
+    }
+}
+
 /// A streaming writer for binary sequence data
 ///
 /// This writer buffers data before writing it to the underlying writer,
@@ -717,6 +1309,29 @@ impl<W: Write> StreamWriter<W> {
     }
 }
 
+#[cfg(feature = "flate2")]
+impl<W: Write> StreamWriter<GzEncoder<W>> {
+    /// Consumes the writer, completes the gzip stream, and returns the inner writer
+    ///
+    /// Dropping the writer also completes the stream, but silently ignores any errors.
+    pub fn finish(self) -> Result<W> {
+        Ok(self.into_inner()?.finish()?)
+    }
+}
+
+impl StreamWriter<Cursor<Vec<u8>>> {
+    /// Consumes the writer and opens its output for reading as a [`StreamReader`]
+    ///
+    /// All buffered data is flushed first, and the reader starts at the beginning of the
+    /// output. This is mainly useful for round-trip testing of in-memory output.
+    ///
+    /// The writer must not be headless, since the reader expects a file header.
+    pub fn into_bq_reader(self) -> Result<StreamReader<Cursor<Vec<u8>>>> {
+        let buffer = self.into_inner()?.into_inner();
+        Ok(StreamReader::new(Cursor::new(buffer)))
+    }
+}
+
 /// Builder for `StreamWriter` instances
 ///
 /// This builder provides a convenient way to create and configure `StreamWriter`
@@ -762,6 +1377,20 @@ impl StreamWriterBuilder {
         self
     }
 
+    /// Compresses the output with gzip at the given `level` (0-9, clamped)
+    ///
+    /// The BQ format itself is uncompressed, but gzipping the byte stream is useful for
+    /// network transfer or archival. Compressed output can be read back with
+    /// [`StreamReader::from_gzip`]. This should be the last option set on the builder.
+    #[cfg(feature = "flate2")]
+    #[must_use]
+    pub fn gzip_output(self, level: u32) -> GzipStreamWriterBuilder {
+        GzipStreamWriterBuilder {
+            builder: self,
+            level: level.min(9),
+        }
+    }
+
     /// Builds a `StreamWriter` with the configured settings
     ///
     /// # Arguments
@@ -788,11 +1417,35 @@ impl StreamWriterBuilder {
     }
 }
 
+/// Builder for `StreamWriter` instances with gzip-compressed output
+///
+/// Created by [`StreamWriterBuilder::gzip_output`].
+#[cfg(feature = "flate2")]
+pub struct GzipStreamWriterBuilder {
+    /// Settings of the underlying stream writer
+    builder: StreamWriterBuilder,
+    /// Gzip compression level
+    level: u32,
+}
+
+#[cfg(feature = "flate2")]
+impl GzipStreamWriterBuilder {
+    /// Builds a `StreamWriter` that gzips its output to `inner`
+    ///
+    /// Use [`StreamWriter::finish`] to complete the gzip stream once all records are written.
+    pub fn build<W: Write>(self, inner: W) -> Result<StreamWriter<GzEncoder<W>>> {
+        let encoder = GzEncoder::new(inner, flate2::Compression::new(self.level));
+        self.builder.build(encoder)
+    }
+}
+
 #[cfg(test)]
 mod testing {
 
     use std::{fs::File, io::BufWriter};
 
+    use rand::{Rng, rngs::StdRng};
+
     use super::*;
     use crate::SequencingRecordBuilder;
     use crate::bq::{FileHeaderBuilder, SIZE_HEADER};
@@ -876,7 +1529,26 @@ mod testing {
         let header = FileHeaderBuilder::new().slen(8).build().unwrap();
         let mut encoder = Encoder::new(header);
         let result = encoder.encode_single(b"ACGT");
-        assert!(result.is_err());
+        assert!(matches!(
+            result,
+            Err(crate::Error::WriteError(WriteError::SequenceTooShort {
+                expected: 8,
+                got: 4
+            }))
+        ));
+
+        let result = encoder.encode_single(b"ACGTACGTAC");
+        let Err(e) = result else {
+            panic!("expected an error for a too-long sequence");
+        };
+        assert!(matches!(
+            e,
+            crate::Error::WriteError(WriteError::SequenceTooLong {
+                expected: 8,
+                got: 10
+            })
+        ));
+        assert!(e.to_string().contains("too long"));
     }
 
     #[test]
@@ -900,7 +1572,12 @@ mod testing {
         let header = FileHeaderBuilder::new().slen(8).xlen(8).build().unwrap();
         let mut encoder = Encoder::new(header);
         let result = encoder.encode_paired(b"ACGT", b"ACGTACGT");
-        assert!(result.is_err());
+        assert!(matches!(
+            result,
+            Err(crate::Error::WriteError(
+                WriteError::SequenceTooShort { .. }
+            ))
+        ));
     }
 
     #[test]
@@ -908,7 +1585,21 @@ mod testing {
         let header = FileHeaderBuilder::new().slen(8).xlen(8).build().unwrap();
         let mut encoder = Encoder::new(header);
         let result = encoder.encode_paired(b"ACGTACGT", b"ACGT");
-        assert!(result.is_err());
+        assert!(matches!(
+            result,
+            Err(crate::Error::WriteError(WriteError::SequenceTooShort {
+                expected: 8,
+                got: 4
+            }))
+        ));
+        let result = encoder.encode_paired(b"ACGTACGT", b"ACGTACGTA");
+        assert!(matches!(
+            result,
+            Err(crate::Error::WriteError(WriteError::SequenceTooLong {
+                expected: 8,
+                got: 9
+            }))
+        ));
     }
 
     #[test]
@@ -1139,6 +1830,78 @@ mod testing {
         Ok(())
     }
 
+    #[test]
+    fn test_stream_writer_into_bq_reader() -> Result<()> {
+        use crate::BinseqRecord;
+        use std::io::Cursor;
+
+        let seqs: Vec<Vec<u8>> = (0..100)
+            .map(|i| (0..32).map(|j| b"ACGT"[(i + j * 3) % 4]).collect())
+            .collect();
+        let header = FileHeaderBuilder::new().slen(32).flags(true).build()?;
+        let mut writer = StreamWriterBuilder::default()
+            .header(header)
+            .build(Cursor::new(Vec::new()))?;
+        for (idx, seq) in seqs.iter().enumerate() {
+            let record = SequencingRecordBuilder::default()
+                .s_seq(seq)
+                .flag(idx as u64)
+                .build()?;
+            assert!(writer.push(record)?);
+        }
+
+        let mut reader = writer.into_bq_reader()?;
+        assert_eq!(reader.read_header()?.slen, 32);
+        let mut n_records = 0;
+        while let Some(record) = reader.next_record() {
+            let record = record?;
+            assert_eq!(record.flag(), Some(n_records as u64));
+            assert_eq!(record.decode_s_alloc()?, seqs[n_records]);
+            n_records += 1;
+        }
+        assert_eq!(n_records, seqs.len());
+        Ok(())
+    }
+
+    #[test]
+    fn test_flagless_file_omits_flag_words() -> Result<()> {
+        use crate::BinseqRecord;
+        use std::io::Cursor;
+
+        let seqs: Vec<Vec<u8>> = (0..50)
+            .map(|i| (0..150).map(|j| b"ACGT"[(i * 7 + j) % 4]).collect())
+            .collect();
+        let write = |flags: bool| -> Result<Vec<u8>> {
+            let header = FileHeaderBuilder::new().slen(150).flags(flags).build()?;
+            let mut writer = WriterBuilder::default().header(header).build(Vec::new())?;
+            for seq in &seqs {
+                let record = SequencingRecordBuilder::default()
+                    .s_seq(seq)
+                    .flag(7)
+                    .build()?;
+                assert!(writer.push(record)?);
+            }
+            writer.flush()?;
+            Ok(writer.into_inner())
+        };
+
+        let flagged = write(true)?;
+        let flagless = write(false)?;
+        assert_eq!(flagged.len() - flagless.len(), 8 * seqs.len());
+
+        let mut reader = StreamReader::new(Cursor::new(flagless));
+        assert!(!reader.read_header()?.flags);
+        let mut n_records = 0;
+        while let Some(record) = reader.next_record() {
+            let record = record?;
+            assert_eq!(record.flag(), None);
+            assert_eq!(record.decode_s_alloc()?, seqs[n_records]);
+            n_records += 1;
+        }
+        assert_eq!(n_records, seqs.len());
+        Ok(())
+    }
+
     #[test]
     fn test_stream_writer_push() -> Result<()> {
         let mut writer = StreamWriter::new(
@@ -1177,4 +1940,442 @@ mod testing {
         assert!(inner.is_empty());
         Ok(())
     }
+
+    #[test]
+    fn test_write_encoded_direct() -> Result<()> {
+        use crate::BinseqRecord;
+        use crate::bq::MmapReader;
+        use bitnuc::BitSize;
+
+        let seqs: Vec<Vec<u8>> = (0..20)
+            .map(|i| (0..40).map(|j| b"ACGT"[(i + j * 3) % 4]).collect())
+            .collect();
+        let header = FileHeaderBuilder::new().slen(40).flags(true).build()?;
+        let mut writer = WriterBuilder::default().header(header).build(Vec::new())?;
+        let mut sbuf = Vec::new();
+        for (flag, seq) in seqs.iter().enumerate() {
+            sbuf.clear();
+            BitSize::Two.encode(seq, &mut sbuf)?;
+            writer.write_encoded_direct(Some(flag as u64), &sbuf)?;
+        }
+
+        // Wrong number of words and paired writes are rejected
+        assert!(matches!(
+            writer.write_encoded_direct(None, &[0]),
+            Err(crate::Error::WriteError(
+                WriteError::EncodedLengthMismatch {
+                    expected: 2,
+                    got: 1
+                }
+            ))
+        ));
+        assert!(
+            writer
+                .write_encoded_direct_paired(None, &sbuf, &sbuf)
+                .is_err()
+        );
+
+        let reader = MmapReader::from_bytes(writer.into_inner())?;
+        assert_eq!(reader.num_records(), seqs.len());
+        for (idx, seq) in seqs.iter().enumerate() {
+            let record = reader.get(idx)?;
+            assert_eq!(record.decode_s_alloc()?, *seq);
+            assert_eq!(record.flag(), Some(idx as u64));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_encoded_direct_matches_encoding_writer() -> Result<()> {
+        use bitnuc::BitSize;
+
+        let pairs: Vec<(Vec<u8>, Vec<u8>)> = (0..25)
+            .map(|i| {
+                let s = (0..50).map(|j| b"ACGT"[(i * 7 + j) % 4]).collect();
+                let x = (0..70).map(|j| b"TGCA"[(i + j * 5) % 4]).collect();
+                (s, x)
+            })
+            .collect();
+
+        for paired in [false, true] {
+            let xlen = if paired { 70 } else { 0 };
+            let header = FileHeaderBuilder::new()
+                .slen(50)
+                .xlen(xlen)
+                .flags(true)
+                .build()?;
+            let mut encoding = WriterBuilder::default().header(header).build(Vec::new())?;
+            let mut direct = WriterBuilder::default().header(header).build(Vec::new())?;
+            let (mut sbuf, mut xbuf) = (Vec::new(), Vec::new());
+            for (flag, (s, x)) in pairs.iter().enumerate() {
+                sbuf.clear();
+                xbuf.clear();
+                BitSize::Two.encode(s, &mut sbuf)?;
+                BitSize::Two.encode(x, &mut xbuf)?;
+                let builder = SequencingRecordBuilder::default()
+                    .s_seq(s)
+                    .flag(flag as u64);
+                if paired {
+                    encoding.push(builder.x_seq(x).build()?)?;
+                    direct.write_encoded_direct_paired(Some(flag as u64), &sbuf, &xbuf)?;
+                } else {
+                    encoding.push(builder.build()?)?;
+                    direct.write_encoded_direct(Some(flag as u64), &sbuf)?;
+                }
+            }
+            assert_eq!(encoding.into_inner(), direct.into_inner());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_encoded_direct_paired() -> Result<()> {
+        use crate::BinseqRecord;
+        use crate::bq::MmapReader;
+        use bitnuc::BitSize;
+
+        let (sseq, xseq) = (b"ACGTACGTAC", b"TTTTGGGGCCCCAAAA");
+        let (mut sbuf, mut xbuf) = (Vec::new(), Vec::new());
+        BitSize::Two.encode(sseq, &mut sbuf)?;
+        BitSize::Two.encode(xseq, &mut xbuf)?;
+
+        let header = FileHeaderBuilder::new().slen(10).xlen(16).build()?;
+        let mut writer = WriterBuilder::default().header(header).build(Vec::new())?;
+        writer.write_encoded_direct_paired(None, &sbuf, &xbuf)?;
+        assert!(writer.write_encoded_direct(None, &sbuf).is_err());
+
+        let reader = MmapReader::from_bytes(writer.into_inner())?;
+        let record = reader.get(0)?;
+        assert_eq!(record.decode_s_alloc()?, sseq);
+        assert_eq!(record.decode_x_alloc()?, xseq);
+        Ok(())
+    }
+
+    #[cfg(feature = "flate2")]
+    #[test]
+    fn test_gzip_output() -> Result<()> {
+        use crate::BinseqRecord;
+
+        let seqs: Vec<Vec<u8>> = (0..500)
+            .map(|i| (0..64).map(|j| b"ACGT"[(i + j) % 4]).collect())
+            .collect();
+        let plain_path = std::env::temp_dir().join("binseq_test_gzip_output.bq");
+        let gzip_path = std::env::temp_dir().join("binseq_test_gzip_output.bq.gz");
+
+        let header = FileHeaderBuilder::new().slen(64).build()?;
+        let mut plain = StreamWriterBuilder::default()
+            .header(header)
+            .build(File::create(&plain_path)?)?;
+        let mut gzip = StreamWriterBuilder::default()
+            .header(header)
+            .gzip_output(6)
+            .build(File::create(&gzip_path)?)?;
+        for seq in &seqs {
+            let record = SequencingRecordBuilder::default().s_seq(seq).build()?;
+            assert!(plain.push(record)?);
+            let record = SequencingRecordBuilder::default().s_seq(seq).build()?;
+            assert!(gzip.push(record)?);
+        }
+        plain.into_inner()?;
+        gzip.finish()?;
+
+        let plain_size = std::fs::metadata(&plain_path)?.len();
+        let gzip_size = std::fs::metadata(&gzip_path)?.len();
+        assert!(gzip_size < plain_size);
+
+        let mut reader = StreamReader::from_gzip(&gzip_path)?;
+        assert_eq!(reader.read_header()?.slen, 64);
+        let mut n_records = 0;
+        while let Some(record) = reader.next_record() {
+            assert_eq!(record?.decode_s_alloc()?, seqs[n_records]);
+            n_records += 1;
+        }
+        assert_eq!(n_records, seqs.len());
+
+        std::fs::remove_file(plain_path)?;
+        std::fs::remove_file(gzip_path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_tee_writer_identical_outputs() -> Result<()> {
+        let header = FileHeaderBuilder::new().slen(32).flags(true).build()?;
+        let primary = WriterBuilder::default()
+            .header(header)
+            .policy(Policy::RandomDraw)
+            .build(Vec::new())?;
+        let secondary = WriterBuilder::default()
+            .header(header)
+            .policy(Policy::RandomDraw)
+            .build(Vec::new())?;
+        let mut tee = TeeWriterBuilder::new(primary, secondary).build()?;
+
+        let mut rng = StdRng::seed_from_u64(RNG_SEED);
+        for idx in 0..100 {
+            let seq: Vec<u8> = (0..32).map(|_| b"ACGTN"[rng.random_range(0..5)]).collect();
+            let record = SequencingRecordBuilder::default()
+                .s_seq(&seq)
+                .flag(idx)
+                .build()?;
+            assert!(tee.push(record)?);
+        }
+        tee.flush()?;
+
+        let (primary, secondary) = tee.into_parts();
+        let (primary, secondary) = (primary.into_inner(), secondary.into_inner());
+        assert_eq!(primary.len(), SIZE_HEADER + 100 * (8 + 8));
+        assert_eq!(primary, secondary);
+        Ok(())
+    }
+
+    #[test]
+    fn test_fork_encodes_like_a_new_writer() -> Result<()> {
+        let header = FileHeaderBuilder::new().slen(8).build()?;
+        let mut writer = WriterBuilder::default()
+            .header(header)
+            .policy(Policy::RandomDraw)
+            .build(Vec::new())?;
+        let record = SequencingRecordBuilder::default()
+            .s_seq(b"ACNNGTNA")
+            .build()?;
+        let mut fork = writer.fork();
+        writer.push(record)?;
+        fork.push(record)?;
+        assert_eq!(writer.into_inner(), fork.into_inner());
+        Ok(())
+    }
+
+    #[test]
+    fn test_policy_seed() -> Result<()> {
+        let header = FileHeaderBuilder::new().slen(32).build()?;
+        let seq: Vec<u8> = b"NNNNNNNN".repeat(4);
+        let record = SequencingRecordBuilder::default().s_seq(&seq).build()?;
+        let encode = |seed: Option<u64>| -> Result<Vec<u8>> {
+            let mut builder = WriterBuilder::default()
+                .header(header)
+                .policy(Policy::RandomDraw);
+            if let Some(seed) = seed {
+                builder = builder.policy_seed(seed);
+            }
+            let mut writer = builder.build(Vec::new())?;
+            assert_eq!(writer.policy_seed(), seed.unwrap_or(RNG_SEED));
+            writer.push(record)?;
+            Ok(writer.into_inner())
+        };
+        assert_eq!(encode(None)?, encode(Some(RNG_SEED))?);
+        assert_eq!(encode(Some(7))?, encode(Some(7))?);
+        assert_ne!(encode(Some(7))?, encode(None)?);
+
+        // Forks keep the seed of their parent
+        let mut writer = WriterBuilder::default()
+            .header(header)
+            .policy(Policy::RandomDraw)
+            .policy_seed(7)
+            .build(Vec::new())?;
+        let mut fork = writer.fork();
+        assert_eq!(fork.policy_seed(), 7);
+        writer.push(record)?;
+        fork.push(record)?;
+        assert_eq!(writer.into_inner(), fork.into_inner());
+        Ok(())
+    }
+
+    #[test]
+    fn test_paired_policy_corrects_each_mate() -> Result<()> {
+        let header = FileHeaderBuilder::new().slen(4).xlen(4).build()?;
+        let mut encoder = Encoder::with_policy(header, Policy::SetToA);
+        let (sbuf, xbuf) = encoder.encode_paired(b"ACGT", b"TNGN")?.unwrap();
+        let (sbuf, xbuf) = (sbuf.to_vec(), xbuf.to_vec());
+
+        let mut expected = Encoder::new(header);
+        let (s_expected, x_expected) = expected.encode_paired(b"ACGT", b"TAGA")?.unwrap();
+        assert_eq!(sbuf, s_expected);
+        assert_eq!(xbuf, x_expected);
+
+        let mut encoder = Encoder::with_policy(header, Policy::BreakOnInvalid);
+        assert!(encoder.encode_paired(b"ACGT", b"TNGN").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_tee_writer_header_mismatch() -> Result<()> {
+        let primary = WriterBuilder::default()
+            .header(FileHeaderBuilder::new().slen(8).build()?)
+            .build(Vec::new())?;
+        let secondary = WriterBuilder::default()
+            .header(FileHeaderBuilder::new().slen(16).build()?)
+            .build(Vec::new())?;
+        assert!(matches!(
+            TeeWriterBuilder::new(primary, secondary).build(),
+            Err(crate::Error::WriteError(WriteError::FormatMismatch))
+        ));
+        Ok(())
+    }
+
+    fn record<'a>(s_seq: &'a [u8], x_seq: Option<&'a [u8]>, flag: u64) -> SequencingRecord<'a> {
+        SequencingRecord::new(s_seq, None, None, x_seq, None, None, Some(flag))
+    }
+
+    #[test]
+    fn test_length_mismatch_error_and_skip() -> Result<()> {
+        let header = FileHeaderBuilder::new().slen(10).build()?;
+        let mut writer = WriterBuilder::default().header(header).build(Vec::new())?;
+        assert!(matches!(
+            writer.push(record(b"ACGTACGT", None, 0)),
+            Err(crate::Error::WriteError(WriteError::SequenceTooShort {
+                expected: 10,
+                got: 8
+            }))
+        ));
+
+        let mut writer = WriterBuilder::default()
+            .header(header)
+            .length_mismatch_policy(LengthMismatchPolicy::SkipAndCount)
+            .build(Vec::new())?;
+        assert!(!writer.push(record(b"ACGTACGT", None, 0))?);
+        assert!(writer.push(record(b"ACGTACGTAC", None, 1))?);
+        assert!(!writer.push(record(b"ACGTACGTACGT", None, 2))?);
+        assert_eq!(
+            writer.write_stats(),
+            WriteStats {
+                skipped: 2,
+                truncated: 0,
+                padded: 0
+            }
+        );
+        assert_eq!(writer.into_inner().len(), SIZE_HEADER + 8);
+        Ok(())
+    }
+
+    #[test]
+    fn test_length_mismatch_truncate_pad_paired() -> Result<()> {
+        use crate::BinseqRecord;
+
+        let header = FileHeaderBuilder::new()
+            .slen(10)
+            .xlen(6)
+            .flags(true)
+            .build()?;
+        let mut writer = WriterBuilder::default()
+            .header(header)
+            .length_mismatch_policy(LengthMismatchPolicy::TruncatePad { pad_base: b'G' })
+            .build(Vec::new())?;
+        // primary too long and extended too short
+        assert!(writer.push(record(b"ACGTACGTACTT", Some(b"TTTT"), 0))?);
+        // primary too short and extended too long
+        assert!(writer.push(record(b"CCC", Some(b"AAAAAACC"), 1))?);
+        // exact lengths
+        assert!(writer.push(record(b"ACGTACGTAC", Some(b"TTTTTT"), 2))?);
+        assert_eq!(
+            writer.write_stats(),
+            WriteStats {
+                skipped: 0,
+                truncated: 2,
+                padded: 2
+            }
+        );
+
+        let reader = crate::bq::MmapReader::from_bytes(writer.into_inner())?;
+        let expected: [(&[u8], &[u8]); 3] = [
+            (b"ACGTACGTAC", b"TTTTGG"),
+            (b"CCCGGGGGGG", b"AAAAAA"),
+            (b"ACGTACGTAC", b"TTTTTT"),
+        ];
+        for (idx, (sseq, xseq)) in expected.into_iter().enumerate() {
+            let rec = reader.get(idx)?;
+            assert_eq!(rec.flag(), Some(idx as u64));
+            assert_eq!(rec.decode_s_alloc()?, sseq);
+            assert_eq!(rec.decode_x_alloc()?, xseq);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_length_mismatch_pad_base_and_policy() -> Result<()> {
+        use crate::BinseqRecord;
+
+        let pad = LengthMismatchPolicy::TruncatePad { pad_base: b'N' };
+
+        // 4-bit files store the pad base as-is
+        let header = FileHeaderBuilder::new()
+            .slen(8)
+            .bitsize(bitnuc::BitSize::Four)
+            .build()?;
+        let mut writer = WriterBuilder::default()
+            .header(header)
+            .length_mismatch_policy(pad)
+            .build(Vec::new())?;
+        assert!(writer.push(record(b"ACGTA", None, 0))?);
+        let reader = crate::bq::MmapReader::from_bytes(writer.into_inner())?;
+        assert_eq!(reader.get(0)?.decode_s_alloc()?, b"ACGTANNN");
+
+        // 2-bit files apply the nucleotide policy to the padded sequence
+        let header = FileHeaderBuilder::new().slen(8).build()?;
+        let mut writer = WriterBuilder::default()
+            .header(header)
+            .policy(Policy::IgnoreSequence)
+            .length_mismatch_policy(pad)
+            .build(Vec::new())?;
+        assert!(!writer.push(record(b"ACGTA", None, 0))?);
+        assert_eq!(writer.write_stats(), WriteStats::default());
+
+        let mut writer = WriterBuilder::default()
+            .header(header)
+            .policy(Policy::SetToT)
+            .length_mismatch_policy(pad)
+            .build(Vec::new())?;
+        assert!(writer.push(record(b"ACGTA", None, 0))?);
+        assert_eq!(writer.write_stats().padded, 1);
+        let reader = crate::bq::MmapReader::from_bytes(writer.into_inner())?;
+        assert_eq!(reader.get(0)?.decode_s_alloc()?, b"ACGTATTT");
+        Ok(())
+    }
+
+    #[test]
+    fn test_infer_lengths() -> Result<()> {
+        use crate::BinseqRecord;
+
+        // the lengths of the header only mark the file as paired
+        let header = FileHeaderBuilder::new().slen(1).xlen(1).build()?;
+        let mut writer = WriterBuilder::default()
+            .header(header)
+            .infer_lengths(true)
+            .build(Vec::new())?;
+        assert!(writer.is_header_pending());
+        assert!(writer.by_ref().is_empty());
+        assert!(matches!(
+            writer.flush(),
+            Err(crate::Error::WriteError(WriteError::LengthsNotInferred(_)))
+        ));
+        assert!(matches!(
+            writer.write_encoded_direct_paired(None, &[0], &[0]),
+            Err(crate::Error::WriteError(WriteError::LengthsNotInferred(_)))
+        ));
+
+        assert!(writer.push(record(b"ACGTACGTACGTACG", Some(b"TTGCA"), 0))?);
+        assert!(!writer.is_header_pending());
+        assert_eq!((writer.header().slen, writer.header().xlen), (15, 5));
+        assert!(
+            writer
+                .push(record(b"ACGTACGTAC", Some(b"TTGCA"), 0))
+                .is_err()
+        );
+        assert!(writer.push(record(b"CCCCACGTACGTACG", Some(b"AAAAA"), 0))?);
+        writer.flush()?;
+
+        let reader = crate::bq::MmapReader::from_bytes(writer.into_inner())?;
+        assert_eq!((reader.header().slen, reader.header().xlen), (15, 5));
+        assert_eq!(reader.num_records(), 2);
+        assert_eq!(reader.get(1)?.decode_s_alloc()?, b"CCCCACGTACGTACG");
+
+        assert!(matches!(
+            WriterBuilder::default()
+                .header(header)
+                .infer_lengths(true)
+                .headless(true)
+                .build(Vec::new()),
+            Err(crate::Error::WriteError(WriteError::LengthsNotInferred(_)))
+        ));
+        Ok(())
+    }
 }
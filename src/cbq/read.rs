@@ -1,13 +1,16 @@
-use std::{fs, io, path::Path, sync::Arc, thread};
+use std::{fs, io, ops::Range, path::Path, sync::Arc};
 
-use memmap2::Mmap;
 use zstd::{stream::copy_decode, zstd_safe};
 
 use crate::{
-    BinseqRecord, ParallelProcessor, ParallelReader, Result,
+    BinseqRecord, Executor, ParallelProcessor, ParallelReader, Result, ScratchBuffers,
     cbq::core::{
         BlockHeader, BlockRange, ColumnarBlock, FileHeader, Index, IndexFooter, IndexHeader,
+        RefRecordIter,
     },
+    error::{HeaderError, ReadError},
+    executor::{self, Job},
+    source::ByteSource,
 };
 
 /// A reader for CBQ files operating on generic readers (streaming).
@@ -98,7 +101,7 @@ impl<R: io::Read> Reader<R> {
 
 /// A memory-mapped reader for CBQ files.
 pub struct MmapReader {
-    inner: Arc<Mmap>,
+    inner: Arc<ByteSource>,
     index: Arc<Index>,
 
     /// Reusable record block
@@ -120,9 +123,30 @@ impl Clone for MmapReader {
 impl MmapReader {
     pub fn new<P: AsRef<Path>>(path: P) -> Result<Self> {
         let file = fs::File::open(path)?;
+        Self::from_source(ByteSource::map(&file)?)
+    }
 
-        // Load the mmap
-        let inner = unsafe { Mmap::map(&file) }?;
+    /// Creates a new reader over the contents of a CBQ file held in memory
+    ///
+    /// This behaves exactly like [`MmapReader::new`] but parses `data` directly instead of
+    /// memory-mapping a file.
+    pub fn from_bytes(data: Vec<u8>) -> Result<Self> {
+        Self::from_arc_bytes(data.into())
+    }
+
+    /// Creates a new reader over shared in-memory file contents
+    ///
+    /// See [`MmapReader::from_bytes`].
+    pub fn from_arc_bytes(data: Arc<[u8]>) -> Result<Self> {
+        Self::from_source(ByteSource::memory(data))
+    }
+
+    /// Creates a new reader over the bytes of a CBQ file
+    fn from_source(inner: ByteSource) -> Result<Self> {
+        let min_size = size_of::<FileHeader>() + size_of::<IndexFooter>();
+        if inner.len() < min_size {
+            return Err(HeaderError::InvalidSize(inner.len(), min_size).into());
+        }
 
         // Build the header
         let header = FileHeader::from_bytes(&inner[..size_of::<FileHeader>()])?;
@@ -136,7 +160,10 @@ impl MmapReader {
             let index_footer = IndexFooter::from_bytes(&footer_buf)?;
 
             // Find the coordinates of the compressed index
-            let z_index_start = footer_start - index_footer.bytes as usize;
+            let z_index_start = usize::try_from(index_footer.bytes)
+                .ok()
+                .and_then(|bytes| footer_start.checked_sub(bytes))
+                .ok_or(ReadError::FileTruncation(inner.len()))?;
             let z_index_slice = &inner[z_index_start..footer_start];
 
             // Decompress the index
@@ -185,6 +212,11 @@ impl MmapReader {
         &self.index
     }
 
+    /// Returns the raw contents of the file
+    pub(crate) fn as_bytes(&self) -> &[u8] {
+        &self.inner
+    }
+
     fn load_block(&mut self, range: BlockRange) -> Result<()> {
         let header_start = range.offset as usize;
         let header_end = size_of::<BlockHeader>() + header_start;
@@ -201,6 +233,12 @@ impl MmapReader {
         Ok(())
     }
 
+    /// Decompresses the block at `range` and iterates over its records
+    pub(crate) fn iter_block_records(&mut self, range: BlockRange) -> Result<RefRecordIter<'_>> {
+        self.load_block(range)?;
+        Ok(self.block.iter_records(range))
+    }
+
     /// Iterate over block headers in the CBQ file.
     ///
     /// Note: This requires reading slices from the file so it will be IO-bound.
@@ -222,6 +260,10 @@ impl ParallelReader for MmapReader {
         num_threads: usize,
     ) -> crate::Result<()> {
         let num_records = self.num_records();
+        if num_records == 0 {
+            // Nothing to process in an empty file
+            return Ok(());
+        }
         self.process_parallel_range(processor, num_threads, 0..num_records)
     }
 
@@ -229,17 +271,38 @@ impl ParallelReader for MmapReader {
         self,
         processor: P,
         num_threads: usize,
-        range: std::ops::Range<usize>,
+        range: Range<usize>,
     ) -> crate::Result<()> {
         let num_threads = if num_threads == 0 {
             num_cpus::get()
         } else {
             num_threads.min(num_cpus::get())
         };
+        let jobs = self.parallel_jobs(&processor, num_threads, &range)?;
+        executor::spawn_and_join(jobs)
+    }
 
+    fn process_parallel_in<P: ParallelProcessor + Clone + 'static>(
+        self,
+        executor: &Executor,
+        processor: P,
+        range: Range<usize>,
+    ) -> crate::Result<()> {
+        let jobs = self.parallel_jobs(&processor, executor.num_threads(), &range)?;
+        executor.run(jobs)
+    }
+}
+impl MmapReader {
+    /// Builds one job per thread, each processing a contiguous group of blocks overlapping `range`
+    fn parallel_jobs<P: ParallelProcessor + Clone + 'static>(
+        self,
+        processor: &P,
+        num_threads: usize,
+        range: &Range<usize>,
+    ) -> crate::Result<Vec<Job>> {
         // validate range
         let total_records = self.num_records();
-        self.validate_range(total_records, &range)?;
+        self.validate_range(total_records, range)?;
 
         let mut iv_start = 0;
         let relevant_blocks = self
@@ -255,14 +318,14 @@ impl ParallelReader for MmapReader {
         let num_blocks = relevant_blocks.len();
 
         if relevant_blocks.is_empty() {
-            return Ok(()); // nothing to do
+            return Ok(Vec::new()); // nothing to do
         }
 
         // Distribute blocks evenly across threads, giving extra blocks to first threads
         let base_blocks_per_thread = num_blocks / num_threads;
         let extra_blocks = num_blocks % num_threads;
 
-        let mut handles = Vec::new();
+        let mut jobs: Vec<Job> = Vec::with_capacity(num_threads);
         for thread_id in 0..num_threads {
             // Threads 0..extra_blocks get one extra block
             let blocks_for_this_thread = if thread_id < extra_blocks {
@@ -287,6 +350,8 @@ impl ParallelReader for MmapReader {
 
             let mut t_reader = self.clone();
             let mut t_proc = processor.clone();
+            let range = range.clone();
+            t_proc.set_tid(thread_id);
 
             // pull all block ranges for this thread
             let t_block_ranges = relevant_blocks
@@ -305,7 +370,8 @@ impl ParallelReader for MmapReader {
             //     t_block_ranges.last().unwrap().cumulative_records
             // );
 
-            let thread_handle = thread::spawn(move || -> crate::Result<()> {
+            jobs.push(Box::new(move || -> crate::Result<()> {
+                let mut scratch = ScratchBuffers::default();
                 for b_range in t_block_ranges {
                     t_reader.load_block(b_range)?;
                     for record in t_reader.block.iter_records(b_range) {
@@ -313,21 +379,16 @@ impl ParallelReader for MmapReader {
 
                         // Only process records within our specified range
                         if global_record_idx >= range.start && global_record_idx < range.end {
-                            t_proc.process_record(record)?;
+                            crate::parallel::process_or_skip(&mut t_proc, record, &mut scratch)?;
                         }
                     }
                     t_proc.on_batch_complete()?;
                 }
                 t_proc.on_thread_complete()?;
                 Ok(())
-            });
-            handles.push(thread_handle);
+            }));
         }
-
-        for handle in handles {
-            handle.join().unwrap()?;
-        }
-        Ok(())
+        Ok(jobs)
     }
 }
 #[cfg(test)]
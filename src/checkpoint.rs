@@ -0,0 +1,319 @@
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::{ParallelProcessor, Result};
+
+/// Storage for the units of work completed by a resumable scan
+///
+/// Resumable scans split a file into units of work (blocks for VBQ, fixed-size chunks of
+/// records for BQ) and record each completed unit in the store. A scan that is interrupted,
+/// e.g. by a preempted job, can be restarted with the same store and only processes the
+/// units that were not recorded.
+///
+/// A unit is recorded after [`ParallelProcessor::on_batch_complete`] succeeds for it. If the
+/// scan is interrupted between the two, the unit is processed again on resume, so every unit
+/// is processed *at least once*. Processors should either be idempotent or aggregate
+/// commutatively and commit their results in `on_batch_complete`.
+///
+/// See [`vbq::MmapReader::process_parallel_resumable`](crate::vbq::MmapReader::process_parallel_resumable)
+/// and [`bq::MmapReader::process_parallel_resumable`](crate::bq::MmapReader::process_parallel_resumable).
+pub trait CheckpointStore: Send {
+    /// Returns `true` if the unit was completed by a previous scan
+    fn is_done(&self, unit: usize) -> bool;
+
+    /// Records that the unit was completed
+    fn mark_done(&mut self, unit: usize) -> Result<()>;
+}
+
+/// A [`CheckpointStore`] backed by a bitmap file
+///
+/// Each unit is one bit of the file, and marking a unit as done rewrites only the byte
+/// holding its bit. A checkpoint is only meaningful for the file (and format) it was
+/// created for.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use binseq::FileCheckpoint;
+/// use binseq::processors::CountProcessor;
+/// use binseq::vbq::MmapReader;
+///
+/// let mut checkpoint = FileCheckpoint::open("scan.ckpt").unwrap();
+/// let reader = MmapReader::new("./data/subset.vbq").unwrap();
+/// reader
+///     .process_parallel_resumable(CountProcessor::new(), 4, &mut checkpoint)
+///     .unwrap();
+/// ```
+pub struct FileCheckpoint {
+    /// Bitmap file
+    file: File,
+
+    /// In-memory copy of the bitmap
+    bits: Vec<u8>,
+}
+impl FileCheckpoint {
+    /// Opens the checkpoint at `path`, creating an empty one if it does not exist
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+        let mut bits = Vec::new();
+        file.read_to_end(&mut bits)?;
+        Ok(Self { file, bits })
+    }
+
+    /// Returns the number of completed units
+    #[must_use]
+    pub fn num_done(&self) -> usize {
+        self.bits
+            .iter()
+            .map(|byte| byte.count_ones() as usize)
+            .sum()
+    }
+}
+impl CheckpointStore for FileCheckpoint {
+    fn is_done(&self, unit: usize) -> bool {
+        self.bits
+            .get(unit / 8)
+            .is_some_and(|byte| byte & (1 << (unit % 8)) != 0)
+    }
+
+    fn mark_done(&mut self, unit: usize) -> Result<()> {
+        let pos = unit / 8;
+        if pos >= self.bits.len() {
+            self.bits.resize(pos + 1, 0);
+        }
+        self.bits[pos] |= 1 << (unit % 8);
+        self.file.seek(SeekFrom::Start(pos as u64))?;
+        self.file.write_all(&self.bits[pos..=pos])?;
+        Ok(())
+    }
+}
+
+/// Processes the units `0..num_units` that are not done in `checkpoint` with `num_threads` threads
+///
+/// Pending units are split into contiguous groups, one per thread. Each thread builds its
+/// scratch state with `init` and processes its units with `process`, which must call
+/// `on_batch_complete` on the processor. Units are marked as done once `process` succeeds.
+/// After the first error no new units are started.
+pub(crate) fn run_resumable<P, S, I, F>(
+    processor: &P,
+    num_threads: usize,
+    num_units: usize,
+    checkpoint: &mut dyn CheckpointStore,
+    init: I,
+    process: F,
+) -> Result<()>
+where
+    P: ParallelProcessor,
+    I: Fn() -> S + Sync,
+    F: Fn(&mut P, &mut S, usize) -> Result<()> + Sync,
+{
+    let pending: Vec<usize> = (0..num_units)
+        .filter(|&unit| !checkpoint.is_done(unit))
+        .collect();
+    if pending.is_empty() {
+        return Ok(());
+    }
+
+    let num_threads = if num_threads == 0 {
+        num_cpus::get()
+    } else {
+        num_threads.min(num_cpus::get())
+    };
+    let units_per_thread = pending.len().div_ceil(num_threads);
+
+    let checkpoint = Mutex::new(checkpoint);
+    let failed = AtomicBool::new(false);
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = pending
+            .chunks(units_per_thread)
+            .enumerate()
+            .map(|(tid, units)| {
+                let mut proc = processor.clone();
+                proc.set_tid(tid);
+                let (checkpoint, failed, init, process) = (&checkpoint, &failed, &init, &process);
+                scope.spawn(move || -> Result<()> {
+                    let mut state = init();
+                    for &unit in units {
+                        if failed.load(Ordering::Relaxed) {
+                            return Ok(());
+                        }
+                        let outcome = process(&mut proc, &mut state, unit).and_then(|()| {
+                            checkpoint
+                                .lock()
+                                .expect("checkpoint lock poisoned")
+                                .mark_done(unit)
+                        });
+                        if outcome.is_err() {
+                            failed.store(true, Ordering::Relaxed);
+                            return outcome;
+                        }
+                    }
+                    proc.on_thread_complete()
+                })
+            })
+            .collect();
+
+        let mut result = Ok(());
+        for handle in handles {
+            let outcome = handle.join().unwrap();
+            if result.is_ok() {
+                result = outcome;
+            }
+        }
+        result
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use std::sync::Arc;
+    use std::sync::atomic::AtomicUsize;
+
+    use super::*;
+    use crate::{BinseqRecord, SequencingRecordBuilder, bq, vbq};
+
+    /// Collects record indices and commits them at the end of each batch
+    #[derive(Clone, Default)]
+    struct CommitProcessor {
+        local: Vec<u64>,
+        committed: Arc<Mutex<Vec<u64>>>,
+        batches: Arc<AtomicUsize>,
+        fail_after: Option<usize>,
+    }
+    impl ParallelProcessor for CommitProcessor {
+        fn process_record<R: BinseqRecord>(&mut self, record: R) -> Result<()> {
+            if self
+                .fail_after
+                .is_some_and(|n| self.batches.load(Ordering::Relaxed) >= n)
+            {
+                return Err(std::io::Error::other("preempted").into());
+            }
+            self.local.push(record.index());
+            Ok(())
+        }
+
+        fn on_batch_complete(&mut self) -> Result<()> {
+            self.committed.lock().unwrap().append(&mut self.local);
+            self.batches.fetch_add(1, Ordering::Relaxed);
+            Ok(())
+        }
+    }
+
+    /// Interrupts a scan after a few units, resumes it, and checks each record was seen once
+    fn check_resume<F>(name: &str, num_records: usize, scan: F) -> Result<()>
+    where
+        F: Fn(CommitProcessor, &mut FileCheckpoint) -> Result<()>,
+    {
+        let path: PathBuf = std::env::temp_dir().join(format!("binseq_test_{name}.ckpt"));
+        let _ = std::fs::remove_file(&path);
+
+        let first = CommitProcessor {
+            fail_after: Some(3),
+            ..Default::default()
+        };
+        let mut checkpoint = FileCheckpoint::open(&path)?;
+        assert!(scan(first.clone(), &mut checkpoint).is_err());
+        let done = checkpoint.num_done();
+        assert_eq!(done, first.batches.load(Ordering::Relaxed));
+        assert!(done >= 3);
+        drop(checkpoint);
+
+        // Resume from the checkpoint file, committing into the same list
+        let second = CommitProcessor {
+            committed: Arc::clone(&first.committed),
+            ..Default::default()
+        };
+        let mut checkpoint = FileCheckpoint::open(&path)?;
+        scan(second.clone(), &mut checkpoint)?;
+        assert!(second.batches.load(Ordering::Relaxed) > 0);
+        assert_eq!(
+            checkpoint.num_done(),
+            done + second.batches.load(Ordering::Relaxed)
+        );
+
+        let mut indices = first.committed.lock().unwrap().clone();
+        indices.sort_unstable();
+        assert_eq!(indices, (0..num_records as u64).collect::<Vec<_>>());
+
+        // A completed scan has nothing left to do
+        let third = CommitProcessor::default();
+        scan(third.clone(), &mut checkpoint)?;
+        assert_eq!(third.batches.load(Ordering::Relaxed), 0);
+
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_vbq_resumable_scan() -> Result<()> {
+        let path = std::env::temp_dir().join("binseq_test_checkpoint.vbq");
+        let header = vbq::FileHeaderBuilder::new().block(1024).build();
+        let mut writer = vbq::WriterBuilder::default()
+            .header(header)
+            .build(File::create(&path)?)?;
+        for idx in 0..2000 {
+            let seq = b"ACGTTGCA".repeat(1 + idx % 8);
+            writer.push(SequencingRecordBuilder::default().s_seq(&seq).build()?)?;
+        }
+        writer.finish()?;
+        drop(writer);
+
+        check_resume("checkpoint_vbq", 2000, |proc, checkpoint| {
+            vbq::MmapReader::new(&path)?.process_parallel_resumable(proc, 2, checkpoint)
+        })?;
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_bq_resumable_scan() -> Result<()> {
+        let header = bq::FileHeaderBuilder::new().slen(16).build()?;
+        let mut writer = bq::WriterBuilder::default()
+            .header(header)
+            .build(Vec::new())?;
+        for _ in 0..8000 {
+            let record = SequencingRecordBuilder::default()
+                .s_seq(b"ACGTACGTTTGGCCAA")
+                .build()?;
+            writer.push(record)?;
+        }
+        let bytes: Arc<[u8]> = writer.into_inner().into();
+
+        check_resume("checkpoint_bq", 8000, |proc, checkpoint| {
+            bq::MmapReader::from_arc_bytes(Arc::clone(&bytes))?
+                .process_parallel_resumable(proc, 2, checkpoint)
+        })
+    }
+
+    #[test]
+    fn test_file_checkpoint_persists() -> Result<()> {
+        let path = std::env::temp_dir().join("binseq_test_file_checkpoint.ckpt");
+        let _ = std::fs::remove_file(&path);
+
+        let mut checkpoint = FileCheckpoint::open(&path)?;
+        assert!(!checkpoint.is_done(0));
+        for unit in [0, 3, 17, 100] {
+            checkpoint.mark_done(unit)?;
+        }
+        drop(checkpoint);
+
+        let checkpoint = FileCheckpoint::open(&path)?;
+        assert_eq!(checkpoint.num_done(), 4);
+        for unit in 0..128 {
+            assert_eq!(checkpoint.is_done(unit), [0, 3, 17, 100].contains(&unit));
+        }
+        assert!(!checkpoint.is_done(10_000));
+
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+}
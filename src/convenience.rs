@@ -0,0 +1,212 @@
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+use crate::{BinseqReader, BitSize, Policy, Result, bq, error::WriteError, vbq};
+
+/// Options for [`create_bq`] and [`create_vbq`]
+///
+/// A plain struct covering the common header settings, so files can be created without
+/// going through the header and writer builders. Settings that don't apply to a format
+/// are ignored (BQ files store neither quality scores nor headers and are not compressed).
+///
+/// # Examples
+///
+/// ```rust
+/// use binseq::WriterOpts;
+///
+/// let opts = WriterOpts {
+///     paired: true,
+///     qual: true,
+///     ..Default::default()
+/// };
+/// assert!(opts.compressed);
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct WriterOpts {
+    /// Whether records are paired
+    pub paired: bool,
+
+    /// Whether quality scores are stored (VBQ only)
+    pub qual: bool,
+
+    /// Whether sequence headers are stored (VBQ only)
+    pub headers: bool,
+
+    /// Whether a flag is stored with each record
+    pub flags: bool,
+
+    /// Whether blocks are compressed (VBQ only)
+    pub compressed: bool,
+
+    /// Bits per nucleotide of the encoding
+    pub bitsize: BitSize,
+
+    /// Policy for handling invalid nucleotides
+    pub policy: Policy,
+
+    /// Virtual block size in bytes (VBQ only, `None` uses the default)
+    pub block_size: Option<usize>,
+
+    /// Length of the extended sequence (BQ only, required if paired)
+    pub xlen: u32,
+}
+impl Default for WriterOpts {
+    fn default() -> Self {
+        Self {
+            paired: false,
+            qual: false,
+            headers: false,
+            flags: false,
+            compressed: true,
+            bitsize: BitSize::Two,
+            policy: Policy::default(),
+            block_size: None,
+            xlen: 0,
+        }
+    }
+}
+
+/// Opens a BINSEQ file of any variant for reading
+///
+/// Equivalent to [`BinseqReader::new`].
+///
+/// # Examples
+///
+/// ```rust
+/// let reader = binseq::open("./data/subset.vbq").unwrap();
+/// assert!(reader.num_records().unwrap() > 0);
+/// ```
+pub fn open<P: AsRef<Path>>(path: P) -> Result<BinseqReader> {
+    BinseqReader::new(path)
+}
+
+/// Creates a buffered BQ writer for records with primary sequences of `slen` bases
+///
+/// Paired files also require [`WriterOpts::xlen`].
+///
+/// # Examples
+///
+/// ```rust
+/// use binseq::{SequencingRecordBuilder, WriterOpts};
+///
+/// let path = std::env::temp_dir().join("binseq_doctest_create.bq");
+/// let mut writer = binseq::create_bq(&path, 8, WriterOpts::default()).unwrap();
+/// let record = SequencingRecordBuilder::default().s_seq(b"ACGTACGT").build().unwrap();
+/// writer.push(record).unwrap();
+/// # drop(writer);
+/// # std::fs::remove_file(&path).unwrap();
+/// ```
+pub fn create_bq<P: AsRef<Path>>(
+    path: P,
+    slen: u32,
+    opts: WriterOpts,
+) -> Result<bq::Writer<BufWriter<File>>> {
+    if opts.paired && opts.xlen == 0 {
+        return Err(WriteError::MissingSequenceLength {
+            exp_primary: true,
+            exp_extended: true,
+            obs_primary: true,
+            obs_extended: false,
+        }
+        .into());
+    }
+    let header = bq::FileHeaderBuilder::new()
+        .slen(slen)
+        .xlen(if opts.paired { opts.xlen } else { 0 })
+        .bitsize(opts.bitsize)
+        .flags(opts.flags)
+        .build()?;
+    bq::WriterBuilder::default()
+        .header(header)
+        .policy(opts.policy)
+        .build(BufWriter::new(File::create(path)?))
+}
+
+/// Creates a buffered VBQ writer
+///
+/// # Examples
+///
+/// ```rust
+/// use binseq::{SequencingRecordBuilder, WriterOpts};
+///
+/// let path = std::env::temp_dir().join("binseq_doctest_create.vbq");
+/// let mut writer = binseq::create_vbq(&path, WriterOpts::default()).unwrap();
+/// let record = SequencingRecordBuilder::default().s_seq(b"ACGTACGT").build().unwrap();
+/// writer.push(record).unwrap();
+/// writer.finish().unwrap();
+/// # drop(writer);
+/// # std::fs::remove_file(&path).unwrap();
+/// ```
+pub fn create_vbq<P: AsRef<Path>>(
+    path: P,
+    opts: WriterOpts,
+) -> Result<vbq::Writer<BufWriter<File>>> {
+    let mut header = vbq::FileHeaderBuilder::new()
+        .paired(opts.paired)
+        .qual(opts.qual)
+        .headers(opts.headers)
+        .flags(opts.flags)
+        .compressed(opts.compressed)
+        .bitsize(opts.bitsize);
+    if let Some(block_size) = opts.block_size {
+        header = header.block(block_size as u64);
+    }
+    vbq::WriterBuilder::default()
+        .header(header.build())
+        .policy(opts.policy)
+        .build(BufWriter::new(File::create(path)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Error, SequencingRecordBuilder};
+
+    #[test]
+    fn test_create_and_open_roundtrip() -> Result<()> {
+        let bq_path = std::env::temp_dir().join("binseq_test_convenience.bq");
+        let vbq_path = std::env::temp_dir().join("binseq_test_convenience.vbq");
+        let opts = WriterOpts {
+            paired: true,
+            xlen: 4,
+            ..Default::default()
+        };
+
+        let mut writer = create_bq(&bq_path, 8, opts)?;
+        let mut vwriter = create_vbq(&vbq_path, opts)?;
+        for _ in 0..10 {
+            let record = SequencingRecordBuilder::default()
+                .s_seq(b"ACGTACGT")
+                .x_seq(b"TTTT")
+                .build()?;
+            writer.push(record)?;
+            vwriter.push(record)?;
+        }
+        drop(writer);
+        vwriter.finish()?;
+        drop(vwriter);
+
+        for path in [&bq_path, &vbq_path] {
+            let reader = open(path)?;
+            assert_eq!(reader.num_records()?, 10);
+            assert!(reader.is_paired());
+            std::fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_create_bq_paired_requires_xlen() {
+        let path = std::env::temp_dir().join("binseq_test_convenience_xlen.bq");
+        let opts = WriterOpts {
+            paired: true,
+            ..Default::default()
+        };
+        assert!(matches!(
+            create_bq(&path, 8, opts),
+            Err(Error::WriteError(WriteError::MissingSequenceLength { .. }))
+        ));
+        assert!(!path.exists());
+    }
+}
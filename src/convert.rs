@@ -0,0 +1,704 @@
+//! Conversion of FASTQ files to BINSEQ with read filtering
+//!
+//! [`fastq_quality_filter_to_vbq`] and [`fastq_quality_filter_to_vbq_paired`] drop reads
+//! whose mean Phred quality is below a threshold while encoding FASTQ into VBQ. Reads that
+//! pass the filter are still subject to the invalid nucleotide [`Policy`] of the writer, so
+//! a read can pass the filter and then be skipped (e.g. with [`Policy::IgnoreSequence`]).
+//!
+//! [`interleaved_fastq_to_vbq`] encodes interleaved FASTQ, with the mates of each pair in
+//! consecutive records, into a paired VBQ file.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use binseq::convert::{VbqConvertOptions, fastq_quality_filter_to_vbq};
+//!
+//! let stats = fastq_quality_filter_to_vbq(
+//!     "input.fastq.gz",
+//!     "output.vbq",
+//!     30.0,
+//!     VbqConvertOptions::default().threads(4),
+//! )?;
+//! eprintln!("kept {} of {} reads", stats.passed, stats.total);
+//! # Ok::<(), binseq::Error>(())
+//! ```
+
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+    sync::Arc,
+};
+
+use paraseq::{
+    Record, fastx,
+    prelude::{IntoProcessError, PairedParallelProcessor, ParallelProcessor, ParallelReader},
+};
+use parking_lot::Mutex;
+
+use crate::{
+    BinseqWriter, BinseqWriterBuilder, IntoBinseqError, Policy, Result, SequencingRecord,
+    SequencingRecordBuilder, error::FastxEncodingError, write::Format,
+};
+
+/// Offset of Phred+33 encoded quality scores
+const PHRED_OFFSET: u8 = 33;
+
+type BoxedWrite = Box<dyn Write + Send>;
+
+/// Configuration of the VBQ file written by a conversion
+///
+/// By default quality scores and headers are kept, blocks are compressed and all available
+/// cores are used.
+#[derive(Debug, Clone, Copy)]
+pub struct VbqConvertOptions {
+    /// Whether quality scores are stored
+    quality: bool,
+
+    /// Whether headers are stored
+    headers: bool,
+
+    /// Whether blocks are compressed
+    compression: bool,
+
+    /// Size of the blocks (uses the VBQ default if unset)
+    block_size: Option<usize>,
+
+    /// Policy for handling invalid nucleotides
+    policy: Policy,
+
+    /// Number of threads (0 uses all available cores)
+    threads: usize,
+}
+impl Default for VbqConvertOptions {
+    fn default() -> Self {
+        Self {
+            quality: true,
+            headers: true,
+            compression: true,
+            block_size: None,
+            policy: Policy::default(),
+            threads: 0,
+        }
+    }
+}
+impl VbqConvertOptions {
+    /// Sets whether quality scores are stored
+    #[must_use]
+    pub fn quality(mut self, quality: bool) -> Self {
+        self.quality = quality;
+        self
+    }
+
+    /// Sets whether headers are stored
+    #[must_use]
+    pub fn headers(mut self, headers: bool) -> Self {
+        self.headers = headers;
+        self
+    }
+
+    /// Sets whether blocks are compressed
+    #[must_use]
+    pub fn compression(mut self, compression: bool) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Sets the size of the blocks
+    #[must_use]
+    pub fn block_size(mut self, block_size: usize) -> Self {
+        self.block_size = Some(block_size);
+        self
+    }
+
+    /// Sets the policy for handling invalid nucleotides
+    #[must_use]
+    pub fn policy(mut self, policy: Policy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Sets the number of threads (0 uses all available cores)
+    #[must_use]
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads;
+        self
+    }
+
+    /// Creates the VBQ writer over `output`
+    fn build_writer(self, output: &Path, paired: bool) -> Result<BinseqWriter<BoxedWrite>> {
+        let mut builder = BinseqWriterBuilder::new(Format::Vbq)
+            .paired(paired)
+            .quality(self.quality)
+            .headers(self.headers)
+            .compression(self.compression)
+            .policy(self.policy);
+        if let Some(block_size) = self.block_size {
+            builder = builder.block_size(block_size);
+        }
+        let output: BoxedWrite = Box::new(BufWriter::new(File::create(output)?));
+        builder.build(output)
+    }
+}
+
+/// Counts of reads (or pairs) seen by a quality filter
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FilterStats {
+    /// Reads passing the filter and written to the output
+    pub passed: u64,
+
+    /// Reads below the minimum mean quality
+    pub failed: u64,
+
+    /// Reads passing the filter but skipped by the invalid nucleotide policy
+    pub skipped: u64,
+
+    /// All reads in the input
+    pub total: u64,
+}
+impl FilterStats {
+    /// Adds the counts of `other` to these counts
+    fn merge(&mut self, other: &Self) {
+        self.passed += other.passed;
+        self.failed += other.failed;
+        self.skipped += other.skipped;
+        self.total += other.total;
+    }
+}
+
+/// Encodes the reads of a FASTQ file with a mean Phred quality of at least
+/// `min_mean_quality` into a VBQ file
+///
+/// Quality scores are expected in Phred+33 encoding. Reads without quality scores (FASTA
+/// input) cannot be filtered and are reported as `FastxEncodingError::MissingQualityScores`.
+pub fn fastq_quality_filter_to_vbq(
+    input: impl AsRef<Path>,
+    output: impl AsRef<Path>,
+    min_mean_quality: f32,
+    options: VbqConvertOptions,
+) -> Result<FilterStats> {
+    let reader =
+        fastx::Reader::from_path(input.as_ref()).map_err(IntoBinseqError::into_binseq_error)?;
+    let writer = options.build_writer(output.as_ref(), false)?;
+    let mut filter = QualityFilter::new(writer, min_mean_quality)?;
+    reader
+        .process_parallel(&mut filter, options.threads)
+        .map_err(IntoBinseqError::into_binseq_error)?;
+    filter.finish()
+}
+
+/// Encodes the read pairs of two FASTQ files into a paired VBQ file, keeping pairs where
+/// both mates have a mean Phred quality of at least `min_mean_quality`
+///
+/// See [`fastq_quality_filter_to_vbq`]. The counts of the returned [`FilterStats`] are
+/// numbers of pairs.
+pub fn fastq_quality_filter_to_vbq_paired(
+    r1: impl AsRef<Path>,
+    r2: impl AsRef<Path>,
+    output: impl AsRef<Path>,
+    min_mean_quality: f32,
+    options: VbqConvertOptions,
+) -> Result<FilterStats> {
+    let r1 = fastx::Reader::from_path(r1.as_ref()).map_err(IntoBinseqError::into_binseq_error)?;
+    let r2 = fastx::Reader::from_path(r2.as_ref()).map_err(IntoBinseqError::into_binseq_error)?;
+    let writer = options.build_writer(output.as_ref(), true)?;
+    let mut filter = QualityFilter::new(writer, min_mean_quality)?;
+    r1.process_parallel_paired(r2, &mut filter, options.threads)
+        .map_err(IntoBinseqError::into_binseq_error)?;
+    filter.finish()
+}
+
+/// Counts of read pairs seen by [`interleaved_fastq_to_vbq`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct InterleavedStats {
+    /// Pairs written to the output
+    pub written: u64,
+
+    /// Pairs skipped by the invalid nucleotide policy of the writer
+    pub skipped: u64,
+
+    /// All pairs in the input
+    pub total: u64,
+}
+
+/// Encodes an interleaved FASTQ file, with the mates of each pair in consecutive records,
+/// into a paired VBQ file
+///
+/// Mates must have the same read name (the header up to the first whitespace), ignoring a
+/// trailing `/1` or `/2`. Headers are stored as-is. Records are read sequentially, so the
+/// `threads` option does not apply.
+///
+/// # Errors
+///
+/// * `FastxEncodingError::MateNameMismatch` - If two consecutive records are not mates
+/// * `FastxEncodingError::UnpairedInterleavedRecord` - If the file ends with a read without
+///   its mate
+pub fn interleaved_fastq_to_vbq(
+    input: impl AsRef<Path>,
+    output: impl AsRef<Path>,
+    options: VbqConvertOptions,
+) -> Result<InterleavedStats> {
+    let mut reader =
+        fastx::Reader::from_path(input.as_ref()).map_err(IntoBinseqError::into_binseq_error)?;
+    let mut writer = options.build_writer(output.as_ref(), true)?;
+    let mut rset = reader.new_record_set();
+    let mut stats = InterleavedStats::default();
+
+    // first mate of the current pair, kept across record sets
+    let mut mate = PendingMate::default();
+    let mut record_idx = 0u64;
+    while rset
+        .fill(&mut reader)
+        .map_err(IntoBinseqError::into_binseq_error)?
+    {
+        for record in rset.iter() {
+            let record = record.map_err(IntoBinseqError::into_binseq_error)?;
+            record_idx += 1;
+            if !mate.pending {
+                mate.set(&record);
+                continue;
+            }
+            mate.pending = false;
+            if read_name(&mate.id) != read_name(record.id()) {
+                return Err(FastxEncodingError::MateNameMismatch {
+                    record: record_idx - 2,
+                    r1: String::from_utf8_lossy(&mate.id).into_owned(),
+                    r2: String::from_utf8_lossy(record.id()).into_owned(),
+                }
+                .into());
+            }
+            let xseq = record.seq();
+            let pair = SequencingRecordBuilder::default()
+                .s_header(&mate.id)
+                .s_seq(&mate.seq)
+                .opt_s_qual(mate.has_qual.then_some(mate.qual.as_slice()))
+                .x_header(record.id())
+                .x_seq(&xseq)
+                .opt_x_qual(record.qual())
+                .build()?;
+            stats.total += 1;
+            if writer.push(pair)? {
+                stats.written += 1;
+            } else {
+                stats.skipped += 1;
+            }
+        }
+    }
+    if mate.pending {
+        return Err(FastxEncodingError::UnpairedInterleavedRecord(record_idx - 1).into());
+    }
+    writer.finish()?;
+    Ok(stats)
+}
+
+/// Copy of the first mate of a pair, reusing its buffers between pairs
+#[derive(Default)]
+struct PendingMate {
+    /// Whether a first mate is waiting for its mate
+    pending: bool,
+    id: Vec<u8>,
+    seq: Vec<u8>,
+    qual: Vec<u8>,
+    has_qual: bool,
+}
+impl PendingMate {
+    fn set<Rf: Record>(&mut self, record: &Rf) {
+        self.pending = true;
+        self.id.clear();
+        self.id.extend_from_slice(record.id());
+        self.seq.clear();
+        self.seq.extend_from_slice(&record.seq());
+        self.qual.clear();
+        self.has_qual = record.qual().is_some();
+        self.qual
+            .extend_from_slice(record.qual().unwrap_or_default());
+    }
+}
+
+/// Returns the read name of a FASTQ header without a `/1` or `/2` mate suffix
+fn read_name(header: &[u8]) -> &[u8] {
+    let name = header
+        .split(u8::is_ascii_whitespace)
+        .next()
+        .unwrap_or_default();
+    name.strip_suffix(b"/1")
+        .or_else(|| name.strip_suffix(b"/2"))
+        .unwrap_or(name)
+}
+
+/// Returns the mean Phred quality of Phred+33 encoded scores (0 for empty scores)
+fn mean_quality(qual: &[u8]) -> f32 {
+    if qual.is_empty() {
+        return 0.0;
+    }
+    let sum: u64 = qual
+        .iter()
+        .map(|&q| u64::from(q.saturating_sub(PHRED_OFFSET)))
+        .sum();
+    sum as f32 / qual.len() as f32
+}
+
+/// Parallel processor writing the records passing the quality filter
+#[derive(Clone)]
+struct QualityFilter {
+    /// Global writer (shared across threads)
+    writer: Arc<Mutex<BinseqWriter<BoxedWrite>>>,
+
+    /// Thread-local writer buffer
+    thread_writer: BinseqWriter<Vec<u8>>,
+
+    /// Global counts (shared across threads)
+    stats: Arc<Mutex<FilterStats>>,
+
+    /// Thread-local counts of the current batch
+    thread_stats: FilterStats,
+
+    /// Minimum mean quality of a passing read
+    min_mean_quality: f32,
+}
+impl QualityFilter {
+    fn new(writer: BinseqWriter<BoxedWrite>, min_mean_quality: f32) -> Result<Self> {
+        let thread_writer = writer.new_headless_buffer()?;
+        Ok(Self {
+            writer: Arc::new(Mutex::new(writer)),
+            thread_writer,
+            stats: Arc::default(),
+            thread_stats: FilterStats::default(),
+            min_mean_quality,
+        })
+    }
+
+    /// Returns whether scores pass the filter
+    fn passes(&self, qual: Option<&[u8]>) -> Result<bool> {
+        let qual = qual.ok_or(FastxEncodingError::MissingQualityScores)?;
+        Ok(mean_quality(qual) >= self.min_mean_quality)
+    }
+
+    /// Counts a read and writes it to the thread-local buffer if it passed the filter
+    fn push(&mut self, passed: bool, record: SequencingRecord) -> Result<()> {
+        self.thread_stats.total += 1;
+        if !passed {
+            self.thread_stats.failed += 1;
+        } else if self.thread_writer.push(record)? {
+            self.thread_stats.passed += 1;
+        } else {
+            self.thread_stats.skipped += 1;
+        }
+        Ok(())
+    }
+
+    /// Moves the thread-local buffer and counts to the global writer and counts
+    fn flush_batch(&mut self) -> Result<()> {
+        self.writer.lock().ingest(&mut self.thread_writer)?;
+        self.stats.lock().merge(&self.thread_stats);
+        self.thread_stats = FilterStats::default();
+        Ok(())
+    }
+
+    /// Finishes the global writer and returns the final counts
+    fn finish(&mut self) -> Result<FilterStats> {
+        self.writer.lock().finish()?;
+        Ok(*self.stats.lock())
+    }
+}
+
+impl<Rf: Record> ParallelProcessor<Rf> for QualityFilter {
+    fn process_record(&mut self, record: Rf) -> paraseq::Result<()> {
+        let passed = self
+            .passes(record.qual())
+            .map_err(IntoProcessError::into_process_error)?;
+        let seq = record.seq();
+        let seq_record = SequencingRecordBuilder::default()
+            .s_header(record.id())
+            .s_seq(&seq)
+            .opt_s_qual(record.qual())
+            .build()
+            .map_err(IntoProcessError::into_process_error)?;
+        self.push(passed, seq_record)
+            .map_err(IntoProcessError::into_process_error)
+    }
+
+    fn on_batch_complete(&mut self) -> paraseq::Result<()> {
+        self.flush_batch()
+            .map_err(IntoProcessError::into_process_error)
+    }
+}
+
+impl<Rf: Record> PairedParallelProcessor<Rf> for QualityFilter {
+    fn process_record_pair(&mut self, record1: Rf, record2: Rf) -> paraseq::Result<()> {
+        let passed = self
+            .passes(record1.qual())
+            .and_then(|p1| Ok(p1 && self.passes(record2.qual())?))
+            .map_err(IntoProcessError::into_process_error)?;
+        let sseq = record1.seq();
+        let xseq = record2.seq();
+        let seq_record = SequencingRecordBuilder::default()
+            .s_header(record1.id())
+            .s_seq(&sseq)
+            .opt_s_qual(record1.qual())
+            .x_header(record2.id())
+            .x_seq(&xseq)
+            .opt_x_qual(record2.qual())
+            .build()
+            .map_err(IntoProcessError::into_process_error)?;
+        self.push(passed, seq_record)
+            .map_err(IntoProcessError::into_process_error)
+    }
+
+    fn on_batch_complete(&mut self) -> paraseq::Result<()> {
+        self.flush_batch()
+            .map_err(IntoProcessError::into_process_error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::{BinseqRecord, vbq};
+
+    /// Writes a FASTQ file of reads with the given sequences and quality strings
+    fn write_fastq(name: &str, reads: &[(&[u8], &[u8])]) -> Result<PathBuf> {
+        let path = std::env::temp_dir().join(format!("binseq_test_convert_{name}.fastq"));
+        let mut file = BufWriter::new(File::create(&path)?);
+        for (idx, (seq, qual)) in reads.iter().enumerate() {
+            writeln!(file, "@read{idx}")?;
+            file.write_all(seq)?;
+            writeln!(file, "\n+")?;
+            file.write_all(qual)?;
+            writeln!(file)?;
+        }
+        file.flush()?;
+        Ok(path)
+    }
+
+    /// Decodes the primary sequences of a VBQ file
+    fn read_sequences(path: &Path) -> Result<Vec<Vec<u8>>> {
+        let mut reader = vbq::MmapReader::new(path)?;
+        let mut block = reader.new_block();
+        let mut sequences = Vec::new();
+        while reader.read_block_into(&mut block)? {
+            for record in block.iter() {
+                sequences.push(record.decode_s_alloc()?);
+            }
+        }
+        sequences.sort();
+        Ok(sequences)
+    }
+
+    #[test]
+    fn test_mean_quality() {
+        assert!((mean_quality(b"IIII") - 40.0).abs() < f32::EPSILON);
+        assert!((mean_quality(b"+5?I") - 25.0).abs() < f32::EPSILON);
+        assert!(mean_quality(b"").abs() < f32::EPSILON);
+    }
+
+    #[test]
+    fn test_quality_filter_single() -> Result<()> {
+        // Mean qualities: 40, 10, 30, 29.75, 35
+        let input = write_fastq(
+            "single",
+            &[
+                (b"AAAA", b"IIII"),
+                (b"CCCC", b"++++"),
+                (b"GGGG", b"????"),
+                (b"TTTT", b"???>"),
+                (b"ACGT", b"DDDD"),
+            ],
+        )?;
+        let output = std::env::temp_dir().join("binseq_test_convert_single.vbq");
+        let stats =
+            fastq_quality_filter_to_vbq(&input, &output, 30.0, VbqConvertOptions::default())?;
+        assert_eq!(
+            stats,
+            FilterStats {
+                passed: 3,
+                failed: 2,
+                skipped: 0,
+                total: 5
+            }
+        );
+        assert_eq!(
+            read_sequences(&output)?,
+            [b"AAAA".to_vec(), b"ACGT".to_vec(), b"GGGG".to_vec()]
+        );
+
+        std::fs::remove_file(input)?;
+        std::fs::remove_file(output)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_quality_filter_applies_policy() -> Result<()> {
+        let input = write_fastq("policy", &[(b"ACNT", b"IIII"), (b"ACGT", b"IIII")])?;
+        let output = std::env::temp_dir().join("binseq_test_convert_policy.vbq");
+        let options = VbqConvertOptions::default().policy(Policy::IgnoreSequence);
+        let stats = fastq_quality_filter_to_vbq(&input, &output, 30.0, options)?;
+        assert_eq!((stats.passed, stats.skipped, stats.total), (1, 1, 2));
+        assert_eq!(read_sequences(&output)?, [b"ACGT".to_vec()]);
+
+        std::fs::remove_file(input)?;
+        std::fs::remove_file(output)?;
+        Ok(())
+    }
+
+    /// Writes an interleaved FASTQ file of `(header, sequence, quality)` reads
+    fn write_interleaved(name: &str, reads: &[(&str, &[u8], &[u8])]) -> Result<PathBuf> {
+        let path = std::env::temp_dir().join(format!("binseq_test_convert_{name}.fastq"));
+        let mut file = BufWriter::new(File::create(&path)?);
+        for (header, seq, qual) in reads {
+            writeln!(file, "@{header}")?;
+            file.write_all(seq)?;
+            writeln!(file, "\n+")?;
+            file.write_all(qual)?;
+            writeln!(file)?;
+        }
+        file.flush()?;
+        Ok(path)
+    }
+
+    /// Reads the pairs of a VBQ file as (header, sequence, quality) triples of both mates
+    fn read_pairs(path: &Path) -> Result<Vec<[(Vec<u8>, Vec<u8>, Vec<u8>); 2]>> {
+        let mut reader = vbq::MmapReader::new(path)?;
+        let mut block = reader.new_block();
+        let mut pairs = Vec::new();
+        while reader.read_block_into(&mut block)? {
+            for record in block.iter() {
+                pairs.push([
+                    (
+                        record.sheader().to_vec(),
+                        record.decode_s_alloc()?,
+                        record.squal().to_vec(),
+                    ),
+                    (
+                        record.xheader().to_vec(),
+                        record.decode_x_alloc()?,
+                        record.xqual().to_vec(),
+                    ),
+                ]);
+            }
+        }
+        Ok(pairs)
+    }
+
+    #[test]
+    fn test_interleaved_round_trip() -> Result<()> {
+        use std::io::Read;
+
+        use crate::{BinseqReader, TextAdapter, TextFormat};
+
+        let reads: [(&str, &[u8], &[u8]); 4] = [
+            ("read0/1", b"ACGTACGT", b"IIIIIIII"),
+            ("read0/2", b"TTGGCC", b"#####I"),
+            ("read1 lane=1", b"GGGGAAAA", b"IIII####"),
+            ("read1 lane=2", b"CACACA", b"IIIIII"),
+        ];
+        let input = write_interleaved("interleaved", &reads)?;
+        let output = std::env::temp_dir().join("binseq_test_convert_interleaved.vbq");
+        let options = VbqConvertOptions::default().compression(false);
+        let stats = interleaved_fastq_to_vbq(&input, &output, options)?;
+        assert_eq!((stats.written, stats.skipped, stats.total), (2, 0, 2));
+        let pairs = read_pairs(&output)?;
+        for (pair, mates) in pairs.iter().zip(reads.chunks(2)) {
+            for (stored, (header, seq, qual)) in pair.iter().zip(mates) {
+                assert_eq!(
+                    stored,
+                    &(header.as_bytes().to_vec(), seq.to_vec(), qual.to_vec())
+                );
+            }
+        }
+
+        // export with mate suffixes and import again
+        let mut text = String::new();
+        TextAdapter::new(BinseqReader::new(&output)?, TextFormat::Fastq)
+            .mate_suffixes(true)
+            .read_to_string(&mut text)?;
+        assert_eq!(
+            text,
+            "@read0/1\nACGTACGT\n+\nIIIIIIII\n@read0/2\nTTGGCC\n+\n#####I\n\
+             @read1/1 lane=1\nGGGGAAAA\n+\nIIII####\n@read1/2 lane=2\nCACACA\n+\nIIIIII\n"
+        );
+        std::fs::write(&input, text)?;
+        interleaved_fastq_to_vbq(&input, &output, options)?;
+        let reimported = read_pairs(&output)?;
+        for (old, new) in pairs.iter().zip(&reimported) {
+            for (old, new) in old.iter().zip(new) {
+                assert_eq!((&old.1, &old.2), (&new.1, &new.2));
+            }
+        }
+        assert_eq!(reimported[1][0].0, b"read1/1 lane=1");
+
+        std::fs::remove_file(input)?;
+        std::fs::remove_file(output)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_interleaved_errors() -> Result<()> {
+        let output = std::env::temp_dir().join("binseq_test_convert_interleaved_err.vbq");
+        let odd = write_interleaved(
+            "interleaved_odd",
+            &[
+                ("a/1", b"ACGT", b"IIII"),
+                ("a/2", b"ACGT", b"IIII"),
+                ("b/1", b"ACGT", b"IIII"),
+            ],
+        )?;
+        assert!(matches!(
+            interleaved_fastq_to_vbq(&odd, &output, VbqConvertOptions::default()),
+            Err(crate::Error::FastxEncodingError(
+                FastxEncodingError::UnpairedInterleavedRecord(2)
+            ))
+        ));
+
+        let mismatch = write_interleaved(
+            "interleaved_mismatch",
+            &[
+                ("a/1", b"ACGT", b"IIII"),
+                ("a/2", b"ACGT", b"IIII"),
+                ("b/1", b"ACGT", b"IIII"),
+                ("c/2", b"ACGT", b"IIII"),
+            ],
+        )?;
+        assert!(matches!(
+            interleaved_fastq_to_vbq(&mismatch, &output, VbqConvertOptions::default()),
+            Err(crate::Error::FastxEncodingError(
+                FastxEncodingError::MateNameMismatch { record: 2, .. }
+            ))
+        ));
+
+        for path in [odd, mismatch, output] {
+            std::fs::remove_file(path).ok();
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_quality_filter_paired() -> Result<()> {
+        // Pairs pass only if both mates pass
+        let r1 = write_fastq(
+            "paired_r1",
+            &[(b"AAAA", b"IIII"), (b"CCCC", b"IIII"), (b"GGGG", b"++++")],
+        )?;
+        let r2 = write_fastq(
+            "paired_r2",
+            &[(b"TTTT", b"IIII"), (b"TTTT", b"++++"), (b"TTTT", b"IIII")],
+        )?;
+        let output = std::env::temp_dir().join("binseq_test_convert_paired.vbq");
+        let stats = fastq_quality_filter_to_vbq_paired(
+            &r1,
+            &r2,
+            &output,
+            30.0,
+            VbqConvertOptions::default(),
+        )?;
+        assert_eq!((stats.passed, stats.failed, stats.total), (1, 2, 3));
+        assert_eq!(read_sequences(&output)?, [b"AAAA".to_vec()]);
+
+        for path in [r1, r2, output] {
+            std::fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,392 @@
+//! Exact-sequence deduplication of BINSEQ files
+//!
+//! [`dedup`] copies the records of a file to a [`RecordWriter`], dropping every record whose
+//! sequence (or pair of sequences) was already written. BQ and VBQ records are keyed by their
+//! encoded words, so nothing is decoded to compare records. The unused bits of the last word
+//! of a sequence are masked off, so records only compare equal if their first `slen` bases
+//! are equal.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use binseq::prelude::*;
+//! use binseq::{DedupOptions, dedup};
+//!
+//! let reader = BinseqReader::new("input.vbq")?;
+//! let mut writer = binseq::create_vbq("dedup.vbq", WriterOpts::default())?;
+//! let stats = dedup(reader, &mut writer, DedupOptions::default())?;
+//! eprintln!("{} of {} records are duplicates", stats.duplicates, stats.seen);
+//! # Ok::<(), binseq::Error>(())
+//! ```
+
+use std::collections::HashSet;
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+use bitnuc::BitSize;
+
+use crate::{BinseqReader, BinseqRecord, RecordWriter, Result, SequencingRecord};
+
+/// Configuration of [`dedup`]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DedupOptions {
+    /// Number of leading bases of each sequence forming the key (all if `None`)
+    prefix_len: Option<usize>,
+
+    /// Store full keys instead of 128-bit hashes
+    exact: bool,
+}
+impl DedupOptions {
+    /// Only compares the first `prefix_len` bases of each sequence
+    ///
+    /// Records whose sequences share the prefix are duplicates regardless of the rest of
+    /// their sequences. For paired records the prefix applies to both mates.
+    #[must_use]
+    pub fn prefix_len(mut self, prefix_len: usize) -> Self {
+        self.prefix_len = Some(prefix_len);
+        self
+    }
+
+    /// Stores the full key of every unique record instead of its 128-bit hash
+    ///
+    /// By default only a 128-bit hash of each key is kept, bounding memory to 16 bytes per
+    /// unique record. Two distinct sequences sharing a hash would wrongly be reported as
+    /// duplicates, which is astronomically unlikely but possible; exact mode rules it out at
+    /// the cost of memory proportional to the total length of the unique sequences.
+    #[must_use]
+    pub fn exact(mut self, exact: bool) -> Self {
+        self.exact = exact;
+        self
+    }
+}
+
+/// Counts of records seen by [`dedup`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DedupStats {
+    /// All records in the input
+    pub seen: u64,
+
+    /// First occurrences, written to the output
+    pub unique: u64,
+
+    /// Repeated occurrences, dropped
+    pub duplicates: u64,
+
+    /// First occurrences skipped by the invalid nucleotide policy of the writer
+    pub skipped: u64,
+}
+
+/// Keys of the records written so far
+enum KeySet {
+    Hashed(HashSet<u128>),
+    Exact(HashSet<Box<[u64]>>),
+}
+impl KeySet {
+    /// Inserts `key`, returning `true` if it was not present
+    fn insert(&mut self, key: &[u64]) -> bool {
+        match self {
+            Self::Hashed(set) => set.insert(hash128(key)),
+            Self::Exact(set) => !set.contains(key) && set.insert(key.into()),
+        }
+    }
+}
+
+/// Deterministic 128-bit hash of a key, from two independently seeded 64-bit hashes
+fn hash128(key: &[u64]) -> u128 {
+    let hash = |seed: u64| {
+        let mut hasher = DefaultHasher::new();
+        seed.hash(&mut hasher);
+        key.hash(&mut hasher);
+        hasher.finish()
+    };
+    (u128::from(hash(0)) << 64) | u128::from(hash(1))
+}
+
+/// Appends the first `len` bases of an encoded sequence to `key`, masking the unused bits
+fn push_encoded(key: &mut Vec<u64>, bitsize: BitSize, ebuf: &[u64], len: usize) {
+    let bits = match bitsize {
+        BitSize::Two => 2,
+        BitSize::Four => 4,
+    };
+    let bases_per_word = 64 / bits;
+    let words = len.div_ceil(bases_per_word).min(ebuf.len());
+    key.extend_from_slice(&ebuf[..words]);
+    let tail = len % bases_per_word;
+    if tail > 0
+        && let Some(last) = key.last_mut()
+    {
+        *last &= (1 << (bits * tail)) - 1;
+    }
+}
+
+/// Appends decoded bases to `key`, eight per word
+fn push_decoded(key: &mut Vec<u64>, seq: &[u8]) {
+    key.extend(seq.chunks(8).map(|chunk| {
+        let mut word = [0; 8];
+        word[..chunk.len()].copy_from_slice(chunk);
+        u64::from_le_bytes(word)
+    }));
+}
+
+/// State of a deduplication pass
+struct Deduplicator<'w, W> {
+    writer: &'w mut W,
+    prefix_len: usize,
+    keys: KeySet,
+    key: Vec<u64>,
+    sbuf: Vec<u8>,
+    xbuf: Vec<u8>,
+    stats: DedupStats,
+}
+impl<W: RecordWriter> Deduplicator<'_, W> {
+    /// Builds the key of `record` into `self.key`
+    ///
+    /// The key starts with the (truncated) sequence lengths, so sequences differing only in
+    /// length never collide. `packed` records are keyed by their encoded words, others by
+    /// their decoded bases.
+    fn build_key<R: BinseqRecord>(&mut self, record: &R, packed: bool) -> Result<()> {
+        let slen = (record.slen() as usize).min(self.prefix_len);
+        let xlen = (record.xlen() as usize).min(self.prefix_len);
+        self.key.clear();
+        self.key.extend([slen as u64, xlen as u64]);
+        if packed {
+            push_encoded(&mut self.key, record.bitsize(), record.sbuf(), slen);
+            if xlen > 0 {
+                push_encoded(&mut self.key, record.bitsize(), record.xbuf(), xlen);
+            }
+        } else {
+            record.decode_pair(&mut self.sbuf, &mut self.xbuf)?;
+            push_decoded(&mut self.key, &self.sbuf[..slen]);
+            push_decoded(&mut self.key, &self.xbuf[..xlen]);
+        }
+        Ok(())
+    }
+
+    fn process<R: BinseqRecord>(&mut self, record: &R, packed: bool) -> Result<()> {
+        self.stats.seen += 1;
+        self.build_key(record, packed)?;
+        if !self.keys.insert(&self.key) {
+            self.stats.duplicates += 1;
+            return Ok(());
+        }
+        record.decode_pair(&mut self.sbuf, &mut self.xbuf)?;
+        let non_empty = |field: &[u8]| -> bool { !field.is_empty() };
+        let squal = record.squal();
+        let xqual = record.xqual();
+        let is_paired = record.is_paired();
+        let written = self.writer.push(SequencingRecord::new(
+            &self.sbuf,
+            non_empty(squal).then_some(squal),
+            Some(record.sheader()),
+            is_paired.then_some(self.xbuf.as_slice()),
+            (is_paired && non_empty(xqual)).then_some(xqual),
+            is_paired.then(|| record.xheader()),
+            record.flag(),
+        ))?;
+        if written {
+            self.stats.unique += 1;
+        } else {
+            self.stats.skipped += 1;
+        }
+        Ok(())
+    }
+}
+
+/// Copies the records of `reader` to `writer`, dropping records with repeated sequences
+///
+/// Records are read in file order and only the first occurrence of each sequence (or pair
+/// of sequences) is written, with its quality scores, headers and flag. Which fields are
+/// stored in the output depends on the configuration of `writer`.
+///
+/// See [`DedupOptions`] for prefix keys and exact comparisons. The writer is not finished.
+pub fn dedup<W: RecordWriter>(
+    reader: BinseqReader,
+    writer: &mut W,
+    opts: DedupOptions,
+) -> Result<DedupStats> {
+    let mut state = Deduplicator {
+        writer,
+        prefix_len: opts.prefix_len.unwrap_or(usize::MAX),
+        keys: if opts.exact {
+            KeySet::Exact(HashSet::new())
+        } else {
+            KeySet::Hashed(HashSet::new())
+        },
+        key: Vec::new(),
+        sbuf: Vec::new(),
+        xbuf: Vec::new(),
+        stats: DedupStats::default(),
+    };
+    match reader {
+        BinseqReader::Bq(reader) => {
+            for idx in 0..reader.num_records() {
+                state.process(&reader.get(idx)?, true)?;
+            }
+        }
+        BinseqReader::PairedBq(reader) => {
+            for idx in 0..reader.num_records() {
+                state.process(&reader.get(idx)?, true)?;
+            }
+        }
+        BinseqReader::Vbq(mut reader) => {
+            let mut block = reader.new_block();
+            while reader.read_block_into(&mut block)? {
+                for record in block.iter() {
+                    state.process(&record, true)?;
+                }
+            }
+        }
+        BinseqReader::Cbq(mut reader) => {
+            let blocks: Vec<_> = reader.index().iter_blocks().collect();
+            for range in blocks {
+                for record in reader.iter_block_records(range)? {
+                    state.process(&record, false)?;
+                }
+            }
+        }
+    }
+    Ok(state.stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, DatasetConfig, LengthDistribution, SyntheticRecord};
+    use crate::{bq, vbq};
+
+    /// Deduplicates `input` into a VBQ file shaped by `config` and returns its records
+    fn dedup_vbq(
+        input: Vec<u8>,
+        opts: DedupOptions,
+        config: &DatasetConfig,
+    ) -> Result<(DedupStats, Vec<SyntheticRecord>)> {
+        let mut buffer = Vec::new();
+        let mut writer = vbq::WriterBuilder::default()
+            .header(config.vbq_header(BitSize::Two, false))
+            .build(&mut buffer)?;
+        let stats = dedup(BinseqReader::from_bytes(input)?, &mut writer, opts)?;
+        writer.finish()?;
+        drop(writer);
+        Ok((stats, testing::read_vbq(buffer, config)?))
+    }
+
+    fn primaries(records: Vec<SyntheticRecord>) -> Vec<Vec<u8>> {
+        records.into_iter().map(|record| record.s_seq).collect()
+    }
+
+    #[test]
+    fn test_dedup_preserves_first_occurrences() -> Result<()> {
+        let config = DatasetConfig::new(LengthDistribution::Uniform { min: 2, max: 5 }).flags(true);
+        let seqs: [&[u8]; 7] = [
+            b"ACGT", b"ACGTA", b"ACGT", b"TTTT", b"ACGTA", b"ACGT", b"GG",
+        ];
+        let records: Vec<_> = seqs
+            .iter()
+            .zip(0..)
+            .map(|(seq, flag)| SyntheticRecord {
+                flag: Some(flag),
+                s_seq: seq.to_vec(),
+                ..SyntheticRecord::default()
+            })
+            .collect();
+        let input = testing::write_vbq(&records, config.vbq_header(BitSize::Two, false))?;
+
+        let (stats, output) = dedup_vbq(input.clone(), DedupOptions::default(), &config)?;
+        assert_eq!(
+            stats,
+            DedupStats {
+                seen: 7,
+                unique: 4,
+                duplicates: 3,
+                skipped: 0
+            }
+        );
+        // first occurrences keep their flags
+        assert_eq!(
+            output,
+            [
+                records[0].clone(),
+                records[1].clone(),
+                records[3].clone(),
+                records[6].clone()
+            ]
+        );
+
+        // a 4-base prefix also merges ACGTA into ACGT, in both key modes
+        for exact in [false, true] {
+            let opts = DedupOptions::default().prefix_len(4).exact(exact);
+            let (stats, output) = dedup_vbq(input.clone(), opts, &config)?;
+            assert_eq!((stats.unique, stats.duplicates), (3, 4));
+            assert_eq!(primaries(output), [&b"ACGT"[..], b"TTTT", b"GG"]);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_dedup_repeated_dataset() -> Result<()> {
+        let config = DatasetConfig::new(LengthDistribution::Uniform { min: 20, max: 80 });
+        let records = testing::random_records(100, config.lengths, config.seed);
+        let repeated = [records.as_slice(), records.as_slice()].concat();
+        let input = testing::write_vbq(&repeated, config.vbq_header(BitSize::Two, true))?;
+
+        let (stats, output) = dedup_vbq(input, DedupOptions::default(), &config)?;
+        assert_eq!(
+            (stats.seen, stats.unique, stats.duplicates),
+            (200, 100, 100)
+        );
+        assert_eq!(output, records);
+        Ok(())
+    }
+
+    #[test]
+    fn test_dedup_ignores_padding_bits() -> Result<()> {
+        // Three records whose words only differ in the unused bits past slen
+        let config = DatasetConfig::new(LengthDistribution::Fixed(10));
+        let mut writer = bq::WriterBuilder::default()
+            .header(config.bq_header(BitSize::Two)?)
+            .build(Vec::new())?;
+        let mut sbuf = Vec::new();
+        BitSize::Two.encode(b"ACGTACGTAC", &mut sbuf)?;
+        let clean = sbuf[0];
+        for garbage in [0, 0xdead_beef << 20, u64::MAX << 20] {
+            writer.write_encoded_direct(None, &[clean | garbage])?;
+        }
+        // and one that differs in the last real base
+        sbuf.clear();
+        BitSize::Two.encode(b"ACGTACGTAA", &mut sbuf)?;
+        writer.write_encoded_direct(None, &sbuf)?;
+
+        let (stats, output) = dedup_vbq(writer.into_inner(), DedupOptions::default(), &config)?;
+        assert_eq!((stats.seen, stats.unique, stats.duplicates), (4, 2, 2));
+        assert_eq!(primaries(output), [&b"ACGTACGTAC"[..], b"ACGTACGTAA"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_dedup_paired_keys_both_mates() -> Result<()> {
+        let config =
+            DatasetConfig::new(LengthDistribution::Uniform { min: 3, max: 4 }).paired(true);
+        let pairs: [(&[u8], &[u8]); 4] = [
+            (b"ACGT", b"TTTT"),
+            (b"ACGT", b"TTTA"),
+            (b"ACGT", b"TTTT"),
+            (b"ACG", b"TTTT"),
+        ];
+        let records: Vec<_> = pairs
+            .iter()
+            .map(|(s, x)| SyntheticRecord {
+                s_seq: s.to_vec(),
+                x_seq: x.to_vec(),
+                ..SyntheticRecord::default()
+            })
+            .collect();
+        let input = testing::write_vbq(&records, config.vbq_header(BitSize::Two, false))?;
+
+        let (stats, output) = dedup_vbq(input, DedupOptions::default(), &config)?;
+        assert_eq!((stats.unique, stats.duplicates), (3, 1));
+        assert_eq!(
+            output,
+            [records[0].clone(), records[1].clone(), records[3].clone()]
+        );
+        Ok(())
+    }
+}
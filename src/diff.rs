@@ -0,0 +1,282 @@
+//! Record-level comparison of BQ files
+//!
+//! [`diff_bq`] checks that two BQ files hold the same records in the same order, e.g. to verify
+//! the output of a conversion tool. Records are compared by their flag and decoded sequences, so
+//! files written with different bit sizes compare equal if they decode to the same nucleotides.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use binseq::diff::{DiffOptions, diff_bq_with};
+//!
+//! let report = diff_bq_with("a.bq", "b.bq", DiffOptions::default().max_diffs(10))?;
+//! for (idx, kind) in &report.records_differ {
+//!     eprintln!("record {idx}: {kind:?}");
+//! }
+//! # Ok::<(), binseq::Error>(())
+//! ```
+
+use std::path::Path;
+
+use crate::{BinseqRecord, Result, bq::MmapReader, error::HeaderError};
+
+/// The first field in which two records differ
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffKind {
+    /// The flags differ (missing flags compare as 0)
+    FlagMismatch(u64, u64),
+
+    /// The decoded primary sequences differ
+    PrimarySequenceMismatch,
+
+    /// The decoded secondary sequences differ
+    SecondarySequenceMismatch,
+}
+
+/// Outcome of comparing two BQ files with [`diff_bq`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DiffReport {
+    /// Number of compared records that are equal
+    pub records_equal: u64,
+
+    /// Index and first differing field of every compared record that differs
+    pub records_differ: Vec<(usize, DiffKind)>,
+
+    /// Number of records in the first file
+    pub num_records_a: usize,
+
+    /// Number of records in the second file
+    pub num_records_b: usize,
+
+    /// Whether the comparison stopped early after [`DiffOptions::max_diffs`] differences
+    pub truncated: bool,
+}
+impl DiffReport {
+    /// Returns `true` if both files hold the same records in the same order
+    #[must_use]
+    pub fn is_equal(&self) -> bool {
+        self.records_differ.is_empty() && self.num_records_a == self.num_records_b
+    }
+}
+
+/// Configuration of [`diff_bq_with`]
+#[derive(Debug, Clone, Copy)]
+pub struct DiffOptions {
+    /// Maximum number of differing records reported before stopping
+    max_diffs: usize,
+}
+impl Default for DiffOptions {
+    /// Reports every difference
+    fn default() -> Self {
+        Self {
+            max_diffs: usize::MAX,
+        }
+    }
+}
+impl DiffOptions {
+    /// Stops the comparison after `max_diffs` differing records
+    #[must_use]
+    pub fn max_diffs(mut self, max_diffs: usize) -> Self {
+        self.max_diffs = max_diffs;
+        self
+    }
+}
+
+/// Compares the records of two BQ files
+///
+/// Records are compared pairwise by index up to the length of the shorter file. See
+/// [`diff_bq_with`] to stop after a number of differences.
+///
+/// # Errors
+///
+/// Returns [`HeaderError::IncompatibleBqHeaders`] if the files store sequences of different
+/// lengths, or an error if either file cannot be read.
+pub fn diff_bq(a: impl AsRef<Path>, b: impl AsRef<Path>) -> Result<DiffReport> {
+    diff_bq_with(a, b, DiffOptions::default())
+}
+
+/// Compares the records of two BQ files with the given options
+///
+/// See [`diff_bq`].
+pub fn diff_bq_with(
+    a: impl AsRef<Path>,
+    b: impl AsRef<Path>,
+    options: DiffOptions,
+) -> Result<DiffReport> {
+    let (a, b) = (MmapReader::new(a)?, MmapReader::new(b)?);
+    diff_readers(&a, &b, options)
+}
+
+/// Returns `true` if two BQ files hold the same records in the same order
+///
+/// Stops at the first difference. See [`diff_bq`] for the errors.
+pub fn are_bq_equal(a: impl AsRef<Path>, b: impl AsRef<Path>) -> Result<bool> {
+    let report = diff_bq_with(a, b, DiffOptions::default().max_diffs(1))?;
+    Ok(report.is_equal())
+}
+
+fn diff_readers(a: &MmapReader, b: &MmapReader, options: DiffOptions) -> Result<DiffReport> {
+    let (header_a, header_b) = (a.header(), b.header());
+    if header_a.slen != header_b.slen || header_a.xlen != header_b.xlen {
+        return Err(HeaderError::IncompatibleBqHeaders(header_a, header_b).into());
+    }
+
+    let mut report = DiffReport {
+        num_records_a: a.num_records(),
+        num_records_b: b.num_records(),
+        ..DiffReport::default()
+    };
+    if options.max_diffs == 0 {
+        report.truncated = true;
+        return Ok(report);
+    }
+
+    let (mut sbuf_a, mut xbuf_a) = (Vec::new(), Vec::new());
+    let (mut sbuf_b, mut xbuf_b) = (Vec::new(), Vec::new());
+    for idx in 0..report.num_records_a.min(report.num_records_b) {
+        let (rec_a, rec_b) = (a.get(idx)?, b.get(idx)?);
+        rec_a.decode_pair(&mut sbuf_a, &mut xbuf_a)?;
+        rec_b.decode_pair(&mut sbuf_b, &mut xbuf_b)?;
+
+        let (flag_a, flag_b) = (rec_a.flag().unwrap_or(0), rec_b.flag().unwrap_or(0));
+        let kind = if flag_a != flag_b {
+            Some(DiffKind::FlagMismatch(flag_a, flag_b))
+        } else if sbuf_a != sbuf_b {
+            Some(DiffKind::PrimarySequenceMismatch)
+        } else if xbuf_a != xbuf_b {
+            Some(DiffKind::SecondarySequenceMismatch)
+        } else {
+            None
+        };
+
+        match kind {
+            Some(kind) => {
+                report.records_differ.push((idx, kind));
+                if report.records_differ.len() >= options.max_diffs {
+                    report.truncated = idx + 1 < report.num_records_a.min(report.num_records_b);
+                    break;
+                }
+            }
+            None => report.records_equal += 1,
+        }
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::SequencingRecordBuilder;
+    use crate::bq::{FileHeaderBuilder, WriterBuilder};
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("binseq_test_diff_{name}.bq"))
+    }
+
+    /// Writes 100 paired records, replacing record `idx` with `(flag, s_seq, x_seq)`
+    fn write_records(path: &Path, patch: Option<(usize, u64, &[u8], &[u8])>) -> Result<()> {
+        let header = FileHeaderBuilder::new()
+            .slen(16)
+            .xlen(8)
+            .flags(true)
+            .build()?;
+        let mut writer = WriterBuilder::default()
+            .header(header)
+            .build(std::fs::File::create(path)?)?;
+        for idx in 0..100 {
+            let s_seq: Vec<u8> = (0..16).map(|i| b"ACGT"[(i * 3 + idx) % 4]).collect();
+            let x_seq: Vec<u8> = (0..8).map(|i| b"ACGT"[(i + idx) % 4]).collect();
+            let (flag, s_seq, x_seq) = match patch {
+                Some((target, flag, s, x)) if target == idx => (flag, s.to_vec(), x.to_vec()),
+                _ => (idx as u64, s_seq, x_seq),
+            };
+            let record = SequencingRecordBuilder::default()
+                .s_seq(&s_seq)
+                .x_seq(&x_seq)
+                .flag(flag)
+                .build()?;
+            writer.push(record)?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_diff_detects_single_record() -> Result<()> {
+        let (a, b) = (temp_path("a"), temp_path("b"));
+        write_records(&a, None)?;
+        assert!(are_bq_equal(&a, &a)?);
+
+        // Record 42 with its original flag and secondary sequence
+        let x_seq: Vec<u8> = (0..8).map(|i| b"ACGT"[(i + 42) % 4]).collect();
+        write_records(&b, Some((42, 42, &b"TTTTTTTTTTTTTTTT"[..], &x_seq[..])))?;
+        let report = diff_bq(&a, &b)?;
+        assert_eq!(report.records_equal, 99);
+        assert_eq!(
+            report.records_differ,
+            vec![(42, DiffKind::PrimarySequenceMismatch)]
+        );
+        assert!(!report.truncated);
+        assert!(!are_bq_equal(&a, &b)?);
+
+        write_records(
+            &b,
+            Some((7, 1000, &b"AAAAAAAAAAAAAAAA"[..], &b"AAAAAAAA"[..])),
+        )?;
+        let report = diff_bq(&a, &b)?;
+        assert_eq!(
+            report.records_differ,
+            vec![(7, DiffKind::FlagMismatch(7, 1000))]
+        );
+
+        std::fs::remove_file(a)?;
+        std::fs::remove_file(b)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_diff_max_diffs_and_incompatible_headers() -> Result<()> {
+        let (a, b) = (temp_path("max_a"), temp_path("max_b"));
+        write_records(&a, None)?;
+        let header = FileHeaderBuilder::new()
+            .slen(16)
+            .xlen(8)
+            .flags(true)
+            .build()?;
+        let mut writer = WriterBuilder::default()
+            .header(header)
+            .build(std::fs::File::create(&b)?)?;
+        for _ in 0..100 {
+            let record = SequencingRecordBuilder::default()
+                .s_seq(b"CCCCCCCCCCCCCCCC")
+                .x_seq(b"CCCCCCCC")
+                .flag(0)
+                .build()?;
+            writer.push(record)?;
+        }
+        writer.flush()?;
+        drop(writer);
+
+        let report = diff_bq_with(&a, &b, DiffOptions::default().max_diffs(3))?;
+        assert_eq!(report.records_differ.len(), 3);
+        assert!(report.truncated);
+
+        let header = FileHeaderBuilder::new().slen(8).build()?;
+        WriterBuilder::default()
+            .header(header)
+            .build(std::fs::File::create(&b)?)?
+            .flush()?;
+        assert!(matches!(
+            diff_bq(&a, &b),
+            Err(crate::Error::HeaderError(
+                HeaderError::IncompatibleBqHeaders(..)
+            ))
+        ));
+
+        std::fs::remove_file(a)?;
+        std::fs::remove_file(b)?;
+        Ok(())
+    }
+}
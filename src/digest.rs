@@ -0,0 +1,411 @@
+//! Container-independent content digests of BINSEQ files
+//!
+//! A digest covers the logical sequence of records (flags, sequences, quality scores, and
+//! headers) rather than the bytes of the file, so two files holding the same records in the same
+//! order produce the same digest regardless of their format, VBQ block size, compression, or
+//! index layout.
+//!
+//! # Construction
+//!
+//! Each record is hashed over a canonical little-endian encoding of its fields. Record hashes
+//! are grouped into fixed chunks of [`DIGEST_CHUNK_SIZE`] consecutive records by index, each
+//! chunk is hashed over the concatenation of its record hashes, and the final digest is the hash
+//! of the record count followed by all chunk hashes in order. Chunks are defined by record index
+//! alone, so the result does not depend on how records are split across blocks or threads.
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+use sha2::Digest as _;
+
+use crate::{BinseqReader, BinseqRecord, ParallelProcessor, ParallelReader, Result};
+
+/// Number of consecutive records hashed together into a single chunk hash
+pub const DIGEST_CHUNK_SIZE: usize = 4096;
+
+/// Domain separation tag prepended to the final digest input
+const DIGEST_TAG: &[u8] = b"binseq-digest-v1";
+
+/// Hash algorithm used to compute a digest
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DigestAlgo {
+    /// BLAKE3 (fast, default)
+    #[default]
+    Blake3,
+    /// SHA-256
+    Sha256,
+}
+impl DigestAlgo {
+    fn hasher(self) -> Hasher {
+        match self {
+            Self::Blake3 => Hasher::Blake3(Box::new(blake3::Hasher::new())),
+            Self::Sha256 => Hasher::Sha256(sha2::Sha256::new()),
+        }
+    }
+}
+
+/// Record fields included in a digest
+///
+/// Sequences are always included. Excluding fields allows comparing files that differ only in
+/// optional content, e.g. a BQ file against a VBQ file of the same records with quality scores.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DigestFields {
+    /// Include record flags
+    pub flags: bool,
+
+    /// Include quality scores
+    pub qual: bool,
+
+    /// Include sequence headers
+    pub headers: bool,
+}
+impl Default for DigestFields {
+    /// Includes all fields
+    fn default() -> Self {
+        Self {
+            flags: true,
+            qual: true,
+            headers: true,
+        }
+    }
+}
+
+/// Computes a digest over all fields of every record in a BINSEQ file
+///
+/// See [`digest_with`] to exclude optional fields from the digest.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use binseq::{BinseqReader, DigestAlgo, digest};
+///
+/// let a = digest(BinseqReader::new("a.vbq").unwrap(), DigestAlgo::Blake3, 0).unwrap();
+/// let b = digest(BinseqReader::new("b.vbq").unwrap(), DigestAlgo::Blake3, 0).unwrap();
+/// assert_eq!(a, b, "re-compression changed the records");
+/// ```
+pub fn digest(reader: BinseqReader, algo: DigestAlgo, threads: usize) -> Result<[u8; 32]> {
+    digest_with(reader, algo, threads, DigestFields::default())
+}
+
+/// Computes a digest over the selected fields of every record in a BINSEQ file
+///
+/// Records are hashed in parallel using `threads` threads (0 uses all available CPUs). The
+/// result is independent of the number of threads.
+pub fn digest_with(
+    reader: BinseqReader,
+    algo: DigestAlgo,
+    threads: usize,
+    fields: DigestFields,
+) -> Result<[u8; 32]> {
+    let num_records = reader.num_records()?;
+    let processor = DigestProcessor::new(algo, fields, num_records);
+    if num_records > 0 {
+        reader.process_parallel(processor.clone(), threads)?;
+    }
+    Ok(processor.finish())
+}
+
+/// Chunk hashes shared between threads
+#[derive(Default)]
+struct ChunkHashes {
+    /// Hashes of chunks fully processed by a single thread
+    complete: BTreeMap<usize, [u8; 32]>,
+
+    /// Record hashes of chunks split between threads, keyed by chunk and first record index
+    partial: BTreeMap<(usize, usize), Vec<[u8; 32]>>,
+}
+
+/// Parallel processor accumulating record hashes into chunk hashes
+#[derive(Clone)]
+struct DigestProcessor {
+    algo: DigestAlgo,
+    fields: DigestFields,
+    num_records: usize,
+
+    /// Chunk currently being accumulated and the index of its first record seen by this thread
+    current: Option<(usize, usize)>,
+
+    /// Record hashes of the current chunk
+    record_hashes: Vec<[u8; 32]>,
+
+    /// Decoding buffers
+    sbuf: Vec<u8>,
+    xbuf: Vec<u8>,
+
+    chunks: Arc<Mutex<ChunkHashes>>,
+}
+impl DigestProcessor {
+    fn new(algo: DigestAlgo, fields: DigestFields, num_records: usize) -> Self {
+        Self {
+            algo,
+            fields,
+            num_records,
+            current: None,
+            record_hashes: Vec::new(),
+            sbuf: Vec::new(),
+            xbuf: Vec::new(),
+            chunks: Arc::default(),
+        }
+    }
+
+    /// Number of records in a chunk
+    fn chunk_len(&self, chunk: usize) -> usize {
+        (self.num_records - chunk * DIGEST_CHUNK_SIZE).min(DIGEST_CHUNK_SIZE)
+    }
+
+    /// Hashes the canonical encoding of a record
+    fn hash_record<R: BinseqRecord>(&mut self, record: &R) -> Result<[u8; 32]> {
+        let mut hasher = self.algo.hasher();
+
+        if self.fields.flags {
+            match record.flag() {
+                Some(flag) => {
+                    hasher.update(&[1]);
+                    hasher.update(&flag.to_le_bytes());
+                }
+                None => hasher.update(&[0]),
+            }
+        }
+
+        self.sbuf.clear();
+        self.xbuf.clear();
+        record.decode_s(&mut self.sbuf)?;
+        record.decode_x(&mut self.xbuf)?;
+        hasher.update_field(&self.sbuf);
+        hasher.update_field(&self.xbuf);
+
+        if self.fields.qual {
+            hasher.update_field(record.squal());
+            hasher.update_field(record.xqual());
+        }
+        if self.fields.headers {
+            hasher.update_field(record.sheader());
+            hasher.update_field(record.xheader());
+        }
+
+        Ok(hasher.finalize())
+    }
+
+    /// Hashes the concatenated record hashes of a chunk
+    fn hash_chunk(&self, record_hashes: &[[u8; 32]]) -> [u8; 32] {
+        let mut hasher = self.algo.hasher();
+        for record_hash in record_hashes {
+            hasher.update(record_hash);
+        }
+        hasher.finalize()
+    }
+
+    /// Publishes the current chunk as either complete or partial
+    fn flush(&mut self) {
+        let Some((chunk, first)) = self.current.take() else {
+            return;
+        };
+        let record_hashes = std::mem::take(&mut self.record_hashes);
+        if record_hashes.len() == self.chunk_len(chunk) {
+            let hash = self.hash_chunk(&record_hashes);
+            self.chunks.lock().unwrap().complete.insert(chunk, hash);
+        } else {
+            self.chunks
+                .lock()
+                .unwrap()
+                .partial
+                .insert((chunk, first), record_hashes);
+        }
+    }
+
+    /// Combines all chunk hashes into the final digest
+    fn finish(self) -> [u8; 32] {
+        let mut chunks = std::mem::take(&mut *self.chunks.lock().unwrap());
+
+        // Reassemble chunks split between threads in record order
+        let mut merged: BTreeMap<usize, Vec<[u8; 32]>> = BTreeMap::new();
+        for ((chunk, _), record_hashes) in chunks.partial {
+            merged.entry(chunk).or_default().extend(record_hashes);
+        }
+        for (chunk, record_hashes) in merged {
+            chunks
+                .complete
+                .insert(chunk, self.hash_chunk(&record_hashes));
+        }
+
+        let mut hasher = self.algo.hasher();
+        hasher.update(DIGEST_TAG);
+        hasher.update(&(self.num_records as u64).to_le_bytes());
+        for chunk_hash in chunks.complete.values() {
+            hasher.update(chunk_hash);
+        }
+        hasher.finalize()
+    }
+}
+impl ParallelProcessor for DigestProcessor {
+    fn process_record<R: BinseqRecord>(&mut self, record: R) -> Result<()> {
+        let idx = record.index() as usize;
+        let chunk = idx / DIGEST_CHUNK_SIZE;
+        if self.current.map(|(current, _)| current) != Some(chunk) {
+            self.flush();
+            self.current = Some((chunk, idx));
+        }
+        let record_hash = self.hash_record(&record)?;
+        self.record_hashes.push(record_hash);
+        Ok(())
+    }
+
+    fn on_thread_complete(&mut self) -> Result<()> {
+        self.flush();
+        Ok(())
+    }
+}
+
+/// Incremental hasher over the supported algorithms
+enum Hasher {
+    Blake3(Box<blake3::Hasher>),
+    Sha256(sha2::Sha256),
+}
+impl Hasher {
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            Self::Blake3(hasher) => {
+                hasher.update(data);
+            }
+            Self::Sha256(hasher) => hasher.update(data),
+        }
+    }
+
+    /// Hashes a length-prefixed field so adjacent fields cannot be confused
+    fn update_field(&mut self, data: &[u8]) {
+        self.update(&(data.len() as u64).to_le_bytes());
+        self.update(data);
+    }
+
+    fn finalize(self) -> [u8; 32] {
+        match self {
+            Self::Blake3(hasher) => *hasher.finalize().as_bytes(),
+            Self::Sha256(hasher) => hasher.finalize().into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use super::*;
+    use crate::{BinseqWriterBuilder, SequencingRecordBuilder, write::Format};
+
+    const N_RECORDS: usize = 3 * DIGEST_CHUNK_SIZE + 17;
+
+    fn temp_path(name: &str, format: Format) -> PathBuf {
+        std::env::temp_dir().join(format!("binseq_test_digest_{name}{}", format.extension()))
+    }
+
+    /// Writes the same set of records with the given container settings
+    fn write_records(path: &Path, format: Format, block: usize, compress: bool, qual: bool) {
+        let mut writer = BinseqWriterBuilder::new(format)
+            .slen(40)
+            .block_size(block)
+            .compression(compress)
+            .quality(qual)
+            .flags(true)
+            .build(std::fs::File::create(path).unwrap())
+            .unwrap();
+        for idx in 0..N_RECORDS {
+            let seq: Vec<u8> = (0..40).map(|i| b"ACGT"[(i * 7 + idx) % 4]).collect();
+            let squal = vec![b'!' + (idx % 40) as u8; seq.len()];
+            let record = SequencingRecordBuilder::default()
+                .s_seq(&seq)
+                .s_qual(&squal)
+                .flag(idx as u64 % 3)
+                .build()
+                .unwrap();
+            writer.push(record).unwrap();
+        }
+        writer.finish().unwrap();
+    }
+
+    fn file_digest(
+        path: &Path,
+        algo: DigestAlgo,
+        threads: usize,
+        fields: DigestFields,
+    ) -> [u8; 32] {
+        digest_with(BinseqReader::new(path).unwrap(), algo, threads, fields).unwrap()
+    }
+
+    #[test]
+    fn test_digest_independent_of_container() {
+        let a = temp_path("a", Format::Vbq);
+        let b = temp_path("b", Format::Vbq);
+        write_records(&a, Format::Vbq, 1024, false, true);
+        write_records(&b, Format::Vbq, 1024 * 1024, true, true);
+
+        for algo in [DigestAlgo::Blake3, DigestAlgo::Sha256] {
+            let fields = DigestFields::default();
+            let expected = file_digest(&a, algo, 1, fields);
+            for threads in [1, 3, 8] {
+                assert_eq!(file_digest(&a, algo, threads, fields), expected);
+                assert_eq!(file_digest(&b, algo, threads, fields), expected);
+            }
+        }
+
+        for path in [a, b] {
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_digest_bq_vbq_without_quality() {
+        let bq = temp_path("cross", Format::Bq);
+        let vbq = temp_path("cross", Format::Vbq);
+        write_records(&bq, Format::Bq, 0, false, false);
+        write_records(&vbq, Format::Vbq, 4096, true, true);
+
+        let all = DigestFields::default();
+        let no_qual = DigestFields {
+            qual: false,
+            headers: false,
+            ..all
+        };
+        assert_eq!(
+            file_digest(&bq, DigestAlgo::Blake3, 2, no_qual),
+            file_digest(&vbq, DigestAlgo::Blake3, 4, no_qual)
+        );
+        assert_ne!(
+            file_digest(&bq, DigestAlgo::Blake3, 2, all),
+            file_digest(&vbq, DigestAlgo::Blake3, 4, all)
+        );
+
+        for path in [bq, vbq] {
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_digest_detects_changes() {
+        let a = digest(
+            BinseqReader::new("./data/subset.vbq").unwrap(),
+            DigestAlgo::Blake3,
+            0,
+        )
+        .unwrap();
+        let b = digest(
+            BinseqReader::new("./data/subset.vbq").unwrap(),
+            DigestAlgo::Sha256,
+            0,
+        )
+        .unwrap();
+        assert_ne!(a, b);
+
+        let vbq = temp_path("changes", Format::Vbq);
+        write_records(&vbq, Format::Vbq, 4096, false, true);
+        let fields = DigestFields::default();
+        let without_flags = DigestFields {
+            flags: false,
+            ..fields
+        };
+        assert_ne!(
+            file_digest(&vbq, DigestAlgo::Blake3, 2, fields),
+            file_digest(&vbq, DigestAlgo::Blake3, 2, without_flags)
+        );
+        std::fs::remove_file(vbq).unwrap();
+    }
+}
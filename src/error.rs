@@ -94,6 +94,24 @@ pub enum HeaderError {
     /// * Second `usize` - The expected number of bytes according to the header
     #[error("Invalid number of bytes provided: {0}. Expected: {1}")]
     InvalidSize(usize, usize),
+
+    /// The magic number belongs to a different BINSEQ structure than the one expected
+    ///
+    /// e.g. a BQ file was opened with the VBQ reader
+    #[error("Found {found} magic at byte {offset} where a {expected} was expected")]
+    MismatchedFormat {
+        found: &'static str,
+        expected: &'static str,
+        offset: usize,
+    },
+
+    /// Two BQ files store sequences of different lengths and cannot be compared
+    ///
+    /// # Arguments
+    /// * First `FileHeader` - The header of the first file
+    /// * Second `FileHeader` - The header of the second file
+    #[error("Incompatible BQ headers: {0:?} and {1:?}")]
+    IncompatibleBqHeaders(crate::bq::FileHeader, crate::bq::FileHeader),
 }
 
 /// Errors that can occur while reading binary sequence data
@@ -143,6 +161,12 @@ pub enum ReadError {
     #[error("Unexpected Block Magic Number found: {0} at position {1}")]
     InvalidBlockMagicNumber(u64, usize),
 
+    /// When the input is not BINSEQ data but starts like another known format
+    ///
+    /// The parameter names the detected format (e.g. gzip, BAM, FASTQ)
+    #[error("Input looks like {0} data, not BINSEQ")]
+    LooksLikeOtherFormat(&'static str),
+
     /// When trying to read a block but reaching the end of the file unexpectedly
     ///
     /// The parameter is the position in the file where the read was attempted
@@ -156,6 +180,49 @@ pub enum ReadError {
     /// Missing the index end magic number
     #[error("Missing index end magic number")]
     MissingIndexEndMagic,
+
+    /// A quality score byte outside of the printable ASCII range (`!` to `~`)
+    ///
+    /// The parameter is the invalid byte
+    #[error("Invalid quality score byte ({0}): expected printable ASCII")]
+    InvalidQualityScore(u8),
+
+    /// When the records of a block are malformed (e.g. lengths pointing past its end)
+    ///
+    /// `offset` is the position of the malformed record within the block data
+    #[error("Corrupt block: {reason} (record at byte {offset} of the block)")]
+    CorruptBlock { offset: usize, reason: &'static str },
+
+    /// When a block holds more bytes than the block size of the file header
+    ///
+    /// Blocks may be shorter than the block size (e.g. an unpadded final block), but never
+    /// longer.
+    #[error("Block holds {got} bytes but the file header allows at most {expected_max}")]
+    BlockSizeMismatch { expected_max: usize, got: usize },
+
+    /// When an operation requires paired records but the file is single-end
+    #[error("Operation requires paired records but the file is not paired")]
+    NotPaired,
+
+    /// When quality scores are requested from a file that does not store them
+    #[error("Operation requires quality scores but the file does not store them")]
+    NoQualityData,
+
+    /// When an offset or length stored in a file does not fit into `usize` (32-bit targets)
+    #[error("Offset or length {0} in the file exceeds the address space of this platform")]
+    OffsetOverflow(u64),
+
+    /// Interleaved mates are out of order (see [`crate::bq::InterleavedPairIter`])
+    ///
+    /// `record` is the position of the offending record in the iterated sequence
+    #[error("Interleaved pairs out of sync at record {record}: {reason}")]
+    InterleaveDesync { record: usize, reason: &'static str },
+
+    /// When a serialized writer checkpoint is truncated or fails its checksum
+    ///
+    /// See [`crate::vbq::WriterCheckpoint::from_bytes`]
+    #[error("Corrupt writer checkpoint: {0}")]
+    CorruptCheckpoint(&'static str),
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -190,13 +257,22 @@ pub enum WriteError {
         obs_extended: bool,
     },
 
-    /// The length of the sequence being written does not match what was specified in the header
+    /// The sequence being written is shorter than the length specified in the header
     ///
     /// # Fields
     /// * `expected` - The sequence length specified in the header
     /// * `got` - The actual length of the sequence being written
-    #[error("Sequence length ({got}) does not match the header ({expected})")]
-    UnexpectedSequenceLength { expected: u32, got: usize },
+    #[error("Sequence is too short: length ({got}) is less than expected ({expected})")]
+    SequenceTooShort { expected: usize, got: usize },
+
+    /// The sequence being written is longer than the length specified in the header, or
+    /// than the maximum length configured on the writer
+    ///
+    /// # Fields
+    /// * `expected` - The expected (or maximum) sequence length
+    /// * `got` - The actual length of the sequence being written
+    #[error("Sequence is too long: length ({got}) exceeds expected ({expected})")]
+    SequenceTooLong { expected: usize, got: usize },
 
     /// The sequence contains invalid nucleotide characters
     ///
@@ -229,6 +305,10 @@ pub enum WriteError {
     #[error("Header flag is set in header but trying to write without headers.")]
     HeaderFlagSet,
 
+    /// When trying to write headers but the header specifies they are not present
+    #[error("Header flag not set in header but trying to write with headers.")]
+    HeaderFlagNotSet,
+
     /// When a record is too large to fit in a block of the configured size
     ///
     /// The first parameter is the record size, the second is the maximum block size
@@ -253,6 +333,91 @@ pub enum WriteError {
     /// When building a `SequencingRecord` without a primary sequence
     #[error("SequencingRecordBuilder requires a primary sequence (s_seq)")]
     MissingSequence,
+
+    /// When writing pre-encoded records with a different bitsize than the writer
+    ///
+    /// The first parameter is the expected bitsize, the second is the found bitsize
+    #[error("Incompatible bitsizes found. Found ({1:?}) Expected ({0:?})")]
+    IncompatibleBitsizes(bitnuc::BitSize, bitnuc::BitSize),
+
+    /// When pairing two files with differing numbers of records
+    ///
+    /// The first parameter is the number of R1 records, the second is the number of R2 records
+    #[error("Cannot pair files with different record counts: R1 has {0} records, R2 has {1}")]
+    RecordCountMismatch(usize, usize),
+
+    /// When sending data to a writer thread that has already stopped
+    #[error("Writer thread channel closed before all chunks were sent")]
+    ChannelClosed,
+
+    /// When pairing two files whose capabilities (quality scores, headers) differ
+    #[error("Cannot pair files with mismatched {attribute} (R1: {r1}, R2: {r2})")]
+    PairCapabilityMismatch {
+        attribute: &'static str,
+        r1: bool,
+        r2: bool,
+    },
+
+    /// When a block holds more records than a block header can count
+    ///
+    /// The parameter is the number of records in the block
+    #[error("Block holds {0} records but a block header can count at most {max}", max = u32::MAX)]
+    BlockRecordCountOverflow(usize),
+
+    /// When a running byte or record count of the writer overflows `usize`
+    ///
+    /// The parameter names the count that overflowed
+    #[error("Number of {0} overflows the address space of this platform")]
+    CountOverflow(&'static str),
+
+    /// When writing a pre-encoded sequence with the wrong number of words
+    #[error("Encoded sequence has {got} words but the header requires {expected}")]
+    EncodedLengthMismatch { expected: usize, got: usize },
+
+    /// When a raw block does not have the size required by its block or file header
+    #[error("Raw block has {got} bytes but {expected} are required")]
+    RawBlockSizeMismatch { expected: u64, got: usize },
+
+    /// When writing a soft-mask bitmap of the wrong size for its sequence
+    #[error("Soft-mask bitmap has {got} bytes but the sequence requires {expected}")]
+    MaskLengthMismatch { expected: usize, got: usize },
+
+    /// When a record does not have the sequence lengths of a fixed-length output
+    ///
+    /// Lengths are given as (primary, extended)
+    #[error("Record {index} has sequence lengths {got:?} but {expected:?} are required")]
+    RecordLengthMismatch {
+        index: u64,
+        expected: (u64, u64),
+        got: (u64, u64),
+    },
+
+    /// When a BQ writer inferring its sequence lengths from the first record is used in a
+    /// way that needs the lengths before they are known
+    ///
+    /// The parameter describes the operation
+    #[error("Sequence lengths are inferred from the first record: {0}")]
+    LengthsNotInferred(&'static str),
+
+    /// When a strict writer receives a field its format cannot store
+    ///
+    /// The parameter names the field
+    #[error("The {0} field cannot be stored in this format")]
+    UnsupportedField(&'static str),
+
+    /// When a VBQ writer is resumed from a checkpoint taken with another file header
+    #[error("Writer checkpoint was taken with another file header")]
+    CheckpointHeaderMismatch,
+
+    /// When oversized records would be truncated to an empty sequence or to a length above
+    /// the maximum sequence length of the writer
+    #[error(
+        "Cannot truncate oversized records to {len} nucleotides (maximum length: {max_length:?})"
+    )]
+    InvalidTruncateLength {
+        len: usize,
+        max_length: Option<usize>,
+    },
 }
 
 /// Errors related to VBQ file indexing
@@ -270,6 +435,16 @@ pub enum IndexError {
     /// Invalid reserved bytes in the index header
     #[error("Invalid reserved bytes in index header")]
     InvalidReservedBytes,
+
+    /// When an index describes a file of a different size than the one it is used with
+    ///
+    /// The first parameter is the size recorded in the index, the second is the actual size
+    #[error("Index describes {0} bytes of data but the file contains {1} bytes")]
+    ByteSizeMismatch(u64, u64),
+
+    /// When a text export of an index (CSV or TSV) cannot be parsed
+    #[error("Invalid index text at line {line}: {reason}")]
+    InvalidText { line: usize, reason: &'static str },
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -325,6 +500,21 @@ pub enum FastxEncodingError {
 
     #[error("Builder not provided with any input")]
     MissingInput,
+
+    #[error("Quality filtering requires quality scores, but a record has none (FASTA input?)")]
+    MissingQualityScores,
+
+    /// When an interleaved FASTQ file ends with a read without its mate
+    ///
+    /// The parameter is the index of the read in the file
+    #[error("Interleaved FASTQ ends with an unpaired read (record {0})")]
+    UnpairedInterleavedRecord(u64),
+
+    /// When consecutive reads of an interleaved FASTQ file are not mates
+    ///
+    /// `record` is the index of the first mate in the file
+    #[error("Reads {record} and {} of interleaved FASTQ are not mates: {r1} vs {r2}", record + 1)]
+    MateNameMismatch { record: u64, r1: String, r2: String },
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -368,6 +558,20 @@ mod testing {
         assert!(matches!(binseq_error, Error::GenericError(_)));
     }
 
+    #[test]
+    fn test_error_is_send_sync() {
+        fn assert_send_sync<T: Send + Sync + 'static>() {}
+        assert_send_sync::<Error>();
+
+        // errors of worker threads are returned through the join handle
+        let handle =
+            std::thread::spawn(|| -> Result<()> { Err(ReadError::FileTruncation(42).into()) });
+        assert!(matches!(
+            handle.join().unwrap(),
+            Err(Error::ReadError(ReadError::FileTruncation(42)))
+        ));
+    }
+
     // ==================== HeaderError Tests ====================
 
     #[test]
@@ -450,12 +654,22 @@ mod testing {
     }
 
     #[test]
-    fn test_write_error_unexpected_sequence_length() {
-        let error = WriteError::UnexpectedSequenceLength {
+    fn test_write_error_sequence_length() {
+        let error = WriteError::SequenceTooShort {
+            expected: 100,
+            got: 50,
+        };
+        let error_str = format!("{error}");
+        assert!(error_str.contains("too short"));
+        assert!(error_str.contains("100"));
+        assert!(error_str.contains("50"));
+
+        let error = WriteError::SequenceTooLong {
             expected: 100,
             got: 150,
         };
         let error_str = format!("{error}");
+        assert!(error_str.contains("too long"));
         assert!(error_str.contains("100"));
         assert!(error_str.contains("150"));
     }
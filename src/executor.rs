@@ -0,0 +1,259 @@
+use std::any::Any;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{Receiver, Sender, channel};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+
+use crate::Result;
+
+/// A unit of parallel work produced by a reader for a single processor clone
+pub(crate) type Job = Box<dyn FnOnce() -> Result<()> + Send + 'static>;
+
+/// A type-erased task executed by a worker thread
+type Task = Box<dyn FnOnce() + Send + 'static>;
+
+/// A reusable pool of worker threads for parallel processing
+///
+/// By default, every call to [`ParallelReader::process_parallel_range`](crate::ParallelReader::process_parallel_range)
+/// spawns and joins fresh threads. When many small ranges are processed in succession that
+/// overhead can dominate, so an `Executor` keeps its worker threads alive and accepts successive
+/// calls through [`ParallelReader::process_parallel_in`](crate::ParallelReader::process_parallel_in).
+///
+/// Within a single call each processor clone receives a unique thread id through
+/// [`ParallelProcessor::set_tid`](crate::ParallelProcessor::set_tid), in the range
+/// `0..executor.num_threads()`, exactly as with spawn-per-call processing.
+///
+/// Worker threads are shut down and joined when the executor is dropped.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use binseq::{BinseqReader, Executor, ParallelReader};
+/// use binseq::processors::CountProcessor;
+///
+/// let executor = Executor::new(4);
+/// for start in (0..10_000).step_by(1_000) {
+///     let reader = BinseqReader::new("./data/subset.bq").unwrap();
+///     let counter = CountProcessor::new();
+///     reader
+///         .process_parallel_in(&executor, counter.clone(), start..start + 1_000)
+///         .unwrap();
+///     assert_eq!(counter.count(), 1_000);
+/// }
+/// ```
+pub struct Executor {
+    /// Sending side of the task queue (taken on drop to stop the workers)
+    sender: Option<Sender<Task>>,
+
+    /// Handles to the worker threads
+    workers: Vec<JoinHandle<()>>,
+}
+impl Executor {
+    /// Creates a new executor with `num_threads` worker threads
+    ///
+    /// If `num_threads` is 0, the number of available CPUs is used.
+    #[must_use]
+    pub fn new(num_threads: usize) -> Self {
+        let num_threads = if num_threads == 0 {
+            num_cpus::get()
+        } else {
+            num_threads
+        };
+
+        let (sender, receiver) = channel::<Task>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        let workers = (0..num_threads)
+            .map(|_| {
+                let receiver = Arc::clone(&receiver);
+                std::thread::spawn(move || work(&receiver))
+            })
+            .collect();
+
+        Self {
+            sender: Some(sender),
+            workers,
+        }
+    }
+
+    /// Returns the number of worker threads
+    #[must_use]
+    pub fn num_threads(&self) -> usize {
+        self.workers.len()
+    }
+
+    /// Runs all jobs on the worker threads and waits for them to complete
+    ///
+    /// Returns the first error reported by any job. If a job panicked, the panic is resumed on
+    /// the calling thread once all other jobs have finished.
+    ///
+    /// Jobs must not call back into the same executor, as they could wait on workers that are
+    /// all occupied.
+    pub(crate) fn run(&self, jobs: Vec<Job>) -> Result<()> {
+        let sender = self
+            .sender
+            .as_ref()
+            .expect("Executor sender is only taken on drop");
+
+        let (tx, rx) = channel();
+        for job in jobs {
+            let tx = tx.clone();
+            sender
+                .send(Box::new(move || {
+                    let outcome = panic::catch_unwind(AssertUnwindSafe(job));
+                    // The receiver outlives all tasks of this run
+                    let _ = tx.send(outcome);
+                }))
+                .expect("Executor workers have shut down");
+        }
+        drop(tx);
+
+        let mut result = Ok(());
+        let mut panic_payload: Option<Box<dyn Any + Send>> = None;
+        for outcome in rx {
+            match outcome {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => {
+                    if result.is_ok() {
+                        result = Err(e);
+                    }
+                }
+                Err(payload) => {
+                    panic_payload.get_or_insert(payload);
+                }
+            }
+        }
+        if let Some(payload) = panic_payload {
+            panic::resume_unwind(payload);
+        }
+        result
+    }
+}
+impl Default for Executor {
+    /// Creates an executor with one worker thread per available CPU
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+impl Drop for Executor {
+    fn drop(&mut self) {
+        // Closing the queue stops each worker once it is idle
+        drop(self.sender.take());
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+/// Worker loop: executes tasks until the queue is closed
+fn work(receiver: &Mutex<Receiver<Task>>) {
+    loop {
+        // The lock is released before the task runs so other workers can pick up tasks
+        let next = receiver.lock().ok().and_then(|rx| rx.recv().ok());
+        let Some(task) = next else {
+            break;
+        };
+        task();
+    }
+}
+
+/// Runs each job on its own freshly spawned thread and joins them all
+///
+/// This is the default spawn-per-call backend used when no [`Executor`] is provided.
+pub(crate) fn spawn_and_join(jobs: Vec<Job>) -> Result<()> {
+    let handles: Vec<_> = jobs.into_iter().map(std::thread::spawn).collect();
+    let mut result = Ok(());
+    for handle in handles {
+        let outcome = handle.join().unwrap();
+        if result.is_ok() {
+            result = outcome;
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+    use crate::{BinseqReader, BinseqRecord, ParallelProcessor, ParallelReader};
+
+    #[derive(Clone, Default)]
+    struct TidProcessor {
+        tid: Option<usize>,
+        count: Arc<AtomicUsize>,
+        tids: Arc<Mutex<Vec<usize>>>,
+    }
+    impl ParallelProcessor for TidProcessor {
+        fn process_record<R: BinseqRecord>(&mut self, _record: R) -> Result<()> {
+            self.count.fetch_add(1, Ordering::Relaxed);
+            Ok(())
+        }
+        fn on_thread_complete(&mut self) -> Result<()> {
+            self.tids.lock().unwrap().push(self.tid.unwrap());
+            Ok(())
+        }
+        fn set_tid(&mut self, tid: usize) {
+            self.tid = Some(tid);
+        }
+        fn get_tid(&self) -> Option<usize> {
+            self.tid
+        }
+    }
+
+    #[test]
+    fn test_executor_reused_across_calls() {
+        let executor = Executor::new(3);
+        assert_eq!(executor.num_threads(), 3);
+
+        for ext in ["bq", "vbq", "cbq"] {
+            let num_records = BinseqReader::new(format!("./data/subset.{ext}"))
+                .unwrap()
+                .num_records()
+                .unwrap();
+            let step = num_records.div_ceil(5);
+            for start in (0..num_records).step_by(step) {
+                let end = (start + step).min(num_records);
+                let reader = BinseqReader::new(format!("./data/subset.{ext}")).unwrap();
+                let processor = TidProcessor::default();
+                reader
+                    .process_parallel_in(&executor, processor.clone(), start..end)
+                    .unwrap();
+                assert_eq!(processor.count.load(Ordering::Relaxed), end - start);
+
+                // Thread ids are unique within a call
+                let tids = processor.tids.lock().unwrap();
+                let unique: HashSet<_> = tids.iter().copied().collect();
+                assert_eq!(unique.len(), tids.len());
+                assert!(tids.iter().all(|&tid| tid < executor.num_threads()));
+            }
+        }
+    }
+
+    #[test]
+    fn test_executor_propagates_errors() {
+        let executor = Executor::new(2);
+        let jobs: Vec<Job> = vec![
+            Box::new(|| Ok(())),
+            Box::new(|| Err(crate::Error::from(std::io::Error::other("job failed")))),
+        ];
+        assert!(executor.run(jobs).is_err());
+
+        // The executor remains usable after a failed run
+        let jobs: Vec<Job> = vec![Box::new(|| Ok(())), Box::new(|| Ok(()))];
+        assert!(executor.run(jobs).is_ok());
+    }
+
+    #[test]
+    fn test_executor_resumes_panics() {
+        let executor = Executor::new(2);
+        let jobs: Vec<Job> = vec![Box::new(|| panic!("job panicked"))];
+        let outcome = panic::catch_unwind(AssertUnwindSafe(|| executor.run(jobs)));
+        assert!(outcome.is_err());
+
+        // Workers survive the panic
+        let jobs: Vec<Job> = vec![Box::new(|| Ok(()))];
+        assert!(executor.run(jobs).is_ok());
+    }
+}
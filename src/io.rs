@@ -0,0 +1,228 @@
+//! Piping BINSEQ data between processes
+//!
+//! Bioinformatics pipelines commonly connect tools with Unix pipes. This module writes
+//! BINSEQ files to `stdout` and reads them back from `stdin`, enabling patterns like
+//! `cat file.bq | bq-tool | cat > output.bq`.
+//!
+//! Files are written unchanged, so the output of a pipe is byte-identical to its input file.
+//! BQ input can be consumed record by record with a [`bq::StreamReader`]. VBQ blocks are
+//! located through the index at the end of the file, so VBQ input is buffered in memory
+//! before it is read.
+
+use std::io::{self, BufWriter, Read, Stdin, Write};
+
+use crate::{BinseqReader, BinseqRecord, Result, SequencingRecordBuilder, bq, vbq};
+
+/// Writes a BQ file (its header followed by its records) to `stdout`
+///
+/// Returns the number of records written.
+pub fn bq_to_stdout(reader: bq::MmapReader) -> Result<u64> {
+    BinseqPipe::to_stdout(BinseqReader::Bq(reader)).run()
+}
+
+/// Creates a [`bq::StreamReader`] over a BQ file piped into `stdin`
+///
+/// The file header is read before returning.
+pub fn bq_from_stdin() -> Result<bq::StreamReader<Stdin>> {
+    bq_from_reader(io::stdin())
+}
+
+/// Creates a [`bq::StreamReader`] over a BQ file read from `reader`
+///
+/// The file header is read before returning.
+pub fn bq_from_reader<R: Read>(reader: R) -> Result<bq::StreamReader<R>> {
+    let mut reader = bq::StreamReader::new(reader);
+    reader.read_header()?;
+    Ok(reader)
+}
+
+/// Writes a VBQ file (including its index) to `stdout`
+///
+/// Returns the number of records written.
+pub fn vbq_to_stdout(reader: vbq::MmapReader) -> Result<u64> {
+    BinseqPipe::to_stdout(BinseqReader::Vbq(reader)).run()
+}
+
+/// Reads a VBQ file piped into `stdin`
+///
+/// The whole input is buffered in memory.
+pub fn vbq_from_stdin() -> Result<vbq::MmapReader> {
+    vbq_from_reader(io::stdin())
+}
+
+/// Reads a VBQ file from `reader`
+///
+/// The whole input is buffered in memory.
+pub fn vbq_from_reader<R: Read>(mut reader: R) -> Result<vbq::MmapReader> {
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data)?;
+    vbq::MmapReader::from_bytes(data)
+}
+
+/// Copies all records of a [`BinseqReader`] to an output stream
+///
+/// BQ, VBQ and CBQ files are copied unchanged. The two files of a
+/// [`BinseqReader::PairedBq`] reader are combined into a single paired BQ file.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use binseq::io::BinseqPipe;
+///
+/// let reader = binseq::open("./data/subset.vbq")?;
+/// let num_records = BinseqPipe::to_stdout(reader).run()?;
+/// eprintln!("piped {num_records} records");
+/// # Ok::<(), binseq::Error>(())
+/// ```
+pub struct BinseqPipe<'a> {
+    /// Source of the records
+    reader: BinseqReader,
+
+    /// Destination of the records
+    output: Box<dyn Write + 'a>,
+}
+impl<'a> BinseqPipe<'a> {
+    /// Creates a pipe copying the records of `reader` to `output`
+    #[must_use]
+    pub fn new(reader: BinseqReader, output: Box<dyn Write + 'a>) -> Self {
+        Self { reader, output }
+    }
+
+    /// Copies all records and flushes the output
+    ///
+    /// Returns the number of records (pairs for paired data) written.
+    pub fn run(mut self) -> Result<u64> {
+        let num_records = match &self.reader {
+            BinseqReader::Bq(reader) => {
+                self.output.write_all(reader.as_bytes())?;
+                reader.num_records()
+            }
+            BinseqReader::Vbq(reader) => {
+                reader.write_to(&mut self.output)?;
+                reader.num_records()?
+            }
+            BinseqReader::Cbq(reader) => {
+                self.output.write_all(reader.as_bytes())?;
+                reader.num_records()
+            }
+            BinseqReader::PairedBq(reader) => write_paired(reader, &mut self.output)?,
+        };
+        self.output.flush()?;
+        Ok(num_records as u64)
+    }
+}
+impl BinseqPipe<'static> {
+    /// Creates a pipe copying the records of `reader` to `stdout`
+    #[must_use]
+    pub fn to_stdout(reader: BinseqReader) -> Self {
+        Self::new(reader, Box::new(BufWriter::new(io::stdout().lock())))
+    }
+}
+
+/// Writes the records of two single-end BQ files as a single paired BQ file
+fn write_paired<W: Write>(reader: &bq::PairedReader, output: W) -> Result<usize> {
+    let (r1, r2) = (reader.r1().header(), reader.r2().header());
+    let header = bq::FileHeaderBuilder::new()
+        .slen(r1.slen)
+        .xlen(r2.slen)
+        .bitsize(r1.bits)
+        .flags(r1.flags)
+        .build()?;
+    let mut writer = bq::WriterBuilder::default().header(header).build(output)?;
+
+    let (mut sbuf, mut xbuf) = (Vec::new(), Vec::new());
+    for idx in 0..reader.num_records() {
+        let pair = reader.get(idx)?;
+        sbuf.clear();
+        xbuf.clear();
+        pair.decode_s(&mut sbuf)?;
+        pair.decode_x(&mut xbuf)?;
+        let mut builder = SequencingRecordBuilder::default().s_seq(&sbuf).x_seq(&xbuf);
+        if let Some(flag) = pair.flag().filter(|_| r1.flags) {
+            builder = builder.flag(flag);
+        }
+        writer.push(builder.build()?)?;
+    }
+    writer.flush()?;
+    Ok(reader.num_records())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    /// Pipes a file through an in-memory output
+    fn pipe(reader: BinseqReader) -> Result<(u64, Vec<u8>)> {
+        let mut output = Cursor::new(Vec::new());
+        let num_records = BinseqPipe::new(reader, Box::new(&mut output)).run()?;
+        Ok((num_records, output.into_inner()))
+    }
+
+    #[test]
+    fn test_bq_roundtrip() -> Result<()> {
+        let reader = bq::MmapReader::new("./data/subset.bq")?;
+        let (header, expected) = (reader.header(), reader.num_records());
+        let (num_records, bytes) = pipe(BinseqReader::Bq(reader))?;
+        assert_eq!(num_records, expected as u64);
+        assert_eq!(bytes, std::fs::read("./data/subset.bq")?);
+
+        // Read the piped bytes back as a stream
+        let mut stream = bq_from_reader(Cursor::new(bytes))?;
+        assert_eq!(*stream.read_header()?, header);
+        let mut count = 0;
+        while let Some(record) = stream.next_record() {
+            record?;
+            count += 1;
+        }
+        assert_eq!(count, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_vbq_roundtrip() -> Result<()> {
+        let reader = vbq::MmapReader::new("./data/subset.vbq")?;
+        let (header, expected) = (reader.header(), reader.num_records()?);
+        let (num_records, bytes) = pipe(BinseqReader::Vbq(reader))?;
+        assert_eq!(num_records, expected as u64);
+
+        let reader = vbq_from_reader(Cursor::new(bytes))?;
+        assert_eq!(reader.header(), header);
+        assert_eq!(reader.num_records()?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_paired_bq_is_combined() -> Result<()> {
+        let mut files = Vec::new();
+        for (name, seq) in [("r1", b"ACGTACGTAC"), ("r2", b"TTTTGGGGCC")] {
+            let header = bq::FileHeaderBuilder::new().slen(10).build()?;
+            let mut writer = bq::WriterBuilder::default()
+                .header(header)
+                .build(Vec::new())?;
+            for _ in 0..5 {
+                writer.push(SequencingRecordBuilder::default().s_seq(seq).build()?)?;
+            }
+            let path = std::env::temp_dir().join(format!("binseq_test_pipe_{name}.bq"));
+            std::fs::write(&path, writer.into_inner())?;
+            files.push(path);
+        }
+
+        let reader = BinseqReader::new_paired(&files[0], &files[1])?;
+        let (num_records, bytes) = pipe(reader)?;
+        assert_eq!(num_records, 5);
+
+        let reader = bq::MmapReader::from_bytes(bytes)?;
+        assert!(reader.is_paired());
+        assert_eq!(reader.num_records(), 5);
+        let record = reader.get(4)?;
+        assert_eq!(record.decode_s_alloc()?, b"ACGTACGTAC");
+        assert_eq!(record.decode_x_alloc()?, b"TTTTGGGGCC");
+
+        for path in files {
+            std::fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+}
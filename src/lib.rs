@@ -65,7 +65,7 @@
 //!     let path = "./data/subset.bq";
 //!
 //!     // open a reader
-//!     let reader = BinseqReader::new(path)?;
+//!     let reader = binseq::open(path)?;
 //!
 //!     // initialize a processor
 //!     let processor = Processor::default();
@@ -75,24 +75,101 @@
 //!     Ok(())
 //! }
 //! ```
+//!
+//! # Example: Writing Records
+//!
+//! ```
+//! use binseq::prelude::*;
+//!
+//! fn main() -> binseq::Result<()> {
+//!     let path = std::env::temp_dir().join("binseq_doctest_lib.vbq");
+//!
+//!     // create a writer and push a record
+//!     let mut writer = binseq::create_vbq(&path, WriterOpts::default())?;
+//!     writer.push(SequencingRecordBuilder::default().s_seq(b"ACGTACGT").build()?)?;
+//!     writer.finish()?;
+//! #   drop(writer);
+//!
+//!     // read it back
+//!     let reader = binseq::open(&path)?;
+//!     assert_eq!(reader.num_records()?, 1);
+//! #   std::fs::remove_file(&path)?;
+//!     Ok(())
+//! }
+//! ```
 
 #![allow(clippy::module_inception)]
 
 /// BQ - fixed length records, no quality scores
 pub mod bq;
 
+/// Container-independent content digests
+#[cfg(feature = "digest")]
+mod digest;
+
+/// Checkpointing of long parallel scans
+mod checkpoint;
+
+/// Shorthands for opening and creating BINSEQ files
+mod convenience;
+
+/// Conversion of FASTQ files to BINSEQ with read filtering
+#[cfg(feature = "paraseq")]
+pub mod convert;
+
+/// Exact-sequence deduplication
+mod dedup;
+
+/// Record-level comparison of BQ files
+pub mod diff;
+
 /// Error definitions
 pub mod error;
 
+/// Reusable worker threads for parallel processing
+mod executor;
+
+/// Piping BINSEQ files between processes
+pub mod io;
+
+/// Diagnostics for magic number mismatches
+mod magic;
+
 /// Parallel processing
 mod parallel;
 
 /// Invalid nucleotide policy
 mod policy;
 
+/// Ready-made parallel processors and combinators
+pub mod processors;
+
 /// Record types and traits shared between BINSEQ variants
 mod record;
 
+/// Work-stealing queues for parallel processing
+#[cfg(feature = "work-stealing")]
+mod stealing;
+
+/// External merge sort of records by flag or sequence
+mod sort;
+
+/// Byte sources backing the readers
+mod source;
+
+/// Lock-free per-thread state for parallel processors
+mod storage;
+
+/// Synthetic datasets and round-trip helpers for tests
+#[doc(hidden)]
+pub mod testing;
+
+/// Text re-serialization of records for tools reading FASTQ, FASTA or TSV
+mod text;
+
+/// Streaming conversion of fixed-length VBQ files to BQ
+mod to_bq;
+
 /// VBQ - Variable length records, optional quality scores, compressed blocks
 pub mod vbq;
 
@@ -108,11 +185,26 @@ pub mod write;
 /// Utilities for working with BINSEQ files
 pub mod utils;
 
+pub use checkpoint::{CheckpointStore, FileCheckpoint};
+pub use convenience::{WriterOpts, create_bq, create_vbq, open};
+pub use dedup::{DedupOptions, DedupStats, dedup};
+#[cfg(feature = "digest")]
+pub use digest::{DIGEST_CHUNK_SIZE, DigestAlgo, DigestFields, digest, digest_with};
 pub use error::{Error, IntoBinseqError, Result};
-pub use parallel::{BinseqReader, ParallelProcessor, ParallelReader};
-pub use policy::{Policy, RNG_SEED};
-pub use record::{BinseqRecord, SequencingRecord, SequencingRecordBuilder};
-pub use write::{BinseqWriter, BinseqWriterBuilder};
+pub use executor::Executor;
+pub use parallel::{
+    BinseqReader, ErrorAction, HeaderInfo, ParallelProcessor, ParallelReader, ScratchBuffers,
+};
+pub use policy::{Correction, Policy, PolicyBuilder, PolicyConstraints, RNG_SEED};
+pub use record::{
+    BinseqRecord, CanonicalKmers, MAX_KMER_SIZE, MateRecord, Minimizers, OwnedRecord, PHRED_OFFSET,
+    RefRecordPair, SequencingRecord, SequencingRecordBuilder, encode_sequence,
+};
+pub use sort::{DEFAULT_SORT_MEMORY, MAX_SORT_PREFIX, SortKey, SortOptions, sort, sort_bq_file};
+pub use storage::{SlotGuard, ThreadLocalStorage};
+pub use text::{PairedMode, TextAdapter, TextFormat, TsvField};
+pub use to_bq::{VbqToBqOptions, VbqToBqStats, vbq_to_bq};
+pub use write::{BinseqWriter, BinseqWriterBuilder, RecordWriter};
 
 /// Re-export `bitnuc::BitSize`
 pub use bitnuc::BitSize;
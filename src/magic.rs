@@ -0,0 +1,102 @@
+use crate::{
+    Error, bq, cbq,
+    error::{HeaderError, ReadError},
+    vbq,
+};
+
+const VBQ_BLOCK_MAGIC: [u8; 8] = vbq::BLOCK_MAGIC.to_le_bytes();
+const VBQ_INDEX_MAGIC: [u8; 8] = vbq::INDEX_MAGIC.to_le_bytes();
+
+/// Magic bytes of the structures of the BINSEQ family
+const BINSEQ_MAGICS: [(&[u8], &str); 5] = [
+    (&bq::FILE_MAGIC, "BQ file"),
+    (&vbq::FILE_MAGIC, "VBQ file"),
+    (cbq::FILE_MAGIC, "CBQ file"),
+    (&VBQ_BLOCK_MAGIC, "VBQ block"),
+    (&VBQ_INDEX_MAGIC, "VBQ index"),
+];
+
+/// Leading bytes of common formats that are mistaken for BINSEQ files
+const OTHER_FORMATS: [(&[u8], &str); 4] = [
+    (b"\x1f\x8b", "gzip"),
+    (b"BAM\x01", "BAM"),
+    (b"@", "FASTQ"),
+    (b">", "FASTA"),
+];
+
+/// Explains a magic number mismatch when the bytes are recognizable
+///
+/// `bytes` start where a structure named `expected` (e.g. `"VBQ block"`) was expected, at
+/// byte `offset` of the input. Returns a [`HeaderError::MismatchedFormat`] if they start
+/// with the magic of another BINSEQ structure, or a [`ReadError::LooksLikeOtherFormat`] if
+/// the input starts like a common non-BINSEQ format. Returns `None` if the bytes are not
+/// recognized, leaving the caller to report the invalid magic number.
+pub(crate) fn diagnose(bytes: &[u8], expected: &'static str, offset: usize) -> Option<Error> {
+    if let Some(&(_, found)) = BINSEQ_MAGICS
+        .iter()
+        .find(|(magic, name)| *name != expected && bytes.starts_with(magic))
+    {
+        return Some(
+            HeaderError::MismatchedFormat {
+                found,
+                expected,
+                offset,
+            }
+            .into(),
+        );
+    }
+
+    // Other formats can only be recognized at the start of the input
+    if offset != 0 {
+        return None;
+    }
+    OTHER_FORMATS
+        .iter()
+        .find(|(prefix, _)| bytes.starts_with(prefix))
+        .map(|&(_, format)| ReadError::LooksLikeOtherFormat(format).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diagnose_binseq_magic() {
+        let error = diagnose(&bq::FILE_MAGIC, "VBQ file", 0);
+        assert!(matches!(
+            error,
+            Some(Error::HeaderError(HeaderError::MismatchedFormat {
+                found: "BQ file",
+                expected: "VBQ file",
+                offset: 0
+            }))
+        ));
+        let error = diagnose(&VBQ_INDEX_MAGIC, "VBQ block", 512);
+        assert!(matches!(
+            error,
+            Some(Error::HeaderError(HeaderError::MismatchedFormat {
+                found: "VBQ index",
+                offset: 512,
+                ..
+            }))
+        ));
+    }
+
+    #[test]
+    fn test_diagnose_other_formats() {
+        for (bytes, format) in [
+            (&b"\x1f\x8b\x08\x00"[..], "gzip"),
+            (b"BAM\x01\x00", "BAM"),
+            (b"@read_1\nACGT", "FASTQ"),
+            (b">chr1\nACGT", "FASTA"),
+        ] {
+            let error = diagnose(bytes, "BQ file", 0);
+            assert!(
+                matches!(error, Some(Error::ReadError(ReadError::LooksLikeOtherFormat(f))) if f == format)
+            );
+            // Only the start of the input is checked for other formats
+            assert!(diagnose(bytes, "VBQ block", 64).is_none());
+        }
+        assert!(diagnose(b"\x00\x01\x02\x03", "BQ file", 0).is_none());
+    }
+}
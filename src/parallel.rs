@@ -1,11 +1,14 @@
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
 use std::fs::File;
 use std::io::Read as _;
 use std::ops::Range;
 use std::path::Path;
+use std::sync::Arc;
 
 use crate::{
-    BinseqRecord, Result, bq, cbq,
-    error::{FormatError, ReadError},
+    BinseqRecord, Executor, Result, bq, cbq,
+    error::{Error, FormatError, ReadError},
     vbq,
     write::Format,
 };
@@ -25,12 +28,35 @@ fn sniff_format<P: AsRef<Path>>(path: P) -> Result<Format> {
     })
 }
 
+/// Determines the BINSEQ format of in-memory file contents by inspecting their leading magic bytes.
+fn sniff_bytes(data: &[u8]) -> Result<Format> {
+    Format::sniff(&data[..data.len().min(MAGIC_PEEK_LEN)])
+        .ok_or_else(|| FormatError::UnrecognizedMagicBytes("<memory>".to_string()).into())
+}
+
+/// Header properties of any BINSEQ file, see [`BinseqReader::header_info`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HeaderInfo {
+    /// Fixed length of the primary sequences (BQ only)
+    pub slen: Option<u32>,
+
+    /// Whether records have an extended sequence
+    pub is_paired: bool,
+
+    /// Whether records store quality scores
+    pub has_quality: bool,
+
+    /// Whether the record data is compressed (always for CBQ)
+    pub has_compression: bool,
+}
+
 /// An enum abstraction for BINSEQ readers that can process records in parallel
 ///
 /// This is a convenience enum that can be used for general workflows where the
 /// distinction between BINSEQ readers is not important.
 ///
-/// For more specialized workflows see [`bq::MmapReader`], [`vbq::MmapReader`], and [`cbq::MmapReader`].
+/// For more specialized workflows see [`bq::MmapReader`], [`vbq::MmapReader`], [`cbq::MmapReader`],
+/// and [`bq::PairedReader`].
 // `cbq::MmapReader` is intrinsically larger than the other variants (it holds a reusable
 // `ColumnarBlock` decode buffer). Boxing it would shrink this enum but is a breaking change to
 // the variant's public field type, so it's left as-is rather than churn downstream consumers.
@@ -39,6 +65,7 @@ pub enum BinseqReader {
     Bq(bq::MmapReader),
     Vbq(vbq::MmapReader),
     Cbq(cbq::MmapReader),
+    PairedBq(bq::PairedReader),
 }
 impl BinseqReader {
     pub fn new<P: AsRef<Path>>(path: P) -> Result<Self> {
@@ -49,12 +76,37 @@ impl BinseqReader {
         }
     }
 
+    /// Opens the R1 and R2 files of a paired dataset stored as two single-end BQ files
+    ///
+    /// Records are processed as pairs, see [`bq::PairedReader`].
+    pub fn new_paired<P: AsRef<Path>, Q: AsRef<Path>>(r1: P, r2: Q) -> Result<Self> {
+        Ok(Self::PairedBq(bq::PairedReader::new(r1, r2)?))
+    }
+
+    /// Creates a reader over the contents of a BINSEQ file held in memory
+    ///
+    /// The format is determined from the magic bytes, as in [`BinseqReader::new`].
+    pub fn from_bytes(data: Vec<u8>) -> Result<Self> {
+        Self::from_arc_bytes(data.into())
+    }
+
+    /// Creates a reader over shared in-memory contents of a BINSEQ file
+    ///
+    /// See [`BinseqReader::from_bytes`].
+    pub fn from_arc_bytes(data: Arc<[u8]>) -> Result<Self> {
+        match sniff_bytes(&data)? {
+            Format::Bq => Ok(Self::Bq(bq::MmapReader::from_arc_bytes(data)?)),
+            Format::Vbq => Ok(Self::Vbq(vbq::MmapReader::from_arc_bytes(data)?)),
+            Format::Cbq => Ok(Self::Cbq(cbq::MmapReader::from_arc_bytes(data)?)),
+        }
+    }
+
     /// Set whether to decode sequences at once in each block
     ///
     /// Note: This setting applies to VBQ readers only.
     pub fn set_decode_block(&mut self, decode_block: bool) {
         match self {
-            Self::Bq(_) | Self::Cbq(_) => {
+            Self::Bq(_) | Self::Cbq(_) | Self::PairedBq(_) => {
                 // no-op
             }
             Self::Vbq(reader) => reader.set_decode_block(decode_block),
@@ -66,6 +118,7 @@ impl BinseqReader {
             Self::Bq(reader) => reader.set_default_quality_score(score),
             Self::Vbq(reader) => reader.set_default_quality_score(score),
             Self::Cbq(reader) => reader.set_default_quality_score(score),
+            Self::PairedBq(reader) => reader.set_default_quality_score(score),
         }
     }
 
@@ -75,6 +128,7 @@ impl BinseqReader {
             Self::Bq(reader) => reader.is_paired(),
             Self::Vbq(reader) => reader.is_paired(),
             Self::Cbq(reader) => reader.is_paired(),
+            Self::PairedBq(_) => true,
         }
     }
 
@@ -83,6 +137,47 @@ impl BinseqReader {
             Self::Bq(reader) => Ok(reader.num_records()),
             Self::Vbq(reader) => reader.num_records(),
             Self::Cbq(reader) => Ok(reader.num_records()),
+            Self::PairedBq(reader) => Ok(reader.num_records()),
+        }
+    }
+
+    /// Returns the properties of the file header shared by all BINSEQ variants
+    #[must_use]
+    pub fn header_info(&self) -> HeaderInfo {
+        match self {
+            Self::Bq(reader) => {
+                let header = reader.header();
+                HeaderInfo {
+                    slen: Some(header.slen),
+                    is_paired: header.is_paired(),
+                    has_quality: false,
+                    has_compression: false,
+                }
+            }
+            Self::Vbq(reader) => {
+                let header = reader.header();
+                HeaderInfo {
+                    slen: None,
+                    is_paired: header.paired,
+                    has_quality: header.qual,
+                    has_compression: header.compressed,
+                }
+            }
+            Self::Cbq(reader) => {
+                let header = reader.header();
+                HeaderInfo {
+                    slen: None,
+                    is_paired: header.is_paired(),
+                    has_quality: header.has_qualities(),
+                    has_compression: true,
+                }
+            }
+            Self::PairedBq(reader) => HeaderInfo {
+                slen: Some(reader.r1().header().slen),
+                is_paired: true,
+                has_quality: false,
+                has_compression: false,
+            },
         }
     }
 
@@ -113,6 +208,7 @@ impl BinseqReader {
             Self::Bq(reader) => reader.process_parallel_range(processor, num_threads, range),
             Self::Vbq(reader) => reader.process_parallel_range(processor, num_threads, range),
             Self::Cbq(reader) => reader.process_parallel_range(processor, num_threads, range),
+            Self::PairedBq(reader) => reader.process_parallel_range(processor, num_threads, range),
         }
     }
 }
@@ -123,6 +219,10 @@ impl ParallelReader for BinseqReader {
         num_threads: usize,
     ) -> Result<()> {
         let num_records = self.num_records()?;
+        if num_records == 0 {
+            // Nothing to process in an empty file
+            return Ok(());
+        }
         self.process_parallel_range(processor, num_threads, 0..num_records)
     }
 
@@ -136,6 +236,21 @@ impl ParallelReader for BinseqReader {
             Self::Bq(reader) => reader.process_parallel_range(processor, num_threads, range),
             Self::Vbq(reader) => reader.process_parallel_range(processor, num_threads, range),
             Self::Cbq(reader) => reader.process_parallel_range(processor, num_threads, range),
+            Self::PairedBq(reader) => reader.process_parallel_range(processor, num_threads, range),
+        }
+    }
+
+    fn process_parallel_in<P: ParallelProcessor + Clone + 'static>(
+        self,
+        executor: &Executor,
+        processor: P,
+        range: Range<usize>,
+    ) -> Result<()> {
+        match self {
+            Self::Bq(reader) => reader.process_parallel_in(executor, processor, range),
+            Self::Vbq(reader) => reader.process_parallel_in(executor, processor, range),
+            Self::Cbq(reader) => reader.process_parallel_in(executor, processor, range),
+            Self::PairedBq(reader) => reader.process_parallel_in(executor, processor, range),
         }
     }
 }
@@ -174,6 +289,31 @@ pub trait ParallelReader {
         range: Range<usize>,
     ) -> Result<()>;
 
+    /// Process records in parallel within a specified range on a reusable [`Executor`]
+    ///
+    /// This behaves like [`process_parallel_range`](Self::process_parallel_range) with
+    /// `executor.num_threads()` threads, but runs on the executor's worker threads instead of
+    /// spawning new ones, which avoids the thread startup cost when processing many small ranges.
+    ///
+    /// The default implementation falls back to spawning threads per call.
+    ///
+    /// # Arguments
+    ///
+    /// * `executor` - The executor whose worker threads run the processors
+    /// * `processor` - The processor to use for each record
+    /// * `range` - The range of record indices to process
+    fn process_parallel_in<P: ParallelProcessor + Clone + 'static>(
+        self,
+        executor: &Executor,
+        processor: P,
+        range: Range<usize>,
+    ) -> Result<()>
+    where
+        Self: Sized,
+    {
+        self.process_parallel_range(processor, executor.num_threads(), range)
+    }
+
     /// Validate the specified range for the file.
     ///
     /// This method checks if the provided range is valid for the file, ensuring that
@@ -214,6 +354,90 @@ pub trait ParallelReader {
     }
 }
 
+/// What to do after [`ParallelProcessor::process_record`] returned an error
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ErrorAction {
+    /// Stop processing and return the error (default)
+    #[default]
+    Abort,
+
+    /// Skip the record and continue with the next one
+    Continue,
+}
+
+/// Reusable buffers owned by the worker loop of a parallel reader, one per thread
+///
+/// The readers create one `ScratchBuffers` per thread and pass it to
+/// [`ParallelProcessor::process_record_with`] for every record, so processors decoding or
+/// copying record data can reuse its allocations instead of keeping their own. The buffers
+/// keep whatever the previous record left in them; clear them before use.
+///
+/// Further per-thread state of any type can be kept in the typed slots of
+/// [`get_or_default`](Self::get_or_default).
+#[derive(Default)]
+pub struct ScratchBuffers {
+    /// Buffer for decoded sequences
+    pub decode: Vec<u8>,
+
+    /// Buffer for quality scores
+    pub qual: Vec<u8>,
+
+    /// Buffer for record headers
+    pub header: Vec<u8>,
+
+    /// User-defined slots, one per type
+    slots: HashMap<TypeId, Box<dyn Any + Send>>,
+}
+impl ScratchBuffers {
+    /// Clears the decode, quality and header buffers, keeping their capacity
+    ///
+    /// The typed slots are left untouched.
+    pub fn clear(&mut self) {
+        self.decode.clear();
+        self.qual.clear();
+        self.header.clear();
+    }
+
+    /// Returns the slot of type `T`, creating it with `T::default()` on first use
+    pub fn get_or_default<T: Any + Send + Default>(&mut self) -> &mut T {
+        self.slots
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| Box::new(T::default()))
+            .downcast_mut()
+            .expect("slots are keyed by the type they hold")
+    }
+
+    /// Returns the slot of type `T` if it was created
+    pub fn get_mut<T: Any + Send>(&mut self) -> Option<&mut T> {
+        self.slots
+            .get_mut(&TypeId::of::<T>())
+            .and_then(|slot| slot.downcast_mut())
+    }
+}
+
+/// Processes a record, letting the processor decide whether an error is fatal
+///
+/// Used by the parallel readers in place of calling
+/// [`process_record_with`](ParallelProcessor::process_record_with) directly.
+pub(crate) fn process_or_skip<P, R>(
+    processor: &mut P,
+    record: R,
+    scratch: &mut ScratchBuffers,
+) -> Result<()>
+where
+    P: ParallelProcessor,
+    R: BinseqRecord,
+{
+    let record_idx = record.index();
+    match processor.process_record_with(record, scratch) {
+        Ok(()) => Ok(()),
+        Err(e) => match processor.on_error(record_idx, &e) {
+            ErrorAction::Continue => Ok(()),
+            ErrorAction::Abort => Err(e),
+        },
+    }
+}
+
 /// Trait for types that can process records in parallel.
 ///
 /// This is implemented by the **processor** not by the **reader**.
@@ -222,13 +446,56 @@ pub trait ParallelProcessor: Send + Clone {
     /// Process a single record
     fn process_record<R: BinseqRecord>(&mut self, record: R) -> Result<()>;
 
+    /// Process a single record with the scratch buffers of the current thread
+    ///
+    /// The parallel readers call this method for every record, passing the same
+    /// [`ScratchBuffers`] for all records of a thread. Processors that need temporary buffers
+    /// can implement it instead of keeping their own; adapters wrapping another processor
+    /// should forward `scratch` to it.
+    ///
+    /// Default implementation calls [`process_record`](Self::process_record)
+    #[allow(unused_variables)]
+    fn process_record_with<R: BinseqRecord>(
+        &mut self,
+        record: R,
+        scratch: &mut ScratchBuffers,
+    ) -> Result<()> {
+        self.process_record(record)
+    }
+
     /// Called when a thread finishes processing its batch
+    ///
+    /// It is called exactly once after each batch, including a final partial batch. Batches
+    /// are blocks for VBQ and CBQ files, and runs of
+    /// [`bq::MmapReader::batch_size`](crate::bq::MmapReader::batch_size) records for BQ files.
+    ///
     /// Default implementation does nothing
     #[allow(unused_variables)]
     fn on_batch_complete(&mut self) -> Result<()> {
         Ok(())
     }
 
+    /// Called when [`process_record`](Self::process_record) returns an error
+    ///
+    /// Returning [`ErrorAction::Continue`] skips the record and keeps processing, returning
+    /// [`ErrorAction::Abort`] stops processing and returns the error from the reader.
+    /// Processors that continue past errors should count the skipped records here and
+    /// report them through [`skipped_records`](Self::skipped_records).
+    ///
+    /// Default implementation aborts on every error
+    #[allow(unused_variables)]
+    fn on_error(&mut self, record_idx: u64, error: &Error) -> ErrorAction {
+        ErrorAction::Abort
+    }
+
+    /// Returns the number of records skipped after [`on_error`](Self::on_error) returned
+    /// [`ErrorAction::Continue`]
+    ///
+    /// Default implementation returns 0
+    fn skipped_records(&self) -> u64 {
+        0
+    }
+
     /// Called when a thread finished processing all its batches
     /// Default implementation does nothing
     #[allow(unused_variables)]
@@ -278,6 +545,47 @@ mod testing {
         std::fs::remove_file(&no_ext).unwrap();
     }
 
+    #[test]
+    fn test_num_records_and_header_info() -> Result<()> {
+        let bq_reader = bq::MmapReader::new("./data/subset.bq")?;
+        let reader = BinseqReader::new("./data/subset.bq")?;
+        assert_eq!(reader.num_records()?, bq_reader.num_records());
+        assert_eq!(reader.is_paired(), bq_reader.is_paired());
+        assert_eq!(
+            reader.header_info(),
+            HeaderInfo {
+                slen: Some(bq_reader.header().slen),
+                is_paired: true,
+                has_quality: false,
+                has_compression: false,
+            }
+        );
+
+        let vbq_reader = vbq::MmapReader::new("./data/subset.vbq")?;
+        let reader = BinseqReader::new("./data/subset.vbq")?;
+        assert_eq!(reader.num_records()?, vbq_reader.num_records()?);
+        assert_eq!(reader.is_paired(), vbq_reader.is_paired());
+        let header = vbq_reader.header();
+        assert_eq!(
+            reader.header_info(),
+            HeaderInfo {
+                slen: None,
+                is_paired: header.paired,
+                has_quality: header.qual,
+                has_compression: header.compressed,
+            }
+        );
+
+        let cbq_reader = cbq::MmapReader::new("./data/subset.cbq")?;
+        let reader = BinseqReader::new("./data/subset.cbq")?;
+        assert_eq!(reader.num_records()?, cbq_reader.num_records());
+        assert_eq!(
+            reader.header_info().has_quality,
+            cbq_reader.header().has_qualities()
+        );
+        Ok(())
+    }
+
     #[test]
     fn test_new_unrecognized_file_errors() {
         let dir = std::env::temp_dir();
@@ -310,6 +618,143 @@ mod testing {
         }
     }
 
+    /// Fails on every record with an odd index
+    #[derive(Clone, Default)]
+    struct FailingProcessor {
+        skip: bool,
+        local_skipped: u64,
+        n_records: Arc<Mutex<usize>>,
+        skipped: Arc<Mutex<u64>>,
+    }
+    impl ParallelProcessor for FailingProcessor {
+        fn process_record<R: BinseqRecord>(&mut self, record: R) -> Result<()> {
+            if record.index() % 2 == 1 {
+                return Err(ReadError::UnexpectedEndOfFile(record.index() as usize).into());
+            }
+            *self.n_records.lock() += 1;
+            Ok(())
+        }
+
+        fn on_error(&mut self, _record_idx: u64, _error: &Error) -> ErrorAction {
+            if self.skip {
+                self.local_skipped += 1;
+                ErrorAction::Continue
+            } else {
+                ErrorAction::Abort
+            }
+        }
+
+        fn skipped_records(&self) -> u64 {
+            *self.skipped.lock()
+        }
+
+        fn on_thread_complete(&mut self) -> Result<()> {
+            *self.skipped.lock() += self.local_skipped;
+            self.local_skipped = 0;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_parallel_processor_on_error() {
+        for ext in ["bq", "vbq", "cbq"] {
+            eprintln!("Testing {ext}");
+            let reader = BinseqReader::new(format!("./data/subset.{ext}")).unwrap();
+            let num_records = reader.num_records().unwrap();
+
+            let processor = FailingProcessor::default();
+            assert!(reader.process_parallel(processor, 4).is_err());
+
+            let processor = FailingProcessor {
+                skip: true,
+                ..Default::default()
+            };
+            reader.process_parallel(processor.clone(), 4).unwrap();
+            assert_eq!(processor.skipped_records() as usize, num_records / 2);
+            assert_eq!(*processor.n_records.lock(), num_records - num_records / 2);
+        }
+    }
+
+    /// Counts records in a typed scratch slot, which lives as long as the thread
+    #[derive(Clone, Default)]
+    struct ScratchProcessor {
+        local_records: u64,
+        slot_records: u64,
+        n_records: Arc<Mutex<u64>>,
+        n_slot_records: Arc<Mutex<u64>>,
+    }
+    impl ParallelProcessor for ScratchProcessor {
+        fn process_record<R: BinseqRecord>(&mut self, _record: R) -> Result<()> {
+            unreachable!("the readers call process_record_with")
+        }
+
+        fn process_record_with<R: BinseqRecord>(
+            &mut self,
+            record: R,
+            scratch: &mut ScratchBuffers,
+        ) -> Result<()> {
+            scratch.clear();
+            record.decode_s(&mut scratch.decode)?;
+            assert_eq!(scratch.decode.len() as u64, record.slen());
+
+            let count = scratch.get_or_default::<u64>();
+            *count += 1;
+            self.slot_records = *count;
+            self.local_records += 1;
+            Ok(())
+        }
+
+        fn on_thread_complete(&mut self) -> Result<()> {
+            *self.n_records.lock() += self.local_records;
+            *self.n_slot_records.lock() += self.slot_records;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_parallel_processor_scratch_buffers() {
+        for ext in ["bq", "vbq", "cbq"] {
+            eprintln!("Testing {ext}");
+            let reader = BinseqReader::new(format!("./data/subset.{ext}")).unwrap();
+            let num_records = reader.num_records().unwrap() as u64;
+
+            let processor = ScratchProcessor::default();
+            reader.process_parallel(processor.clone(), 4).unwrap();
+            assert_eq!(*processor.n_records.lock(), num_records);
+            assert_eq!(*processor.n_slot_records.lock(), num_records);
+        }
+
+        let mut scratch = ScratchBuffers::default();
+        assert!(scratch.get_mut::<String>().is_none());
+        scratch.get_or_default::<String>().push_str("kept");
+        assert_eq!(scratch.get_mut::<String>().unwrap(), "kept");
+    }
+
+    #[test]
+    fn test_tee_processor_on_error() {
+        use crate::processors::{CountProcessor, TeeProcessor};
+
+        for ext in ["bq", "vbq", "cbq"] {
+            eprintln!("Testing {ext}");
+            let reader = BinseqReader::new(format!("./data/subset.{ext}")).unwrap();
+            let num_records = reader.num_records().unwrap();
+
+            let tee = TeeProcessor::new(CountProcessor::new(), FailingProcessor::default());
+            assert!(reader.process_parallel(tee, 4).is_err());
+
+            let counter = CountProcessor::new();
+            let failing = FailingProcessor {
+                skip: true,
+                ..Default::default()
+            };
+            let tee = TeeProcessor::new(counter.clone(), failing.clone());
+            reader.process_parallel(tee.clone(), 4).unwrap();
+            assert_eq!(tee.skipped_records() as usize, num_records / 2);
+            assert_eq!(counter.count() as usize, num_records);
+            assert_eq!(*failing.n_records.lock(), num_records - num_records / 2);
+        }
+    }
+
     #[test]
     fn test_parallel_processor_range() {
         for ext in ["bq", "vbq", "cbq"] {
@@ -394,4 +839,24 @@ mod testing {
             assert_eq!(*processor.n_records.lock(), num_records);
         }
     }
+
+    #[test]
+    fn test_from_bytes_matches_path_reader() {
+        for ext in ["bq", "vbq", "cbq"] {
+            eprintln!("Testing {ext}");
+            let path = format!("./data/subset.{ext}");
+            let expected = BinseqReader::new(&path).unwrap().num_records().unwrap();
+            let reader = BinseqReader::from_bytes(std::fs::read(&path).unwrap()).unwrap();
+            assert_eq!(reader.num_records().unwrap(), expected);
+            let processor = TestProcessor::default();
+            assert!(reader.process_parallel(processor.clone(), 0).is_ok());
+            assert_eq!(*processor.n_records.lock(), expected);
+        }
+    }
+
+    #[test]
+    fn test_from_bytes_unrecognized_errors() {
+        assert!(BinseqReader::from_bytes(b"not a binseq file at all".to_vec()).is_err());
+        assert!(BinseqReader::from_bytes(Vec::new()).is_err());
+    }
 }
@@ -4,6 +4,9 @@
 //! during encoding operations. Different policies allow for ignoring, rejecting,
 //! or correcting sequences with invalid nucleotides.
 
+use std::ops::Range;
+
+use bitnuc::BitSize;
 use rand::Rng;
 
 use crate::error::{Result, WriteError};
@@ -45,8 +48,179 @@ pub enum Policy {
 
     /// Replace all invalid nucleotides with 'T'
     SetToT,
+
+    /// Correct invalid nucleotides with `correction` only if they satisfy `constraints`
+    ///
+    /// Sequences whose invalid nucleotides violate the constraints are skipped. Built with
+    /// [`PolicyBuilder`].
+    Constrained {
+        /// Substitution applied to the invalid nucleotides
+        correction: Correction,
+
+        /// Limits on the invalid nucleotides that are corrected
+        constraints: PolicyConstraints,
+    },
+}
+
+/// Substitution strategy for the invalid nucleotides of a [`Policy::Constrained`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Correction {
+    /// Replace invalid nucleotides with randomly chosen valid nucleotides
+    RandomDraw,
+
+    /// Replace all invalid nucleotides with 'A'
+    SetToA,
+
+    /// Replace all invalid nucleotides with 'C'
+    SetToC,
+
+    /// Replace all invalid nucleotides with 'G'
+    SetToG,
+
+    /// Replace all invalid nucleotides with 'T'
+    SetToT,
+}
+impl Correction {
+    /// Writes `sequence` with its invalid nucleotides replaced to `ibuf`
+    fn apply<R: Rng>(self, sequence: &[u8], ibuf: &mut Vec<u8>, rng: &mut R) {
+        match self {
+            Self::RandomDraw => Policy::fill_with_random(sequence, rng, ibuf),
+            Self::SetToA => Policy::fill_with_known(sequence, b'A', ibuf),
+            Self::SetToC => Policy::fill_with_known(sequence, b'C', ibuf),
+            Self::SetToG => Policy::fill_with_known(sequence, b'G', ibuf),
+            Self::SetToT => Policy::fill_with_known(sequence, b'T', ibuf),
+        }
+    }
+}
+
+/// Limits on the invalid nucleotides corrected by a [`Policy::Constrained`]
+///
+/// A sequence satisfies the constraints if it has at most `max_invalid` invalid nucleotides
+/// and all of them lie within `allowed_region`. Unset limits are not enforced.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PolicyConstraints {
+    /// Maximum number of invalid nucleotides per sequence
+    pub max_invalid: Option<usize>,
+
+    /// Positions `(start, end)` (end exclusive) that may hold invalid nucleotides
+    pub allowed_region: Option<(usize, usize)>,
+}
+impl PolicyConstraints {
+    /// Returns `true` if the invalid nucleotides of `sequence` satisfy the constraints
+    #[must_use]
+    pub fn allows(&self, sequence: &[u8]) -> bool {
+        let mut n_invalid = 0;
+        for (pos, &n) in sequence.iter().enumerate() {
+            if matches!(n, b'A' | b'C' | b'G' | b'T') {
+                continue;
+            }
+            n_invalid += 1;
+            if self.max_invalid.is_some_and(|max| n_invalid > max)
+                || self
+                    .allowed_region
+                    .is_some_and(|(start, end)| !(start..end).contains(&pos))
+            {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// A builder for [`Policy::Constrained`]
+///
+/// Constraints only apply to correcting policies ([`Policy::RandomDraw`] and the
+/// `SetTo*` policies). Other policies are returned unchanged by [`build`](Self::build).
+///
+/// # Examples
+///
+/// ```
+/// use binseq::{Policy, PolicyBuilder};
+///
+/// // Fix up to 2 invalid nucleotides by random draw, skip the sequence if there are more
+/// let policy = PolicyBuilder::new(Policy::RandomDraw).max_invalid(2).build();
+///
+/// // Only tolerate invalid nucleotides in the last 5 cycles of 150bp reads
+/// let policy = PolicyBuilder::new(Policy::SetToA)
+///     .allowed_region(145..150)
+///     .build();
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct PolicyBuilder {
+    /// The policy to constrain
+    policy: Policy,
+
+    /// Constraints applied to the policy
+    constraints: PolicyConstraints,
+}
+impl PolicyBuilder {
+    /// Creates a builder constraining `policy`
+    #[must_use]
+    pub fn new(policy: Policy) -> Self {
+        Self {
+            policy,
+            constraints: PolicyConstraints::default(),
+        }
+    }
+
+    /// Sets the maximum number of invalid nucleotides corrected per sequence
+    #[must_use]
+    pub fn max_invalid(mut self, max_invalid: usize) -> Self {
+        self.constraints.max_invalid = Some(max_invalid);
+        self
+    }
+
+    /// Sets the positions of a sequence that may hold invalid nucleotides
+    #[must_use]
+    pub fn allowed_region(mut self, region: Range<usize>) -> Self {
+        self.constraints.allowed_region = Some((region.start, region.end));
+        self
+    }
+
+    /// Builds the constrained policy
+    #[must_use]
+    pub fn build(self) -> Policy {
+        match self.policy.correction() {
+            Some(correction) => Policy::Constrained {
+                correction,
+                constraints: self.constraints,
+            },
+            None => self.policy,
+        }
+    }
 }
+
 impl Policy {
+    /// Returns the substitution applied by a correcting policy
+    ///
+    /// Returns `None` for [`Policy::IgnoreSequence`] and [`Policy::BreakOnInvalid`].
+    #[must_use]
+    pub fn correction(&self) -> Option<Correction> {
+        match self {
+            Self::IgnoreSequence | Self::BreakOnInvalid => None,
+            Self::RandomDraw => Some(Correction::RandomDraw),
+            Self::SetToA => Some(Correction::SetToA),
+            Self::SetToC => Some(Correction::SetToC),
+            Self::SetToG => Some(Correction::SetToG),
+            Self::SetToT => Some(Correction::SetToT),
+            Self::Constrained { correction, .. } => Some(*correction),
+        }
+    }
+
+    /// Returns `true` if the policy corrects the invalid nucleotides of `sequence`
+    ///
+    /// This distinguishes sequences that are corrected within the constraints of a
+    /// [`Policy::Constrained`] from sequences that are skipped for violating them. Policies
+    /// without constraints correct every sequence if they correct at all.
+    #[must_use]
+    pub fn corrects(&self, sequence: &[u8]) -> bool {
+        match self {
+            Self::IgnoreSequence | Self::BreakOnInvalid => false,
+            Self::Constrained { constraints, .. } => constraints.allows(sequence),
+            _ => true,
+        }
+    }
+
     /// Helper method to replace invalid nucleotides with a specific nucleotide
     ///
     /// This internal method processes a sequence and replaces any non-standard
@@ -58,12 +232,7 @@ impl Policy {
     /// * `val` - The replacement nucleotide (should be one of A, C, G, or T)
     /// * `ibuf` - The output buffer to store the processed sequence
     fn fill_with_known(sequence: &[u8], val: u8, ibuf: &mut Vec<u8>) {
-        for &n in sequence {
-            ibuf.push(match n {
-                b'A' | b'C' | b'G' | b'T' => n,
-                _ => val,
-            });
-        }
+        Self::invalid_nucleotides(sequence, ibuf).for_each(|n| *n = val);
     }
 
     /// Helper method to replace invalid nucleotides with random valid nucleotides
@@ -81,18 +250,54 @@ impl Policy {
     ///
     /// * `R` - A type that implements the `Rng` trait from the `rand` crate
     fn fill_with_random<R: Rng>(sequence: &[u8], rng: &mut R, ibuf: &mut Vec<u8>) {
-        for &n in sequence {
-            ibuf.push(match n {
-                b'A' | b'C' | b'G' | b'T' => n,
-                _ => match rng.random_range(0..4) {
-                    0 => b'A',
-                    1 => b'C',
-                    2 => b'G',
-                    3 => b'T',
-                    _ => unreachable!(),
-                },
-            });
+        for n in Self::invalid_nucleotides(sequence, ibuf) {
+            *n = match rng.random_range(0..4) {
+                0 => b'A',
+                1 => b'C',
+                2 => b'G',
+                3 => b'T',
+                _ => unreachable!(),
+            };
+        }
+    }
+
+    /// Appends `sequence` to `ibuf` and returns its invalid nucleotides for substitution
+    ///
+    /// The sequence is copied in bulk, so only the invalid nucleotides are visited
+    /// individually (in order).
+    fn invalid_nucleotides<'a>(
+        sequence: &[u8],
+        ibuf: &'a mut Vec<u8>,
+    ) -> impl Iterator<Item = &'a mut u8> {
+        let start = ibuf.len();
+        ibuf.extend_from_slice(sequence);
+        ibuf[start..]
+            .iter_mut()
+            .filter(|n| !matches!(**n, b'A' | b'C' | b'G' | b'T'))
+    }
+
+    /// Encodes `sequence` into `ebuf`, applying the policy if it has invalid nucleotides
+    ///
+    /// Valid sequences are encoded directly. Otherwise the policy writes the corrected
+    /// sequence to `ibuf`, which is encoded in place of the original. Returns `false` if the
+    /// sequence should be skipped, in which case `ebuf` is left empty.
+    pub(crate) fn encode<R: Rng>(
+        &self,
+        bitsize: BitSize,
+        sequence: &[u8],
+        ibuf: &mut Vec<u8>,
+        ebuf: &mut Vec<u64>,
+        rng: &mut R,
+    ) -> Result<bool> {
+        if bitsize.encode(sequence, ebuf).is_ok() {
+            return Ok(true);
         }
+        ebuf.clear();
+        if !self.handle(sequence, ibuf, rng)? {
+            return Ok(false);
+        }
+        bitsize.encode(ibuf, ebuf)?;
+        Ok(true)
     }
 
     /// Process a sequence according to the selected policy for handling invalid nucleotides
@@ -147,23 +352,33 @@ impl Policy {
                 Err(WriteError::InvalidNucleotideSequence(seq_str).into())
             }
             Self::RandomDraw => {
-                Self::fill_with_random(sequence, rng, ibuf);
+                Correction::RandomDraw.apply(sequence, ibuf, rng);
                 Ok(true)
             }
             Self::SetToA => {
-                Self::fill_with_known(sequence, b'A', ibuf);
+                Correction::SetToA.apply(sequence, ibuf, rng);
                 Ok(true)
             }
             Self::SetToC => {
-                Self::fill_with_known(sequence, b'C', ibuf);
+                Correction::SetToC.apply(sequence, ibuf, rng);
                 Ok(true)
             }
             Self::SetToG => {
-                Self::fill_with_known(sequence, b'G', ibuf);
+                Correction::SetToG.apply(sequence, ibuf, rng);
                 Ok(true)
             }
             Self::SetToT => {
-                Self::fill_with_known(sequence, b'T', ibuf);
+                Correction::SetToT.apply(sequence, ibuf, rng);
+                Ok(true)
+            }
+            Self::Constrained {
+                correction,
+                constraints,
+            } => {
+                if !constraints.allows(sequence) {
+                    return Ok(false);
+                }
+                correction.apply(sequence, ibuf, rng);
                 Ok(true)
             }
         }
@@ -447,4 +662,160 @@ mod tests {
 
         assert_eq!(output, b"TTTTTT"); // All ambiguous codes replaced with T
     }
+
+    // ==================== Constrained Policy Tests ====================
+
+    #[test]
+    fn test_builder_ignores_non_correcting_policies() {
+        let policy = PolicyBuilder::new(Policy::IgnoreSequence)
+            .max_invalid(2)
+            .build();
+        assert!(matches!(policy, Policy::IgnoreSequence));
+
+        let policy = PolicyBuilder::new(Policy::SetToG)
+            .max_invalid(2)
+            .allowed_region(3..8)
+            .build();
+        assert!(matches!(
+            policy,
+            Policy::Constrained {
+                correction: Correction::SetToG,
+                constraints: PolicyConstraints {
+                    max_invalid: Some(2),
+                    allowed_region: Some((3, 8)),
+                },
+            }
+        ));
+    }
+
+    #[test]
+    fn test_constrained_max_invalid() {
+        let policy = PolicyBuilder::new(Policy::SetToA).max_invalid(2).build();
+        let mut output = Vec::new();
+        let mut rng = StdRng::seed_from_u64(RNG_SEED);
+
+        assert!(policy.handle(b"ACNGTN", &mut output, &mut rng).unwrap());
+        assert_eq!(output, b"ACAGTA");
+        assert!(!policy.handle(b"NCNGTN", &mut output, &mut rng).unwrap());
+        assert!(output.is_empty());
+    }
+
+    #[test]
+    fn test_constrained_allowed_region() {
+        let policy = PolicyBuilder::new(Policy::SetToT)
+            .allowed_region(5..8)
+            .build();
+        let mut output = Vec::new();
+        let mut rng = StdRng::seed_from_u64(RNG_SEED);
+
+        assert!(policy.handle(b"ACGTANNN", &mut output, &mut rng).unwrap());
+        assert_eq!(output, b"ACGTATTT");
+        assert!(!policy.handle(b"ACGTNACG", &mut output, &mut rng).unwrap());
+    }
+
+    #[test]
+    fn test_constrained_thresholds_property() {
+        let mut rng = StdRng::seed_from_u64(RNG_SEED);
+        for _ in 0..2000 {
+            let len = rng.random_range(1..200);
+            let max_invalid = rng.random_range(0..5);
+            let start = rng.random_range(0..len);
+            let end = rng.random_range(start..=len);
+            let policy = PolicyBuilder::new(Policy::RandomDraw)
+                .max_invalid(max_invalid)
+                .allowed_region(start..end)
+                .build();
+
+            let mut sequence: Vec<u8> = (0..len).map(|_| b"ACGT"[rng.random_range(0..4)]).collect();
+            let n_invalid = rng.random_range(0..8).min(len);
+            for _ in 0..n_invalid {
+                let pos = rng.random_range(0..len);
+                sequence[pos] = b'N';
+            }
+            let positions: Vec<usize> = (0..len).filter(|&i| sequence[i] == b'N').collect();
+            let expected = positions.len() <= max_invalid
+                && positions.iter().all(|pos| (start..end).contains(pos));
+
+            let mut output = Vec::new();
+            let corrected = policy.handle(&sequence, &mut output, &mut rng).unwrap();
+            assert_eq!(corrected, expected);
+            assert_eq!(policy.corrects(&sequence), expected);
+            if corrected {
+                assert_eq!(output.len(), len);
+                for (i, (&orig, &fixed)) in sequence.iter().zip(&output).enumerate() {
+                    assert!(b"ACGT".contains(&fixed));
+                    if !positions.contains(&i) {
+                        assert_eq!(orig, fixed);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_corrects_unconstrained() {
+        assert!(Policy::RandomDraw.corrects(b"NNNN"));
+        assert!(!Policy::IgnoreSequence.corrects(b"ACGN"));
+        assert!(!Policy::BreakOnInvalid.corrects(b"ACGN"));
+    }
+
+    #[test]
+    fn test_constrained_policy_in_writer() -> Result<()> {
+        use crate::SequencingRecordBuilder;
+        use crate::bq::{FileHeaderBuilder, MmapReader, WriterBuilder};
+
+        let header = FileHeaderBuilder::new().slen(8).build()?;
+        let mut writer = WriterBuilder::default()
+            .header(header)
+            .policy(PolicyBuilder::new(Policy::SetToC).max_invalid(1).build())
+            .build(Vec::new())?;
+        for (seq, written) in [
+            (b"ACGTACGN", true),
+            (b"ACNTACGN", false),
+            (b"ACGTACGT", true),
+        ] {
+            let record = SequencingRecordBuilder::default().s_seq(seq).build()?;
+            assert_eq!(writer.push(record)?, written);
+        }
+
+        let reader = MmapReader::from_bytes(writer.into_inner())?;
+        assert_eq!(reader.num_records(), 2);
+        assert_eq!(
+            crate::BinseqRecord::decode_s_alloc(&reader.get(0)?)?,
+            b"ACGTACGC"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_encode_patches_invalid_nucleotides() -> Result<()> {
+        let mut rng = StdRng::seed_from_u64(RNG_SEED);
+        let (mut ibuf, mut ebuf) = (Vec::new(), Vec::new());
+        let encoded = |seq: &[u8]| -> Result<Vec<u64>> {
+            let mut buf = Vec::new();
+            BitSize::Two.encode(seq, &mut buf)?;
+            Ok(buf)
+        };
+
+        // Valid sequences are encoded without touching the policy buffer
+        assert!(Policy::SetToG.encode(BitSize::Two, b"ACGT", &mut ibuf, &mut ebuf, &mut rng)?);
+        assert!(ibuf.is_empty());
+        assert_eq!(ebuf, encoded(b"ACGT")?);
+
+        ebuf.clear();
+        assert!(Policy::SetToG.encode(BitSize::Two, b"ANGTN", &mut ibuf, &mut ebuf, &mut rng)?);
+        assert_eq!(ibuf, b"AGGTG");
+        assert_eq!(ebuf, encoded(b"AGGTG")?);
+
+        ebuf.clear();
+        assert!(!Policy::IgnoreSequence.encode(
+            BitSize::Two,
+            b"ANGT",
+            &mut ibuf,
+            &mut ebuf,
+            &mut rng
+        )?);
+        assert!(ebuf.is_empty());
+        Ok(())
+    }
 }
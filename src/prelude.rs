@@ -1,4 +1,11 @@
 pub use super::{
-    BinseqReader, BinseqRecord, ParallelProcessor, ParallelReader, SequencingRecord,
-    SequencingRecordBuilder,
+    BinseqReader, BinseqRecord, BitSize, ErrorAction, ParallelProcessor, ParallelReader, Policy,
+    RefRecordPair, ScratchBuffers, SequencingRecord, SequencingRecordBuilder, WriterOpts,
+    create_bq, create_vbq, open,
 };
+
+/// Memory-mapped reader for BQ files
+pub use super::bq::MmapReader as BqMmapReader;
+
+/// Memory-mapped reader for VBQ files
+pub use super::vbq::MmapReader as VbqMmapReader;
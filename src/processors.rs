@@ -0,0 +1,1475 @@
+//! Ready-made parallel processors and combinators
+//!
+//! This module provides small, reusable implementations of [`ParallelProcessor`] that cover
+//! common one-off tasks (counting, filtering, fanning out) without writing a dedicated struct
+//! and trait implementation each time.
+//!
+//! Closure-based processors operate on a [`RecordView`], a lightweight type-erased view of a
+//! record which exposes lengths, headers, and encoded buffers directly. Building a view never
+//! decodes or allocates, so the hot path stays as cheap as a hand-written processor.
+//!
+//! # Example
+//!
+//! ```
+//! use binseq::prelude::*;
+//! use binseq::processors::{CountProcessor, FilterAdapter};
+//!
+//! # fn main() -> binseq::Result<()> {
+//! let reader = BinseqReader::new("./data/subset.bq")?;
+//!
+//! // Count all records whose primary sequence is at least 10bp long
+//! let counter = CountProcessor::new();
+//! let filter = FilterAdapter::new(counter.clone(), |view| view.slen() >= 10);
+//! reader.process_parallel(filter, 4)?;
+//!
+//! println!("Matching records: {}", counter.count());
+//! # Ok(())
+//! # }
+//! ```
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+
+use bitnuc::BitSize;
+use rand::rngs::SmallRng;
+use rand::{Rng, SeedableRng};
+
+use crate::{
+    BinseqRecord, Error, ErrorAction, ParallelProcessor, Result, ScratchBuffers, SequencingRecord,
+};
+
+/// A type-erased, zero-copy view of a [`BinseqRecord`]
+///
+/// The view borrows the underlying record so it can be handed to non-generic closures.
+/// Every accessor forwards to the record, so nothing is decoded or copied unless
+/// explicitly requested (e.g. through [`BinseqRecord::decode_s`]).
+///
+/// Note that CBQ records do not expose their encoded buffers, so [`BinseqRecord::sbuf`]
+/// and [`BinseqRecord::xbuf`] are only available for BQ and VBQ records.
+#[derive(Clone, Copy)]
+pub struct RecordView<'a> {
+    record: &'a dyn BinseqRecord,
+}
+impl<'a> RecordView<'a> {
+    /// Creates a view over an existing record
+    #[must_use]
+    pub fn new<R: BinseqRecord>(record: &'a R) -> Self {
+        Self { record }
+    }
+}
+impl BinseqRecord for RecordView<'_> {
+    fn bitsize(&self) -> BitSize {
+        self.record.bitsize()
+    }
+    fn index(&self) -> u64 {
+        self.record.index()
+    }
+    fn flag(&self) -> Option<u64> {
+        self.record.flag()
+    }
+    fn sheader(&self) -> &[u8] {
+        self.record.sheader()
+    }
+    fn xheader(&self) -> &[u8] {
+        self.record.xheader()
+    }
+    fn slen(&self) -> u64 {
+        self.record.slen()
+    }
+    fn xlen(&self) -> u64 {
+        self.record.xlen()
+    }
+    fn sbuf(&self) -> &[u64] {
+        self.record.sbuf()
+    }
+    fn xbuf(&self) -> &[u64] {
+        self.record.xbuf()
+    }
+    fn squal(&self) -> &[u8] {
+        self.record.squal()
+    }
+    fn xqual(&self) -> &[u8] {
+        self.record.xqual()
+    }
+    fn decode_s(&self, buf: &mut Vec<u8>) -> Result<()> {
+        self.record.decode_s(buf)
+    }
+    fn decode_x(&self, buf: &mut Vec<u8>) -> Result<()> {
+        self.record.decode_x(buf)
+    }
+    fn sseq(&self) -> &[u8] {
+        self.record.sseq()
+    }
+    fn xseq(&self) -> &[u8] {
+        self.record.xseq()
+    }
+    fn is_paired(&self) -> bool {
+        self.record.is_paired()
+    }
+    fn has_quality(&self) -> bool {
+        self.record.has_quality()
+    }
+}
+
+/// A record whose sequences have already been decoded
+///
+/// Passed to the inner processor of a [`DecodeAdapter`]. [`BinseqRecord::sseq`] and
+/// [`BinseqRecord::xseq`] return the cached sequences, and [`BinseqRecord::decode_s`] /
+/// [`BinseqRecord::decode_x`] copy from the cache instead of decoding again. All other
+/// accessors forward to the underlying record.
+pub struct DecodedRecord<'a, R> {
+    record: R,
+    sseq: &'a [u8],
+    xseq: &'a [u8],
+}
+impl<R: BinseqRecord> BinseqRecord for DecodedRecord<'_, R> {
+    fn bitsize(&self) -> BitSize {
+        self.record.bitsize()
+    }
+    fn index(&self) -> u64 {
+        self.record.index()
+    }
+    fn flag(&self) -> Option<u64> {
+        self.record.flag()
+    }
+    fn sheader(&self) -> &[u8] {
+        self.record.sheader()
+    }
+    fn xheader(&self) -> &[u8] {
+        self.record.xheader()
+    }
+    fn slen(&self) -> u64 {
+        self.record.slen()
+    }
+    fn xlen(&self) -> u64 {
+        self.record.xlen()
+    }
+    fn sbuf(&self) -> &[u64] {
+        self.record.sbuf()
+    }
+    fn xbuf(&self) -> &[u64] {
+        self.record.xbuf()
+    }
+    fn squal(&self) -> &[u8] {
+        self.record.squal()
+    }
+    fn xqual(&self) -> &[u8] {
+        self.record.xqual()
+    }
+    fn decode_s(&self, buf: &mut Vec<u8>) -> Result<()> {
+        buf.extend_from_slice(self.sseq);
+        Ok(())
+    }
+    fn decode_x(&self, buf: &mut Vec<u8>) -> Result<()> {
+        buf.extend_from_slice(self.xseq);
+        Ok(())
+    }
+    fn sseq(&self) -> &[u8] {
+        self.sseq
+    }
+    fn xseq(&self) -> &[u8] {
+        self.xseq
+    }
+    fn is_paired(&self) -> bool {
+        self.record.is_paired()
+    }
+    fn has_quality(&self) -> bool {
+        self.record.has_quality()
+    }
+}
+
+/// Decodes each record once and shares the decoded sequences with an inner processor
+///
+/// When several layered processors (e.g. a [`FilterAdapter`] feeding a [`TeeProcessor`])
+/// each need the decoded sequence, wrapping the stack in a `DecodeAdapter` decodes every
+/// record exactly once into reusable per-thread buffers. The inner processor receives a
+/// [`DecodedRecord`] and can access the sequences through [`BinseqRecord::sseq`] and
+/// [`BinseqRecord::xseq`] without allocating.
+///
+/// Processors that never need decoded sequences should not be wrapped, as the adapter
+/// always decodes.
+///
+/// # Example
+///
+/// ```
+/// use binseq::prelude::*;
+/// use binseq::processors::{CountProcessor, DecodeAdapter, FilterAdapter};
+///
+/// # fn main() -> binseq::Result<()> {
+/// let reader = BinseqReader::new("./data/subset.vbq")?;
+///
+/// // Count records without any ambiguous nucleotides
+/// let counter = CountProcessor::new();
+/// let filter = FilterAdapter::new(counter.clone(), |view| !view.sseq().contains(&b'N'));
+/// reader.process_parallel(DecodeAdapter::new(filter), 4)?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct DecodeAdapter<P> {
+    inner: P,
+    sbuf: Vec<u8>,
+    xbuf: Vec<u8>,
+}
+impl<P: ParallelProcessor> DecodeAdapter<P> {
+    /// Wraps `inner`, decoding the sequences of every record before forwarding it
+    #[must_use]
+    pub fn new(inner: P) -> Self {
+        Self {
+            inner,
+            sbuf: Vec::new(),
+            xbuf: Vec::new(),
+        }
+    }
+
+    /// Returns a reference to the inner processor
+    pub fn inner(&self) -> &P {
+        &self.inner
+    }
+
+    /// Consumes the adapter and returns the inner processor
+    pub fn into_inner(self) -> P {
+        self.inner
+    }
+}
+impl<P: ParallelProcessor> DecodeAdapter<P> {
+    /// Decodes the sequences of `record` into the buffers of the adapter
+    fn decode<R: BinseqRecord>(&mut self, record: &R) -> Result<()> {
+        self.sbuf.clear();
+        self.xbuf.clear();
+        record.decode_s(&mut self.sbuf)?;
+        if record.is_paired() {
+            record.decode_x(&mut self.xbuf)?;
+        }
+        Ok(())
+    }
+}
+impl<P: ParallelProcessor> ParallelProcessor for DecodeAdapter<P> {
+    fn process_record<R: BinseqRecord>(&mut self, record: R) -> Result<()> {
+        self.decode(&record)?;
+        self.inner.process_record(DecodedRecord {
+            record,
+            sseq: &self.sbuf,
+            xseq: &self.xbuf,
+        })
+    }
+
+    fn process_record_with<R: BinseqRecord>(
+        &mut self,
+        record: R,
+        scratch: &mut ScratchBuffers,
+    ) -> Result<()> {
+        self.decode(&record)?;
+        self.inner.process_record_with(
+            DecodedRecord {
+                record,
+                sseq: &self.sbuf,
+                xseq: &self.xbuf,
+            },
+            scratch,
+        )
+    }
+
+    fn on_error(&mut self, record_idx: u64, error: &Error) -> ErrorAction {
+        self.inner.on_error(record_idx, error)
+    }
+
+    fn skipped_records(&self) -> u64 {
+        self.inner.skipped_records()
+    }
+
+    fn on_batch_complete(&mut self) -> Result<()> {
+        self.inner.on_batch_complete()
+    }
+
+    fn on_thread_complete(&mut self) -> Result<()> {
+        self.inner.on_thread_complete()
+    }
+
+    fn set_tid(&mut self, tid: usize) {
+        self.inner.set_tid(tid);
+    }
+
+    fn get_tid(&self) -> Option<usize> {
+        self.inner.get_tid()
+    }
+}
+
+/// Counts the number of records processed across all threads
+///
+/// Each thread accumulates a local count which is published to the shared counter
+/// when the thread completes, so there is no contention on the hot path.
+#[derive(Clone, Default)]
+pub struct CountProcessor {
+    /// Thread-local record count
+    local_count: u64,
+    /// Global record count
+    count: Arc<AtomicU64>,
+}
+impl CountProcessor {
+    /// Creates a processor with a count of zero
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of records counted so far
+    ///
+    /// Only includes counts from threads that have completed.
+    #[must_use]
+    pub fn count(&self) -> u64 {
+        self.count.load(Ordering::Relaxed)
+    }
+}
+impl ParallelProcessor for CountProcessor {
+    fn process_record<R: BinseqRecord>(&mut self, _record: R) -> Result<()> {
+        self.local_count += 1;
+        Ok(())
+    }
+
+    fn on_thread_complete(&mut self) -> Result<()> {
+        self.count.fetch_add(self.local_count, Ordering::Relaxed);
+        self.local_count = 0;
+        Ok(())
+    }
+}
+
+/// Wraps a closure as a [`ParallelProcessor`]
+///
+/// The closure is shared (not cloned) between threads, so any state it captures must be
+/// thread-safe.
+///
+/// # Example
+///
+/// ```
+/// use std::sync::Arc;
+/// use std::sync::atomic::{AtomicU64, Ordering};
+///
+/// use binseq::prelude::*;
+/// use binseq::processors::FnProcessor;
+///
+/// # fn main() -> binseq::Result<()> {
+/// let total_bp = Arc::new(AtomicU64::new(0));
+/// let processor = FnProcessor::new({
+///     let total_bp = total_bp.clone();
+///     move |view| {
+///         total_bp.fetch_add(view.slen() + view.xlen(), Ordering::Relaxed);
+///         Ok(())
+///     }
+/// });
+///
+/// BinseqReader::new("./data/subset.vbq")?.process_parallel(processor, 2)?;
+/// assert!(total_bp.load(Ordering::Relaxed) > 0);
+/// # Ok(())
+/// # }
+/// ```
+pub struct FnProcessor<F> {
+    func: Arc<F>,
+}
+impl<F> FnProcessor<F>
+where
+    F: Fn(&RecordView) -> Result<()> + Send + Sync,
+{
+    /// Creates a processor calling `func` with every record
+    #[must_use]
+    pub fn new(func: F) -> Self {
+        Self {
+            func: Arc::new(func),
+        }
+    }
+}
+impl<F> Clone for FnProcessor<F> {
+    fn clone(&self) -> Self {
+        Self {
+            func: Arc::clone(&self.func),
+        }
+    }
+}
+impl<F> ParallelProcessor for FnProcessor<F>
+where
+    F: Fn(&RecordView) -> Result<()> + Send + Sync,
+{
+    fn process_record<R: BinseqRecord>(&mut self, record: R) -> Result<()> {
+        (self.func)(&RecordView::new(&record))
+    }
+}
+
+/// Forwards only the records passing a predicate to an inner processor
+///
+/// Batch and thread completion events are always forwarded to the inner processor.
+pub struct FilterAdapter<P, F> {
+    inner: P,
+    predicate: Arc<F>,
+}
+impl<P, F> FilterAdapter<P, F>
+where
+    P: ParallelProcessor,
+    F: Fn(&RecordView) -> bool + Send + Sync,
+{
+    /// Wraps `inner`, forwarding only the records for which `predicate` returns `true`
+    #[must_use]
+    pub fn new(inner: P, predicate: F) -> Self {
+        Self {
+            inner,
+            predicate: Arc::new(predicate),
+        }
+    }
+
+    /// Returns a reference to the inner processor
+    pub fn inner(&self) -> &P {
+        &self.inner
+    }
+
+    /// Consumes the adapter and returns the inner processor
+    pub fn into_inner(self) -> P {
+        self.inner
+    }
+}
+impl<P: Clone, F> Clone for FilterAdapter<P, F> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            predicate: Arc::clone(&self.predicate),
+        }
+    }
+}
+impl<P, F> ParallelProcessor for FilterAdapter<P, F>
+where
+    P: ParallelProcessor,
+    F: Fn(&RecordView) -> bool + Send + Sync,
+{
+    fn process_record<R: BinseqRecord>(&mut self, record: R) -> Result<()> {
+        if (self.predicate)(&RecordView::new(&record)) {
+            self.inner.process_record(record)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn process_record_with<R: BinseqRecord>(
+        &mut self,
+        record: R,
+        scratch: &mut ScratchBuffers,
+    ) -> Result<()> {
+        if (self.predicate)(&RecordView::new(&record)) {
+            self.inner.process_record_with(record, scratch)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn on_error(&mut self, record_idx: u64, error: &Error) -> ErrorAction {
+        self.inner.on_error(record_idx, error)
+    }
+
+    fn skipped_records(&self) -> u64 {
+        self.inner.skipped_records()
+    }
+
+    fn on_batch_complete(&mut self) -> Result<()> {
+        self.inner.on_batch_complete()
+    }
+
+    fn on_thread_complete(&mut self) -> Result<()> {
+        self.inner.on_thread_complete()
+    }
+
+    fn set_tid(&mut self, tid: usize) {
+        self.inner.set_tid(tid);
+    }
+
+    fn get_tid(&self) -> Option<usize> {
+        self.inner.get_tid()
+    }
+}
+
+/// Forwards every record to two processors in turn
+///
+/// Processing stops at the first error returned by either processor. Errors are passed to
+/// [`on_error`](ParallelProcessor::on_error) of the processor that returned them.
+#[derive(Clone)]
+pub struct TeeProcessor<A, B> {
+    first: A,
+    second: B,
+
+    /// Whether the last error was returned by the first processor
+    first_failed: bool,
+}
+impl<A: ParallelProcessor, B: ParallelProcessor> TeeProcessor<A, B> {
+    /// Creates a processor forwarding every record to `first`, then to `second`
+    #[must_use]
+    pub fn new(first: A, second: B) -> Self {
+        Self {
+            first,
+            second,
+            first_failed: false,
+        }
+    }
+
+    /// Consumes the tee and returns both inner processors
+    pub fn into_inner(self) -> (A, B) {
+        (self.first, self.second)
+    }
+}
+impl<A: ParallelProcessor, B: ParallelProcessor> ParallelProcessor for TeeProcessor<A, B> {
+    fn process_record<R: BinseqRecord>(&mut self, record: R) -> Result<()> {
+        self.first_failed = true;
+        self.first.process_record(&record)?;
+        self.first_failed = false;
+        self.second.process_record(&record)
+    }
+
+    fn process_record_with<R: BinseqRecord>(
+        &mut self,
+        record: R,
+        scratch: &mut ScratchBuffers,
+    ) -> Result<()> {
+        self.first_failed = true;
+        self.first.process_record_with(&record, scratch)?;
+        self.first_failed = false;
+        self.second.process_record_with(&record, scratch)
+    }
+
+    fn on_error(&mut self, record_idx: u64, error: &Error) -> ErrorAction {
+        if self.first_failed {
+            self.first.on_error(record_idx, error)
+        } else {
+            self.second.on_error(record_idx, error)
+        }
+    }
+
+    fn skipped_records(&self) -> u64 {
+        self.first.skipped_records() + self.second.skipped_records()
+    }
+
+    fn on_batch_complete(&mut self) -> Result<()> {
+        self.first.on_batch_complete()?;
+        self.second.on_batch_complete()
+    }
+
+    fn on_thread_complete(&mut self) -> Result<()> {
+        self.first.on_thread_complete()?;
+        self.second.on_thread_complete()
+    }
+
+    fn set_tid(&mut self, tid: usize) {
+        self.first.set_tid(tid);
+        self.second.set_tid(tid);
+    }
+
+    fn get_tid(&self) -> Option<usize> {
+        self.first.get_tid().or_else(|| self.second.get_tid())
+    }
+}
+
+/// Routes every record to the first sub-processor whose flag pattern it matches
+///
+/// Each partition is a `(mask, value, processor)` triple and matches records whose flag
+/// satisfies `flag & mask == value`. Records matching no partition, including records
+/// without a flag, go to the default processor if one is set and are dropped otherwise.
+///
+/// Batch and thread completion events and thread IDs are forwarded to all sub-processors.
+/// Errors are passed to [`on_error`](ParallelProcessor::on_error) of the sub-processor that
+/// received the failing record.
+///
+/// # Example
+///
+/// ```
+/// use binseq::prelude::*;
+/// use binseq::processors::{CountProcessor, PartitionedProcessor};
+///
+/// # fn main() -> binseq::Result<()> {
+/// // split records by the lowest bit of their flag
+/// let processor = PartitionedProcessor::new(vec![
+///     (1, 1, CountProcessor::new()),
+///     (1, 0, CountProcessor::new()),
+/// ]);
+/// BinseqReader::new("./data/subset.vbq")?.process_parallel(processor.clone(), 2)?;
+/// let counts: Vec<u64> = processor.results().iter().map(CountProcessor::count).collect();
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct PartitionedProcessor<P> {
+    partitions: Vec<(u64, u64, P)>,
+    default_processor: Option<P>,
+
+    /// Index of the sub-processor that received the last record, counting the default
+    /// processor as the last partition
+    last_target: Option<usize>,
+}
+impl<P: ParallelProcessor> PartitionedProcessor<P> {
+    /// Creates a processor routing records to `partitions` of `(mask, value, processor)`
+    pub fn new(partitions: Vec<(u64, u64, P)>) -> Self {
+        Self {
+            partitions,
+            default_processor: None,
+            last_target: None,
+        }
+    }
+
+    /// Sets the processor receiving the records that match no partition
+    #[must_use]
+    pub fn with_default(mut self, processor: P) -> Self {
+        self.default_processor = Some(processor);
+        self
+    }
+
+    /// Consumes the processor and returns the sub-processors
+    ///
+    /// The partition processors come first, in their original order, followed by the
+    /// default processor if one is set.
+    pub fn results(self) -> Vec<P> {
+        self.partitions
+            .into_iter()
+            .map(|(_, _, processor)| processor)
+            .chain(self.default_processor)
+            .collect()
+    }
+
+    /// Returns the processor of the first partition matching `flag`, or the default processor
+    ///
+    /// The chosen processor is remembered for [`on_error`](ParallelProcessor::on_error).
+    fn target(&mut self, flag: Option<u64>) -> Option<&mut P> {
+        self.last_target = flag
+            .and_then(|flag| {
+                self.partitions
+                    .iter()
+                    .position(|(mask, value, _)| flag & mask == *value)
+            })
+            .or_else(|| {
+                self.default_processor
+                    .is_some()
+                    .then_some(self.partitions.len())
+            });
+        self.last_target.and_then(|idx| self.processor_mut(idx))
+    }
+
+    /// Returns the sub-processor at `idx`, counting the default processor as the last partition
+    fn processor_mut(&mut self, idx: usize) -> Option<&mut P> {
+        match self.partitions.get_mut(idx) {
+            Some((_, _, processor)) => Some(processor),
+            None => self.default_processor.as_mut(),
+        }
+    }
+
+    /// Iterates over all sub-processors, including the default processor
+    fn processors(&self) -> impl Iterator<Item = &P> {
+        self.partitions
+            .iter()
+            .map(|(_, _, processor)| processor)
+            .chain(self.default_processor.as_ref())
+    }
+
+    /// Iterates mutably over all sub-processors, including the default processor
+    fn processors_mut(&mut self) -> impl Iterator<Item = &mut P> {
+        self.partitions
+            .iter_mut()
+            .map(|(_, _, processor)| processor)
+            .chain(self.default_processor.as_mut())
+    }
+}
+impl<P: ParallelProcessor> ParallelProcessor for PartitionedProcessor<P> {
+    fn process_record<R: BinseqRecord>(&mut self, record: R) -> Result<()> {
+        match self.target(record.flag()) {
+            Some(processor) => processor.process_record(record),
+            None => Ok(()),
+        }
+    }
+
+    fn process_record_with<R: BinseqRecord>(
+        &mut self,
+        record: R,
+        scratch: &mut ScratchBuffers,
+    ) -> Result<()> {
+        match self.target(record.flag()) {
+            Some(processor) => processor.process_record_with(record, scratch),
+            None => Ok(()),
+        }
+    }
+
+    fn on_error(&mut self, record_idx: u64, error: &Error) -> ErrorAction {
+        match self.last_target.and_then(|idx| self.processor_mut(idx)) {
+            Some(processor) => processor.on_error(record_idx, error),
+            None => ErrorAction::Abort,
+        }
+    }
+
+    fn skipped_records(&self) -> u64 {
+        self.processors()
+            .map(ParallelProcessor::skipped_records)
+            .sum()
+    }
+
+    fn on_batch_complete(&mut self) -> Result<()> {
+        self.processors_mut()
+            .try_for_each(ParallelProcessor::on_batch_complete)
+    }
+
+    fn on_thread_complete(&mut self) -> Result<()> {
+        self.processors_mut()
+            .try_for_each(ParallelProcessor::on_thread_complete)
+    }
+
+    fn set_tid(&mut self, tid: usize) {
+        for processor in self.processors_mut() {
+            processor.set_tid(tid);
+        }
+    }
+
+    fn get_tid(&self) -> Option<usize> {
+        self.processors().find_map(ParallelProcessor::get_tid)
+    }
+}
+
+/// Shares one processor between all threads behind a mutex
+///
+/// Every call locks the mutex and forwards to the inner processor, so all threads feed the
+/// same state, e.g. a single output writer, without merging per-thread copies. This
+/// serializes processing: it only pays off if the inner work is cheap compared to reading
+/// and decoding records (e.g. I/O bound writes). CPU-heavy processors should keep per-thread
+/// state instead.
+///
+/// [`on_thread_complete`](ParallelProcessor::on_thread_complete) is forwarded once per
+/// worker thread. Thread IDs are not forwarded, as the inner processor is shared.
+///
+/// # Example
+///
+/// ```
+/// use binseq::prelude::*;
+/// use binseq::processors::MutexProcessor;
+///
+/// #[derive(Clone, Default)]
+/// struct Collector {
+///     lengths: Vec<u64>,
+/// }
+/// impl ParallelProcessor for Collector {
+///     fn process_record<R: BinseqRecord>(&mut self, record: R) -> binseq::Result<()> {
+///         self.lengths.push(record.slen());
+///         Ok(())
+///     }
+/// }
+///
+/// # fn main() -> binseq::Result<()> {
+/// let (processor, handle) = MutexProcessor::new(Collector::default());
+/// let reader = BinseqReader::new("./data/subset.vbq")?;
+/// let num_records = reader.num_records()?;
+/// reader.process_parallel(processor, 4)?;
+/// assert_eq!(handle.lock().unwrap().lengths.len(), num_records);
+/// # Ok(())
+/// # }
+/// ```
+impl<P: ParallelProcessor> ParallelProcessor for Arc<Mutex<P>> {
+    fn process_record<R: BinseqRecord>(&mut self, record: R) -> Result<()> {
+        self.lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .process_record(record)
+    }
+
+    fn process_record_with<R: BinseqRecord>(
+        &mut self,
+        record: R,
+        scratch: &mut ScratchBuffers,
+    ) -> Result<()> {
+        self.lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .process_record_with(record, scratch)
+    }
+
+    fn on_error(&mut self, record_idx: u64, error: &Error) -> ErrorAction {
+        self.lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .on_error(record_idx, error)
+    }
+
+    fn skipped_records(&self) -> u64 {
+        self.lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .skipped_records()
+    }
+
+    fn on_batch_complete(&mut self) -> Result<()> {
+        self.lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .on_batch_complete()
+    }
+
+    fn on_thread_complete(&mut self) -> Result<()> {
+        self.lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .on_thread_complete()
+    }
+}
+
+/// Constructor for processors shared between threads, see the [`ParallelProcessor`]
+/// implementation of `Arc<Mutex<P>>`
+pub struct MutexProcessor;
+impl MutexProcessor {
+    /// Wraps `inner` in an `Arc<Mutex<_>>`
+    ///
+    /// Returns two handles to the same processor: one to pass to
+    /// [`process_parallel`](crate::ParallelReader::process_parallel) and one to inspect the
+    /// processor afterwards.
+    #[must_use]
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new<P: ParallelProcessor>(inner: P) -> (Arc<Mutex<P>>, Arc<Mutex<P>>) {
+        let shared = Arc::new(Mutex::new(inner));
+        (shared.clone(), shared)
+    }
+}
+
+/// An owned, decoded record kept by a [`ReservoirSampler`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SampledRecord {
+    /// Index of the record in the file
+    pub index: u64,
+    /// Flag of the record, if the file stores flags
+    pub flag: Option<u64>,
+    /// Decoded primary sequence
+    pub sseq: Vec<u8>,
+    /// Quality scores of the primary sequence (empty if the file has none)
+    pub squal: Vec<u8>,
+    /// Header of the primary sequence (empty if the file has none)
+    pub sheader: Vec<u8>,
+    /// Decoded extended sequence (empty for single-end records)
+    pub xseq: Vec<u8>,
+    /// Quality scores of the extended sequence
+    pub xqual: Vec<u8>,
+    /// Header of the extended sequence
+    pub xheader: Vec<u8>,
+}
+impl SampledRecord {
+    /// Decodes an owned copy of `record`
+    pub fn from_record<R: BinseqRecord>(record: &R) -> Result<Self> {
+        let mut sampled = Self {
+            index: record.index(),
+            flag: record.flag(),
+            sseq: Vec::new(),
+            squal: record.squal().to_vec(),
+            sheader: record.sheader().to_vec(),
+            xseq: Vec::new(),
+            xqual: Vec::new(),
+            xheader: Vec::new(),
+        };
+        record.decode_s(&mut sampled.sseq)?;
+        if record.is_paired() {
+            record.decode_x(&mut sampled.xseq)?;
+            sampled.xqual.extend_from_slice(record.xqual());
+            sampled.xheader.extend_from_slice(record.xheader());
+        }
+        Ok(sampled)
+    }
+
+    /// Checks if the record has an extended sequence
+    #[must_use]
+    pub fn is_paired(&self) -> bool {
+        !self.xseq.is_empty()
+    }
+
+    /// Borrows the record for writing, e.g. with [`RecordWriter::push`](crate::RecordWriter::push)
+    #[must_use]
+    pub fn as_record(&self) -> SequencingRecord<'_> {
+        let non_empty = |field: &[u8]| -> bool { !field.is_empty() };
+        let paired = self.is_paired();
+        SequencingRecord::new(
+            &self.sseq,
+            non_empty(self.squal.as_slice()).then_some(self.squal.as_slice()),
+            non_empty(self.sheader.as_slice()).then_some(self.sheader.as_slice()),
+            paired.then_some(self.xseq.as_slice()),
+            (paired && non_empty(self.xqual.as_slice())).then_some(self.xqual.as_slice()),
+            (paired && non_empty(self.xheader.as_slice())).then_some(self.xheader.as_slice()),
+            self.flag,
+        )
+    }
+}
+
+/// The reservoir of a completed thread
+struct Reservoir {
+    tid: usize,
+    seen: u64,
+    records: Vec<SampledRecord>,
+}
+
+/// Draws a uniform random sample of `k` records in a single parallel pass
+///
+/// Each thread keeps a reservoir of up to `k` records (Algorithm R) and counts the records it
+/// saw. [`merge`](Self::merge) combines the reservoirs into a sample that is uniform over
+/// the whole file: every draw picks a thread with probability proportional to the records it
+/// saw and not yet drawn, then a random record of its reservoir. Paired records are sampled
+/// as a unit.
+///
+/// Sampled records are decoded into [`SampledRecord`]s when they enter a reservoir. For a
+/// given seed the sample is reproducible as long as records are assigned to the same
+/// threads, which holds for a single thread.
+///
+/// # Example
+///
+/// ```
+/// use binseq::prelude::*;
+/// use binseq::processors::ReservoirSampler;
+///
+/// # fn main() -> binseq::Result<()> {
+/// let sampler = ReservoirSampler::new(100, 42);
+/// BinseqReader::new("./data/subset.vbq")?.process_parallel(sampler.clone(), 4)?;
+/// let sample = sampler.merge();
+/// assert_eq!(sample.len(), 100);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct ReservoirSampler {
+    /// Size of the sample
+    k: usize,
+    /// Seed of the per-thread and merge random number generators
+    seed: u64,
+    /// Thread ID
+    tid: Option<usize>,
+    /// Thread-local random number generator, seeded on the first record
+    rng: Option<SmallRng>,
+    /// Thread-local number of records seen
+    seen: u64,
+    /// Thread-local reservoir
+    reservoir: Vec<SampledRecord>,
+    /// Reservoirs of the completed threads
+    completed: Arc<Mutex<Vec<Reservoir>>>,
+}
+impl ReservoirSampler {
+    /// Creates a sampler drawing `k` records, seeding its random number generators with `seed`
+    #[must_use]
+    pub fn new(k: usize, seed: u64) -> Self {
+        Self {
+            k,
+            seed,
+            tid: None,
+            rng: None,
+            seen: 0,
+            reservoir: Vec::with_capacity(k),
+            completed: Arc::default(),
+        }
+    }
+
+    /// Returns the number of records the completed threads saw
+    #[must_use]
+    pub fn seen(&self) -> u64 {
+        self.completed
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .map(|reservoir| reservoir.seen)
+            .sum()
+    }
+
+    /// Combines the reservoirs of the completed threads into a uniform sample
+    ///
+    /// Returns `min(k, seen)` records ordered by their index in the file. The reservoirs are
+    /// consumed, so the sampler can be reused for another pass.
+    #[must_use]
+    pub fn merge(&self) -> Vec<SampledRecord> {
+        let mut parts = std::mem::take(
+            &mut *self
+                .completed
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner),
+        );
+        parts.sort_by_key(|part| part.tid);
+
+        let mut rng = SmallRng::seed_from_u64(self.seed);
+        let mut remaining: Vec<u64> = parts.iter().map(|part| part.seen).collect();
+        let mut total: u64 = remaining.iter().sum();
+        let mut sample = Vec::with_capacity(self.k);
+        while sample.len() < self.k && total > 0 {
+            // a thread with n of the remaining records is drawn with probability n / total
+            let mut draw = rng.random_range(0..total);
+            let Some(part) = remaining.iter().position(|&n| {
+                if draw < n {
+                    true
+                } else {
+                    draw -= n;
+                    false
+                }
+            }) else {
+                break;
+            };
+
+            // reservoirs are uniform, so any of their records stands for the drawn one
+            let records = &mut parts[part].records;
+            let pick = rng.random_range(0..records.len());
+            sample.push(records.swap_remove(pick));
+            remaining[part] -= 1;
+            total -= 1;
+        }
+        sample.sort_by_key(|record| record.index);
+        sample
+    }
+}
+impl ParallelProcessor for ReservoirSampler {
+    fn process_record<R: BinseqRecord>(&mut self, record: R) -> Result<()> {
+        self.seen += 1;
+        if self.reservoir.len() < self.k {
+            self.reservoir.push(SampledRecord::from_record(&record)?);
+            return Ok(());
+        }
+        let (seed, tid) = (self.seed, self.tid.unwrap_or(0) as u64);
+        let rng = self.rng.get_or_insert_with(|| {
+            SmallRng::seed_from_u64(seed ^ (tid + 1).wrapping_mul(0x9E37_79B9_7F4A_7C15))
+        });
+        let slot = rng.random_range(0..self.seen);
+        if let Ok(slot) = usize::try_from(slot)
+            && slot < self.k
+        {
+            self.reservoir[slot] = SampledRecord::from_record(&record)?;
+        }
+        Ok(())
+    }
+
+    fn on_thread_complete(&mut self) -> Result<()> {
+        let reservoir = Reservoir {
+            tid: self.tid.unwrap_or(0),
+            seen: std::mem::take(&mut self.seen),
+            records: std::mem::take(&mut self.reservoir),
+        };
+        self.rng = None;
+        self.completed
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(reservoir);
+        Ok(())
+    }
+
+    fn set_tid(&mut self, tid: usize) {
+        self.tid = Some(tid);
+    }
+
+    fn get_tid(&self) -> Option<usize> {
+        self.tid
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicBool;
+
+    use super::*;
+    use crate::{BinseqReader, ParallelReader};
+
+    const EXTENSIONS: [&str; 3] = ["bq", "vbq", "cbq"];
+
+    fn num_records(ext: &str) -> usize {
+        BinseqReader::new(format!("./data/subset.{ext}"))
+            .unwrap()
+            .num_records()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_count_processor() {
+        for ext in EXTENSIONS {
+            let reader = BinseqReader::new(format!("./data/subset.{ext}")).unwrap();
+            let counter = CountProcessor::new();
+            reader.process_parallel(counter.clone(), 2).unwrap();
+            assert_eq!(counter.count() as usize, num_records(ext));
+        }
+    }
+
+    #[test]
+    fn test_fn_processor() {
+        for ext in EXTENSIONS {
+            let reader = BinseqReader::new(format!("./data/subset.{ext}")).unwrap();
+            let seen = Arc::new(AtomicU64::new(0));
+            let processor = FnProcessor::new({
+                let seen = seen.clone();
+                move |view: &RecordView| {
+                    assert!(view.slen() > 0);
+                    seen.fetch_add(1, Ordering::Relaxed);
+                    Ok(())
+                }
+            });
+            reader.process_parallel(processor, 2).unwrap();
+            assert_eq!(seen.load(Ordering::Relaxed) as usize, num_records(ext));
+        }
+    }
+
+    #[test]
+    fn test_fn_processor_propagates_errors() {
+        let reader = BinseqReader::new("./data/subset.vbq").unwrap();
+        let processor =
+            FnProcessor::new(|_view: &RecordView| Err(crate::error::ReadError::EndOfStream.into()));
+        assert!(reader.process_parallel(processor, 1).is_err());
+    }
+
+    #[test]
+    fn test_filter_adapter() {
+        for ext in EXTENSIONS {
+            let reader = BinseqReader::new(format!("./data/subset.{ext}")).unwrap();
+            let counter = CountProcessor::new();
+            let filter = FilterAdapter::new(counter.clone(), |view| view.index() % 2 == 0);
+            reader.process_parallel(filter, 2).unwrap();
+            assert_eq!(counter.count() as usize, num_records(ext).div_ceil(2));
+        }
+    }
+
+    #[test]
+    fn test_filter_adapter_rejects_all() {
+        let reader = BinseqReader::new("./data/subset.bq").unwrap();
+        let counter = CountProcessor::new();
+        let filter = FilterAdapter::new(counter.clone(), |_| false);
+        reader.process_parallel(filter, 2).unwrap();
+        assert_eq!(counter.count(), 0);
+    }
+
+    #[test]
+    fn test_tee_processor() {
+        for ext in EXTENSIONS {
+            let reader = BinseqReader::new(format!("./data/subset.{ext}")).unwrap();
+            let all = CountProcessor::new();
+            let filtered = CountProcessor::new();
+            let tee = TeeProcessor::new(
+                all.clone(),
+                FilterAdapter::new(filtered.clone(), |view| view.index() < 10),
+            );
+            reader.process_parallel(tee, 2).unwrap();
+            assert_eq!(all.count() as usize, num_records(ext));
+            assert_eq!(filtered.count(), 10);
+        }
+    }
+
+    #[test]
+    fn test_record_view_decodes_cbq() {
+        let reader = BinseqReader::new("./data/subset.cbq").unwrap();
+        let processor = FnProcessor::new(|view: &RecordView| {
+            let seq = view.decode_s_alloc()?;
+            assert_eq!(seq.len() as u64, view.slen());
+            Ok(())
+        });
+        reader.process_parallel(processor, 2).unwrap();
+    }
+
+    #[test]
+    fn test_record_view_decodes() {
+        let reader = crate::bq::MmapReader::new("./data/subset.bq").unwrap();
+        let record = reader.get(0).unwrap();
+        let view = RecordView::new(&record);
+        assert_eq!(view.index(), record.index());
+        assert_eq!(view.flag(), record.flag());
+        assert_eq!(view.slen(), record.slen());
+        assert_eq!(view.xlen(), record.xlen());
+        assert_eq!(
+            view.decode_s_alloc().unwrap(),
+            record.decode_s_alloc().unwrap()
+        );
+        assert_eq!(
+            view.decode_x_alloc().unwrap(),
+            record.decode_x_alloc().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_decode_adapter_shares_decoded_sequences() {
+        for ext in EXTENSIONS {
+            let reader = BinseqReader::new(format!("./data/subset.{ext}")).unwrap();
+            let seen = Arc::new(AtomicU64::new(0));
+            let check = |seen: Arc<AtomicU64>| {
+                FnProcessor::new(move |view: &RecordView| {
+                    assert_eq!(view.sseq().len() as u64, view.slen());
+                    assert_eq!(view.xseq().len() as u64, view.xlen());
+                    assert_eq!(view.decode_s_alloc()?, view.sseq());
+                    assert_eq!(view.decode_x_alloc()?, view.xseq());
+                    seen.fetch_add(1, Ordering::Relaxed);
+                    Ok(())
+                })
+            };
+            let tee = TeeProcessor::new(check(seen.clone()), check(seen.clone()));
+            reader.process_parallel(DecodeAdapter::new(tee), 2).unwrap();
+            assert_eq!(seen.load(Ordering::Relaxed) as usize, 2 * num_records(ext));
+        }
+    }
+
+    #[test]
+    fn test_decoded_record_matches_source() {
+        let reader = crate::bq::MmapReader::new("./data/subset.bq").unwrap();
+        let record = reader.get(0).unwrap();
+        let sseq = record.decode_s_alloc().unwrap();
+        let xseq = record.decode_x_alloc().unwrap();
+        let decoded = DecodedRecord {
+            record: &record,
+            sseq: &sseq,
+            xseq: &xseq,
+        };
+        let mut buf = b"prefix".to_vec();
+        decoded.decode_s(&mut buf).unwrap();
+        assert_eq!(&buf[..6], b"prefix");
+        assert_eq!(&buf[6..], sseq.as_slice());
+        assert_eq!(decoded.index(), record.index());
+        assert_eq!(decoded.slen(), record.slen());
+    }
+
+    #[test]
+    fn test_tee_forwards_tid() {
+        #[derive(Clone, Default)]
+        struct TidProcessor {
+            tid: Option<usize>,
+            checked: Arc<AtomicBool>,
+        }
+        impl ParallelProcessor for TidProcessor {
+            fn process_record<R: BinseqRecord>(&mut self, _record: R) -> Result<()> {
+                if self.tid.is_some() {
+                    self.checked.store(true, Ordering::Relaxed);
+                }
+                Ok(())
+            }
+            fn set_tid(&mut self, tid: usize) {
+                self.tid = Some(tid);
+            }
+            fn get_tid(&self) -> Option<usize> {
+                self.tid
+            }
+        }
+
+        let reader = BinseqReader::new("./data/subset.bq").unwrap();
+        let inner = TidProcessor::default();
+        let tee = TeeProcessor::new(inner.clone(), CountProcessor::new());
+        reader.process_parallel(tee, 1).unwrap();
+        assert!(inner.checked.load(Ordering::Relaxed));
+    }
+
+    #[derive(Clone, Default)]
+    struct CountingProcessor {
+        records: usize,
+        batches: usize,
+    }
+    impl ParallelProcessor for CountingProcessor {
+        fn process_record<R: BinseqRecord>(&mut self, _record: R) -> Result<()> {
+            self.records += 1;
+            Ok(())
+        }
+
+        fn on_batch_complete(&mut self) -> Result<()> {
+            self.batches += 1;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_mutex_processor() {
+        for ext in EXTENSIONS {
+            for threads in [1, 4] {
+                let reader = BinseqReader::new(format!("./data/subset.{ext}")).unwrap();
+                let (processor, handle) = MutexProcessor::new(CountingProcessor::default());
+                reader.process_parallel(processor, threads).unwrap();
+                let counts = handle.lock().unwrap();
+                assert_eq!(counts.records, num_records(ext));
+                assert!(counts.batches > 0);
+            }
+        }
+
+        // per-thread state is published once per thread into the shared processor
+        let reader = BinseqReader::new("./data/subset.vbq").unwrap();
+        let (processor, handle) = MutexProcessor::new(CountProcessor::new());
+        reader.process_parallel(processor, 4).unwrap();
+        assert_eq!(handle.lock().unwrap().count() as usize, num_records("vbq"));
+    }
+
+    /// Feeds the records of a file to one sampler per thread and merges the reservoirs
+    fn sample_by_threads(
+        reader: &crate::vbq::MmapReader,
+        sampler: &ReservoirSampler,
+        threads: &[std::ops::Range<usize>],
+    ) -> Result<Vec<SampledRecord>> {
+        let mut block = reader.new_block();
+        let mut records = Vec::new();
+        for block_idx in 0.. {
+            if !reader.read_block_at_index(block_idx, &mut block)? {
+                break;
+            }
+            records.extend(block.iter().map(crate::OwnedRecord::from));
+        }
+        for (tid, range) in threads.iter().enumerate() {
+            let mut thread = sampler.clone();
+            thread.set_tid(tid);
+            for record in &records[range.clone()] {
+                thread.process_record(record)?;
+            }
+            thread.on_thread_complete()?;
+        }
+        Ok(sampler.merge())
+    }
+
+    /// Writes `n_records` single-end records with flags `0..n_records` to an in-memory VBQ file
+    fn flagged_vbq(n_records: u64) -> Result<Vec<u8>> {
+        use crate::SequencingRecordBuilder;
+        use crate::vbq::{FileHeaderBuilder, WriterBuilder};
+
+        let mut bytes = Vec::new();
+        let header = FileHeaderBuilder::new().flags(true).block(256).build();
+        let mut writer = WriterBuilder::default().header(header).build(&mut bytes)?;
+        for flag in 0..n_records {
+            writer.push(
+                SequencingRecordBuilder::default()
+                    .s_seq(b"ACGTACGTAC")
+                    .flag(flag)
+                    .build()?,
+            )?;
+        }
+        writer.finish()?;
+        drop(writer);
+        Ok(bytes)
+    }
+
+    #[test]
+    fn test_partitioned_processor() -> Result<()> {
+        let bytes = flagged_vbq(1000)?;
+        let reader = || BinseqReader::from_bytes(bytes.clone());
+
+        // eight groups by the three lowest flag bits
+        let partitions = (0..8).map(|g| (0b111, g, CountProcessor::new())).collect();
+        let processor = PartitionedProcessor::new(partitions).with_default(CountProcessor::new());
+        reader()?.process_parallel(processor.clone(), 4)?;
+        let counts: Vec<u64> = processor
+            .results()
+            .iter()
+            .map(CountProcessor::count)
+            .collect();
+        assert_eq!(counts, [125, 125, 125, 125, 125, 125, 125, 125, 0]);
+
+        // the first matching partition wins, the rest goes to the default processor
+        let processor = PartitionedProcessor::new(vec![
+            (0b11, 0, CountProcessor::new()),
+            (0b1, 0, CountProcessor::new()),
+        ])
+        .with_default(CountProcessor::new());
+        reader()?.process_parallel(processor.clone(), 4)?;
+        let counts: Vec<u64> = processor
+            .results()
+            .iter()
+            .map(CountProcessor::count)
+            .collect();
+        assert_eq!(counts, [250, 250, 500]);
+
+        // without a default processor unmatched records are dropped
+        let processor = PartitionedProcessor::new(vec![(0b1, 1, CountProcessor::new())]);
+        reader()?.process_parallel(processor.clone(), 4)?;
+        let counts: Vec<u64> = processor
+            .results()
+            .iter()
+            .map(CountProcessor::count)
+            .collect();
+        assert_eq!(counts, [500]);
+        Ok(())
+    }
+
+    /// Fails on every record if `fail` is set and skips the failed records
+    #[derive(Clone, Default)]
+    struct SkippingProcessor {
+        fail: bool,
+        skipped: Arc<AtomicU64>,
+    }
+    impl ParallelProcessor for SkippingProcessor {
+        fn process_record<R: BinseqRecord>(&mut self, _record: R) -> Result<()> {
+            if self.fail {
+                return Err(crate::error::ReadError::EndOfStream.into());
+            }
+            Ok(())
+        }
+
+        fn on_error(&mut self, _record_idx: u64, _error: &Error) -> ErrorAction {
+            self.skipped.fetch_add(1, Ordering::Relaxed);
+            ErrorAction::Continue
+        }
+
+        fn skipped_records(&self) -> u64 {
+            self.skipped.load(Ordering::Relaxed)
+        }
+    }
+
+    #[test]
+    fn test_partitioned_processor_on_error() -> Result<()> {
+        let bytes = flagged_vbq(1000)?;
+
+        // only the records routed to the failing partition are skipped
+        let failing = SkippingProcessor {
+            fail: true,
+            ..Default::default()
+        };
+        let processor = PartitionedProcessor::new(vec![(0b1, 1, failing.clone())])
+            .with_default(SkippingProcessor::default());
+        BinseqReader::from_bytes(bytes)?.process_parallel(processor.clone(), 4)?;
+        assert_eq!(processor.skipped_records(), 500);
+        assert_eq!(failing.skipped_records(), 500);
+        Ok(())
+    }
+
+    #[test]
+    fn test_reservoir_sampler_is_uniform() -> Result<()> {
+        use crate::SequencingRecordBuilder;
+        use crate::vbq::{FileHeaderBuilder, MmapReader, WriterBuilder};
+
+        let n_records = 100;
+        let mut bytes = Vec::new();
+        {
+            let header = FileHeaderBuilder::new().paired(true).build();
+            let mut writer = WriterBuilder::default().header(header).build(&mut bytes)?;
+            for idx in 0..n_records {
+                let seq: Vec<u8> = (0..10).map(|j| b"ACGT"[(idx >> j) & 3]).collect();
+                writer.push(
+                    SequencingRecordBuilder::default()
+                        .s_seq(&seq)
+                        .x_seq(&seq[..5])
+                        .build()?,
+                )?;
+            }
+            writer.finish()?;
+        }
+        let reader = MmapReader::from_bytes(bytes)?;
+
+        // threads seeing very different numbers of records must be weighted accordingly
+        let threads = [0..70, 70..90, 90..100];
+        let (k, trials) = (10, 2000);
+        let mut counts = vec![0u64; n_records];
+        for seed in 0..trials {
+            let sampler = ReservoirSampler::new(k, seed);
+            let sample = sample_by_threads(&reader, &sampler, &threads)?;
+            assert_eq!(sample.len(), k);
+            assert!(sample.windows(2).all(|w| w[0].index < w[1].index));
+            for record in sample {
+                // mates are sampled together
+                assert_eq!(record.xseq, record.sseq[..5]);
+                counts[record.index as usize] += 1;
+            }
+        }
+
+        // Pearson's chi-squared test of uniform record indices: 99 degrees of freedom, the
+        // critical value at p = 0.001 is 148.23. The seeds are fixed, so the test is
+        // deterministic and only fails if the merge is biased.
+        let expected = (trials * k as u64) as f64 / n_records as f64;
+        let chi2: f64 = counts
+            .iter()
+            .map(|&count| (count as f64 - expected).powi(2) / expected)
+            .sum();
+        assert!(chi2 < 148.23, "chi-squared statistic {chi2} too large");
+        Ok(())
+    }
+
+    #[test]
+    fn test_reservoir_sampler_small_input_and_reproducibility() -> Result<()> {
+        // fewer records than k: everything is returned
+        let reader = BinseqReader::new("./data/subset.vbq")?;
+        let sampler = ReservoirSampler::new(30_000, 7);
+        reader.process_parallel(sampler.clone(), 4)?;
+        assert_eq!(sampler.seen(), num_records("vbq") as u64);
+        let sample = sampler.merge();
+        assert_eq!(sample.len(), num_records("vbq"));
+        assert!(sampler.merge().is_empty());
+
+        // a single thread yields the same sample for the same seed
+        let run = |seed| -> Result<Vec<SampledRecord>> {
+            let sampler = ReservoirSampler::new(50, seed);
+            BinseqReader::new("./data/subset.bq")?.process_parallel(sampler.clone(), 1)?;
+            Ok(sampler.merge())
+        };
+        let sample = run(3)?;
+        assert_eq!(sample.len(), 50);
+        assert_eq!(sample, run(3)?);
+        assert_ne!(sample, run(4)?);
+        Ok(())
+    }
+}
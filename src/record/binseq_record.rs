@@ -1,7 +1,86 @@
+use std::fmt;
+use std::str::Utf8Error;
+
 use auto_impl::auto_impl;
 use bitnuc::BitSize;
 
-use crate::Result;
+use super::kmers::{CanonicalKmers, Minimizers};
+use crate::{Result, error::ReadError};
+
+/// Offset of Phred+33 (Sanger / Illumina 1.8+) encoded quality scores
+pub const PHRED_OFFSET: u8 = 33;
+
+/// Number of leading bases shown by the `Debug` implementations of records
+const DEBUG_PREFIX_LEN: usize = 16;
+
+/// Decodes at most [`DEBUG_PREFIX_LEN`] bases of an encoded sequence
+///
+/// Only the words holding the prefix are decoded, so this is cheap for long sequences.
+/// An ellipsis marks a truncated sequence.
+fn debug_prefix(bitsize: BitSize, ebuf: &[u64], len: u64) -> String {
+    let n = (len as usize).min(DEBUG_PREFIX_LEN);
+    let bases_per_word = match bitsize {
+        BitSize::Two => 32,
+        BitSize::Four => 16,
+    };
+    let words = n.div_ceil(bases_per_word).min(ebuf.len());
+    let mut dbuf = Vec::with_capacity(n);
+    if bitsize.decode(&ebuf[..words], n, &mut dbuf).is_err() {
+        return String::from("<invalid>");
+    }
+    let mut prefix = String::from_utf8_lossy(&dbuf).into_owned();
+    if n < len as usize {
+        prefix.push_str("...");
+    }
+    prefix
+}
+
+/// Formats a record for `Debug` as its index, lengths, flag and leading bases
+pub(crate) fn debug_record<R: BinseqRecord + ?Sized>(
+    record: &R,
+    name: &str,
+    f: &mut fmt::Formatter<'_>,
+) -> fmt::Result {
+    let mut s = f.debug_struct(name);
+    s.field("index", &record.index())
+        .field("slen", &record.slen())
+        .field("xlen", &record.xlen())
+        .field("flag", &record.flag())
+        .field(
+            "sseq",
+            &debug_prefix(record.bitsize(), record.sbuf(), record.slen()),
+        );
+    if record.is_paired() {
+        s.field(
+            "xseq",
+            &debug_prefix(record.bitsize(), record.xbuf(), record.xlen()),
+        );
+    }
+    s.finish()
+}
+
+/// Appends the 2-bit encoding of a nucleotide sequence to `dst`
+///
+/// This is the inverse of [`BinseqRecord::decode_s`] for 2-bit records, e.g. to store a
+/// sequence again after editing its decoded form. Each `u64` holds 32 nucleotides, the
+/// first nucleotide in the lowest bits.
+///
+/// # Errors
+///
+/// Returns an error if `seq` contains anything other than `A`, `C`, `G` or `T`.
+///
+/// # Examples
+///
+/// ```rust
+/// let mut dst = Vec::new();
+/// binseq::encode_sequence(b"ACGT", &mut dst)?;
+/// assert_eq!(dst, [0b11_10_01_00]);
+/// # Ok::<(), binseq::Error>(())
+/// ```
+pub fn encode_sequence(seq: &[u8], dst: &mut Vec<u64>) -> Result<()> {
+    BitSize::Two.encode(seq, dst)?;
+    Ok(())
+}
 
 /// Record trait shared between BINSEQ variants.
 ///
@@ -70,6 +149,44 @@ pub trait BinseqRecord {
         Ok(())
     }
 
+    /// Decodes both sequences of this record into the provided buffers.
+    ///
+    /// Both buffers are cleared first. The extended buffer is left empty for single-end records.
+    fn decode_pair(&self, sbuf: &mut Vec<u8>, xbuf: &mut Vec<u8>) -> Result<()> {
+        sbuf.clear();
+        xbuf.clear();
+        self.decode_s(sbuf)?;
+        if self.xlen() > 0 {
+            self.decode_x(xbuf)?;
+        }
+        Ok(())
+    }
+
+    /// Decodes the primary sequence of this record into the provided buffer, masking
+    /// low-quality positions with `N`.
+    ///
+    /// Any position whose quality score is below `quality_threshold` (a Phred score, with
+    /// quality bytes assumed to be Phred+33 encoded) is replaced with `N`.
+    ///
+    /// Records without quality scores are decoded identically to [`decode_s`](Self::decode_s).
+    fn decode_masked(&self, quality_threshold: u8, buf: &mut Vec<u8>) -> Result<()> {
+        let offset = buf.len();
+        self.decode_s(buf)?;
+        mask_low_quality(&mut buf[offset..], self.squal(), quality_threshold);
+        Ok(())
+    }
+
+    /// Decodes the extended sequence of this record into the provided buffer, masking
+    /// low-quality positions with `N`.
+    ///
+    /// See [`decode_masked`](Self::decode_masked) for details.
+    fn decode_x_masked(&self, quality_threshold: u8, buf: &mut Vec<u8>) -> Result<()> {
+        let offset = buf.len();
+        self.decode_x(buf)?;
+        mask_low_quality(&mut buf[offset..], self.xqual(), quality_threshold);
+        Ok(())
+    }
+
     /// Returns a reference to the primary decoded sequence of this record.
     ///
     /// This is not available on all types that implement the `Record` trait.
@@ -104,6 +221,30 @@ pub trait BinseqRecord {
         Ok(buf)
     }
 
+    /// Decodes the primary sequence of this record into a newly allocated string.
+    ///
+    /// Allocates on every call like [`decode_s_alloc`](Self::decode_s_alloc), so this is
+    /// meant for tests and display code rather than hot loops.
+    fn decode_to_string(&self) -> Result<String> {
+        let buf = self.decode_s_alloc()?;
+        Ok(String::from_utf8(buf).map_err(|e| e.utf8_error())?)
+    }
+
+    /// Decodes the extended sequence of this record into a newly allocated string.
+    ///
+    /// Returns an empty string for single-end records.
+    fn decode_x_to_string(&self) -> Result<String> {
+        let buf = self.decode_x_alloc()?;
+        Ok(String::from_utf8(buf).map_err(|e| e.utf8_error())?)
+    }
+
+    /// Decodes both sequences of this record into newly allocated strings.
+    ///
+    /// The extended string is empty for single-end records.
+    fn decoded_pair_to_strings(&self) -> Result<(String, String)> {
+        Ok((self.decode_to_string()?, self.decode_x_to_string()?))
+    }
+
     /// A convenience function to check if the record is paired.
     fn is_paired(&self) -> bool {
         self.xlen() > 0
@@ -113,6 +254,178 @@ pub trait BinseqRecord {
     fn has_quality(&self) -> bool {
         !self.squal().is_empty()
     }
+
+    /// Returns the header of this record as a validated UTF-8 string
+    fn sheader_str(&self) -> std::result::Result<&str, Utf8Error> {
+        std::str::from_utf8(self.sheader())
+    }
+
+    /// Returns the header of the extended sequence as a validated UTF-8 string
+    fn xheader_str(&self) -> std::result::Result<&str, Utf8Error> {
+        std::str::from_utf8(self.xheader())
+    }
+
+    /// Returns the quality scores of the primary sequence as a validated UTF-8 string
+    fn squal_str(&self) -> std::result::Result<&str, Utf8Error> {
+        std::str::from_utf8(self.squal())
+    }
+
+    /// Returns the quality scores of the extended sequence as a validated UTF-8 string
+    fn xqual_str(&self) -> std::result::Result<&str, Utf8Error> {
+        std::str::from_utf8(self.xqual())
+    }
+
+    /// Appends the numeric Phred scores of the primary sequence to `out`.
+    ///
+    /// Quality bytes are assumed to be Phred+33 encoded, see
+    /// [`squal_phred_with_offset`](Self::squal_phred_with_offset).
+    fn squal_phred(&self, out: &mut Vec<u8>) -> Result<()> {
+        self.squal_phred_with_offset(PHRED_OFFSET, out)
+    }
+
+    /// Appends the numeric Phred scores of the extended sequence to `out`.
+    ///
+    /// Quality bytes are assumed to be Phred+33 encoded, see
+    /// [`squal_phred_with_offset`](Self::squal_phred_with_offset).
+    fn xqual_phred(&self, out: &mut Vec<u8>) -> Result<()> {
+        self.xqual_phred_with_offset(PHRED_OFFSET, out)
+    }
+
+    /// Appends the numeric Phred scores of the primary sequence to `out`, subtracting
+    /// `offset` from every quality byte.
+    ///
+    /// Bytes below `offset` saturate to zero (e.g. negative Solexa scores with an offset of 64).
+    ///
+    /// # Errors
+    ///
+    /// Returns `ReadError::InvalidQualityScore` if a byte is outside of the printable ASCII
+    /// range. Scores appended before the invalid byte are kept in `out`.
+    fn squal_phred_with_offset(&self, offset: u8, out: &mut Vec<u8>) -> Result<()> {
+        phred_scores(self.squal(), offset, out)
+    }
+
+    /// Appends the numeric Phred scores of the extended sequence to `out`, subtracting
+    /// `offset` from every quality byte.
+    ///
+    /// See [`squal_phred_with_offset`](Self::squal_phred_with_offset) for details.
+    fn xqual_phred_with_offset(&self, offset: u8, out: &mut Vec<u8>) -> Result<()> {
+        phred_scores(self.xqual(), offset, out)
+    }
+
+    /// Returns the mean Phred+33 quality score of the primary sequence.
+    ///
+    /// Returns `None` if the record has no quality scores.
+    fn mean_quality(&self) -> Option<f64> {
+        mean_phred(self.squal())
+    }
+
+    /// Returns the mean Phred+33 quality score of the extended sequence.
+    ///
+    /// Returns `None` if the record has no extended quality scores.
+    fn xmean_quality(&self) -> Option<f64> {
+        mean_phred(self.xqual())
+    }
+
+    /// Returns an iterator over the canonical k-mers of the primary sequence.
+    ///
+    /// K-mers are read from the encoded sequence without decoding it.
+    /// See [`CanonicalKmers`] for the k-mer representation.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `k` is zero or greater than [`MAX_KMER_SIZE`](crate::MAX_KMER_SIZE).
+    fn canonical_kmers(&self, k: usize) -> CanonicalKmers<'_> {
+        CanonicalKmers::new(self.sbuf(), self.bitsize(), self.slen() as usize, k)
+    }
+
+    /// Returns an iterator over the minimizers of windows of `w` canonical k-mers of the primary sequence.
+    ///
+    /// See [`Minimizers`] for how minimizers are selected.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `w` is zero, or if `k` is zero or greater than [`MAX_KMER_SIZE`](crate::MAX_KMER_SIZE).
+    fn minimizers(&self, k: usize, w: usize) -> Minimizers<'_> {
+        Minimizers::new(self.sbuf(), self.bitsize(), self.slen() as usize, k, w)
+    }
+
+    /// Returns the edit (Levenshtein) distance between the primary sequences of two records
+    /// if it is at most `max`.
+    ///
+    /// Returns `None` as soon as the distance is known to exceed `max`, which makes this
+    /// cheap for the small bounds used in barcode and UMI correction. Sequences differing in
+    /// length by more than `max` are rejected without decoding. Records may use different
+    /// bitsizes. Returns `None` if either sequence cannot be decoded.
+    fn edit_distance_bounded<R: BinseqRecord>(&self, other: &R, max: usize) -> Option<usize> {
+        if self.slen().abs_diff(other.slen()) > max as u64 {
+            return None;
+        }
+        let (seq, other_seq) = (self.decode_s_alloc().ok()?, other.decode_s_alloc().ok()?);
+        bounded_edit_distance(&seq, &other_seq, max)
+    }
+}
+
+/// Appends the quality bytes in `qual` with `offset` subtracted to `out`
+fn phred_scores(qual: &[u8], offset: u8, out: &mut Vec<u8>) -> Result<()> {
+    out.reserve(qual.len());
+    for &q in qual {
+        if !(b'!'..=b'~').contains(&q) {
+            return Err(ReadError::InvalidQualityScore(q).into());
+        }
+        out.push(q.saturating_sub(offset));
+    }
+    Ok(())
+}
+
+/// Computes the mean Phred+33 score of `qual` without allocating
+fn mean_phred(qual: &[u8]) -> Option<f64> {
+    if qual.is_empty() {
+        return None;
+    }
+    let total: u64 = qual
+        .iter()
+        .map(|&q| u64::from(q.saturating_sub(PHRED_OFFSET)))
+        .sum();
+    Some(total as f64 / qual.len() as f64)
+}
+
+/// Computes the edit distance between `a` and `b` if it is at most `max`
+///
+/// Uses two rows of the dynamic programming matrix over the shorter sequence and stops once
+/// every entry of a row exceeds `max`, since entries never decrease from one row to the next
+/// along any alignment path.
+fn bounded_edit_distance(a: &[u8], b: &[u8], max: usize) -> Option<usize> {
+    let (long, short) = if a.len() >= b.len() { (a, b) } else { (b, a) };
+    if long.len() - short.len() > max {
+        return None;
+    }
+
+    let mut prev: Vec<usize> = (0..=short.len()).collect();
+    let mut curr = vec![0; short.len() + 1];
+    for (i, &l) in long.iter().enumerate() {
+        curr[0] = i + 1;
+        let mut row_min = curr[0];
+        for (j, &s) in short.iter().enumerate() {
+            let substitution = prev[j] + usize::from(l != s);
+            curr[j + 1] = substitution.min(prev[j + 1] + 1).min(curr[j] + 1);
+            row_min = row_min.min(curr[j + 1]);
+        }
+        if row_min > max {
+            return None;
+        }
+        std::mem::swap(&mut prev, &mut curr);
+    }
+    let distance = prev[short.len()];
+    (distance <= max).then_some(distance)
+}
+
+/// Replaces every nucleotide whose Phred+33 quality score is below `threshold` with `N`
+fn mask_low_quality(seq: &mut [u8], qual: &[u8], threshold: u8) {
+    for (nuc, q) in seq.iter_mut().zip(qual) {
+        if q.saturating_sub(PHRED_OFFSET) < threshold {
+            *nuc = b'N';
+        }
+    }
 }
 
 #[cfg(test)]
@@ -165,6 +478,12 @@ mod tests {
         }
     }
 
+    impl fmt::Debug for MockRecord {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            debug_record(self, "MockRecord", f)
+        }
+    }
+
     fn unpaired_record() -> MockRecord {
         let seq = b"ACGTACGTAC";
         let mut sbuf = Vec::new();
@@ -237,6 +556,34 @@ mod tests {
         assert_eq!(buf, b"TTGGCCAATT");
     }
 
+    #[test]
+    fn test_decode_to_string() {
+        let record = paired_record();
+        let sseq = record.decode_to_string().unwrap();
+        assert_eq!(sseq, "ACGTACGTAC");
+        assert!(sseq.bytes().all(|b| b"ACGT".contains(&b)));
+        assert_eq!(record.decode_x_to_string().unwrap(), "TTGGCCAATT");
+        assert_eq!(
+            record.decoded_pair_to_strings().unwrap(),
+            ("ACGTACGTAC".to_string(), "TTGGCCAATT".to_string())
+        );
+    }
+
+    #[test]
+    fn test_decode_to_string_empty() {
+        let record = unpaired_record();
+        assert_eq!(record.decode_x_to_string().unwrap(), "");
+        let (_, xseq) = record.decoded_pair_to_strings().unwrap();
+        assert!(xseq.is_empty());
+
+        let empty = MockRecord {
+            sbuf: Vec::new(),
+            slen: 0,
+            ..unpaired_record()
+        };
+        assert_eq!(empty.decode_to_string().unwrap(), "");
+    }
+
     #[test]
     #[should_panic(expected = "does not implement direct sequence access")]
     fn test_sseq_default_panics() {
@@ -268,4 +615,281 @@ mod tests {
         let record = unpaired_record();
         assert!(record.xqual().is_empty());
     }
+
+    #[test]
+    fn test_canonical_kmers_and_minimizers() {
+        let record = unpaired_record();
+        // ACGTACGTAC: CGT and TAC are the reverse complements of ACG and GTA
+        let (acg, gta) = (0b00_01_10, 0b10_11_00);
+        let kmers: Vec<u64> = record.canonical_kmers(3).map(|(_, kmer)| kmer).collect();
+        assert_eq!(kmers, [acg, acg, gta, gta, acg, acg, gta, gta]);
+        let minimizers: Vec<_> = record.minimizers(3, 4).collect();
+        assert_eq!(minimizers, [(0, acg), (1, acg), (4, acg)]);
+    }
+
+    #[test]
+    fn test_decode_masked() {
+        let mut record = unpaired_record();
+        // Phred scores: 40, 2, 40, 29, 30, 40, 10, 40, 40, 31
+        record.squal = b"I#I>?I+IIB".to_vec();
+        let mut buf = Vec::new();
+        record.decode_masked(30, &mut buf).unwrap();
+        assert_eq!(buf, b"ANGNTANGAC");
+    }
+
+    #[test]
+    fn test_decode_masked_appends() {
+        let mut record = unpaired_record();
+        record.squal = vec![b'#'; 10];
+        let mut buf = b"XX".to_vec();
+        record.decode_masked(30, &mut buf).unwrap();
+        assert_eq!(buf, b"XXNNNNNNNNNN");
+    }
+
+    #[test]
+    fn test_decode_masked_without_quality() {
+        let record = unpaired_record();
+        let mut buf = Vec::new();
+        record.decode_masked(30, &mut buf).unwrap();
+        assert_eq!(buf, record.decode_s_alloc().unwrap());
+    }
+
+    #[test]
+    fn test_decode_x_masked_without_quality() {
+        let record = paired_record();
+        let mut buf = Vec::new();
+        record.decode_x_masked(30, &mut buf).unwrap();
+        assert_eq!(buf, b"TTGGCCAATT");
+    }
+
+    struct HeaderRecord {
+        header: Vec<u8>,
+        squal: Vec<u8>,
+    }
+    impl BinseqRecord for HeaderRecord {
+        fn bitsize(&self) -> BitSize {
+            BitSize::Two
+        }
+        fn index(&self) -> u64 {
+            0
+        }
+        fn flag(&self) -> Option<u64> {
+            None
+        }
+        fn sheader(&self) -> &[u8] {
+            &self.header
+        }
+        fn xheader(&self) -> &[u8] {
+            b""
+        }
+        fn slen(&self) -> u64 {
+            self.squal.len() as u64
+        }
+        fn xlen(&self) -> u64 {
+            0
+        }
+        fn sbuf(&self) -> &[u64] {
+            &[]
+        }
+        fn xbuf(&self) -> &[u64] {
+            &[]
+        }
+        fn squal(&self) -> &[u8] {
+            &self.squal
+        }
+    }
+
+    #[test]
+    fn test_quality_helpers_without_quality() {
+        let record = unpaired_record();
+        let mut out = Vec::new();
+        record.squal_phred(&mut out).unwrap();
+        record.xqual_phred(&mut out).unwrap();
+        assert!(out.is_empty());
+        assert_eq!(record.mean_quality(), None);
+        assert_eq!(record.xmean_quality(), None);
+        assert_eq!(record.squal_str().unwrap(), "");
+    }
+
+    #[test]
+    fn test_squal_phred() {
+        let record = paired_record();
+        let mut out = vec![99];
+        record.squal_phred(&mut out).unwrap();
+        assert_eq!(out[0], 99);
+        assert_eq!(&out[1..], &[40; 10]);
+        assert_eq!(record.mean_quality(), Some(40.0));
+        assert_eq!(record.squal_str().unwrap(), "IIIIIIIIII");
+    }
+
+    #[test]
+    fn test_squal_phred_offset_64() {
+        // Illumina 1.3+ encoding, with Solexa-style negative scores saturating to zero
+        let record = HeaderRecord {
+            header: b"read".to_vec(),
+            squal: b"@Jh;".to_vec(),
+        };
+        let mut out = Vec::new();
+        record.squal_phred_with_offset(64, &mut out).unwrap();
+        assert_eq!(out, [0, 10, 40, 0]);
+    }
+
+    #[test]
+    fn test_squal_phred_invalid_byte() {
+        let record = HeaderRecord {
+            header: b"read".to_vec(),
+            squal: vec![b'I', b'\n', b'I'],
+        };
+        let mut out = Vec::new();
+        let err = record.squal_phred(&mut out).unwrap_err();
+        assert!(matches!(
+            err,
+            crate::Error::ReadError(ReadError::InvalidQualityScore(b'\n'))
+        ));
+        assert_eq!(out, [40]);
+    }
+
+    #[test]
+    fn test_header_str() {
+        let record = HeaderRecord {
+            header: b"read_1".to_vec(),
+            squal: Vec::new(),
+        };
+        assert_eq!(record.sheader_str().unwrap(), "read_1");
+        assert_eq!(record.xheader_str().unwrap(), "");
+
+        let record = HeaderRecord {
+            header: vec![b'r', 0xFF, 0xFE],
+            squal: Vec::new(),
+        };
+        assert!(record.sheader_str().is_err());
+    }
+
+    /// Builds a single-end record holding `seq`
+    fn record_from(seq: &[u8], bitsize: BitSize) -> MockRecord {
+        let mut sbuf = Vec::new();
+        bitsize.encode(seq, &mut sbuf).unwrap();
+        MockRecord {
+            bitsize,
+            index: 0,
+            flag: None,
+            sbuf,
+            xbuf: Vec::new(),
+            slen: seq.len() as u64,
+            xlen: 0,
+            squal: Vec::new(),
+        }
+    }
+
+    /// Textbook Levenshtein distance over the full matrix
+    fn reference_edit_distance(a: &[u8], b: &[u8]) -> usize {
+        let mut matrix = vec![vec![0; b.len() + 1]; a.len() + 1];
+        for (i, row) in matrix.iter_mut().enumerate() {
+            row[0] = i;
+        }
+        for (j, cell) in matrix[0].iter_mut().enumerate() {
+            *cell = j;
+        }
+        for i in 1..=a.len() {
+            for j in 1..=b.len() {
+                let cost = usize::from(a[i - 1] != b[j - 1]);
+                matrix[i][j] = (matrix[i - 1][j - 1] + cost)
+                    .min(matrix[i - 1][j] + 1)
+                    .min(matrix[i][j - 1] + 1);
+            }
+        }
+        matrix[a.len()][b.len()]
+    }
+
+    #[test]
+    fn test_edit_distance_bounded_examples() {
+        let distance = |a: &[u8], b: &[u8], max| {
+            record_from(a, BitSize::Two).edit_distance_bounded(&record_from(b, BitSize::Two), max)
+        };
+        // Identical, substitution, insertion, deletion
+        assert_eq!(distance(b"ACGTACGT", b"ACGTACGT", 0), Some(0));
+        assert_eq!(distance(b"ACGTACGT", b"ACGAACGT", 2), Some(1));
+        assert_eq!(distance(b"ACGTACGT", b"ACGTTACGT", 2), Some(1));
+        assert_eq!(distance(b"ACGTACGT", b"ACTACGT", 2), Some(1));
+        assert_eq!(distance(b"ACGTACGT", b"TGCATGCA", 8), Some(8));
+
+        // Different sequences exceed a bound of 0
+        assert_eq!(distance(b"ACGTACGT", b"ACGAACGT", 0), None);
+        assert_eq!(distance(b"ACGTACGT", b"TGCATGCA", 7), None);
+
+        // Length differences beyond the bound are rejected
+        assert_eq!(distance(b"ACGTACGT", b"ACG", 4), None);
+        assert_eq!(distance(b"ACGTACGT", b"ACG", 5), Some(5));
+        assert_eq!(distance(b"", b"ACG", 3), Some(3));
+
+        // Records of different bitsizes compare their decoded sequences
+        let four_bit = record_from(b"ACGTACGA", BitSize::Four);
+        let two_bit = record_from(b"ACGTACGT", BitSize::Two);
+        assert_eq!(two_bit.edit_distance_bounded(&four_bit, 1), Some(1));
+    }
+
+    #[test]
+    fn test_edit_distance_bounded_matches_reference() {
+        use rand::rngs::StdRng;
+        use rand::{Rng, SeedableRng};
+
+        let mut rng = StdRng::seed_from_u64(crate::RNG_SEED);
+        for _ in 0..500 {
+            let a: Vec<u8> = (0..rng.random_range(0..16))
+                .map(|_| b"ACGT"[rng.random_range(0..4)])
+                .collect();
+
+            // Apply a few random substitutions, insertions and deletions
+            let mut b = a.clone();
+            for _ in 0..rng.random_range(0..5) {
+                let nuc = b"ACGT"[rng.random_range(0..4)];
+                let pos = rng.random_range(0..=b.len());
+                match rng.random_range(0..3) {
+                    0 if pos < b.len() => b[pos] = nuc,
+                    1 if pos < b.len() => {
+                        b.remove(pos);
+                    }
+                    _ => b.insert(pos, nuc),
+                }
+            }
+
+            let expected = reference_edit_distance(&a, &b);
+            let (ra, rb) = (record_from(&a, BitSize::Two), record_from(&b, BitSize::Two));
+            for max in 0..=8 {
+                assert_eq!(
+                    ra.edit_distance_bounded(&rb, max),
+                    (expected <= max).then_some(expected)
+                );
+                assert_eq!(
+                    rb.edit_distance_bounded(&ra, max),
+                    (expected <= max).then_some(expected)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_debug_shows_bounded_prefix() {
+        assert_eq!(
+            format!("{:?}", unpaired_record()),
+            "MockRecord { index: 7, slen: 10, xlen: 0, flag: Some(3), sseq: \"ACGTACGTAC\" }"
+        );
+        assert!(format!("{:?}", paired_record()).ends_with("xseq: \"TTGGCCAATT\" }"));
+
+        // only the first 16 bases of a long 4-bit sequence are decoded
+        let seq: Vec<u8> = b"ACGTN".iter().copied().cycle().take(1000).collect();
+        let mut sbuf = Vec::new();
+        BitSize::Four.encode(&seq, &mut sbuf).unwrap();
+        let record = MockRecord {
+            bitsize: BitSize::Four,
+            index: 0,
+            flag: None,
+            sbuf,
+            xbuf: Vec::new(),
+            slen: seq.len() as u64,
+            xlen: 0,
+            squal: Vec::new(),
+        };
+        assert!(format!("{record:?}").ends_with("sseq: \"ACGTNACGTNACGTNA...\" }"));
+    }
 }
@@ -0,0 +1,364 @@
+//! Canonical k-mer and minimizer iteration over encoded sequences
+//!
+//! K-mers are read directly from the packed words of a record, so sequences are never
+//! decoded to ASCII. Each k-mer is reported as a 2-bit packed `u64` (`A=0, C=1, G=2, T=3`)
+//! with the first base in the highest bits, which makes the numeric order of k-mers their
+//! lexicographic order.
+
+use std::collections::VecDeque;
+
+use bitnuc::BitSize;
+
+/// Largest k-mer size that fits in a packed `u64`
+pub const MAX_KMER_SIZE: usize = 32;
+
+/// Iterator over the canonical k-mers of an encoded sequence
+///
+/// Yields `(position, kmer)` pairs where `position` is the index of the first base of the
+/// k-mer and `kmer` is the smaller of the forward and reverse-complement encodings. The
+/// reverse complement is maintained incrementally alongside the forward k-mer.
+///
+/// For 4-bit sequences, k-mers containing a base other than `A`, `C`, `G` or `T` are skipped.
+///
+/// Created by [`BinseqRecord::canonical_kmers`](crate::BinseqRecord::canonical_kmers).
+#[derive(Clone)]
+pub struct CanonicalKmers<'a> {
+    /// Encoded sequence
+    sbuf: &'a [u64],
+
+    /// Bitsize of the encoded sequence
+    bitsize: BitSize,
+
+    /// Number of bases in the sequence
+    slen: usize,
+
+    /// Size of the k-mers
+    k: usize,
+
+    /// Index of the next base to read
+    pos: usize,
+
+    /// Number of consecutive unambiguous bases ending before `pos`
+    valid: usize,
+
+    /// Rolling forward k-mer
+    fwd: u64,
+
+    /// Rolling reverse-complement k-mer
+    rev: u64,
+
+    /// Mask of the `2k` low bits
+    mask: u64,
+}
+impl<'a> CanonicalKmers<'a> {
+    /// Creates an iterator over the k-mers of the `slen` bases encoded in `sbuf`
+    ///
+    /// # Panics
+    ///
+    /// Panics if `k` is zero or greater than [`MAX_KMER_SIZE`].
+    #[must_use]
+    pub fn new(sbuf: &'a [u64], bitsize: BitSize, slen: usize, k: usize) -> Self {
+        assert!(
+            (1..=MAX_KMER_SIZE).contains(&k),
+            "k-mer size must be between 1 and {MAX_KMER_SIZE}, got {k}"
+        );
+        let mask = if k == MAX_KMER_SIZE {
+            u64::MAX
+        } else {
+            (1 << (2 * k)) - 1
+        };
+        Self {
+            sbuf,
+            bitsize,
+            slen,
+            k,
+            pos: 0,
+            valid: 0,
+            fwd: 0,
+            rev: 0,
+            mask,
+        }
+    }
+
+    /// Returns the 2-bit code of the base at `pos`, or `None` if it is ambiguous
+    fn base_code(&self, pos: usize) -> Option<u64> {
+        match self.bitsize {
+            BitSize::Two => Some((self.sbuf[pos / 32] >> (2 * (pos % 32))) & 0b11),
+            BitSize::Four => match (self.sbuf[pos / 16] >> (4 * (pos % 16))) & 0b1111 {
+                0b0001 => Some(0),
+                0b0010 => Some(1),
+                0b0100 => Some(2),
+                0b1000 => Some(3),
+                _ => None,
+            },
+        }
+    }
+}
+impl Iterator for CanonicalKmers<'_> {
+    type Item = (usize, u64);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.pos < self.slen {
+            let pos = self.pos;
+            self.pos += 1;
+            let Some(code) = self.base_code(pos) else {
+                self.valid = 0;
+                continue;
+            };
+            self.fwd = ((self.fwd << 2) | code) & self.mask;
+            self.rev = (self.rev >> 2) | ((0b11 ^ code) << (2 * (self.k - 1)));
+            self.valid += 1;
+            if self.valid >= self.k {
+                return Some((pos + 1 - self.k, self.fwd.min(self.rev)));
+            }
+        }
+        None
+    }
+}
+
+/// Iterator over the minimizers of an encoded sequence
+///
+/// The minimizer of a window of `w` consecutive canonical k-mers is its smallest k-mer
+/// (the leftmost one on ties). Consecutive windows usually share their minimizer, so each
+/// minimizer is yielded once, as a `(position, kmer)` pair, when it first becomes the
+/// minimizer of a window.
+///
+/// For 4-bit sequences, windows containing a base other than `A`, `C`, `G` or `T` are skipped.
+///
+/// Created by [`BinseqRecord::minimizers`](crate::BinseqRecord::minimizers).
+#[derive(Clone)]
+pub struct Minimizers<'a> {
+    /// Canonical k-mers of the sequence
+    kmers: CanonicalKmers<'a>,
+
+    /// Number of k-mers per window
+    w: usize,
+
+    /// Candidate minimizers of the current window in increasing position and k-mer order
+    window: VecDeque<(usize, u64)>,
+
+    /// Position of the previous k-mer
+    prev: Option<usize>,
+
+    /// Number of consecutive k-mers ending at `prev`
+    run: usize,
+
+    /// Position of the last yielded minimizer
+    last: Option<usize>,
+}
+impl<'a> Minimizers<'a> {
+    /// Creates an iterator over the minimizers of the `slen` bases encoded in `sbuf`
+    ///
+    /// # Panics
+    ///
+    /// Panics if `w` is zero, or if `k` is zero or greater than [`MAX_KMER_SIZE`].
+    #[must_use]
+    pub fn new(sbuf: &'a [u64], bitsize: BitSize, slen: usize, k: usize, w: usize) -> Self {
+        assert!(w > 0, "minimizer window size must be positive");
+        Self {
+            kmers: CanonicalKmers::new(sbuf, bitsize, slen, k),
+            w,
+            window: VecDeque::with_capacity(w),
+            prev: None,
+            run: 0,
+            last: None,
+        }
+    }
+}
+impl Iterator for Minimizers<'_> {
+    type Item = (usize, u64);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (pos, kmer) = self.kmers.next()?;
+
+            // A gap in positions means ambiguous bases were skipped
+            if self.prev.is_some_and(|prev| prev + 1 != pos) {
+                self.window.clear();
+                self.run = 0;
+            }
+            self.prev = Some(pos);
+            self.run += 1;
+
+            while self.window.back().is_some_and(|&(_, back)| back > kmer) {
+                self.window.pop_back();
+            }
+            self.window.push_back((pos, kmer));
+            while self
+                .window
+                .front()
+                .is_some_and(|&(front, _)| front + self.w <= pos)
+            {
+                self.window.pop_front();
+            }
+
+            if self.run >= self.w {
+                let (mpos, minimizer) = self.window[0];
+                if self.last != Some(mpos) {
+                    self.last = Some(mpos);
+                    return Some((mpos, minimizer));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the 2-bit code of an unambiguous base
+    fn code(base: u8) -> Option<u64> {
+        match base {
+            b'A' => Some(0),
+            b'C' => Some(1),
+            b'G' => Some(2),
+            b'T' => Some(3),
+            _ => None,
+        }
+    }
+
+    /// Packs a k-mer from its ASCII bases, returning `None` if any base is ambiguous
+    fn pack(kmer: &[u8]) -> Option<u64> {
+        kmer.iter()
+            .try_fold(0, |acc, &base| Some((acc << 2) | code(base)?))
+    }
+
+    fn reverse_complement(seq: &[u8]) -> Vec<u8> {
+        seq.iter()
+            .rev()
+            .map(|&base| match base {
+                b'A' => b'T',
+                b'C' => b'G',
+                b'G' => b'C',
+                b'T' => b'A',
+                other => other,
+            })
+            .collect()
+    }
+
+    fn naive_kmers(seq: &[u8], k: usize) -> Vec<(usize, u64)> {
+        if seq.len() < k {
+            return Vec::new();
+        }
+        (0..=seq.len() - k)
+            .filter_map(|pos| {
+                let kmer = &seq[pos..pos + k];
+                let fwd = pack(kmer)?;
+                let rev = pack(&reverse_complement(kmer))?;
+                Some((pos, fwd.min(rev)))
+            })
+            .collect()
+    }
+
+    fn naive_minimizers(seq: &[u8], k: usize, w: usize) -> Vec<(usize, u64)> {
+        let kmers = naive_kmers(seq, k);
+        let mut minimizers: Vec<(usize, u64)> = Vec::new();
+        for window in kmers.windows(w) {
+            // Windows must be w consecutive k-mers of the sequence
+            if window[w - 1].0 - window[0].0 != w - 1 {
+                continue;
+            }
+            let &(pos, kmer) = window
+                .iter()
+                .min_by_key(|&&(pos, kmer)| (kmer, pos))
+                .unwrap();
+            if minimizers.last().is_none_or(|&(last, _)| last != pos) {
+                minimizers.push((pos, kmer));
+            }
+        }
+        minimizers
+    }
+
+    fn sequence(len: usize, seed: usize) -> Vec<u8> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                state = state
+                    .wrapping_mul(6_364_136_223_846_793_005)
+                    .wrapping_add(1);
+                b"ACGT"[(state >> 33) % 4]
+            })
+            .collect()
+    }
+
+    fn encode(bitsize: BitSize, seq: &[u8]) -> Vec<u64> {
+        let mut sbuf = Vec::new();
+        bitsize.encode(seq, &mut sbuf).unwrap();
+        sbuf
+    }
+
+    #[test]
+    fn test_canonical_kmers_match_naive() {
+        for (len, k) in [(150, 21), (100, 31), (64, 32), (40, 1), (10, 11)] {
+            let seq = sequence(len, len + k);
+            let expected = naive_kmers(&seq, k);
+            for bitsize in [BitSize::Two, BitSize::Four] {
+                let sbuf = encode(bitsize, &seq);
+                let kmers: Vec<_> = CanonicalKmers::new(&sbuf, bitsize, len, k).collect();
+                assert_eq!(kmers, expected, "len={len} k={k}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_canonical_kmer_is_strand_independent() {
+        let seq = sequence(80, 7);
+        let rc = reverse_complement(&seq);
+        let (sbuf, rbuf) = (encode(BitSize::Two, &seq), encode(BitSize::Two, &rc));
+        let mut fwd: Vec<u64> = CanonicalKmers::new(&sbuf, BitSize::Two, 80, 15)
+            .map(|(_, kmer)| kmer)
+            .collect();
+        let rev: Vec<u64> = CanonicalKmers::new(&rbuf, BitSize::Two, 80, 15)
+            .map(|(_, kmer)| kmer)
+            .collect();
+        fwd.reverse();
+        assert_eq!(fwd, rev);
+    }
+
+    #[test]
+    fn test_minimizers_match_naive() {
+        for (len, k, w) in [(200, 15, 10), (150, 21, 5), (50, 5, 1), (30, 11, 25)] {
+            let seq = sequence(len, k * w);
+            let expected = naive_minimizers(&seq, k, w);
+            for bitsize in [BitSize::Two, BitSize::Four] {
+                let sbuf = encode(bitsize, &seq);
+                let minimizers: Vec<_> = Minimizers::new(&sbuf, bitsize, len, k, w).collect();
+                assert_eq!(minimizers, expected, "len={len} k={k} w={w}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_four_bit_skips_ambiguous_bases() {
+        let mut seq = sequence(120, 3);
+        seq[20] = b'N';
+        seq[21] = b'N';
+        seq[70] = b'N';
+        let sbuf = encode(BitSize::Four, &seq);
+
+        let kmers: Vec<_> = CanonicalKmers::new(&sbuf, BitSize::Four, 120, 9).collect();
+        assert_eq!(kmers, naive_kmers(&seq, 9));
+        assert!(
+            kmers
+                .iter()
+                .all(|&(pos, _)| !(pos..pos + 9).any(|i| [20, 21, 70].contains(&i)))
+        );
+
+        let minimizers: Vec<_> = Minimizers::new(&sbuf, BitSize::Four, 120, 9, 6).collect();
+        assert_eq!(minimizers, naive_minimizers(&seq, 9, 6));
+    }
+
+    #[test]
+    fn test_short_sequence_has_no_kmers() {
+        let sbuf = encode(BitSize::Two, b"ACGT");
+        assert_eq!(CanonicalKmers::new(&sbuf, BitSize::Two, 4, 5).count(), 0);
+        assert_eq!(Minimizers::new(&sbuf, BitSize::Two, 4, 2, 4).count(), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "k-mer size")]
+    fn test_oversized_k_panics() {
+        let _ = CanonicalKmers::new(&[], BitSize::Two, 0, MAX_KMER_SIZE + 1);
+    }
+}
@@ -1,5 +1,12 @@
 mod binseq_record;
+mod kmers;
+mod owned_record;
+mod record_pair;
 mod sequencing_record;
 
-pub use binseq_record::BinseqRecord;
+pub(crate) use binseq_record::debug_record;
+pub use binseq_record::{BinseqRecord, PHRED_OFFSET, encode_sequence};
+pub use kmers::{CanonicalKmers, MAX_KMER_SIZE, Minimizers};
+pub use owned_record::OwnedRecord;
+pub use record_pair::{MateRecord, RefRecordPair};
 pub use sequencing_record::{SequencingRecord, SequencingRecordBuilder};
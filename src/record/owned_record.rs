@@ -0,0 +1,283 @@
+use std::cmp::Ordering;
+
+use bitnuc::BitSize;
+
+use super::{BinseqRecord, encode_sequence};
+use crate::{Result, bq, vbq};
+
+/// An owned copy of a BINSEQ record
+///
+/// Unlike the zero-copy `RefRecord` types, an `OwnedRecord` does not borrow from a
+/// reader or block, so it can be stored across block reads or moved between threads.
+///
+/// The record keeps its sequences in their **encoded** form alongside the quality scores,
+/// headers, and flag of the source record.
+///
+/// Records are ordered by their decoded primary sequence, lexicographically, with a sequence
+/// sorting before the longer sequences it is a prefix of. Ties are broken by `slen`, `xlen`
+/// and the flag, and then by the remaining fields so that the order agrees with equality.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OwnedRecord {
+    bitsize: BitSize,
+    index: u64,
+    flag: Option<u64>,
+    slen: u64,
+    xlen: u64,
+    sbuf: Vec<u64>,
+    xbuf: Vec<u64>,
+    squal: Vec<u8>,
+    xqual: Vec<u8>,
+    sheader: Vec<u8>,
+    xheader: Vec<u8>,
+}
+impl OwnedRecord {
+    /// Creates an owned copy of any record exposing its encoded sequence buffers
+    ///
+    /// # Panics
+    ///
+    /// Panics if the record does not expose its encoded buffers (e.g. CBQ records).
+    #[must_use]
+    pub fn from_record<R: BinseqRecord>(record: &R) -> Self {
+        Self {
+            bitsize: record.bitsize(),
+            index: record.index(),
+            flag: record.flag(),
+            slen: record.slen(),
+            xlen: record.xlen(),
+            sbuf: record.sbuf().to_vec(),
+            xbuf: record.xbuf().to_vec(),
+            squal: record.squal().to_vec(),
+            xqual: record.xqual().to_vec(),
+            sheader: record.sheader().to_vec(),
+            xheader: record.xheader().to_vec(),
+        }
+    }
+
+    /// Appends the 2-bit encoding of the primary sequence to `dst`
+    ///
+    /// The encoded buffer is copied as-is for 2-bit records. 4-bit records are decoded and
+    /// encoded again, which fails if the sequence contains nucleotides other than `ACGT`.
+    /// See [`encode_sequence`] to encode an edited sequence.
+    pub fn encode_into_buffer(&self, dst: &mut Vec<u64>) -> Result<()> {
+        if matches!(self.bitsize, BitSize::Two) {
+            dst.extend_from_slice(&self.sbuf);
+            return Ok(());
+        }
+        let mut seq = Vec::with_capacity(self.slen as usize);
+        self.decode_s(&mut seq)?;
+        encode_sequence(&seq, dst)
+    }
+}
+impl Ord for OwnedRecord {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.cmp_sequence(other)
+            .then(self.slen.cmp(&other.slen))
+            .then(self.xlen.cmp(&other.xlen))
+            .then(self.flag.cmp(&other.flag))
+            .then_with(|| u8::from(self.bitsize).cmp(&u8::from(other.bitsize)))
+            .then_with(|| self.sbuf.cmp(&other.sbuf))
+            .then_with(|| self.xbuf.cmp(&other.xbuf))
+            .then_with(|| self.squal.cmp(&other.squal))
+            .then_with(|| self.xqual.cmp(&other.xqual))
+            .then_with(|| self.sheader.cmp(&other.sheader))
+            .then_with(|| self.xheader.cmp(&other.xheader))
+            .then(self.index.cmp(&other.index))
+    }
+}
+impl PartialOrd for OwnedRecord {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl OwnedRecord {
+    /// Compares the bases the primary sequences have in common
+    ///
+    /// 2-bit sequences are compared on their encoded words, whose codes are ordered like the
+    /// bases. Other sequences are decoded first.
+    fn cmp_sequence(&self, other: &Self) -> Ordering {
+        let n_bases = self.slen.min(other.slen) as usize;
+        if matches!(self.bitsize, BitSize::Two) && matches!(other.bitsize, BitSize::Two) {
+            return cmp_packed(&self.sbuf, &other.sbuf, n_bases);
+        }
+        // the words of an owned record always decode
+        let (lhs, rhs) = (
+            self.decode_s_alloc().unwrap_or_default(),
+            other.decode_s_alloc().unwrap_or_default(),
+        );
+        lhs[..n_bases.min(lhs.len())].cmp(&rhs[..n_bases.min(rhs.len())])
+    }
+}
+
+/// Compares the first `n_bases` bases of two 2-bit encoded sequences
+///
+/// Bases are packed from the least significant bits of each word, so the first differing
+/// base is found at the lowest differing bit pair.
+fn cmp_packed(lhs: &[u64], rhs: &[u64], n_bases: usize) -> Ordering {
+    for (idx, (&l, &r)) in lhs.iter().zip(rhs).enumerate() {
+        let valid = n_bases.saturating_sub(idx * 32).min(32);
+        if valid == 0 {
+            break;
+        }
+        let mask = if valid == 32 {
+            u64::MAX
+        } else {
+            (1 << (2 * valid)) - 1
+        };
+        let diff = (l ^ r) & mask;
+        if diff != 0 {
+            let shift = diff.trailing_zeros() & !1;
+            return ((l >> shift) & 0b11).cmp(&((r >> shift) & 0b11));
+        }
+    }
+    Ordering::Equal
+}
+impl From<bq::RefRecord<'_>> for OwnedRecord {
+    fn from(record: bq::RefRecord<'_>) -> Self {
+        Self::from_record(&record)
+    }
+}
+impl From<vbq::RefRecord<'_>> for OwnedRecord {
+    fn from(record: vbq::RefRecord<'_>) -> Self {
+        Self::from_record(&record)
+    }
+}
+impl BinseqRecord for OwnedRecord {
+    fn bitsize(&self) -> BitSize {
+        self.bitsize
+    }
+    fn index(&self) -> u64 {
+        self.index
+    }
+    fn flag(&self) -> Option<u64> {
+        self.flag
+    }
+    fn sheader(&self) -> &[u8] {
+        &self.sheader
+    }
+    fn xheader(&self) -> &[u8] {
+        &self.xheader
+    }
+    fn slen(&self) -> u64 {
+        self.slen
+    }
+    fn xlen(&self) -> u64 {
+        self.xlen
+    }
+    fn sbuf(&self) -> &[u64] {
+        &self.sbuf
+    }
+    fn xbuf(&self) -> &[u64] {
+        &self.xbuf
+    }
+    fn squal(&self) -> &[u8] {
+        &self.squal
+    }
+    fn xqual(&self) -> &[u8] {
+        &self.xqual
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_bq_record() {
+        let reader = bq::MmapReader::new("./data/subset.bq").unwrap();
+        let record = reader.get(3).unwrap();
+        let owned = OwnedRecord::from(record);
+        assert_eq!(owned.index(), 3);
+        assert_eq!(owned.flag(), record.flag());
+        assert_eq!(owned.sbuf(), record.sbuf());
+        assert_eq!(owned.xbuf(), record.xbuf());
+        assert_eq!(
+            owned.decode_s_alloc().unwrap(),
+            record.decode_s_alloc().unwrap()
+        );
+        assert_eq!(
+            owned.decode_x_alloc().unwrap(),
+            record.decode_x_alloc().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_encode_into_buffer_reflects_mutation() -> Result<()> {
+        let reader = bq::MmapReader::new("./data/subset.bq")?;
+        let owned = OwnedRecord::from(reader.get(0)?);
+        let mut encoded = Vec::new();
+        owned.encode_into_buffer(&mut encoded)?;
+        assert_eq!(encoded, owned.sbuf());
+
+        // Replace the base at position 5 with a different one
+        let mut seq = owned.decode_s_alloc()?;
+        let (base, code) = if seq[5] == b'A' { (b'C', 1) } else { (b'A', 0) };
+        seq[5] = base;
+        let mut mutated = Vec::new();
+        encode_sequence(&seq, &mut mutated)?;
+
+        let mask = 0b11 << 10;
+        assert_eq!(mutated[0] & mask, code << 10);
+        assert_eq!(mutated[0] & !mask, encoded[0] & !mask);
+        assert_eq!(mutated[1..], encoded[1..]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_vbq_record_outlives_block() {
+        let mut reader = vbq::MmapReader::new("./data/subset.vbq").unwrap();
+        let mut block = reader.new_block();
+        assert!(reader.read_block_into(&mut block).unwrap());
+
+        let (owned, expected) = {
+            let record = block.iter().next().unwrap();
+            let expected = record.decode_s_alloc().unwrap();
+            (OwnedRecord::from(record), expected)
+        };
+
+        // Reusing the block does not invalidate the owned record
+        assert!(reader.read_block_into(&mut block).unwrap());
+        assert_eq!(owned.index(), 0);
+        assert_eq!(owned.decode_s_alloc().unwrap(), expected);
+    }
+
+    #[test]
+    fn test_ord_by_sequence() -> Result<()> {
+        let owned = |seq: &[u8], flag: u64| {
+            let mut sbuf = Vec::new();
+            encode_sequence(seq, &mut sbuf).unwrap();
+            OwnedRecord {
+                bitsize: BitSize::Two,
+                index: 0,
+                flag: Some(flag),
+                slen: seq.len() as u64,
+                xlen: 0,
+                sbuf,
+                xbuf: Vec::new(),
+                squal: Vec::new(),
+                xqual: Vec::new(),
+                sheader: Vec::new(),
+                xheader: Vec::new(),
+            }
+        };
+        let long: Vec<u8> = (0..40).map(|i| b"ACGT"[i % 4]).collect();
+        let mut later = long.clone();
+        later[35] = b'T';
+        assert!(owned(b"ACGT", 0) < owned(b"ACGTA", 0));
+        assert!(owned(b"ACGTA", 0) < owned(b"ACGTC", 0));
+        assert!(owned(b"CA", 0) > owned(b"ACGTT", 0));
+        assert!(owned(&long, 0) < owned(&later, 0));
+        assert!(owned(b"ACGT", 1) > owned(b"ACGT", 0));
+        assert_eq!(owned(b"ACGT", 1).cmp(&owned(b"ACGT", 1)), Ordering::Equal);
+
+        // the packed comparison agrees with the decoded sequences
+        let reader = bq::MmapReader::new("./data/subset.bq")?;
+        let mut records: Vec<_> = (0..reader.num_records().min(200))
+            .map(|idx| reader.get(idx).map(OwnedRecord::from))
+            .collect::<Result<_>>()?;
+        records.sort();
+        for pair in records.windows(2) {
+            assert!(pair[0].decode_s_alloc()? <= pair[1].decode_s_alloc()?);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,278 @@
+use bitnuc::BitSize;
+
+use super::BinseqRecord;
+
+/// A paired record viewed as its two mates
+///
+/// Wraps any paired [`BinseqRecord`] (e.g. a [`bq::RefRecord`](crate::bq::RefRecord) or
+/// [`vbq::RefRecord`](crate::vbq::RefRecord)) and exposes each mate as a single-ended
+/// [`MateRecord`] through [`r1`](Self::r1) and [`r2`](Self::r2).
+///
+/// BINSEQ files store one flag per record, so both mates share the flag (and index) of the
+/// wrapped record. For single-end records, [`r2`](Self::r2) is an empty record.
+///
+/// # Examples
+///
+/// ```rust
+/// use binseq::{BinseqRecord, RefRecordPair, bq::MmapReader};
+///
+/// let reader = MmapReader::new("./data/subset.bq").unwrap();
+/// let pair = RefRecordPair::new(reader.get(0).unwrap());
+/// assert_eq!(pair.r1().slen(), reader.header().slen as u64);
+/// assert_eq!(pair.r2().slen(), reader.header().xlen as u64);
+/// assert_eq!(pair.r1().flag(), pair.r2().flag());
+/// ```
+#[derive(Clone, Copy)]
+pub struct RefRecordPair<R: BinseqRecord> {
+    /// The paired record
+    record: R,
+}
+impl<R: BinseqRecord> RefRecordPair<R> {
+    /// Creates a pair view of `record`
+    #[must_use]
+    pub fn new(record: R) -> Self {
+        Self { record }
+    }
+
+    /// Returns the first mate (the primary sequence of the record)
+    #[must_use]
+    pub fn r1(&self) -> MateRecord<'_, R> {
+        MateRecord {
+            record: &self.record,
+            extended: false,
+        }
+    }
+
+    /// Returns the second mate (the extended sequence of the record)
+    #[must_use]
+    pub fn r2(&self) -> MateRecord<'_, R> {
+        MateRecord {
+            record: &self.record,
+            extended: true,
+        }
+    }
+
+    /// Returns the wrapped record
+    #[must_use]
+    pub fn record(&self) -> &R {
+        &self.record
+    }
+
+    /// Consumes the pair and returns the wrapped record
+    #[must_use]
+    pub fn into_inner(self) -> R {
+        self.record
+    }
+}
+impl<R: BinseqRecord> From<R> for RefRecordPair<R> {
+    fn from(record: R) -> Self {
+        Self::new(record)
+    }
+}
+
+/// A single mate of a [`RefRecordPair`]
+///
+/// Implements [`BinseqRecord`] as a single-end record: the sequence, quality scores and
+/// header of the mate are exposed as the primary fields, and the extended fields are empty.
+/// The index and flag are those of the whole pair.
+pub struct MateRecord<'a, R: BinseqRecord> {
+    /// The paired record
+    record: &'a R,
+
+    /// Whether this is the second mate (the extended sequence)
+    extended: bool,
+}
+impl<R: BinseqRecord> Clone for MateRecord<'_, R> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+impl<R: BinseqRecord> Copy for MateRecord<'_, R> {}
+impl<R: BinseqRecord> MateRecord<'_, R> {
+    /// Returns `true` if this is the second mate of the pair
+    #[must_use]
+    pub fn is_r2(&self) -> bool {
+        self.extended
+    }
+}
+impl<R: BinseqRecord> BinseqRecord for MateRecord<'_, R> {
+    fn bitsize(&self) -> BitSize {
+        self.record.bitsize()
+    }
+    fn index(&self) -> u64 {
+        self.record.index()
+    }
+    fn flag(&self) -> Option<u64> {
+        self.record.flag()
+    }
+    fn sheader(&self) -> &[u8] {
+        if self.extended {
+            self.record.xheader()
+        } else {
+            self.record.sheader()
+        }
+    }
+    fn xheader(&self) -> &[u8] {
+        &[]
+    }
+    fn slen(&self) -> u64 {
+        if self.extended {
+            self.record.xlen()
+        } else {
+            self.record.slen()
+        }
+    }
+    fn xlen(&self) -> u64 {
+        0
+    }
+    fn sbuf(&self) -> &[u64] {
+        if self.extended {
+            self.record.xbuf()
+        } else {
+            self.record.sbuf()
+        }
+    }
+    fn xbuf(&self) -> &[u64] {
+        &[]
+    }
+    fn squal(&self) -> &[u8] {
+        if self.extended {
+            self.record.xqual()
+        } else {
+            self.record.squal()
+        }
+    }
+    fn sseq(&self) -> &[u8] {
+        if self.extended {
+            self.record.xseq()
+        } else {
+            self.record.sseq()
+        }
+    }
+    fn xseq(&self) -> &[u8] {
+        &[]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+
+    use super::*;
+    use crate::{Result, SequencingRecordBuilder, bq, vbq};
+
+    const R1: [&[u8]; 3] = [b"ACGTACGTAA", b"TTTTGGGGCC", b"GATTACAGAT"];
+    const R2: [&[u8]; 3] = [b"CCCCAAAATTTTGG", b"ACACACACACACAC", b"GGGGGGGGTTTTTT"];
+
+    #[test]
+    fn test_paired_bq_mates() -> Result<()> {
+        for flags in [true, false] {
+            let header = bq::FileHeaderBuilder::new()
+                .slen(10)
+                .xlen(14)
+                .flags(flags)
+                .build()?;
+            let mut writer = bq::WriterBuilder::default()
+                .header(header)
+                .build(Vec::new())?;
+            for (idx, (s, x)) in R1.iter().zip(R2).enumerate() {
+                let record = SequencingRecordBuilder::default()
+                    .s_seq(s)
+                    .x_seq(x)
+                    .flag(idx as u64 + 7)
+                    .build()?;
+                writer.push(record)?;
+            }
+            let reader = bq::MmapReader::from_bytes(writer.into_inner())?;
+
+            let (mut s, mut x) = (Vec::new(), Vec::new());
+            for idx in 0..reader.num_records() {
+                let record = reader.get(idx)?;
+                record.decode_pair(&mut s, &mut x)?;
+                assert_eq!((s.as_slice(), x.as_slice()), (R1[idx], R2[idx]));
+
+                let pair = RefRecordPair::new(record);
+                let (r1, r2) = (pair.r1(), pair.r2());
+                assert!(!r1.is_paired() && !r2.is_paired() && r2.is_r2());
+                assert_eq!(r1.decode_s_alloc()?, R1[idx]);
+                assert_eq!(r2.decode_s_alloc()?, R2[idx]);
+                assert_eq!((r1.slen(), r2.slen()), (10, 14));
+                assert!(r1.decode_x_alloc()?.is_empty());
+
+                let flag = flags.then_some(idx as u64 + 7);
+                assert_eq!((r1.flag(), r2.flag()), (flag, flag));
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_paired_vbq_mates_with_quality_and_headers() -> Result<()> {
+        let path = std::env::temp_dir().join("binseq_test_record_pair.vbq");
+        let quals: Vec<Vec<u8>> = R1.iter().map(|s| vec![b'I'; s.len()]).collect();
+        let xquals: Vec<Vec<u8>> = R2.iter().map(|x| vec![b'#'; x.len()]).collect();
+
+        let header = vbq::FileHeaderBuilder::new()
+            .paired(true)
+            .qual(true)
+            .headers(true)
+            .build();
+        let mut writer = vbq::WriterBuilder::default()
+            .header(header)
+            .build(File::create(&path)?)?;
+        for idx in 0..R1.len() {
+            let (sheader, xheader) = (format!("read_{idx}/1"), format!("read_{idx}/2"));
+            let record = SequencingRecordBuilder::default()
+                .s_seq(R1[idx])
+                .s_qual(&quals[idx])
+                .s_header(sheader.as_bytes())
+                .x_seq(R2[idx])
+                .x_qual(&xquals[idx])
+                .x_header(xheader.as_bytes())
+                .build()?;
+            writer.push(record)?;
+        }
+        writer.finish()?;
+        drop(writer);
+
+        let mut reader = vbq::MmapReader::new(&path)?;
+        let mut block = reader.new_block();
+        let mut n_records = 0;
+        while reader.read_block_into(&mut block)? {
+            for record in block.iter() {
+                let idx = record.index() as usize;
+                let pair = RefRecordPair::from(record);
+                let (r1, r2) = (pair.r1(), pair.r2());
+                assert_eq!(r1.decode_s_alloc()?, R1[idx]);
+                assert_eq!(r2.decode_s_alloc()?, R2[idx]);
+                assert_eq!(r1.squal(), quals[idx].as_slice());
+                assert_eq!(r2.squal(), xquals[idx].as_slice());
+                assert!(r1.xqual().is_empty() && r2.xqual().is_empty());
+                assert_eq!(r1.sheader(), format!("read_{idx}/1").as_bytes());
+                assert_eq!(r2.sheader(), format!("read_{idx}/2").as_bytes());
+                assert!(r2.xheader().is_empty());
+                n_records += 1;
+            }
+        }
+        assert_eq!(n_records, R1.len());
+
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_pair_clears_buffers() -> Result<()> {
+        let reader = bq::MmapReader::new("./data/subset.bq")?;
+        let record = reader.get(0)?;
+        let (mut s, mut x) = (b"stale".to_vec(), b"stale".to_vec());
+        record.decode_pair(&mut s, &mut x)?;
+        assert_eq!(s, record.decode_s_alloc()?);
+        assert_eq!(x, record.decode_x_alloc()?);
+
+        // Mates are single-end, so their extended buffer is left empty
+        let pair = RefRecordPair::new(record);
+        pair.r1().decode_pair(&mut s, &mut x)?;
+        assert!(x.is_empty());
+        Ok(())
+    }
+}
@@ -189,41 +189,42 @@ impl<'a> SequencingRecord<'a> {
         // VBQ packs sequences into u64 words
         let nucs_per_word = nucs_per_byte * 8;
 
-        let mut size = 0;
+        // Sizes saturate so that pathological lengths exceed any block size instead of wrapping
+        let mut size: usize = 0;
 
         // Length prefixes: s_len and x_len (always present)
-        size += 16; // 2 * u64
+        size = size.saturating_add(16); // 2 * u64
 
         // Flag (8 bytes, if has_flags)
         if has_flags {
-            size += 8;
+            size = size.saturating_add(8);
         }
 
         // Primary sequence (encoded into u64 words)
         let s_chunks = self.s_seq.len().div_ceil(nucs_per_word);
-        size += s_chunks * 8;
+        size = size.saturating_add(s_chunks.saturating_mul(8));
 
         // Extended sequence (only if writer is configured for paired)
         if is_paired {
             let x_chunks = self.x_seq.map_or(0, |x| x.len().div_ceil(nucs_per_word));
-            size += x_chunks * 8;
+            size = size.saturating_add(x_chunks.saturating_mul(8));
         }
 
         // Quality scores (raw bytes, only if writer configured for qualities)
         if has_qualities {
-            size += self.s_qual.map_or(0, <[u8]>::len);
+            size = size.saturating_add(self.s_qual.map_or(0, <[u8]>::len));
             if is_paired {
-                size += self.x_qual.map_or(0, <[u8]>::len);
+                size = size.saturating_add(self.x_qual.map_or(0, <[u8]>::len));
             }
         }
 
         // Headers (length prefix + raw bytes, only if writer configured for headers)
         if has_headers {
             if let Some(h) = self.s_header {
-                size += 8 + h.len(); // length prefix + header bytes
+                size = size.saturating_add(h.len().saturating_add(8)); // length prefix + header bytes
             }
             if is_paired && let Some(h) = self.x_header {
-                size += 8 + h.len(); // length prefix + header bytes
+                size = size.saturating_add(h.len().saturating_add(8)); // length prefix + header bytes
             }
         }
 
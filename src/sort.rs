@@ -0,0 +1,614 @@
+//! Sorting BINSEQ files by flag or sequence with an external merge sort
+//!
+//! [`sort`] reads records in chunks bounded by a memory budget, sorts each chunk in memory and
+//! spills it to a temporary VBQ file (a *run*) next to a file of its sort keys. The runs are
+//! then merged into the output writer, reading one block of each run at a time. Inputs fitting
+//! into the budget are sorted in memory without touching the disk.
+//!
+//! The sort is stable: records with equal keys keep their input order.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use binseq::prelude::*;
+//! use binseq::{SortKey, SortOptions, sort};
+//!
+//! // cluster records by the barcode stored in their flag
+//! let reader = BinseqReader::new("input.vbq")?;
+//! let mut writer = binseq::create_vbq("sorted.vbq", WriterOpts::default())?;
+//! sort(reader, &mut writer, &SortKey::Flag, SortOptions::default())?;
+//! writer.finish()?;
+//! # Ok::<(), binseq::Error>(())
+//! ```
+
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, VecDeque};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use bitnuc::BitSize;
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+
+use crate::error::WriteError;
+use crate::processors::RecordView;
+use crate::vbq::{self, FileHeaderBuilder, RecordBlock};
+use crate::{
+    BinseqReader, BinseqRecord, OwnedRecord, Policy, RecordWriter, Result, SequencingRecord, bq,
+};
+
+/// Longest sequence prefix usable as a [`SortKey::SequencePrefix`] (3 bits per base)
+pub const MAX_SORT_PREFIX: usize = 21;
+
+/// Memory budget of [`sort`] unless set with [`SortOptions::memory_budget`] (256 MB)
+pub const DEFAULT_SORT_MEMORY: usize = 256 * 1024 * 1024;
+
+/// Distinguishes the runs of concurrent sorts in the same process
+static RUN_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// The key records are ordered by in [`sort`]
+pub enum SortKey {
+    /// The flag of each record (records without a flag sort as `0`)
+    Flag,
+
+    /// The first `k` bases of the primary sequence, in lexicographic order
+    ///
+    /// `k` is capped at [`MAX_SORT_PREFIX`]. Shorter sequences sort before longer sequences
+    /// sharing their prefix.
+    SequencePrefix(usize),
+
+    /// A key computed from each record, see [`SortKey::custom`]
+    Custom(Box<dyn Fn(&RecordView) -> u64>),
+}
+impl SortKey {
+    /// Orders records by the key returned by `key_fn`
+    ///
+    /// The record index seen by `key_fn` is the position of the record in the input.
+    #[must_use]
+    pub fn custom<F: Fn(&RecordView) -> u64 + 'static>(key_fn: F) -> Self {
+        Self::Custom(Box::new(key_fn))
+    }
+
+    /// Computes the key of `record`, whose decoded primary sequence is `sseq`
+    fn key(&self, record: &RecordView, sseq: &[u8]) -> u64 {
+        match self {
+            Self::Flag => record.flag().unwrap_or(0),
+            Self::SequencePrefix(k) => prefix_key(sseq, *k),
+            Self::Custom(key_fn) => key_fn(record),
+        }
+    }
+}
+
+/// Packs the first `k` bases of `seq` into a key ordered like the bases
+fn prefix_key(seq: &[u8], k: usize) -> u64 {
+    (0..k.min(MAX_SORT_PREFIX)).fold(0, |key, idx| {
+        let code = match seq.get(idx) {
+            None => 0,
+            Some(b'A') => 1,
+            Some(b'C') => 2,
+            Some(b'G') => 3,
+            Some(b'N') => 4,
+            Some(b'T') => 5,
+            Some(_) => 6,
+        };
+        (key << 3) | code
+    })
+}
+
+/// Configuration of [`sort`]
+#[derive(Debug, Clone, Default)]
+pub struct SortOptions {
+    /// Approximate bytes of records held in memory at once
+    memory_budget: Option<usize>,
+
+    /// Directory of the temporary runs
+    temp_dir: Option<PathBuf>,
+}
+impl SortOptions {
+    /// Sets the approximate number of bytes of records sorted in memory at once
+    ///
+    /// Records are counted with their decoded sequences, quality scores and headers. Each
+    /// chunk of this size is spilled to a temporary run. Defaults to [`DEFAULT_SORT_MEMORY`].
+    #[must_use]
+    pub fn memory_budget(mut self, bytes: usize) -> Self {
+        self.memory_budget = Some(bytes);
+        self
+    }
+
+    /// Sets the directory of the temporary runs (the system temporary directory by default)
+    #[must_use]
+    pub fn temp_dir<P: Into<PathBuf>>(mut self, dir: P) -> Self {
+        self.temp_dir = Some(dir.into());
+        self
+    }
+}
+
+/// A decoded record with its sort key
+struct SortItem {
+    key: u64,
+    flag: Option<u64>,
+    paired: bool,
+    sseq: Vec<u8>,
+    squal: Vec<u8>,
+    sheader: Vec<u8>,
+    xseq: Vec<u8>,
+    xqual: Vec<u8>,
+    xheader: Vec<u8>,
+}
+impl SortItem {
+    /// Decodes `record`, keeping its quality scores and flag only if `qual` and `flags`
+    fn new<R: BinseqRecord>(record: &R, qual: bool, flags: bool) -> Result<Self> {
+        let paired = record.is_paired();
+        let mut item = Self {
+            key: 0,
+            flag: if flags { record.flag() } else { None },
+            paired,
+            sseq: Vec::new(),
+            squal: Vec::new(),
+            sheader: record.sheader().to_vec(),
+            xseq: Vec::new(),
+            xqual: Vec::new(),
+            xheader: Vec::new(),
+        };
+        record.decode_s(&mut item.sseq)?;
+        if qual {
+            item.squal.extend_from_slice(record.squal());
+        }
+        if paired {
+            record.decode_x(&mut item.xseq)?;
+            item.xheader.extend_from_slice(record.xheader());
+            if qual {
+                item.xqual.extend_from_slice(record.xqual());
+            }
+        }
+        Ok(item)
+    }
+
+    /// Approximate memory used by the item
+    fn size(&self) -> usize {
+        size_of::<Self>()
+            + self.sseq.len()
+            + self.squal.len()
+            + self.sheader.len()
+            + self.xseq.len()
+            + self.xqual.len()
+            + self.xheader.len()
+    }
+
+    fn as_record(&self) -> SequencingRecord<'_> {
+        let non_empty = |field: &[u8]| -> bool { !field.is_empty() };
+        SequencingRecord::new(
+            &self.sseq,
+            non_empty(self.squal.as_slice()).then_some(self.squal.as_slice()),
+            Some(self.sheader.as_slice()),
+            self.paired.then_some(self.xseq.as_slice()),
+            (self.paired && non_empty(self.xqual.as_slice())).then_some(self.xqual.as_slice()),
+            self.paired.then_some(self.xheader.as_slice()),
+            self.flag,
+        )
+    }
+}
+
+/// A sorted run spilled to disk, removed when dropped
+struct Run {
+    /// VBQ file of the records
+    records: PathBuf,
+    /// Little-endian `u64` keys of the records, in the same order
+    keys: PathBuf,
+}
+impl Drop for Run {
+    fn drop(&mut self) {
+        // best effort, the files are temporary
+        std::fs::remove_file(&self.records).ok();
+        std::fs::remove_file(&self.keys).ok();
+    }
+}
+
+/// Sequential reader of the items of a run
+struct RunCursor {
+    reader: vbq::MmapReader,
+    block: RecordBlock,
+    keys: BufReader<File>,
+    pending: VecDeque<SortItem>,
+}
+impl RunCursor {
+    fn open(run: &Run) -> Result<Self> {
+        let reader = vbq::MmapReader::new(&run.records)?;
+        Ok(Self {
+            block: reader.new_block(),
+            reader,
+            keys: BufReader::new(File::open(&run.keys)?),
+            pending: VecDeque::new(),
+        })
+    }
+
+    /// Returns the next item of the run, decoding the next block if needed
+    fn next_item(&mut self) -> Result<Option<SortItem>> {
+        let header = self.reader.header();
+        while self.pending.is_empty() {
+            if !self.reader.read_block_into(&mut self.block)? {
+                return Ok(None);
+            }
+            for record in self.block.iter() {
+                let mut item = SortItem::new(&record, header.qual, header.flags)?;
+                item.key = self.keys.read_u64::<LittleEndian>()?;
+                self.pending.push_back(item);
+            }
+        }
+        Ok(self.pending.pop_front())
+    }
+}
+
+/// State of a sort: the current chunk and the runs spilled so far
+struct Sorter<'k> {
+    key: &'k SortKey,
+    budget: usize,
+    temp_dir: PathBuf,
+    chunk: Vec<SortItem>,
+    chunk_size: usize,
+    runs: Vec<Run>,
+}
+impl Sorter<'_> {
+    fn push(&mut self, record: &RecordView) -> Result<()> {
+        let mut item = SortItem::new(record, true, true)?;
+        item.key = self.key.key(record, &item.sseq);
+        self.chunk_size += item.size();
+        self.chunk.push(item);
+        if self.chunk_size >= self.budget {
+            self.spill()?;
+        }
+        Ok(())
+    }
+
+    /// Sorts the current chunk and writes it to a new run
+    fn spill(&mut self) -> Result<()> {
+        if self.chunk.is_empty() {
+            return Ok(());
+        }
+        // stable, so equal keys keep their input order
+        self.chunk.sort_by_key(|item| item.key);
+
+        let stem = format!(
+            "binseq-sort-{}-{}",
+            std::process::id(),
+            RUN_ID.fetch_add(1, Ordering::Relaxed)
+        );
+        let run = Run {
+            records: self.temp_dir.join(format!("{stem}.vbq")),
+            keys: self.temp_dir.join(format!("{stem}.keys")),
+        };
+
+        // runs store every field present in the chunk, 4-bit encoded to keep all bases
+        let first = &self.chunk[0];
+        let header = FileHeaderBuilder::new()
+            .bitsize(BitSize::Four)
+            .paired(first.paired)
+            .qual(
+                self.chunk
+                    .iter()
+                    .all(|item| !item.squal.is_empty() && (!item.paired || !item.xqual.is_empty())),
+            )
+            .headers(self.chunk.iter().any(|item| !item.sheader.is_empty()))
+            .flags(self.chunk.iter().any(|item| item.flag.is_some()))
+            .build();
+        let mut writer = vbq::WriterBuilder::default()
+            .header(header)
+            .policy(Policy::BreakOnInvalid)
+            .build(BufWriter::new(File::create(&run.records)?))?;
+        let mut keys = BufWriter::new(File::create(&run.keys)?);
+        for item in &self.chunk {
+            if item.paired != first.paired {
+                return Err(WriteError::ConfigurationMismatch {
+                    attribute: "paired",
+                    expected: first.paired,
+                    actual: item.paired,
+                }
+                .into());
+            }
+            writer.push(item.as_record())?;
+            keys.write_u64::<LittleEndian>(item.key)?;
+        }
+        writer.finish()?;
+        keys.flush()?;
+
+        self.runs.push(run);
+        self.chunk.clear();
+        self.chunk_size = 0;
+        Ok(())
+    }
+
+    /// Writes all records to `writer` in sorted order
+    fn finish<W: RecordWriter>(mut self, writer: &mut W) -> Result<()> {
+        if self.runs.is_empty() {
+            self.chunk.sort_by_key(|item| item.key);
+            for item in &self.chunk {
+                writer.push(item.as_record())?;
+            }
+            return Ok(());
+        }
+        self.spill()?;
+
+        // k-way merge, ties are broken by run so earlier input comes first
+        let mut cursors = self
+            .runs
+            .iter()
+            .map(RunCursor::open)
+            .collect::<Result<Vec<_>>>()?;
+        let mut heads = Vec::with_capacity(cursors.len());
+        let mut heap = BinaryHeap::with_capacity(cursors.len());
+        for (run_idx, cursor) in cursors.iter_mut().enumerate() {
+            let head = cursor.next_item()?;
+            if let Some(item) = &head {
+                heap.push(Reverse((item.key, run_idx)));
+            }
+            heads.push(head);
+        }
+        while let Some(Reverse((_, run_idx))) = heap.pop() {
+            if let Some(item) = heads[run_idx].take() {
+                writer.push(item.as_record())?;
+            }
+            heads[run_idx] = cursors[run_idx].next_item()?;
+            if let Some(item) = &heads[run_idx] {
+                heap.push(Reverse((item.key, run_idx)));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Calls `f` on every record of `reader`, in file order
+fn for_each_record<F: FnMut(&RecordView) -> Result<()>>(
+    reader: BinseqReader,
+    mut f: F,
+) -> Result<()> {
+    match reader {
+        BinseqReader::Bq(reader) => {
+            for idx in 0..reader.num_records() {
+                f(&RecordView::new(&reader.get(idx)?))?;
+            }
+        }
+        BinseqReader::PairedBq(reader) => {
+            for idx in 0..reader.num_records() {
+                f(&RecordView::new(&reader.get(idx)?))?;
+            }
+        }
+        BinseqReader::Vbq(mut reader) => {
+            let mut block = reader.new_block();
+            while reader.read_block_into(&mut block)? {
+                for record in block.iter() {
+                    f(&RecordView::new(&record))?;
+                }
+            }
+        }
+        BinseqReader::Cbq(mut reader) => {
+            let blocks: Vec<_> = reader.index().iter_blocks().collect();
+            for range in blocks {
+                for record in reader.iter_block_records(range)? {
+                    f(&RecordView::new(&record))?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Writes the records of `reader` to `writer`, ordered by `key`
+///
+/// Records are decoded and written with their quality scores, headers and flag; which of
+/// them are stored in the output depends on the configuration of `writer`. The sort is
+/// stable. Inputs larger than the memory budget of `opts` are sorted in runs spilled to
+/// temporary files, which are removed once the sort completes or fails.
+///
+/// The writer is not finished.
+pub fn sort<W: RecordWriter>(
+    reader: BinseqReader,
+    writer: &mut W,
+    key: &SortKey,
+    opts: SortOptions,
+) -> Result<()> {
+    let mut sorter = Sorter {
+        key,
+        budget: opts.memory_budget.unwrap_or(DEFAULT_SORT_MEMORY),
+        temp_dir: opts.temp_dir.unwrap_or_else(std::env::temp_dir),
+        chunk: Vec::new(),
+        chunk_size: 0,
+        runs: Vec::new(),
+    };
+    for_each_record(reader, |record| sorter.push(record))?;
+    sorter.finish(writer)
+}
+
+/// Sorts the records of a BQ file by their primary sequence into a new BQ file
+///
+/// Records are ordered like [`OwnedRecord`]: lexicographically by primary sequence, then by
+/// flag. All records are held in memory; encoded sequences are copied without re-encoding,
+/// and the output has the header of the input. With the `rayon` feature the records are
+/// sorted in parallel.
+pub fn sort_bq_file<P: AsRef<Path>, Q: AsRef<Path>>(path: P, output: Q) -> Result<()> {
+    let reader = bq::MmapReader::new(path)?;
+    let mut records = (0..reader.num_records())
+        .map(|idx| reader.get(idx).map(OwnedRecord::from))
+        .collect::<Result<Vec<_>>>()?;
+    #[cfg(feature = "rayon")]
+    {
+        use rayon::slice::ParallelSliceMut;
+        records.par_sort_unstable();
+    }
+    #[cfg(not(feature = "rayon"))]
+    records.sort_unstable();
+
+    let mut writer = bq::WriterBuilder::default()
+        .header(reader.header())
+        .build(BufWriter::new(File::create(output)?))?;
+    for record in &records {
+        if record.is_paired() {
+            writer.write_encoded_direct_paired(record.flag(), record.sbuf(), record.xbuf())?;
+        } else {
+            writer.write_encoded_direct(record.flag(), record.sbuf())?;
+        }
+    }
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SequencingRecordBuilder;
+
+    /// Writes `(flag, sequence)` records to an in-memory VBQ file with quality scores
+    fn write_input(records: &[(u64, Vec<u8>)]) -> Result<Vec<u8>> {
+        let header = FileHeaderBuilder::new()
+            .block(1024)
+            .qual(true)
+            .flags(true)
+            .headers(true)
+            .build();
+        let mut buffer = Vec::new();
+        let mut writer = vbq::WriterBuilder::default()
+            .header(header)
+            .build(&mut buffer)?;
+        for (idx, (flag, seq)) in records.iter().enumerate() {
+            let qual = vec![b'!' + (idx % 40) as u8; seq.len()];
+            let name = format!("read{idx}");
+            writer.push(
+                SequencingRecordBuilder::default()
+                    .s_seq(seq)
+                    .s_qual(&qual)
+                    .s_header(name.as_bytes())
+                    .flag(*flag)
+                    .build()?,
+            )?;
+        }
+        writer.finish()?;
+        drop(writer);
+        Ok(buffer)
+    }
+
+    /// Sorts `input` and returns the flags, sequences and headers of the output
+    fn sort_vbq(
+        input: Vec<u8>,
+        key: &SortKey,
+        opts: SortOptions,
+    ) -> Result<Vec<(u64, Vec<u8>, Vec<u8>)>> {
+        let header = FileHeaderBuilder::new()
+            .qual(true)
+            .flags(true)
+            .headers(true)
+            .build();
+        let mut buffer = Vec::new();
+        let mut writer = vbq::WriterBuilder::default()
+            .header(header)
+            .build(&mut buffer)?;
+        sort(BinseqReader::from_bytes(input)?, &mut writer, key, opts)?;
+        writer.finish()?;
+        drop(writer);
+
+        let mut reader = vbq::MmapReader::from_bytes(buffer)?;
+        let mut block = reader.new_block();
+        let mut output = Vec::new();
+        while reader.read_block_into(&mut block)? {
+            for record in block.iter() {
+                output.push((
+                    record.flag().unwrap_or_default(),
+                    record.decode_s_alloc()?,
+                    record.sheader().to_vec(),
+                ));
+            }
+        }
+        Ok(output)
+    }
+
+    fn records(n: usize) -> Vec<(u64, Vec<u8>)> {
+        (0..n)
+            .map(|idx| {
+                let seq = (0..20 + idx % 5).map(|j| b"ACGT"[(idx * 7 + j) % 4]);
+                ((idx * 13 % 5) as u64, seq.collect())
+            })
+            .collect()
+    }
+
+    /// Expected output of a stable sort by `key_fn`
+    fn expected<K: Ord>(
+        records: &[(u64, Vec<u8>)],
+        key_fn: impl Fn(&(u64, Vec<u8>)) -> K,
+    ) -> Vec<(u64, Vec<u8>, Vec<u8>)> {
+        let mut indexed: Vec<_> = records.iter().enumerate().collect();
+        indexed.sort_by_key(|(_, record)| key_fn(record));
+        indexed
+            .into_iter()
+            .map(|(idx, (flag, seq))| (*flag, seq.clone(), format!("read{idx}").into_bytes()))
+            .collect()
+    }
+
+    #[test]
+    fn test_sort_by_flag_is_stable() -> Result<()> {
+        let records = records(500);
+        let input = write_input(&records)?;
+        let want = expected(&records, |(flag, _)| *flag);
+
+        // in memory, and with runs of about 20 records
+        for budget in [DEFAULT_SORT_MEMORY, 20 * (size_of::<SortItem>() + 50)] {
+            let opts = SortOptions::default().memory_budget(budget);
+            assert_eq!(sort_vbq(input.clone(), &SortKey::Flag, opts)?, want);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_sort_by_sequence_prefix_and_custom_key() -> Result<()> {
+        let records = records(300);
+        let input = write_input(&records)?;
+        let opts = SortOptions::default().memory_budget(4096);
+
+        let want = expected(&records, |(_, seq)| seq[..6].to_vec());
+        let sorted = sort_vbq(input.clone(), &SortKey::SequencePrefix(6), opts.clone())?;
+        assert_eq!(sorted, want);
+
+        // reverse input order through the record index
+        let key = SortKey::custom(|record| u64::MAX - record.index());
+        let sorted = sort_vbq(input, &key, opts)?;
+        let mut want = expected(&records, |_| 0);
+        want.reverse();
+        assert_eq!(sorted, want);
+        Ok(())
+    }
+
+    #[test]
+    fn test_prefix_key_order() {
+        assert!(prefix_key(b"AC", 4) < prefix_key(b"ACA", 4));
+        assert!(prefix_key(b"ACGT", 4) < prefix_key(b"ACNA", 4));
+        assert!(prefix_key(b"ACNA", 4) < prefix_key(b"ACTA", 4));
+        assert_eq!(prefix_key(b"ACGTA", 4), prefix_key(b"ACGTC", 4));
+    }
+
+    #[test]
+    fn test_sort_bq_file() -> Result<()> {
+        let input = std::env::temp_dir().join("binseq_test_sort_bq_input.bq");
+        let output = std::env::temp_dir().join("binseq_test_sort_bq_output.bq");
+        let header = bq::FileHeaderBuilder::new().slen(50).flags(true).build()?;
+        let mut writer = bq::WriterBuilder::default()
+            .header(header)
+            .build(BufWriter::new(File::create(&input)?))?;
+        for idx in 0..1000_usize {
+            let seq: Vec<u8> = (0..50)
+                .map(|j| b"ACGT"[(idx * 7919 + j * (idx % 13 + 1)) % 4])
+                .collect();
+            writer.write_record(Some(idx as u64), &seq)?;
+        }
+        writer.flush()?;
+        drop(writer);
+
+        sort_bq_file(&input, &output)?;
+        let reader = bq::MmapReader::new(&output)?;
+        assert_eq!(reader.num_records(), 1000);
+        let sequences = (0..1000)
+            .map(|idx| reader.get(idx)?.decode_s_alloc())
+            .collect::<Result<Vec<_>>>()?;
+        assert!(sequences.is_sorted());
+
+        std::fs::remove_file(input)?;
+        std::fs::remove_file(output)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,231 @@
+use std::fs::File;
+use std::io::{self, Write};
+use std::ops::Deref;
+use std::sync::Arc;
+
+use memmap2::Mmap;
+
+use crate::Result;
+use crate::error::ReadError;
+
+/// Size of the chunks in which unmapped files are copied to a writer
+const COPY_CHUNK_SIZE: usize = 1 << 20;
+
+/// Random access to the bytes of a file by offset
+///
+/// Implemented by in-memory bytes, which also hand out slices without copying, and by
+/// [`FileSource`], which reads every range from the file on demand.
+pub(crate) trait ReadAt {
+    /// Returns the total number of bytes
+    fn size(&self) -> u64;
+
+    /// Fills `buf` with the bytes starting at `offset`
+    ///
+    /// Fails with [`ReadError::UnexpectedEndOfFile`] if the range extends past the end.
+    fn read_exact_at(&self, buf: &mut [u8], offset: u64) -> Result<()>;
+
+    /// Returns the `len` bytes at `offset` without copying, if they are held in memory
+    fn slice_at(&self, _offset: u64, _len: usize) -> Option<&[u8]> {
+        None
+    }
+
+    /// Checks if the bytes are a memory-mapped file
+    fn is_mapped(&self) -> bool {
+        false
+    }
+
+    /// Returns the `len` bytes at `offset`, borrowed if they are in memory or read into `buf`
+    fn bytes_at<'a>(&'a self, offset: u64, len: usize, buf: &'a mut Vec<u8>) -> Result<&'a [u8]> {
+        if let Some(bytes) = self.slice_at(offset, len) {
+            return Ok(bytes);
+        }
+        // Check the range before allocating for it
+        if offset
+            .checked_add(len as u64)
+            .is_none_or(|end| end > self.size())
+        {
+            return Err(end_of_file(offset));
+        }
+        buf.clear();
+        buf.resize(len, 0);
+        self.read_exact_at(buf, offset)?;
+        Ok(buf)
+    }
+
+    /// Copies all bytes to `writer`, returning the number of bytes written
+    fn write_all_to(&self, writer: &mut dyn Write) -> Result<u64> {
+        let size = self.size();
+        if let Some(bytes) = usize::try_from(size)
+            .ok()
+            .and_then(|len| self.slice_at(0, len))
+        {
+            writer.write_all(bytes)?;
+            return Ok(size);
+        }
+        let mut buf = Vec::new();
+        let mut offset = 0;
+        while offset < size {
+            let len = usize::try_from(size - offset)
+                .map_or(COPY_CHUNK_SIZE, |rest| rest.min(COPY_CHUNK_SIZE));
+            writer.write_all(self.bytes_at(offset, len, &mut buf)?)?;
+            offset += len as u64;
+        }
+        Ok(size)
+    }
+}
+
+/// Error for a read at `offset` extending past the end of the data
+fn end_of_file(offset: u64) -> crate::Error {
+    ReadError::UnexpectedEndOfFile(usize::try_from(offset).unwrap_or(usize::MAX)).into()
+}
+
+impl ReadAt for [u8] {
+    fn size(&self) -> u64 {
+        self.len() as u64
+    }
+
+    fn read_exact_at(&self, buf: &mut [u8], offset: u64) -> Result<()> {
+        let bytes = self
+            .slice_at(offset, buf.len())
+            .ok_or_else(|| end_of_file(offset))?;
+        buf.copy_from_slice(bytes);
+        Ok(())
+    }
+
+    fn slice_at(&self, offset: u64, len: usize) -> Option<&[u8]> {
+        let start = usize::try_from(offset).ok()?;
+        self.get(start..start.checked_add(len)?)
+    }
+}
+
+/// An open file read with positional reads instead of being memory-mapped
+///
+/// Every access reads the requested range at its offset (`pread` on Unix), so memory use is
+/// bounded by the ranges in use rather than the size of the file. Reads do not move a shared
+/// cursor and can be issued from several threads at once.
+pub(crate) struct FileSource {
+    file: File,
+    len: u64,
+}
+impl FileSource {
+    /// Wraps an open file, taking its current length as the size of the data
+    pub(crate) fn new(file: File) -> Result<Self> {
+        let len = file.metadata()?.len();
+        Ok(Self { file, len })
+    }
+}
+impl ReadAt for FileSource {
+    fn size(&self) -> u64 {
+        self.len
+    }
+
+    fn read_exact_at(&self, buf: &mut [u8], offset: u64) -> Result<()> {
+        if offset
+            .checked_add(buf.len() as u64)
+            .is_none_or(|end| end > self.len)
+        {
+            return Err(end_of_file(offset));
+        }
+        read_exact_at(&self.file, buf, offset)?;
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+fn read_exact_at(file: &File, buf: &mut [u8], offset: u64) -> io::Result<()> {
+    std::os::unix::fs::FileExt::read_exact_at(file, buf, offset)
+}
+
+#[cfg(windows)]
+fn read_exact_at(file: &File, mut buf: &mut [u8], mut offset: u64) -> io::Result<()> {
+    use std::os::windows::fs::FileExt;
+
+    // `seek_read` may return fewer bytes than requested, like `read`
+    while !buf.is_empty() {
+        match file.seek_read(buf, offset) {
+            Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+            Ok(n) => {
+                buf = &mut buf[n..];
+                offset += n as u64;
+            }
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(any(unix, windows)))]
+fn read_exact_at(_file: &File, _buf: &mut [u8], _offset: u64) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "positional reads are not supported on this platform",
+    ))
+}
+
+/// The bytes backing a reader
+///
+/// Readers are usually backed by a memory-mapped file, but can also parse contents that are
+/// already in memory (e.g. fetched over the network) without writing a temporary file.
+pub(crate) enum ByteSource {
+    /// A memory-mapped file
+    Mmap(Mmap),
+
+    /// An in-memory buffer
+    Memory(Arc<[u8]>),
+
+    /// A copy of an in-memory buffer that was not aligned to 8 bytes
+    Aligned { words: Vec<u64>, len: usize },
+}
+impl ByteSource {
+    /// Memory-maps an open file
+    pub(crate) fn map(file: &File) -> Result<Self> {
+        // Safety: the file is open and won't be modified while mapped
+        let mmap = unsafe { Mmap::map(file)? };
+        Ok(Self::Mmap(mmap))
+    }
+
+    /// Wraps an in-memory buffer
+    ///
+    /// Encoded records are read as `u64` slices, so buffers that are not aligned to 8 bytes
+    /// are copied into an aligned allocation.
+    pub(crate) fn memory(data: Arc<[u8]>) -> Self {
+        if data.as_ptr().align_offset(align_of::<u64>()) == 0 {
+            return Self::Memory(data);
+        }
+        let mut words = vec![0u64; data.len().div_ceil(8)];
+        bytemuck::cast_slice_mut::<u64, u8>(&mut words)[..data.len()].copy_from_slice(&data);
+        Self::Aligned {
+            words,
+            len: data.len(),
+        }
+    }
+}
+impl Deref for ByteSource {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            Self::Mmap(mmap) => mmap,
+            Self::Memory(bytes) => bytes,
+            Self::Aligned { words, len } => &bytemuck::cast_slice::<u64, u8>(words)[..*len],
+        }
+    }
+}
+impl ReadAt for ByteSource {
+    fn size(&self) -> u64 {
+        self.len() as u64
+    }
+
+    fn read_exact_at(&self, buf: &mut [u8], offset: u64) -> Result<()> {
+        (**self).read_exact_at(buf, offset)
+    }
+
+    fn slice_at(&self, offset: u64, len: usize) -> Option<&[u8]> {
+        (**self).slice_at(offset, len)
+    }
+
+    fn is_mapped(&self) -> bool {
+        matches!(self, Self::Mmap(_))
+    }
+}
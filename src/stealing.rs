@@ -0,0 +1,92 @@
+use std::iter;
+use std::sync::Arc;
+
+use crossbeam_deque::{Injector, Steal, Stealer, Worker};
+
+/// A per-thread handle onto a shared work-stealing queue of work units
+///
+/// All units are loaded into a global injector up front. Each thread first drains its own
+/// local deque, refills it in batches from the injector, and finally steals from the local
+/// deques of other threads. Since no units are added after construction, a thread can stop
+/// as soon as [`WorkQueue::next`] returns `None`.
+pub(crate) struct WorkQueue {
+    /// Local deque of this thread
+    local: Worker<usize>,
+
+    /// Global queue shared by all threads
+    injector: Arc<Injector<usize>>,
+
+    /// Stealing handles onto the local deques of all threads
+    stealers: Arc<Vec<Stealer<usize>>>,
+}
+impl WorkQueue {
+    /// Builds one queue per thread over the provided work units
+    pub(crate) fn build(units: impl IntoIterator<Item = usize>, num_threads: usize) -> Vec<Self> {
+        let injector = Arc::new(Injector::new());
+        for unit in units {
+            injector.push(unit);
+        }
+
+        let locals: Vec<_> = (0..num_threads).map(|_| Worker::new_fifo()).collect();
+        let stealers = Arc::new(locals.iter().map(Worker::stealer).collect::<Vec<_>>());
+
+        locals
+            .into_iter()
+            .map(|local| Self {
+                local,
+                injector: Arc::clone(&injector),
+                stealers: Arc::clone(&stealers),
+            })
+            .collect()
+    }
+
+    /// Returns the next work unit for this thread, or `None` once all units are taken
+    pub(crate) fn next(&self) -> Option<usize> {
+        self.local.pop().or_else(|| {
+            iter::repeat_with(|| {
+                self.injector.steal_batch_and_pop(&self.local).or_else(|| {
+                    self.stealers
+                        .iter()
+                        .map(Stealer::steal)
+                        .collect::<Steal<_>>()
+                })
+            })
+            .find(|steal| !steal.is_retry())
+            .and_then(Steal::success)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use std::sync::Mutex;
+
+    use super::*;
+
+    #[test]
+    fn test_work_queue_yields_each_unit_once() {
+        let queues = WorkQueue::build(0..10_000, 4);
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let handles: Vec<_> = queues
+            .into_iter()
+            .map(|queue| {
+                let seen = Arc::clone(&seen);
+                std::thread::spawn(move || {
+                    let mut local = Vec::new();
+                    while let Some(unit) = queue.next() {
+                        local.push(unit);
+                    }
+                    seen.lock().unwrap().extend(local);
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let seen = seen.lock().unwrap();
+        assert_eq!(seen.len(), 10_000);
+        assert_eq!(seen.iter().copied().collect::<HashSet<_>>().len(), 10_000);
+    }
+}
@@ -0,0 +1,211 @@
+use std::cell::UnsafeCell;
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Per-thread state shared by the clones of a [`ParallelProcessor`](crate::ParallelProcessor)
+///
+/// Holds one slot per thread id. Processors share the storage through an `Arc` and access
+/// the slot of the thread id they received in
+/// [`set_tid`](crate::ParallelProcessor::set_tid), which avoids the lock contention of a
+/// shared `Arc<Mutex<_>>`. Since every thread id is unique within a parallel run, each slot
+/// is only ever accessed by one thread.
+///
+/// The results are collected with [`into_results`](Self::into_results) once all processors
+/// are dropped.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::sync::Arc;
+///
+/// use binseq::prelude::*;
+/// use binseq::ThreadLocalStorage;
+///
+/// #[derive(Clone)]
+/// struct LengthHistogram {
+///     tid: usize,
+///     storage: Arc<ThreadLocalStorage<Vec<usize>>>,
+/// }
+/// impl ParallelProcessor for LengthHistogram {
+///     fn process_record<R: BinseqRecord>(&mut self, record: R) -> binseq::Result<()> {
+///         let mut histogram = self.storage.slot(self.tid);
+///         let len = record.slen() as usize;
+///         if histogram.len() <= len {
+///             histogram.resize(len + 1, 0);
+///         }
+///         histogram[len] += 1;
+///         Ok(())
+///     }
+///
+///     fn set_tid(&mut self, tid: usize) {
+///         self.tid = tid;
+///     }
+/// }
+///
+/// let storage = Arc::new(ThreadLocalStorage::new(4));
+/// let processor = LengthHistogram { tid: 0, storage: Arc::clone(&storage) };
+/// binseq::open("./data/subset.vbq")?.process_parallel(processor, 4)?;
+///
+/// let histograms = Arc::try_unwrap(storage).ok().unwrap().into_results();
+/// assert_eq!(histograms.len(), 4);
+/// # Ok::<(), binseq::Error>(())
+/// ```
+pub struct ThreadLocalStorage<T> {
+    /// One slot per thread id
+    slots: Vec<Slot<T>>,
+}
+
+/// A value owned by a single thread at a time
+struct Slot<T> {
+    /// The value of the slot
+    value: UnsafeCell<T>,
+
+    /// Whether a [`SlotGuard`] to the value is alive
+    borrowed: AtomicBool,
+}
+
+// SAFETY: the value of a slot is only reachable through a `SlotGuard`, and `borrowed` ensures
+// at most one guard per slot exists at a time, so values are never accessed concurrently.
+// Values may be created and dropped on different threads, so they must be `Send`.
+unsafe impl<T: Send> Sync for ThreadLocalStorage<T> {}
+
+impl<T: Default> ThreadLocalStorage<T> {
+    /// Creates a storage with a default-initialized slot for each of `n_threads` threads
+    #[must_use]
+    pub fn new(n_threads: usize) -> Self {
+        Self {
+            slots: (0..n_threads)
+                .map(|_| Slot {
+                    value: UnsafeCell::new(T::default()),
+                    borrowed: AtomicBool::new(false),
+                })
+                .collect(),
+        }
+    }
+}
+impl<T> ThreadLocalStorage<T> {
+    /// Returns the number of slots
+    #[must_use]
+    pub fn n_threads(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Returns exclusive access to the slot of thread `tid`
+    ///
+    /// The slot is released when the returned guard is dropped.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `tid` is not less than [`n_threads`](Self::n_threads), or if the slot is
+    /// already borrowed (e.g. two processors were given the same thread id).
+    #[must_use]
+    pub fn slot(&self, tid: usize) -> SlotGuard<'_, T> {
+        let slot = &self.slots[tid];
+        assert!(
+            !slot.borrowed.swap(true, Ordering::Acquire),
+            "slot {tid} of the thread-local storage is already borrowed"
+        );
+        SlotGuard { slot }
+    }
+
+    /// Consumes the storage and returns the value of each slot in thread id order
+    #[must_use]
+    pub fn into_results(self) -> Vec<T> {
+        self.slots
+            .into_iter()
+            .map(|slot| slot.value.into_inner())
+            .collect()
+    }
+}
+
+/// Exclusive access to a slot of a [`ThreadLocalStorage`]
+///
+/// Created by [`ThreadLocalStorage::slot`].
+pub struct SlotGuard<'a, T> {
+    /// The borrowed slot
+    slot: &'a Slot<T>,
+}
+impl<T> Deref for SlotGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: the guard is the only one of its slot (see `ThreadLocalStorage::slot`)
+        unsafe { &*self.slot.value.get() }
+    }
+}
+impl<T> DerefMut for SlotGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        // SAFETY: the guard is the only one of its slot (see `ThreadLocalStorage::slot`)
+        unsafe { &mut *self.slot.value.get() }
+    }
+}
+impl<T> Drop for SlotGuard<'_, T> {
+    fn drop(&mut self) {
+        self.slot.borrowed.store(false, Ordering::Release);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{BinseqRecord, ParallelProcessor, ParallelReader, Result, bq};
+
+    #[derive(Clone)]
+    struct SlotProcessor {
+        tid: Option<usize>,
+        storage: Arc<ThreadLocalStorage<Vec<u64>>>,
+    }
+    impl ParallelProcessor for SlotProcessor {
+        fn process_record<R: BinseqRecord>(&mut self, record: R) -> Result<()> {
+            self.storage.slot(self.tid.unwrap()).push(record.index());
+            Ok(())
+        }
+
+        fn set_tid(&mut self, tid: usize) {
+            self.tid = Some(tid);
+        }
+    }
+
+    #[test]
+    fn test_thread_local_storage_slots() -> Result<()> {
+        let reader = bq::MmapReader::new("./data/subset.bq")?;
+        let num_records = reader.num_records();
+        let storage = Arc::new(ThreadLocalStorage::new(4));
+        let processor = SlotProcessor {
+            tid: None,
+            storage: Arc::clone(&storage),
+        };
+        reader.process_parallel(processor, 4)?;
+
+        // Each thread processes a contiguous share of the records
+        let slots = Arc::try_unwrap(storage).ok().unwrap().into_results();
+        let n_threads = 4.min(num_cpus::get());
+        let share = num_records.div_ceil(n_threads);
+        for (tid, slot) in slots.iter().enumerate() {
+            let start = (tid * share).min(num_records);
+            let end = ((tid + 1) * share).min(num_records);
+            assert_eq!(*slot, (start as u64..end as u64).collect::<Vec<_>>());
+        }
+        assert_eq!(slots.iter().map(Vec::len).sum::<usize>(), num_records);
+        Ok(())
+    }
+
+    #[test]
+    #[should_panic(expected = "already borrowed")]
+    fn test_slot_is_exclusive() {
+        let storage = ThreadLocalStorage::<u64>::new(2);
+        let _first = storage.slot(1);
+        let _second = storage.slot(1);
+    }
+
+    #[test]
+    fn test_slot_is_released() {
+        let storage = ThreadLocalStorage::<u64>::new(2);
+        *storage.slot(0) += 2;
+        *storage.slot(0) += 3;
+        *storage.slot(1) += 1;
+        assert_eq!(storage.into_results(), vec![5, 1]);
+    }
+}
@@ -0,0 +1,404 @@
+//! Synthetic datasets and round-trip helpers for testing code built on BINSEQ
+//!
+//! This module is not part of the stable API. It is used by the crate's integration tests
+//! and is exposed so downstream crates can generate reproducible fixtures without
+//! shipping binary files.
+//!
+//! ```
+//! use binseq::testing::{DatasetConfig, LengthDistribution, random_dataset, roundtrip_vbq};
+//!
+//! let config = DatasetConfig::new(LengthDistribution::Uniform { min: 10, max: 200 })
+//!     .paired(true)
+//!     .quality(true)
+//!     .headers(true);
+//! let records = random_dataset(100, &config);
+//! let decoded = roundtrip_vbq(&records, &config, true).unwrap();
+//! assert_eq!(records, decoded);
+//! ```
+
+use std::sync::{Arc, Mutex, PoisonError};
+
+use bitnuc::BitSize;
+use rand::{Rng, SeedableRng, rngs::SmallRng};
+
+use crate::{
+    BinseqReader, BinseqRecord, ParallelProcessor, ParallelReader, RNG_SEED, Result,
+    SequencingRecord, SequencingRecordBuilder, bq, error::WriteError, vbq,
+};
+
+/// Distribution of the sequence lengths of a synthetic dataset
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LengthDistribution {
+    /// Every sequence has the same length
+    Fixed(usize),
+    /// Lengths are drawn uniformly from `min..=max`
+    Uniform { min: usize, max: usize },
+}
+impl LengthDistribution {
+    fn sample(self, rng: &mut SmallRng) -> usize {
+        match self {
+            Self::Fixed(len) => len,
+            Self::Uniform { min, max } => rng.random_range(min..=max.max(min)),
+        }
+    }
+
+    /// Returns the length if all sequences have the same length
+    #[must_use]
+    pub fn fixed_len(self) -> Option<usize> {
+        match self {
+            Self::Fixed(len) => Some(len),
+            Self::Uniform { min, max } if min == max => Some(min),
+            Self::Uniform { .. } => None,
+        }
+    }
+}
+
+/// Shape of a synthetic dataset
+///
+/// Every optional field (mates, quality scores, headers, flags) is either present on all
+/// records or on none, matching what a single BINSEQ file can store.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DatasetConfig {
+    /// Lengths of the primary sequences
+    pub lengths: LengthDistribution,
+    /// Lengths of the secondary sequences, `None` for single-end data
+    pub x_lengths: Option<LengthDistribution>,
+    /// Generate quality scores
+    pub quality: bool,
+    /// Generate sequence headers
+    pub headers: bool,
+    /// Generate flags
+    pub flags: bool,
+    /// Fraction of bases replaced by `N`, only meaningful for 4-bit encodings
+    pub ambiguous: f64,
+    /// Seed of the random number generator
+    pub seed: u64,
+}
+impl DatasetConfig {
+    /// Single-end records without quality scores, headers or flags
+    #[must_use]
+    pub fn new(lengths: LengthDistribution) -> Self {
+        Self {
+            lengths,
+            x_lengths: None,
+            quality: false,
+            headers: false,
+            flags: false,
+            ambiguous: 0.0,
+            seed: RNG_SEED,
+        }
+    }
+
+    /// Generates mates with the same length distribution as the primary sequences
+    #[must_use]
+    pub fn paired(mut self, paired: bool) -> Self {
+        self.x_lengths = paired.then_some(self.lengths);
+        self
+    }
+
+    #[must_use]
+    pub fn x_lengths(mut self, x_lengths: LengthDistribution) -> Self {
+        self.x_lengths = Some(x_lengths);
+        self
+    }
+
+    #[must_use]
+    pub fn quality(mut self, quality: bool) -> Self {
+        self.quality = quality;
+        self
+    }
+
+    #[must_use]
+    pub fn headers(mut self, headers: bool) -> Self {
+        self.headers = headers;
+        self
+    }
+
+    #[must_use]
+    pub fn flags(mut self, flags: bool) -> Self {
+        self.flags = flags;
+        self
+    }
+
+    #[must_use]
+    pub fn ambiguous(mut self, ambiguous: f64) -> Self {
+        self.ambiguous = ambiguous;
+        self
+    }
+
+    #[must_use]
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    #[must_use]
+    pub fn is_paired(&self) -> bool {
+        self.x_lengths.is_some()
+    }
+
+    /// Returns a BQ header matching this configuration
+    ///
+    /// Fails if the sequence lengths are not fixed.
+    pub fn bq_header(&self, bitsize: BitSize) -> Result<bq::FileHeader> {
+        let fixed = |dist: LengthDistribution| {
+            dist.fixed_len().ok_or(WriteError::ConfigurationMismatch {
+                attribute: "fixed_length",
+                expected: true,
+                actual: false,
+            })
+        };
+        let slen = fixed(self.lengths)?;
+        let xlen = self.x_lengths.map(fixed).transpose()?.unwrap_or(0);
+        bq::FileHeaderBuilder::new()
+            .slen(slen as u32)
+            .xlen(xlen as u32)
+            .bitsize(bitsize)
+            .flags(self.flags)
+            .build()
+    }
+
+    /// Returns a VBQ header matching this configuration
+    #[must_use]
+    pub fn vbq_header(&self, bitsize: BitSize, compressed: bool) -> vbq::FileHeader {
+        vbq::FileHeaderBuilder::new()
+            .qual(self.quality)
+            .paired(self.is_paired())
+            .headers(self.headers)
+            .flags(self.flags)
+            .compressed(compressed)
+            .bitsize(bitsize)
+            .build()
+    }
+}
+
+/// An owned record of a synthetic dataset
+///
+/// Absent fields are empty, and `flag` is `None` if flags are not generated.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct SyntheticRecord {
+    pub flag: Option<u64>,
+    pub s_seq: Vec<u8>,
+    pub s_qual: Vec<u8>,
+    pub s_header: Vec<u8>,
+    pub x_seq: Vec<u8>,
+    pub x_qual: Vec<u8>,
+    pub x_header: Vec<u8>,
+}
+
+fn non_empty(field: &[u8]) -> Option<&[u8]> {
+    (!field.is_empty()).then_some(field)
+}
+
+impl SyntheticRecord {
+    /// Borrows the record for writing
+    pub fn as_record(&self) -> Result<SequencingRecord<'_>> {
+        SequencingRecordBuilder::default()
+            .s_seq(&self.s_seq)
+            .opt_s_qual(non_empty(&self.s_qual))
+            .opt_s_header(non_empty(&self.s_header))
+            .opt_x_seq(non_empty(&self.x_seq))
+            .opt_x_qual(non_empty(&self.x_qual))
+            .opt_x_header(non_empty(&self.x_header))
+            .opt_flag(self.flag)
+            .build()
+    }
+
+    /// Copies the fields of `record` that a file written from `config` stores
+    ///
+    /// Readers substitute defaults for fields missing from a file (e.g. the record index as
+    /// header), so only the fields present in `config` are read.
+    pub fn from_record<R: BinseqRecord>(record: &R, config: &DatasetConfig) -> Result<Self> {
+        let mut out = Self {
+            flag: if config.flags { record.flag() } else { None },
+            ..Self::default()
+        };
+        record.decode_s(&mut out.s_seq)?;
+        if config.quality {
+            out.s_qual.extend_from_slice(record.squal());
+        }
+        if config.headers {
+            out.s_header.extend_from_slice(record.sheader());
+        }
+        if config.is_paired() {
+            record.decode_x(&mut out.x_seq)?;
+            if config.quality {
+                out.x_qual.extend_from_slice(record.xqual());
+            }
+            if config.headers {
+                out.x_header.extend_from_slice(record.xheader());
+            }
+        }
+        Ok(out)
+    }
+}
+
+fn random_seq(rng: &mut SmallRng, len: usize, ambiguous: f64) -> Vec<u8> {
+    (0..len)
+        .map(|_| {
+            if ambiguous > 0.0 && rng.random_bool(ambiguous.min(1.0)) {
+                b'N'
+            } else {
+                b"ACGT"[rng.random_range(0..4)]
+            }
+        })
+        .collect()
+}
+
+fn random_qual(rng: &mut SmallRng, len: usize) -> Vec<u8> {
+    (0..len).map(|_| rng.random_range(b'!'..=b'J')).collect()
+}
+
+/// Generates `n` single-end records with sequence lengths drawn from `lengths`
+#[must_use]
+pub fn random_records(n: usize, lengths: LengthDistribution, seed: u64) -> Vec<SyntheticRecord> {
+    random_dataset(n, &DatasetConfig::new(lengths).seed(seed))
+}
+
+/// Generates `n` records shaped by `config`
+///
+/// The output only depends on `n` and `config`.
+#[must_use]
+pub fn random_dataset(n: usize, config: &DatasetConfig) -> Vec<SyntheticRecord> {
+    let mut rng = SmallRng::seed_from_u64(config.seed);
+    (0..n)
+        .map(|idx| {
+            let mut record = SyntheticRecord::default();
+            let slen = config.lengths.sample(&mut rng);
+            record.s_seq = random_seq(&mut rng, slen, config.ambiguous);
+            if config.quality {
+                record.s_qual = random_qual(&mut rng, slen);
+            }
+            if config.headers {
+                record.s_header = format!("synthetic.{idx} 1").into_bytes();
+            }
+            if let Some(x_lengths) = config.x_lengths {
+                let xlen = x_lengths.sample(&mut rng);
+                record.x_seq = random_seq(&mut rng, xlen, config.ambiguous);
+                if config.quality {
+                    record.x_qual = random_qual(&mut rng, xlen);
+                }
+                if config.headers {
+                    record.x_header = format!("synthetic.{idx} 2").into_bytes();
+                }
+            }
+            if config.flags {
+                record.flag = Some(rng.random());
+            }
+            record
+        })
+        .collect()
+}
+
+/// Writes `records` to an in-memory BQ file
+pub fn write_bq(records: &[SyntheticRecord], header: bq::FileHeader) -> Result<Vec<u8>> {
+    let mut buffer = Vec::new();
+    let mut writer = bq::WriterBuilder::default()
+        .header(header)
+        .build(&mut buffer)?;
+    for record in records {
+        writer.push(record.as_record()?)?;
+    }
+    writer.flush()?;
+    drop(writer);
+    Ok(buffer)
+}
+
+/// Writes `records` to an in-memory VBQ file
+pub fn write_vbq(records: &[SyntheticRecord], header: vbq::FileHeader) -> Result<Vec<u8>> {
+    let mut buffer = Vec::new();
+    let mut writer = vbq::WriterBuilder::default()
+        .header(header)
+        .build(&mut buffer)?;
+    for record in records {
+        writer.push(record.as_record()?)?;
+    }
+    writer.finish()?;
+    drop(writer);
+    Ok(buffer)
+}
+
+/// Reads all records of an in-memory BQ file sequentially
+pub fn read_bq(bytes: Vec<u8>, config: &DatasetConfig) -> Result<Vec<SyntheticRecord>> {
+    let reader = bq::MmapReader::from_bytes(bytes)?;
+    (0..reader.num_records())
+        .map(|idx| SyntheticRecord::from_record(&reader.get(idx)?, config))
+        .collect()
+}
+
+/// Reads all records of an in-memory VBQ file sequentially
+pub fn read_vbq(bytes: Vec<u8>, config: &DatasetConfig) -> Result<Vec<SyntheticRecord>> {
+    let mut reader = vbq::MmapReader::from_bytes(bytes)?;
+    let mut block = reader.new_block();
+    let mut records = Vec::new();
+    while reader.read_block_into(&mut block)? {
+        for record in block.iter() {
+            records.push(SyntheticRecord::from_record(&record, config)?);
+        }
+    }
+    Ok(records)
+}
+
+/// Collects records processed in parallel, keyed by record index
+#[derive(Clone)]
+struct Collector {
+    config: DatasetConfig,
+    local: Vec<(u64, SyntheticRecord)>,
+    global: Arc<Mutex<Vec<(u64, SyntheticRecord)>>>,
+}
+impl ParallelProcessor for Collector {
+    fn process_record<R: BinseqRecord>(&mut self, record: R) -> Result<()> {
+        let owned = SyntheticRecord::from_record(&record, &self.config)?;
+        self.local.push((record.index(), owned));
+        Ok(())
+    }
+
+    fn on_batch_complete(&mut self) -> Result<()> {
+        self.global
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .append(&mut self.local);
+        Ok(())
+    }
+}
+
+/// Reads all records of an in-memory BINSEQ file with `num_threads` parallel workers
+///
+/// Records are returned in file order.
+pub fn read_parallel(
+    bytes: Vec<u8>,
+    config: &DatasetConfig,
+    num_threads: usize,
+) -> Result<Vec<SyntheticRecord>> {
+    let global = Arc::new(Mutex::new(Vec::new()));
+    let collector = Collector {
+        config: *config,
+        local: Vec::new(),
+        global: global.clone(),
+    };
+    BinseqReader::from_bytes(bytes)?.process_parallel(collector, num_threads)?;
+    let mut records = std::mem::take(&mut *global.lock().unwrap_or_else(PoisonError::into_inner));
+    records.sort_unstable_by_key(|(idx, _)| *idx);
+    Ok(records.into_iter().map(|(_, record)| record).collect())
+}
+
+/// Writes `records` to BQ and reads them back
+pub fn roundtrip_bq(
+    records: &[SyntheticRecord],
+    config: &DatasetConfig,
+    bitsize: BitSize,
+) -> Result<Vec<SyntheticRecord>> {
+    read_bq(write_bq(records, config.bq_header(bitsize)?)?, config)
+}
+
+/// Writes `records` to a 2-bit VBQ file and reads them back
+pub fn roundtrip_vbq(
+    records: &[SyntheticRecord],
+    config: &DatasetConfig,
+    compressed: bool,
+) -> Result<Vec<SyntheticRecord>> {
+    read_vbq(
+        write_vbq(records, config.vbq_header(BitSize::Two, compressed))?,
+        config,
+    )
+}
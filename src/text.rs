@@ -0,0 +1,485 @@
+use std::io::{self, Read};
+
+use crate::{
+    BinseqReader, BinseqRecord, DEFAULT_QUALITY_SCORE, Error, RefRecordPair, Result, cbq, vbq,
+};
+
+/// Number of BQ records formatted at a time
+const RECORDS_PER_REFILL: usize = 1024;
+
+/// Text format produced by a [`TextAdapter`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TextFormat {
+    /// Four-line FASTQ records
+    ///
+    /// Records without quality scores are written with the default quality score.
+    Fastq,
+
+    /// Two-line FASTA records
+    Fasta,
+
+    /// One tab-separated line per record with the given fields
+    Tsv(Vec<TsvField>),
+}
+
+/// A column of [`TextFormat::Tsv`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TsvField {
+    /// Global index of the record
+    Index,
+
+    /// Sequence header
+    Header,
+
+    /// Nucleotide sequence
+    Sequence,
+
+    /// Quality scores (empty if the record has none)
+    Quality,
+
+    /// Record flag (empty if the file stores no flags)
+    Flag,
+}
+
+/// How a [`TextAdapter`] writes paired records
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PairedMode {
+    /// Write both mates of each pair, R1 followed by R2
+    #[default]
+    Interleaved,
+
+    /// Only write the first mate (the primary sequence)
+    PrimaryOnly,
+}
+
+/// Appends a record header, adding the mate suffix `/{mate}` to its name if it has none
+///
+/// The name is the header up to the first whitespace. Names already ending in `/1` or `/2`
+/// are kept as-is.
+fn push_header(text: &mut Vec<u8>, header: &[u8], mate: Option<u8>) {
+    let name_len = header
+        .iter()
+        .position(u8::is_ascii_whitespace)
+        .unwrap_or(header.len());
+    let (name, comment) = header.split_at(name_len);
+    text.extend_from_slice(name);
+    if let Some(mate) = mate
+        && !(name.ends_with(b"/1") || name.ends_with(b"/2"))
+    {
+        text.extend_from_slice(&[b'/', mate]);
+    }
+    text.extend_from_slice(comment);
+}
+
+/// A [`Read`] adapter serving the records of a BINSEQ file as text
+///
+/// Records are pulled from the reader on demand (one block of VBQ and CBQ files, or a fixed
+/// number of BQ records, at a time) and formatted into an internal buffer, which is served
+/// across calls to [`read`](Read::read). Memory use is bounded by a single block regardless of
+/// the file size, so the adapter can feed tools that only read FASTQ from stdin without
+/// writing a temporary file.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use std::process::{Command, Stdio};
+///
+/// use binseq::{TextAdapter, TextFormat};
+///
+/// let reader = binseq::open("./data/subset.vbq")?;
+/// let mut text = TextAdapter::new(reader, TextFormat::Fastq);
+///
+/// let mut child = Command::new("legacy-tool").stdin(Stdio::piped()).spawn()?;
+/// std::io::copy(&mut text, child.stdin.as_mut().unwrap())?;
+/// drop(child.stdin.take());
+/// child.wait()?;
+/// # Ok::<(), binseq::Error>(())
+/// ```
+pub struct TextAdapter {
+    /// Source of the records
+    reader: BinseqReader,
+
+    /// Formats records into the text buffer
+    formatter: Formatter,
+
+    /// Position of the next byte to serve from the text buffer
+    pos: usize,
+
+    /// Next record (BQ) or block (VBQ, CBQ) to format
+    next: usize,
+
+    /// Reusable block of a VBQ reader
+    vbq_block: Option<vbq::RecordBlock>,
+
+    /// Blocks of a CBQ reader
+    cbq_blocks: Vec<cbq::BlockRange>,
+}
+impl TextAdapter {
+    /// Creates an adapter serving the records of `reader` as `format`
+    ///
+    /// Paired records are interleaved, see [`paired_mode`](Self::paired_mode).
+    #[must_use]
+    pub fn new(reader: BinseqReader, format: TextFormat) -> Self {
+        let cbq_blocks = match &reader {
+            BinseqReader::Cbq(reader) => reader.index().iter_blocks().collect(),
+            _ => Vec::new(),
+        };
+        Self {
+            reader,
+            formatter: Formatter {
+                format,
+                mode: PairedMode::default(),
+                mate_suffixes: false,
+                text: Vec::new(),
+                seq: Vec::new(),
+                qual: Vec::new(),
+            },
+            pos: 0,
+            next: 0,
+            vbq_block: None,
+            cbq_blocks,
+        }
+    }
+
+    /// Sets how paired records are written
+    #[must_use]
+    pub fn paired_mode(mut self, mode: PairedMode) -> Self {
+        self.formatter.mode = mode;
+        self
+    }
+
+    /// Sets whether interleaved mates get `/1` and `/2` suffixes (default: `false`)
+    ///
+    /// The suffix is appended to the read name (the header up to the first whitespace)
+    /// unless the name already ends in `/1` or `/2`. Single-end records and
+    /// [`PairedMode::PrimaryOnly`] output are not changed.
+    #[must_use]
+    pub fn mate_suffixes(mut self, mate_suffixes: bool) -> Self {
+        self.formatter.mate_suffixes = mate_suffixes;
+        self
+    }
+
+    /// Formats the next records into the text buffer
+    ///
+    /// Returns `false` once all records were formatted.
+    fn refill(&mut self) -> Result<bool> {
+        let Self {
+            reader,
+            formatter,
+            next,
+            vbq_block,
+            cbq_blocks,
+            ..
+        } = self;
+        match reader {
+            BinseqReader::Bq(reader) => {
+                let end = (*next + RECORDS_PER_REFILL).min(reader.num_records());
+                if *next >= end {
+                    return Ok(false);
+                }
+                for idx in *next..end {
+                    formatter.push(reader.get(idx)?)?;
+                }
+                *next = end;
+            }
+            BinseqReader::PairedBq(reader) => {
+                let end = (*next + RECORDS_PER_REFILL).min(reader.num_records());
+                if *next >= end {
+                    return Ok(false);
+                }
+                for idx in *next..end {
+                    formatter.push(reader.get(idx)?)?;
+                }
+                *next = end;
+            }
+            BinseqReader::Vbq(reader) => {
+                let block = vbq_block.get_or_insert_with(|| reader.new_block());
+                if !reader.read_block_into(block)? {
+                    return Ok(false);
+                }
+                for record in block.iter() {
+                    formatter.push(record)?;
+                }
+            }
+            BinseqReader::Cbq(reader) => {
+                let Some(range) = cbq_blocks.get(*next).copied() else {
+                    return Ok(false);
+                };
+                for record in reader.iter_block_records(range)? {
+                    formatter.push(record)?;
+                }
+                *next += 1;
+            }
+        }
+        Ok(true)
+    }
+}
+impl Read for TextAdapter {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.pos == self.formatter.text.len() {
+            self.formatter.text.clear();
+            self.pos = 0;
+            match self.refill() {
+                Ok(true) => {}
+                Ok(false) => return Ok(0),
+                Err(Error::IoError(err)) => return Err(err),
+                Err(err) => return Err(io::Error::other(err)),
+            }
+        }
+        let n = buf.len().min(self.formatter.text.len() - self.pos);
+        buf[..n].copy_from_slice(&self.formatter.text[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+/// Formats records as text
+struct Formatter {
+    /// Output format
+    format: TextFormat,
+
+    /// Handling of paired records
+    mode: PairedMode,
+
+    /// Whether interleaved mates get `/1` and `/2` suffixes
+    mate_suffixes: bool,
+
+    /// Formatted text
+    text: Vec<u8>,
+
+    /// Reusable buffer for decoded sequences
+    seq: Vec<u8>,
+
+    /// Reusable buffer for default quality scores
+    qual: Vec<u8>,
+}
+impl Formatter {
+    /// Appends a record, splitting paired records into their mates
+    fn push<R: BinseqRecord>(&mut self, record: R) -> Result<()> {
+        if !record.is_paired() {
+            return self.push_single(&record, None);
+        }
+        let pair = RefRecordPair::new(record);
+        if self.mode == PairedMode::Interleaved {
+            let suffixes = self.mate_suffixes;
+            self.push_single(&pair.r1(), suffixes.then_some(b'1'))?;
+            self.push_single(&pair.r2(), suffixes.then_some(b'2'))?;
+        } else {
+            self.push_single(&pair.r1(), None)?;
+        }
+        Ok(())
+    }
+
+    /// Appends the primary sequence of a record, with the mate suffix `mate` if given
+    fn push_single<R: BinseqRecord>(&mut self, record: &R, mate: Option<u8>) -> Result<()> {
+        self.seq.clear();
+        record.decode_s(&mut self.seq)?;
+        let header = record.sheader();
+        match &self.format {
+            TextFormat::Fastq => {
+                let qual = if record.squal().len() == self.seq.len() {
+                    record.squal()
+                } else {
+                    self.qual.resize(self.seq.len(), DEFAULT_QUALITY_SCORE);
+                    &self.qual[..self.seq.len()]
+                };
+                self.text.push(b'@');
+                push_header(&mut self.text, header, mate);
+                self.text.push(b'\n');
+                self.text.extend_from_slice(&self.seq);
+                self.text.extend_from_slice(b"\n+\n");
+                self.text.extend_from_slice(qual);
+            }
+            TextFormat::Fasta => {
+                self.text.push(b'>');
+                push_header(&mut self.text, header, mate);
+                self.text.push(b'\n');
+                self.text.extend_from_slice(&self.seq);
+            }
+            TextFormat::Tsv(fields) => {
+                let mut itoa = itoa::Buffer::new();
+                for (i, field) in fields.iter().enumerate() {
+                    if i > 0 {
+                        self.text.push(b'\t');
+                    }
+                    match field {
+                        TsvField::Index => self
+                            .text
+                            .extend_from_slice(itoa.format(record.index()).as_bytes()),
+                        TsvField::Header => push_header(&mut self.text, header, mate),
+                        TsvField::Sequence => self.text.extend_from_slice(&self.seq),
+                        TsvField::Quality => self.text.extend_from_slice(record.squal()),
+                        TsvField::Flag => {
+                            if let Some(flag) = record.flag() {
+                                self.text.extend_from_slice(itoa.format(flag).as_bytes());
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        self.text.push(b'\n');
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{SequencingRecordBuilder, bq};
+
+    /// Reads all text from `adapter` through a 7 byte buffer
+    fn read_small(mut adapter: TextAdapter) -> String {
+        let mut text = Vec::new();
+        let mut buf = [0; 7];
+        loop {
+            let n = adapter.read(&mut buf).unwrap();
+            if n == 0 {
+                break;
+            }
+            assert!(n <= buf.len());
+            text.extend_from_slice(&buf[..n]);
+        }
+        String::from_utf8(text).unwrap()
+    }
+
+    /// Writes a small paired VBQ file with quality scores and headers to memory
+    fn paired_vbq() -> Result<BinseqReader> {
+        paired_vbq_with_headers([("read0/1", "read0/2"), ("read1/1", "read1/2")])
+    }
+
+    /// Writes a small paired VBQ file with the given mate headers to memory
+    fn paired_vbq_with_headers(headers: [(&str, &str); 2]) -> Result<BinseqReader> {
+        let mut bytes = Vec::new();
+        let header = vbq::FileHeaderBuilder::new()
+            .paired(true)
+            .qual(true)
+            .headers(true)
+            .flags(true)
+            .build();
+        let mut writer = vbq::WriterBuilder::default()
+            .header(header)
+            .build(&mut bytes)?;
+        for (idx, (s, x)) in [(b"ACGTACGTAC", b"TTGGCC"), (b"GGGGAAAATT", b"ACACAC")]
+            .iter()
+            .enumerate()
+        {
+            let (sheader, xheader) = headers[idx];
+            let record = SequencingRecordBuilder::default()
+                .s_seq(*s)
+                .s_qual(b"IIIIIIIII#")
+                .s_header(sheader.as_bytes())
+                .x_seq(*x)
+                .x_qual(b"#####I")
+                .x_header(xheader.as_bytes())
+                .flag(idx as u64 + 4)
+                .build()?;
+            writer.push(record)?;
+        }
+        writer.finish()?;
+        drop(writer);
+        BinseqReader::from_bytes(bytes)
+    }
+
+    #[test]
+    fn test_fastq_interleaved() -> Result<()> {
+        let text = read_small(TextAdapter::new(paired_vbq()?, TextFormat::Fastq));
+        assert_eq!(
+            text,
+            "@read0/1\nACGTACGTAC\n+\nIIIIIIIII#\n\
+             @read0/2\nTTGGCC\n+\n#####I\n\
+             @read1/1\nGGGGAAAATT\n+\nIIIIIIIII#\n\
+             @read1/2\nACACAC\n+\n#####I\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_fastq_mate_suffixes() -> Result<()> {
+        let reader = paired_vbq_with_headers([("read0/1", "read0/2"), ("read1 lane=1", "read1")])?;
+        let text = read_small(TextAdapter::new(reader, TextFormat::Fastq).mate_suffixes(true));
+        assert_eq!(
+            text,
+            "@read0/1\nACGTACGTAC\n+\nIIIIIIIII#\n\
+             @read0/2\nTTGGCC\n+\n#####I\n\
+             @read1/1 lane=1\nGGGGAAAATT\n+\nIIIIIIIII#\n\
+             @read1/2\nACACAC\n+\n#####I\n"
+        );
+
+        // suffixes only apply to interleaved mates
+        let reader = paired_vbq_with_headers([("a", "a"), ("b", "b")])?;
+        let adapter = TextAdapter::new(reader, TextFormat::Fasta)
+            .paired_mode(PairedMode::PrimaryOnly)
+            .mate_suffixes(true);
+        assert_eq!(read_small(adapter), ">a\nACGTACGTAC\n>b\nGGGGAAAATT\n");
+        Ok(())
+    }
+
+    #[test]
+    fn test_fasta_and_tsv_primary_only() -> Result<()> {
+        let adapter =
+            TextAdapter::new(paired_vbq()?, TextFormat::Fasta).paired_mode(PairedMode::PrimaryOnly);
+        assert_eq!(
+            read_small(adapter),
+            ">read0/1\nACGTACGTAC\n>read1/1\nGGGGAAAATT\n"
+        );
+
+        let fields = vec![
+            TsvField::Index,
+            TsvField::Flag,
+            TsvField::Header,
+            TsvField::Sequence,
+            TsvField::Quality,
+        ];
+        let adapter = TextAdapter::new(paired_vbq()?, TextFormat::Tsv(fields))
+            .paired_mode(PairedMode::PrimaryOnly);
+        assert_eq!(
+            read_small(adapter),
+            "0\t4\tread0/1\tACGTACGTAC\tIIIIIIIII#\n1\t5\tread1/1\tGGGGAAAATT\tIIIIIIIII#\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_bq_fastq_uses_default_quality() -> Result<()> {
+        let header = bq::FileHeaderBuilder::new().slen(8).build()?;
+        let mut writer = bq::WriterBuilder::default()
+            .header(header)
+            .build(Vec::new())?;
+        for _ in 0..3 {
+            let record = SequencingRecordBuilder::default()
+                .s_seq(b"ACGTTGCA")
+                .build()?;
+            writer.push(record)?;
+        }
+        let reader = BinseqReader::from_bytes(writer.into_inner())?;
+
+        let text = read_small(TextAdapter::new(reader, TextFormat::Fastq));
+        let qual = String::from_utf8(vec![DEFAULT_QUALITY_SCORE; 8]).unwrap();
+        let expected: String = (0..3)
+            .map(|idx| format!("@{idx}\nACGTTGCA\n+\n{qual}\n"))
+            .collect();
+        assert_eq!(text, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_all_formats_match_record_count() -> Result<()> {
+        for path in ["./data/subset.bq", "./data/subset.vbq", "./data/subset.cbq"] {
+            let reader = BinseqReader::new(path)?;
+            let mates = if reader.is_paired() { 2 } else { 1 };
+            let num_records = reader.num_records()?;
+
+            let text = read_small(TextAdapter::new(reader, TextFormat::Fastq));
+            assert_eq!(text.lines().count(), 4 * mates * num_records, "{path}");
+            assert!(text.lines().step_by(4).all(|line| line.starts_with('@')));
+            assert!(
+                text.lines()
+                    .skip(1)
+                    .step_by(4)
+                    .all(|line| line.bytes().all(|b| b"ACGTN".contains(&b)))
+            );
+        }
+        Ok(())
+    }
+}
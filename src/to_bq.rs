@@ -0,0 +1,247 @@
+//! Streaming conversion of fixed-length VBQ files to BQ
+//!
+//! [`vbq_to_bq`] copies the encoded sequence words of every VBQ record into a BQ record, so
+//! nothing is decoded and only one block is held in memory at a time. The BQ sequence
+//! lengths are taken from the first record. Quality scores, headers and soft-masks have no
+//! place in BQ and are dropped.
+//!
+//! # Examples
+//!
+//! ```rust,no_run
+//! use std::fs::File;
+//!
+//! use binseq::{VbqToBqOptions, vbq_to_bq};
+//!
+//! let output = File::create("reads.bq")?;
+//! let stats = vbq_to_bq("reads.vbq", output, VbqToBqOptions::default().skip_mismatched(true))?;
+//! eprintln!("converted {} records, skipped {}", stats.written, stats.skipped);
+//! # Ok::<(), binseq::Error>(())
+//! ```
+
+use std::io::Write;
+use std::path::Path;
+
+use crate::error::WriteError;
+use crate::{BinseqRecord, Result, bq, vbq};
+
+/// Configuration of [`vbq_to_bq`]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct VbqToBqOptions {
+    /// Skip records whose lengths differ from the first record instead of failing
+    skip_mismatched: bool,
+}
+impl VbqToBqOptions {
+    /// Skips and counts records whose sequence lengths differ from the first record
+    ///
+    /// By default such a record fails the conversion with
+    /// [`WriteError::RecordLengthMismatch`], naming the index of the record.
+    #[must_use]
+    pub fn skip_mismatched(mut self, skip_mismatched: bool) -> Self {
+        self.skip_mismatched = skip_mismatched;
+        self
+    }
+}
+
+/// Counts of records seen by [`vbq_to_bq`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct VbqToBqStats {
+    /// All records in the input
+    pub records: u64,
+
+    /// Records written to the output
+    pub written: u64,
+
+    /// Records skipped for their sequence lengths
+    pub skipped: u64,
+
+    /// Whether the input had quality scores, which were dropped
+    pub dropped_qualities: bool,
+
+    /// Whether the input had record headers, which were dropped
+    pub dropped_headers: bool,
+}
+
+/// Converts a VBQ file with fixed-length records into a BQ file written to `output`
+///
+/// The BQ header takes its sequence lengths from the first record and its bitsize and flag
+/// setting from the VBQ header, so flags are carried over whenever the input has them.
+/// Encoded sequence words are copied as-is. Records whose lengths differ from the first
+/// record fail the conversion unless [`VbqToBqOptions::skip_mismatched`] is set.
+///
+/// The file is streamed block by block, so memory use is bounded by the block size of the
+/// input. `output` is flushed when all records are written.
+///
+/// # Errors
+///
+/// * `WriteError::LengthsNotInferred` - If the input has no records
+/// * `WriteError::RecordLengthMismatch` - If a record has other sequence lengths than the
+///   first and mismatched records are not skipped
+pub fn vbq_to_bq<P: AsRef<Path>, W: Write>(
+    input: P,
+    output: W,
+    opts: VbqToBqOptions,
+) -> Result<VbqToBqStats> {
+    convert_reader(vbq::MmapReader::new(input)?, output, opts)
+}
+
+/// Converts the records of an open VBQ reader, see [`vbq_to_bq`]
+fn convert_reader<W: Write>(
+    mut reader: vbq::MmapReader,
+    output: W,
+    opts: VbqToBqOptions,
+) -> Result<VbqToBqStats> {
+    reader.set_decode_block(false);
+    let header = reader.header();
+    let mut stats = VbqToBqStats {
+        dropped_qualities: header.qual,
+        dropped_headers: header.headers,
+        ..VbqToBqStats::default()
+    };
+
+    let mut block = reader.new_block();
+    if !reader.read_block_into(&mut block)? {
+        return Err(WriteError::LengthsNotInferred("the input has no records").into());
+    }
+    let Some(first) = block.iter().next() else {
+        return Err(WriteError::LengthsNotInferred("the input has no records").into());
+    };
+    let (slen, xlen) = (first.slen(), first.xlen());
+    let to_u32 = |len: u64| {
+        u32::try_from(len).map_err(|_| WriteError::SequenceTooLong {
+            expected: u32::MAX as usize,
+            got: len as usize,
+        })
+    };
+    let bq_header = bq::FileHeaderBuilder::new()
+        .slen(to_u32(slen)?)
+        .xlen(to_u32(xlen)?)
+        .bitsize(header.bits)
+        .flags(header.flags)
+        .build()?;
+    let mut writer = bq::WriterBuilder::default()
+        .header(bq_header)
+        .build(output)?;
+
+    loop {
+        for record in block.iter() {
+            stats.records += 1;
+            if record.slen() != slen || record.xlen() != xlen {
+                if opts.skip_mismatched {
+                    stats.skipped += 1;
+                    continue;
+                }
+                return Err(WriteError::RecordLengthMismatch {
+                    index: record.index(),
+                    expected: (slen, xlen),
+                    got: (record.slen(), record.xlen()),
+                }
+                .into());
+            }
+            if xlen > 0 {
+                writer.write_encoded_direct_paired(record.flag(), record.sbuf(), record.xbuf())?;
+            } else {
+                writer.write_encoded_direct(record.flag(), record.sbuf())?;
+            }
+            stats.written += 1;
+        }
+        if !reader.read_block_into(&mut block)? {
+            break;
+        }
+    }
+    writer.flush()?;
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use bitnuc::BitSize;
+
+    use super::*;
+    use crate::error::Error;
+    use crate::testing::{self, DatasetConfig, LengthDistribution, SyntheticRecord};
+
+    /// Fixed-length records with qualities, headers and flags
+    fn config(xlen: Option<usize>) -> DatasetConfig {
+        let config = DatasetConfig::new(LengthDistribution::Fixed(50))
+            .quality(true)
+            .headers(true)
+            .flags(true);
+        match xlen {
+            Some(xlen) => config.x_lengths(LengthDistribution::Fixed(xlen)),
+            None => config,
+        }
+    }
+
+    /// Converts `records` written to an in-memory VBQ file
+    fn convert(
+        records: &[SyntheticRecord],
+        config: &DatasetConfig,
+        opts: VbqToBqOptions,
+    ) -> Result<(VbqToBqStats, Vec<u8>)> {
+        let header = config.vbq_header(BitSize::Two, false).with_block(1024);
+        let reader = vbq::MmapReader::from_bytes(testing::write_vbq(records, header)?)?;
+        let mut output = Vec::new();
+        let stats = convert_reader(reader, &mut output, opts)?;
+        Ok((stats, output))
+    }
+
+    #[test]
+    fn test_vbq_to_bq_round_trip() -> Result<()> {
+        for xlen in [None, Some(30)] {
+            let config = config(xlen);
+            let records = testing::random_dataset(300, &config);
+            let (stats, output) = convert(&records, &config, VbqToBqOptions::default())?;
+            assert_eq!(stats.records, 300);
+            assert_eq!(stats.written, 300);
+            assert!(stats.dropped_qualities && stats.dropped_headers);
+
+            let header = bq::MmapReader::from_bytes(output.clone())?.header();
+            assert_eq!(header.slen, 50);
+            assert_eq!(header.xlen, xlen.map_or(0, |xlen| xlen as u32));
+
+            // only the sequences and flags are carried over
+            let expected: Vec<_> = records
+                .into_iter()
+                .map(|record| SyntheticRecord {
+                    flag: record.flag,
+                    s_seq: record.s_seq,
+                    x_seq: record.x_seq,
+                    ..SyntheticRecord::default()
+                })
+                .collect();
+            let bq_config = config.quality(false).headers(false);
+            assert_eq!(testing::read_bq(output, &bq_config)?, expected);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_vbq_to_bq_length_mismatch() -> Result<()> {
+        let config = config(None);
+        let mut records = testing::random_dataset(10, &config);
+        for record in records.iter_mut().skip(2).step_by(3) {
+            record.s_seq.truncate(35);
+            record.s_qual.truncate(35);
+        }
+
+        let err = convert(&records, &config, VbqToBqOptions::default());
+        assert!(matches!(
+            err,
+            Err(Error::WriteError(WriteError::RecordLengthMismatch {
+                index: 2,
+                ..
+            }))
+        ));
+
+        let opts = VbqToBqOptions::default().skip_mismatched(true);
+        let (stats, output) = convert(&records, &config, opts)?;
+        assert_eq!((stats.records, stats.written, stats.skipped), (10, 7, 3));
+        assert_eq!(bq::MmapReader::from_bytes(output)?.num_records(), 7);
+
+        assert!(matches!(
+            convert(&[], &config, VbqToBqOptions::default()),
+            Err(Error::WriteError(WriteError::LengthsNotInferred(_)))
+        ));
+        Ok(())
+    }
+}
@@ -0,0 +1,422 @@
+//! Converting encoded sequences between the 2-bit and 4-bit encodings
+//!
+//! Sequences are transcoded word by word without decoding them to ASCII:
+//!
+//! - **2-bit to 4-bit** is a pure expansion, every 2-bit code `c` becomes the nibble `1 << c`.
+//! - **4-bit to 2-bit** repacks pairs of nibbles through a lookup table. Only the one-hot
+//!   nibbles of `A`, `C`, `G` and `T` have a 2-bit code, so sequences with ambiguous bases
+//!   (e.g. `N`) cannot be transcoded and are handled by [`OnIncompatible`].
+//!
+//! See [`vbq::convert_bitsize`](crate::vbq::convert_bitsize) and
+//! [`bq::convert_bitsize`](crate::bq::convert_bitsize) to convert whole files.
+
+use bitnuc::BitSize;
+use rand::SeedableRng;
+use rand::rngs::SmallRng;
+
+use crate::BinseqRecord;
+use crate::error::{Result, WriteError};
+use crate::policy::{Policy, RNG_SEED};
+
+/// Marks a byte of a 4-bit sequence holding a nibble without 2-bit code
+const INVALID: u8 = 0xFF;
+
+/// Two nibbles of a 4-bit sequence (one byte) mapped to two 2-bit codes
+const PACK_TABLE: [u8; 256] = build_pack_table();
+
+/// Four 2-bit codes (one byte) mapped to four nibbles
+const EXPAND_TABLE: [u16; 256] = build_expand_table();
+
+/// Nibbles of `A` in all positions of a word, used as padding before repacking
+const PADDING_A: u64 = 0x1111_1111_1111_1111;
+
+const fn nibble_to_2bit(nibble: u8) -> u8 {
+    match nibble {
+        0b0001 => 0,
+        0b0010 => 1,
+        0b0100 => 2,
+        0b1000 => 3,
+        _ => INVALID,
+    }
+}
+
+const fn build_pack_table() -> [u8; 256] {
+    let mut table = [INVALID; 256];
+    let mut byte = 0;
+    while byte < 256 {
+        let lo = nibble_to_2bit((byte & 0xF) as u8);
+        let hi = nibble_to_2bit((byte >> 4) as u8);
+        if lo != INVALID && hi != INVALID {
+            table[byte] = lo | (hi << 2);
+        }
+        byte += 1;
+    }
+    table
+}
+
+const fn build_expand_table() -> [u16; 256] {
+    let mut table = [0; 256];
+    let mut byte = 0;
+    while byte < 256 {
+        let mut nibbles = 0u16;
+        let mut pos = 0;
+        while pos < 4 {
+            let code = (byte >> (2 * pos)) & 0b11;
+            nibbles |= (1 << code) << (4 * pos);
+            pos += 1;
+        }
+        table[byte] = nibbles;
+        byte += 1;
+    }
+    table
+}
+
+/// Repacks `len` nucleotides of a 4-bit sequence into 2-bit words
+///
+/// Returns `false` if a nucleotide is not one of `A`, `C`, `G` or `T`.
+fn pack_4bit_to_2bit(src: &[u64], len: usize, dst: &mut Vec<u64>) -> bool {
+    dst.clear();
+    dst.resize(len.div_ceil(32), 0);
+    for (idx, &word) in src.iter().enumerate().take(len.div_ceil(16)) {
+        // padding nibbles are replaced by A, which is encoded as 0 in 2-bit
+        let remaining = len - 16 * idx;
+        let word = if remaining < 16 {
+            let mask = (1u64 << (4 * remaining)) - 1;
+            (word & mask) | (PADDING_A & !mask)
+        } else {
+            word
+        };
+
+        let mut packed = 0u64;
+        for (pos, byte) in word.to_le_bytes().into_iter().enumerate() {
+            let codes = PACK_TABLE[byte as usize];
+            if codes == INVALID {
+                return false;
+            }
+            packed |= u64::from(codes) << (4 * pos);
+        }
+        dst[idx / 2] |= packed << (32 * (idx % 2));
+    }
+    true
+}
+
+/// Expands `len` nucleotides of a 2-bit sequence into 4-bit words
+fn expand_2bit_to_4bit(src: &[u64], len: usize, dst: &mut Vec<u64>) {
+    dst.clear();
+    dst.reserve(len.div_ceil(16));
+    for idx in 0..len.div_ceil(16) {
+        let half = (src[idx / 2] >> (32 * (idx % 2))) as u32;
+        let mut expanded = 0u64;
+        for (pos, byte) in half.to_le_bytes().into_iter().enumerate() {
+            expanded |= u64::from(EXPAND_TABLE[byte as usize]) << (16 * pos);
+        }
+
+        // padding 2-bit codes would expand to A, so they are cleared
+        let remaining = len - 16 * idx;
+        if remaining < 16 {
+            expanded &= (1u64 << (4 * remaining)) - 1;
+        }
+        dst.push(expanded);
+    }
+}
+
+/// Transcodes `len` nucleotides encoded with `from` into words encoded with `to`
+///
+/// `dst` is cleared first and receives exactly the number of words required by `to`.
+/// Sequences with equal bitsizes are copied.
+///
+/// Returns `false` if the sequence has nucleotides that cannot be encoded with `to`, in which
+/// case the contents of `dst` are unspecified. This only happens for ambiguous bases when
+/// transcoding from 4-bit to 2-bit.
+///
+/// # Panics
+///
+/// Panics if `src` has fewer words than required for `len` nucleotides with `from`.
+///
+/// # Examples
+///
+/// ```rust
+/// use binseq::BitSize;
+/// use binseq::utils::transcode_words;
+///
+/// let mut four = Vec::new();
+/// BitSize::Four.encode(b"ACGTN", &mut four).unwrap();
+///
+/// let mut two = Vec::new();
+/// assert!(!transcode_words(&four, 5, BitSize::Four, BitSize::Two, &mut two));
+/// assert!(transcode_words(&four, 4, BitSize::Four, BitSize::Two, &mut two));
+/// ```
+pub fn transcode_words(
+    src: &[u64],
+    len: usize,
+    from: BitSize,
+    to: BitSize,
+    dst: &mut Vec<u64>,
+) -> bool {
+    match (from, to) {
+        (BitSize::Four, BitSize::Two) => pack_4bit_to_2bit(src, len, dst),
+        (BitSize::Two, BitSize::Four) => {
+            expand_2bit_to_4bit(src, len, dst);
+            true
+        }
+        (BitSize::Two, BitSize::Two) => {
+            dst.clear();
+            dst.extend_from_slice(&src[..len.div_ceil(32)]);
+            true
+        }
+        (BitSize::Four, BitSize::Four) => {
+            dst.clear();
+            dst.extend_from_slice(&src[..len.div_ceil(16)]);
+            true
+        }
+    }
+}
+
+/// Handling of sequences that cannot be stored with the target bitsize of a conversion
+///
+/// Only sequences with ambiguous bases (e.g. `N`) converted from 4-bit to 2-bit are affected.
+#[derive(Debug, Clone, Copy, Default)]
+pub enum OnIncompatible {
+    /// Fail the conversion with `WriteError::InvalidNucleotideSequence` (default)
+    #[default]
+    Error,
+
+    /// Decode the sequence and encode it again with the given [`Policy`]
+    ///
+    /// Records whose sequences are skipped by the policy are not written.
+    Policy(Policy),
+}
+
+/// Statistics of a bitsize conversion
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ConvertStats {
+    /// Number of records read from the input
+    pub records: usize,
+
+    /// Number of records written to the output
+    pub written: usize,
+
+    /// Number of written records that were corrected by [`OnIncompatible::Policy`]
+    pub corrected: usize,
+
+    /// Number of records skipped by [`OnIncompatible::Policy`]
+    pub skipped: usize,
+}
+
+/// Outcome of transcoding a single record
+pub(crate) enum Transcoded {
+    /// The record was transcoded as-is
+    Converted,
+    /// At least one sequence was corrected by the policy
+    Corrected,
+    /// The record was skipped by the policy
+    Skipped,
+}
+
+/// Transcodes the sequences of records into reusable buffers
+pub(crate) struct Transcoder {
+    from: BitSize,
+    to: BitSize,
+    on_incompatible: OnIncompatible,
+    rng: SmallRng,
+    dbuf: Vec<u8>,
+    ibuf: Vec<u8>,
+
+    /// Transcoded words of the primary sequence
+    pub sbuf: Vec<u64>,
+
+    /// Transcoded words of the extended sequence (empty if unpaired)
+    pub xbuf: Vec<u64>,
+}
+impl Transcoder {
+    pub fn new(from: BitSize, to: BitSize, on_incompatible: OnIncompatible) -> Self {
+        Self {
+            from,
+            to,
+            on_incompatible,
+            rng: SmallRng::seed_from_u64(RNG_SEED),
+            dbuf: Vec::new(),
+            ibuf: Vec::new(),
+            sbuf: Vec::new(),
+            xbuf: Vec::new(),
+        }
+    }
+
+    /// Transcodes the sequences of `record` into [`sbuf`](Self::sbuf) and [`xbuf`](Self::xbuf)
+    pub fn transcode<R: BinseqRecord>(&mut self, record: &R) -> Result<Transcoded> {
+        let Some(s_corrected) = self.transcode_sequence(record, true)? else {
+            return Ok(Transcoded::Skipped);
+        };
+        let x_corrected = if record.is_paired() {
+            let Some(corrected) = self.transcode_sequence(record, false)? else {
+                return Ok(Transcoded::Skipped);
+            };
+            corrected
+        } else {
+            self.xbuf.clear();
+            false
+        };
+        if s_corrected || x_corrected {
+            Ok(Transcoded::Corrected)
+        } else {
+            Ok(Transcoded::Converted)
+        }
+    }
+
+    /// Transcodes one sequence, returning whether it was corrected or `None` if it is skipped
+    fn transcode_sequence<R: BinseqRecord>(
+        &mut self,
+        record: &R,
+        primary: bool,
+    ) -> Result<Option<bool>> {
+        let (src, len, buf) = if primary {
+            (record.sbuf(), record.slen(), &mut self.sbuf)
+        } else {
+            (record.xbuf(), record.xlen(), &mut self.xbuf)
+        };
+        if transcode_words(src, len as usize, self.from, self.to, buf) {
+            return Ok(Some(false));
+        }
+
+        match self.on_incompatible {
+            OnIncompatible::Error => Err(WriteError::InvalidNucleotideSequence(format!(
+                "record {} has bases that cannot be encoded with {:?}",
+                record.index(),
+                self.to
+            ))
+            .into()),
+            OnIncompatible::Policy(policy) => {
+                self.dbuf.clear();
+                if primary {
+                    record.decode_s(&mut self.dbuf)?;
+                } else {
+                    record.decode_x(&mut self.dbuf)?;
+                }
+                buf.clear();
+                let encoded =
+                    policy.encode(self.to, &self.dbuf, &mut self.ibuf, buf, &mut self.rng)?;
+                Ok(encoded.then_some(true))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(bitsize: BitSize, seq: &[u8]) -> Vec<u64> {
+        let mut buf = Vec::new();
+        bitsize.encode(seq, &mut buf).unwrap();
+        buf
+    }
+
+    #[test]
+    fn test_pack_table_all_nibble_pairs() {
+        for byte in 0..=255u8 {
+            let (lo, hi) = (byte & 0xF, byte >> 4);
+            let expected = match (nibble_to_2bit(lo), nibble_to_2bit(hi)) {
+                (INVALID, _) | (_, INVALID) => INVALID,
+                (lo, hi) => lo | (hi << 2),
+            };
+            assert_eq!(PACK_TABLE[byte as usize], expected, "byte {byte:#010b}");
+
+            // every nibble pair transcoded as a two base sequence
+            let mut dst = Vec::new();
+            let valid =
+                transcode_words(&[u64::from(byte)], 2, BitSize::Four, BitSize::Two, &mut dst);
+            assert_eq!(valid, expected != INVALID, "byte {byte:#010b}");
+            if valid {
+                assert_eq!(dst, vec![u64::from(expected)]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_single_nibbles() {
+        for nibble in 0..16u64 {
+            let mut dst = Vec::new();
+            let valid = transcode_words(&[nibble], 1, BitSize::Four, BitSize::Two, &mut dst);
+            assert_eq!(valid, nibble.count_ones() == 1, "nibble {nibble:#06b}");
+            if valid {
+                assert_eq!(dst, vec![u64::from(nibble.trailing_zeros())]);
+
+                // and back again
+                let mut back = Vec::new();
+                assert!(transcode_words(
+                    &dst,
+                    1,
+                    BitSize::Two,
+                    BitSize::Four,
+                    &mut back
+                ));
+                assert_eq!(back, vec![nibble]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_expand_table() {
+        for byte in 0..=255usize {
+            let codes: Vec<u8> = (0..4)
+                .map(|pos| b"ACGT"[(byte >> (2 * pos)) & 0b11])
+                .collect();
+            let expected = encode(BitSize::Four, &codes);
+            assert_eq!(u64::from(EXPAND_TABLE[byte]), expected[0], "byte {byte}");
+        }
+    }
+
+    #[test]
+    fn test_transcode_matches_encoding() {
+        let mut dst = Vec::new();
+        for len in 0..100 {
+            let seq: Vec<u8> = (0..len)
+                .map(|idx| b"ACGT"[(idx * 7 + idx / 3) % 4])
+                .collect();
+            let two = encode(BitSize::Two, &seq);
+            let four = encode(BitSize::Four, &seq);
+
+            assert!(transcode_words(
+                &four,
+                len,
+                BitSize::Four,
+                BitSize::Two,
+                &mut dst
+            ));
+            assert_eq!(dst, two, "4 -> 2 with length {len}");
+            assert!(transcode_words(
+                &two,
+                len,
+                BitSize::Two,
+                BitSize::Four,
+                &mut dst
+            ));
+            assert_eq!(dst, four, "2 -> 4 with length {len}");
+            assert!(transcode_words(
+                &two,
+                len,
+                BitSize::Two,
+                BitSize::Two,
+                &mut dst
+            ));
+            assert_eq!(dst, two);
+        }
+    }
+
+    #[test]
+    fn test_ambiguous_bases_are_rejected() {
+        let mut dst = Vec::new();
+        for pos in [0, 15, 16, 40] {
+            let mut seq = vec![b'A'; 41];
+            seq[pos] = b'N';
+            let four = encode(BitSize::Four, &seq);
+            assert!(!transcode_words(
+                &four,
+                41,
+                BitSize::Four,
+                BitSize::Two,
+                &mut dst
+            ));
+        }
+    }
+}
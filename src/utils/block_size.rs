@@ -0,0 +1,149 @@
+//! Choosing a VBQ block size from a sample of sequences
+//!
+//! The block is the unit of parallelism and of compression in VBQ files, and the block size
+//! trades the two off against each other:
+//!
+//! - **Parallelism**: threads process whole blocks, so a file needs many more blocks than
+//!   threads to balance work. Large blocks leave threads idle at the end of a scan and make
+//!   random access decompress more data per record.
+//! - **Overhead**: every block costs a 32-byte block header and a 32-byte index entry, and
+//!   per-block setup (allocation, decompression context) is paid once per block. Small
+//!   blocks spend a larger share of time and space on this overhead.
+//! - **Compression**: zstd finds matches within a block only, so larger blocks generally
+//!   compress better, with diminishing returns past a few MB.
+//!
+//! The estimate targets [`DEFAULT_RECORDS_PER_BLOCK`] records per block, which keeps the
+//! overhead negligible while leaving thousands of blocks in typical short-read files.
+
+use bitnuc::BitSize;
+
+use crate::vbq::FileHeader;
+
+/// Number of records per block targeted by the block size estimates
+pub const DEFAULT_RECORDS_PER_BLOCK: usize = 10_000;
+
+/// Largest block size returned by the block size estimates (16 MB)
+pub const MAX_ESTIMATED_BLOCK_SIZE: u64 = 16 * 1024 * 1024;
+
+/// Size in bytes of the bitnuc encoding of `seq` in a VBQ record
+///
+/// Sequences with non-ACGT bases are measured with the 4-bit encoding they require.
+fn encoded_size(seq: &[u8], ebuf: &mut Vec<u64>) -> usize {
+    ebuf.clear();
+    if BitSize::Two.encode(seq, ebuf).is_err() {
+        ebuf.clear();
+        if BitSize::Four.encode(seq, ebuf).is_err() {
+            // not encodable at all, assume the 4-bit size
+            return seq.len().div_ceil(16) * 8;
+        }
+    }
+    ebuf.len() * 8
+}
+
+/// Computes the block size from the total encoded size of `n` sampled records
+fn block_size_for(total_size: usize, n: usize, target_block_fill: f64) -> u64 {
+    if n == 0 {
+        return FileHeader::default().block;
+    }
+    let fill = if target_block_fill > 0.0 {
+        target_block_fill.min(1.0)
+    } else {
+        1.0
+    };
+    let mean_record_size = total_size as f64 / n as f64;
+    let raw = (mean_record_size * DEFAULT_RECORDS_PER_BLOCK as f64 / fill).ceil() as u64;
+    raw.next_power_of_two().min(MAX_ESTIMATED_BLOCK_SIZE)
+}
+
+/// Estimates a VBQ block size for single-end records like `sample_seqs`
+///
+/// The sequences are encoded to measure the mean encoded record size (length prefixes and
+/// sequence words, without quality scores or headers), and the block size is chosen to
+/// hold [`DEFAULT_RECORDS_PER_BLOCK`] such records while only filling `target_block_fill`
+/// of the block, leaving room for records longer than the sample. `target_block_fill` is
+/// clamped to `(0, 1]`, with values outside of it meaning a full block.
+///
+/// The result is rounded up to a power of two and capped at [`MAX_ESTIMATED_BLOCK_SIZE`], so
+/// blocks of long reads hold fewer records. An empty sample yields the default block size of
+/// [`FileHeader`].
+///
+/// # Examples
+///
+/// ```rust
+/// use binseq::utils::estimate_optimal_vbq_block_size;
+///
+/// let seq = [b'A'; 150];
+/// let sample = vec![&seq[..]; 100];
+/// let block_size = estimate_optimal_vbq_block_size(&sample, 1.0);
+/// assert!(block_size.is_power_of_two());
+/// ```
+#[must_use]
+pub fn estimate_optimal_vbq_block_size(sample_seqs: &[&[u8]], target_block_fill: f64) -> u64 {
+    let mut ebuf = Vec::new();
+    let total = sample_seqs
+        .iter()
+        .map(|seq| 16 + encoded_size(seq, &mut ebuf))
+        .sum();
+    block_size_for(total, sample_seqs.len(), target_block_fill)
+}
+
+/// Estimates a VBQ block size for paired records like `sample_pairs`
+///
+/// See [`estimate_optimal_vbq_block_size`], both mates count towards the record size.
+#[must_use]
+pub fn estimate_optimal_vbq_block_size_paired(
+    sample_pairs: &[(&[u8], &[u8])],
+    target_block_fill: f64,
+) -> u64 {
+    let mut ebuf = Vec::new();
+    let total = sample_pairs
+        .iter()
+        .map(|(s, x)| 16 + encoded_size(s, &mut ebuf) + encoded_size(x, &mut ebuf))
+        .sum();
+    block_size_for(total, sample_pairs.len(), target_block_fill)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(n: usize, len: usize) -> Vec<Vec<u8>> {
+        (0..n)
+            .map(|i| (0..len).map(|j| b"ACGT"[(i + j) % 4]).collect())
+            .collect()
+    }
+
+    #[test]
+    fn test_estimate_short_reads() {
+        let seqs = sample(100, 150);
+        let refs: Vec<&[u8]> = seqs.iter().map(Vec::as_slice).collect();
+
+        let block_size = estimate_optimal_vbq_block_size(&refs, 1.0);
+        assert!(block_size.is_power_of_two());
+        assert!((32 * 1024..=4 * 1024 * 1024).contains(&block_size));
+        // 16 bytes of lengths + 5 words per record, 10k records
+        assert_eq!(block_size, (56 * 10_000u64).next_power_of_two());
+
+        // a lower fill leaves headroom
+        assert_eq!(estimate_optimal_vbq_block_size(&refs, 0.25), 4 * block_size);
+        assert_eq!(estimate_optimal_vbq_block_size(&refs, 0.0), block_size);
+    }
+
+    #[test]
+    fn test_estimate_paired_and_bounds() {
+        let seqs = sample(100, 150);
+        let pairs: Vec<(&[u8], &[u8])> = seqs.iter().map(|s| (&s[..], &s[..])).collect();
+        let block_size = estimate_optimal_vbq_block_size_paired(&pairs, 1.0);
+        assert_eq!(block_size, (96 * 10_000u64).next_power_of_two());
+
+        assert_eq!(
+            estimate_optimal_vbq_block_size(&[], 1.0),
+            FileHeader::default().block
+        );
+        let long = sample(1, 1_000_000);
+        assert_eq!(
+            estimate_optimal_vbq_block_size(&[long[0].as_slice()], 1.0),
+            MAX_ESTIMATED_BLOCK_SIZE
+        );
+    }
+}
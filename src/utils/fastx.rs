@@ -444,6 +444,60 @@ mod tests {
         assert!(encoder_builder.run().is_ok());
     }
 
+    #[test]
+    fn test_encode_fasta_soft_masked() -> Result<()> {
+        use crate::BinseqRecord;
+        use crate::vbq::MmapReader;
+
+        let records: Vec<(String, Vec<u8>)> = (0..200)
+            .map(|i| {
+                let mut seq = b"ACGTTGCA".repeat(1 + i % 9);
+                // Soft-mask a run of bases, as a repeat masker would
+                let start = i % seq.len();
+                let end = (start + 3 + i % 11).min(seq.len());
+                seq[start..end].make_ascii_lowercase();
+                (format!("seq_{i}"), seq)
+            })
+            .collect();
+        let fasta_path = std::env::temp_dir().join("binseq_test_soft_masked.fa");
+        let vbq_path = std::env::temp_dir().join("binseq_test_soft_masked.vbq");
+        let mut fasta = Vec::new();
+        for (id, seq) in &records {
+            fasta.extend_from_slice(format!(">{id}\n").as_bytes());
+            fasta.extend_from_slice(seq);
+            fasta.push(b'\n');
+        }
+        std::fs::write(&fasta_path, fasta)?;
+
+        BinseqWriterBuilder::new(Format::Vbq)
+            .headers(true)
+            .masked(true)
+            .encode_fastx(std::fs::File::create(&vbq_path)?)
+            .input(&fasta_path)
+            .threads(1)
+            .run()?;
+
+        let mut reader = MmapReader::new(&vbq_path)?;
+        let mut block = reader.new_block();
+        let mut decoded = Vec::new();
+        while reader.read_block_into(&mut block)? {
+            for record in block.iter() {
+                let mut seq = Vec::new();
+                record.decode_s_masked(&mut seq)?;
+                let id = String::from_utf8(record.sheader().to_vec()).unwrap();
+                decoded.push((id, seq));
+            }
+        }
+        let mut expected = records;
+        expected.sort();
+        decoded.sort();
+        assert_eq!(decoded, expected);
+
+        std::fs::remove_file(&fasta_path)?;
+        std::fs::remove_file(&vbq_path)?;
+        Ok(())
+    }
+
     #[test]
     fn test_encoder_builder_paired() {
         let builder = BinseqWriterBuilder::new(Format::Vbq);
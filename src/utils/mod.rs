@@ -1,7 +1,17 @@
 //! Utility modules for working with BINSEQ files
 
+mod bitsize;
+mod block_size;
+
 #[cfg(feature = "paraseq")]
 pub mod fastx;
 
 #[cfg(feature = "paraseq")]
 pub use fastx::FastxEncoderBuilder;
+
+pub use bitsize::{ConvertStats, OnIncompatible, transcode_words};
+pub(crate) use bitsize::{Transcoded, Transcoder};
+pub use block_size::{
+    DEFAULT_RECORDS_PER_BLOCK, MAX_ESTIMATED_BLOCK_SIZE, estimate_optimal_vbq_block_size,
+    estimate_optimal_vbq_block_size_paired,
+};
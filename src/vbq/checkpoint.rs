@@ -0,0 +1,276 @@
+//! Checkpoints of a VBQ writer for resumable output
+//!
+//! A [`WriterCheckpoint`] captures everything a [`Writer`](super::Writer) needs to continue a
+//! file: the running byte and record counts, the block ranges of the embedded index and the
+//! records of the current, not yet written block. The bytes the writer has emitted so far are
+//! not part of the checkpoint. A writer resumed from a checkpoint writes only what follows
+//! them, so its output is appended to the already emitted bytes (e.g. as the next part of a
+//! multipart upload).
+//!
+//! Checkpoints have a compact binary encoding ([`WriterCheckpoint::to_bytes`]) guarded by a
+//! checksum, and store a checksum of the file header they were taken with.
+//!
+//! # Example
+//!
+//! ```rust
+//! use binseq::vbq::{FileHeader, Writer, WriterCheckpoint};
+//! use binseq::{Policy, SequencingRecordBuilder};
+//!
+//! let header = FileHeader::default();
+//! let record = SequencingRecordBuilder::default()
+//!     .s_seq(b"ACGTACGT")
+//!     .build()
+//!     .unwrap();
+//!
+//! let mut first = Vec::new();
+//! let mut writer = Writer::new(&mut first, header, Policy::default(), false).unwrap();
+//! writer.push(record).unwrap();
+//! let bytes = writer.suspend().unwrap().to_bytes();
+//!
+//! // ... later, possibly in another process
+//! let checkpoint = WriterCheckpoint::from_bytes(&bytes).unwrap();
+//! let mut second = Vec::new();
+//! let mut writer = Writer::resume(&mut second, header, Policy::default(), &checkpoint).unwrap();
+//! writer.push(record).unwrap();
+//! writer.finish().unwrap();
+//! drop(writer);
+//!
+//! first.extend_from_slice(&second);
+//! ```
+
+use byteorder::{ByteOrder, LittleEndian};
+
+use super::BlockRange;
+use super::header::FileHeader;
+use super::index::SIZE_BLOCK_RANGE;
+use crate::error::{ReadError, Result};
+
+/// Magic bytes of a serialized checkpoint
+const CHECKPOINT_MAGIC: [u8; 8] = *b"VBQCKPT1";
+
+/// The state of a VBQ writer between two records
+///
+/// Taken with [`Writer::checkpoint`](super::Writer::checkpoint) or
+/// [`Writer::suspend`](super::Writer::suspend) and restored with
+/// [`Writer::resume`](super::Writer::resume).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WriterCheckpoint {
+    /// Checksum of the file header of the writer
+    pub(crate) header_checksum: u64,
+    /// Bytes emitted by the writer, including the file header
+    pub(crate) bytes_written: u64,
+    /// Records in the written blocks
+    pub(crate) records_written: u64,
+    /// Block ranges of the written blocks
+    pub(crate) ranges: Vec<BlockRange>,
+    /// Uncompressed records of the current block
+    pub(crate) ubuf: Vec<u8>,
+    /// Start positions of the records of the current block
+    pub(crate) starts: Vec<u64>,
+    /// Fill of the current block in bytes
+    pub(crate) pos: u64,
+}
+impl WriterCheckpoint {
+    /// Returns the number of bytes the writer emitted before the checkpoint
+    ///
+    /// This is the offset at which the output of a resumed writer continues.
+    #[must_use]
+    pub fn bytes_written(&self) -> u64 {
+        self.bytes_written
+    }
+
+    /// Returns the number of records in the blocks emitted before the checkpoint
+    #[must_use]
+    pub fn records_written(&self) -> u64 {
+        self.records_written
+    }
+
+    /// Returns the number of records in the current block, which are not emitted yet
+    #[must_use]
+    pub fn pending_records(&self) -> usize {
+        self.starts.len()
+    }
+
+    /// Serializes the checkpoint
+    ///
+    /// All integers are little endian. The encoding ends with a checksum of the preceding
+    /// bytes.
+    #[must_use]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(
+            80 + self.ranges.len() * (SIZE_BLOCK_RANGE + 17)
+                + self.starts.len() * 8
+                + self.ubuf.len(),
+        );
+        buf.extend_from_slice(&CHECKPOINT_MAGIC);
+        for value in [
+            self.header_checksum,
+            self.bytes_written,
+            self.records_written,
+            self.pos,
+            self.ranges.len() as u64,
+        ] {
+            push_u64(&mut buf, value);
+        }
+        for range in &self.ranges {
+            range
+                .write_bytes(&mut buf)
+                .expect("writing to a Vec cannot fail");
+            let (flag_or, flag_and) = range.flag_summary.unwrap_or_default();
+            buf.push(range.flag_summary.is_some().into());
+            push_u64(&mut buf, flag_or);
+            push_u64(&mut buf, flag_and);
+        }
+        push_u64(&mut buf, self.starts.len() as u64);
+        for &start in &self.starts {
+            push_u64(&mut buf, start);
+        }
+        push_u64(&mut buf, self.ubuf.len() as u64);
+        buf.extend_from_slice(&self.ubuf);
+        let checksum = checksum(&buf);
+        push_u64(&mut buf, checksum);
+        buf
+    }
+
+    /// Deserializes a checkpoint written by [`to_bytes`](Self::to_bytes)
+    ///
+    /// # Errors
+    ///
+    /// * `ReadError::CorruptCheckpoint` - If the bytes are truncated, do not start with the
+    ///   checkpoint magic or fail the checksum
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let Some(payload_len) = bytes.len().checked_sub(8) else {
+            return Err(ReadError::CorruptCheckpoint("truncated").into());
+        };
+        let (payload, stored) = bytes.split_at(payload_len);
+        if checksum(payload) != LittleEndian::read_u64(stored) {
+            return Err(ReadError::CorruptCheckpoint("checksum mismatch").into());
+        }
+
+        let mut src = Source(payload);
+        if src.take(CHECKPOINT_MAGIC.len())? != CHECKPOINT_MAGIC {
+            return Err(ReadError::CorruptCheckpoint("missing magic").into());
+        }
+        let header_checksum = src.u64()?;
+        let bytes_written = src.u64()?;
+        let records_written = src.u64()?;
+        let pos = src.u64()?;
+
+        let n_ranges = src.length()?;
+        let mut ranges = Vec::with_capacity(n_ranges.min(payload.len() / SIZE_BLOCK_RANGE));
+        for _ in 0..n_ranges {
+            let mut range = BlockRange::from_bytes(src.take(SIZE_BLOCK_RANGE)?);
+            let has_summary = src.take(1)?[0] != 0;
+            let summary = (src.u64()?, src.u64()?);
+            range.flag_summary = has_summary.then_some(summary);
+            ranges.push(range);
+        }
+
+        let n_starts = src.length()?;
+        let mut starts = Vec::with_capacity(n_starts.min(payload.len() / 8));
+        for _ in 0..n_starts {
+            starts.push(src.u64()?);
+        }
+
+        let ubuf_len = src.length()?;
+        let ubuf = src.take(ubuf_len)?.to_vec();
+        if !src.0.is_empty() {
+            return Err(ReadError::CorruptCheckpoint("trailing bytes").into());
+        }
+        Ok(Self {
+            header_checksum,
+            bytes_written,
+            records_written,
+            ranges,
+            ubuf,
+            starts,
+            pos,
+        })
+    }
+}
+
+/// Returns the checksum identifying a file header in a checkpoint
+pub(crate) fn header_checksum(header: &FileHeader) -> u64 {
+    let mut buf = Vec::new();
+    header
+        .write_bytes(&mut buf)
+        .expect("writing to a Vec cannot fail");
+    checksum(&buf)
+}
+
+/// 64-bit FNV-1a hash of `bytes`
+fn checksum(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+fn push_u64(buf: &mut Vec<u8>, value: u64) {
+    buf.extend_from_slice(&value.to_le_bytes());
+}
+
+/// Reads the fields of a serialized checkpoint in order
+struct Source<'a>(&'a [u8]);
+impl<'a> Source<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8]> {
+        if n > self.0.len() {
+            return Err(ReadError::CorruptCheckpoint("truncated").into());
+        }
+        let (head, tail) = self.0.split_at(n);
+        self.0 = tail;
+        Ok(head)
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        self.take(8).map(LittleEndian::read_u64)
+    }
+
+    /// Reads a length prefix, which cannot exceed the remaining bytes of a valid checkpoint
+    fn length(&mut self) -> Result<usize> {
+        usize::try_from(self.u64()?)
+            .ok()
+            .filter(|&len| len <= self.0.len())
+            .ok_or_else(|| ReadError::CorruptCheckpoint("length exceeds the checkpoint").into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Error;
+
+    fn sample() -> WriterCheckpoint {
+        let mut range = BlockRange::new(32, 100, 4, 0);
+        range.flag_summary = Some((0b101, 0b001));
+        WriterCheckpoint {
+            header_checksum: header_checksum(&FileHeader::default()),
+            bytes_written: 164,
+            records_written: 4,
+            ranges: vec![range, BlockRange::new(164, 100, 2, 4)],
+            ubuf: vec![1, 2, 3, 4, 5],
+            starts: vec![0, 3],
+            pos: 5,
+        }
+    }
+
+    #[test]
+    fn test_checkpoint_round_trip() -> Result<()> {
+        let checkpoint = sample();
+        let bytes = checkpoint.to_bytes();
+        assert_eq!(WriterCheckpoint::from_bytes(&bytes)?, checkpoint);
+        Ok(())
+    }
+
+    #[test]
+    fn test_checkpoint_corrupt() {
+        let bytes = sample().to_bytes();
+        let mut flipped = bytes.clone();
+        flipped[20] ^= 1;
+        for corrupt in [&bytes[..bytes.len() - 1], &flipped[..], &[][..]] {
+            assert!(matches!(
+                WriterCheckpoint::from_bytes(corrupt),
+                Err(Error::ReadError(ReadError::CorruptCheckpoint(_)))
+            ));
+        }
+    }
+}
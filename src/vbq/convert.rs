@@ -0,0 +1,200 @@
+//! Converting VBQ files between the 2-bit and 4-bit encodings
+//!
+//! Sequences are transcoded in their encoded form (see
+//! [`transcode_words`](crate::utils::transcode_words)), so records are
+//! never decoded to ASCII unless they need to be corrected by a [`Policy`](crate::Policy).
+
+use std::io::Write;
+
+use super::{EncodedRecord, MmapReader, Writer};
+use crate::BinseqRecord;
+use crate::error::Result;
+use crate::utils::{ConvertStats, OnIncompatible, Transcoded, Transcoder};
+
+/// Copies the records of `reader` to `writer`, converting them to the bitsize of the writer
+///
+/// The target bitsize is taken from the header of `writer`, which should otherwise match the
+/// configuration of [`reader.header()`](MmapReader::header). Flags, quality scores, headers
+/// and soft-masks are copied without decoding.
+///
+/// Converting from 2-bit to 4-bit never fails. Converting from 4-bit to 2-bit fails for
+/// sequences with ambiguous bases (e.g. `N`), which are handled by `on_incompatible`.
+///
+/// The writer is not finished, call [`Writer::finish`] (or drop it) to write the index.
+///
+/// # Errors
+///
+/// * `WriteError::InvalidNucleotideSequence` - If a sequence cannot be encoded with the target
+///   bitsize and `on_incompatible` is [`OnIncompatible::Error`] (or a breaking policy)
+/// * Any error of [`Writer::write_encoded_record`], e.g. if the writer is not configured for
+///   the paired records or quality scores of the input
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use binseq::BitSize;
+/// use binseq::utils::OnIncompatible;
+/// use binseq::vbq::{FileHeaderBuilder, MmapReader, WriterBuilder, convert_bitsize};
+/// use std::fs::File;
+///
+/// let mut reader = MmapReader::new("legacy.vbq").unwrap();
+/// let input = reader.header();
+/// let header = FileHeaderBuilder::new()
+///     .block(input.block)
+///     .compressed(input.compressed)
+///     .flags(input.flags)
+///     .qual(input.qual)
+///     .paired(input.paired)
+///     .headers(input.headers)
+///     .masked(input.masked)
+///     .bitsize(BitSize::Two)
+///     .build();
+/// let mut writer = WriterBuilder::default()
+///     .header(header)
+///     .build(File::create("converted.vbq").unwrap())
+///     .unwrap();
+///
+/// let stats = convert_bitsize(&mut reader, &mut writer, OnIncompatible::Error).unwrap();
+/// writer.finish().unwrap();
+/// println!("converted {} records", stats.written);
+/// ```
+pub fn convert_bitsize<W: Write>(
+    reader: &mut MmapReader,
+    writer: &mut Writer<W>,
+    on_incompatible: OnIncompatible,
+) -> Result<ConvertStats> {
+    let mut transcoder =
+        Transcoder::new(reader.header().bits, writer.header().bits, on_incompatible);
+    let mut stats = ConvertStats::default();
+
+    let mut block = reader.new_block();
+    while reader.read_block_into(&mut block)? {
+        for record in block.iter() {
+            stats.records += 1;
+            match transcoder.transcode(&record)? {
+                Transcoded::Skipped => {
+                    stats.skipped += 1;
+                    continue;
+                }
+                Transcoded::Corrected => stats.corrected += 1,
+                Transcoded::Converted => {}
+            }
+            writer.write_encoded_record(EncodedRecord {
+                sbuf: &transcoder.sbuf,
+                xbuf: &transcoder.xbuf,
+                smask: record.smask().unwrap_or_default(),
+                xmask: record.xmask().unwrap_or_default(),
+                ..EncodedRecord::from_record(&record)
+            })?;
+            stats.written += 1;
+        }
+    }
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use bitnuc::BitSize;
+
+    use super::*;
+    use crate::Policy;
+    use crate::error::{Error, WriteError};
+    use crate::testing::{self, DatasetConfig, LengthDistribution, SyntheticRecord};
+    use crate::vbq::{FileHeader, WriterBuilder};
+
+    fn config() -> DatasetConfig {
+        DatasetConfig::new(LengthDistribution::Uniform { min: 20, max: 56 })
+            .x_lengths(LengthDistribution::Uniform { min: 5, max: 27 })
+            .quality(true)
+            .headers(true)
+            .flags(true)
+    }
+
+    fn header(bits: BitSize) -> FileHeader {
+        config().vbq_header(bits, false).with_block(4096)
+    }
+
+    /// Converts an in-memory VBQ file to the other bitsize
+    fn convert(input: Vec<u8>, on_incompatible: OnIncompatible) -> Result<(ConvertStats, Vec<u8>)> {
+        let mut reader = MmapReader::from_bytes(input)?;
+        let bits = match reader.header().bits {
+            BitSize::Two => BitSize::Four,
+            BitSize::Four => BitSize::Two,
+        };
+        let mut buffer = Vec::new();
+        let mut writer = WriterBuilder::default()
+            .header(header(bits))
+            .build(&mut buffer)?;
+        let stats = convert_bitsize(&mut reader, &mut writer, on_incompatible)?;
+        writer.finish()?;
+        drop(writer);
+        Ok((stats, buffer))
+    }
+
+    #[test]
+    fn test_convert_round_trip() -> Result<()> {
+        let records = testing::random_dataset(500, &config());
+        let two = testing::write_vbq(&records, header(BitSize::Two))?;
+
+        let (stats, four) = convert(two, OnIncompatible::Error)?;
+        assert_eq!(stats.records, 500);
+        assert_eq!(stats.written, 500);
+        assert_eq!(
+            MmapReader::from_bytes(four.clone())?.header().bits,
+            BitSize::Four
+        );
+        assert_eq!(testing::read_vbq(four.clone(), &config())?, records);
+
+        let (stats, back) = convert(four, OnIncompatible::Error)?;
+        assert_eq!(stats.written, 500);
+        assert_eq!(testing::read_vbq(back, &config())?, records);
+        Ok(())
+    }
+
+    #[test]
+    fn test_convert_incompatible() -> Result<()> {
+        let config = config().ambiguous(0.005);
+        let records = testing::random_dataset(100, &config);
+        let four = testing::write_vbq(&records, header(BitSize::Four))?;
+        let has_n =
+            |record: &SyntheticRecord| record.s_seq.contains(&b'N') || record.x_seq.contains(&b'N');
+        let ambiguous = records.iter().filter(|record| has_n(record)).count() as u64;
+        assert!(ambiguous > 0);
+
+        let err = convert(four.clone(), OnIncompatible::Error).unwrap_err();
+        assert!(matches!(
+            err,
+            Error::WriteError(WriteError::InvalidNucleotideSequence(_))
+        ));
+
+        let ignore = OnIncompatible::Policy(Policy::IgnoreSequence);
+        let (stats, two) = convert(four.clone(), ignore)?;
+        assert_eq!(stats.records, 100);
+        assert_eq!(stats.skipped, ambiguous);
+        assert_eq!(stats.written, 100 - ambiguous);
+        let kept: Vec<_> = records
+            .iter()
+            .filter(|record| !has_n(record))
+            .cloned()
+            .collect();
+        assert_eq!(testing::read_vbq(two, &config)?, kept);
+
+        let (stats, two) = convert(four, OnIncompatible::Policy(Policy::SetToA))?;
+        assert_eq!(stats.written, 100);
+        assert_eq!(stats.corrected, ambiguous);
+        let corrected: Vec<_> = records
+            .iter()
+            .cloned()
+            .map(|mut record| {
+                for base in record.s_seq.iter_mut().chain(&mut record.x_seq) {
+                    if *base == b'N' {
+                        *base = b'A';
+                    }
+                }
+                record
+            })
+            .collect();
+        assert_eq!(testing::read_vbq(two, &config)?, corrected);
+        Ok(())
+    }
+}
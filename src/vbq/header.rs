@@ -11,13 +11,36 @@
 //!    information specific to that block like its size and number of records.
 //!
 //! Both headers are fixed-size and include magic numbers to validate file integrity.
+//!
+//! A `FileHeader` is configured either with a `FileHeaderBuilder` or by chaining the `with_`
+//! setters of the header itself; both produce the same header:
+//!
+//! ```rust
+//! use binseq::vbq::{FileHeader, FileHeaderBuilder};
+//!
+//! let built = FileHeaderBuilder::new()
+//!     .compressed(true)
+//!     .qual(true)
+//!     .block(65536)
+//!     .build();
+//! let chained = FileHeader::default()
+//!     .with_compressed(true)
+//!     .with_qual(true)
+//!     .with_block(65536);
+//! assert_eq!(built, chained);
+//! ```
 
+use std::fmt;
 use std::io::{Read, Write};
 
 use bitnuc::BitSize;
 use byteorder::{ByteOrder, LittleEndian};
 
-use crate::error::{HeaderError, ReadError, Result};
+use super::index::{INDEX_HEADER_SIZE, SIZE_BLOCK_RANGE};
+use crate::{
+    error::{HeaderError, ReadError, Result},
+    magic,
+};
 
 /// Magic number for file identification: "VSEQ" in ASCII (0x51455356)
 ///
@@ -34,12 +57,18 @@ pub const FILE_MAGIC: [u8; 4] = MAGIC.to_le_bytes();
 ///
 /// This constant is used in block headers to validate block integrity.
 #[allow(clippy::unreadable_literal)]
-const BLOCK_MAGIC: u64 = 0x5145534B434F4C42;
+pub(crate) const BLOCK_MAGIC: u64 = 0x5145534B434F4C42;
 
 /// Current format version number
 ///
 /// This should be incremented when making backwards-incompatible changes to the format.
-const FORMAT: u8 = 1;
+/// Version 2 adds soft-mask bitmaps, per-block header prefixes, and sparse embedded indices
+/// or indices with flag summaries. Files using none of its features are still written as
+/// version 1, so older readers can open them.
+const FORMAT: u8 = 2;
+
+/// Format version of files without any version 2 feature
+const FORMAT_V1: u8 = 1;
 
 /// Size of the file header in bytes (32 bytes)
 ///
@@ -60,13 +89,16 @@ pub const BLOCK_SIZE: u64 = 128 * 1024;
 /// Reserved bytes for future use in the file header
 ///
 /// These bytes are set to a placeholder value (42) and reserved for future extensions.
-pub const RESERVED_BYTES: [u8; 13] = [42; 13];
+pub const RESERVED_BYTES: [u8; 12] = [42; 12];
 
 /// Reserved bytes for future use in block headers (12 bytes)
 ///
 /// These bytes are set to a placeholder value (42) and reserved for future extensions.
 pub const RESERVED_BYTES_BLOCK: [u8; 12] = [42; 12];
 
+/// Value of the `headers` byte of files storing a header prefix per block
+const HEADERS_WITH_PREFIX: u8 = 3;
+
 #[derive(Default, Debug, Clone, Copy)]
 pub struct FileHeaderBuilder {
     qual: Option<bool>,
@@ -76,6 +108,8 @@ pub struct FileHeaderBuilder {
     bitsize: Option<BitSize>,
     headers: Option<bool>,
     flags: Option<bool>,
+    masked: Option<bool>,
+    header_prefix: Option<bool>,
 }
 impl FileHeaderBuilder {
     #[must_use]
@@ -117,17 +151,32 @@ impl FileHeaderBuilder {
         self.flags = Some(flags);
         self
     }
+    /// Stores a soft-mask bitmap with each sequence to preserve lowercase bases
+    #[must_use]
+    pub fn masked(mut self, masked: bool) -> Self {
+        self.masked = Some(masked);
+        self
+    }
+    /// Stores the common prefix of the sequence headers of each block only once
+    ///
+    /// Only has an effect if headers are stored, see [`FileHeader::header_prefix`].
+    #[must_use]
+    pub fn header_prefix(mut self, header_prefix: bool) -> Self {
+        self.header_prefix = Some(header_prefix);
+        self
+    }
     #[must_use]
     pub fn build(self) -> FileHeader {
-        FileHeader::with_capacity(
-            self.block.unwrap_or(BLOCK_SIZE),
-            self.qual.unwrap_or(false),
-            self.compressed.unwrap_or(false),
-            self.paired.unwrap_or(false),
-            self.bitsize.unwrap_or_default(),
-            self.headers.unwrap_or(false),
-            self.flags.unwrap_or(false),
-        )
+        FileHeader::default()
+            .with_block(self.block.unwrap_or(BLOCK_SIZE))
+            .with_qual(self.qual.unwrap_or(false))
+            .with_compressed(self.compressed.unwrap_or(false))
+            .with_paired(self.paired.unwrap_or(false))
+            .with_bits(self.bitsize.unwrap_or_default())
+            .with_headers(self.headers.unwrap_or(false))
+            .with_flags(self.flags.unwrap_or(false))
+            .with_masked(self.masked.unwrap_or(false))
+            .with_header_prefix(self.header_prefix.unwrap_or(false))
     }
 }
 
@@ -146,8 +195,9 @@ impl FileHeaderBuilder {
 /// * `qual` - Whether quality scores are included (1 byte boolean)
 /// * `compressed` - Whether blocks are ZSTD compressed (1 byte boolean)
 /// * `paired` - Whether records contain paired sequences (1 byte boolean)
-/// * `reserved` - Reserved bytes for future extensions (16 bytes)
-#[derive(Clone, Copy, Debug, PartialEq)]
+/// * `masked` - Whether sequences carry a soft-mask bitmap (1 byte boolean)
+/// * `reserved` - Reserved bytes for future extensions (12 bytes)
+#[derive(Clone, Copy, PartialEq)]
 pub struct FileHeader {
     /// Magic number to identify the file format ("VSEQ")
     ///
@@ -156,7 +206,8 @@ pub struct FileHeader {
 
     /// Version of the file format
     ///
-    /// Currently set to 1 (1 byte)
+    /// Set to 2 for files with soft-mask bitmaps, per-block header prefixes, or a sparse
+    /// embedded index or one with flag summaries, and to 1 otherwise (1 byte)
     pub format: u8,
 
     /// Block size in bytes
@@ -198,10 +249,25 @@ pub struct FileHeader {
     /// for both primary and extended (paired) sequences
     pub flags: bool,
 
+    /// Whether sequences carry a soft-mask bitmap (1 byte)
+    ///
+    /// When true, each sequence is followed (after its quality scores) by a bitmap of
+    /// `ceil(len / 8)` bytes marking its lowercase bases, see
+    /// [`RefRecord::smask`](crate::vbq::RefRecord::smask)
+    pub masked: bool,
+
+    /// Whether the sequence headers of each block share a prefix stored once per block
+    ///
+    /// Only meaningful together with `headers`. Each block then starts with the longest
+    /// common prefix of its headers, and records store only the rest of their headers with
+    /// a variable-length size. Stored in the `headers` byte (value 3) of version 2 headers,
+    /// so files written without it are unaffected.
+    pub header_prefix: bool,
+
     /// Reserved bytes for future format extensions
     ///
-    /// Currently filled with placeholder values (13 bytes)
-    pub reserved: [u8; 13],
+    /// Currently filled with placeholder values (12 bytes)
+    pub reserved: [u8; 12],
 }
 impl Default for FileHeader {
     /// Creates a default header with default block size and all features disabled
@@ -294,13 +360,15 @@ impl FileHeader {
     ) -> Self {
         Self {
             magic: MAGIC,
-            format: FORMAT,
+            format: FORMAT_V1,
             block,
             qual,
             compressed,
             paired,
             headers,
             flags,
+            masked: false,
+            header_prefix: false,
             bits: bitsize,
             reserved: RESERVED_BYTES,
         }
@@ -311,6 +379,99 @@ impl FileHeader {
         self.bits = bits;
     }
 
+    /// Returns the header with the given block size in bytes
+    #[must_use]
+    pub fn with_block(mut self, block: u64) -> Self {
+        self.block = block;
+        self
+    }
+
+    /// Returns the header with quality scores stored or not
+    #[must_use]
+    pub fn with_qual(mut self, qual: bool) -> Self {
+        self.qual = qual;
+        self
+    }
+
+    /// Returns the header with blocks compressed or not
+    #[must_use]
+    pub fn with_compressed(mut self, compressed: bool) -> Self {
+        self.compressed = compressed;
+        self
+    }
+
+    /// Returns the header with paired records or not
+    #[must_use]
+    pub fn with_paired(mut self, paired: bool) -> Self {
+        self.paired = paired;
+        self
+    }
+
+    /// Returns the header with the given encoding bitsize
+    #[must_use]
+    pub fn with_bits(mut self, bits: BitSize) -> Self {
+        self.bits = bits;
+        self
+    }
+
+    /// Returns the header with sequence headers stored or not
+    #[must_use]
+    pub fn with_headers(mut self, headers: bool) -> Self {
+        self.headers = headers;
+        self.format = self.required_format();
+        self
+    }
+
+    /// Returns the header with flags stored or not
+    #[must_use]
+    pub fn with_flags(mut self, flags: bool) -> Self {
+        self.flags = flags;
+        self
+    }
+
+    /// Returns the header with soft-mask bitmaps stored or not
+    #[must_use]
+    pub fn with_masked(mut self, masked: bool) -> Self {
+        self.masked = masked;
+        self.format = self.required_format();
+        self
+    }
+
+    /// Returns the header with per-block header prefixes or not
+    #[must_use]
+    pub fn with_header_prefix(mut self, header_prefix: bool) -> Self {
+        self.header_prefix = header_prefix;
+        self.format = self.required_format();
+        self
+    }
+
+    /// Returns the lowest format version able to represent this header
+    ///
+    /// Headers using soft-mask bitmaps or per-block header prefixes need version 2, so that
+    /// readers predating them reject the file instead of misreading the records.
+    fn required_format(&self) -> u8 {
+        if self.masked || (self.headers && self.header_prefix) {
+            FORMAT
+        } else {
+            FORMAT_V1
+        }
+    }
+
+    /// Returns the header tagged with the format version needed by its embedded index
+    ///
+    /// Sparse indices (`index_stride > 1`) and indices with flag summaries are only marked in
+    /// the index header, which readers predating them never check: they would take every
+    /// entry for a single block and skip the blocks in between, or read the 48-byte entries
+    /// of flag summaries as 32-byte block ranges. Files with either are therefore written as
+    /// version 2. Flag summaries are only stored if the header stores flags.
+    #[must_use]
+    pub(crate) fn with_index_layout(mut self, index_stride: usize, flag_summary: bool) -> Self {
+        if index_stride > 1 || (flag_summary && self.flags) {
+            self.format = FORMAT;
+        }
+        self
+    }
+
     /// Creates a header from a 32-byte buffer
     ///
     /// This function parses a raw byte buffer into a `FileHeader` structure,
@@ -326,16 +487,19 @@ impl FileHeader {
     ///
     /// # Errors
     ///
+    /// * `HeaderError::MismatchedFormat` - If the buffer starts with the magic of another BINSEQ format
+    /// * `ReadError::LooksLikeOtherFormat` - If the buffer starts like e.g. a gzip or FASTQ file
     /// * `HeaderError::InvalidMagicNumber` - If the magic number doesn't match "VSEQ"
     /// * `HeaderError::InvalidFormatVersion` - If the format version is unsupported
     /// * `HeaderError::InvalidReservedBytes` - If the reserved bytes section is invalid
     pub fn from_bytes(buffer: &[u8; SIZE_HEADER]) -> Result<Self> {
         let magic = LittleEndian::read_u32(&buffer[0..4]);
         if magic != MAGIC {
-            return Err(HeaderError::InvalidMagicNumber(magic).into());
+            return Err(magic::diagnose(buffer, "VBQ file", 0)
+                .unwrap_or_else(|| HeaderError::InvalidMagicNumber(magic).into()));
         }
         let format = buffer[4];
-        if format != FORMAT {
+        if !(FORMAT_V1..=FORMAT).contains(&format) {
             return Err(HeaderError::InvalidFormatVersion(format).into());
         }
         let block = LittleEndian::read_u64(&buffer[5..13]);
@@ -351,8 +515,12 @@ impl FileHeader {
             0 | 42 => false, // backwards compatibility
             _ => true,
         };
+        // version 1 readers take any other value as plain headers
+        let header_prefix = format > FORMAT_V1 && buffer[17] == HEADERS_WITH_PREFIX;
         let flags = buffer[18] != 0;
-        let Ok(reserved) = buffer[19..32].try_into() else {
+        // byte 19 is reserved in version 1
+        let masked = format > FORMAT_V1 && buffer[19] != 0;
+        let Ok(reserved) = buffer[20..32].try_into() else {
             return Err(HeaderError::InvalidReservedBytes.into());
         };
         Ok(Self {
@@ -365,6 +533,8 @@ impl FileHeader {
             bits,
             headers,
             flags,
+            masked,
+            header_prefix,
             reserved,
         })
     }
@@ -388,15 +558,20 @@ impl FileHeader {
     pub fn write_bytes<W: Write>(&self, writer: &mut W) -> Result<()> {
         let mut buffer = [0u8; SIZE_HEADER];
         LittleEndian::write_u32(&mut buffer[0..4], self.magic);
-        buffer[4] = self.format;
+        buffer[4] = self.format.max(self.required_format());
         LittleEndian::write_u64(&mut buffer[5..13], self.block);
         buffer[13] = self.qual.into();
         buffer[14] = self.compressed.into();
         buffer[15] = self.paired.into();
         buffer[16] = self.bits.into();
-        buffer[17] = self.headers.into();
+        buffer[17] = if self.headers && self.header_prefix {
+            HEADERS_WITH_PREFIX
+        } else {
+            self.headers.into()
+        };
         buffer[18] = self.flags.into();
-        buffer[19..32].copy_from_slice(&self.reserved);
+        buffer[19] = self.masked.into();
+        buffer[20..32].copy_from_slice(&self.reserved);
         writer.write_all(&buffer)?;
         Ok(())
     }
@@ -428,6 +603,125 @@ impl FileHeader {
     pub fn is_paired(&self) -> bool {
         self.paired
     }
+
+    /// Checks if records of `other` can be combined with records of this header
+    ///
+    /// Records can be merged or concatenated if they are encoded with the same bit size and
+    /// store the same fields. The block size and compression may differ, since records are
+    /// rewritten into the blocks of the output. See
+    /// [`compatibility_diff`](Self::compatibility_diff) for the differences.
+    ///
+    /// Note that [`Writer::ingest`](super::Writer::ingest) copies blocks as-is and requires
+    /// equal headers.
+    #[must_use]
+    pub fn is_compatible_with(&self, other: &FileHeader) -> bool {
+        self.bits == other.bits
+            && self.qual == other.qual
+            && self.paired == other.paired
+            && self.headers == other.headers
+    }
+
+    /// Returns every reason records of `other` can not be combined with this header
+    ///
+    /// The list is empty iff [`is_compatible_with`](Self::is_compatible_with) holds. Each
+    /// reason holds the value of this header first.
+    #[must_use]
+    pub fn compatibility_diff(&self, other: &FileHeader) -> Vec<IncompatibilityReason> {
+        let mut reasons = Vec::new();
+        if self.bits != other.bits {
+            reasons.push(IncompatibilityReason::BitsMismatch(self.bits, other.bits));
+        }
+        if self.qual != other.qual {
+            reasons.push(IncompatibilityReason::QualMismatch(self.qual, other.qual));
+        }
+        if self.paired != other.paired {
+            reasons.push(IncompatibilityReason::PairedMismatch(
+                self.paired,
+                other.paired,
+            ));
+        }
+        if self.headers != other.headers {
+            reasons.push(IncompatibilityReason::HeadersMismatch(
+                self.headers,
+                other.headers,
+            ));
+        }
+        reasons
+    }
+
+    /// Estimates the size in bytes of a file containing `n_records` records
+    ///
+    /// The estimate assumes every record has a primary sequence of `mean_slen` nucleotides
+    /// (and an extended sequence of the same length if the file is paired), and accounts for
+    /// the file header, block headers, block fill, and the embedded index.
+    ///
+    /// Uncompressed files are padded to the full block size, so the estimate is tight (it
+    /// slightly over-estimates the compressed index). For compressed files the payload is
+    /// scaled by a fixed heuristic compression ratio and the result should be treated as a
+    /// rough guide only. Sequence headers are not included in the estimate.
+    #[must_use]
+    pub fn estimated_file_size(&self, n_records: u64, mean_slen: f64) -> u64 {
+        if n_records == 0 {
+            return SIZE_HEADER as u64 + index_overhead(0);
+        }
+        let record_size = self.estimated_record_size(mean_slen);
+        let n_blocks = self.estimated_blocks(n_records, mean_slen);
+
+        let payload = if self.compressed {
+            (n_records as f64 * record_size as f64 * ESTIMATED_COMPRESSION_RATIO).ceil() as u64
+        } else {
+            n_blocks * self.block
+        };
+
+        SIZE_HEADER as u64
+            + n_blocks * SIZE_BLOCK_HEADER as u64
+            + payload
+            + index_overhead(n_blocks)
+    }
+
+    /// Estimates the number of blocks taken by `n_records` records with the given mean length
+    pub(crate) fn estimated_blocks(&self, n_records: u64, mean_slen: f64) -> u64 {
+        let records_per_block = (self.block / self.estimated_record_size(mean_slen)).max(1);
+        n_records.div_ceil(records_per_block)
+    }
+
+    /// Estimates the uncompressed in-block size of a record with the given mean length
+    fn estimated_record_size(&self, mean_slen: f64) -> u64 {
+        let slen = mean_slen.max(0.0).ceil() as u64;
+        let nucs_per_word = match self.bits {
+            BitSize::Two => 32,
+            BitSize::Four => 16,
+        };
+        let n_seqs = if self.paired { 2 } else { 1 };
+
+        // length prefixes
+        let mut size = 16;
+        if self.flags {
+            size += 8;
+        }
+        size += n_seqs * 8 * slen.div_ceil(nucs_per_word);
+        if self.qual {
+            size += n_seqs * slen;
+        }
+        if self.headers {
+            // length prefixes only, header contents are unknown
+            size += n_seqs * 8;
+        }
+        if self.masked {
+            size += n_seqs * slen.div_ceil(8);
+        }
+        size
+    }
+}
+
+/// Heuristic ratio of compressed to uncompressed block size used for size estimates
+const ESTIMATED_COMPRESSION_RATIO: f64 = 0.5;
+
+/// Upper bound on the bytes taken by the embedded index for a file with `n_blocks` blocks
+///
+/// The index is ZSTD-compressed on disk, so the true overhead is usually smaller.
+fn index_overhead(n_blocks: u64) -> u64 {
+    (INDEX_HEADER_SIZE + 16) as u64 + n_blocks * SIZE_BLOCK_RANGE as u64
 }
 
 /// Block header for VBQ block data
@@ -441,7 +735,7 @@ impl FileHeader {
 /// * `size` - Actual size of the block in bytes (8 bytes)
 /// * `records` - Number of records in the block (4 bytes)
 /// * `reserved` - Reserved bytes for future extensions (12 bytes)
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy)]
 pub struct BlockHeader {
     /// Magic number to identify the block ("BLOCKSEQ")
     ///
@@ -548,9 +842,24 @@ impl BlockHeader {
     ///
     /// * `ReadError::InvalidBlockMagicNumber` - If the magic number doesn't match "BLOCKSEQ"
     pub fn from_bytes(buffer: &[u8; SIZE_BLOCK_HEADER]) -> Result<Self> {
+        Self::from_bytes_at(buffer, 0)
+    }
+
+    /// Creates a block header from a 32-byte buffer read at byte `offset` of a file
+    ///
+    /// Identical to [`from_bytes`](Self::from_bytes), but errors report `offset` as the
+    /// position of the block.
+    ///
+    /// # Errors
+    ///
+    /// * `HeaderError::MismatchedFormat` - If the buffer starts with the magic of another
+    ///   BINSEQ structure (e.g. the index)
+    /// * `ReadError::InvalidBlockMagicNumber` - If the magic number doesn't match "BLOCKSEQ"
+    pub fn from_bytes_at(buffer: &[u8; SIZE_BLOCK_HEADER], offset: usize) -> Result<Self> {
         let magic = LittleEndian::read_u64(&buffer[0..8]);
         if magic != BLOCK_MAGIC {
-            return Err(ReadError::InvalidBlockMagicNumber(magic, 0).into());
+            return Err(magic::diagnose(buffer, "VBQ block", offset)
+                .unwrap_or_else(|| ReadError::InvalidBlockMagicNumber(magic, offset).into()));
         }
         let size = LittleEndian::read_u64(&buffer[8..16]);
         let records = LittleEndian::read_u32(&buffer[16..20]);
@@ -563,6 +872,80 @@ impl BlockHeader {
     }
 }
 
+/// A difference between two VBQ headers preventing their records from being combined
+///
+/// Returned by [`FileHeader::compatibility_diff`], with the value of the receiving header
+/// first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IncompatibilityReason {
+    /// The bit sizes differ
+    BitsMismatch(BitSize, BitSize),
+    /// Only one of the headers stores quality scores
+    QualMismatch(bool, bool),
+    /// Only one of the headers is paired
+    PairedMismatch(bool, bool),
+    /// Only one of the headers stores sequence headers
+    HeadersMismatch(bool, bool),
+}
+
+impl fmt::Display for FileHeader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let bits = match self.bits {
+            BitSize::Two => 2,
+            BitSize::Four => 4,
+        };
+        write!(f, "VBQ {{ version: {}, block_size: ", self.format)?;
+        if self.block % 1024 == 0 {
+            write!(f, "{} KB", self.block / 1024)?;
+        } else {
+            write!(f, "{} B", self.block)?;
+        }
+        write!(
+            f,
+            ", qual: {}, compressed: {}, paired: {}, headers: {}, flags: {}, masked: {}, bitsize: {bits} }}",
+            self.qual, self.compressed, self.paired, self.headers, self.flags, self.masked,
+        )
+    }
+}
+
+impl fmt::Debug for FileHeader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FileHeader")
+            .field("magic", &format_args!("{:#010x}", self.magic))
+            .field("format", &self.format)
+            .field("block", &self.block)
+            .field("qual", &self.qual)
+            .field("compressed", &self.compressed)
+            .field("paired", &self.paired)
+            .field("bits", &self.bits)
+            .field("headers", &self.headers)
+            .field("flags", &self.flags)
+            .field("masked", &self.masked)
+            .field("header_prefix", &self.header_prefix)
+            .finish_non_exhaustive()
+    }
+}
+
+impl fmt::Display for BlockHeader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Block {{ size: {} B, records: {} }}",
+            self.size, self.records
+        )
+    }
+}
+
+impl fmt::Debug for BlockHeader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BlockHeader")
+            .field("magic", &format_args!("{:#018x}", self.magic))
+            .field("size", &self.size)
+            .field("records", &self.records)
+            .finish_non_exhaustive()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -588,6 +971,38 @@ mod tests {
         assert!(!header.paired);
         assert!(!header.headers);
         assert!(!header.flags);
+        assert!(!header.masked);
+    }
+
+    #[test]
+    fn test_builder_matches_with_setters() {
+        for (compressed, qual, paired, bits) in [
+            (false, false, false, BitSize::Two),
+            (true, true, false, BitSize::Four),
+            (true, false, true, BitSize::Two),
+        ] {
+            let built = FileHeaderBuilder::new()
+                .block(65536)
+                .compressed(compressed)
+                .qual(qual)
+                .paired(paired)
+                .bitsize(bits)
+                .headers(qual)
+                .flags(paired)
+                .masked(compressed)
+                .build();
+            let chained = FileHeader::default()
+                .with_block(65536)
+                .with_compressed(compressed)
+                .with_qual(qual)
+                .with_paired(paired)
+                .with_bits(bits)
+                .with_headers(qual)
+                .with_flags(paired)
+                .with_masked(compressed);
+            assert_eq!(built, chained);
+        }
+        assert_eq!(FileHeaderBuilder::new().build(), FileHeader::default());
     }
 
     // ==================== FileHeader Constructor Tests ====================
@@ -631,6 +1046,70 @@ mod tests {
         assert_eq!(parsed, header);
     }
 
+    #[test]
+    fn test_file_header_masked_roundtrip() {
+        let header = FileHeaderBuilder::new().masked(true).build();
+        let mut buffer = [0u8; SIZE_HEADER];
+        header.write_bytes(&mut &mut buffer[..]).unwrap();
+        assert_eq!(buffer[4], FORMAT);
+        let parsed = FileHeader::from_bytes(&buffer).unwrap();
+        assert!(parsed.masked);
+        assert_eq!(parsed, header);
+
+        // Unmasked files stay readable by version 1 readers
+        let unmasked = header.with_masked(false);
+        unmasked.write_bytes(&mut &mut buffer[..]).unwrap();
+        assert_eq!(buffer[4], FORMAT_V1);
+        assert_eq!(FileHeader::from_bytes(&buffer).unwrap(), unmasked);
+
+        // Byte 19 is reserved in version 1 headers
+        buffer[19] = 42;
+        assert!(!FileHeader::from_bytes(&buffer).unwrap().masked);
+
+        // Setting the field directly still writes a version 2 header
+        let direct = FileHeader {
+            masked: true,
+            ..FileHeader::default()
+        };
+        direct.write_bytes(&mut &mut buffer[..]).unwrap();
+        assert_eq!(buffer[4], FORMAT);
+        assert!(FileHeader::from_bytes(&buffer).unwrap().masked);
+    }
+
+    #[test]
+    fn test_file_header_prefix_roundtrip() {
+        let header = FileHeaderBuilder::new()
+            .headers(true)
+            .header_prefix(true)
+            .build();
+        let mut buffer = [0u8; SIZE_HEADER];
+        header.write_bytes(&mut &mut buffer[..]).unwrap();
+        assert_eq!(buffer[4], FORMAT);
+        assert_eq!(buffer[17], HEADERS_WITH_PREFIX);
+        let parsed = FileHeader::from_bytes(&buffer).unwrap();
+        assert!(parsed.headers && parsed.header_prefix);
+        assert_eq!(parsed, header);
+
+        // Files with headers written without prefixes are unaffected
+        buffer[17] = 1;
+        let parsed = FileHeader::from_bytes(&buffer).unwrap();
+        assert!(parsed.headers && !parsed.header_prefix);
+
+        // Version 1 headers have no prefixes
+        buffer[4] = FORMAT_V1;
+        buffer[17] = HEADERS_WITH_PREFIX;
+        let parsed = FileHeader::from_bytes(&buffer).unwrap();
+        assert!(parsed.headers && !parsed.header_prefix);
+
+        // Prefixes are not stored without headers
+        let header = FileHeaderBuilder::new().header_prefix(true).build();
+        header.write_bytes(&mut &mut buffer[..]).unwrap();
+        assert_eq!(
+            FileHeader::from_bytes(&buffer).unwrap(),
+            FileHeader::default()
+        );
+    }
+
     #[test]
     fn test_file_header_from_bytes_four_bit() {
         let header = FileHeader::new(false, false, false, BitSize::Four, false, false);
@@ -692,6 +1171,44 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_file_header_from_bq_bytes() {
+        let mut buffer = [0u8; SIZE_HEADER];
+        buffer[..4].copy_from_slice(&crate::bq::FILE_MAGIC);
+        let result = FileHeader::from_bytes(&buffer);
+        assert!(matches!(
+            result,
+            Err(crate::Error::HeaderError(HeaderError::MismatchedFormat {
+                found: "BQ file",
+                expected: "VBQ file",
+                offset: 0
+            }))
+        ));
+    }
+
+    #[test]
+    fn test_block_header_from_index_bytes() {
+        let mut buffer = [0u8; SIZE_BLOCK_HEADER];
+        buffer[..8].copy_from_slice(&super::super::INDEX_MAGIC.to_le_bytes());
+        let result = BlockHeader::from_bytes_at(&buffer, 4096);
+        assert!(matches!(
+            result,
+            Err(crate::Error::HeaderError(HeaderError::MismatchedFormat {
+                found: "VBQ index",
+                expected: "VBQ block",
+                offset: 4096
+            }))
+        ));
+
+        let result = BlockHeader::from_bytes_at(&[0u8; SIZE_BLOCK_HEADER], 4096);
+        assert!(matches!(
+            result,
+            Err(crate::Error::ReadError(ReadError::InvalidBlockMagicNumber(
+                0, 4096
+            )))
+        ));
+    }
+
     #[test]
     fn test_block_header_roundtrip() {
         let header = BlockHeader::new(2048, 42);
@@ -704,4 +1221,158 @@ mod tests {
         assert_eq!(parsed.records, 42);
         assert!(!parsed.is_empty());
     }
+
+    // ==================== Size Estimate Tests ====================
+
+    fn write_records(header: FileHeader, n_records: u64, slen: usize) -> Result<usize> {
+        use crate::SequencingRecordBuilder;
+        use crate::vbq::WriterBuilder;
+
+        let seq = b"ACGT".repeat(slen.div_ceil(4));
+        let seq = &seq[..slen];
+        let qual = vec![b'I'; slen];
+        let mut buffer = Vec::new();
+        let mut writer = WriterBuilder::default().header(header).build(&mut buffer)?;
+        for idx in 0..n_records {
+            let record = SequencingRecordBuilder::default()
+                .s_seq(seq)
+                .s_qual(&qual)
+                .x_seq(seq)
+                .x_qual(&qual)
+                .flag(idx)
+                .build()?;
+            writer.push(record)?;
+        }
+        writer.finish()?;
+        drop(writer);
+        Ok(buffer.len())
+    }
+
+    #[test]
+    fn test_estimated_file_size_uncompressed() -> Result<()> {
+        for (qual, paired, flags) in [
+            (false, false, false),
+            (true, false, true),
+            (true, true, false),
+            (false, true, true),
+        ] {
+            let header = FileHeaderBuilder::new()
+                .block(4096)
+                .qual(qual)
+                .paired(paired)
+                .flags(flags)
+                .build();
+            for n_records in [1, 100, 1000] {
+                let actual = write_records(header, n_records, 150)? as u64;
+                let estimate = header.estimated_file_size(n_records, 150.0);
+
+                // The index is compressed on disk so the estimate is an upper bound
+                let n_blocks = estimate.div_ceil(header.block);
+                assert!(estimate >= actual);
+                assert!(estimate - actual <= n_blocks * SIZE_BLOCK_RANGE as u64);
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_estimated_file_size_compressed_is_bounded() -> Result<()> {
+        let header = FileHeaderBuilder::new()
+            .block(4096)
+            .qual(true)
+            .compressed(true)
+            .build();
+        let estimate = header.estimated_file_size(1000, 150.0);
+        let uncompressed = FileHeaderBuilder::new()
+            .block(4096)
+            .qual(true)
+            .build()
+            .estimated_file_size(1000, 150.0);
+        assert!(estimate < uncompressed);
+        assert!(write_records(header, 1000, 150)? > 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_estimated_file_size_empty() {
+        let header = FileHeaderBuilder::new().build();
+        assert!(header.estimated_file_size(0, 150.0) > SIZE_HEADER as u64);
+    }
+
+    #[test]
+    fn test_display_and_debug() {
+        let header = FileHeaderBuilder::new()
+            .qual(true)
+            .paired(true)
+            .bitsize(BitSize::Four)
+            .build();
+        assert_eq!(
+            header.to_string(),
+            "VBQ { version: 1, block_size: 128 KB, qual: true, compressed: false, paired: true, headers: false, flags: false, masked: false, bitsize: 4 }"
+        );
+        let odd = FileHeaderBuilder::new().block(1000).build();
+        assert!(odd.to_string().contains("block_size: 1000 B"));
+        assert!(!format!("{header:?}").contains("reserved"));
+
+        let block = BlockHeader::new(512, 10);
+        assert_eq!(block.to_string(), "Block { size: 512 B, records: 10 }");
+        let debug = format!("{block:?}");
+        assert!(debug.starts_with("BlockHeader { magic: 0x5145534b434f4c42, size: 512"));
+        assert!(!debug.contains("reserved"));
+    }
+
+    #[test]
+    fn test_compatibility() {
+        let header = FileHeaderBuilder::new()
+            .qual(true)
+            .paired(true)
+            .headers(true)
+            .build();
+
+        // block size, compression, flags and soft-masking may differ
+        let other = FileHeaderBuilder::new()
+            .block(4096)
+            .compressed(true)
+            .qual(true)
+            .paired(true)
+            .headers(true)
+            .flags(true)
+            .masked(true)
+            .build();
+        assert!(header.is_compatible_with(&other));
+        assert!(header.compatibility_diff(&other).is_empty());
+
+        // every combination of differing fields
+        for mask in 1..16u32 {
+            let other = FileHeaderBuilder::new()
+                .bitsize(if mask & 1 == 0 {
+                    BitSize::Two
+                } else {
+                    BitSize::Four
+                })
+                .qual(mask & 2 == 0)
+                .paired(mask & 4 == 0)
+                .headers(mask & 8 == 0)
+                .build();
+            let mut expected = Vec::new();
+            if mask & 1 != 0 {
+                expected.push(IncompatibilityReason::BitsMismatch(
+                    BitSize::Two,
+                    BitSize::Four,
+                ));
+            }
+            if mask & 2 != 0 {
+                expected.push(IncompatibilityReason::QualMismatch(true, false));
+            }
+            if mask & 4 != 0 {
+                expected.push(IncompatibilityReason::PairedMismatch(true, false));
+            }
+            if mask & 8 != 0 {
+                expected.push(IncompatibilityReason::HeadersMismatch(true, false));
+            }
+            assert!(!header.is_compatible_with(&other));
+            assert!(!other.is_compatible_with(&header));
+            assert_eq!(header.compatibility_diff(&other), expected);
+        }
+    }
 }
@@ -22,9 +22,17 @@
 //!
 //! ## Index Contents
 //!
-//! The compressed index contains:
-//! 1. **`IndexHeader`** (32 bytes): Metadata about the indexed file
-//! 2. **`BlockRange` entries** (32 bytes each): One per data block
+//! The index contains:
+//! 1. **`IndexHeader`** (32 bytes, uncompressed): Metadata about the indexed file
+//! 2. **`BlockRange` entries** (32 bytes each, compressed): One per data block
+//!
+//! ## Sparse Indices
+//!
+//! Files with many small blocks can be written with a sparse index that only keeps an
+//! entry for every k-th block (the *stride*). Blocks between two entries are located by
+//! scanning forward over their block headers. The stride and the total number of records
+//! are stored in the `IndexHeader`, so the record count is available without decompressing
+//! the block ranges.
 //!
 //! ## Key Changes from v0.6.x
 //!
@@ -33,8 +41,10 @@
 //! - Support for files with more than 4 billion records
 
 use std::{
+    fmt,
     fs::File,
     io::{Cursor, Read, Write},
+    ops::Range,
     path::Path,
 };
 
@@ -45,7 +55,11 @@ use super::{
     BlockHeader, FileHeader,
     header::{SIZE_BLOCK_HEADER, SIZE_HEADER},
 };
-use crate::error::{IndexError, Result};
+use crate::{
+    error::{HeaderError, IndexError, Result},
+    magic,
+    source::ReadAt,
+};
 
 /// Size of `BlockRange` in bytes
 pub const SIZE_BLOCK_RANGE: usize = 32;
@@ -59,6 +73,24 @@ pub const INDEX_MAGIC: u64 = 0x5845444e49514256;
 pub const INDEX_END_MAGIC: u64 = 0x444E455845444E49;
 /// Index Block Reservation
 pub const INDEX_RESERVATION: [u8; 4] = [42; 4];
+/// Tag marking an `IndexHeader` that stores the record count and stride (IDX2)
+const INDEX_LAYOUT_TAG: [u8; 4] = *b"IDX2";
+/// Tag marking an `IndexHeader` whose block ranges are followed by flag summaries (IDX3)
+const INDEX_SUMMARY_LAYOUT_TAG: [u8; 4] = *b"IDX3";
+/// Size of the flag summary following each `BlockRange` in indices with flag summaries
+pub const SIZE_FLAG_SUMMARY: usize = 16;
+
+/// Columns of the text export of an index (see [`BlockIndex::to_csv`])
+const TEXT_COLUMNS: [&str; 5] = [
+    "block_idx",
+    "start_offset",
+    "len",
+    "block_records",
+    "cumulative_records",
+];
+
+/// Columns appended to the text export of an index with flag summaries
+const TEXT_SUMMARY_COLUMNS: [&str; 2] = ["flag_or", "flag_and"];
 
 /// Descriptor of the dimensions of a block in a VBQ file
 ///
@@ -92,7 +124,7 @@ pub const INDEX_RESERVATION: [u8; 4] = [42; 4];
 /// println!("Block starts at byte {}", range.start_offset);
 /// println!("Block contains {} records", range.block_records);
 /// ```
-#[derive(Debug, Clone, Copy)]
+#[derive(Clone, Copy, PartialEq, Eq)]
 pub struct BlockRange {
     /// File offset where the block starts (in bytes, including headers)
     ///
@@ -128,6 +160,11 @@ pub struct BlockRange {
 
     /// Reserved bytes for future extensions
     pub reservation: [u8; 4],
+
+    /// Bitwise OR and AND of the flags of all records in the block, if recorded
+    ///
+    /// (16 bytes in serialized form, only in indices with flag summaries)
+    pub(crate) flag_summary: Option<(u64, u64)>,
 }
 impl BlockRange {
     /// Creates a new `BlockRange` with the specified parameters
@@ -159,9 +196,61 @@ impl BlockRange {
             block_records,
             cumulative_records,
             reservation: INDEX_RESERVATION,
+            flag_summary: None,
+        }
+    }
+
+    /// Returns a copy of the range with the flag summary of its block
+    ///
+    /// `flag_or` and `flag_and` are the bitwise OR and AND of the flags of all records in
+    /// the block.
+    #[must_use]
+    pub fn with_flag_summary(self, flag_or: u64, flag_and: u64) -> Self {
+        Self {
+            flag_summary: Some((flag_or, flag_and)),
+            ..self
         }
     }
 
+    /// Returns the bitwise OR of the flags of all records in the block
+    ///
+    /// A bit unset in the OR is unset in every record of the block. Returns `None` if the
+    /// index has no flag summaries (see [`WriterBuilder::index_flag_summary`](crate::vbq::WriterBuilder::index_flag_summary)).
+    #[must_use]
+    pub fn flag_or(&self) -> Option<u64> {
+        self.flag_summary.map(|(flag_or, _)| flag_or)
+    }
+
+    /// Returns the bitwise AND of the flags of all records in the block
+    ///
+    /// A bit set in the AND is set in every record of the block. Returns `None` if the
+    /// index has no flag summaries.
+    #[must_use]
+    pub fn flag_and(&self) -> Option<u64> {
+        self.flag_summary.map(|(_, flag_and)| flag_and)
+    }
+
+    /// Returns the global indices of the records in the block
+    #[must_use]
+    pub fn record_global_range(&self) -> Range<u64> {
+        self.cumulative_records..self.cumulative_records + u64::from(self.block_records)
+    }
+
+    /// Returns `true` if the record with global index `record_idx` is in the block
+    #[must_use]
+    pub fn contains_record(&self, record_idx: u64) -> bool {
+        self.record_global_range().contains(&record_idx)
+    }
+
+    /// Returns the index within the block of the record with global index `record_idx`
+    ///
+    /// Returns `None` if the record is not in the block.
+    #[must_use]
+    pub fn record_local_index(&self, record_idx: u64) -> Option<usize> {
+        self.contains_record(record_idx)
+            .then(|| (record_idx - self.cumulative_records) as usize)
+    }
+
     /// Serializes the block range to a binary format and writes it to the provided writer
     ///
     /// This method serializes the `BlockRange` to a fixed-size 32-byte structure and
@@ -220,6 +309,7 @@ impl BlockRange {
             block_records: LittleEndian::read_u32(&buffer[16..20]),
             cumulative_records: LittleEndian::read_u64(&buffer[20..28]),
             reservation: INDEX_RESERVATION,
+            flag_summary: None,
         }
     }
 
@@ -248,13 +338,48 @@ impl BlockRange {
     }
 }
 
+impl fmt::Display for BlockRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "BlockRange {{ offset: {}, len: {} B, records: {}, cumulative: {} }}",
+            self.start_offset, self.len, self.block_records, self.cumulative_records
+        )
+    }
+}
+
+impl fmt::Debug for BlockRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut s = f.debug_struct("BlockRange");
+        s.field("start_offset", &self.start_offset)
+            .field("len", &self.len)
+            .field("block_records", &self.block_records)
+            .field("cumulative_records", &self.cumulative_records);
+        if let Some((flag_or, flag_and)) = self.flag_summary {
+            s.field("flag_or", &format_args!("{flag_or:#x}"))
+                .field("flag_and", &format_args!("{flag_and:#x}"));
+        }
+        s.finish_non_exhaustive()
+    }
+}
+
+/// Minimum, mean and maximum of `values`, `None` if empty
+fn min_mean_max(values: impl Iterator<Item = u64>) -> Option<(u64, f64, u64)> {
+    let (count, sum, min, max) = values.fold((0u64, 0u64, u64::MAX, 0u64), |acc, v| {
+        (acc.0 + 1, acc.1 + v, acc.2.min(v), acc.3.max(v))
+    });
+    (count > 0).then(|| (min, sum as f64 / count as f64, max))
+}
+
 /// Header for a VBQ index file
 ///
 /// The `IndexHeader` contains metadata about an index file, including a magic number
 /// for validation and the size of the indexed file. This allows verifying that an index
 /// file matches its corresponding VBQ file.
 ///
-/// The header has a fixed size of 32 bytes to ensure compatibility across versions.
+/// The header has a fixed size of 32 bytes to ensure compatibility across versions. Indices
+/// written by older versions leave the trailing 16 bytes reserved, in which case the record
+/// count is unknown and the index is dense.
 #[derive(Debug, Clone, Copy)]
 pub struct IndexHeader {
     /// Magic number to designate the index file ("VBQINDEX" in ASCII)
@@ -269,10 +394,20 @@ pub struct IndexHeader {
     /// (8 bytes in serialized form)
     bytes: u64,
 
-    /// Reserved bytes for future extensions
+    /// Total number of records in the indexed file, if recorded
+    ///
+    /// (8 bytes in serialized form)
+    records: Option<u64>,
+
+    /// Number of blocks per index entry (1 for a dense index)
     ///
-    /// (16 bytes in serialized form)
-    reserved: [u8; INDEX_HEADER_SIZE - 16],
+    /// (4 bytes in serialized form, followed by a 4 byte layout tag)
+    stride: u32,
+
+    /// Whether each block range is followed by the flag summary of its block
+    ///
+    /// (encoded in the layout tag)
+    flag_summary: bool,
 }
 impl IndexHeader {
     /// Creates a new index header for a VBQ file of the specified size
@@ -288,7 +423,9 @@ impl IndexHeader {
         Self {
             magic: INDEX_MAGIC,
             bytes,
-            reserved: [42; INDEX_HEADER_SIZE - 16],
+            records: None,
+            stride: 1,
+            flag_summary: false,
         }
     }
     /// Reads an index header from the provided reader
@@ -311,25 +448,72 @@ impl IndexHeader {
     /// The header is expected to be 32 bytes with the following structure:
     /// - Bytes 0-7: magic number (u64, little endian, must be `INDEX_MAGIC`)
     /// - Bytes 8-15: file size in bytes (u64, little endian)
-    /// - Bytes 16-31: reserved for future extensions
+    /// - Bytes 16-23: total number of records (u64, little endian)
+    /// - Bytes 24-27: index stride (u32, little endian)
+    /// - Bytes 28-31: layout tag (`IDX2`, or `IDX3` if the block ranges are followed by flag
+    ///   summaries), or reserved bytes in older indices
     pub fn from_reader<R: Read>(reader: &mut R) -> Result<Self> {
         let mut buffer = [0; INDEX_HEADER_SIZE];
         reader.read_exact(&mut buffer)?;
         let magic = LittleEndian::read_u64(&buffer[0..8]);
         let bytes = LittleEndian::read_u64(&buffer[8..16]);
-        let Ok(reserved) = buffer[16..INDEX_HEADER_SIZE].try_into() else {
-            return Err(IndexError::InvalidReservedBytes.into());
-        };
         if magic != INDEX_MAGIC {
-            return Err(IndexError::InvalidMagicNumber(magic).into());
+            return Err(magic::diagnose(&buffer, "VBQ index", 0)
+                .unwrap_or_else(|| IndexError::InvalidMagicNumber(magic).into()));
+        }
+        let flag_summary = buffer[28..] == INDEX_SUMMARY_LAYOUT_TAG;
+        if buffer[28..] != INDEX_LAYOUT_TAG && !flag_summary {
+            // Reserved bytes of an index written before the record count was stored
+            return Ok(Self::new(bytes));
+        }
+        let stride = LittleEndian::read_u32(&buffer[24..28]);
+        if stride == 0 {
+            return Err(IndexError::InvalidReservedBytes.into());
         }
         Ok(Self {
             magic,
             bytes,
-            reserved,
+            records: Some(LittleEndian::read_u64(&buffer[16..24])),
+            stride,
+            flag_summary,
         })
     }
 
+    /// Returns the size in bytes of the VBQ data described by this index
+    ///
+    /// This covers the file header and all data blocks, but not the embedded index itself.
+    #[must_use]
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    /// Returns the total number of records in the indexed file, if recorded in the header
+    #[must_use]
+    pub fn records(&self) -> Option<u64> {
+        self.records
+    }
+
+    /// Returns the number of blocks per index entry (1 for a dense index)
+    #[must_use]
+    pub fn stride(&self) -> usize {
+        self.stride as usize
+    }
+
+    /// Returns a copy of the header with the flag summary layout set to `flag_summary`
+    #[must_use]
+    pub(crate) fn with_flag_summary(self, flag_summary: bool) -> Self {
+        Self {
+            flag_summary,
+            ..self
+        }
+    }
+
+    /// Returns true if the block ranges of the index store the flag summaries of their blocks
+    #[must_use]
+    pub fn has_flag_summary(&self) -> bool {
+        self.flag_summary
+    }
+
     pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
         let mut buffer = [0; INDEX_HEADER_SIZE];
         buffer.copy_from_slice(&bytes[..INDEX_HEADER_SIZE]);
@@ -352,15 +536,21 @@ impl IndexHeader {
     ///
     /// # Format
     ///
-    /// The header is serialized as:
-    /// - Bytes 0-7: magic number (u64, little endian)
-    /// - Bytes 8-15: file size in bytes (u64, little endian)
-    /// - Bytes 16-31: reserved for future extensions
+    /// The header is serialized as described in [`from_reader`](Self::from_reader). Headers
+    /// without a record count are written with reserved trailing bytes.
     pub fn write_bytes<W: Write>(&self, writer: &mut W) -> Result<()> {
-        let mut buffer = [0; INDEX_HEADER_SIZE];
+        let mut buffer = [42; INDEX_HEADER_SIZE];
         LittleEndian::write_u64(&mut buffer[0..8], self.magic);
         LittleEndian::write_u64(&mut buffer[8..16], self.bytes);
-        buffer[16..].copy_from_slice(&self.reserved);
+        if let Some(records) = self.records {
+            LittleEndian::write_u64(&mut buffer[16..24], records);
+            LittleEndian::write_u32(&mut buffer[24..28], self.stride);
+            buffer[28..].copy_from_slice(if self.flag_summary {
+                &INDEX_SUMMARY_LAYOUT_TAG
+            } else {
+                &INDEX_LAYOUT_TAG
+            });
+        }
         writer.write_all(&buffer)?;
         Ok(())
     }
@@ -440,7 +630,11 @@ impl BlockIndex {
 
     /// Write the index to an output buffer
     pub fn write_bytes<W: Write>(&self, writer: &mut W) -> Result<()> {
-        self.header.write_bytes(writer)?;
+        let header = IndexHeader {
+            records: Some(self.num_records() as u64),
+            ..self.header
+        };
+        header.write_bytes(writer)?;
         let mut writer = Encoder::new(writer, 3)?.auto_finish();
         self.write_range(&mut writer)?;
         writer.flush()?;
@@ -453,6 +647,10 @@ impl BlockIndex {
     /// This method is used internally to write the block ranges to the embedded index.
     /// It can also be used to serialize an index to any destination that implements `Write`.
     ///
+    /// If the header has flag summaries, each range is followed by the OR and AND of the
+    /// flags of its block (`u64::MAX` and 0 for ranges without a summary, which never
+    /// excludes a flag).
+    ///
     /// # Parameters
     ///
     /// * `writer` - The destination to write the block ranges to
@@ -465,7 +663,17 @@ impl BlockIndex {
         self.ranges
             .iter()
             .filter(|range| range.block_records > 0)
-            .try_for_each(|range| -> Result<()> { range.write_bytes(writer) })
+            .try_for_each(|range| -> Result<()> {
+                range.write_bytes(writer)?;
+                if self.header.flag_summary {
+                    let (flag_or, flag_and) = range.flag_summary.unwrap_or((u64::MAX, 0));
+                    let mut buf = [0; SIZE_FLAG_SUMMARY];
+                    LittleEndian::write_u64(&mut buf[0..8], flag_or);
+                    LittleEndian::write_u64(&mut buf[8..16], flag_and);
+                    writer.write_all(&buf)?;
+                }
+                Ok(())
+            })
     }
 
     /// Adds a block range to the index
@@ -522,43 +730,64 @@ impl BlockIndex {
     pub fn from_vbq<P: AsRef<Path>>(path: P) -> Result<Self> {
         let file = File::open(path)?;
         let mmap = unsafe { memmap2::Mmap::map(&file)? };
-        let file_size = mmap.len();
+        Self::scan(&*mmap, mmap.len() as u64)
+    }
 
-        // Read header from mapped memory (unused but checks for validity)
+    /// Creates a new index by scanning the block headers of VBQ data without an embedded index
+    ///
+    /// The first `data_len` bytes of `source` must contain the file header followed by data
+    /// blocks only.
+    pub(crate) fn scan<S: ReadAt + ?Sized>(source: &S, data_len: u64) -> Result<Self> {
+        // Read the file header (unused but checks for validity)
+        if data_len < SIZE_HEADER as u64 {
+            return Err(HeaderError::InvalidSize(data_len as usize, SIZE_HEADER).into());
+        }
         let _header = {
             let mut header_bytes = [0u8; SIZE_HEADER];
-            header_bytes.copy_from_slice(&mmap[..SIZE_HEADER]);
+            source.read_exact_at(&mut header_bytes, 0)?;
             FileHeader::from_bytes(&header_bytes)?
         };
 
         // Initialize position after the header
-        let mut pos = SIZE_HEADER;
+        let mut pos = SIZE_HEADER as u64;
 
         // Initialize the collection
-        let index_header = IndexHeader::new(file_size as u64);
+        let index_header = IndexHeader::new(data_len);
         let mut index = BlockIndex::new(index_header);
 
         // Find all block headers
         let mut record_total = 0;
-        while pos < mmap.len() {
+        while pos + SIZE_BLOCK_HEADER as u64 <= data_len {
             let block_header = {
                 let mut header_bytes = [0u8; SIZE_BLOCK_HEADER];
-                header_bytes.copy_from_slice(&mmap[pos..pos + SIZE_BLOCK_HEADER]);
-                BlockHeader::from_bytes(&header_bytes)?
+                source.read_exact_at(&mut header_bytes, pos)?;
+                BlockHeader::from_bytes_at(&header_bytes, pos as usize)?
             };
             index.add_range(BlockRange::new(
-                pos as u64,
+                pos,
                 block_header.size,
                 block_header.records,
                 record_total,
             ));
-            pos += SIZE_BLOCK_HEADER + block_header.size as usize;
+            pos = pos
+                .saturating_add(SIZE_BLOCK_HEADER as u64)
+                .saturating_add(block_header.size);
             record_total += u64::from(block_header.records);
         }
 
         Ok(index)
     }
 
+    /// Loads an index stored in a standalone file
+    ///
+    /// This reads legacy `.vqi` sidecar indices written alongside VBQ files before the index
+    /// was embedded (pre-v0.7.0). The sidecar contains the same bytes as an embedded index,
+    /// without the trailing size and magic number.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let bytes = std::fs::read(path)?;
+        Self::from_bytes(&bytes)
+    }
+
     pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
         let index_header = IndexHeader::from_bytes(bytes)?;
         let buffer = {
@@ -568,13 +797,21 @@ impl BlockIndex {
             buffer
         };
 
+        let entry_size = if index_header.flag_summary {
+            SIZE_BLOCK_RANGE + SIZE_FLAG_SUMMARY
+        } else {
+            SIZE_BLOCK_RANGE
+        };
         let mut ranges = Self::new(index_header);
-        let mut pos = 0;
-        while pos < buffer.len() {
-            let bound = pos + SIZE_BLOCK_RANGE;
-            let range = BlockRange::from_bytes(&buffer[pos..bound]);
+        for entry in buffer.chunks_exact(entry_size) {
+            let mut range = BlockRange::from_bytes(&entry[..SIZE_BLOCK_RANGE]);
+            if index_header.flag_summary {
+                range = range.with_flag_summary(
+                    LittleEndian::read_u64(&entry[SIZE_BLOCK_RANGE..SIZE_BLOCK_RANGE + 8]),
+                    LittleEndian::read_u64(&entry[SIZE_BLOCK_RANGE + 8..]),
+                );
+            }
             ranges.add_range(range);
-            pos += SIZE_BLOCK_RANGE;
         }
 
         Ok(ranges)
@@ -610,6 +847,42 @@ impl BlockIndex {
         &self.ranges
     }
 
+    /// Returns a multi-line summary of the index
+    ///
+    /// Lists the number of blocks and records, and the minimum, mean and maximum records and
+    /// bytes per block. For a sparse index the statistics are per index entry.
+    ///
+    /// ```text
+    /// blocks: 2
+    /// records: 8
+    /// block records: min 3, mean 4.0, max 5
+    /// block sizes: min 64 B, mean 96.0 B, max 128 B
+    /// ```
+    #[must_use]
+    pub fn summary(&self) -> String {
+        let mut lines = vec![
+            format!("blocks: {}", self.n_blocks()),
+            format!("records: {}", self.num_records()),
+        ];
+        if self.is_sparse() {
+            lines.push(format!("stride: {}", self.stride()));
+        }
+        if let Some((min, mean, max)) =
+            min_mean_max(self.ranges.iter().map(|r| u64::from(r.block_records)))
+        {
+            lines.push(format!(
+                "block records: min {min}, mean {mean:.1}, max {max}"
+            ));
+        }
+        if let Some((min, mean, max)) = min_mean_max(self.ranges.iter().map(|r| r.len)) {
+            lines.push(format!(
+                "block sizes: min {min} B, mean {mean:.1} B, max {max} B"
+            ));
+        }
+        lines.join("\n")
+    }
+
+    #[deprecated(note = "use `summary` instead")]
     pub fn pprint(&self) {
         self.ranges.iter().for_each(|range| {
             println!(
@@ -619,15 +892,508 @@ impl BlockIndex {
         });
     }
 
+    /// Exports the index as CSV, one row per block range
+    ///
+    /// The columns are `block_idx,start_offset,len,block_records,cumulative_records`,
+    /// followed by `flag_or,flag_and` if the index has flag summaries. The fields of the
+    /// index header are written as leading `# key=value` comment lines, so that
+    /// [`from_csv`](Self::from_csv) restores an index with the same binary serialization.
+    ///
+    /// ```text
+    /// # bytes=1114
+    /// # stride=1
+    /// block_idx,start_offset,len,block_records,cumulative_records
+    /// 0,32,512,10,0
+    /// 1,576,512,10,10
+    /// ```
+    #[must_use]
+    pub fn to_csv(&self) -> String {
+        self.to_text(',')
+    }
+
+    /// Exports the index as TSV, see [`to_csv`](Self::to_csv)
+    #[must_use]
+    pub fn to_tsv(&self) -> String {
+        self.to_text('\t')
+    }
+
+    /// Parses an index exported by [`to_csv`](Self::to_csv)
+    ///
+    /// Missing header comments default to a dense index covering the data up to the end of
+    /// the last block.
+    ///
+    /// # Errors
+    ///
+    /// * `IndexError::InvalidText` - If a line is malformed, the columns do not match the
+    ///   export format, or the block indices are not consecutive
+    pub fn from_csv(s: &str) -> Result<Self> {
+        Self::from_text(s, ',')
+    }
+
+    /// Parses an index exported by [`to_tsv`](Self::to_tsv), see [`from_csv`](Self::from_csv)
+    pub fn from_tsv(s: &str) -> Result<Self> {
+        Self::from_text(s, '\t')
+    }
+
+    /// Exports the index as text with `sep`-separated columns
+    fn to_text(&self, sep: char) -> String {
+        use std::fmt::Write as _;
+
+        let mut out = String::new();
+        let _ = writeln!(out, "# bytes={}", self.header.bytes);
+        if let Some(records) = self.header.records {
+            let _ = writeln!(out, "# records={records}");
+        }
+        let _ = writeln!(out, "# stride={}", self.header.stride);
+
+        let mut columns = TEXT_COLUMNS.to_vec();
+        if self.header.flag_summary {
+            columns.extend(TEXT_SUMMARY_COLUMNS);
+        }
+        let _ = writeln!(out, "{}", columns.join(&sep.to_string()));
+
+        for (idx, range) in self.ranges.iter().enumerate() {
+            let _ = write!(
+                out,
+                "{idx}{sep}{}{sep}{}{sep}{}{sep}{}",
+                range.start_offset, range.len, range.block_records, range.cumulative_records
+            );
+            if self.header.flag_summary {
+                let (flag_or, flag_and) = range.flag_summary.unwrap_or((u64::MAX, 0));
+                let _ = write!(out, "{sep}{flag_or}{sep}{flag_and}");
+            }
+            out.push('\n');
+        }
+        out
+    }
+
+    /// Parses an index from text with `sep`-separated columns
+    fn from_text(s: &str, sep: char) -> Result<Self> {
+        let invalid = |line: usize, reason: &'static str| IndexError::InvalidText {
+            line: line + 1,
+            reason,
+        };
+        let (mut bytes, mut records, mut stride) = (None, None, 1);
+        let mut flag_summary = None;
+        let mut ranges = Vec::new();
+        for (line_idx, line) in s.lines().enumerate() {
+            let line = line.trim_end_matches('\r');
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            // Header fields
+            if let Some(comment) = line.strip_prefix('#') {
+                let Some((key, value)) = comment.trim().split_once('=') else {
+                    continue;
+                };
+                let value = value
+                    .trim()
+                    .parse::<u64>()
+                    .map_err(|_| invalid(line_idx, "invalid header value"))?;
+                match key.trim() {
+                    "bytes" => bytes = Some(value),
+                    "records" => records = Some(value),
+                    "stride" => {
+                        stride = u32::try_from(value)
+                            .ok()
+                            .filter(|&stride| stride > 0)
+                            .ok_or_else(|| invalid(line_idx, "invalid stride"))?;
+                    }
+                    _ => {}
+                }
+                continue;
+            }
+
+            // Column names
+            let Some(has_summary) = flag_summary else {
+                let columns: Vec<&str> = line.split(sep).map(str::trim).collect();
+                let has_summary = columns.len() == TEXT_COLUMNS.len() + TEXT_SUMMARY_COLUMNS.len();
+                let expected = TEXT_COLUMNS.iter().chain(TEXT_SUMMARY_COLUMNS.iter());
+                if columns.len() < TEXT_COLUMNS.len()
+                    || (columns.len() > TEXT_COLUMNS.len() && !has_summary)
+                    || !columns.iter().zip(expected).all(|(a, b)| a == b)
+                {
+                    return Err(invalid(line_idx, "unexpected columns").into());
+                }
+                flag_summary = Some(has_summary);
+                continue;
+            };
+
+            // Block ranges
+            let fields = line
+                .split(sep)
+                .map(|field| field.trim().parse::<u64>())
+                .collect::<std::result::Result<Vec<_>, _>>()
+                .map_err(|_| invalid(line_idx, "invalid number"))?;
+            let n_columns = TEXT_COLUMNS.len() + if has_summary { 2 } else { 0 };
+            if fields.len() != n_columns {
+                return Err(invalid(line_idx, "wrong number of fields").into());
+            }
+            if fields[0] != ranges.len() as u64 {
+                return Err(invalid(line_idx, "block indices are not consecutive").into());
+            }
+            let block_records = u32::try_from(fields[3])
+                .map_err(|_| invalid(line_idx, "block record count exceeds u32"))?;
+            let mut range = BlockRange::new(fields[1], fields[2], block_records, fields[4]);
+            if has_summary {
+                range = range.with_flag_summary(fields[5], fields[6]);
+            }
+            ranges.push(range);
+        }
+
+        let bytes = bytes.unwrap_or_else(|| {
+            ranges.last().map_or(SIZE_HEADER as u64, |range| {
+                range.start_offset + SIZE_BLOCK_HEADER as u64 + range.len
+            })
+        });
+        Ok(Self {
+            header: IndexHeader {
+                records,
+                stride,
+                ..IndexHeader::new(bytes)
+            }
+            .with_flag_summary(flag_summary.unwrap_or(false)),
+            ranges,
+        })
+    }
+
     /// Returns the total number of records in the dataset
     #[must_use]
     pub fn num_records(&self) -> usize {
+        if let Some(records) = self.header.records {
+            return records as usize;
+        }
         self.ranges
             .iter()
             .next_back()
-            .map(|r| (r.cumulative_records + u64::from(r.block_records)) as usize)
+            .map(|r| r.record_global_range().end as usize)
             .unwrap_or_default()
     }
+
+    /// Returns the number of blocks per index entry (1 for a dense index)
+    #[must_use]
+    pub fn stride(&self) -> usize {
+        self.header.stride()
+    }
+
+    /// Returns true if the index only has an entry for every [`stride`](Self::stride)-th block
+    #[must_use]
+    pub fn is_sparse(&self) -> bool {
+        self.stride() > 1
+    }
+
+    /// Returns a sparse copy of this dense index keeping an entry for every `stride`-th block
+    ///
+    /// Empty blocks are not indexed. A stride of 0 or 1 keeps every entry.
+    #[must_use]
+    pub fn to_sparse(&self, stride: usize) -> Self {
+        let stride = stride.max(1);
+        Self {
+            header: IndexHeader {
+                records: Some(self.num_records() as u64),
+                stride: stride as u32,
+                ..self.header
+            },
+            ranges: self
+                .ranges
+                .iter()
+                .filter(|range| range.block_records > 0)
+                .step_by(stride)
+                .copied()
+                .collect(),
+        }
+    }
+
+    /// Merges the index of a VBQ segment appended after the data described by this index
+    ///
+    /// Used when independently written segments are concatenated into a single file. The
+    /// ranges of `other` are shifted by `byte_offset` bytes and `record_offset` records,
+    /// i.e. its block at `start_offset` is found at `start_offset + byte_offset` in the
+    /// merged file. When the segment's file header is dropped during concatenation,
+    /// `byte_offset` is the end of this data minus the size of the file header.
+    ///
+    /// The merged index keeps the header of `self`, with the data size and record count
+    /// updated to the end of the shifted segment.
+    #[must_use]
+    pub fn merge(&self, other: &BlockIndex, byte_offset: u64, record_offset: u64) -> Self {
+        let mut ranges = self.ranges.clone();
+        ranges.extend(other.ranges.iter().map(|range| BlockRange {
+            start_offset: range.start_offset + byte_offset,
+            cumulative_records: range.cumulative_records + record_offset,
+            ..*range
+        }));
+        Self {
+            header: IndexHeader {
+                bytes: other.header.bytes + byte_offset,
+                records: Some(record_offset + other.num_records() as u64),
+                ..self.header
+            },
+            ranges,
+        }
+    }
+
+    /// Merges the indices of consecutive VBQ segments into a single index
+    ///
+    /// Each entry is an index with its byte and record offsets in the merged file, as in
+    /// [`merge`](Self::merge). Entries must be in file order. The merged index keeps the
+    /// header of the first index, and is empty if `indices` is empty.
+    #[must_use]
+    pub fn merge_all(indices: &[(BlockIndex, u64, u64)]) -> Self {
+        let header = indices
+            .first()
+            .map_or_else(|| IndexHeader::new(0), |(index, ..)| index.header);
+        indices.iter().fold(
+            Self::new(header),
+            |merged, (index, byte_offset, record_offset)| {
+                merged.merge(index, *byte_offset, *record_offset)
+            },
+        )
+    }
+
+    /// Rebuilds a dense index from a sparse index by scanning the block headers of `source`
+    ///
+    /// `source` must contain the VBQ file described by this index.
+    pub(crate) fn expand<S: ReadAt + ?Sized>(&self, source: &S) -> Result<Self> {
+        let data_len = self.header.bytes;
+        if data_len > source.size() {
+            return Err(IndexError::ByteSizeMismatch(data_len, source.size()).into());
+        }
+        let mut index = Self::scan(source, data_len)?;
+        index.ranges.retain(|range| range.block_records > 0);
+
+        // Only the indexed blocks have a flag summary
+        for range in &mut index.ranges {
+            if let Ok(pos) = self
+                .ranges
+                .binary_search_by_key(&range.start_offset, |entry| entry.start_offset)
+            {
+                range.flag_summary = self.ranges[pos].flag_summary;
+            }
+        }
+        index.header = IndexHeader {
+            stride: 1,
+            ..self.header
+        };
+        Ok(index)
+    }
+
+    /// Cross-checks this index against the block headers of `source`
+    ///
+    /// The first `data_end` bytes of `source` must contain the file header followed by the
+    /// data blocks, without the embedded index. With a `sample_rate` of 1 (or more) the block
+    /// headers are walked from the start of the data and every block is compared with its
+    /// index entry. With a lower rate only that fraction of the index entries is checked, by
+    /// reading the block header at the offset each entry claims; extra blocks are not counted
+    /// in this mode.
+    pub(crate) fn validate<S: ReadAt + ?Sized>(
+        &self,
+        source: &S,
+        data_end: u64,
+        sample_rate: f32,
+    ) -> IndexValidationReport {
+        if sample_rate < 1.0 {
+            return self.validate_sampled(source, data_end, sample_rate);
+        }
+
+        // Walk the block headers until the end of the data or the first invalid header
+        let mut blocks = Vec::new();
+        let mut pos = SIZE_HEADER as u64;
+        let mut record_total = 0;
+        while let Some(block) = read_block_at(source, data_end, pos, record_total) {
+            pos += SIZE_BLOCK_HEADER as u64 + block.len;
+            record_total += u64::from(block.block_records);
+            blocks.push(block);
+        }
+
+        // Sparse indices only have an entry for every `stride`-th non-empty block
+        if self.is_sparse() {
+            blocks = blocks
+                .into_iter()
+                .filter(|block| block.block_records > 0)
+                .step_by(self.stride())
+                .collect();
+        }
+
+        let mut report = IndexValidationReport {
+            extra_blocks_in_file: blocks.len().saturating_sub(self.ranges.len()),
+            extra_blocks_in_index: self.ranges.len().saturating_sub(blocks.len()),
+            ..IndexValidationReport::default()
+        };
+        for (idx, (range, block)) in self.ranges.iter().zip(&blocks).enumerate() {
+            report.blocks_checked += 1;
+            if !same_block(range, block) {
+                report.blocks_mismatched.push((
+                    idx,
+                    MismatchDetail {
+                        index: *range,
+                        file: Some(*block),
+                    },
+                ));
+            }
+        }
+        report
+    }
+
+    /// Checks a `sample_rate` fraction of the entries against the block at their offset
+    fn validate_sampled<S: ReadAt + ?Sized>(
+        &self,
+        source: &S,
+        data_end: u64,
+        sample_rate: f32,
+    ) -> IndexValidationReport {
+        let mut report = IndexValidationReport::default();
+
+        // Entries are picked at an even spacing, starting with the first
+        let mut credit = 1.0;
+        for (idx, range) in self.ranges.iter().enumerate() {
+            if credit < 1.0 {
+                credit += sample_rate;
+                continue;
+            }
+            credit += sample_rate - 1.0;
+
+            report.blocks_checked += 1;
+            let block = Some(range.start_offset)
+                .filter(|&pos| pos >= SIZE_HEADER as u64)
+                .and_then(|pos| read_block_at(source, data_end, pos, range.cumulative_records));
+            if !block.is_some_and(|block| same_block(range, &block)) {
+                report.blocks_mismatched.push((
+                    idx,
+                    MismatchDetail {
+                        index: *range,
+                        file: block,
+                    },
+                ));
+            }
+        }
+        report
+    }
+
+    /// Finds the block containing the record at `record_idx`
+    ///
+    /// For sparse indices the nearest preceding entry is found by binary search, followed by
+    /// a forward scan over the block headers in `bytes` up to the next entry. `bytes` must
+    /// contain the VBQ file described by this index.
+    ///
+    /// Returns `None` if `record_idx` is beyond the number of records in the file.
+    pub fn find_record(&self, bytes: &[u8], record_idx: usize) -> Result<Option<BlockRange>> {
+        self.find_record_in(bytes, record_idx)
+    }
+
+    /// Finds the block containing the record at `record_idx`, reading block headers from
+    /// `source`
+    ///
+    /// See [`find_record`](Self::find_record).
+    pub(crate) fn find_record_in<S: ReadAt + ?Sized>(
+        &self,
+        source: &S,
+        record_idx: usize,
+    ) -> Result<Option<BlockRange>> {
+        if record_idx >= self.num_records() {
+            return Ok(None);
+        }
+        let contains = |range: &BlockRange| range.record_global_range().end as usize > record_idx;
+
+        // Nearest entry starting at or before the record
+        let pos = self
+            .ranges
+            .partition_point(|range| range.cumulative_records as usize <= record_idx);
+        let Some(mut range) = pos.checked_sub(1).map(|pos| self.ranges[pos]) else {
+            return Ok(None);
+        };
+
+        // Blocks between this entry and the next one are found through their headers
+        let scan_end = self
+            .ranges
+            .get(pos)
+            .map_or(self.header.bytes, |next| next.start_offset)
+            .min(source.size());
+        while !contains(&range) {
+            let pos = range.start_offset + range.len + SIZE_BLOCK_HEADER as u64;
+            if pos + SIZE_BLOCK_HEADER as u64 > scan_end {
+                return Ok(None);
+            }
+            let mut header_bytes = [0u8; SIZE_BLOCK_HEADER];
+            source.read_exact_at(&mut header_bytes, pos)?;
+            let block_header = BlockHeader::from_bytes_at(&header_bytes, pos as usize)?;
+            range = BlockRange::new(
+                pos,
+                block_header.size,
+                block_header.records,
+                range.cumulative_records + u64::from(range.block_records),
+            );
+        }
+        Ok(Some(range))
+    }
+}
+
+/// Reads the block starting at byte `pos` of `source`
+///
+/// Returns `None` if no valid block header is found at `pos`, or if the block extends past
+/// `data_end`.
+fn read_block_at<S: ReadAt + ?Sized>(
+    source: &S,
+    data_end: u64,
+    pos: u64,
+    cumulative_records: u64,
+) -> Option<BlockRange> {
+    let data_start = pos.checked_add(SIZE_BLOCK_HEADER as u64)?;
+    if data_start > data_end {
+        return None;
+    }
+    let mut header_bytes = [0u8; SIZE_BLOCK_HEADER];
+    source.read_exact_at(&mut header_bytes, pos).ok()?;
+    let header = BlockHeader::from_bytes_at(&header_bytes, pos as usize).ok()?;
+    let end = data_start.checked_add(header.size)?;
+    (end <= data_end).then(|| BlockRange::new(pos, header.size, header.records, cumulative_records))
+}
+
+/// Returns true if an index entry describes the block read from the file
+fn same_block(range: &BlockRange, block: &BlockRange) -> bool {
+    range.start_offset == block.start_offset
+        && range.len == block.len
+        && range.block_records == block.block_records
+        && range.cumulative_records == block.cumulative_records
+}
+
+/// Result of cross-checking a block index against the blocks of its file
+///
+/// Created by [`MmapReader::validate_index`](super::MmapReader::validate_index).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IndexValidationReport {
+    /// Number of index entries compared with a block of the file
+    pub blocks_checked: usize,
+
+    /// Index entries that do not match the block of the file, by entry position
+    pub blocks_mismatched: Vec<(usize, MismatchDetail)>,
+
+    /// Number of blocks in the file past the last index entry
+    pub extra_blocks_in_file: usize,
+
+    /// Number of index entries past the last block of the file
+    pub extra_blocks_in_index: usize,
+}
+impl IndexValidationReport {
+    /// Returns true if no mismatches or extra blocks were found
+    #[must_use]
+    pub fn is_valid(&self) -> bool {
+        self.blocks_mismatched.is_empty()
+            && self.extra_blocks_in_file == 0
+            && self.extra_blocks_in_index == 0
+    }
+}
+
+/// An index entry that does not match the file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MismatchDetail {
+    /// The entry as claimed by the index
+    pub index: BlockRange,
+
+    /// The block found in the file, or `None` if there is no valid block at the claimed offset
+    ///
+    /// When the whole file is walked, this is the block at the entry's position in the file.
+    pub file: Option<BlockRange>,
 }
 
 #[cfg(test)]
@@ -653,6 +1419,31 @@ mod tests {
         std::fs::write(path, buffer).unwrap();
     }
 
+    // ==================== BlockRange record index Tests ====================
+
+    #[test]
+    fn test_block_range_record_indices() {
+        let range = BlockRange::new(1024, 8192, 100, 5000);
+        assert_eq!(range.record_global_range(), 5000..5100);
+
+        // first and last record of the block
+        assert!(range.contains_record(5000));
+        assert_eq!(range.record_local_index(5000), Some(0));
+        assert!(range.contains_record(5099));
+        assert_eq!(range.record_local_index(5099), Some(99));
+
+        // records of the neighbouring blocks
+        for record_idx in [0, 4999, 5100, u64::MAX] {
+            assert!(!range.contains_record(record_idx));
+            assert_eq!(range.record_local_index(record_idx), None);
+        }
+
+        // an empty block contains no records
+        let empty = BlockRange::new(1024, 0, 0, 5000);
+        assert!(empty.record_global_range().is_empty());
+        assert!(!empty.contains_record(5000));
+    }
+
     // ==================== BlockIndex::from_vbq Tests ====================
 
     #[test]
@@ -687,6 +1478,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(deprecated)]
     fn test_pprint() {
         let path = "test_index_pprint.vbq";
         write_raw_vbq_file(path, &[(32, 1)]);
@@ -697,6 +1489,36 @@ mod tests {
         index.pprint();
     }
 
+    #[test]
+    fn test_summary() {
+        let path = "test_index_summary.vbq";
+        write_raw_vbq_file(path, &[(64, 3), (128, 5), (96, 4)]);
+
+        let index = BlockIndex::from_vbq(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(
+            index.summary(),
+            "blocks: 3\nrecords: 12\nblock records: min 3, mean 4.0, max 5\nblock sizes: min 64 B, mean 96.0 B, max 128 B"
+        );
+
+        let empty = BlockIndex::new(IndexHeader::new(0));
+        assert_eq!(empty.summary(), "blocks: 0\nrecords: 0");
+    }
+
+    #[test]
+    fn test_block_range_display_and_debug() {
+        let range = BlockRange::new(32, 512, 10, 20);
+        assert_eq!(
+            range.to_string(),
+            "BlockRange { offset: 32, len: 512 B, records: 10, cumulative: 20 }"
+        );
+        let debug = format!("{range:?}");
+        assert!(!debug.contains("reservation"));
+        assert!(!debug.contains("flag_or"));
+        let debug = format!("{:?}", range.with_flag_summary(0b110, 0b010));
+        assert!(debug.contains("flag_or: 0x6, flag_and: 0x2"));
+    }
+
     #[test]
     fn test_num_records_empty_index() {
         let index = BlockIndex::new(IndexHeader::new(0));
@@ -752,4 +1574,214 @@ mod tests {
         index.write_range(&mut buffer).unwrap();
         assert_eq!(buffer.len(), SIZE_BLOCK_RANGE);
     }
+
+    #[test]
+    fn test_index_header_legacy_reserved_bytes() {
+        let mut buffer = [42u8; INDEX_HEADER_SIZE];
+        LittleEndian::write_u64(&mut buffer[0..8], INDEX_MAGIC);
+        LittleEndian::write_u64(&mut buffer[8..16], 777);
+        let parsed = IndexHeader::from_bytes(&buffer).unwrap();
+        assert_eq!(parsed.bytes(), 777);
+        assert_eq!(parsed.records(), None);
+        assert_eq!(parsed.stride(), 1);
+    }
+
+    #[test]
+    fn test_sparse_index_roundtrip() {
+        let mut index = BlockIndex::new(IndexHeader::new(999));
+        for block in 0..10u64 {
+            index.add_range(BlockRange::new(32 + block * 100, 68, 3, block * 3));
+        }
+
+        let sparse = index.to_sparse(4);
+        assert!(sparse.is_sparse());
+        assert_eq!(sparse.n_blocks(), 3);
+        assert_eq!(sparse.num_records(), 30);
+
+        let mut buffer = Vec::new();
+        sparse.write_bytes(&mut buffer).unwrap();
+        let header = IndexHeader::from_bytes(&buffer).unwrap();
+        assert_eq!(header.records(), Some(30));
+        assert_eq!(header.stride(), 4);
+
+        let parsed = BlockIndex::from_bytes(&buffer).unwrap();
+        assert_eq!(parsed.stride(), 4);
+        assert_eq!(parsed.num_records(), 30);
+        assert_eq!(parsed.ranges(), sparse.ranges());
+    }
+
+    #[test]
+    fn test_find_record_dense() {
+        let mut index = BlockIndex::new(IndexHeader::new(0));
+        index.add_range(BlockRange::new(32, 100, 5, 0));
+        index.add_range(BlockRange::new(164, 200, 7, 5));
+
+        assert_eq!(index.find_record(&[], 0).unwrap(), Some(index.ranges()[0]));
+        assert_eq!(index.find_record(&[], 4).unwrap(), Some(index.ranges()[0]));
+        assert_eq!(index.find_record(&[], 5).unwrap(), Some(index.ranges()[1]));
+        assert_eq!(index.find_record(&[], 11).unwrap(), Some(index.ranges()[1]));
+        assert_eq!(index.find_record(&[], 12).unwrap(), None);
+    }
+
+    #[test]
+    fn test_merge_all_matches_concatenated_index() -> Result<()> {
+        use crate::{SequencingRecordBuilder, vbq};
+
+        // Writes records `range` into an in-memory segment with small blocks
+        let write_segment = |range: std::ops::Range<usize>| -> Result<Vec<u8>> {
+            let mut bytes = Vec::new();
+            let header = vbq::FileHeaderBuilder::new().block(256).build();
+            let mut writer = vbq::WriterBuilder::default()
+                .header(header)
+                .build(&mut bytes)?;
+            for idx in range {
+                let seq = b"ACGTTGCA".repeat(1 + idx % 5);
+                writer.push(SequencingRecordBuilder::default().s_seq(&seq).build()?)?;
+            }
+            writer.finish()?;
+            drop(writer);
+            Ok(bytes)
+        };
+        let (first, second) = (write_segment(0..300)?, write_segment(300..700)?);
+        let first_index = vbq::MmapReader::from_bytes(first.clone())?.load_index()?;
+        let second_index = vbq::MmapReader::from_bytes(second.clone())?.load_index()?;
+        assert!(first_index.n_blocks() > 1 && second_index.n_blocks() > 1);
+
+        // Concatenate the data blocks, dropping the file header of the second segment
+        let first_end = first_index.header.bytes() as usize;
+        let mut concatenated = first[..first_end].to_vec();
+        concatenated.extend_from_slice(&second[SIZE_HEADER..second_index.header.bytes() as usize]);
+        let expected = BlockIndex::scan(&concatenated)?;
+
+        let merged = BlockIndex::merge_all(&[
+            (first_index.clone(), 0, 0),
+            (
+                second_index,
+                (first_end - SIZE_HEADER) as u64,
+                first_index.num_records() as u64,
+            ),
+        ]);
+        assert_eq!(merged.ranges(), expected.ranges());
+        assert_eq!(merged.num_records(), 700);
+        assert_eq!(merged.header.bytes(), concatenated.len() as u64);
+
+        // The merged index can be embedded and used to read the concatenated file
+        let mut index_bytes = Vec::new();
+        merged.write_bytes(&mut index_bytes)?;
+        concatenated.extend_from_slice(&index_bytes);
+        concatenated.extend_from_slice(&(index_bytes.len() as u64).to_le_bytes());
+        concatenated.extend_from_slice(&INDEX_END_MAGIC.to_le_bytes());
+        let reader = vbq::MmapReader::from_bytes(concatenated)?;
+        assert_eq!(reader.num_records()?, 700);
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge_all_empty() {
+        let merged = BlockIndex::merge_all(&[]);
+        assert_eq!(merged.n_blocks(), 0);
+        assert_eq!(merged.num_records(), 0);
+    }
+
+    /// Builds an index of `n_blocks` consecutive blocks
+    fn text_test_index(n_blocks: usize, flag_summary: bool) -> BlockIndex {
+        let mut index = BlockIndex::new(IndexHeader::new(0).with_flag_summary(flag_summary));
+        let (mut offset, mut cumulative) = (SIZE_HEADER as u64, 0);
+        for idx in 0..n_blocks as u64 {
+            let block_records = 100 + idx as u32;
+            let mut range = BlockRange::new(offset, 4096 + idx, block_records, cumulative);
+            if flag_summary {
+                range = range.with_flag_summary(idx | 8, idx & 8);
+            }
+            index.ranges.push(range);
+            offset += SIZE_BLOCK_HEADER as u64 + 4096 + idx;
+            cumulative += u64::from(block_records);
+        }
+        index.header.bytes = offset;
+        index
+    }
+
+    fn index_bytes(index: &BlockIndex) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        index.write_bytes(&mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn test_text_roundtrip() {
+        for n_blocks in [10, 1, 0] {
+            for flag_summary in [false, true] {
+                let index = text_test_index(n_blocks, flag_summary);
+                for (text, parsed) in [
+                    (index.to_csv(), BlockIndex::from_csv(&index.to_csv())),
+                    (index.to_tsv(), BlockIndex::from_tsv(&index.to_tsv())),
+                ] {
+                    let parsed = parsed.unwrap();
+                    assert_eq!(text.lines().count(), 3 + n_blocks);
+                    assert_eq!(parsed.ranges(), index.ranges());
+                    assert_eq!(parsed.header.bytes(), index.header.bytes());
+                    assert_eq!(parsed.header.has_flag_summary(), flag_summary);
+                    assert_eq!(parsed.num_records(), index.num_records());
+                    assert_eq!(index_bytes(&parsed), index_bytes(&index));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_text_columns() {
+        let csv = text_test_index(2, false).to_csv();
+        let mut rows = csv.lines().filter(|line| !line.starts_with('#'));
+        assert_eq!(
+            rows.next(),
+            Some("block_idx,start_offset,len,block_records,cumulative_records")
+        );
+        assert_eq!(rows.next(), Some("0,32,4096,100,0"));
+        assert_eq!(rows.next(), Some("1,4160,4097,101,100"));
+        assert_eq!(rows.next(), None);
+
+        let tsv = text_test_index(1, true).to_tsv();
+        assert!(tsv.contains("cumulative_records\tflag_or\tflag_and\n"));
+    }
+
+    #[test]
+    fn test_text_sparse_header() {
+        let sparse = text_test_index(10, false).to_sparse(3);
+        let parsed = BlockIndex::from_csv(&sparse.to_csv()).unwrap();
+        assert_eq!(parsed.stride(), 3);
+        assert_eq!(parsed.num_records(), sparse.num_records());
+        assert_eq!(index_bytes(&parsed), index_bytes(&sparse));
+    }
+
+    #[test]
+    fn test_text_without_header_comments() {
+        let csv = "block_idx,start_offset,len,block_records,cumulative_records\n\
+                   0,32,100,5,0\n\
+                   1,164,50,2,5\n";
+        let index = BlockIndex::from_csv(csv).unwrap();
+        assert_eq!(index.n_blocks(), 2);
+        assert_eq!(index.num_records(), 7);
+        assert_eq!(index.header.bytes(), 164 + SIZE_BLOCK_HEADER as u64 + 50);
+        assert!(!index.is_sparse());
+    }
+
+    #[test]
+    fn test_text_invalid() {
+        let header = "block_idx,start_offset,len,block_records,cumulative_records\n";
+        for (text, line) in [
+            ("block_idx,start_offset\n".to_string(), 1),
+            (format!("{header}0,32,100,5\n"), 2),
+            (format!("{header}0,32,100,5,x\n"), 2),
+            (format!("{header}0,32,100,5,0\n2,164,50,2,5\n"), 3),
+            (format!("{header}0,32,100,{},0\n", u64::MAX), 2),
+            (format!("# stride=0\n{header}"), 1),
+        ] {
+            match BlockIndex::from_csv(&text) {
+                Err(crate::Error::IndexError(IndexError::InvalidText { line: got, .. })) => {
+                    assert_eq!(got, line, "{text}");
+                }
+                other => panic!("expected an invalid text error for {text:?}, got {other:?}"),
+            }
+        }
+    }
 }
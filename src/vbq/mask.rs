@@ -0,0 +1,85 @@
+//! Soft-mask bitmaps of VBQ sequences
+//!
+//! Nucleotides are encoded case-insensitively, so files with the `masked` header flag store
+//! the case of each sequence separately: a bitmap of `ceil(len / 8)` bytes where bit `i % 8`
+//! of byte `i / 8` is set if base `i` is lowercase.
+
+/// Number of bytes in the soft-mask bitmap of a sequence of `len` nucleotides
+pub(super) fn mask_len(len: usize) -> usize {
+    len.div_ceil(8)
+}
+
+/// Appends the uppercased `seq` to `upper` and its soft-mask bitmap to `mask`
+pub(super) fn split_case(seq: &[u8], upper: &mut Vec<u8>, mask: &mut Vec<u8>) {
+    upper.extend(seq.iter().map(u8::to_ascii_uppercase));
+    mask.extend(seq.chunks(8).map(|chunk| {
+        chunk
+            .iter()
+            .enumerate()
+            .filter(|(_, base)| base.is_ascii_lowercase())
+            .fold(0u8, |byte, (bit, _)| byte | (1 << bit))
+    }));
+}
+
+/// Reusable buffers for the uppercased sequences and soft-mask bitmaps of a record
+#[derive(Clone, Default)]
+pub(super) struct CaseSplitter {
+    pub(super) s_seq: Vec<u8>,
+    pub(super) s_mask: Vec<u8>,
+    pub(super) x_seq: Vec<u8>,
+    pub(super) x_mask: Vec<u8>,
+}
+impl CaseSplitter {
+    /// Splits the sequences of a record into their uppercased bases and soft-mask bitmaps
+    pub(super) fn split(&mut self, s_seq: &[u8], x_seq: &[u8]) {
+        self.s_seq.clear();
+        self.s_mask.clear();
+        self.x_seq.clear();
+        self.x_mask.clear();
+        split_case(s_seq, &mut self.s_seq, &mut self.s_mask);
+        split_case(x_seq, &mut self.x_seq, &mut self.x_mask);
+    }
+}
+
+/// Lowercases the bases of a decoded sequence marked in its soft-mask bitmap
+///
+/// See [`RefRecord::smask`](crate::vbq::RefRecord::smask) and
+/// [`RefRecord::xmask`](crate::vbq::RefRecord::xmask). Bits beyond the end of `seq` are
+/// ignored.
+///
+/// # Examples
+///
+/// ```rust
+/// use binseq::vbq::apply_soft_mask;
+///
+/// let mut seq = b"ACGTACGTAC".to_vec();
+/// apply_soft_mask(&mut seq, &[0b0000_0110, 0b0000_0010]);
+/// assert_eq!(seq, b"AcgTACGTAc");
+/// ```
+pub fn apply_soft_mask(seq: &mut [u8], mask: &[u8]) {
+    for (chunk, &byte) in seq.chunks_mut(8).zip(mask) {
+        for (bit, base) in chunk.iter_mut().enumerate() {
+            if byte & (1 << bit) != 0 {
+                base.make_ascii_lowercase();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_case_roundtrip() {
+        let seq = b"ACgtnNacGTTTaaaacccGG";
+        let (mut upper, mut mask) = (Vec::new(), Vec::new());
+        split_case(seq, &mut upper, &mut mask);
+        assert_eq!(upper, seq.to_ascii_uppercase());
+        assert_eq!(mask.len(), mask_len(seq.len()));
+        assert_eq!(mask[0], 0b1101_1100);
+
+        apply_soft_mask(&mut upper, &mask);
+        assert_eq!(upper, seq);
+    }
+}
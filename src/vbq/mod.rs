@@ -68,6 +68,10 @@
 //! * Extended header length (8 bytes, if paired and `headers` flag set)
 //! * Extended header data (UTF-8 string, if paired and `headers` flag set)
 //!
+//! Files with the [`header_prefix`](FileHeader::header_prefix) flag store the longest common
+//! prefix of the headers of a block once, after a marker byte at the start of the block. Each
+//! header is then stored as a variable-length size followed by the bytes after the prefix.
+//!
 //! ## Recent Format Changes (v0.7.0+)
 //!
 //! * **Embedded Index**: Index data is now stored within the VBQ file itself, eliminating
@@ -135,22 +139,45 @@
 //! while reader.read_block_into(&mut block).unwrap() {
 //!     for record in block.iter() {
 //!         record.decode_s(&mut seq_buffer).unwrap();
-//!         let header = record.sheader();
-//!         println!("Header: {}", std::str::from_utf8(header).unwrap());
+//!         println!("Header: {}", record.sheader_str().unwrap_or("<invalid UTF-8>"));
 //!         println!("Sequence: {}", std::str::from_utf8(&seq_buffer).unwrap());
-//!         println!("Quality: {}", std::str::from_utf8(record.squal()).unwrap());
+//!         println!("Quality: {}", record.squal_str().unwrap_or("<invalid UTF-8>"));
 //!         seq_buffer.clear();
 //!     }
 //! }
 //! # std::fs::remove_file("example.vbq").unwrap_or(());
 //! ```
 
+mod checkpoint;
+mod convert;
 mod header;
 mod index;
+mod mask;
+mod pair;
+#[cfg(feature = "rayon")]
+mod par_iter;
+mod parallel_writer;
+mod prefix;
 mod reader;
+mod rewrite;
+mod sharded;
 mod writer;
 
-pub use header::{BlockHeader, FILE_MAGIC, FileHeader, FileHeaderBuilder};
-pub use index::{BlockIndex, BlockRange};
-pub use reader::{MmapReader, RecordBlock, RecordBlockIter, RefRecord};
-pub use writer::{Writer, WriterBuilder};
+pub use checkpoint::WriterCheckpoint;
+pub use convert::convert_bitsize;
+pub(crate) use header::BLOCK_MAGIC;
+pub use header::{BlockHeader, FILE_MAGIC, FileHeader, FileHeaderBuilder, IncompatibilityReason};
+pub(crate) use index::INDEX_MAGIC;
+pub use index::{BlockIndex, BlockRange, IndexValidationReport, MismatchDetail};
+pub use mask::apply_soft_mask;
+pub use pair::{PairOptions, PairStats, pair_files};
+#[cfg(feature = "rayon")]
+pub use par_iter::ParIterBuilder;
+pub use parallel_writer::{ParallelWriter, ParallelWriterHandle};
+pub use reader::{BlockRangeIter, FlagFilter, MmapReader, RecordBlock, RecordBlockIter, RefRecord};
+pub use rewrite::rewrite_headers;
+pub use sharded::{ShardedWriter, ShardedWriterBuilder};
+pub use writer::{
+    BlockFlushCallback, BlockFlushInfo, EncodedRecord, OnOversize, WriteStats, Writer,
+    WriterBuilder,
+};
@@ -0,0 +1,303 @@
+//! Combining single-end VBQ files into a paired VBQ file
+//!
+//! Records are copied in their encoded form, so no nucleotide decoding or re-encoding
+//! takes place.
+
+use std::io::Write;
+use std::path::Path;
+
+use super::{FileHeaderBuilder, MmapReader, RecordBlock, WriterBuilder};
+use crate::error::{Result, WriteError};
+
+/// Options for [`pair_files`]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PairOptions {
+    /// Allow quality scores to be dropped if only one of the inputs has them
+    ///
+    /// If false (default), a mismatch in quality scores between the inputs is an error.
+    pub allow_drop_qual: bool,
+
+    /// Allow sequence headers to be dropped if only one of the inputs has them
+    ///
+    /// If false (default), a mismatch in sequence headers between the inputs is an error.
+    pub allow_drop_headers: bool,
+}
+
+/// Summary of a completed [`pair_files`] run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PairStats {
+    /// Number of paired records written
+    pub records: usize,
+
+    /// Whether the output file contains quality scores
+    pub qual: bool,
+
+    /// Whether the output file contains sequence headers
+    pub headers: bool,
+}
+
+/// Combines two single-end VBQ files into a single paired VBQ file
+///
+/// Both inputs are read sequentially in lockstep and each pair of records is written to
+/// `out` by copying the encoded sequence words, quality scores, and headers directly (see
+/// [`Writer::write_encoded_paired_record`](super::Writer::write_encoded_paired_record)).
+///
+/// The output uses the block size, compression, and flags of `r1`. It contains quality
+/// scores and headers only if both inputs do.
+///
+/// # Errors
+///
+/// * `WriteError::RecordCountMismatch` - If the inputs contain different numbers of records
+/// * `WriteError::PairCapabilityMismatch` - If only one input has quality scores (or headers)
+///   and dropping them was not allowed in `opts`
+/// * `WriteError::PairedFlagSet` - If either input is already paired
+/// * `WriteError::IncompatibleBitsizes` - If the inputs use different bitsizes
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use binseq::vbq::{pair_files, PairOptions};
+/// use std::fs::File;
+///
+/// let out = File::create("paired.vbq").unwrap();
+/// let stats = pair_files("r1.vbq", "r2.vbq", out, PairOptions::default()).unwrap();
+/// println!("Wrote {} paired records", stats.records);
+/// ```
+pub fn pair_files<P1, P2, W>(r1: P1, r2: P2, out: W, opts: PairOptions) -> Result<PairStats>
+where
+    P1: AsRef<Path>,
+    P2: AsRef<Path>,
+    W: Write,
+{
+    let mut r1 = MmapReader::new(r1)?;
+    let mut r2 = MmapReader::new(r2)?;
+    let (h1, h2) = (r1.header(), r2.header());
+
+    if h1.paired || h2.paired {
+        return Err(WriteError::PairedFlagSet.into());
+    }
+    if h1.bits != h2.bits {
+        return Err(WriteError::IncompatibleBitsizes(h1.bits, h2.bits).into());
+    }
+    let qual = shared_capability("qual", h1.qual, h2.qual, opts.allow_drop_qual)?;
+    let headers = shared_capability("headers", h1.headers, h2.headers, opts.allow_drop_headers)?;
+
+    let (n1, n2) = (r1.num_records()?, r2.num_records()?);
+    if n1 != n2 {
+        return Err(WriteError::RecordCountMismatch(n1, n2).into());
+    }
+
+    let header = FileHeaderBuilder::new()
+        .block(h1.block)
+        .compressed(h1.compressed)
+        .bitsize(h1.bits)
+        .flags(h1.flags)
+        .qual(qual)
+        .headers(headers)
+        .paired(true)
+        .build();
+    let mut writer = WriterBuilder::default().header(header).build(out)?;
+
+    let mut b1 = r1.new_block();
+    let mut b2 = r2.new_block();
+    let (mut p1, mut p2) = (0, 0);
+    let mut records = 0;
+    while records < n1 {
+        if !next_nonempty_block(&mut r1, &mut b1, &mut p1)?
+            || !next_nonempty_block(&mut r2, &mut b2, &mut p2)?
+        {
+            // Index and block contents disagree
+            return Err(WriteError::RecordCountMismatch(n1, n2).into());
+        }
+
+        // Pair as many records as are available in both current blocks
+        let n_pairs = (b1.n_records() - p1).min(b2.n_records() - p2);
+        for (primary, extended) in b1.iter().skip(p1).zip(b2.iter().skip(p2)).take(n_pairs) {
+            writer.write_encoded_paired_record(&primary, &extended)?;
+        }
+        p1 += n_pairs;
+        p2 += n_pairs;
+        records += n_pairs;
+    }
+    writer.finish()?;
+
+    Ok(PairStats {
+        records,
+        qual,
+        headers,
+    })
+}
+
+/// Determines whether the output can carry a capability given both inputs
+fn shared_capability(
+    attribute: &'static str,
+    r1: bool,
+    r2: bool,
+    allow_drop: bool,
+) -> Result<bool> {
+    if r1 != r2 && !allow_drop {
+        return Err(WriteError::PairCapabilityMismatch { attribute, r1, r2 }.into());
+    }
+    Ok(r1 && r2)
+}
+
+/// Ensures `block` has unconsumed records, reading the next non-empty block if needed
+///
+/// Returns false if the reader is exhausted.
+fn next_nonempty_block(
+    reader: &mut MmapReader,
+    block: &mut RecordBlock,
+    pos: &mut usize,
+) -> Result<bool> {
+    while *pos >= block.n_records() {
+        if !reader.read_block_into(block)? {
+            return Ok(false);
+        }
+        *pos = 0;
+    }
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+
+    use super::*;
+    use crate::vbq::FileHeader;
+    use crate::{BinseqRecord, SequencingRecordBuilder};
+
+    fn write_single_end(path: &Path, header: FileHeader, seqs: &[Vec<u8>]) {
+        let mut writer = WriterBuilder::default()
+            .header(header)
+            .build(File::create(path).unwrap())
+            .unwrap();
+        for (idx, seq) in seqs.iter().enumerate() {
+            let qual = vec![b'!' + (idx % 40) as u8; seq.len()];
+            let sheader = format!("read_{idx}");
+            let record = SequencingRecordBuilder::default()
+                .s_seq(seq)
+                .s_qual(&qual)
+                .s_header(sheader.as_bytes())
+                .flag(idx as u64)
+                .build()
+                .unwrap();
+            writer.push(record).unwrap();
+        }
+        writer.finish().unwrap();
+    }
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("binseq_test_pair_{name}.vbq"))
+    }
+
+    fn r1_seqs(n: usize) -> Vec<Vec<u8>> {
+        (0..n).map(|i| b"ACGT".repeat(5 + i % 7)).collect()
+    }
+
+    fn r2_seqs(n: usize) -> Vec<Vec<u8>> {
+        (0..n).map(|i| b"TTGCA".repeat(3 + i % 11)).collect()
+    }
+
+    #[test]
+    fn test_pair_files() {
+        let (p1, p2, out) = (temp_path("r1"), temp_path("r2"), temp_path("out"));
+        let n = 500;
+        let (s1, s2) = (r1_seqs(n), r2_seqs(n));
+        let header = FileHeaderBuilder::new()
+            .block(2048)
+            .qual(true)
+            .headers(true)
+            .flags(true)
+            .build();
+        write_single_end(&p1, header, &s1);
+
+        // Use a different block size for R2 so blocks do not line up
+        let header = FileHeaderBuilder::new()
+            .block(4096)
+            .qual(true)
+            .headers(true)
+            .compressed(true)
+            .build();
+        write_single_end(&p2, header, &s2);
+
+        let stats = pair_files(
+            &p1,
+            &p2,
+            File::create(&out).unwrap(),
+            PairOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            stats,
+            PairStats {
+                records: n,
+                qual: true,
+                headers: true
+            }
+        );
+
+        let mut reader = MmapReader::new(&out).unwrap();
+        assert!(reader.is_paired());
+        assert_eq!(reader.num_records().unwrap(), n);
+        let mut block = reader.new_block();
+        let mut idx = 0;
+        while reader.read_block_into(&mut block).unwrap() {
+            for record in block.iter() {
+                assert_eq!(record.decode_s_alloc().unwrap(), s1[idx]);
+                assert_eq!(record.decode_x_alloc().unwrap(), s2[idx]);
+                assert_eq!(record.flag(), Some(idx as u64));
+                assert_eq!(record.sheader(), format!("read_{idx}").as_bytes());
+                assert_eq!(record.xheader(), format!("read_{idx}").as_bytes());
+                assert_eq!(record.squal().len(), s1[idx].len());
+                assert_eq!(record.xqual().len(), s2[idx].len());
+                idx += 1;
+            }
+        }
+        assert_eq!(idx, n);
+
+        for path in [p1, p2, out] {
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_pair_files_record_count_mismatch() {
+        let (p1, p2) = (temp_path("count_r1"), temp_path("count_r2"));
+        let header = FileHeaderBuilder::new().qual(true).headers(true).build();
+        let s1 = r1_seqs(10);
+        let s2 = r2_seqs(12);
+        write_single_end(&p1, header, &s1);
+        write_single_end(&p2, header, &s2);
+
+        let err = pair_files(&p1, &p2, Vec::new(), PairOptions::default()).unwrap_err();
+        let msg = format!("{err}");
+        assert!(msg.contains("10") && msg.contains("12"));
+
+        for path in [p1, p2] {
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_pair_files_quality_mismatch() {
+        let (p1, p2) = (temp_path("qual_r1"), temp_path("qual_r2"));
+        let s1 = r1_seqs(10);
+        let s2 = r2_seqs(10);
+        write_single_end(&p1, FileHeaderBuilder::new().qual(true).build(), &s1);
+        write_single_end(&p2, FileHeaderBuilder::new().build(), &s2);
+
+        assert!(pair_files(&p1, &p2, Vec::new(), PairOptions::default()).is_err());
+
+        let opts = PairOptions {
+            allow_drop_qual: true,
+            ..PairOptions::default()
+        };
+        let stats = pair_files(&p1, &p2, Vec::new(), opts).unwrap();
+        assert_eq!(stats.records, 10);
+        assert!(!stats.qual);
+
+        for path in [p1, p2] {
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+}
@@ -0,0 +1,143 @@
+//! Rayon parallel iterators over VBQ records
+//!
+//! Enabled with the `rayon` feature. Blocks are the unit of parallelism: each rayon task
+//! decodes one block into [`OwnedRecord`]s.
+
+use std::sync::Arc;
+
+use rayon::prelude::*;
+
+use super::MmapReader;
+use crate::{OwnedRecord, Result};
+
+/// Builder for a rayon [`ParallelIterator`] over all records of a VBQ file
+///
+/// By default blocks are visited in file order, so collecting the iterator yields the
+/// records in the same order as a sequential read. Disabling ordering schedules the
+/// largest blocks first, which shortens the tail of the computation when block sizes vary,
+/// at the cost of a non-deterministic record order.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use binseq::vbq::{MmapReader, ParIterBuilder};
+/// use rayon::prelude::*;
+///
+/// let reader = MmapReader::new("example.vbq").unwrap();
+/// let n_records = ParIterBuilder::new(reader)
+///     .with_order(false)
+///     .build()
+///     .filter_map(|record| record.ok())
+///     .count();
+/// ```
+pub struct ParIterBuilder {
+    /// Reader over the file to iterate
+    reader: MmapReader,
+
+    /// Whether blocks are visited in file order
+    ordered: bool,
+}
+impl ParIterBuilder {
+    /// Creates a builder for an ordered parallel iterator over the records of `reader`
+    #[must_use]
+    pub fn new(reader: MmapReader) -> Self {
+        Self {
+            reader,
+            ordered: true,
+        }
+    }
+
+    /// Sets whether blocks are visited in file order (default: `true`)
+    #[must_use]
+    pub fn with_order(mut self, ordered: bool) -> Self {
+        self.ordered = ordered;
+        self
+    }
+
+    /// Builds the parallel iterator
+    ///
+    /// Errors while loading the block index or decoding a block are yielded as items.
+    pub fn build(self) -> impl ParallelIterator<Item = Result<OwnedRecord>> {
+        let (mut ranges, error) = match self.reader.index() {
+            Ok(index) => (index.ranges().to_vec(), None),
+            Err(e) => (Vec::new(), Some(e)),
+        };
+        if !self.ordered {
+            ranges.sort_unstable_by_key(|range| std::cmp::Reverse(range.len));
+        }
+
+        let reader = Arc::new(self.reader);
+        let records = ranges.into_par_iter().flat_map_iter(move |range| {
+            match reader.owned_block_records(&range) {
+                Ok(records) => records.into_iter().map(Ok).collect(),
+                Err(e) => vec![Err(e)],
+            }
+        });
+        error.into_par_iter().map(Err).chain(records)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BinseqRecord;
+
+    const TEST_VBQ_FILE: &str = "./data/subset.vbq";
+
+    #[test]
+    fn test_into_par_iter_count() {
+        let num_records = MmapReader::new(TEST_VBQ_FILE)
+            .unwrap()
+            .num_records()
+            .unwrap();
+        let reader = MmapReader::new(TEST_VBQ_FILE).unwrap();
+        assert_eq!(reader.into_par_iter().count(), num_records);
+    }
+
+    #[test]
+    fn test_par_iter_order() {
+        let reader = MmapReader::new(TEST_VBQ_FILE).unwrap();
+        let indices: Vec<u64> = reader
+            .into_par_iter()
+            .map(|record| record.unwrap().index())
+            .collect();
+        assert!(indices.windows(2).all(|w| w[0] + 1 == w[1]));
+
+        let reader = MmapReader::new(TEST_VBQ_FILE).unwrap();
+        let mut unordered: Vec<u64> = ParIterBuilder::new(reader)
+            .with_order(false)
+            .build()
+            .map(|record| record.unwrap().index())
+            .collect();
+        unordered.sort_unstable();
+        assert_eq!(unordered, indices);
+    }
+
+    #[test]
+    fn test_iter_blocks_par() {
+        let reader = MmapReader::new(TEST_VBQ_FILE).unwrap();
+        let num_records = reader.num_records().unwrap();
+        let count = reader
+            .iter_blocks_par()
+            .flat_map(|block| {
+                let block = block.unwrap();
+                block.iter().map(OwnedRecord::from).collect::<Vec<_>>()
+            })
+            .count();
+        assert_eq!(count, num_records);
+
+        // records keep their order within each block
+        let mut indices: Vec<Vec<u64>> = reader
+            .iter_blocks_par()
+            .map(|block| block.unwrap().iter().map(|r| r.index()).collect())
+            .collect();
+        assert!(
+            indices
+                .iter()
+                .all(|block| block.windows(2).all(|w| w[0] + 1 == w[1]))
+        );
+        indices.sort_unstable();
+        let flat: Vec<u64> = indices.into_iter().flatten().collect();
+        assert_eq!(flat, (0..num_records as u64).collect::<Vec<_>>());
+    }
+}
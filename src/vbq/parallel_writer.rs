@@ -0,0 +1,241 @@
+//! Multi-producer single-consumer writing for VBQ files
+//!
+//! A [`ParallelWriter`] owns the output and a dedicated I/O thread. Worker threads encode and
+//! compress records into in-memory [`Writer<Vec<u8>>`](Writer) chunks and send them to the I/O
+//! thread through a [`ParallelWriterHandle`], where they are ingested into the output as they
+//! arrive.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use binseq::vbq::{FileHeaderBuilder, ParallelWriter};
+//! use binseq::SequencingRecordBuilder;
+//! use std::fs::File;
+//!
+//! let header = FileHeaderBuilder::new().compressed(true).build();
+//! let writer = ParallelWriter::new(File::create("output.vbq").unwrap(), header).unwrap();
+//!
+//! std::thread::scope(|scope| {
+//!     for _ in 0..4 {
+//!         let handle = writer.handle();
+//!         scope.spawn(move || {
+//!             let mut chunk = handle.new_chunk().unwrap();
+//!             let record = SequencingRecordBuilder::default()
+//!                 .s_seq(b"ACGTACGT")
+//!                 .build()
+//!                 .unwrap();
+//!             chunk.push(record).unwrap();
+//!             handle.send_chunk(chunk).unwrap();
+//!         });
+//!     }
+//! });
+//!
+//! writer.finish().unwrap();
+//! ```
+
+use std::io::Write;
+use std::sync::mpsc::{Receiver, Sender, channel};
+use std::thread::JoinHandle;
+
+use super::{FileHeader, Writer, WriterBuilder};
+use crate::error::{Result, WriteError};
+
+/// A VBQ writer that ingests in-memory chunks from many threads on a dedicated I/O thread
+///
+/// Chunks are ingested in the order they are received. Records within a chunk keep their
+/// order, so records sent from a single thread appear in the output in the order they were
+/// sent.
+pub struct ParallelWriter {
+    /// Sending side of the chunk channel
+    sender: Sender<Writer<Vec<u8>>>,
+
+    /// The I/O thread owning the output writer
+    thread: JoinHandle<Result<()>>,
+
+    /// Header shared by the output and all chunks
+    header: FileHeader,
+}
+impl ParallelWriter {
+    /// Creates a new parallel writer and spawns its I/O thread
+    ///
+    /// The file header is written to `inner` immediately.
+    pub fn new<W: Write + Send + 'static>(inner: W, header: FileHeader) -> Result<Self> {
+        let writer = WriterBuilder::default().header(header).build(inner)?;
+        let (sender, receiver) = channel();
+        let thread = std::thread::spawn(move || drain_chunks(writer, &receiver));
+        Ok(Self {
+            sender,
+            thread,
+            header,
+        })
+    }
+
+    /// Returns the header of the output file
+    #[must_use]
+    pub fn header(&self) -> FileHeader {
+        self.header
+    }
+
+    /// Creates a new handle for sending chunks to the I/O thread
+    #[must_use]
+    pub fn handle(&self) -> ParallelWriterHandle {
+        ParallelWriterHandle {
+            sender: self.sender.clone(),
+            header: self.header,
+        }
+    }
+
+    /// Closes the channel, waits for all chunks to be ingested, and finishes the output
+    ///
+    /// This blocks until every [`ParallelWriterHandle`] has been dropped.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first error encountered by the I/O thread while ingesting chunks
+    /// or finishing the output.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the I/O thread panicked.
+    pub fn finish(self) -> Result<()> {
+        drop(self.sender);
+        self.thread.join().unwrap()
+    }
+}
+
+/// A cloneable handle for sending chunks to a [`ParallelWriter`]
+#[derive(Clone)]
+pub struct ParallelWriterHandle {
+    sender: Sender<Writer<Vec<u8>>>,
+    header: FileHeader,
+}
+impl ParallelWriterHandle {
+    /// Creates an empty headless in-memory chunk matching the output header
+    pub fn new_chunk(&self) -> Result<Writer<Vec<u8>>> {
+        WriterBuilder::default()
+            .header(self.header)
+            .headless(true)
+            .build(Vec::new())
+    }
+
+    /// Sends a chunk to the I/O thread to be ingested into the output
+    ///
+    /// Chunks must be headless and share the output header (see [`new_chunk`](Self::new_chunk)).
+    ///
+    /// # Errors
+    ///
+    /// Returns `WriteError::ChannelClosed` if the I/O thread has stopped, which happens
+    /// when it encountered an error. The error itself is reported by
+    /// [`ParallelWriter::finish`].
+    pub fn send_chunk(&self, chunk: Writer<Vec<u8>>) -> Result<()> {
+        self.sender
+            .send(chunk)
+            .map_err(|_| WriteError::ChannelClosed.into())
+    }
+}
+
+/// Ingests chunks into the output until all senders are dropped
+fn drain_chunks<W: Write>(
+    mut writer: Writer<W>,
+    receiver: &Receiver<Writer<Vec<u8>>>,
+) -> Result<()> {
+    for mut chunk in receiver {
+        writer.ingest(&mut chunk)?;
+    }
+    writer.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+
+    use super::*;
+    use crate::vbq::{FileHeaderBuilder, MmapReader};
+    use crate::{BinseqRecord, SequencingRecordBuilder};
+
+    #[test]
+    fn test_parallel_writer() -> Result<()> {
+        let path = std::env::temp_dir().join("binseq_test_parallel_writer.vbq");
+        let header = FileHeaderBuilder::new()
+            .block(4096)
+            .flags(true)
+            .compressed(true)
+            .build();
+        let writer = ParallelWriter::new(File::create(&path)?, header)?;
+
+        let n_threads = 4;
+        let n_records = 1000;
+        let chunk_size = 250;
+        std::thread::scope(|scope| -> Result<()> {
+            let producers: Vec<_> = (0..n_threads)
+                .map(|tid| {
+                    let handle = writer.handle();
+                    scope.spawn(move || -> Result<()> {
+                        let seq = b"ACGT".repeat(tid + 1);
+                        let mut chunk = handle.new_chunk()?;
+                        for idx in 0..n_records {
+                            let record = SequencingRecordBuilder::default()
+                                .s_seq(&seq)
+                                .flag((tid * n_records + idx) as u64)
+                                .build()?;
+                            chunk.push(record)?;
+                            if (idx + 1) % chunk_size == 0 {
+                                handle.send_chunk(chunk)?;
+                                chunk = handle.new_chunk()?;
+                            }
+                        }
+                        Ok(())
+                    })
+                })
+                .collect();
+            for producer in producers {
+                producer.join().unwrap()?;
+            }
+            Ok(())
+        })?;
+        writer.finish()?;
+
+        let mut reader = MmapReader::new(&path)?;
+        assert_eq!(reader.num_records()?, n_threads * n_records);
+
+        let mut block = reader.new_block();
+        let mut last_seen = vec![None; n_threads];
+        let mut total = 0;
+        while reader.read_block_into(&mut block)? {
+            for record in block.iter() {
+                let flag = record.flag().unwrap() as usize;
+                let (tid, idx) = (flag / n_records, flag % n_records);
+                assert_eq!(record.slen() as usize, 4 * (tid + 1));
+
+                // Records from each thread appear in the order they were written
+                if let Some(prev) = last_seen[tid] {
+                    assert_eq!(idx, prev + 1);
+                } else {
+                    assert_eq!(idx, 0);
+                }
+                last_seen[tid] = Some(idx);
+                total += 1;
+            }
+        }
+        assert_eq!(total, n_threads * n_records);
+
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_parallel_writer_rejects_mismatched_chunk() -> Result<()> {
+        let writer = ParallelWriter::new(Vec::new(), FileHeaderBuilder::new().build())?;
+        let handle = writer.handle();
+        let chunk = WriterBuilder::default()
+            .header(FileHeaderBuilder::new().qual(true).build())
+            .headless(true)
+            .build(Vec::new())?;
+
+        // The send may or may not succeed depending on timing, but finishing must fail
+        let _ = handle.send_chunk(chunk);
+        drop(handle);
+        assert!(writer.finish().is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,195 @@
+//! Per-block prefixes of VBQ sequence headers
+//!
+//! Headers of a run are often long and nearly identical (instrument, run and flowcell
+//! followed by the tile and coordinates). Files with the
+//! [`header_prefix`](super::FileHeader::header_prefix) flag store the longest common prefix
+//! of the headers of a block once, at the start of the block:
+//!
+//! ```text
+//! marker (1 byte) | prefix length (varint) | prefix | records ...
+//! ```
+//!
+//! In the records, each header is stored as the varint length of its suffix followed by the
+//! suffix, instead of an 8-byte length followed by the full header. Varints are LEB128:
+//! 7 bits per byte, least significant first, with the high bit set on all but the last byte.
+//!
+//! The writer assembles blocks with full headers, so blocks can be ingested and checkpointed
+//! as usual, and rewrites them when they are flushed.
+
+use std::ops::Range;
+
+use bitnuc::BitSize;
+use byteorder::{ByteOrder, LittleEndian};
+
+use super::mask::mask_len;
+use super::reader::encoded_sequence_len;
+
+/// First byte of a block storing a header prefix
+pub(super) const PREFIX_MARKER: u8 = b'P';
+
+/// Appends `value` as a varint to `buf`
+pub(super) fn write_varint(buf: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buf.push(value.to_le_bytes()[0] | 0x80);
+        value >>= 7;
+    }
+    buf.push(value.to_le_bytes()[0]);
+}
+
+/// Reads a varint from the start of `bytes`, returning its value and encoded length
+///
+/// Returns `None` if `bytes` ends within the varint or its value exceeds 64 bits.
+pub(super) fn read_varint(bytes: &[u8]) -> Option<(u64, usize)> {
+    let mut value = 0u64;
+    let mut shift = 0u32;
+    for (idx, &byte) in bytes.iter().enumerate().take(10) {
+        let bits = u64::from(byte & 0x7f);
+        if shift == 63 && bits > 1 {
+            return None;
+        }
+        value |= bits << shift;
+        if byte & 0x80 == 0 {
+            return Some((value, idx + 1));
+        }
+        shift += 7;
+    }
+    None
+}
+
+/// The fields of the records of a block, as far as needed to locate their headers
+#[derive(Debug, Clone, Copy)]
+pub(super) struct RecordLayout {
+    pub(super) bits: BitSize,
+    pub(super) flags: bool,
+    pub(super) qual: bool,
+    pub(super) mask: bool,
+}
+impl RecordLayout {
+    /// Number of bytes of the encoded sequence, quality scores and soft-mask of `len` bases
+    fn body_len(&self, len: u64) -> usize {
+        let mut n_bytes = 8 * encoded_sequence_len(len, self.bits) as usize;
+        if self.qual {
+            n_bytes += len as usize;
+        }
+        if self.mask {
+            n_bytes += mask_len(len as usize);
+        }
+        n_bytes
+    }
+
+    /// Returns the span of the header (including its 8-byte length) starting at `pos`
+    fn header_span(ubuf: &[u8], pos: usize) -> Range<usize> {
+        let len = LittleEndian::read_u64(&ubuf[pos..pos + 8]) as usize;
+        pos..pos + 8 + len
+    }
+
+    /// Appends the spans of the headers of the record with full headers at `start`
+    fn push_header_spans(&self, ubuf: &[u8], start: usize, spans: &mut Vec<Range<usize>>) {
+        let mut pos = if self.flags { start + 8 } else { start };
+        let slen = LittleEndian::read_u64(&ubuf[pos..pos + 8]);
+        let xlen = LittleEndian::read_u64(&ubuf[pos + 8..pos + 16]);
+        pos += 16 + self.body_len(slen);
+        let sheader = Self::header_span(ubuf, pos);
+        pos = sheader.end + self.body_len(xlen);
+        spans.push(sheader);
+        if xlen > 0 {
+            spans.push(Self::header_span(ubuf, pos));
+        }
+    }
+}
+
+/// Rewrites a block with full headers into `out`, storing the common header prefix once
+///
+/// `ubuf` holds the records of the block, starting at `starts`, as written without header
+/// prefixes. The rewritten block is never larger than `ubuf` for headers below 2 MiB. For
+/// larger headers, the block falls back to an empty prefix if it would not fit into
+/// `block_size` bytes otherwise: this adds two bytes to the block but saves at least three
+/// per header, so the block always fits.
+pub(super) fn encode_block(
+    layout: RecordLayout,
+    ubuf: &[u8],
+    starts: &[usize],
+    block_size: usize,
+    out: &mut Vec<u8>,
+) {
+    let mut spans = Vec::with_capacity(starts.len());
+    for &start in starts {
+        layout.push_header_spans(ubuf, start, &mut spans);
+    }
+
+    let mut headers = spans.iter().map(|span| &ubuf[span.start + 8..span.end]);
+    let first = headers.next().unwrap_or_default();
+    let prefix_len = headers.fold(first.len(), |len, header| {
+        common_prefix_len(&first[..len], header)
+    });
+
+    write_block(ubuf, &spans, &first[..prefix_len], out);
+    if out.len() > block_size {
+        write_block(ubuf, &spans, &[], out);
+    }
+}
+
+/// Writes the records of `ubuf` with the headers at `spans` stored as suffixes of `prefix`
+fn write_block(ubuf: &[u8], spans: &[Range<usize>], prefix: &[u8], out: &mut Vec<u8>) {
+    out.clear();
+    out.push(PREFIX_MARKER);
+    write_varint(out, prefix.len() as u64);
+    out.extend_from_slice(prefix);
+
+    // Records are contiguous, so everything between two headers is copied as-is
+    let mut pos = 0;
+    for span in spans {
+        out.extend_from_slice(&ubuf[pos..span.start]);
+        let suffix = &ubuf[span.start + 8 + prefix.len()..span.end];
+        write_varint(out, suffix.len() as u64);
+        out.extend_from_slice(suffix);
+        pos = span.end;
+    }
+    out.extend_from_slice(&ubuf[pos..]);
+}
+
+/// Length of the longest common prefix of `a` and `b`
+fn common_prefix_len(a: &[u8], b: &[u8]) -> usize {
+    a.iter().zip(b).take_while(|(x, y)| x == y).count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_varint_round_trip() {
+        let mut buf = Vec::new();
+        for value in [
+            0,
+            1,
+            127,
+            128,
+            300,
+            16_383,
+            16_384,
+            u64::from(u32::MAX),
+            u64::MAX,
+        ] {
+            buf.clear();
+            write_varint(&mut buf, value);
+            assert_eq!(read_varint(&buf), Some((value, buf.len())));
+            assert_eq!(read_varint(&buf[..buf.len() - 1]), None);
+        }
+        assert_eq!(buf.len(), 10);
+
+        // Values beyond 64 bits are rejected
+        let mut overflow = vec![0xff; 9];
+        overflow.push(0x02);
+        assert_eq!(read_varint(&overflow), None);
+        assert_eq!(read_varint(&[0x80; 11]), None);
+    }
+
+    #[test]
+    fn test_common_prefix_len() {
+        assert_eq!(common_prefix_len(b"read:1", b"read:2"), 5);
+        assert_eq!(common_prefix_len(b"read", b"read:2"), 4);
+        assert_eq!(common_prefix_len(b"", b"read"), 0);
+        assert_eq!(common_prefix_len(&[0xff, 0xfe], &[0xff, 0x00]), 1);
+    }
+}
@@ -39,35 +39,41 @@
 //! while reader.read_block_into(&mut block).unwrap() {
 //!     for record in block.iter() {
 //!         let seq = record.sseq();
-//!         let header = record.sheader();
-//!         println!("Header: {}", std::str::from_utf8(header).unwrap());
+//!         println!("Header: {}", record.sheader_str().unwrap_or("<invalid UTF-8>"));
 //!         println!("Sequence: {}", std::str::from_utf8(seq).unwrap());
-//!         if !record.squal().is_empty() {
-//!             println!("Quality: {}", std::str::from_utf8(record.squal()).unwrap());
+//!         if let Some(mean) = record.mean_quality() {
+//!             println!("Mean quality: {mean:.1}");
 //!         }
 //!     }
 //! }
 //! ```
 
-use std::fs::File;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
 use std::ops::Range;
-use std::path::Path;
-use std::sync::Arc;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
 
 use bitnuc::BitSize;
-use byteorder::{ByteOrder, LittleEndian};
-use memmap2::Mmap;
+use byteorder::{ByteOrder, LittleEndian, WriteBytesExt};
 use zstd::zstd_safe;
 
 use super::{
-    BlockHeader, BlockIndex, BlockRange, FileHeader,
+    BlockHeader, BlockIndex, BlockRange, FileHeader, IndexValidationReport,
     header::{SIZE_BLOCK_HEADER, SIZE_HEADER},
+    mask::apply_soft_mask,
+    prefix::{PREFIX_MARKER, read_varint},
 };
 use crate::DEFAULT_QUALITY_SCORE;
 use crate::vbq::index::{INDEX_END_MAGIC, INDEX_HEADER_SIZE, IndexHeader};
 use crate::{
-    BinseqRecord, ParallelProcessor, ParallelReader,
-    error::{ReadError, Result},
+    BinseqRecord, CheckpointStore, Executor, OwnedRecord, ParallelProcessor, ParallelReader,
+    ScratchBuffers,
+    checkpoint::run_resumable,
+    error::{HeaderError, IndexError, ReadError, Result},
+    executor::{self, Job},
+    record::debug_record,
+    source::{ByteSource, FileSource, ReadAt},
 };
 
 /// Calculates the number of 64-bit words needed to store a nucleotide sequence of the given length
@@ -82,10 +88,10 @@ use crate::{
 /// # Returns
 ///
 /// The number of 64-bit words required to encode the sequence
-fn encoded_sequence_len(len: u64, bitsize: BitSize) -> usize {
+pub(super) fn encoded_sequence_len(len: u64, bitsize: BitSize) -> u64 {
     match bitsize {
-        BitSize::Two => len.div_ceil(32) as usize,
-        BitSize::Four => len.div_ceil(16) as usize,
+        BitSize::Two => len.div_ceil(32),
+        BitSize::Four => len.div_ceil(16),
     }
 }
 
@@ -111,6 +117,131 @@ impl Span {
     }
 }
 
+/// Bounds-checked reader over the data of a block
+struct BlockCursor<'a> {
+    /// Data of the block
+    bytes: &'a [u8],
+
+    /// Current position in the block
+    pos: usize,
+
+    /// Start of the record being parsed (reported in errors)
+    record_start: usize,
+}
+impl<'a> BlockCursor<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self {
+            bytes,
+            pos: 0,
+            record_start: 0,
+        }
+    }
+
+    /// Number of bytes left in the block
+    fn remaining(&self) -> usize {
+        self.bytes.len() - self.pos
+    }
+
+    /// Marks the current position as the start of a record
+    fn start_record(&mut self) {
+        self.record_start = self.pos;
+    }
+
+    /// Skips `len` bytes, returning their span
+    fn skip(&mut self, len: u64, reason: &'static str) -> Result<Span> {
+        match usize::try_from(len) {
+            Ok(len) if len <= self.remaining() => {
+                let span = Span::new(self.pos, len);
+                self.pos += len;
+                Ok(span)
+            }
+            _ => Err(ReadError::CorruptBlock {
+                offset: self.record_start,
+                reason,
+            }
+            .into()),
+        }
+    }
+
+    /// Reads a little-endian `u64`
+    fn read_u64(&mut self, reason: &'static str) -> Result<u64> {
+        let span = self.skip(8, reason)?;
+        Ok(LittleEndian::read_u64(span.slice(self.bytes)))
+    }
+
+    /// Reads a LEB128 varint
+    fn read_varint(&mut self, reason: &'static str) -> Result<u64> {
+        let Some((value, len)) = read_varint(&self.bytes[self.pos..]) else {
+            return Err(ReadError::CorruptBlock {
+                offset: self.record_start,
+                reason,
+            }
+            .into());
+        };
+        self.pos += len;
+        Ok(value)
+    }
+
+    /// Reads the header prefix at the start of a block
+    ///
+    /// Returns the span of the prefix.
+    fn read_header_prefix(&mut self) -> Result<Span> {
+        if self.read_u8("truncated header prefix")? != PREFIX_MARKER {
+            return Err(ReadError::CorruptBlock {
+                offset: 0,
+                reason: "missing header prefix marker",
+            }
+            .into());
+        }
+        let len = self.read_varint("truncated header prefix")?;
+        self.skip(len, "header prefix exceeds the block")
+    }
+
+    /// Reads a header stored as its suffix after `prefix`
+    ///
+    /// The full header is appended to `arena`, returning its span there.
+    fn read_prefixed_header(
+        &mut self,
+        prefix: Span,
+        arena: &mut Vec<u8>,
+        reason: &'static str,
+    ) -> Result<Span> {
+        let suffix_len = self.read_varint("truncated header length")?;
+        let suffix = self.skip(suffix_len, reason)?;
+        let span = Span::new(arena.len(), prefix.len + suffix.len);
+        arena.extend_from_slice(prefix.slice(self.bytes));
+        arena.extend_from_slice(suffix.slice(self.bytes));
+        Ok(span)
+    }
+
+    /// Reads a single byte
+    fn read_u8(&mut self, reason: &'static str) -> Result<u8> {
+        let span = self.skip(1, reason)?;
+        Ok(self.bytes[span.offset])
+    }
+
+    /// Reads the encoded words of a sequence of `len` nucleotides into `sequences`
+    ///
+    /// Returns the span of the words in `sequences`.
+    fn read_words(
+        &mut self,
+        sequences: &mut Vec<u64>,
+        len: u64,
+        bitsize: BitSize,
+        reason: &'static str,
+    ) -> Result<Span> {
+        let n_words = encoded_sequence_len(len, bitsize);
+        let span = self.skip(n_words.saturating_mul(8), reason)?;
+        let words = Span::new(sequences.len(), span.len / 8);
+        sequences.extend(
+            span.slice(self.bytes)
+                .chunks_exact(8)
+                .map(LittleEndian::read_u64),
+        );
+        Ok(words)
+    }
+}
+
 /// Metadata for a single record, storing spans into rbuf
 #[derive(Debug, Clone, Copy)]
 struct RecordMetadata {
@@ -121,17 +252,29 @@ struct RecordMetadata {
     // Spans for primary sequence
     s_seq_span: Span,    // Encoded sequence words (u64s) (into `.sequences` buffer)
     s_qual_span: Span,   // Quality bytes
-    s_header_span: Span, // Header bytes
+    s_mask_span: Span,   // Soft-mask bytes
+    s_header_span: Span, // Header bytes (into `.hbuf` with a header prefix)
 
     // Spans for extended sequence
     x_seq_span: Span,    // Encoded sequence words (u64s) (into `.sequences` buffer)
     x_qual_span: Span,   // Quality bytes
-    x_header_span: Span, // Header bytes
+    x_mask_span: Span,   // Soft-mask bytes
+    x_header_span: Span, // Header bytes (into `.hbuf` with a header prefix)
 
     /// Indicates whether the record has quality scores
     has_quality: bool,
+
+    /// Indicates whether the record has soft-mask bitmaps
+    has_mask: bool,
 }
 
+/// Largest zstd window log, accepted when decompressing blocks
+const MAX_WINDOW_LOG: u32 = if cfg!(target_pointer_width = "64") {
+    31
+} else {
+    30
+};
+
 /// A container for a block of VBQ records
 ///
 /// The `RecordBlock` struct represents a single block of records read from a VBQ file.
@@ -189,6 +332,15 @@ pub struct RecordBlock {
 
     /// Default quality score for the block
     default_quality_score: u8,
+
+    /// Whether the headers of the block are stored as suffixes of a common prefix
+    header_prefix: bool,
+
+    /// Reconstructed headers of the block (if stored with a prefix)
+    hbuf: Vec<u8>,
+
+    /// Reusable buffer for block data read from an unmapped file
+    fbuf: Vec<u8>,
 }
 impl RecordBlock {
     /// Creates a new empty `RecordBlock` with the specified block size
@@ -207,6 +359,11 @@ impl RecordBlock {
     /// A new empty `RecordBlock` instance
     #[must_use]
     pub fn new(bitsize: BitSize, block_size: usize) -> Self {
+        // Accept frames with any window, e.g. compressed with long-distance matching (this
+        // cannot fail for a window log within the bounds of zstd)
+        let mut dctx = zstd_safe::DCtx::create();
+        dctx.set_parameter(zstd_safe::DParameter::WindowLogMax(MAX_WINDOW_LOG))
+            .ok();
         Self {
             bitsize,
             index: 0,
@@ -215,12 +372,57 @@ impl RecordBlock {
             sequences: Vec::default(),
             rbuf: Vec::default(),
             dbuf: Vec::default(),
-            dctx: zstd_safe::DCtx::create(),
+            dctx,
             qbuf: Vec::default(),
             default_quality_score: DEFAULT_QUALITY_SCORE,
+            header_prefix: false,
+            hbuf: Vec::default(),
+            fbuf: Vec::default(),
         }
     }
 
+    /// Creates a new empty `RecordBlock` for the records of a file with the given header
+    fn for_header(header: &FileHeader) -> Self {
+        let mut block = Self::new(header.bits, header.block as usize);
+        block.header_prefix = header.header_prefix;
+        block
+    }
+
+    /// Creates a block from the raw (uncompressed) bytes of a VBQ block
+    ///
+    /// This parses block data produced outside of a file (e.g. received over the network)
+    /// without a [`MmapReader`]. `bytes` is the block data following its block header and
+    /// must be at most `header.block` bytes long; blocks need not be padded to the block
+    /// size. Record indices start at 0.
+    ///
+    /// # Errors
+    ///
+    /// * `ReadError::BlockSizeMismatch` - If `bytes` is longer than one block
+    /// * `ReadError::CorruptBlock` - If the records of the block are malformed
+    pub fn from_bytes(bytes: Vec<u8>, header: FileHeader) -> Result<Self> {
+        let mut block = Self::for_header(&header);
+        block.check_block_size(bytes.len())?;
+        block.rbuf = bytes;
+        block.parse_records(header.qual, header.headers, header.flags, header.masked)?;
+        Ok(block)
+    }
+
+    /// Creates a block from the zstd-compressed bytes of a VBQ block
+    ///
+    /// See [`from_bytes`](Self::from_bytes). `bytes` must decompress to at most
+    /// `header.block` bytes.
+    pub fn from_compressed_bytes(bytes: Vec<u8>, header: FileHeader) -> Result<Self> {
+        let mut block = Self::for_header(&header);
+        block.ingest_compressed_bytes(
+            &bytes,
+            header.qual,
+            header.headers,
+            header.flags,
+            header.masked,
+        )?;
+        Ok(block)
+    }
+
     /// Sets the default quality score for the block
     ///
     /// # Parameters
@@ -271,6 +473,22 @@ impl RecordBlock {
         RecordBlockIter::new(self)
     }
 
+    /// Returns owned copies of all records in this block
+    ///
+    /// Unlike the `RefRecord`s yielded by [`iter`](Self::iter), the owned records remain
+    /// valid after the block is cleared or refilled (e.g. by
+    /// [`read_block_into`](MmapReader::read_block_into)).
+    #[must_use]
+    pub fn to_vec_owned(&self) -> Vec<OwnedRecord> {
+        self.iter().map(OwnedRecord::from).collect()
+    }
+
+    /// Returns an owned copy of the first record in this block, or `None` if it is empty
+    #[must_use]
+    pub fn first_owned(&self) -> Option<OwnedRecord> {
+        self.iter().next().map(OwnedRecord::from)
+    }
+
     /// Updates the starting index of the block
     ///
     /// This is used internally to keep track of the global position of records
@@ -293,6 +511,7 @@ impl RecordBlock {
         self.records.clear();
         self.sequences.clear();
         self.dbuf.clear();
+        self.hbuf.clear();
         // Note: We keep rbuf allocated for reuse
         // Note: We keep qbuf allocated for reuse
     }
@@ -310,20 +529,65 @@ impl RecordBlock {
     /// * `bytes` - A slice of bytes containing the block data
     /// * `has_quality` - A boolean indicating whether the block contains quality scores
     /// * `has_header` - A boolean indicating whether the block contains headers
+    /// * `has_flags` - A boolean indicating whether the block contains flags
+    /// * `has_mask` - A boolean indicating whether the block contains soft-mask bitmaps
     fn ingest_bytes(
         &mut self,
         bytes: &[u8],
         has_quality: bool,
         has_header: bool,
         has_flags: bool,
+        has_mask: bool,
     ) -> Result<()> {
-        if bytes.len() != self.block_size {
-            return Err(ReadError::PartialRecord(bytes.len()).into());
-        }
+        self.check_block_size(bytes.len())?;
         self.rbuf.clear();
         self.rbuf.extend_from_slice(bytes);
-        self.parse_records(has_quality, has_header, has_flags);
-        Ok(())
+        self.parse_records(has_quality, has_header, has_flags, has_mask)
+    }
+
+    /// Reads the `len` bytes of block data at `offset` of `source` and ingests them
+    ///
+    /// Mapped and in-memory data is ingested in place, unmapped files are read into `fbuf`
+    /// first. Blocks extending past the end of the file and uncompressed blocks longer than
+    /// the block size are rejected before reading.
+    fn ingest_at<S: ReadAt + ?Sized>(
+        &mut self,
+        source: &S,
+        offset: u64,
+        len: usize,
+        header: &FileHeader,
+    ) -> Result<()> {
+        if offset
+            .checked_add(len as u64)
+            .is_none_or(|end| end > source.size())
+        {
+            return Err(ReadError::UnexpectedEndOfFile(offset as usize).into());
+        }
+        if !header.compressed {
+            self.check_block_size(len)?;
+        }
+        let mut fbuf = std::mem::take(&mut self.fbuf);
+        let result = source.bytes_at(offset, len, &mut fbuf).and_then(|bytes| {
+            if header.compressed {
+                self.ingest_compressed_bytes(
+                    bytes,
+                    header.qual,
+                    header.headers,
+                    header.flags,
+                    header.masked,
+                )
+            } else {
+                self.ingest_bytes(
+                    bytes,
+                    header.qual,
+                    header.headers,
+                    header.flags,
+                    header.masked,
+                )
+            }
+        });
+        self.fbuf = fbuf;
+        result
     }
 
     /// Decompresses the given bytes and ingests them into the record block.
@@ -333,12 +597,16 @@ impl RecordBlock {
         has_quality: bool,
         has_header: bool,
         has_flags: bool,
+        has_mask: bool,
     ) -> Result<()> {
+        // Reject oversized frames up front: they would not fit the buffer below
+        if let Ok(Some(content_size)) = zstd_safe::get_frame_content_size(bytes) {
+            self.check_block_size(usize::try_from(content_size).unwrap_or(usize::MAX))?;
+        }
+
         // Clear and ensure capacity
         self.rbuf.clear();
-        if self.rbuf.capacity() < self.block_size {
-            self.rbuf.reserve(self.block_size - self.rbuf.capacity());
-        }
+        self.rbuf.reserve(self.block_size);
 
         // Reuse the decompression context - avoids allocation!
         let bytes_read = self
@@ -346,109 +614,154 @@ impl RecordBlock {
             .decompress(&mut self.rbuf, bytes)
             .map_err(|code| std::io::Error::other(zstd_safe::get_error_name(code)))?;
 
-        if bytes_read != self.block_size {
-            return Err(ReadError::PartialRecord(bytes_read).into());
+        // Short blocks are fine: parsing stops at the end of the decompressed bytes
+        self.check_block_size(bytes_read)?;
+
+        self.parse_records(has_quality, has_header, has_flags, has_mask)
+    }
+
+    /// Returns the buffer the header spans of the records point into
+    fn header_bytes(&self) -> &[u8] {
+        if self.header_prefix {
+            &self.hbuf
+        } else {
+            &self.rbuf
         }
+    }
 
-        self.parse_records(has_quality, has_header, has_flags);
+    /// Checks that a block of `len` bytes fits into the block size of the file
+    fn check_block_size(&self, len: usize) -> Result<()> {
+        if len > self.block_size {
+            return Err(ReadError::BlockSizeMismatch {
+                expected_max: self.block_size,
+                got: len,
+            }
+            .into());
+        }
         Ok(())
     }
+
     /// Parse records from rbuf, storing spans for all data
-    fn parse_records(&mut self, has_quality: bool, has_header: bool, has_flags: bool) {
+    ///
+    /// Every length read from the block is checked against the bytes remaining in it, so
+    /// malformed blocks are reported as [`ReadError::CorruptBlock`] and allocations are
+    /// bounded by the block size. Headers stored with a prefix are reconstructed into `hbuf`,
+    /// which is bounded by the block size times the length of the prefix.
+    fn parse_records(
+        &mut self,
+        has_quality: bool,
+        has_header: bool,
+        has_flags: bool,
+        has_mask: bool,
+    ) -> Result<()> {
         self.records.clear();
         self.sequences.clear();
+        self.hbuf.clear();
 
-        let mut pos = 0;
-        let bytes = &self.rbuf;
+        let mut cursor = BlockCursor::new(&self.rbuf);
+        let min_header_size = if has_flags { 24 } else { 16 };
 
-        loop {
-            // Check if we have enough bytes for the minimum record header
-            let min_header_size = if has_flags { 24 } else { 16 };
-            if pos + min_header_size > bytes.len() {
-                break;
-            }
+        // Common prefix of the headers, stored at the start of the block
+        let header_prefix = has_header && self.header_prefix;
+        let prefix = if header_prefix && cursor.remaining() > 0 {
+            cursor.read_header_prefix()?
+        } else {
+            Span::new(0, 0)
+        };
+
+        // Check if we have enough bytes for the minimum record header
+        while cursor.remaining() >= min_header_size {
+            cursor.start_record();
 
             // Read flag
             let flag = if has_flags {
-                let flag = LittleEndian::read_u64(&bytes[pos..pos + 8]);
-                pos += 8;
-                Some(flag)
+                Some(cursor.read_u64("truncated flag")?)
             } else {
                 None
             };
 
             // Read lengths
-            let slen = LittleEndian::read_u64(&bytes[pos..pos + 8]);
-            pos += 8;
-            let xlen = LittleEndian::read_u64(&bytes[pos..pos + 8]);
-            pos += 8;
+            let slen = cursor.read_u64("truncated sequence length")?;
+            let xlen = cursor.read_u64("truncated sequence length")?;
 
             // Check for end of records
             if slen == 0 {
                 break;
             }
 
-            // Calculate sizes
-            let s_seq_words = encoded_sequence_len(slen, self.bitsize);
-            let x_seq_words = encoded_sequence_len(xlen, self.bitsize);
-
             // Primary sequence - store span into sequences Vec
-            let s_seq_span = Span::new(self.sequences.len(), s_seq_words);
-            for _ in 0..s_seq_words {
-                let val = LittleEndian::read_u64(&bytes[pos..pos + 8]);
-                self.sequences.push(val);
-                pos += 8;
-            }
+            let s_seq_span = cursor.read_words(
+                &mut self.sequences,
+                slen,
+                self.bitsize,
+                "primary sequence exceeds the block",
+            )?;
 
             // Primary quality - store span into rbuf
             let s_qual_span = if has_quality {
-                let span = Span::new(pos, slen as usize);
-                pos += slen as usize;
-                span
+                cursor.skip(slen, "primary quality scores exceed the block")?
             } else {
                 Span::new(0, 0)
             };
 
-            // Primary header - store span into rbuf
-            let s_header_span = if has_header {
-                let header_len = LittleEndian::read_u64(&bytes[pos..pos + 8]) as usize;
-                pos += 8;
-                let span = Span::new(pos, header_len);
-                pos += header_len;
-                span
+            // Primary soft-mask - store span into rbuf
+            let s_mask_span = if has_mask {
+                cursor.skip(slen.div_ceil(8), "primary soft-mask exceeds the block")?
+            } else {
+                Span::new(0, 0)
+            };
+
+            // Primary header - store span into rbuf (or hbuf if stored with a prefix)
+            let s_header_span = if header_prefix {
+                cursor.read_prefixed_header(
+                    prefix,
+                    &mut self.hbuf,
+                    "primary header exceeds the block",
+                )?
+            } else if has_header {
+                let header_len = cursor.read_u64("truncated header length")?;
+                cursor.skip(header_len, "primary header exceeds the block")?
             } else {
                 Span::new(0, 0)
             };
 
             // Extended sequence - store span into sequences Vec
-            let x_seq_span = Span::new(self.sequences.len(), x_seq_words);
-            for _ in 0..x_seq_words {
-                let val = LittleEndian::read_u64(&bytes[pos..pos + 8]);
-                self.sequences.push(val);
-                pos += 8;
-            }
+            let x_seq_span = cursor.read_words(
+                &mut self.sequences,
+                xlen,
+                self.bitsize,
+                "extended sequence exceeds the block",
+            )?;
 
             // Extended quality - store span into rbuf
             let x_qual_span = if has_quality {
-                let span = Span::new(pos, xlen as usize);
-                pos += xlen as usize;
-                span
+                cursor.skip(xlen, "extended quality scores exceed the block")?
+            } else {
+                Span::new(0, 0)
+            };
+
+            // Extended soft-mask - store span into rbuf
+            let x_mask_span = if has_mask {
+                cursor.skip(xlen.div_ceil(8), "extended soft-mask exceeds the block")?
             } else {
                 Span::new(0, 0)
             };
 
-            // Extended header - store span into rbuf
-            let x_header_span = if has_header && xlen > 0 {
-                let header_len = LittleEndian::read_u64(&bytes[pos..pos + 8]) as usize;
-                pos += 8;
-                let span = Span::new(pos, header_len);
-                pos += header_len;
-                span
+            // Extended header - store span into rbuf (or hbuf if stored with a prefix)
+            let x_header_span = if header_prefix && xlen > 0 {
+                cursor.read_prefixed_header(
+                    prefix,
+                    &mut self.hbuf,
+                    "extended header exceeds the block",
+                )?
+            } else if has_header && xlen > 0 {
+                let header_len = cursor.read_u64("truncated header length")?;
+                cursor.skip(header_len, "extended header exceeds the block")?
             } else {
                 Span::new(0, 0)
             };
 
-            // Update qbuf size
+            // Update qbuf size (sequences fit in the block, so this is bounded by its size)
             if !has_quality {
                 let max_size = slen.max(xlen) as usize;
                 if self.qbuf.len() < max_size {
@@ -463,13 +776,17 @@ impl RecordBlock {
                 xlen,
                 s_seq_span,
                 s_qual_span,
+                s_mask_span,
                 s_header_span,
                 x_seq_span,
                 x_qual_span,
+                x_mask_span,
                 x_header_span,
                 has_quality,
+                has_mask,
             });
         }
+        Ok(())
     }
 
     /// Decodes all sequences in the block at once.
@@ -539,6 +856,35 @@ impl RecordBlock {
     }
 }
 
+/// Iterator over a range of blocks of a VBQ file
+///
+/// Created by [`MmapReader::read_block_range`].
+pub struct BlockRangeIter<'a> {
+    reader: &'a MmapReader,
+    blocks: Range<usize>,
+}
+impl Iterator for BlockRangeIter<'_> {
+    type Item = Result<RecordBlock>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let block_idx = self.blocks.next()?;
+        let mut block = self.reader.new_block();
+        match self.reader.read_block_at_index(block_idx, &mut block) {
+            Ok(true) => Some(Ok(block)),
+            Ok(false) => {
+                // Past the last block of the file
+                self.blocks = 0..0;
+                None
+            }
+            Err(error) => Some(Err(error)),
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.blocks.len()))
+    }
+}
+
 pub struct RecordBlockIter<'a> {
     block: &'a RecordBlock,
     pos: usize,
@@ -609,9 +955,15 @@ impl<'a> Iterator for RecordBlockIter<'a> {
             // Pass quality score buffers
             squal,
             xqual,
-            // Slice into rbuf using span
-            sheader: meta.s_header_span.slice(&self.block.rbuf),
-            xheader: meta.x_header_span.slice(&self.block.rbuf),
+            // Slice into rbuf (or the reconstructed headers) using span
+            sheader: meta.s_header_span.slice(self.block.header_bytes()),
+            xheader: meta.x_header_span.slice(self.block.header_bytes()),
+            smask: meta
+                .has_mask
+                .then(|| meta.s_mask_span.slice(&self.block.rbuf)),
+            xmask: meta
+                .has_mask
+                .then(|| meta.x_mask_span.slice(&self.block.rbuf)),
             header_buf,
             header_len,
         })
@@ -633,10 +985,53 @@ pub struct RefRecord<'a> {
     xqual: &'a [u8],
     sheader: &'a [u8],
     xheader: &'a [u8],
+    smask: Option<&'a [u8]>,
+    xmask: Option<&'a [u8]>,
     header_buf: [u8; 20],
     header_len: usize,
 }
 
+impl<'a> RefRecord<'a> {
+    /// Returns the soft-mask bitmap of the primary sequence
+    ///
+    /// The bitmap has `ceil(slen / 8)` bytes, where bit `i % 8` of byte `i / 8` is set if
+    /// base `i` is lowercase. Returns `None` if the file was not written with
+    /// [`masked`](crate::vbq::FileHeaderBuilder::masked).
+    #[must_use]
+    pub fn smask(&self) -> Option<&'a [u8]> {
+        self.smask
+    }
+
+    /// Returns the soft-mask bitmap of the extended sequence
+    ///
+    /// See [`smask`](Self::smask).
+    #[must_use]
+    pub fn xmask(&self) -> Option<&'a [u8]> {
+        self.xmask
+    }
+
+    /// Decodes the primary sequence into `buf`, restoring its lowercase (soft-masked) bases
+    ///
+    /// Equivalent to [`decode_s`](BinseqRecord::decode_s) for files without soft-masks. Not to
+    /// be confused with [`decode_masked`](BinseqRecord::decode_masked), which masks bases
+    /// with `N` by quality. The case of the extended sequence can be restored with
+    /// [`apply_soft_mask`](crate::vbq::apply_soft_mask) and [`xmask`](Self::xmask).
+    pub fn decode_s_masked(&self, buf: &mut Vec<u8>) -> Result<()> {
+        let start = buf.len();
+        self.decode_s(buf)?;
+        if let Some(mask) = self.smask {
+            apply_soft_mask(&mut buf[start..], mask);
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for RefRecord<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        debug_record(self, "RefRecord", f)
+    }
+}
+
 impl BinseqRecord for RefRecord<'_> {
     fn bitsize(&self) -> BitSize {
         self.bitsize
@@ -792,14 +1187,17 @@ impl BinseqRecord for RefRecord<'_> {
 /// }
 /// ```
 pub struct MmapReader {
-    /// Memory-mapped file contents for efficient access
-    mmap: Arc<Mmap>,
+    /// Path of the memory-mapped file
+    path: PathBuf,
+
+    /// Contents of the file, memory-mapped, in memory or read from the file on demand
+    source: Arc<dyn ReadAt + Send + Sync>,
 
     /// Parsed header information from the file
     header: FileHeader,
 
     /// Current cursor position in the file (in bytes)
-    pos: usize,
+    pos: u64,
 
     /// Total number of records read from the file so far
     total: usize,
@@ -809,6 +1207,12 @@ pub struct MmapReader {
 
     /// Default quality score for this reader
     default_quality_score: u8,
+
+    /// Lazily loaded block index shared between clones of the index handle
+    index: Arc<OnceLock<BlockIndex>>,
+
+    /// Lazily expanded dense copy of a sparse block index
+    dense_index: Arc<OnceLock<BlockIndex>>,
 }
 impl MmapReader {
     /// Creates a new `MmapReader` for a VBQ file
@@ -819,7 +1223,9 @@ impl MmapReader {
     ///
     /// ## Index Loading (v0.7.0+)
     ///
-    /// The embedded index is automatically loaded from the end of the file.
+    /// The embedded index is automatically loaded from the end of the file. Legacy files
+    /// without an embedded index fall back to a `.vqi` sidecar or a scan of the block headers
+    /// (see [`load_index`](Self::load_index)).
     ///
     /// # Parameters
     ///
@@ -849,23 +1255,78 @@ impl MmapReader {
             return Err(ReadError::InvalidFileType.into());
         }
 
-        // Safety: The file is open and won't be modified while mapped
-        let mmap = unsafe { Mmap::map(&file)? };
+        let source = ByteSource::map(&file)?;
+        Self::from_source(path.as_ref().to_path_buf(), Arc::new(source))
+    }
+
+    /// Creates a new reader that reads blocks from the file instead of memory-mapping it
+    ///
+    /// Use this for files that can not be mapped, e.g. on file systems without mmap support
+    /// (some FUSE mounts) or files locked by another process on Windows, for which
+    /// [`MmapReader::new`] fails. Every block, block header and index is read at its offset
+    /// when it is needed, so memory use is bounded by the blocks in use rather than the size
+    /// of the file. The reader otherwise behaves the same.
+    ///
+    /// # Errors
+    ///
+    /// * `ReadError::InvalidFileType` if the path doesn't point to a regular file
+    /// * I/O errors if the file can't be opened or read
+    /// * Header validation errors if the file doesn't contain a valid VBQ header
+    pub fn new_unmapped<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let file = File::open(&path)?;
+        if !file.metadata()?.is_file() {
+            return Err(ReadError::InvalidFileType.into());
+        }
+
+        let source = FileSource::new(file)?;
+        Self::from_source(path.as_ref().to_path_buf(), Arc::new(source))
+    }
+
+    /// Checks if the reader is backed by a memory-mapped file
+    #[must_use]
+    pub fn is_mapped(&self) -> bool {
+        self.source.is_mapped()
+    }
+
+    /// Creates a new `MmapReader` over the contents of a VBQ file held in memory
+    ///
+    /// This behaves exactly like [`MmapReader::new`] but parses `data` directly instead of
+    /// memory-mapping a file, e.g. for file contents fetched over the network. Since there
+    /// is no path, legacy sidecar indices are not available and
+    /// [`write_embedded_index`](Self::write_embedded_index) fails.
+    pub fn from_bytes(data: Vec<u8>) -> Result<Self> {
+        Self::from_arc_bytes(data.into())
+    }
+
+    /// Creates a new `MmapReader` over shared in-memory file contents
+    ///
+    /// See [`MmapReader::from_bytes`].
+    pub fn from_arc_bytes(data: Arc<[u8]>) -> Result<Self> {
+        Self::from_source(PathBuf::new(), Arc::new(ByteSource::memory(data)))
+    }
 
-        // Read header from mapped memory
+    /// Creates a new `MmapReader` over the bytes of a VBQ file
+    fn from_source(path: PathBuf, source: Arc<dyn ReadAt + Send + Sync>) -> Result<Self> {
+        // Read the file header
+        if source.size() < SIZE_HEADER as u64 {
+            return Err(HeaderError::InvalidSize(source.size() as usize, SIZE_HEADER).into());
+        }
         let header = {
             let mut header_bytes = [0u8; SIZE_HEADER];
-            header_bytes.copy_from_slice(&mmap[..SIZE_HEADER]);
+            source.read_exact_at(&mut header_bytes, 0)?;
             FileHeader::from_bytes(&header_bytes)?
         };
 
         Ok(Self {
-            mmap: Arc::new(mmap),
+            path,
+            source,
             header,
-            pos: SIZE_HEADER,
+            pos: SIZE_HEADER as u64,
             total: 0,
             decode_block: true,
             default_quality_score: DEFAULT_QUALITY_SCORE,
+            index: Arc::new(OnceLock::new()),
+            dense_index: Arc::new(OnceLock::new()),
         })
     }
 
@@ -892,7 +1353,7 @@ impl MmapReader {
     /// ```
     #[must_use]
     pub fn new_block(&self) -> RecordBlock {
-        let mut block = RecordBlock::new(self.header.bits, self.header.block as usize);
+        let mut block = RecordBlock::for_header(&self.header);
         block.set_default_quality_score(self.default_quality_score);
         block
     }
@@ -929,6 +1390,13 @@ impl MmapReader {
         self.header
     }
 
+    /// Copies the raw contents of the file, including the embedded index, to `writer`
+    ///
+    /// Returns the number of bytes written.
+    pub(crate) fn write_to(&self, writer: &mut dyn Write) -> Result<u64> {
+        self.source.write_all_to(writer)
+    }
+
     /// Checks if the file contains paired records
     #[must_use]
     pub fn is_paired(&self) -> bool {
@@ -986,21 +1454,25 @@ impl MmapReader {
         block.clear();
 
         // Validate the next block header is within bounds and present
-        if self.pos + SIZE_BLOCK_HEADER > self.mmap.len() {
+        let size = self.source.size();
+        if self.pos + SIZE_BLOCK_HEADER as u64 > size {
             return Ok(false);
         }
         let mut header_bytes = [0u8; SIZE_BLOCK_HEADER];
-        header_bytes.copy_from_slice(&self.mmap[self.pos..self.pos + SIZE_BLOCK_HEADER]);
-        let header = match BlockHeader::from_bytes(&header_bytes) {
+        self.source.read_exact_at(&mut header_bytes, self.pos)?;
+        let header = match BlockHeader::from_bytes_at(&header_bytes, self.pos as usize) {
             Ok(header) => {
-                self.pos += SIZE_BLOCK_HEADER;
+                self.pos += SIZE_BLOCK_HEADER as u64;
                 header
             }
             // Bytes left - but not a BlockHeader - could be the index
             Err(e) => {
+                if self.pos + INDEX_HEADER_SIZE as u64 > size {
+                    return Err(e);
+                }
                 let mut index_header_bytes = [0u8; INDEX_HEADER_SIZE];
-                index_header_bytes
-                    .copy_from_slice(&self.mmap[self.pos..self.pos + INDEX_HEADER_SIZE]);
+                self.source
+                    .read_exact_at(&mut index_header_bytes, self.pos)?;
                 if IndexHeader::from_bytes(&index_header_bytes).is_ok() {
                     // Expected end of file
                     return Ok(false);
@@ -1009,46 +1481,55 @@ impl MmapReader {
             }
         };
 
-        // Read the block contents
-        let rbound = if self.header.compressed {
-            header.size as usize
-        } else {
-            self.header.block as usize
-        };
-        if self.pos + rbound > self.mmap.len() {
-            return Err(ReadError::UnexpectedEndOfFile(self.pos).into());
-        }
-        let block_buffer = &self.mmap[self.pos..self.pos + rbound];
-        if self.header.compressed {
-            block.ingest_compressed_bytes(
-                block_buffer,
-                self.header.qual,
-                self.header.headers,
-                self.header.flags,
-            )?;
-        } else {
-            block.ingest_bytes(
-                block_buffer,
-                self.header.qual,
-                self.header.headers,
-                self.header.flags,
-            )?;
-        }
+        // Read the block contents (the block header gives the size of uncompressed blocks
+        // too, which may be shorter than the block size of the file)
+        let rbound = checked_usize(header.size)?;
+        block.ingest_at(&*self.source, self.pos, rbound, &self.header)?;
 
         // Update the block index
         block.update_index(self.total);
 
-        self.pos += rbound;
+        self.pos += header.size;
         self.total += header.records as usize;
 
         Ok(true)
     }
 
-    /// Loads the embedded block index from this VBQ file
+    /// Returns the path of the legacy `.vqi` sidecar index for this file
+    ///
+    /// Files written before v0.7.0 stored their index next to the data at `<path>.vqi`
+    /// instead of embedding it.
+    #[must_use]
+    pub fn index_path(&self) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(".vqi");
+        PathBuf::from(path)
+    }
+
+    /// Returns true if the file ends with an embedded index
+    fn has_embedded_index(&self) -> Result<bool> {
+        let size = self.source.size();
+        if size < SIZE_HEADER as u64 + 16 {
+            return Ok(false);
+        }
+        let mut magic = [0u8; 8];
+        self.source.read_exact_at(&mut magic, size - 8)?;
+        Ok(LittleEndian::read_u64(&magic) == INDEX_END_MAGIC)
+    }
+
+    /// Loads the block index for this VBQ file
     ///
     /// The block index provides metadata about each block in the file, enabling
-    /// random access to blocks and parallel processing. This method reads the
-    /// embedded index from the end of the VBQ file.
+    /// random access to blocks and parallel processing. The index is taken from the first
+    /// available source:
+    ///
+    /// 1. The embedded index at the end of the file (v0.7.0+)
+    /// 2. A legacy sidecar index at [`index_path`](Self::index_path), validated against the
+    ///    file size
+    /// 3. A scan over all block headers in the file
+    ///
+    /// Legacy files can be migrated to an embedded index with
+    /// [`write_embedded_index`](Self::write_embedded_index).
     ///
     /// # Returns
     ///
@@ -1057,7 +1538,8 @@ impl MmapReader {
     /// # Errors
     ///
     /// * File I/O errors when reading the index
-    /// * Parsing errors if the VBQ file has invalid format or missing index
+    /// * `IndexError::ByteSizeMismatch` if a sidecar index describes a different file size
+    /// * Parsing errors if the VBQ file has invalid format
     ///
     /// # Examples
     ///
@@ -1073,71 +1555,509 @@ impl MmapReader {
     /// println!("Number of blocks: {}", index.n_blocks());
     /// ```
     pub fn load_index(&self) -> Result<BlockIndex> {
-        let start_pos_magic = self.mmap.len() - 8;
-        let start_pos_index_size = start_pos_magic - 8;
+        if self.has_embedded_index()? {
+            return self.load_embedded_index();
+        }
 
-        // Validate the magic number
-        let magic = LittleEndian::read_u64(&self.mmap[start_pos_magic..]);
-        if magic != INDEX_END_MAGIC {
-            return Err(ReadError::MissingIndexEndMagic.into());
+        let index_path = self.index_path();
+        if !self.path.as_os_str().is_empty() && index_path.is_file() {
+            let index = BlockIndex::from_path(index_path)?;
+            let (indexed, actual) = (index.header.bytes(), self.source.size());
+            if indexed != actual {
+                return Err(IndexError::ByteSizeMismatch(indexed, actual).into());
+            }
+            return Ok(index);
         }
 
-        // Get the index size
-        let index_size = LittleEndian::read_u64(&self.mmap[start_pos_index_size..start_pos_magic]);
+        BlockIndex::scan(&*self.source, self.source.size())
+    }
 
-        // Determine the start position of the index bytes
-        let start_pos_index = start_pos_index_size - index_size as usize;
+    /// Loads the index embedded at the end of the file
+    fn load_embedded_index(&self) -> Result<BlockIndex> {
+        let mut buf = Vec::new();
+        BlockIndex::from_bytes(self.embedded_index_bytes(&mut buf)?)
+    }
 
-        // Slice into the index bytes
-        let index_bytes = &self.mmap[start_pos_index..start_pos_index_size];
+    /// Returns the byte range of the index embedded at the end of the file
+    fn embedded_index_range(&self) -> Result<Range<u64>> {
+        let start_pos_index_size = self.source.size() - 16;
 
-        // Build the index from the bytes
-        BlockIndex::from_bytes(index_bytes)
+        // Get the index size
+        let mut size_bytes = [0u8; 8];
+        self.source
+            .read_exact_at(&mut size_bytes, start_pos_index_size)?;
+        let index_size = LittleEndian::read_u64(&size_bytes);
+
+        // The index must fit between the file header and its size
+        if index_size < INDEX_HEADER_SIZE as u64
+            || index_size > start_pos_index_size - SIZE_HEADER as u64
+        {
+            return Err(ReadError::FileTruncation(checked_usize(start_pos_index_size)?).into());
+        }
+        Ok(start_pos_index_size - index_size..start_pos_index_size)
     }
 
-    pub fn num_records(&self) -> Result<usize> {
-        let index = self.load_index()?;
-        Ok(index.num_records())
+    /// Returns the bytes of the index embedded at the end of the file, read into `buf` if the
+    /// file is not in memory
+    fn embedded_index_bytes<'a>(&'a self, buf: &'a mut Vec<u8>) -> Result<&'a [u8]> {
+        let range = self.embedded_index_range()?;
+        let len = checked_usize(range.end - range.start)?;
+        self.source.bytes_at(range.start, len, buf)
     }
-}
 
-impl ParallelReader for MmapReader {
-    /// Processes all records in the file in parallel using multiple threads
+    /// Cross-checks the block index against the blocks of the file
     ///
-    /// This method provides efficient parallel processing of VBQ files by distributing
-    /// blocks across multiple worker threads. The file's block structure is leveraged to divide
-    /// the work evenly without requiring thread synchronization during processing, which leads
-    /// to near-linear scaling with the number of threads.
+    /// Loads the index (see [`load_index`](Self::load_index)) and walks the block headers
+    /// of the file, comparing the offset, size and record counts of each block with its
+    /// index entry. This detects stale or corrupted indices, e.g. of partially written or
+    /// manually edited files.
     ///
-    /// The method automatically loads or creates an index file to identify block boundaries,
-    /// then distributes the blocks among the requested number of threads. Each thread processes
-    /// its assigned blocks sequentially, but multiple blocks are processed in parallel across
-    /// threads.
+    /// See [`validate_index_sampled`](Self::validate_index_sampled) to only check a fraction
+    /// of the blocks of large files.
     ///
-    /// # Type Parameters
+    /// # Examples
     ///
-    /// * `P` - A type that implements the `ParallelProcessor` trait, which defines how records are processed
+    /// ```rust
+    /// use binseq::vbq::MmapReader;
     ///
-    /// # Parameters
+    /// let reader = MmapReader::new("./data/subset.vbq").unwrap();
+    /// let report = reader.validate_index().unwrap();
+    /// assert!(report.is_valid());
+    /// ```
+    pub fn validate_index(&self) -> Result<IndexValidationReport> {
+        self.validate_index_sampled(1.0)
+    }
+
+    /// Cross-checks a `sample_rate` fraction of the block index against the file
     ///
-    /// * `self` - Consumes the reader, as it will be used across multiple threads
-    /// * `processor` - An instance of a type implementing `ParallelProcessor` that will be cloned for each thread
-    /// * `num_threads` - Number of worker threads to use for processing
+    /// Rates of 1 or more check every block, as in [`validate_index`](Self::validate_index).
+    /// Lower rates check evenly spaced index entries by reading the block header at the
+    /// offset each one claims, without walking the rest of the file. Extra blocks in the
+    /// file or the index are then not counted.
+    pub fn validate_index_sampled(&self, sample_rate: f32) -> Result<IndexValidationReport> {
+        let index = self.load_index()?;
+        let data_end = if self.has_embedded_index()? {
+            self.embedded_index_range()?.start
+        } else {
+            self.source.size()
+        };
+        Ok(index.validate(&*self.source, data_end, sample_rate))
+    }
+
+    /// Returns the block index as stored in the file, loading it on first access
+    fn stored_index(&self) -> Result<&BlockIndex> {
+        if let Some(index) = self.index.get() {
+            return Ok(index);
+        }
+        let index = self.load_index()?;
+        Ok(self.index.get_or_init(|| index))
+    }
+
+    /// Returns the dense block index, loading it on first access
     ///
-    /// # Returns
+    /// Unlike [`load_index`](Self::load_index), the parsed index is cached on the reader
+    /// so repeated calls do not re-read the end of the file. Sparse indices are expanded
+    /// once by scanning the block headers of the file.
+    pub(crate) fn index(&self) -> Result<&BlockIndex> {
+        let index = self.stored_index()?;
+        if !index.is_sparse() {
+            return Ok(index);
+        }
+        if let Some(dense) = self.dense_index.get() {
+            return Ok(dense);
+        }
+        let dense = index.expand(&*self.source)?;
+        Ok(self.dense_index.get_or_init(|| dense))
+    }
+
+    /// Returns the total number of records in the file
     ///
-    /// * `Ok(())` - If all records were successfully processed
-    /// * `Err(_)` - If an error occurs during processing
+    /// Embedded indices that store the record count in their header are answered without
+    /// decompressing the block ranges.
+    pub fn num_records(&self) -> Result<usize> {
+        if let Some(index) = self.index.get() {
+            return Ok(index.num_records());
+        }
+        if self.has_embedded_index()? {
+            let mut header_bytes = [0u8; INDEX_HEADER_SIZE];
+            let index_start = self.embedded_index_range()?.start;
+            self.source.read_exact_at(&mut header_bytes, index_start)?;
+            let header = IndexHeader::from_bytes(&header_bytes)?;
+            if let Some(records) = header.records() {
+                return checked_usize(records);
+            }
+        }
+        Ok(self.stored_index()?.num_records())
+    }
+
+    /// Returns the range of the block containing the record at `record_idx`
     ///
-    /// # Examples
+    /// With a sparse index only the block headers following the nearest index entry are
+    /// scanned. Returns `None` if `record_idx` is beyond the number of records in the file.
+    pub fn find_record_block(&self, record_idx: usize) -> Result<Option<BlockRange>> {
+        self.stored_index()?
+            .find_record_in(&*self.source, record_idx)
+    }
+
+    /// Copies the quality scores of the primary sequence of record `idx` into `qual_buf`
     ///
-    /// ```rust,no_run
-    /// use binseq::vbq::{MmapReader, RefRecord};
-    /// use binseq::{ParallelProcessor, ParallelReader, BinseqRecord, Result};
-    /// use std::sync::atomic::{AtomicUsize, Ordering};
-    /// use std::sync::Arc;
+    /// The contents of `qual_buf` are replaced. The embedded index locates the block of the
+    /// record and only that block is read. Quality scores are stored alongside the other
+    /// fields of each record, so the whole block is still decompressed: true quality-only
+    /// random access would need a quality-specific index. To read the qualities of many
+    /// records, iterate the blocks instead.
     ///
-    /// // Create a simple processor that counts records
+    /// Returns [`ReadError::NoQualityData`] if the file stores no quality scores and
+    /// [`ReadError::OutOfRange`] if `idx` is beyond the number of records.
+    pub fn read_quality_at(&self, idx: u64, qual_buf: &mut Vec<u8>) -> Result<()> {
+        self.read_quality_into(idx, qual_buf, false)
+    }
+
+    /// Copies the quality scores of the extended sequence of record `idx` into `qual_buf`
+    ///
+    /// See [`read_quality_at`](Self::read_quality_at). Returns [`ReadError::NotPaired`] if
+    /// the file is single-end.
+    pub fn read_xquality_at(&self, idx: u64, qual_buf: &mut Vec<u8>) -> Result<()> {
+        self.read_quality_into(idx, qual_buf, true)
+    }
+
+    /// Reads the block of record `idx` and copies its primary or extended quality scores
+    fn read_quality_into(&self, idx: u64, qual_buf: &mut Vec<u8>, extended: bool) -> Result<()> {
+        if !self.header.qual {
+            return Err(ReadError::NoQualityData.into());
+        }
+        if extended && !self.header.paired {
+            return Err(ReadError::NotPaired.into());
+        }
+        let out_of_range = || ReadError::OutOfRange {
+            requested_index: usize::try_from(idx).unwrap_or(usize::MAX),
+            max_index: self.num_records().unwrap_or(0),
+        };
+        let record_idx = usize::try_from(idx).map_err(|_| out_of_range())?;
+        let Some(range) = self.find_record_block(record_idx)? else {
+            return Err(out_of_range().into());
+        };
+
+        let mut block = self.new_block();
+        ingest_block(&mut block, &*self.source, &range, self.header, false)?;
+        let record = range
+            .record_local_index(idx)
+            .and_then(|local_idx| block.iter().nth(local_idx))
+            .ok_or_else(out_of_range)?;
+
+        qual_buf.clear();
+        qual_buf.extend_from_slice(if extended {
+            record.xqual()
+        } else {
+            record.squal()
+        });
+        Ok(())
+    }
+
+    /// Reads the block described by `block_range` into owned records
+    #[cfg(feature = "rayon")]
+    pub(crate) fn owned_block_records(&self, block_range: &BlockRange) -> Result<Vec<OwnedRecord>> {
+        let mut block = self.new_block();
+        ingest_block(&mut block, &*self.source, block_range, self.header, false)?;
+        Ok(block.to_vec_owned())
+    }
+
+    /// Returns a parallel iterator over all records of the file
+    ///
+    /// Blocks are decoded on the rayon thread pool and records are yielded as
+    /// [`OwnedRecord`]s in file order. Errors while loading the index or decoding a block are
+    /// yielded as items. See [`ParIterBuilder`](super::ParIterBuilder) for further options.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use binseq::vbq::MmapReader;
+    /// use rayon::prelude::*;
+    ///
+    /// let reader = MmapReader::new("example.vbq").unwrap();
+    /// let total_bp: u64 = reader
+    ///     .into_par_iter()
+    ///     .filter_map(|record| record.ok())
+    ///     .map(|record| binseq::BinseqRecord::slen(&record))
+    ///     .sum();
+    /// ```
+    #[cfg(feature = "rayon")]
+    pub fn into_par_iter(self) -> impl rayon::iter::ParallelIterator<Item = Result<OwnedRecord>> {
+        super::ParIterBuilder::new(self).build()
+    }
+
+    /// Returns a parallel iterator over the blocks of the file
+    ///
+    /// Each block of the index is read and decompressed as its own rayon task, so blocks are
+    /// produced in no particular order while the records within a block keep their order and
+    /// report their global index. Errors while loading the index or decoding a block are
+    /// yielded as items.
+    ///
+    /// Unlike [`into_par_iter`](Self::into_par_iter), the reader is borrowed and records can
+    /// be visited without copying them into [`OwnedRecord`]s.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use binseq::OwnedRecord;
+    /// use binseq::vbq::MmapReader;
+    /// use rayon::prelude::*;
+    ///
+    /// let reader = MmapReader::new("example.vbq").unwrap();
+    /// let n_records = reader
+    ///     .iter_blocks_par()
+    ///     .flat_map(|block| {
+    ///         let block = block.unwrap();
+    ///         block.iter().map(OwnedRecord::from).collect::<Vec<_>>()
+    ///     })
+    ///     .count();
+    /// ```
+    #[cfg(feature = "rayon")]
+    pub fn iter_blocks_par(
+        &self,
+    ) -> impl rayon::iter::ParallelIterator<Item = Result<RecordBlock>> + '_ {
+        use rayon::prelude::*;
+
+        let (ranges, error) = match self.index() {
+            Ok(index) => (index.ranges(), None),
+            Err(e) => (&[][..], Some(e)),
+        };
+        let blocks = ranges.par_iter().map(|range| {
+            let mut block = self.new_block();
+            ingest_block(&mut block, &*self.source, range, self.header, false)?;
+            Ok(block)
+        });
+        error.into_par_iter().map(Err).chain(blocks)
+    }
+
+    /// Appends an embedded index to a file that does not have one
+    ///
+    /// This migrates legacy files (see [`load_index`](Self::load_index)) so that subsequent
+    /// opens read the index directly from the end of the file. The index is taken from the
+    /// sidecar if present, or built by scanning the file otherwise. Files that already have an
+    /// embedded index are left untouched.
+    ///
+    /// This reader keeps its original view of the file; open a new reader to use the
+    /// embedded index. The sidecar index is not removed.
+    pub fn write_embedded_index(&self) -> Result<()> {
+        if self.has_embedded_index()? {
+            return Ok(());
+        }
+
+        let index = BlockIndex {
+            header: IndexHeader::new(self.source.size()),
+            ranges: self.index()?.ranges().to_vec(),
+        };
+        let mut buffer = Vec::new();
+        index.write_bytes(&mut buffer)?;
+
+        let mut file = OpenOptions::new().append(true).open(&self.path)?;
+        file.write_all(&buffer)?;
+        file.write_u64::<LittleEndian>(buffer.len() as u64)?;
+        file.write_u64::<LittleEndian>(INDEX_END_MAGIC)?;
+        file.flush()?;
+        Ok(())
+    }
+
+    /// Fills an existing `RecordBlock` with the block at the given position in the index
+    ///
+    /// This provides random access to blocks using the embedded index. Unlike
+    /// [`read_block_into`](Self::read_block_into), this does not advance the reader's
+    /// cursor, so sequential and random-access reads can be freely interleaved.
+    ///
+    /// Records in the filled block report their global index within the file.
+    ///
+    /// # Parameters
+    ///
+    /// * `block_idx` - The index of the block to read (0-based)
+    /// * `block` - A mutable reference to a `RecordBlock` to be filled with data
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(true)` - If the block was successfully read
+    /// * `Ok(false)` - If `block_idx` is past the last block in the file
+    /// * `Err(_)` - If the index could not be loaded or the block could not be read
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use binseq::vbq::MmapReader;
+    ///
+    /// let reader = MmapReader::new("example.vbq").unwrap();
+    /// let mut block = reader.new_block();
+    ///
+    /// // Read the blocks in reverse order
+    /// let n_blocks = reader.load_index().unwrap().n_blocks();
+    /// for block_idx in (0..n_blocks).rev() {
+    ///     reader.read_block_at_index(block_idx, &mut block).unwrap();
+    ///     println!("Block {} has {} records", block_idx, block.n_records());
+    /// }
+    /// ```
+    pub fn read_block_at_index(&self, block_idx: usize, block: &mut RecordBlock) -> Result<bool> {
+        block.clear();
+
+        let Some(range) = self.index()?.ranges().get(block_idx).copied() else {
+            return Ok(false);
+        };
+
+        ingest_block(block, &*self.source, &range, self.header, false)?;
+        Ok(true)
+    }
+
+    /// Returns an iterator over the blocks `start_block..end_block` of the index
+    ///
+    /// Each block is read with [`read_block_at_index`](Self::read_block_at_index) into a
+    /// newly allocated [`RecordBlock`], so blocks can be kept or sent to other threads. Blocks
+    /// past the end of the file are not yielded. Combined with
+    /// [`split_into_ranges`](Self::split_into_ranges), this gives each thread direct control
+    /// over the blocks it reads.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use binseq::vbq::MmapReader;
+    ///
+    /// let reader = MmapReader::new("example.vbq").unwrap();
+    /// for block in reader.read_block_range(2, 4) {
+    ///     println!("Block has {} records", block.unwrap().n_records());
+    /// }
+    /// ```
+    #[must_use]
+    pub fn read_block_range(&self, start_block: usize, end_block: usize) -> BlockRangeIter<'_> {
+        BlockRangeIter {
+            reader: self,
+            blocks: start_block..end_block,
+        }
+    }
+
+    /// Divides the blocks of the file into `n` contiguous ranges of block indices
+    ///
+    /// The ranges cover every block exactly once, in order, and their lengths differ by at
+    /// most one (ranges are empty if `n` exceeds the number of blocks). Pass them to
+    /// [`read_block_range`](Self::read_block_range) to assign blocks to threads.
+    pub fn split_into_ranges(&self, n: usize) -> Result<Vec<Range<usize>>> {
+        let n_blocks = self.index()?.n_blocks();
+        if n == 0 {
+            return Ok(Vec::new());
+        }
+        let (size, extra) = (n_blocks / n, n_blocks % n);
+        let mut start = 0;
+        Ok((0..n)
+            .map(|idx| {
+                let end = start + size + usize::from(idx < extra);
+                let range = start..end;
+                start = end;
+                range
+            })
+            .collect())
+    }
+
+    /// Divides the records of the file into contiguous ranges of about `target_bytes` of
+    /// block data each
+    ///
+    /// Blocks are never split: each range spans whole blocks, whose stored sizes (including
+    /// block headers, after compression) add up to as close to `target_bytes` as the block
+    /// boundaries allow. The record ranges are disjoint, cover every record in order and can
+    /// be passed to [`ParallelReader::process_parallel_range`], e.g. one per task of an external
+    /// scheduler. A target larger than the file yields a single range; an empty file yields
+    /// the single empty range `0..0`.
+    pub fn partition_by_bytes(&self, target_bytes: u64) -> Result<Vec<Range<usize>>> {
+        partition_blocks(self.index()?.ranges(), target_bytes)
+            .into_iter()
+            .map(|range| {
+                let to_usize =
+                    |idx: u64| usize::try_from(idx).map_err(|_| ReadError::OffsetOverflow(idx));
+                Ok(to_usize(range.start)?..to_usize(range.end)?)
+            })
+            .collect()
+    }
+
+    /// Returns owned copies of the first `n` records in the file
+    ///
+    /// Only the blocks containing the requested records are read. If `n` exceeds the number
+    /// of records in the file, all records are returned.
+    pub fn head(&self, n: usize) -> Result<Vec<OwnedRecord>> {
+        let n = n.min(self.num_records()?);
+        let mut records = Vec::with_capacity(n);
+        let mut block = self.new_block();
+        let mut block_idx = 0;
+        while records.len() < n && self.read_block_at_index(block_idx, &mut block)? {
+            let remaining = n - records.len();
+            records.extend(block.iter().take(remaining).map(OwnedRecord::from));
+            block_idx += 1;
+        }
+        Ok(records)
+    }
+
+    /// Returns owned copies of the last `n` records in the file
+    ///
+    /// The embedded index is used to jump directly to the final block(s), so only the blocks
+    /// containing the requested records are read regardless of the file size. If `n` exceeds
+    /// the number of records in the file, all records are returned.
+    pub fn tail(&self, n: usize) -> Result<Vec<OwnedRecord>> {
+        let index = self.index()?;
+        let total = index.num_records();
+        let start = total.saturating_sub(n);
+
+        // Find the first block containing the starting record
+        let first_block = index
+            .ranges()
+            .iter()
+            .position(|r| r.record_global_range().end as usize > start)
+            .unwrap_or(index.n_blocks());
+
+        let mut records = Vec::with_capacity(total - start);
+        let mut block = self.new_block();
+        for block_idx in first_block..index.n_blocks() {
+            self.read_block_at_index(block_idx, &mut block)?;
+            records.extend(
+                block
+                    .iter()
+                    .filter(|record| record.index() as usize >= start)
+                    .map(OwnedRecord::from),
+            );
+        }
+        Ok(records)
+    }
+}
+
+impl ParallelReader for MmapReader {
+    /// Processes all records in the file in parallel using multiple threads
+    ///
+    /// This method provides efficient parallel processing of VBQ files by distributing
+    /// blocks across multiple worker threads. The file's block structure is leveraged to divide
+    /// the work evenly without requiring thread synchronization during processing, which leads
+    /// to near-linear scaling with the number of threads.
+    ///
+    /// The method automatically loads or creates an index file to identify block boundaries,
+    /// then distributes the blocks among the requested number of threads. Each thread processes
+    /// its assigned blocks sequentially, but multiple blocks are processed in parallel across
+    /// threads.
+    ///
+    /// # Type Parameters
+    ///
+    /// * `P` - A type that implements the `ParallelProcessor` trait, which defines how records are processed
+    ///
+    /// # Parameters
+    ///
+    /// * `self` - Consumes the reader, as it will be used across multiple threads
+    /// * `processor` - An instance of a type implementing `ParallelProcessor` that will be cloned for each thread
+    /// * `num_threads` - Number of worker threads to use for processing
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - If all records were successfully processed
+    /// * `Err(_)` - If an error occurs during processing
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use binseq::vbq::{MmapReader, RefRecord};
+    /// use binseq::{ParallelProcessor, ParallelReader, BinseqRecord, Result};
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use std::sync::Arc;
+    ///
+    /// // Create a simple processor that counts records
     /// struct RecordCounter {
     ///     count: Arc<AtomicUsize>,
     ///     thread_id: usize,
@@ -1205,6 +2125,10 @@ impl ParallelReader for MmapReader {
         num_threads: usize,
     ) -> Result<()> {
         let num_records = self.num_records()?;
+        if num_records == 0 {
+            // Nothing to process in an empty file
+            return Ok(());
+        }
         self.process_parallel_range(processor, num_threads, 0..num_records)
     }
 
@@ -1241,39 +2165,60 @@ impl ParallelReader for MmapReader {
         } else {
             num_threads.min(num_cpus::get())
         };
+        let jobs = self.parallel_jobs(&processor, num_threads, &range, FlagFilter::default())?;
+        executor::spawn_and_join(jobs)
+    }
 
-        // Generate or load the index first
-        let index = self.load_index()?;
-
-        // Validate range
-        let total_records = index.num_records();
-        self.validate_range(total_records, &range)?;
+    /// Process records in parallel within a specified range on a reusable [`Executor`]
+    ///
+    /// Blocks are assigned the same way as in
+    /// [`process_parallel_range`](ParallelReader::process_parallel_range), using one group of
+    /// blocks per worker thread of the executor.
+    fn process_parallel_in<P: ParallelProcessor + Clone + 'static>(
+        self,
+        executor: &Executor,
+        processor: P,
+        range: Range<usize>,
+    ) -> Result<()> {
+        let jobs = self.parallel_jobs(
+            &processor,
+            executor.num_threads(),
+            &range,
+            FlagFilter::default(),
+        )?;
+        executor.run(jobs)
+    }
+}
 
+impl MmapReader {
+    /// Builds one job per thread, each processing a contiguous group of blocks overlapping `range`
+    ///
+    /// Only records matching `filter` are processed, and blocks whose flag summary shows
+    /// that none of their records match are skipped.
+    fn parallel_jobs<P: ParallelProcessor + Clone + 'static>(
+        self,
+        processor: &P,
+        num_threads: usize,
+        range: &Range<usize>,
+        filter: FlagFilter,
+    ) -> Result<Vec<Job>> {
         // Find blocks that contain records in the specified range
-        let relevant_blocks = index
-            .ranges()
-            .iter()
-            .filter(|r| {
-                let iv_start = r.cumulative_records as usize;
-                let iv_end = (r.cumulative_records + u64::from(r.block_records)) as usize;
-                iv_start < range.end && iv_end > range.start
-            })
-            .copied()
-            .collect::<Vec<_>>();
+        let mut relevant_blocks = self.relevant_blocks(range)?;
+        relevant_blocks.retain(|block_range| filter.may_match(block_range));
 
         if relevant_blocks.is_empty() {
-            return Ok(()); // No relevant blocks
+            return Ok(Vec::new()); // No relevant blocks
         }
 
         // Calculate block assignments for threads
         let blocks_per_thread = relevant_blocks.len().div_ceil(num_threads);
 
         // Create shared resources
-        let mmap = Arc::clone(&self.mmap);
+        let source = Arc::clone(&self.source);
         let header = self.header;
 
-        // Spawn worker threads
-        let mut handles = Vec::new();
+        // Build one job per thread
+        let mut jobs: Vec<Job> = Vec::with_capacity(num_threads);
 
         for thread_id in 0..num_threads {
             // Calculate this thread's block range
@@ -1285,122 +2230,417 @@ impl ParallelReader for MmapReader {
                 continue;
             }
 
-            let mmap = Arc::clone(&mmap);
+            let source = Arc::clone(&source);
             let mut proc = processor.clone();
+            let range = range.clone();
+            let decode_block = self.decode_block;
             proc.set_tid(thread_id);
 
             // Get block ranges for this thread
             let thread_blocks: Vec<BlockRange> =
                 relevant_blocks[start_block_idx..end_block_idx].to_vec();
 
-            let handle = std::thread::spawn(move || -> Result<()> {
-                // Create block to reuse for processing (within thread)
-                let mut record_block = RecordBlock::new(header.bits, header.block as usize);
+            jobs.push(Box::new(move || -> Result<()> {
+                // Create block and scratch buffers to reuse for processing (within thread)
+                let mut record_block = RecordBlock::for_header(&header);
+                let mut scratch = ScratchBuffers::default();
 
                 // Process each assigned block
-                for block_range in thread_blocks {
-                    // Clear the block for reuse
-                    record_block.clear();
-
-                    // Skip the block header to get to data
-                    let block_start = block_range.start_offset as usize + SIZE_BLOCK_HEADER;
-                    let block_data = &mmap[block_start..block_start + block_range.len as usize];
-
-                    // Ingest data according to the compression setting
-                    if header.compressed {
-                        record_block.ingest_compressed_bytes(
-                            block_data,
-                            header.qual,
-                            header.headers,
-                            header.flags,
-                        )?;
-                    } else {
-                        record_block.ingest_bytes(
-                            block_data,
-                            header.qual,
-                            header.headers,
-                            header.flags,
-                        )?;
-                    }
-
-                    // Update the record block index
-                    record_block.update_index(block_range.cumulative_records as usize);
-
-                    // decode the data
-                    if self.decode_block {
-                        record_block.decode_all()?;
-                    }
-
-                    // Process records in this block that fall within our range
-                    for record in record_block.iter() {
-                        let global_record_idx = record.index as usize;
-
-                        // Only process records within our specified range
-                        if global_record_idx >= range.start && global_record_idx < range.end {
-                            proc.process_record(record)?;
-                        }
-                    }
-
-                    // Signal batch completion
-                    proc.on_batch_complete()?;
+                for block_range in &thread_blocks {
+                    process_block(
+                        &mut proc,
+                        &mut record_block,
+                        &*source,
+                        block_range,
+                        header,
+                        decode_block,
+                        &range,
+                        filter,
+                        &mut scratch,
+                    )?;
                 }
 
                 // Signal thread completion
                 proc.on_thread_complete()?;
 
                 Ok(())
-            });
-
-            handles.push(handle);
-        }
-
-        // Wait for all threads to complete
-        for handle in handles {
-            handle.join().unwrap()?;
+            }));
         }
 
-        Ok(())
+        Ok(jobs)
     }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::BinseqRecord;
 
-    const TEST_VBQ_FILE: &str = "./data/subset.vbq";
+    /// Returns the blocks containing records in `range` after validating it against the index
+    fn relevant_blocks(&self, range: &Range<usize>) -> Result<Vec<BlockRange>> {
+        // Generate or load the index first
+        let index = self.index()?;
 
-    // ==================== MmapReader Basic Tests ====================
+        // Validate range
+        let total_records = index.num_records();
+        self.validate_range(total_records, range)?;
 
-    #[test]
-    fn test_mmap_reader_new() {
-        let reader = MmapReader::new(TEST_VBQ_FILE);
-        assert!(reader.is_ok(), "Failed to create VBQ reader");
+        Ok(index
+            .ranges()
+            .iter()
+            .filter(|r| {
+                let records = r.record_global_range();
+                (records.start as usize) < range.end && records.end as usize > range.start
+            })
+            .copied()
+            .collect())
     }
 
-    #[test]
-    fn test_mmap_reader_num_records() {
-        let reader = MmapReader::new(TEST_VBQ_FILE).unwrap();
-        let num_records = reader.num_records();
-        assert!(num_records.is_ok(), "Failed to get num_records");
-        assert!(num_records.unwrap() > 0, "Expected non-zero records");
+    /// Process the records matching a flag filter in parallel
+    ///
+    /// Behaves like [`process_parallel`](ParallelReader::process_parallel), but only passes
+    /// records matching `filter` to the processor. If the embedded index stores flag
+    /// summaries (see [`WriterBuilder::index_flag_summary`](crate::vbq::WriterBuilder::index_flag_summary)),
+    /// blocks without matching records are skipped without being decompressed. Blocks
+    /// without a summary are always read.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use binseq::processors::CountProcessor;
+    /// use binseq::vbq::{FlagFilter, MmapReader};
+    ///
+    /// // Only process records with the QC-fail bit (0x200) unset
+    /// let reader = MmapReader::new("example.vbq").unwrap();
+    /// let filter = FlagFilter::new().forbid(0x200);
+    /// reader
+    ///     .process_parallel_filtered(CountProcessor::new(), 4, filter)
+    ///     .unwrap();
+    /// ```
+    pub fn process_parallel_filtered<P: ParallelProcessor + Clone + 'static>(
+        self,
+        processor: P,
+        num_threads: usize,
+        filter: FlagFilter,
+    ) -> Result<()> {
+        let num_threads = if num_threads == 0 {
+            num_cpus::get()
+        } else {
+            num_threads.min(num_cpus::get())
+        };
+        let range = 0..self.num_records()?;
+        let jobs = self.parallel_jobs(&processor, num_threads, &range, filter)?;
+        executor::spawn_and_join(jobs)
     }
 
-    #[test]
-    fn test_mmap_reader_is_paired() {
-        let reader = MmapReader::new(TEST_VBQ_FILE).unwrap();
-        // The fixture file contains paired records
-        assert!(reader.is_paired());
+    /// Process records in parallel within a specified range using work stealing
+    ///
+    /// Unlike [`process_parallel_range`](ParallelReader::process_parallel_range), which assigns
+    /// each thread a fixed contiguous group of blocks, all blocks overlapping the range are
+    /// loaded into a shared queue. Each thread pulls blocks from the queue and steals from
+    /// other threads once it runs dry, which balances the load when the processing time per
+    /// record varies.
+    ///
+    /// Records within a block are processed in order, but blocks are not processed in any
+    /// particular order.
+    #[cfg(feature = "work-stealing")]
+    pub fn process_parallel_work_stealing<P: ParallelProcessor + Clone + 'static>(
+        self,
+        processor: P,
+        num_threads: usize,
+        range: Range<usize>,
+    ) -> Result<()> {
+        let num_threads = if num_threads == 0 {
+            num_cpus::get()
+        } else {
+            num_threads.min(num_cpus::get())
+        };
+        let relevant_blocks = Arc::new(self.relevant_blocks(&range)?);
+        let queues = crate::stealing::WorkQueue::build(0..relevant_blocks.len(), num_threads);
+        let header = self.header;
+        let decode_block = self.decode_block;
+
+        let mut jobs: Vec<Job> = Vec::with_capacity(num_threads);
+        for (tid, queue) in queues.into_iter().enumerate() {
+            let source = Arc::clone(&self.source);
+            let blocks = Arc::clone(&relevant_blocks);
+            let mut proc = processor.clone();
+            let range = range.clone();
+            proc.set_tid(tid);
+
+            jobs.push(Box::new(move || -> Result<()> {
+                let mut record_block = RecordBlock::for_header(&header);
+                let mut scratch = ScratchBuffers::default();
+                while let Some(block_idx) = queue.next() {
+                    process_block(
+                        &mut proc,
+                        &mut record_block,
+                        &*source,
+                        &blocks[block_idx],
+                        header,
+                        decode_block,
+                        &range,
+                        FlagFilter::default(),
+                        &mut scratch,
+                    )?;
+                }
+                proc.on_thread_complete()
+            }));
+        }
+        executor::spawn_and_join(jobs)
     }
 
-    #[test]
-    fn test_mmap_reader_header_access() {
-        let reader = MmapReader::new(TEST_VBQ_FILE).unwrap();
-        let header = &reader.header;
-        assert!(header.block > 0, "Expected non-zero block size");
+    /// Process all records in parallel, skipping blocks completed by a previous scan
+    ///
+    /// Each block is a unit of work of the `checkpoint`: blocks marked as done are skipped,
+    /// and every other block is marked once [`ParallelProcessor::on_batch_complete`] succeeds
+    /// for it. If the scan fails or is interrupted, calling this method again with the same
+    /// checkpoint resumes it. Blocks are processed *at least once*, see [`CheckpointStore`].
+    ///
+    /// Pending blocks are split into contiguous groups, one per thread.
+    pub fn process_parallel_resumable<P: ParallelProcessor>(
+        self,
+        processor: P,
+        num_threads: usize,
+        checkpoint: &mut dyn CheckpointStore,
+    ) -> Result<()> {
+        let range = 0..self.num_records()?;
+        let blocks = self.relevant_blocks(&range)?;
+        let source = Arc::clone(&self.source);
+        let header = self.header;
+        let decode_block = self.decode_block;
+        run_resumable(
+            &processor,
+            num_threads,
+            blocks.len(),
+            checkpoint,
+            || (RecordBlock::for_header(&header), ScratchBuffers::default()),
+            |proc, (record_block, scratch), block_idx| {
+                process_block(
+                    proc,
+                    record_block,
+                    &*source,
+                    &blocks[block_idx],
+                    header,
+                    decode_block,
+                    &range,
+                    FlagFilter::default(),
+                    scratch,
+                )
+            },
+        )
+    }
+}
+
+/// Selection of records by the bits of their flag
+///
+/// A record matches if all `required` bits are set in its flag and none of the `forbidden`
+/// bits are. Records without a flag are treated as having a flag of 0. The default filter
+/// matches every record.
+///
+/// # Examples
+///
+/// ```rust
+/// use binseq::vbq::FlagFilter;
+///
+/// let filter = FlagFilter::new().require(0x1).forbid(0x200);
+/// assert!(filter.matches(Some(0x3)));
+/// assert!(!filter.matches(Some(0x201)));
+/// assert!(!filter.matches(None));
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FlagFilter {
+    /// Bits that must be set in the flag
+    pub required: u64,
+
+    /// Bits that must be unset in the flag
+    pub forbidden: u64,
+}
+impl FlagFilter {
+    /// Creates a filter matching every record
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Requires `bits` to be set in the flag
+    #[must_use]
+    pub fn require(mut self, bits: u64) -> Self {
+        self.required |= bits;
+        self
+    }
+
+    /// Requires `bits` to be unset in the flag
+    #[must_use]
+    pub fn forbid(mut self, bits: u64) -> Self {
+        self.forbidden |= bits;
+        self
+    }
+
+    /// Returns `true` if a record with `flag` matches the filter
+    #[must_use]
+    pub fn matches(&self, flag: Option<u64>) -> bool {
+        let flag = flag.unwrap_or(0);
+        flag & self.required == self.required && flag & self.forbidden == 0
+    }
+
+    /// Returns `false` if the flag summary of a block shows that none of its records match
+    ///
+    /// Blocks without a flag summary may always match.
+    #[must_use]
+    pub fn may_match(&self, range: &BlockRange) -> bool {
+        match (range.flag_or(), range.flag_and()) {
+            (Some(flag_or), Some(flag_and)) => {
+                flag_or & self.required == self.required && flag_and & self.forbidden == 0
+            }
+            _ => true,
+        }
+    }
+}
+
+/// Converts an offset, length or count stored in the file to `usize`
+///
+/// Fails with [`ReadError::OffsetOverflow`] instead of truncating on 32-bit targets.
+fn checked_usize(value: u64) -> Result<usize> {
+    usize::try_from(value).map_err(|_| ReadError::OffsetOverflow(value).into())
+}
+
+/// Reads the block described by `block_range` from the file into `record_block`
+///
+/// Ranges pointing past the end of the file (e.g. from a corrupt index) are reported as
+/// [`ReadError::UnexpectedEndOfFile`].
+fn ingest_block<S: ReadAt + ?Sized>(
+    record_block: &mut RecordBlock,
+    source: &S,
+    block_range: &BlockRange,
+    header: FileHeader,
+    decode_block: bool,
+) -> Result<()> {
+    // Clear the block for reuse
+    record_block.clear();
+
+    // Ingest the data following the block header
+    let offset = block_range
+        .start_offset
+        .saturating_add(SIZE_BLOCK_HEADER as u64);
+    record_block.ingest_at(source, offset, checked_usize(block_range.len)?, &header)?;
+
+    // Update the record block index
+    record_block.update_index(checked_usize(block_range.cumulative_records)?);
+
+    // decode the data
+    if decode_block {
+        record_block.decode_all()?;
+    }
+
+    Ok(())
+}
+
+/// Groups consecutive blocks into record ranges of about `target_bytes` each
+///
+/// A group is closed before a block that would take it further from the target than it
+/// already is. Every group holds at least one block.
+fn partition_blocks(blocks: &[BlockRange], target_bytes: u64) -> Vec<Range<u64>> {
+    let (Some(first), Some(last)) = (blocks.first(), blocks.last()) else {
+        return vec![0..0];
+    };
+    let mut partitions = Vec::new();
+    let mut start = first.cumulative_records;
+    let mut size = 0;
+    for block in blocks {
+        let block_size = block.len + SIZE_BLOCK_HEADER as u64;
+        let grown = size + block_size;
+        if size > 0
+            && (size >= target_bytes
+                || (grown > target_bytes && grown - target_bytes > target_bytes - size))
+        {
+            partitions.push(start..block.cumulative_records);
+            start = block.cumulative_records;
+            size = 0;
+        }
+        size += block_size;
+    }
+    partitions.push(start..last.record_global_range().end);
+    partitions
+}
+
+/// Decodes a single block and passes its records within `range` matching `filter` to the
+/// processor as one batch
+#[allow(clippy::too_many_arguments)]
+fn process_block<P: ParallelProcessor, S: ReadAt + ?Sized>(
+    proc: &mut P,
+    record_block: &mut RecordBlock,
+    source: &S,
+    block_range: &BlockRange,
+    header: FileHeader,
+    decode_block: bool,
+    range: &Range<usize>,
+    filter: FlagFilter,
+    scratch: &mut ScratchBuffers,
+) -> Result<()> {
+    ingest_block(record_block, source, block_range, header, decode_block)?;
+
+    // Process records in this block that fall within our range
+    for record in record_block.iter() {
+        let global_record_idx = record.index as usize;
+
+        // Only process records within our specified range
+        if global_record_idx >= range.start
+            && global_record_idx < range.end
+            && filter.matches(record.flag)
+        {
+            crate::parallel::process_or_skip(proc, record, scratch)?;
+        }
+    }
+
+    // Signal batch completion
+    proc.on_batch_complete()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BinseqRecord;
+
+    const TEST_VBQ_FILE: &str = "./data/subset.vbq";
+
+    // ==================== MmapReader Basic Tests ====================
+
+    #[test]
+    fn test_mmap_reader_new() {
+        let reader = MmapReader::new(TEST_VBQ_FILE);
+        assert!(reader.is_ok(), "Failed to create VBQ reader");
+    }
+
+    #[test]
+    fn test_mmap_reader_num_records() {
+        let reader = MmapReader::new(TEST_VBQ_FILE).unwrap();
+        let num_records = reader.num_records();
+        assert!(num_records.is_ok(), "Failed to get num_records");
+        assert!(num_records.unwrap() > 0, "Expected non-zero records");
+    }
+
+    #[test]
+    fn test_mmap_reader_is_paired() {
+        let reader = MmapReader::new(TEST_VBQ_FILE).unwrap();
+        // The fixture file contains paired records
+        assert!(reader.is_paired());
+    }
+
+    #[test]
+    fn test_mmap_reader_header_access() {
+        let reader = MmapReader::new(TEST_VBQ_FILE).unwrap();
+        let header = &reader.header;
+        assert!(header.block > 0, "Expected non-zero block size");
         assert_eq!(header.magic, 0x5145_5356, "Expected VSEQ magic number");
     }
 
+    #[test]
+    fn test_ref_record_debug() {
+        let mut reader = MmapReader::new(TEST_VBQ_FILE).unwrap();
+        let mut block = reader.new_block();
+        reader.read_block_into(&mut block).unwrap();
+        let record = block.iter().next().unwrap();
+        assert_eq!(
+            format!("{record:?}"),
+            "RefRecord { index: 0, slen: 28, xlen: 90, flag: None, sseq: \"CGGTATTGTTAGCGCC...\", xseq: \"ACGCGGTTAGCACGTA...\" }"
+        );
+    }
+
     // ==================== RecordBlock Tests ====================
 
     #[test]
@@ -1892,4 +3132,1219 @@ mod tests {
             }
         }
     }
+
+    // ==================== Random Block Access Tests ====================
+
+    /// Writes `n_records` single-end records of `slen` nucleotides with a small block size
+    fn write_small_block_file(path: &std::path::Path, n_records: usize, block: u64, slen: usize) {
+        use crate::SequencingRecordBuilder;
+        use crate::vbq::{FileHeaderBuilder, WriterBuilder};
+
+        let seq = b"ACGT".repeat(slen.div_ceil(4));
+        let header = FileHeaderBuilder::new().block(block).build();
+        let mut writer = WriterBuilder::default()
+            .header(header)
+            .build(File::create(path).unwrap())
+            .unwrap();
+        for _ in 0..n_records {
+            let record = SequencingRecordBuilder::default()
+                .s_seq(&seq[..slen])
+                .build()
+                .unwrap();
+            writer.push(record).unwrap();
+        }
+        writer.finish().unwrap();
+    }
+
+    /// Converts a file with an embedded index into a legacy file, returning the index bytes
+    fn strip_embedded_index(path: &std::path::Path) -> Vec<u8> {
+        let bytes = std::fs::read(path).unwrap();
+        let trailer = bytes.len() - 16;
+        let index_size = LittleEndian::read_u64(&bytes[trailer..trailer + 8]) as usize;
+        let index_bytes = bytes[trailer - index_size..trailer].to_vec();
+        std::fs::write(path, &bytes[..trailer - index_size]).unwrap();
+        index_bytes
+    }
+
+    #[test]
+    fn test_load_index_legacy_sidecar() {
+        let path = std::env::temp_dir().join("binseq_test_legacy_sidecar.vbq");
+        write_small_block_file(&path, 500, 1024, 32);
+        let expected = MmapReader::new(&path).unwrap().load_index().unwrap();
+
+        let index_bytes = strip_embedded_index(&path);
+        let reader = MmapReader::new(&path).unwrap();
+        std::fs::write(reader.index_path(), index_bytes).unwrap();
+        assert_eq!(
+            reader.index_path(),
+            std::env::temp_dir().join("binseq_test_legacy_sidecar.vbq.vqi")
+        );
+
+        let index = reader.load_index().unwrap();
+        assert_eq!(index.ranges(), expected.ranges());
+        assert_eq!(reader.num_records().unwrap(), 500);
+
+        let mut block = reader.new_block();
+        assert!(reader.read_block_at_index(1, &mut block).unwrap());
+        assert_eq!(block.iter().next().unwrap().index(), 42);
+
+        std::fs::remove_file(reader.index_path()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_load_index_sidecar_size_mismatch() {
+        let path = std::env::temp_dir().join("binseq_test_legacy_mismatch.vbq");
+        write_small_block_file(&path, 100, 1024, 32);
+        let index_bytes = strip_embedded_index(&path);
+
+        // Append a stray block to the data so the sidecar no longer describes it
+        let mut bytes = std::fs::read(&path).unwrap();
+        let extra = bytes[SIZE_HEADER..SIZE_HEADER + SIZE_BLOCK_HEADER + 1024].to_vec();
+        bytes.extend_from_slice(&extra);
+        std::fs::write(&path, &bytes).unwrap();
+
+        let reader = MmapReader::new(&path).unwrap();
+        std::fs::write(reader.index_path(), index_bytes).unwrap();
+        assert!(reader.load_index().is_err());
+
+        std::fs::remove_file(reader.index_path()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_load_index_scan_and_migrate() {
+        let path = std::env::temp_dir().join("binseq_test_legacy_scan.vbq");
+        write_small_block_file(&path, 500, 1024, 32);
+        let expected = MmapReader::new(&path).unwrap().load_index().unwrap();
+        strip_embedded_index(&path);
+
+        // Without a sidecar the index is rebuilt from the block headers
+        let reader = MmapReader::new(&path).unwrap();
+        assert!(!reader.index_path().exists());
+        assert_eq!(reader.load_index().unwrap().ranges(), expected.ranges());
+
+        // Migrating appends an embedded index that new readers pick up
+        reader.write_embedded_index().unwrap();
+        let migrated = MmapReader::new(&path).unwrap();
+        assert!(migrated.has_embedded_index().unwrap());
+        assert_eq!(
+            migrated.load_embedded_index().unwrap().ranges(),
+            expected.ranges()
+        );
+
+        // Migrating again is a no-op
+        let len = std::fs::metadata(&path).unwrap().len();
+        migrated.write_embedded_index().unwrap();
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), len);
+
+        // The migrated file still reads sequentially
+        let mut reader = MmapReader::new(&path).unwrap();
+        let mut block = reader.new_block();
+        let mut n_records = 0;
+        while reader.read_block_into(&mut block).unwrap() {
+            n_records += block.n_records();
+        }
+        assert_eq!(n_records, 500);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_flag_free_records() {
+        use crate::SequencingRecordBuilder;
+        use crate::vbq::{FileHeaderBuilder, WriterBuilder};
+
+        let n_records = 100;
+        let mut records_per_block = Vec::new();
+        for flags in [false, true] {
+            let path = std::env::temp_dir().join(format!("binseq_test_flag_free_{flags}.vbq"));
+            let header = FileHeaderBuilder::new().block(1024).flags(flags).build();
+            let mut writer = WriterBuilder::default()
+                .header(header)
+                .build(File::create(&path).unwrap())
+                .unwrap();
+            for idx in 0..n_records {
+                let record = SequencingRecordBuilder::default()
+                    .s_seq(&b"ACGT".repeat(8))
+                    .flag(idx as u64 + 1)
+                    .build()
+                    .unwrap();
+                writer.push(record).unwrap();
+            }
+            writer.finish().unwrap();
+
+            let mut reader = MmapReader::new(&path).unwrap();
+            assert_eq!(reader.header().flags, flags);
+            let mut block = reader.new_block();
+            let mut idx = 0;
+            while reader.read_block_into(&mut block).unwrap() {
+                for record in block.iter() {
+                    let expected = flags.then_some(idx as u64 + 1);
+                    assert_eq!(record.flag(), expected);
+                    assert_eq!(record.decode_s_alloc().unwrap(), b"ACGT".repeat(8));
+                    idx += 1;
+                }
+            }
+            assert_eq!(idx, n_records);
+            records_per_block.push(reader.load_index().unwrap().ranges()[0].block_records);
+
+            std::fs::remove_file(&path).unwrap();
+        }
+
+        // Flag-free records are 24 bytes instead of 32, so more fit in each block
+        assert_eq!(records_per_block, vec![42, 32]);
+    }
+
+    #[test]
+    fn test_read_block_at_index_reverse() {
+        let path = std::env::temp_dir().join("binseq_test_read_block_at_index.vbq");
+
+        // Each record is 24 bytes (2 length prefixes + 1 word), so 42 fit in a 1024-byte block
+        let records_per_block = 42;
+        let n_records = records_per_block * 4 + 10;
+        write_small_block_file(&path, n_records, 1024, 32);
+
+        let reader = MmapReader::new(&path).unwrap();
+        let n_blocks = reader.load_index().unwrap().n_blocks();
+        assert_eq!(n_blocks, 5);
+
+        let mut block = reader.new_block();
+        for block_idx in (0..n_blocks).rev() {
+            assert!(reader.read_block_at_index(block_idx, &mut block).unwrap());
+            let expected_len = if block_idx == n_blocks - 1 {
+                10
+            } else {
+                records_per_block
+            };
+            assert_eq!(block.n_records(), expected_len);
+            for (offset, record) in block.iter().enumerate() {
+                assert_eq!(
+                    record.index() as usize,
+                    block_idx * records_per_block + offset
+                );
+                assert_eq!(record.slen(), 32);
+            }
+        }
+
+        // Out of range block indices are not an error
+        assert!(!reader.read_block_at_index(n_blocks, &mut block).unwrap());
+        assert_eq!(block.n_records(), 0);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_read_block_at_index_does_not_advance() {
+        let mut reader = MmapReader::new(TEST_VBQ_FILE).unwrap();
+        let mut random_block = reader.new_block();
+        let mut seq_block = reader.new_block();
+
+        // Random access to the second block does not move the sequential cursor
+        assert!(reader.read_block_at_index(1, &mut random_block).unwrap());
+        assert!(reader.read_block_into(&mut seq_block).unwrap());
+        assert_eq!(seq_block.iter().next().unwrap().index(), 0);
+
+        // The first block read both ways is identical
+        assert!(reader.read_block_at_index(0, &mut random_block).unwrap());
+        assert_eq!(random_block.n_records(), seq_block.n_records());
+        for (a, b) in random_block.iter().zip(seq_block.iter()) {
+            assert_eq!(a.index(), b.index());
+            assert_eq!(a.sbuf(), b.sbuf());
+        }
+    }
+
+    // ==================== Head / Tail Tests ====================
+
+    #[test]
+    fn test_head_tail_across_blocks() {
+        let path = std::env::temp_dir().join("binseq_test_head_tail.vbq");
+
+        // 42 records per block, with a small final block of 10 records
+        let n_records = 42 * 4 + 10;
+        write_small_block_file(&path, n_records, 1024, 32);
+        let reader = MmapReader::new(&path).unwrap();
+
+        let head = reader.head(50).unwrap();
+        assert_eq!(head.len(), 50);
+        for (idx, record) in head.iter().enumerate() {
+            assert_eq!(record.index() as usize, idx);
+        }
+
+        // The tail spans the small final block and part of the preceding block
+        let tail = reader.tail(25).unwrap();
+        assert_eq!(tail.len(), 25);
+        for (offset, record) in tail.iter().enumerate() {
+            assert_eq!(record.index() as usize, n_records - 25 + offset);
+            assert_eq!(record.decode_s_alloc().unwrap(), b"ACGT".repeat(8));
+        }
+
+        // Requesting more records than exist returns everything
+        assert_eq!(reader.head(n_records + 1).unwrap().len(), n_records);
+        assert_eq!(reader.tail(n_records + 1).unwrap().len(), n_records);
+        assert!(reader.tail(0).unwrap().is_empty());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_tail_matches_sequential() {
+        let mut reader = MmapReader::new(TEST_VBQ_FILE).unwrap();
+        let total = reader.num_records().unwrap();
+        let tail = reader.tail(3).unwrap();
+
+        let mut block = reader.new_block();
+        let mut expected = Vec::new();
+        while reader.read_block_into(&mut block).unwrap() {
+            for record in block.iter() {
+                if record.index() as usize >= total - 3 {
+                    expected.push(record.decode_s_alloc().unwrap());
+                }
+            }
+        }
+        let observed: Vec<_> = tail.iter().map(|r| r.decode_s_alloc().unwrap()).collect();
+        assert_eq!(observed, expected);
+    }
+
+    #[test]
+    fn test_to_vec_owned_outlives_block_reuse() {
+        let path = std::env::temp_dir().join("binseq_test_to_vec_owned.vbq");
+        write_small_block_file(&path, 42 * 2, 1024, 32);
+        let mut reader = MmapReader::new(&path).unwrap();
+        let mut block = reader.new_block();
+
+        assert!(reader.read_block_into(&mut block).unwrap());
+        let expected: Vec<_> = block
+            .iter()
+            .map(|r| (r.index(), r.decode_s_alloc().unwrap(), r.sheader().to_vec()))
+            .collect();
+        let owned = block.to_vec_owned();
+        let first = block.first_owned().unwrap();
+
+        // Refill the block with the next block's records
+        assert!(reader.read_block_into(&mut block).unwrap());
+        assert_ne!(block.iter().next().unwrap().index(), expected[0].0);
+
+        assert_eq!(owned.len(), expected.len());
+        for (record, (index, seq, header)) in owned.iter().zip(&expected) {
+            assert_eq!(record.index(), *index);
+            assert_eq!(record.decode_s_alloc().unwrap(), *seq);
+            assert_eq!(record.sheader(), header.as_slice());
+        }
+        assert_eq!(first.index(), expected[0].0);
+
+        block.clear();
+        assert!(block.first_owned().is_none());
+        assert!(block.to_vec_owned().is_empty());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_sparse_index() {
+        use crate::SequencingRecordBuilder;
+        use crate::vbq::{FileHeaderBuilder, WriterBuilder};
+
+        let dense_path = std::env::temp_dir().join("binseq_test_index_dense.vbq");
+        let sparse_path = std::env::temp_dir().join("binseq_test_index_sparse.vbq");
+        let n_records = 42 * 9 + 10;
+        write_small_block_file(&dense_path, n_records, 1024, 32);
+
+        let seq = b"ACGT".repeat(8);
+        let header = FileHeaderBuilder::new().block(1024).build();
+        let mut writer = WriterBuilder::default()
+            .header(header)
+            .index_stride(4)
+            .build(File::create(&sparse_path).unwrap())
+            .unwrap();
+        for _ in 0..n_records {
+            let record = SequencingRecordBuilder::default()
+                .s_seq(&seq)
+                .build()
+                .unwrap();
+            writer.push(record).unwrap();
+        }
+        writer.finish().unwrap();
+
+        let dense = MmapReader::new(&dense_path).unwrap();
+        let sparse = MmapReader::new(&sparse_path).unwrap();
+
+        // The record count is read from the index header
+        assert_eq!(sparse.num_records().unwrap(), n_records);
+        assert!(sparse.index.get().is_none());
+
+        let stored = sparse.load_index().unwrap();
+        assert_eq!(stored.stride(), 4);
+        assert_eq!(
+            stored.n_blocks(),
+            dense.load_index().unwrap().n_blocks().div_ceil(4)
+        );
+
+        for record_idx in 0..=n_records {
+            assert_eq!(
+                sparse.find_record_block(record_idx).unwrap(),
+                dense.find_record_block(record_idx).unwrap()
+            );
+        }
+        assert_eq!(
+            sparse.index().unwrap().ranges(),
+            dense.index().unwrap().ranges()
+        );
+        assert_eq!(sparse.tail(50).unwrap(), dense.tail(50).unwrap());
+
+        std::fs::remove_file(&dense_path).unwrap();
+        std::fs::remove_file(&sparse_path).unwrap();
+    }
+
+    #[test]
+    fn test_decode_masked_roundtrip() {
+        use crate::SequencingRecordBuilder;
+        use crate::vbq::{FileHeaderBuilder, WriterBuilder};
+
+        let path = std::env::temp_dir().join("binseq_test_decode_masked.vbq");
+        let header = FileHeaderBuilder::new().qual(true).paired(true).build();
+        let mut writer = WriterBuilder::default()
+            .header(header)
+            .build(File::create(&path).unwrap())
+            .unwrap();
+        let record = SequencingRecordBuilder::default()
+            .s_seq(b"ACGTACGT")
+            .s_qual(b"II#III5I")
+            .x_seq(b"TTTTGGGG")
+            .x_qual(b"#IIIIII?")
+            .build()
+            .unwrap();
+        writer.push(record).unwrap();
+        writer.finish().unwrap();
+        drop(writer);
+
+        let mut reader = MmapReader::new(&path).unwrap();
+        let mut block = reader.new_block();
+        assert!(reader.read_block_into(&mut block).unwrap());
+        let record = block.iter().next().unwrap();
+
+        let mut buf = Vec::new();
+        record.decode_masked(30, &mut buf).unwrap();
+        assert_eq!(buf, b"ACNTACNT");
+
+        buf.clear();
+        record.decode_x_masked(30, &mut buf).unwrap();
+        assert_eq!(buf, b"NTTTGGGG");
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    /// Counts processed records and blocks
+    #[derive(Clone, Default)]
+    struct BlockCounter {
+        records: Arc<std::sync::Mutex<Vec<u64>>>,
+        blocks: Arc<std::sync::Mutex<usize>>,
+    }
+
+    impl ParallelProcessor for BlockCounter {
+        fn process_record<R: BinseqRecord>(&mut self, record: R) -> Result<()> {
+            self.records.lock().unwrap().push(record.index());
+            Ok(())
+        }
+
+        fn on_batch_complete(&mut self) -> Result<()> {
+            *self.blocks.lock().unwrap() += 1;
+            Ok(())
+        }
+    }
+
+    /// Writes 2000 flagged records where only records 500..600 and 1500..1550 have bit 0x4
+    fn write_flagged_file(path: &Path, flag_summary: bool) {
+        use crate::SequencingRecordBuilder;
+        use crate::vbq::{FileHeaderBuilder, WriterBuilder};
+
+        let header = FileHeaderBuilder::new().flags(true).block(1024).build();
+        let mut writer = WriterBuilder::default()
+            .header(header)
+            .index_flag_summary(flag_summary)
+            .build(File::create(path).unwrap())
+            .unwrap();
+        for idx in 0..2000u64 {
+            let flag = if (500..600).contains(&idx) || (1500..1550).contains(&idx) {
+                0x4 | (idx % 2)
+            } else {
+                idx % 2
+            };
+            let record = SequencingRecordBuilder::default()
+                .s_seq(b"ACGTACGTACGTACGT")
+                .flag(flag)
+                .build()
+                .unwrap();
+            writer.push(record).unwrap();
+        }
+        writer.finish().unwrap();
+    }
+
+    #[test]
+    fn test_flag_summary_index_roundtrip() {
+        let path = std::env::temp_dir().join("binseq_test_flag_summary_index.vbq");
+        write_flagged_file(&path, true);
+
+        let reader = MmapReader::new(&path).unwrap();
+        let index = reader.load_index().unwrap();
+        assert!(index.header.has_flag_summary());
+        for range in index.ranges() {
+            let start = range.cumulative_records;
+            let flags: Vec<u64> = (start..start + u64::from(range.block_records))
+                .map(|idx| {
+                    let bit = (500..600).contains(&idx) || (1500..1550).contains(&idx);
+                    (u64::from(bit) * 0x4) | (idx % 2)
+                })
+                .collect();
+            let flag_or = flags.iter().fold(0, |acc, flag| acc | flag);
+            let flag_and = flags.iter().fold(u64::MAX, |acc, flag| acc & flag);
+            assert_eq!(range.flag_or(), Some(flag_or));
+            assert_eq!(range.flag_and(), Some(flag_and));
+        }
+
+        // Sparse indices keep the summaries of their entries
+        let sparse = index.to_sparse(3);
+        let mut buffer = Vec::new();
+        sparse.write_bytes(&mut buffer).unwrap();
+        let parsed = BlockIndex::from_bytes(&buffer).unwrap();
+        assert_eq!(parsed.ranges(), sparse.ranges());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_process_parallel_filtered_skips_blocks() {
+        for flag_summary in [true, false] {
+            let path =
+                std::env::temp_dir().join(format!("binseq_test_flag_summary_{flag_summary}.vbq"));
+            write_flagged_file(&path, flag_summary);
+            let index = MmapReader::new(&path).unwrap().load_index().unwrap();
+            assert_eq!(index.header.has_flag_summary(), flag_summary);
+
+            // Blocks holding at least one record with bit 0x4
+            let matching_blocks = index
+                .ranges()
+                .iter()
+                .filter(|range| {
+                    let (start, end) = (
+                        range.cumulative_records,
+                        range.cumulative_records + u64::from(range.block_records),
+                    );
+                    (start < 600 && end > 500) || (start < 1550 && end > 1500)
+                })
+                .count();
+            assert!(matching_blocks < index.n_blocks());
+
+            let counter = BlockCounter::default();
+            MmapReader::new(&path)
+                .unwrap()
+                .process_parallel_filtered(counter.clone(), 2, FlagFilter::new().require(0x4))
+                .unwrap();
+            let mut records = counter.records.lock().unwrap().clone();
+            records.sort_unstable();
+            let expected: Vec<u64> = (500..600).chain(1500..1550).collect();
+            assert_eq!(records, expected);
+
+            // Without summaries every block is decompressed
+            let blocks = *counter.blocks.lock().unwrap();
+            if flag_summary {
+                assert_eq!(blocks, matching_blocks);
+            } else {
+                assert_eq!(blocks, index.n_blocks());
+            }
+
+            // Forbidding the bit selects the complement
+            let counter = BlockCounter::default();
+            MmapReader::new(&path)
+                .unwrap()
+                .process_parallel_filtered(counter.clone(), 2, FlagFilter::new().forbid(0x4))
+                .unwrap();
+            assert_eq!(counter.records.lock().unwrap().len(), 1850);
+
+            std::fs::remove_file(&path).unwrap();
+        }
+    }
+
+    #[cfg(feature = "work-stealing")]
+    #[derive(Clone, Default)]
+    struct IndexCollector {
+        indices: Arc<std::sync::Mutex<Vec<u64>>>,
+    }
+
+    #[cfg(feature = "work-stealing")]
+    impl ParallelProcessor for IndexCollector {
+        fn process_record<R: BinseqRecord>(&mut self, record: R) -> Result<()> {
+            self.indices.lock().unwrap().push(record.index());
+            Ok(())
+        }
+    }
+
+    #[cfg(feature = "work-stealing")]
+    #[test]
+    fn test_parallel_processing_work_stealing() {
+        let num_records = MmapReader::new(TEST_VBQ_FILE)
+            .unwrap()
+            .num_records()
+            .unwrap();
+        for range in [0..num_records, 10..num_records / 2] {
+            let reader = MmapReader::new(TEST_VBQ_FILE).unwrap();
+            let collector = IndexCollector::default();
+            reader
+                .process_parallel_work_stealing(collector.clone(), 4, range.clone())
+                .unwrap();
+
+            // Every record is processed exactly once
+            let mut indices = collector.indices.lock().unwrap().clone();
+            indices.sort_unstable();
+            let expected: Vec<u64> = range.map(|idx| idx as u64).collect();
+            assert_eq!(indices, expected);
+        }
+    }
+
+    #[test]
+    fn test_empty_file() {
+        use crate::vbq::{FileHeaderBuilder, WriterBuilder};
+
+        let path = std::env::temp_dir().join("binseq_test_empty.vbq");
+        {
+            let header = FileHeaderBuilder::new().qual(true).compressed(true).build();
+            let mut writer = WriterBuilder::default()
+                .header(header)
+                .build(File::create(&path).unwrap())
+                .unwrap();
+            writer.finish().unwrap();
+            // Finishing twice must not write a second index
+            writer.finish().unwrap();
+        }
+
+        let mut reader = MmapReader::new(&path).unwrap();
+        assert_eq!(reader.num_records().unwrap(), 0);
+        assert_eq!(reader.load_index().unwrap().n_blocks(), 0);
+        let mut block = reader.new_block();
+        assert!(!reader.read_block_into(&mut block).unwrap());
+
+        let processor = VbqCountingProcessor::default();
+        reader.process_parallel(processor.clone(), 2).unwrap();
+        assert_eq!(*processor.count.lock().unwrap(), 0);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_header_only_file() {
+        let path = std::env::temp_dir().join("binseq_test_header_only.vbq");
+        let mut bytes = Vec::new();
+        FileHeader::default().write_bytes(&mut bytes).unwrap();
+        std::fs::write(&path, &bytes).unwrap();
+
+        let mut reader = MmapReader::new(&path).unwrap();
+        assert_eq!(reader.num_records().unwrap(), 0);
+        let mut block = reader.new_block();
+        assert!(!reader.read_block_into(&mut block).unwrap());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_zero_byte_file() {
+        let path = std::env::temp_dir().join("binseq_test_zero_byte.vbq");
+        std::fs::write(&path, []).unwrap();
+
+        let result = MmapReader::new(&path);
+        assert!(matches!(
+            result,
+            Err(crate::Error::HeaderError(HeaderError::InvalidSize(
+                0,
+                SIZE_HEADER
+            )))
+        ));
+        assert!(BlockIndex::from_vbq(&path).is_err());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    // ==================== Index Validation Tests ====================
+
+    /// Writes an in-memory VBQ file with several small blocks
+    fn write_multi_block() -> Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        let header = crate::vbq::FileHeaderBuilder::new().block(256).build();
+        let mut writer = crate::vbq::WriterBuilder::default()
+            .header(header)
+            .build(&mut bytes)?;
+        for idx in 0..500 {
+            let seq = b"ACGTTGCA".repeat(1 + idx % 5);
+            writer.push(
+                crate::SequencingRecordBuilder::default()
+                    .s_seq(&seq)
+                    .build()?,
+            )?;
+        }
+        writer.finish()?;
+        drop(writer);
+        Ok(bytes)
+    }
+
+    #[test]
+    fn test_record_block_from_bytes() -> Result<()> {
+        for compressed in [false, true] {
+            let mut bytes = Vec::new();
+            let header = crate::vbq::FileHeaderBuilder::new()
+                .block(256)
+                .compressed(compressed)
+                .flags(true)
+                .build();
+            let mut writer = crate::vbq::WriterBuilder::default()
+                .header(header)
+                .build(&mut bytes)?;
+            for idx in 0..100 {
+                let seq = b"ACGTTGCA".repeat(1 + idx % 5);
+                let record = crate::SequencingRecordBuilder::default()
+                    .s_seq(&seq)
+                    .flag(idx as u64)
+                    .build()?;
+                writer.push(record)?;
+            }
+            writer.finish()?;
+            drop(writer);
+
+            let reader = MmapReader::from_bytes(bytes.clone())?;
+            let index = reader.load_index()?;
+            assert!(index.n_blocks() > 1);
+            let mut expected = reader.new_block();
+            for (block_idx, range) in index.ranges.iter().enumerate() {
+                let data = block_data(&bytes, range)?.to_vec();
+                let block = if compressed {
+                    RecordBlock::from_compressed_bytes(data, header)?
+                } else {
+                    RecordBlock::from_bytes(data, header)?
+                };
+                reader.read_block_at_index(block_idx, &mut expected)?;
+                assert_eq!(block.n_records(), expected.n_records());
+                for (record, expected) in block.iter().zip(expected.iter()) {
+                    assert_eq!(record.flag(), expected.flag());
+                    assert_eq!(record.decode_s_alloc()?, expected.decode_s_alloc()?);
+                }
+            }
+        }
+
+        // blocks may be short, but not longer than the block size
+        let header = crate::vbq::FileHeaderBuilder::new().block(256).build();
+        assert_eq!(
+            RecordBlock::from_bytes(vec![0; 255], header)?.n_records(),
+            0
+        );
+        assert!(matches!(
+            RecordBlock::from_bytes(vec![0; 257], header),
+            Err(crate::Error::ReadError(ReadError::BlockSizeMismatch {
+                expected_max: 256,
+                got: 257
+            }))
+        ));
+        Ok(())
+    }
+
+    /// Rebuilds a VBQ file without its index, with every block cut to its records and
+    /// followed by `trailing` as an extra block of zeros (if non-zero)
+    fn unpad_blocks(bytes: &[u8], compressed: bool, trailing: usize) -> Result<Vec<u8>> {
+        let index = MmapReader::from_bytes(bytes.to_vec())?.load_index()?;
+        let mut out = bytes[..SIZE_HEADER].to_vec();
+        let mut push_block = |data: &[u8], records: u32| -> Result<()> {
+            let data = if compressed {
+                zstd::bulk::compress(data, 0)?
+            } else {
+                data.to_vec()
+            };
+            BlockHeader::new(data.len() as u64, records).write_bytes(&mut out)?;
+            out.extend_from_slice(&data);
+            Ok(())
+        };
+        for range in &index.ranges {
+            let data = block_data(bytes, range)?;
+            let data = if compressed {
+                zstd::bulk::decompress(data, 256)?
+            } else {
+                data.to_vec()
+            };
+            // records are made of 8-byte words without qualities and headers
+            let used = data
+                .iter()
+                .rposition(|&b| b != 0)
+                .map_or(0, |pos| (pos + 1).next_multiple_of(8));
+            push_block(&data[..used], range.block_records)?;
+        }
+        if trailing > 0 {
+            push_block(&vec![0; trailing], 0)?;
+        }
+        Ok(out)
+    }
+
+    #[test]
+    fn test_read_short_and_oversized_blocks() -> Result<()> {
+        for compressed in [false, true] {
+            let mut bytes = Vec::new();
+            let header = crate::vbq::FileHeaderBuilder::new()
+                .block(256)
+                .compressed(compressed)
+                .build();
+            let mut writer = crate::vbq::WriterBuilder::default()
+                .header(header)
+                .build(&mut bytes)?;
+            for idx in 0..100 {
+                let seq = b"ACGTTGCA".repeat(1 + idx % 5);
+                writer.push(
+                    crate::SequencingRecordBuilder::default()
+                        .s_seq(&seq)
+                        .build()?,
+                )?;
+            }
+            writer.finish()?;
+            drop(writer);
+
+            let read_all = |bytes: Vec<u8>| -> Result<Vec<(u64, Vec<u8>)>> {
+                let mut reader = MmapReader::from_bytes(bytes)?;
+                let mut block = reader.new_block();
+                let mut records = Vec::new();
+                while reader.read_block_into(&mut block)? {
+                    for record in block.iter() {
+                        records.push((record.index(), record.decode_s_alloc()?));
+                    }
+                }
+                Ok(records)
+            };
+
+            // short blocks are read, and the next block is found after each of them
+            let expected = read_all(bytes.clone())?;
+            assert_eq!(expected.len(), 100);
+            assert_eq!(read_all(unpad_blocks(&bytes, compressed, 0)?)?, expected);
+
+            // a block longer than the block size is corrupt
+            assert!(matches!(
+                read_all(unpad_blocks(&bytes, compressed, 264)?),
+                Err(crate::Error::ReadError(ReadError::BlockSizeMismatch {
+                    expected_max: 256,
+                    got: 264
+                }))
+            ));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_block_range_and_split() -> Result<()> {
+        let reader = MmapReader::from_bytes(write_multi_block()?)?;
+        let n_blocks = reader.load_index()?.n_blocks();
+        assert!(n_blocks > 4);
+
+        let ranges = reader.split_into_ranges(4)?;
+        assert_eq!(ranges.len(), 4);
+        let covered: Vec<usize> = ranges.iter().cloned().flatten().collect();
+        assert_eq!(covered, (0..n_blocks).collect::<Vec<_>>());
+        let lengths: Vec<usize> = ranges.iter().map(ExactSizeIterator::len).collect();
+        assert!(lengths.iter().max().unwrap() - lengths.iter().min().unwrap() <= 1);
+
+        // Reading every range yields every record once, in order
+        let mut expected_index = 0;
+        for range in ranges {
+            for block in reader.read_block_range(range.start, range.end) {
+                for record in block?.iter() {
+                    assert_eq!(record.index(), expected_index);
+                    expected_index += 1;
+                }
+            }
+        }
+        assert_eq!(expected_index as usize, reader.num_records()?);
+
+        // Ranges past the end of the file are cut off
+        assert_eq!(
+            reader.read_block_range(n_blocks - 1, n_blocks + 5).count(),
+            1
+        );
+        assert_eq!(
+            reader.split_into_ranges(n_blocks + 2)?.last(),
+            Some(&(n_blocks..n_blocks))
+        );
+        assert!(reader.split_into_ranges(0)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_partition_blocks_random_indices() {
+        use rand::{Rng, SeedableRng, rngs::SmallRng};
+
+        let mut rng = SmallRng::seed_from_u64(42);
+        for _ in 0..200 {
+            let n_blocks = rng.random_range(1..60);
+            let mut cumulative = 0;
+            let blocks: Vec<BlockRange> = (0..n_blocks)
+                .map(|_| {
+                    let records = rng.random_range(1..500);
+                    let block =
+                        BlockRange::new(0, rng.random_range(1..10_000), records, cumulative);
+                    cumulative += u64::from(records);
+                    block
+                })
+                .collect();
+            let block_size = |block: &BlockRange| block.len + SIZE_BLOCK_HEADER as u64;
+            let total: u64 = blocks.iter().map(block_size).sum();
+            let largest = blocks.iter().map(block_size).max().unwrap();
+            let target = rng.random_range(0..total * 2);
+
+            let partitions = partition_blocks(&blocks, target);
+            assert_eq!(partitions.first().unwrap().start, 0);
+            assert_eq!(partitions.last().unwrap().end, cumulative);
+            for pair in partitions.windows(2) {
+                assert_eq!(pair[0].end, pair[1].start);
+            }
+            for partition in &partitions {
+                assert!(partition.start < partition.end);
+                // partitions hold whole blocks and miss the target by less than one block
+                let size: u64 = blocks
+                    .iter()
+                    .filter(|block| partition.contains(&block.cumulative_records))
+                    .inspect(|block| assert!(block.record_global_range().end <= partition.end))
+                    .map(block_size)
+                    .sum();
+                assert!(size < target + largest);
+            }
+            if target >= total {
+                assert_eq!(partitions.len(), 1);
+            }
+        }
+        assert_eq!(partition_blocks(&[], 100), vec![0..0]);
+    }
+
+    #[test]
+    fn test_partition_by_bytes() -> Result<()> {
+        use crate::processors::CountProcessor;
+
+        let bytes = write_multi_block()?;
+        let reader = MmapReader::from_bytes(bytes.clone())?;
+        let n_records = reader.num_records()?;
+        assert_eq!(reader.partition_by_bytes(u64::MAX)?, vec![0..n_records]);
+
+        let partitions = reader.partition_by_bytes(reader.index()?.ranges()[0].len * 2)?;
+        assert!(partitions.len() > 1);
+        let mut counted = 0;
+        for partition in partitions {
+            let counter = CountProcessor::new();
+            MmapReader::from_bytes(bytes.clone())?.process_parallel_range(
+                counter.clone(),
+                2,
+                partition.clone(),
+            )?;
+            assert_eq!(counter.count() as usize, partition.len());
+            counted += counter.count() as usize;
+        }
+        assert_eq!(counted, n_records);
+        Ok(())
+    }
+
+    /// Replaces the embedded index of a VBQ file
+    fn with_index(bytes: &[u8], index: &BlockIndex) -> Result<Vec<u8>> {
+        let mut file = bytes[..index.header.bytes() as usize].to_vec();
+        let mut index_bytes = Vec::new();
+        index.write_bytes(&mut index_bytes)?;
+        file.extend_from_slice(&index_bytes);
+        file.extend_from_slice(&(index_bytes.len() as u64).to_le_bytes());
+        file.extend_from_slice(&INDEX_END_MAGIC.to_le_bytes());
+        Ok(file)
+    }
+
+    #[test]
+    fn test_validate_index() -> Result<()> {
+        let bytes = write_multi_block()?;
+        let reader = MmapReader::from_bytes(bytes.clone())?;
+        let index = reader.load_index()?;
+        let n_blocks = index.n_blocks();
+        assert!(n_blocks > 4);
+
+        let report = reader.validate_index()?;
+        assert!(report.is_valid());
+        assert_eq!(report.blocks_checked, n_blocks);
+
+        // Shift the offset of one entry
+        let mut corrupted = index.clone();
+        corrupted.ranges[2].start_offset += 8;
+        let reader = MmapReader::from_bytes(with_index(&bytes, &corrupted)?)?;
+        let report = reader.validate_index()?;
+        assert!(!report.is_valid());
+        assert_eq!(report.blocks_checked, n_blocks);
+        assert_eq!(report.blocks_mismatched.len(), 1);
+        let (idx, detail) = report.blocks_mismatched[0];
+        assert_eq!(idx, 2);
+        assert_eq!(detail.index, corrupted.ranges[2]);
+        assert_eq!(detail.file, Some(index.ranges[2]));
+
+        // Sampling every other entry reads the block at the claimed offset
+        let report = reader.validate_index_sampled(0.5)?;
+        assert_eq!(report.blocks_checked, n_blocks.div_ceil(2));
+        assert_eq!(report.blocks_mismatched.len(), 1);
+        assert_eq!(report.blocks_mismatched[0].0, 2);
+        assert_eq!(report.blocks_mismatched[0].1.file, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_validate_index_extra_blocks() -> Result<()> {
+        let bytes = write_multi_block()?;
+        let index = MmapReader::from_bytes(bytes.clone())?.load_index()?;
+        let n_blocks = index.n_blocks();
+
+        // An index missing its last entry
+        let mut truncated = index.clone();
+        truncated.ranges.pop();
+        let report = MmapReader::from_bytes(with_index(&bytes, &truncated)?)?.validate_index()?;
+        assert_eq!(report.extra_blocks_in_file, 1);
+        assert_eq!(report.extra_blocks_in_index, 0);
+        assert_eq!(report.blocks_checked, n_blocks - 1);
+        assert!(report.blocks_mismatched.is_empty());
+
+        // An index with an entry past the end of the file
+        let mut extended = index.clone();
+        let last = *extended.ranges.last().unwrap();
+        extended.ranges.push(BlockRange::new(
+            last.start_offset + SIZE_BLOCK_HEADER as u64 + last.len,
+            last.len,
+            last.block_records,
+            last.cumulative_records + u64::from(last.block_records),
+        ));
+        let report = MmapReader::from_bytes(with_index(&bytes, &extended)?)?.validate_index()?;
+        assert_eq!(report.extra_blocks_in_file, 0);
+        assert_eq!(report.extra_blocks_in_index, 1);
+        assert!(!report.is_valid());
+        Ok(())
+    }
+
+    /// Builds raw block data from little-endian words and trailing bytes
+    fn raw_block(words: &[u64], tail: &[u8]) -> Vec<u8> {
+        let mut bytes: Vec<u8> = words.iter().flat_map(|w| w.to_le_bytes()).collect();
+        bytes.extend_from_slice(tail);
+        bytes
+    }
+
+    fn assert_corrupt(result: Result<()>, expected: &str) {
+        match result {
+            Err(crate::Error::ReadError(ReadError::CorruptBlock { reason, .. })) => {
+                assert_eq!(reason, expected);
+            }
+            other => panic!("expected a corrupt block error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_parse_records_corrupt_blocks() {
+        let mut block = RecordBlock::new(BitSize::Two, 1024);
+
+        // Sequence length far beyond the block
+        let bytes = raw_block(&[u64::MAX, 0], &[]);
+        assert_corrupt(
+            block.ingest_bytes(&bytes, false, false, false, false),
+            "primary sequence exceeds the block",
+        );
+
+        // Quality scores cut short
+        let bytes = raw_block(&[32, 0, 0], &[b'I'; 10]);
+        assert_corrupt(
+            block.ingest_bytes(&bytes, true, false, false, false),
+            "primary quality scores exceed the block",
+        );
+
+        // Header length overflowing `usize` arithmetic
+        let bytes = raw_block(&[32, 0, 0, u64::MAX - 4], &[]);
+        assert_corrupt(
+            block.ingest_bytes(&bytes, false, true, false, false),
+            "primary header exceeds the block",
+        );
+
+        // Header length field cut short
+        let bytes = raw_block(&[32, 0, 0], &[0; 4]);
+        assert_corrupt(
+            block.ingest_bytes(&bytes, false, true, false, false),
+            "truncated header length",
+        );
+
+        // Extended sequence past the end of the block
+        let bytes = raw_block(&[32, 64, 0, 0], &[]);
+        assert_corrupt(
+            block.ingest_bytes(&bytes, false, false, false, false),
+            "extended sequence exceeds the block",
+        );
+    }
+
+    #[test]
+    fn test_parse_records_valid_block() -> Result<()> {
+        let mut block = RecordBlock::new(BitSize::Two, 1024);
+
+        // Two records followed by zero padding
+        let mut bytes = raw_block(&[4, 0, 0b1110_0100], &[]);
+        bytes.extend(raw_block(&[4, 0, 0b0001_1011], &[]));
+        bytes.resize(bytes.len() + 20, 0);
+        block.ingest_bytes(&bytes, false, false, false, false)?;
+        assert_eq!(block.n_records(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_records_header_prefix() -> Result<()> {
+        let mut block = RecordBlock::new(BitSize::Two, 1024);
+        block.header_prefix = true;
+
+        // Prefix "rd:" followed by records with the suffixes "1" and "" (prefix-only)
+        let mut bytes = vec![PREFIX_MARKER, 3, b'r', b'd', b':'];
+        bytes.extend(raw_block(&[4, 0, 0b1110_0100], &[1, b'1']));
+        bytes.extend(raw_block(&[4, 0, 0b0001_1011], &[0]));
+        bytes.resize(bytes.len() + 20, 0);
+        block.ingest_bytes(&bytes, false, true, false, false)?;
+        let headers: Vec<Vec<u8>> = block.iter().map(|r| r.sheader().to_vec()).collect();
+        assert_eq!(headers, [b"rd:1".to_vec(), b"rd:".to_vec()]);
+
+        // Empty blocks have no prefix
+        block.ingest_bytes(&[], false, true, false, false)?;
+        assert_eq!(block.n_records(), 0);
+
+        bytes[0] = b'X';
+        assert_corrupt(
+            block.ingest_bytes(&bytes, false, true, false, false),
+            "missing header prefix marker",
+        );
+        assert_corrupt(
+            block.ingest_bytes(&[PREFIX_MARKER, 10, b'r'], false, true, false, false),
+            "header prefix exceeds the block",
+        );
+        assert_corrupt(
+            block.ingest_bytes(&[PREFIX_MARKER, 0x80], false, true, false, false),
+            "truncated header prefix",
+        );
+
+        // Suffix length cut short
+        let mut bytes = vec![PREFIX_MARKER, 0];
+        bytes.extend(raw_block(&[4, 0, 0b1110_0100], &[0x80]));
+        assert_corrupt(
+            block.ingest_bytes(&bytes, false, true, false, false),
+            "truncated header length",
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_block_at_index_out_of_file() -> Result<()> {
+        let bytes = write_multi_block()?;
+        let mut index = MmapReader::from_bytes(bytes.clone())?.load_index()?;
+        index.ranges[0].start_offset = u64::MAX - 4;
+        index.ranges[1].len = u64::MAX;
+        let reader = MmapReader::from_bytes(with_index(&bytes, &index)?)?;
+
+        let mut block = reader.new_block();
+        for block_idx in 0..2 {
+            assert!(matches!(
+                reader.read_block_at_index(block_idx, &mut block),
+                Err(crate::Error::ReadError(ReadError::UnexpectedEndOfFile(_)))
+            ));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_quality_at() -> Result<()> {
+        use crate::SequencingRecord;
+        use crate::vbq::{FileHeaderBuilder, WriterBuilder};
+
+        let qual = |idx: usize, len: usize| -> Vec<u8> {
+            (0..len).map(|j| b'!' + ((idx + j) % 40) as u8).collect()
+        };
+        let header = FileHeaderBuilder::new()
+            .block(512)
+            .qual(true)
+            .paired(true)
+            .compressed(true)
+            .build();
+        let n_records = 100;
+        let mut bytes = Vec::new();
+        {
+            let mut writer = WriterBuilder::default().header(header).build(&mut bytes)?;
+            for idx in 0..n_records {
+                let (sseq, xseq) = (b"ACGT".repeat(5 + idx % 3), b"TTGCA".repeat(3));
+                let (squal, xqual) = (qual(idx, sseq.len()), qual(idx + 1, xseq.len()));
+                writer.push(SequencingRecord::new(
+                    &sseq,
+                    Some(&squal),
+                    None,
+                    Some(&xseq),
+                    Some(&xqual),
+                    None,
+                    None,
+                ))?;
+            }
+            writer.finish()?;
+        }
+        let reader = MmapReader::from_bytes(bytes)?;
+        assert!(reader.load_index()?.n_blocks() > 1);
+
+        let mut buf = vec![b'X'; 3];
+        for idx in [0, 1, 37, n_records - 1] {
+            reader.read_quality_at(idx as u64, &mut buf)?;
+            assert_eq!(buf, qual(idx, 4 * (5 + idx % 3)));
+            reader.read_xquality_at(idx as u64, &mut buf)?;
+            assert_eq!(buf, qual(idx + 1, 15));
+        }
+        assert!(matches!(
+            reader.read_quality_at(n_records as u64, &mut buf),
+            Err(crate::Error::ReadError(ReadError::OutOfRange { .. }))
+        ));
+
+        // files without quality scores
+        let mut bytes = Vec::new();
+        {
+            let mut writer = WriterBuilder::default().build(&mut bytes)?;
+            writer.push(SequencingRecord::new(
+                b"ACGT", None, None, None, None, None, None,
+            ))?;
+            writer.finish()?;
+        }
+        let reader = MmapReader::from_bytes(bytes)?;
+        assert!(matches!(
+            reader.read_quality_at(0, &mut buf),
+            Err(crate::Error::ReadError(ReadError::NoQualityData))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_unmapped_reader_matches_mapped() -> Result<()> {
+        let mut mapped = MmapReader::new(TEST_VBQ_FILE)?;
+        let mut unmapped = MmapReader::new_unmapped(TEST_VBQ_FILE)?;
+        assert!(mapped.is_mapped());
+        assert!(!unmapped.is_mapped());
+        assert_eq!(unmapped.num_records()?, mapped.num_records()?);
+        assert!(unmapped.validate_index()?.is_valid());
+
+        let (mut a, mut b) = (mapped.new_block(), unmapped.new_block());
+        while mapped.read_block_into(&mut a)? {
+            assert!(unmapped.read_block_into(&mut b)?);
+            assert_eq!(a.to_vec_owned(), b.to_vec_owned());
+        }
+        assert!(!unmapped.read_block_into(&mut b)?);
+
+        let last = mapped.load_index()?.n_blocks() - 1;
+        assert!(mapped.read_block_at_index(last, &mut a)?);
+        assert!(unmapped.read_block_at_index(last, &mut b)?);
+        assert_eq!(a.to_vec_owned(), b.to_vec_owned());
+
+        let mut copy = Vec::new();
+        unmapped.write_to(&mut copy)?;
+        assert_eq!(copy, std::fs::read(TEST_VBQ_FILE)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_checked_usize() {
+        assert_eq!(checked_usize(42).unwrap(), 42);
+        if usize::BITS < 64 {
+            assert!(matches!(
+                checked_usize(u64::MAX),
+                Err(crate::Error::ReadError(ReadError::OffsetOverflow(u64::MAX)))
+            ));
+        }
+    }
 }
@@ -0,0 +1,219 @@
+//! Rewriting the headers of a VBQ file
+//!
+//! Records are copied in their encoded form, so only the header bytes change between the
+//! input and the output.
+
+use std::io::Write;
+use std::path::Path;
+
+use super::{EncodedRecord, FileHeaderBuilder, MmapReader, WriterBuilder};
+use crate::BinseqRecord;
+use crate::error::Result;
+
+/// Copies a VBQ file to `out`, replacing the header of every sequence
+///
+/// `f` is called with each header (primary and extended) and a cleared buffer receiving the
+/// new header. Sequences, quality scores and flags are copied without decoding (see
+/// [`Writer::write_encoded_record`](super::Writer::write_encoded_record)), as are soft-masks.
+///
+/// The output uses the configuration of the input, except that it always stores headers:
+/// for inputs without headers, `f` is called with empty headers. Records whose new headers
+/// no longer fit in a block are reported as `WriteError::RecordSizeExceedsMaximumBlockSize`.
+///
+/// Returns the number of records written.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use binseq::vbq::rewrite_headers;
+/// use std::fs::File;
+///
+/// // Strip comments after the first space
+/// let out = File::create("renamed.vbq").unwrap();
+/// rewrite_headers("input.vbq", out, |header, new| {
+///     let end = header.iter().position(|&b| b == b' ').unwrap_or(header.len());
+///     new.extend_from_slice(&header[..end]);
+/// })
+/// .unwrap();
+/// ```
+pub fn rewrite_headers<P, W, F>(input: P, out: W, mut f: F) -> Result<usize>
+where
+    P: AsRef<Path>,
+    W: Write,
+    F: FnMut(&[u8], &mut Vec<u8>),
+{
+    let mut reader = MmapReader::new(input)?;
+    let input_header = reader.header();
+    let header = FileHeaderBuilder::new()
+        .block(input_header.block)
+        .compressed(input_header.compressed)
+        .bitsize(input_header.bits)
+        .flags(input_header.flags)
+        .qual(input_header.qual)
+        .paired(input_header.paired)
+        .masked(input_header.masked)
+        .header_prefix(input_header.header_prefix)
+        .headers(true)
+        .build();
+    let mut writer = WriterBuilder::default().header(header).build(out)?;
+
+    let mut block = reader.new_block();
+    let (mut sheader, mut xheader) = (Vec::new(), Vec::new());
+    let mut records = 0;
+    while reader.read_block_into(&mut block)? {
+        for record in block.iter() {
+            sheader.clear();
+            f(record.sheader(), &mut sheader);
+            xheader.clear();
+            if record.is_paired() {
+                f(record.xheader(), &mut xheader);
+            }
+            writer.write_encoded_record(EncodedRecord {
+                sheader: &sheader,
+                xheader: &xheader,
+                smask: record.smask().unwrap_or_default(),
+                xmask: record.xmask().unwrap_or_default(),
+                ..EncodedRecord::from_record(&record)
+            })?;
+            records += 1;
+        }
+    }
+    writer.finish()?;
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+
+    use super::*;
+    use crate::SequencingRecordBuilder;
+    use crate::vbq::FileHeader;
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("binseq_test_rewrite_{name}.vbq"))
+    }
+
+    fn write_file(path: &Path, header: FileHeader, n: usize) {
+        let mut writer = WriterBuilder::default()
+            .header(header)
+            .build(File::create(path).unwrap())
+            .unwrap();
+        for idx in 0..n {
+            let sseq = b"ACGTT".repeat(1 + idx % 13);
+            let xseq = b"TTGCA".repeat(2 + idx % 7);
+            let squal = vec![b'!' + (idx % 40) as u8; sseq.len()];
+            let xqual = vec![b'#'; xseq.len()];
+            let sheader = format!("RUN1:{idx} 1:N:0");
+            let xheader = format!("RUN1:{idx} 2:N:0");
+            let mut builder = SequencingRecordBuilder::default()
+                .s_seq(&sseq)
+                .s_qual(&squal)
+                .s_header(sheader.as_bytes())
+                .flag(idx as u64);
+            if header.paired {
+                builder = builder
+                    .x_seq(&xseq)
+                    .x_qual(&xqual)
+                    .x_header(xheader.as_bytes());
+            }
+            writer.push(builder.build().unwrap()).unwrap();
+        }
+        writer.finish().unwrap();
+    }
+
+    /// Returns the headers and the non-header contents of all records of a file
+    #[allow(clippy::type_complexity)]
+    fn read_file(
+        path: &Path,
+    ) -> (
+        Vec<Vec<u8>>,
+        Vec<(Option<u64>, Vec<u64>, Vec<u64>, Vec<u8>)>,
+    ) {
+        let mut reader = MmapReader::new(path).unwrap();
+        let mut block = reader.new_block();
+        let (mut headers, mut contents) = (Vec::new(), Vec::new());
+        while reader.read_block_into(&mut block).unwrap() {
+            for record in block.iter() {
+                headers.push(record.sheader().to_vec());
+                if record.is_paired() {
+                    headers.push(record.xheader().to_vec());
+                }
+                let mut qual = record.squal().to_vec();
+                qual.extend_from_slice(record.xqual());
+                contents.push((
+                    record.flag(),
+                    record.sbuf().to_vec(),
+                    record.xbuf().to_vec(),
+                    qual,
+                ));
+            }
+        }
+        (headers, contents)
+    }
+
+    #[test]
+    fn test_rewrite_headers() {
+        for paired in [false, true] {
+            let (input, output) = (temp_path("input"), temp_path("output"));
+            let header = FileHeaderBuilder::new()
+                .block(1024)
+                .qual(true)
+                .headers(true)
+                .flags(true)
+                .paired(paired)
+                .build();
+            write_file(&input, header, 300);
+
+            let records = rewrite_headers(&input, File::create(&output).unwrap(), |h, new| {
+                let end = h.iter().position(|&b| b == b' ').unwrap_or(h.len());
+                new.extend_from_slice(b"RUN2");
+                new.extend_from_slice(&h[4..end]);
+            })
+            .unwrap();
+            assert_eq!(records, 300);
+
+            let (in_headers, in_contents) = read_file(&input);
+            let (out_headers, out_contents) = read_file(&output);
+            assert_eq!(
+                MmapReader::new(&output).unwrap().num_records().unwrap(),
+                300
+            );
+            assert_eq!(in_contents, out_contents);
+            assert_eq!(in_headers.len(), out_headers.len());
+            for (before, after) in in_headers.iter().zip(&out_headers) {
+                let before = std::str::from_utf8(before).unwrap();
+                let expected = before.split(' ').next().unwrap().replace("RUN1", "RUN2");
+                assert_eq!(after, expected.as_bytes());
+            }
+
+            for path in [input, output] {
+                std::fs::remove_file(path).unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn test_rewrite_headers_adds_headers() {
+        let (input, output) = (temp_path("noheader_input"), temp_path("noheader_output"));
+        write_file(&input, FileHeaderBuilder::new().qual(true).build(), 50);
+
+        let mut idx = 0;
+        rewrite_headers(&input, File::create(&output).unwrap(), |h, new| {
+            assert!(h.is_empty());
+            new.extend_from_slice(format!("read_{idx}").as_bytes());
+            idx += 1;
+        })
+        .unwrap();
+
+        let reader = MmapReader::new(&output).unwrap();
+        assert!(reader.header().headers);
+        let (headers, contents) = read_file(&output);
+        assert_eq!(headers[49], b"read_49");
+        assert_eq!(contents, read_file(&input).1);
+
+        for path in [input, output] {
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+}
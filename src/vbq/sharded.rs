@@ -0,0 +1,341 @@
+//! Writing VBQ output split across multiple files
+//!
+//! A [`ShardedWriter`] writes records to `<prefix>.0001.vbq`, `<prefix>.0002.vbq`, ... and
+//! starts a new shard once the current one reaches a size or record limit. Shards are only
+//! rotated between records, after the current block is written, so every shard is a complete
+//! VBQ file with its own header and embedded index.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use binseq::vbq::{FileHeaderBuilder, ShardedWriterBuilder};
+//! use binseq::SequencingRecordBuilder;
+//!
+//! let header = FileHeaderBuilder::new().compressed(true).build();
+//! let mut writer = ShardedWriterBuilder::default()
+//!     .header(header)
+//!     .max_shard_records(1_000_000)
+//!     .build("output")
+//!     .unwrap();
+//!
+//! let record = SequencingRecordBuilder::default()
+//!     .s_seq(b"ACGTACGT")
+//!     .build()
+//!     .unwrap();
+//! writer.push(record).unwrap();
+//! writer.finish().unwrap();
+//!
+//! // output.0001.vbq
+//! println!("{:?}", writer.shard_paths());
+//! ```
+
+use std::ffi::OsString;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+use super::{FileHeader, Writer, WriterBuilder};
+use crate::error::Result;
+use crate::policy::Policy;
+use crate::record::SequencingRecord;
+
+/// Builder for [`ShardedWriter`]
+///
+/// Without a limit all records are written to a single shard.
+#[derive(Debug, Default, Clone)]
+pub struct ShardedWriterBuilder {
+    /// Header shared by all shards
+    header: Option<FileHeader>,
+    /// Optional policy for encoding
+    policy: Option<Policy>,
+    /// Optional seed of the random number generator of the policy
+    policy_seed: Option<u64>,
+    /// Optional size limit of a shard in bytes
+    max_shard_bytes: Option<usize>,
+    /// Optional record limit of a shard
+    max_shard_records: Option<usize>,
+}
+impl ShardedWriterBuilder {
+    /// Sets the header of every shard
+    #[must_use]
+    pub fn header(mut self, header: FileHeader) -> Self {
+        self.header = Some(header);
+        self
+    }
+
+    /// Sets the policy for handling invalid nucleotides
+    #[must_use]
+    pub fn policy(mut self, policy: Policy) -> Self {
+        self.policy = Some(policy);
+        self
+    }
+
+    /// Sets the seed of the random number generator of the policy
+    ///
+    /// See [`WriterBuilder::policy_seed`]. The encoder is seeded again for every shard.
+    #[must_use]
+    pub fn policy_seed(mut self, seed: u64) -> Self {
+        self.policy_seed = Some(seed);
+        self
+    }
+
+    /// Sets the size at which a new shard is started
+    ///
+    /// The size counts the file header, the written blocks and the filled part of the
+    /// current block. Since shards are only rotated between records, a shard exceeds the
+    /// limit by up to one block and its embedded index.
+    #[must_use]
+    pub fn max_shard_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_shard_bytes = Some(max_bytes);
+        self
+    }
+
+    /// Sets the maximum number of records of a shard
+    ///
+    /// Records ingested with [`ShardedWriter::ingest`] are not split across shards, so a shard
+    /// may exceed the limit by the records of one ingested writer.
+    #[must_use]
+    pub fn max_shard_records(mut self, max_records: usize) -> Self {
+        self.max_shard_records = Some(max_records);
+        self
+    }
+
+    /// Creates the first shard and builds the [`ShardedWriter`]
+    ///
+    /// Shard paths are formed by appending `.0001.vbq`, `.0002.vbq`, ... to `prefix`.
+    pub fn build<P: AsRef<Path>>(self, prefix: P) -> Result<ShardedWriter> {
+        let mut writer = ShardedWriter {
+            prefix: prefix.as_ref().as_os_str().to_owned(),
+            header: self.header.unwrap_or_default(),
+            policy: self.policy.unwrap_or_default(),
+            policy_seed: self.policy_seed,
+            max_shard_bytes: self.max_shard_bytes.unwrap_or(usize::MAX),
+            max_shard_records: self.max_shard_records.unwrap_or(usize::MAX),
+            paths: Vec::new(),
+            writer: None,
+        };
+        writer.open_shard()?;
+        Ok(writer)
+    }
+}
+
+/// A VBQ writer that splits its output into shards of bounded size
+///
+/// See the [module documentation](self) for the shard layout. Records keep their order: the
+/// concatenated records of the shards, in the order of [`shard_paths`](Self::shard_paths),
+/// are the records written.
+pub struct ShardedWriter {
+    /// Path prefix of the shards
+    prefix: OsString,
+    /// Header shared by all shards
+    header: FileHeader,
+    /// Policy for encoding
+    policy: Policy,
+    /// Optional seed of the random number generator of the policy
+    policy_seed: Option<u64>,
+    /// Size at which a new shard is started
+    max_shard_bytes: usize,
+    /// Number of records at which a new shard is started
+    max_shard_records: usize,
+    /// Paths of all shards created so far
+    paths: Vec<PathBuf>,
+    /// Writer of the current shard
+    writer: Option<Writer<BufWriter<File>>>,
+}
+impl ShardedWriter {
+    /// Returns the header shared by all shards
+    #[must_use]
+    pub fn header(&self) -> FileHeader {
+        self.header
+    }
+
+    /// Returns the paths of all shards created so far, in order
+    #[must_use]
+    pub fn shard_paths(&self) -> &[PathBuf] {
+        &self.paths
+    }
+
+    /// Writes a record to the current shard, starting a new shard first if it is full
+    ///
+    /// Returns `Ok(false)` if the record was skipped by the invalid nucleotide policy. See
+    /// [`Writer::push`].
+    pub fn push(&mut self, record: SequencingRecord) -> Result<bool> {
+        self.rotate_if_full()?;
+        self.current().push(record)
+    }
+
+    /// Ingests the records of a headless writer into the current shard
+    ///
+    /// A new shard is started first if the current one is full. The records of `other` are
+    /// never split across shards. See [`Writer::ingest`].
+    pub fn ingest(&mut self, other: &mut Writer<Vec<u8>>) -> Result<()> {
+        self.rotate_if_full()?;
+        self.current().ingest(other)
+    }
+
+    /// Writes the remaining data and the embedded index of the current shard
+    pub fn finish(&mut self) -> Result<()> {
+        self.current().finish()
+    }
+
+    /// Returns the writer of the current shard
+    fn current(&mut self) -> &mut Writer<BufWriter<File>> {
+        self.writer
+            .as_mut()
+            .expect("ShardedWriter always holds an open shard")
+    }
+
+    /// Finishes the current shard and opens the next one if a limit is reached
+    fn rotate_if_full(&mut self) -> Result<()> {
+        let (bytes, records) = self.current().written();
+        if records > 0 && (bytes >= self.max_shard_bytes || records >= self.max_shard_records) {
+            self.current().finish()?;
+            self.open_shard()?;
+        }
+        Ok(())
+    }
+
+    /// Creates the next shard file and its writer
+    fn open_shard(&mut self) -> Result<()> {
+        let mut path = self.prefix.clone();
+        path.push(format!(".{:04}.vbq", self.paths.len() + 1));
+        let path = PathBuf::from(path);
+
+        let mut builder = WriterBuilder::default()
+            .header(self.header)
+            .policy(self.policy);
+        if let Some(seed) = self.policy_seed {
+            builder = builder.policy_seed(seed);
+        }
+        let writer = builder.build(BufWriter::new(File::create(&path)?))?;
+
+        // the previous shard is already finished, so dropping it only closes the file
+        self.writer = Some(writer);
+        self.paths.push(path);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BinseqRecord;
+    use crate::vbq::{FileHeaderBuilder, MmapReader};
+
+    fn sequence(idx: usize) -> Vec<u8> {
+        (0..40 + idx % 7)
+            .map(|j| b"ACGT"[(idx * 3 + j) % 4])
+            .collect()
+    }
+
+    #[test]
+    fn test_sharded_writer_rotates() -> Result<()> {
+        let prefix = std::env::temp_dir().join("binseq_test_sharded");
+        let header = FileHeaderBuilder::new().block(256).compressed(true).build();
+
+        let mut writer = ShardedWriterBuilder::default()
+            .header(header)
+            .max_shard_records(40)
+            .build(&prefix)?;
+        let n_records = 150;
+        for idx in 0..n_records {
+            let seq = sequence(idx);
+            assert!(writer.push(SequencingRecord::new(
+                &seq, None, None, None, None, None, None
+            ))?);
+        }
+        writer.finish()?;
+        let paths = writer.shard_paths().to_vec();
+        drop(writer);
+
+        assert_eq!(paths.len(), 4);
+        assert!(
+            paths[0]
+                .to_string_lossy()
+                .ends_with("binseq_test_sharded.0001.vbq")
+        );
+
+        let mut idx = 0;
+        let mut sbuf = Vec::new();
+        for path in &paths {
+            let mut reader = MmapReader::new(path)?;
+            assert!(reader.validate_index()?.is_valid());
+            assert!(reader.num_records()? <= 40);
+            let mut block = reader.new_block();
+            while reader.read_block_into(&mut block)? {
+                for record in block.iter() {
+                    sbuf.clear();
+                    record.decode_s(&mut sbuf)?;
+                    assert_eq!(sbuf, sequence(idx));
+                    idx += 1;
+                }
+            }
+            std::fs::remove_file(path)?;
+        }
+        assert_eq!(idx, n_records);
+        Ok(())
+    }
+
+    #[test]
+    fn test_sharded_writer_record_counts() -> Result<()> {
+        let prefix = std::env::temp_dir().join("binseq_test_sharded_counts");
+        let mut writer = ShardedWriterBuilder::default()
+            .max_shard_records(1000)
+            .build(&prefix)?;
+        for idx in 0..2500 {
+            let seq = sequence(idx);
+            writer.push(SequencingRecord::new(
+                &seq, None, None, None, None, None, None,
+            ))?;
+        }
+        writer.finish()?;
+        let paths = writer.shard_paths().to_vec();
+        drop(writer);
+
+        assert_eq!(paths.len(), 3);
+        for (path, expected) in paths.iter().zip([1000, 1000, 500]) {
+            assert_eq!(MmapReader::new(path)?.num_records()?, expected);
+            std::fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_sharded_writer_ingest() -> Result<()> {
+        let prefix = std::env::temp_dir().join("binseq_test_sharded_ingest");
+        let header = FileHeaderBuilder::new().block(256).build();
+
+        let mut writer = ShardedWriterBuilder::default()
+            .header(header)
+            .max_shard_bytes(1024)
+            .build(&prefix)?;
+        let mut idx = 0;
+        for _ in 0..6 {
+            let mut chunk = WriterBuilder::default()
+                .header(header)
+                .headless(true)
+                .build(Vec::new())?;
+            for _ in 0..10 {
+                let seq = sequence(idx);
+                chunk.push(SequencingRecord::new(
+                    &seq, None, None, None, None, None, None,
+                ))?;
+                idx += 1;
+            }
+            writer.ingest(&mut chunk)?;
+        }
+        writer.finish()?;
+        let paths = writer.shard_paths().to_vec();
+        drop(writer);
+
+        assert!(paths.len() >= 3);
+        let mut total = 0;
+        for path in &paths {
+            let reader = MmapReader::new(path)?;
+            total += reader.num_records()?;
+            std::fs::remove_file(path)?;
+        }
+        assert_eq!(total, idx);
+        Ok(())
+    }
+}
@@ -63,20 +63,100 @@
 //! ```
 
 use std::io::Write;
+use std::sync::{Arc, Mutex, PoisonError};
 
 use bitnuc::BitSize;
-use byteorder::{LittleEndian, WriteBytesExt};
+use byteorder::{ByteOrder, LittleEndian, WriteBytesExt};
 use rand::SeedableRng;
 use rand::rngs::SmallRng;
 use zstd::stream::copy_encode;
 
+use super::MmapReader;
+use super::checkpoint::{WriterCheckpoint, header_checksum};
 use super::header::{BlockHeader, FileHeader};
-use crate::SequencingRecord;
-use crate::error::{Result, WriteError};
+use super::mask::{CaseSplitter, mask_len};
+use super::prefix::{self, RecordLayout};
+use crate::error::{ReadError, Result, WriteError};
 use crate::policy::{Policy, RNG_SEED};
 use crate::vbq::header::{SIZE_BLOCK_HEADER, SIZE_HEADER};
 use crate::vbq::index::{INDEX_END_MAGIC, IndexHeader};
 use crate::vbq::{BlockIndex, BlockRange};
+use crate::{BinseqRecord, SequencingRecord};
+
+/// Determines how a `Writer` handles records that are too long
+///
+/// A record is considered oversized if any of its sequences is longer than the
+/// configured [`WriterBuilder::max_length`], or if its embedded size exceeds the
+/// block size of the file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OnOversize {
+    /// Return an error when an oversized record is pushed
+    #[default]
+    Error,
+
+    /// Drop oversized records (counted in [`WriteStats::skipped_long`])
+    Skip,
+
+    /// Hard-clip the sequences and quality scores to the given length, keeping the headers
+    /// (counted in [`WriteStats::truncated`])
+    ///
+    /// The length must be positive and at most the maximum length of the writer.
+    Truncate(usize),
+}
+
+/// Counts of records affected by the length policy of a `Writer`
+///
+/// See [`Writer::write_stats`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WriteStats {
+    /// Number of records dropped for being shorter than the minimum length
+    pub skipped_short: usize,
+
+    /// Number of oversized records dropped with [`OnOversize::Skip`]
+    pub skipped_long: usize,
+
+    /// Number of oversized records clipped with [`OnOversize::Truncate`]
+    pub truncated: usize,
+}
+
+/// Outcome of a block written by a `Writer`, passed to its block-flush observer
+///
+/// See [`WriterBuilder::on_block_flush`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BlockFlushInfo {
+    /// Size of the block data before compression (the block size, as blocks are zero-padded)
+    pub uncompressed_size: u64,
+
+    /// Size of the block data as stored (equal to `uncompressed_size` without compression)
+    pub compressed_size: u64,
+
+    /// Number of records in the block
+    pub records: u32,
+
+    /// Number of blocks written so far, including this one
+    pub total_blocks: usize,
+
+    /// Number of records written so far, including those of this block
+    pub total_records: usize,
+
+    /// Number of bytes written so far (file header and block headers included)
+    pub total_bytes: usize,
+}
+
+/// Callback invoked with every block written by a `Writer`
+pub type BlockFlushCallback = Box<dyn FnMut(BlockFlushInfo) + Send>;
+
+/// Optional block-flush observer, shared by clones of a `Writer`
+#[derive(Clone, Default)]
+struct FlushObserver(Option<Arc<Mutex<BlockFlushCallback>>>);
+impl FlushObserver {
+    fn notify(&self, info: BlockFlushInfo) {
+        if let Some(callback) = &self.0 {
+            let mut callback = callback.lock().unwrap_or_else(PoisonError::into_inner);
+            (*callback)(info);
+        }
+    }
+}
 
 /// A builder for creating configured `Writer` instances
 ///
@@ -113,6 +193,30 @@ pub struct WriterBuilder {
     policy: Option<Policy>,
     /// Optional headless mode (used in parallel writing)
     headless: Option<bool>,
+    /// Optional minimum sequence length
+    min_length: Option<usize>,
+    /// Optional maximum sequence length
+    max_length: Option<usize>,
+    /// Optional handling of oversized records
+    on_oversize: Option<OnOversize>,
+    /// Optional number of blocks per embedded index entry
+    index_stride: Option<usize>,
+    /// Optional strict mode (reject records with data the header does not store)
+    strict_mode: Option<bool>,
+    /// Optional flag summaries in the embedded index
+    index_flag_summary: Option<bool>,
+    /// Optional hint of the number of records to be written
+    estimated_records: Option<u64>,
+    /// Optional mean sequence length used with the number of records hint
+    mean_sequence_length: Option<usize>,
+    /// Optional seed of the random number generator of the policy
+    policy_seed: Option<u64>,
+    /// Optional observer of written blocks
+    on_block_flush: Option<BlockFlushCallback>,
+    /// Optional advanced zstd settings
+    zstd: Option<ZstdOptions>,
+    /// Optional checkpoint to resume from
+    checkpoint: Option<WriterCheckpoint>,
 }
 impl WriterBuilder {
     /// Sets the header for the VBQ file
@@ -205,6 +309,210 @@ impl WriterBuilder {
         self
     }
 
+    /// Sets the minimum sequence length
+    ///
+    /// Records with any sequence shorter than `min_length` are dropped: `push` returns
+    /// `Ok(false)` and the record is counted in [`WriteStats::skipped_short`].
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use binseq::vbq::WriterBuilder;
+    ///
+    /// let builder = WriterBuilder::default().min_length(50);
+    /// ```
+    #[must_use]
+    pub fn min_length(mut self, min_length: usize) -> Self {
+        self.min_length = Some(min_length);
+        self
+    }
+
+    /// Sets the maximum sequence length
+    ///
+    /// Records with any sequence longer than `max_length` are oversized and handled
+    /// according to [`WriterBuilder::on_oversize`].
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use binseq::vbq::{OnOversize, WriterBuilder};
+    ///
+    /// let builder = WriterBuilder::default()
+    ///     .max_length(150)
+    ///     .on_oversize(OnOversize::Truncate(150));
+    /// ```
+    #[must_use]
+    pub fn max_length(mut self, max_length: usize) -> Self {
+        self.max_length = Some(max_length);
+        self
+    }
+
+    /// Sets how oversized records are handled
+    ///
+    /// Defaults to [`OnOversize::Error`]. [`build`](Self::build) rejects
+    /// [`OnOversize::Truncate`] lengths of zero or above the [`max_length`](Self::max_length).
+    #[must_use]
+    pub fn on_oversize(mut self, on_oversize: OnOversize) -> Self {
+        self.on_oversize = Some(on_oversize);
+        self
+    }
+
+    /// Sets the number of blocks per entry of the embedded index
+    ///
+    /// The default of 1 writes a dense index with an entry for every block. Larger strides
+    /// write a sparse index that is `stride` times smaller, which keeps the index of files
+    /// with many small blocks compact at the cost of scanning up to `stride` block headers
+    /// when locating a block (see [`BlockIndex::find_record`]). Files with a sparse index
+    /// are written as format version 2, which older readers reject.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use binseq::vbq::{FileHeaderBuilder, WriterBuilder};
+    ///
+    /// // 16KB blocks with an index entry for every 64th block
+    /// let header = FileHeaderBuilder::new().block(16 * 1024).build();
+    /// let builder = WriterBuilder::default().header(header).index_stride(64);
+    /// ```
+    #[must_use]
+    pub fn index_stride(mut self, stride: usize) -> Self {
+        self.index_stride = Some(stride);
+        self
+    }
+
+    /// Sets whether to reject records that do not match the header configuration
+    ///
+    /// By default, data that the header does not store (quality scores or headers when
+    /// the corresponding flag is unset, or an extended sequence for a single-end file) is
+    /// silently dropped. In strict mode, writing such a record returns a
+    /// [`WriteError::QualityFlagNotSet`], [`WriteError::HeaderFlagNotSet`] or
+    /// [`WriteError::PairedFlagNotSet`] instead.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use binseq::vbq::{FileHeaderBuilder, WriterBuilder};
+    ///
+    /// let header = FileHeaderBuilder::new().headers(true).build();
+    /// let builder = WriterBuilder::default().header(header).strict_mode(true);
+    /// ```
+    #[must_use]
+    pub fn strict_mode(mut self, strict_mode: bool) -> Self {
+        self.strict_mode = Some(strict_mode);
+        self
+    }
+
+    /// Sets whether the embedded index stores a summary of the record flags of each block
+    ///
+    /// The summary holds the bitwise OR and AND of the flags of all records in a block
+    /// (see [`BlockRange::flag_or`] and [`BlockRange::flag_and`]), which lets
+    /// [`MmapReader::process_parallel_filtered`](crate::vbq::MmapReader::process_parallel_filtered)
+    /// skip blocks without matching records. Each index entry grows by 16 bytes, and the file
+    /// is written as format version 2, which older readers reject. Ignored if the header does
+    /// not store flags.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use binseq::vbq::{FileHeaderBuilder, WriterBuilder};
+    ///
+    /// let header = FileHeaderBuilder::new().flags(true).build();
+    /// let builder = WriterBuilder::default()
+    ///     .header(header)
+    ///     .index_flag_summary(true);
+    /// ```
+    #[must_use]
+    pub fn index_flag_summary(mut self, index_flag_summary: bool) -> Self {
+        self.index_flag_summary = Some(index_flag_summary);
+        self
+    }
+
+    /// Sets a hint of the number of records that will be written
+    ///
+    /// The writer keeps a block range for every flushed block to build the embedded index.
+    /// With a hint, the ranges of the expected number of blocks are allocated up front
+    /// instead of growing as blocks are flushed. The number of blocks is estimated from the
+    /// block size and the configuration of the header, assuming sequences of
+    /// [`mean_sequence_length`](Self::mean_sequence_length) nucleotides (150 by default).
+    ///
+    /// The hint only affects allocations: writing more or fewer records is fine.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use binseq::vbq::WriterBuilder;
+    ///
+    /// let builder = WriterBuilder::default()
+    ///     .estimated_records(1_000_000)
+    ///     .mean_sequence_length(100);
+    /// ```
+    #[must_use]
+    pub fn estimated_records(mut self, estimated_records: u64) -> Self {
+        self.estimated_records = Some(estimated_records);
+        self
+    }
+
+    /// Sets the mean sequence length assumed by [`estimated_records`](Self::estimated_records)
+    #[must_use]
+    pub fn mean_sequence_length(mut self, mean_sequence_length: usize) -> Self {
+        self.mean_sequence_length = Some(mean_sequence_length);
+        self
+    }
+
+    /// Sets the seed of the random number generator used by [`Policy::RandomDraw`]
+    ///
+    /// Defaults to [`RNG_SEED`](crate::RNG_SEED), so output is reproducible across runs.
+    #[must_use]
+    pub fn policy_seed(mut self, seed: u64) -> Self {
+        self.policy_seed = Some(seed);
+        self
+    }
+
+    /// Enables zstd long-distance matching with a window of `2^window_log` bytes
+    ///
+    /// Long-distance matching finds repeats further apart than the default window of the
+    /// compression level, which improves the ratio of large blocks (e.g. 8-64MB). The window
+    /// log must be within the bounds of zstd (10 to 30, or 31 on 64-bit targets); readers
+    /// accept windows up to this maximum. Only applies to compressed blocks.
+    #[must_use]
+    pub fn zstd_long_matching(mut self, window_log: u32) -> Self {
+        self.zstd.get_or_insert_default().window_log = Some(window_log);
+        self
+    }
+
+    /// Compresses each block with `workers` zstd worker threads (0 compresses on the
+    /// calling thread)
+    ///
+    /// This shortens the compression of large blocks. Only applies to compressed blocks.
+    #[must_use]
+    pub fn zstd_workers(mut self, workers: u32) -> Self {
+        self.zstd.get_or_insert_default().workers = workers;
+        self
+    }
+
+    /// Sets a callback invoked with the outcome of every block written to the output
+    ///
+    /// Blocks are reported in file order, including partial blocks written by
+    /// [`Writer::flush_block`] or [`Writer::finish`] and blocks taken over by
+    /// [`Writer::ingest`], so the number of calls matches the number of blocks in the index.
+    /// Clones of the writer share the callback.
+    #[must_use]
+    pub fn on_block_flush(mut self, callback: BlockFlushCallback) -> Self {
+        self.on_block_flush = Some(callback);
+        self
+    }
+
+    /// Resumes the writer from a checkpoint instead of starting a new file
+    ///
+    /// The built writer emits no file header and continues exactly where the checkpointed
+    /// writer stopped, so its output is appended to the bytes emitted before the checkpoint.
+    /// The header must be the one the checkpoint was taken with. See [`Writer::resume`].
+    #[must_use]
+    pub fn resume(mut self, checkpoint: WriterCheckpoint) -> Self {
+        self.checkpoint = Some(checkpoint);
+        self
+    }
+
     /// Builds a `Writer` with the configured settings
     ///
     /// This finalizes the builder and creates a new `Writer` instance using
@@ -232,12 +540,123 @@ impl WriterBuilder {
     ///     .unwrap();
     /// ```
     pub fn build<W: Write>(self, inner: W) -> Result<Writer<W>> {
-        Writer::new(
-            inner,
-            self.header.unwrap_or_default(),
-            self.policy.unwrap_or_default(),
-            self.headless.unwrap_or(false),
-        )
+        if let Some(OnOversize::Truncate(len)) = self.on_oversize
+            && (len == 0 || self.max_length.is_some_and(|max| len > max))
+        {
+            return Err(WriteError::InvalidTruncateLength {
+                len,
+                max_length: self.max_length,
+            }
+            .into());
+        }
+        let policy = self.policy.unwrap_or_default();
+        let headless = self.headless.unwrap_or(false) || self.checkpoint.is_some();
+        let index_stride = self.index_stride.unwrap_or(1);
+        let index_flag_summary = self.index_flag_summary.unwrap_or(false);
+        let header = self
+            .header
+            .unwrap_or_default()
+            .with_index_layout(index_stride, index_flag_summary);
+        // the file header is written once the index layout is known
+        let mut writer = Writer::new(inner, header, policy, true)?;
+        if let Some(checkpoint) = &self.checkpoint {
+            writer.restore(checkpoint)?;
+        }
+        if let Some(seed) = self.policy_seed {
+            writer.encoder = Encoder::with_policy_seed(writer.header.bits, policy, seed);
+        }
+        writer.min_length = self.min_length;
+        writer.max_length = self.max_length;
+        writer.on_oversize = self.on_oversize.unwrap_or_default();
+        writer.index_stride = index_stride;
+        writer.strict_mode = self.strict_mode.unwrap_or(false);
+        writer.index_flag_summary = index_flag_summary;
+        writer.cblock.zstd = self.zstd.unwrap_or_default();
+        writer.on_block_flush = FlushObserver(
+            self.on_block_flush
+                .map(|callback| Arc::new(Mutex::new(callback))),
+        );
+        if let Some(n_records) = self.estimated_records {
+            let mean_slen = self
+                .mean_sequence_length
+                .unwrap_or(DEFAULT_MEAN_SEQUENCE_LENGTH);
+            let n_blocks = writer.header.estimated_blocks(n_records, mean_slen as f64);
+            // The estimate is only a hint, so an allocation failure is left to later pushes
+            writer
+                .ranges
+                .try_reserve_exact(usize::try_from(n_blocks).unwrap_or(usize::MAX))
+                .ok();
+        }
+        if !headless {
+            writer.init()?;
+        }
+        Ok(writer)
+    }
+}
+
+/// Mean sequence length assumed by [`WriterBuilder::estimated_records`] if not set
+const DEFAULT_MEAN_SEQUENCE_LENGTH: usize = 150;
+
+/// The already-encoded components of a record, see [`Writer::write_encoded_record`]
+///
+/// Sequences are stored as encoded words (`sbuf`, `xbuf`) along with their lengths in
+/// nucleotides. Missing components are left empty.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EncodedRecord<'a> {
+    /// Flag of the record
+    pub flag: Option<u64>,
+
+    /// Length of the primary sequence in nucleotides
+    pub slen: u64,
+
+    /// Length of the extended sequence in nucleotides (0 if unpaired)
+    pub xlen: u64,
+
+    /// Encoded words of the primary sequence
+    pub sbuf: &'a [u64],
+
+    /// Encoded words of the extended sequence
+    pub xbuf: &'a [u64],
+
+    /// Quality scores of the primary sequence
+    pub squal: &'a [u8],
+
+    /// Quality scores of the extended sequence
+    pub xqual: &'a [u8],
+
+    /// Soft-mask bitmap of the primary sequence (empty if all bases are uppercase)
+    pub smask: &'a [u8],
+
+    /// Soft-mask bitmap of the extended sequence (empty if all bases are uppercase)
+    pub xmask: &'a [u8],
+
+    /// Header of the primary sequence
+    pub sheader: &'a [u8],
+
+    /// Header of the extended sequence
+    pub xheader: &'a [u8],
+}
+impl<'a> EncodedRecord<'a> {
+    /// Borrows the encoded components of a record read from a BINSEQ file
+    ///
+    /// Quality scores are only taken from records that have them. Soft-mask bitmaps are not
+    /// part of [`BinseqRecord`] and are left empty.
+    #[must_use]
+    pub fn from_record<R: BinseqRecord>(record: &'a R) -> Self {
+        let has_quality = record.has_quality();
+        Self {
+            flag: record.flag(),
+            slen: record.slen(),
+            xlen: record.xlen(),
+            sbuf: record.sbuf(),
+            xbuf: record.xbuf(),
+            squal: if has_quality { record.squal() } else { &[] },
+            xqual: if has_quality { record.xqual() } else { &[] },
+            smask: &[],
+            xmask: &[],
+            sheader: record.sheader(),
+            xheader: record.xheader(),
+        }
     }
 }
 
@@ -300,6 +719,9 @@ pub struct Writer<W: Write> {
     /// Encoder for nucleotide sequences
     encoder: Encoder,
 
+    /// Reusable buffers separating the case of soft-masked sequences
+    case_splitter: CaseSplitter,
+
     /// Pre-initialized writer for compressed blocks
     cblock: BlockWriter,
 
@@ -314,6 +736,30 @@ pub struct Writer<W: Write> {
 
     /// Determines if index is already written
     index_written: bool,
+
+    /// Minimum sequence length (shorter records are dropped)
+    min_length: Option<usize>,
+
+    /// Maximum sequence length (longer records are oversized)
+    max_length: Option<usize>,
+
+    /// Handling of oversized records
+    on_oversize: OnOversize,
+
+    /// Counts of records affected by the length policy
+    stats: WriteStats,
+
+    /// Number of blocks per embedded index entry
+    index_stride: usize,
+
+    /// Whether records with data the header does not store are rejected
+    strict_mode: bool,
+
+    /// Whether the embedded index stores the flag summary of each block
+    index_flag_summary: bool,
+
+    /// Observer of the written blocks
+    on_block_flush: FlushObserver,
 }
 impl<W: Write> Writer<W> {
     pub fn new(inner: W, header: FileHeader, policy: Policy, headless: bool) -> Result<Self> {
@@ -321,17 +767,20 @@ impl<W: Write> Writer<W> {
             inner,
             header,
             encoder: Encoder::with_policy(header.bits, policy),
-            cblock: BlockWriter::new(
-                header.block as usize,
-                header.compressed,
-                header.flags,
-                header.qual,
-                header.headers,
-            ),
+            case_splitter: CaseSplitter::default(),
+            cblock: BlockWriter::new(&header),
             ranges: Vec::new(),
             bytes_written: 0,
             records_written: 0,
             index_written: false,
+            min_length: None,
+            max_length: None,
+            on_oversize: OnOversize::default(),
+            stats: WriteStats::default(),
+            index_stride: 1,
+            strict_mode: false,
+            index_flag_summary: false,
+            on_block_flush: FlushObserver::default(),
         };
         if !headless {
             wtr.init()?;
@@ -396,6 +845,13 @@ impl<W: Write> Writer<W> {
         self.encoder.policy
     }
 
+    /// Returns the seed of the random number generator of the N-policy
+    ///
+    /// See [`WriterBuilder::policy_seed`].
+    pub fn policy_seed(&self) -> u64 {
+        self.encoder.seed
+    }
+
     /// Checks if the writer is configured for quality scores
     ///
     /// This method returns whether the writer expects quality scores based on the
@@ -433,6 +889,158 @@ impl<W: Write> Writer<W> {
         self.header.headers
     }
 
+    /// Returns `true` if the writer rejects records that do not match its header
+    ///
+    /// See [`WriterBuilder::strict_mode`].
+    pub fn is_strict(&self) -> bool {
+        self.strict_mode
+    }
+
+    /// Writes a single-end sequence with a header
+    ///
+    /// Shorthand for [`push`](Self::push) with a record holding only a flag, a header
+    /// and a sequence.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`WriteError::HeaderFlagNotSet`] if the writer does not store headers.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use binseq::vbq::{FileHeaderBuilder, WriterBuilder};
+    /// use std::fs::File;
+    ///
+    /// let header = FileHeaderBuilder::new().headers(true).build();
+    /// let mut writer = WriterBuilder::default()
+    ///     .header(header)
+    ///     .build(File::create("example.vbq").unwrap())
+    ///     .unwrap();
+    ///
+    /// writer
+    ///     .write_nucleotides_with_header(0, b"seq_001", b"ACGTACGT")
+    ///     .unwrap();
+    /// writer.finish().unwrap();
+    /// ```
+    pub fn write_nucleotides_with_header(
+        &mut self,
+        flag: u64,
+        header: &[u8],
+        sequence: &[u8],
+    ) -> Result<bool> {
+        self.check_stored_fields(true, false, false)?;
+        let record =
+            SequencingRecord::new(sequence, None, Some(header), None, None, None, Some(flag));
+        self.push(record)
+    }
+
+    /// Writes a single-end sequence with quality scores and a header
+    ///
+    /// Shorthand for [`push`](Self::push) with a record holding only a flag, a header,
+    /// a sequence and its quality scores.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`WriteError::HeaderFlagNotSet`] if the writer does not store headers, or
+    /// [`WriteError::QualityFlagNotSet`] if it does not store quality scores.
+    pub fn write_nucleotides_quality_with_header(
+        &mut self,
+        flag: u64,
+        header: &[u8],
+        sequence: &[u8],
+        quality: &[u8],
+    ) -> Result<bool> {
+        self.check_stored_fields(true, true, false)?;
+        let record = SequencingRecord::new(
+            sequence,
+            Some(quality),
+            Some(header),
+            None,
+            None,
+            None,
+            Some(flag),
+        );
+        self.push(record)
+    }
+
+    /// Writes a pair of sequences with their headers
+    ///
+    /// Shorthand for [`push`](Self::push) with a paired record holding only a flag and
+    /// the header and sequence of each mate.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`WriteError::HeaderFlagNotSet`] if the writer does not store headers, or
+    /// [`WriteError::PairedFlagNotSet`] if it is single-end.
+    pub fn write_paired_with_headers(
+        &mut self,
+        flag: u64,
+        s_header: &[u8],
+        s_sequence: &[u8],
+        x_header: &[u8],
+        x_sequence: &[u8],
+    ) -> Result<bool> {
+        self.check_stored_fields(true, false, true)?;
+        let record = SequencingRecord::new(
+            s_sequence,
+            None,
+            Some(s_header),
+            Some(x_sequence),
+            None,
+            Some(x_header),
+            Some(flag),
+        );
+        self.push(record)
+    }
+
+    /// Writes a pair of sequences with their quality scores and headers
+    ///
+    /// Shorthand for [`push`](Self::push) with a paired record holding a flag and the
+    /// header, sequence and quality scores of each mate.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`WriteError::HeaderFlagNotSet`] if the writer does not store headers,
+    /// [`WriteError::QualityFlagNotSet`] if it does not store quality scores, or
+    /// [`WriteError::PairedFlagNotSet`] if it is single-end.
+    #[allow(clippy::too_many_arguments)]
+    pub fn write_paired_quality_with_headers(
+        &mut self,
+        flag: u64,
+        s_header: &[u8],
+        s_sequence: &[u8],
+        s_qual: &[u8],
+        x_header: &[u8],
+        x_sequence: &[u8],
+        x_qual: &[u8],
+    ) -> Result<bool> {
+        self.check_stored_fields(true, true, true)?;
+        let record = SequencingRecord::new(
+            s_sequence,
+            Some(s_qual),
+            Some(s_header),
+            Some(x_sequence),
+            Some(x_qual),
+            Some(x_header),
+            Some(flag),
+        );
+        self.push(record)
+    }
+
+    /// Checks that the header stores each of the requested fields
+    fn check_stored_fields(&self, headers: bool, qual: bool, paired: bool) -> Result<()> {
+        if headers && !self.header.headers {
+            return Err(WriteError::HeaderFlagNotSet.into());
+        }
+        if qual && !self.header.qual {
+            return Err(WriteError::QualityFlagNotSet.into());
+        }
+        if paired && !self.header.paired {
+            return Err(WriteError::PairedFlagNotSet.into());
+        }
+        Ok(())
+    }
+
     #[deprecated(note = "use `push` method with SequencingRecord instead")]
     pub fn write_record(
         &mut self,
@@ -544,20 +1152,43 @@ impl<W: Write> Writer<W> {
             .into());
         }
 
-        let record_size = record.configured_size_vbq(
-            self.header.paired,
-            self.header.flags,
-            self.header.headers,
-            self.header.qual,
-            self.header.bits,
-        );
+        // In strict mode, extra data is rejected instead of ignored
+        if self.strict_mode {
+            self.check_stored_fields(
+                record.has_headers(),
+                record.has_qualities(),
+                record.is_paired(),
+            )?;
+        }
+
+        let Some(record) = self.apply_length_policy(record)? else {
+            return Ok(false);
+        };
+
+        let record_size = self.configured_size(&record);
+
+        // Soft-masked sequences are encoded in uppercase with their case stored separately
+        let x_seq = if self.header.paired {
+            record.x_seq.unwrap_or_default()
+        } else {
+            &[]
+        };
+        let (s_seq, x_seq, s_mask, x_mask) = if self.header.masked {
+            self.case_splitter.split(record.s_seq, x_seq);
+            let splitter = &self.case_splitter;
+            (
+                splitter.s_seq.as_slice(),
+                splitter.x_seq.as_slice(),
+                Some(splitter.s_mask.as_slice()),
+                Some(splitter.x_mask.as_slice()),
+            )
+        } else {
+            (record.s_seq, x_seq, None, None)
+        };
 
         if self.header.is_paired() {
             // encode the sequences
-            if let Some((sbuffer, xbuffer)) = self
-                .encoder
-                .encode_paired(record.s_seq, record.x_seq.unwrap_or_default())?
-            {
+            if let Some((sbuffer, xbuffer)) = self.encoder.encode_paired(s_seq, x_seq)? {
                 if self.cblock.exceeds_block_size(record_size)? {
                     impl_flush_block(
                         &mut self.inner,
@@ -565,17 +1196,19 @@ impl<W: Write> Writer<W> {
                         &mut self.ranges,
                         &mut self.bytes_written,
                         &mut self.records_written,
+                        &self.on_block_flush,
                     )?;
                 }
 
-                self.cblock.write_record(&record, sbuffer, Some(xbuffer))?;
+                self.cblock
+                    .write_record(&record, sbuffer, Some(xbuffer), s_mask, x_mask)?;
                 Ok(true)
             } else {
                 Ok(false)
             }
         } else {
             // encode the sequence
-            if let Some(sbuffer) = self.encoder.encode_single(record.s_seq)? {
+            if let Some(sbuffer) = self.encoder.encode_single(s_seq)? {
                 if self.cblock.exceeds_block_size(record_size)? {
                     impl_flush_block(
                         &mut self.inner,
@@ -583,10 +1216,12 @@ impl<W: Write> Writer<W> {
                         &mut self.ranges,
                         &mut self.bytes_written,
                         &mut self.records_written,
+                        &self.on_block_flush,
                     )?;
                 }
 
-                self.cblock.write_record(&record, sbuffer, None)?;
+                self.cblock
+                    .write_record(&record, sbuffer, None, s_mask, None)?;
                 Ok(true)
             } else {
                 Ok(false)
@@ -594,6 +1229,259 @@ impl<W: Write> Writer<W> {
         }
     }
 
+    /// Embedded size of a record under the writer configuration
+    fn configured_size(&self, record: &SequencingRecord) -> usize {
+        let size = record.configured_size_vbq(
+            self.header.paired,
+            self.header.flags,
+            self.header.headers,
+            self.header.qual,
+            self.header.bits,
+        );
+        if !self.header.masked {
+            return size;
+        }
+        let x_len = if self.header.paired {
+            record.x_seq.map_or(0, <[u8]>::len)
+        } else {
+            0
+        };
+        size.saturating_add(mask_len(record.s_seq.len()) + mask_len(x_len))
+    }
+
+    /// Applies the minimum/maximum length settings to a record
+    ///
+    /// Returns `None` if the record should be dropped, otherwise the (possibly truncated)
+    /// record to write.
+    fn apply_length_policy<'a>(
+        &mut self,
+        record: SequencingRecord<'a>,
+    ) -> Result<Option<SequencingRecord<'a>>> {
+        let slen = record.s_seq.len();
+        let xlen = if self.header.paired {
+            record.x_seq.map(<[u8]>::len)
+        } else {
+            None
+        };
+        let shortest = xlen.map_or(slen, |xlen| slen.min(xlen));
+        let longest = xlen.map_or(slen, |xlen| slen.max(xlen));
+
+        if self.min_length.is_some_and(|min| shortest < min) {
+            self.stats.skipped_short += 1;
+            return Ok(None);
+        }
+
+        let too_long = self.max_length.filter(|&max| longest > max);
+        let too_large = self.configured_size(&record) > self.header.block as usize;
+        if too_long.is_none() && !too_large {
+            return Ok(Some(record));
+        }
+
+        match self.on_oversize {
+            OnOversize::Error => match too_long {
+                Some(max) => Err(WriteError::SequenceTooLong {
+                    expected: max,
+                    got: longest,
+                }
+                .into()),
+                // Oversized blocks are reported when the record is written
+                None => Ok(Some(record)),
+            },
+            OnOversize::Skip => {
+                self.stats.skipped_long += 1;
+                Ok(None)
+            }
+            OnOversize::Truncate(len) => {
+                self.stats.truncated += 1;
+                Ok(Some(truncate_record(record, len)))
+            }
+        }
+    }
+
+    /// Returns the counts of records affected by the length policy
+    ///
+    /// See [`WriterBuilder::min_length`], [`WriterBuilder::max_length`], and
+    /// [`WriterBuilder::on_oversize`].
+    #[must_use]
+    pub fn write_stats(&self) -> WriteStats {
+        self.stats
+    }
+
+    /// Writes a paired record from two already-encoded records without re-encoding
+    ///
+    /// The encoded sequence words of `primary` and `extended` are copied directly into the
+    /// current block, bypassing the encoder (and therefore the invalid nucleotide
+    /// [`Policy`]). Quality scores and headers are copied if the writer is configured for
+    /// them, and the flag is taken from the primary record.
+    ///
+    /// This is useful for combining single-end files into a paired file, see
+    /// [`pair_files`](crate::vbq::pair_files).
+    ///
+    /// # Errors
+    ///
+    /// * `WriteError::PairedFlagNotSet` - If the writer is not configured for paired records
+    /// * `WriteError::IncompatibleBitsizes` - If either record uses a different bitsize than the writer
+    /// * `WriteError::QualityFlagSet` - If the writer expects quality scores that the records lack
+    pub fn write_encoded_paired_record<A: BinseqRecord, B: BinseqRecord>(
+        &mut self,
+        primary: &A,
+        extended: &B,
+    ) -> Result<()> {
+        if !self.header.paired {
+            return Err(WriteError::PairedFlagNotSet.into());
+        }
+        for bitsize in [primary.bitsize(), extended.bitsize()] {
+            if bitsize != self.header.bits {
+                return Err(WriteError::IncompatibleBitsizes(self.header.bits, bitsize).into());
+            }
+        }
+
+        let (slen, xlen) = (primary.slen(), extended.slen());
+        let (s_qual, x_qual) = (primary.squal(), extended.squal());
+        if self.header.qual && (s_qual.len() as u64 != slen || x_qual.len() as u64 != xlen) {
+            return Err(WriteError::QualityFlagSet.into());
+        }
+        let (s_header, x_header) = (primary.sheader(), extended.sheader());
+
+        // Determine the embedded size of the record
+        let mut record_size = 16 + 8 * (primary.sbuf().len() + extended.sbuf().len());
+        if self.header.flags {
+            record_size += 8;
+        }
+        if self.header.qual {
+            record_size += s_qual.len() + x_qual.len();
+        }
+        if self.header.headers {
+            record_size += 16 + s_header.len() + x_header.len();
+        }
+        if self.header.masked {
+            record_size += mask_len(slen as usize) + mask_len(xlen as usize);
+        }
+
+        if self.cblock.exceeds_block_size(record_size)? {
+            impl_flush_block(
+                &mut self.inner,
+                &mut self.cblock,
+                &mut self.ranges,
+                &mut self.bytes_written,
+                &mut self.records_written,
+                &self.on_block_flush,
+            )?;
+        }
+
+        self.cblock.write_parts(
+            primary.flag(),
+            slen,
+            xlen,
+            primary.sbuf(),
+            Some(s_qual),
+            Some(s_header),
+            Some(extended.sbuf()),
+            Some(x_qual),
+            Some(x_header),
+            None,
+            None,
+        )
+    }
+
+    /// Writes a record from its already-encoded components without re-encoding
+    ///
+    /// The encoded sequence words are copied directly into the current block, bypassing the
+    /// encoder (and therefore the invalid nucleotide [`Policy`]) and the length policies.
+    /// Flags, quality scores and headers are written if the writer is configured for them.
+    /// The record is subject to the same block size checks as [`push`](Self::push).
+    ///
+    /// This is useful to rewrite parts of records (e.g. their headers) without decoding
+    /// their sequences, see [`rewrite_headers`](crate::vbq::rewrite_headers).
+    ///
+    /// # Errors
+    ///
+    /// * `WriteError::PairedFlagNotSet` - If the record has an extended sequence but the
+    ///   writer is not configured for paired records
+    /// * `WriteError::EncodedLengthMismatch` - If a sequence does not have the number of
+    ///   words required by its length and the bitsize of the writer
+    /// * `WriteError::QualityFlagSet` - If the writer expects quality scores that the record lacks
+    /// * `WriteError::RecordSizeExceedsMaximumBlockSize` - If the record does not fit in a block
+    pub fn write_encoded_record(&mut self, record: EncodedRecord) -> Result<()> {
+        if !self.header.paired && (record.xlen > 0 || !record.xbuf.is_empty()) {
+            return Err(WriteError::PairedFlagNotSet.into());
+        }
+        for (len, buf) in [(record.slen, record.sbuf), (record.xlen, record.xbuf)] {
+            let expected = match self.header.bits {
+                BitSize::Two => len.div_ceil(32),
+                BitSize::Four => len.div_ceil(16),
+            } as usize;
+            if buf.len() != expected {
+                return Err(WriteError::EncodedLengthMismatch {
+                    expected,
+                    got: buf.len(),
+                }
+                .into());
+            }
+        }
+        if self.header.qual
+            && (record.squal.len() as u64 != record.slen
+                || record.xqual.len() as u64 != record.xlen)
+        {
+            return Err(WriteError::QualityFlagSet.into());
+        }
+        if self.header.masked {
+            for (len, mask) in [(record.slen, record.smask), (record.xlen, record.xmask)] {
+                let expected = mask_len(len as usize);
+                if !mask.is_empty() && mask.len() != expected {
+                    return Err(WriteError::MaskLengthMismatch {
+                        expected,
+                        got: mask.len(),
+                    }
+                    .into());
+                }
+            }
+        }
+
+        // Extended headers are only stored for records with an extended sequence
+        let x_header = (record.xlen > 0).then_some(record.xheader);
+
+        // Determine the embedded size of the record
+        let mut record_size = 16 + 8 * (record.sbuf.len() + record.xbuf.len());
+        if self.header.flags {
+            record_size += 8;
+        }
+        if self.header.qual {
+            record_size += record.squal.len() + record.xqual.len();
+        }
+        if self.header.headers {
+            record_size += 8 + record.sheader.len() + x_header.map_or(0, |h| 8 + h.len());
+        }
+        if self.header.masked {
+            record_size += mask_len(record.slen as usize) + mask_len(record.xlen as usize);
+        }
+
+        if self.cblock.exceeds_block_size(record_size)? {
+            impl_flush_block(
+                &mut self.inner,
+                &mut self.cblock,
+                &mut self.ranges,
+                &mut self.bytes_written,
+                &mut self.records_written,
+                &self.on_block_flush,
+            )?;
+        }
+
+        self.cblock.write_parts(
+            record.flag,
+            record.slen,
+            record.xlen,
+            record.sbuf,
+            Some(record.squal),
+            Some(record.sheader),
+            self.header.paired.then_some(record.xbuf),
+            Some(record.xqual),
+            x_header,
+            Some(record.smask),
+            Some(record.xmask),
+        )
+    }
+
     /// Finishes writing and flushes all data to the underlying writer
     ///
     /// This method should be called when you're done writing to ensure all data
@@ -638,6 +1526,7 @@ impl<W: Write> Writer<W> {
             &mut self.ranges,
             &mut self.bytes_written,
             &mut self.records_written,
+            &self.on_block_flush,
         )?;
         self.inner.flush()?;
 
@@ -646,6 +1535,7 @@ impl<W: Write> Writer<W> {
         if !self.index_written {
             self.write_index()?;
             self.index_written = true;
+            self.inner.flush()?;
         }
         Ok(())
     }
@@ -727,18 +1617,23 @@ impl<W: Write> Writer<W> {
         {
             for range in other.ranges.drain(..) {
                 // Build the updated range with main-file specific information
-                let updated_range = BlockRange::new(
-                    self.bytes_written as u64, // Current position in main file
-                    range.len,
-                    range.block_records,
-                    self.records_written as u64, // Current number of records written in main file
-                );
+                // (current position and number of records written in the main file)
+                let updated_range = BlockRange {
+                    start_offset: self.bytes_written as u64,
+                    cumulative_records: self.records_written as u64,
+                    ..range
+                };
 
                 self.ranges.push(updated_range);
 
                 // Update counters incrementally for each range
-                self.bytes_written += (range.len + SIZE_BLOCK_HEADER as u64) as usize;
-                self.records_written += range.block_records as usize;
+                advance_counts(
+                    &mut self.bytes_written,
+                    &mut self.records_written,
+                    range.len,
+                    range.block_records,
+                )?;
+                self.notify_block_flush(range.len, range.block_records);
             }
 
             // reset the other writer
@@ -746,31 +1641,254 @@ impl<W: Write> Writer<W> {
             other.records_written = 0;
         }
 
+        // Merge the length policy statistics
+        {
+            let stats = std::mem::take(&mut other.stats);
+            self.stats.skipped_short += stats.skipped_short;
+            self.stats.skipped_long += stats.skipped_long;
+            self.stats.truncated += stats.truncated;
+        }
+
         // Ingest incomplete block from other
         {
-            let header = self.cblock.ingest(other.cblock_mut(), &mut self.inner)?;
+            let (header, flag_summary) = self.cblock.ingest(other.cblock_mut(), &mut self.inner)?;
             if !header.is_empty() {
-                let range = BlockRange::new(
-                    self.bytes_written as u64,
+                let range = BlockRange {
+                    flag_summary,
+                    ..BlockRange::new(
+                        self.bytes_written as u64,
+                        header.size,
+                        header.records,
+                        self.records_written as u64,
+                    )
+                };
+                self.ranges.push(range);
+                advance_counts(
+                    &mut self.bytes_written,
+                    &mut self.records_written,
                     header.size,
                     header.records,
-                    self.records_written as u64,
-                );
-                self.ranges.push(range);
-                self.bytes_written += header.size_with_header();
-                self.records_written += header.records as usize;
+                )?;
+                self.notify_block_flush(header.size, header.records);
+            }
+        }
+        Ok(())
+    }
+
+    /// Reports the last block added to the output to the block-flush observer
+    fn notify_block_flush(&self, compressed_size: u64, records: u32) {
+        self.on_block_flush.notify(BlockFlushInfo {
+            uncompressed_size: self.header.block,
+            compressed_size,
+            records,
+            total_blocks: self.ranges.len(),
+            total_records: self.records_written,
+            total_bytes: self.bytes_written,
+        });
+    }
+
+    /// Returns the zstd long-distance matching window log, if enabled
+    ///
+    /// See [`WriterBuilder::zstd_long_matching`].
+    pub fn zstd_long_matching(&self) -> Option<u32> {
+        self.cblock.zstd.window_log
+    }
+
+    /// Returns the number of zstd worker threads compressing each block
+    ///
+    /// See [`WriterBuilder::zstd_workers`].
+    pub fn zstd_workers(&self) -> u32 {
+        self.cblock.zstd.workers
+    }
+
+    /// Returns the number of bytes used in the current block and the block size
+    ///
+    /// The current block is written once the next record does not fit. Callers can use the
+    /// fill level to write a partial block early with [`flush_block`](Self::flush_block).
+    pub fn current_block_fill(&self) -> (usize, usize) {
+        (self.cblock.pos, self.cblock.block_size)
+    }
+
+    /// Writes a complete block produced outside of this writer
+    ///
+    /// `block_bytes` are the contents of the block, compressed if the file header is, and
+    /// `block_header` gives their size and number of records. The block is written as-is
+    /// and added to the embedded index, so it must follow the record layout of the file
+    /// header; nothing but its size is validated. Records pushed before are written first
+    /// in a partial block, so the output keeps the order of calls.
+    ///
+    /// Uncompressed blocks must be padded to the block size of the file header.
+    pub fn write_block_raw(&mut self, block_bytes: &[u8], block_header: BlockHeader) -> Result<()> {
+        if block_bytes.len() as u64 != block_header.size {
+            return Err(WriteError::RawBlockSizeMismatch {
+                expected: block_header.size,
+                got: block_bytes.len(),
+            }
+            .into());
+        }
+        if !self.header.compressed && block_header.size != self.header.block {
+            return Err(WriteError::RawBlockSizeMismatch {
+                expected: self.header.block,
+                got: block_bytes.len(),
             }
+            .into());
+        }
+
+        // keep the order of previously pushed records
+        impl_flush_block(
+            &mut self.inner,
+            &mut self.cblock,
+            &mut self.ranges,
+            &mut self.bytes_written,
+            &mut self.records_written,
+            &self.on_block_flush,
+        )?;
+
+        block_header.write_bytes(&mut self.inner)?;
+        self.inner.write_all(block_bytes)?;
+        self.ranges.push(BlockRange::new(
+            self.bytes_written as u64,
+            block_header.size,
+            block_header.records,
+            self.records_written as u64,
+        ));
+        advance_counts(
+            &mut self.bytes_written,
+            &mut self.records_written,
+            block_header.size,
+            block_header.records,
+        )?;
+        self.notify_block_flush(block_header.size, block_header.records);
+        Ok(())
+    }
+
+    /// Returns the number of bytes and records written so far, including the current block
+    ///
+    /// Bytes of the current block are counted uncompressed.
+    pub(crate) fn written(&self) -> (usize, usize) {
+        (
+            self.bytes_written + self.cblock.pos,
+            self.records_written + self.cblock.starts.len(),
+        )
+    }
+
+    /// Writes the current partial block and flushes the underlying writer
+    ///
+    /// This makes all records pushed so far durable (e.g. before an idle period of a
+    /// streaming ingest), at the cost of a zero-padded block. Does nothing if the current
+    /// block is empty. The file remains open for writing.
+    pub fn flush_block(&mut self) -> Result<()> {
+        impl_flush_block(
+            &mut self.inner,
+            &mut self.cblock,
+            &mut self.ranges,
+            &mut self.bytes_written,
+            &mut self.records_written,
+            &self.on_block_flush,
+        )?;
+        self.inner.flush()?;
+        Ok(())
+    }
+
+    /// Captures the state of the writer to resume writing later
+    ///
+    /// The checkpoint holds the running counts, the block ranges and the records of the
+    /// current block. It describes the bytes passed to the inner writer so far, so buffered
+    /// inner writers should be flushed before their output is considered emitted.
+    ///
+    /// Taking a checkpoint leaves the writer unchanged. Use [`suspend`](Self::suspend) to
+    /// stop a writer without writing the current block and the index.
+    pub fn checkpoint(&self) -> WriterCheckpoint {
+        WriterCheckpoint {
+            header_checksum: header_checksum(&self.header),
+            bytes_written: self.bytes_written as u64,
+            records_written: self.records_written as u64,
+            ranges: self.ranges.clone(),
+            ubuf: self.cblock.ubuf.clone(),
+            starts: self
+                .cblock
+                .starts
+                .iter()
+                .map(|&start| start as u64)
+                .collect(),
+            pos: self.cblock.pos as u64,
+        }
+    }
+
+    /// Flushes the inner writer and stops the writer at a checkpoint
+    ///
+    /// The current block and the embedded index are not written, neither now nor when the
+    /// writer is dropped. Writing continues with [`Writer::resume`].
+    pub fn suspend(mut self) -> Result<WriterCheckpoint> {
+        self.inner.flush()?;
+        let checkpoint = self.checkpoint();
+        self.cblock.clear();
+        self.index_written = true;
+        Ok(checkpoint)
+    }
+
+    /// Creates a writer continuing from `checkpoint`
+    ///
+    /// `inner` receives only the bytes following those emitted before the checkpoint; no
+    /// file header is written. Concatenated, the two outputs form the same file as an
+    /// uninterrupted writer. Use [`WriterBuilder::resume`] to configure further settings.
+    ///
+    /// # Errors
+    ///
+    /// * `WriteError::CheckpointHeaderMismatch` - If `header` is not the header the
+    ///   checkpoint was taken with
+    /// * `ReadError::CorruptCheckpoint` - If the current block of the checkpoint does not fit
+    ///   into the block size of `header`
+    pub fn resume(
+        inner: W,
+        header: FileHeader,
+        policy: Policy,
+        checkpoint: &WriterCheckpoint,
+    ) -> Result<Self> {
+        let mut writer = Self::new(inner, header, policy, true)?;
+        writer.restore(checkpoint)?;
+        Ok(writer)
+    }
+
+    /// Restores the counts, block ranges and current block of a checkpoint
+    fn restore(&mut self, checkpoint: &WriterCheckpoint) -> Result<()> {
+        if checkpoint.header_checksum != header_checksum(&self.header) {
+            return Err(WriteError::CheckpointHeaderMismatch.into());
+        }
+        let to_usize =
+            |value: u64, name| usize::try_from(value).map_err(|_| WriteError::CountOverflow(name));
+        let pos = to_usize(checkpoint.pos, "block bytes")?;
+        let starts = checkpoint
+            .starts
+            .iter()
+            .map(|&start| to_usize(start, "block bytes"))
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        if pos != checkpoint.ubuf.len()
+            || pos > self.cblock.block_size
+            || starts.iter().any(|&start| start >= pos)
+        {
+            return Err(ReadError::CorruptCheckpoint("current block out of range").into());
         }
+
+        self.bytes_written = to_usize(checkpoint.bytes_written, "bytes written")?;
+        self.records_written = to_usize(checkpoint.records_written, "records written")?;
+        self.ranges.clone_from(&checkpoint.ranges);
+        self.cblock.clear();
+        self.cblock.ubuf.extend_from_slice(&checkpoint.ubuf);
+        self.cblock.starts = starts;
+        self.cblock.pos = pos;
         Ok(())
     }
 
     pub fn write_index(&mut self) -> Result<()> {
         // Build the index
-        let index_header = IndexHeader::new(self.bytes_written as u64);
+        let index_header = IndexHeader::new(self.bytes_written as u64)
+            .with_flag_summary(self.index_flag_summary && self.header.flags);
         let block_index = BlockIndex {
             header: index_header,
             ranges: self.ranges.clone(),
-        };
+        }
+        .to_sparse(self.index_stride);
 
         // Write the index to a temporary buffer
         let mut buffer = Vec::new();
@@ -792,32 +1910,159 @@ impl<W: Write> Writer<W> {
     }
 }
 
+/// Hard-clips the sequences and quality scores of a record to `len`, keeping the headers
+fn truncate_record(record: SequencingRecord<'_>, len: usize) -> SequencingRecord<'_> {
+    fn clip(bytes: &[u8], len: usize) -> &[u8] {
+        &bytes[..bytes.len().min(len)]
+    }
+    SequencingRecord {
+        s_seq: clip(record.s_seq, len),
+        s_qual: record.s_qual.map(|qual| clip(qual, len)),
+        x_seq: record.x_seq.map(|seq| clip(seq, len)),
+        x_qual: record.x_qual.map(|qual| clip(qual, len)),
+        ..record
+    }
+}
+
 fn impl_flush_block<W: Write>(
     writer: &mut W,
     cblock: &mut BlockWriter,
     ranges: &mut Vec<BlockRange>,
     bytes_written: &mut usize,
     records_written: &mut usize,
+    observer: &FlushObserver,
 ) -> Result<()> {
+    let uncompressed_size = cblock.block_size as u64;
+    let flag_summary = cblock.flag_summary();
     let block_header = cblock.flush(writer)?;
-    let range = BlockRange::new(
-        *bytes_written as u64,
+    if block_header.is_empty() {
+        // Nothing was written for an empty block
+        return Ok(());
+    }
+    let range = BlockRange {
+        flag_summary,
+        ..BlockRange::new(
+            *bytes_written as u64,
+            block_header.size,
+            block_header.records,
+            *records_written as u64,
+        )
+    };
+    ranges.push(range);
+    advance_counts(
+        bytes_written,
+        records_written,
         block_header.size,
         block_header.records,
-        *records_written as u64,
-    );
-    ranges.push(range);
-    *bytes_written += block_header.size_with_header();
-    *records_written += block_header.records as usize;
+    )?;
+    observer.notify(BlockFlushInfo {
+        uncompressed_size,
+        compressed_size: block_header.size,
+        records: block_header.records,
+        total_blocks: ranges.len(),
+        total_records: *records_written,
+        total_bytes: *bytes_written,
+    });
+    Ok(())
+}
+
+/// Adds a written block of `size` bytes (excluding its header) and `records` records to the
+/// running counts of a writer
+///
+/// Fails instead of wrapping when a count no longer fits in `usize` (e.g. on 32-bit targets).
+fn advance_counts(
+    bytes_written: &mut usize,
+    records_written: &mut usize,
+    size: u64,
+    records: u32,
+) -> Result<()> {
+    *bytes_written = usize::try_from(size)
+        .ok()
+        .and_then(|size| size.checked_add(SIZE_BLOCK_HEADER))
+        .and_then(|size| bytes_written.checked_add(size))
+        .ok_or(WriteError::CountOverflow("bytes written"))?;
+    *records_written = usize::try_from(records)
+        .ok()
+        .and_then(|records| records_written.checked_add(records))
+        .ok_or(WriteError::CountOverflow("records written"))?;
     Ok(())
 }
 
+/// Converts the number of records in a block to the `u32` stored in its header
+fn block_record_count(n_records: usize) -> Result<u32> {
+    u32::try_from(n_records).map_err(|_| WriteError::BlockRecordCountOverflow(n_records).into())
+}
+
+impl Writer<Vec<u8>> {
+    /// Finishes the writer and opens its output as an [`MmapReader`]
+    ///
+    /// The finished output is handed to [`MmapReader::from_bytes`] without touching the
+    /// file system, so the whole file is held in memory. This is meant for round-trip
+    /// testing and debugging of in-memory output.
+    ///
+    /// The writer must not be headless, since the reader expects a file header.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use binseq::vbq::WriterBuilder;
+    /// use binseq::SequencingRecordBuilder;
+    ///
+    /// let mut writer = WriterBuilder::default().build(Vec::new()).unwrap();
+    /// let record = SequencingRecordBuilder::default().s_seq(b"ACGT").build().unwrap();
+    /// writer.push(record).unwrap();
+    ///
+    /// let reader = writer.into_mmap_reader().unwrap();
+    /// assert_eq!(reader.num_records().unwrap(), 1);
+    /// ```
+    pub fn into_mmap_reader(mut self) -> Result<MmapReader> {
+        self.finish()?;
+        MmapReader::from_bytes(std::mem::take(&mut self.inner))
+    }
+
+    /// Finishes the writer and opens its output as an [`MmapReader`]
+    #[deprecated(note = "use `into_mmap_reader` instead")]
+    pub fn into_vbq_mmap_reader(self) -> Result<MmapReader> {
+        self.into_mmap_reader()
+    }
+}
+
 impl<W: Write> Drop for Writer<W> {
     fn drop(&mut self) {
         self.finish().expect("Writer: Failed to finish writing");
     }
 }
 
+/// Advanced zstd settings of compressed blocks
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct ZstdOptions {
+    /// Window log of long-distance matching (disabled if unset)
+    window_log: Option<u32>,
+    /// Number of worker threads (0 compresses on the calling thread)
+    workers: u32,
+}
+impl ZstdOptions {
+    /// Compresses `src` into `dst` as a single zstd frame
+    fn compress(self, src: &[u8], dst: &mut Vec<u8>, level: i32) -> Result<()> {
+        if self == Self::default() {
+            copy_encode(src, dst, level)?;
+            return Ok(());
+        }
+        let mut encoder = zstd::stream::write::Encoder::new(dst, level)?;
+        encoder.set_pledged_src_size(Some(src.len() as u64))?;
+        if let Some(window_log) = self.window_log {
+            encoder.long_distance_matching(true)?;
+            encoder.window_log(window_log)?;
+        }
+        if self.workers > 0 {
+            encoder.multithread(self.workers)?;
+        }
+        encoder.write_all(src)?;
+        encoder.finish()?;
+        Ok(())
+    }
+}
+
 #[derive(Clone)]
 struct BlockWriter {
     /// Current position in the block
@@ -828,6 +2073,8 @@ struct BlockWriter {
     block_size: usize,
     /// Compression level
     level: i32,
+    /// Advanced zstd settings
+    zstd: ZstdOptions,
     /// Uncompressed buffer
     ubuf: Vec<u8>,
     /// Compressed buffer
@@ -843,27 +2090,38 @@ struct BlockWriter {
     has_qualities: bool,
     /// Has headers
     has_headers: bool,
+    /// Has soft-mask bitmaps
+    has_mask: bool,
+    /// Record layout for storing header prefixes (if enabled)
+    header_prefix: Option<RecordLayout>,
+    /// Reusable buffer for the block with header prefixes
+    pbuf: Vec<u8>,
 }
 impl BlockWriter {
-    fn new(
-        block_size: usize,
-        compress: bool,
-        has_flags: bool,
-        has_qualities: bool,
-        has_headers: bool,
-    ) -> Self {
+    fn new(header: &FileHeader) -> Self {
+        let block_size = header.block as usize;
+        let header_prefix = (header.headers && header.header_prefix).then_some(RecordLayout {
+            bits: header.bits,
+            flags: header.flags,
+            qual: header.qual,
+            mask: header.masked,
+        });
         Self {
             pos: 0,
             starts: Vec::default(),
             block_size,
             level: 3,
+            zstd: ZstdOptions::default(),
             ubuf: Vec::with_capacity(block_size),
             zbuf: Vec::with_capacity(block_size),
             padding: vec![0; block_size],
-            compress,
-            has_flags,
-            has_qualities,
-            has_headers,
+            compress: header.compressed,
+            has_flags: header.flags,
+            has_qualities: header.qual,
+            has_headers: header.headers,
+            has_mask: header.masked,
+            header_prefix,
+            pbuf: Vec::new(),
         }
     }
 
@@ -883,32 +2141,72 @@ impl BlockWriter {
         record: &SequencingRecord,
         sbuf: &[u64],
         xbuf: Option<&[u64]>,
+        s_mask: Option<&[u8]>,
+        x_mask: Option<&[u8]>,
+    ) -> Result<()> {
+        self.write_parts(
+            record.flag,
+            record.s_seq.len() as u64,
+            record.x_seq.map_or(0, <[u8]>::len) as u64,
+            sbuf,
+            record.s_qual,
+            record.s_header,
+            xbuf,
+            record.x_qual,
+            record.x_header,
+            s_mask,
+            x_mask,
+        )
+    }
+
+    /// Writes the already-encoded components of a record into the block
+    ///
+    /// Missing soft-mask bitmaps are written as all-uppercase.
+    #[allow(clippy::too_many_arguments)]
+    fn write_parts(
+        &mut self,
+        flag: Option<u64>,
+        slen: u64,
+        xlen: u64,
+        sbuf: &[u64],
+        s_qual: Option<&[u8]>,
+        s_header: Option<&[u8]>,
+        xbuf: Option<&[u64]>,
+        x_qual: Option<&[u8]>,
+        x_header: Option<&[u8]>,
+        s_mask: Option<&[u8]>,
+        x_mask: Option<&[u8]>,
     ) -> Result<()> {
         // Tracks the record start position
         self.starts.push(self.pos);
 
         // Write the flag (only if configured)
         if self.has_flags {
-            self.write_flag(record.flag.unwrap_or(0))?;
+            self.write_flag(flag.unwrap_or(0))?;
         }
 
         // Write the lengths
-        self.write_length(record.s_seq.len() as u64)?;
-        self.write_length(record.x_seq.map_or(0, <[u8]>::len) as u64)?;
+        self.write_length(slen)?;
+        self.write_length(xlen)?;
 
         // Write the primary sequence
         self.write_buffer(sbuf)?;
 
         // Write primary quality (only if configured)
         if self.has_qualities
-            && let Some(qual) = record.s_qual
+            && let Some(qual) = s_qual
         {
             self.write_u8buf(qual)?;
         }
 
+        // Write primary soft-mask (only if configured)
+        if self.has_mask {
+            self.write_mask(s_mask, slen)?;
+        }
+
         // Write primary header (only if configured)
         if self.has_headers
-            && let Some(sheader) = record.s_header
+            && let Some(sheader) = s_header
         {
             self.write_length(sheader.len() as u64)?;
             self.write_u8buf(sheader)?;
@@ -921,14 +2219,19 @@ impl BlockWriter {
 
         // Write extended quality (only if configured)
         if self.has_qualities
-            && let Some(qual) = record.x_qual
+            && let Some(qual) = x_qual
         {
             self.write_u8buf(qual)?;
         }
 
+        // Write extended soft-mask (only if configured)
+        if self.has_mask {
+            self.write_mask(x_mask, xlen)?;
+        }
+
         // Write extended header (only if configured)
         if self.has_headers
-            && let Some(xheader) = record.x_header
+            && let Some(xheader) = x_header
         {
             self.write_length(xheader.len() as u64)?;
             self.write_u8buf(xheader)?;
@@ -937,6 +2240,19 @@ impl BlockWriter {
         Ok(())
     }
 
+    /// Writes the soft-mask bitmap of a sequence of `len` nucleotides (all-uppercase if empty)
+    fn write_mask(&mut self, mask: Option<&[u8]>, len: u64) -> Result<()> {
+        match mask {
+            Some(mask) if !mask.is_empty() => self.write_u8buf(mask),
+            _ => {
+                let n_bytes = mask_len(len as usize);
+                self.ubuf.resize(self.ubuf.len() + n_bytes, 0);
+                self.pos += n_bytes;
+                Ok(())
+            }
+        }
+    }
+
     fn write_flag(&mut self, flag: u64) -> Result<()> {
         self.ubuf.write_u64::<LittleEndian>(flag)?;
         self.pos += 8;
@@ -964,10 +2280,13 @@ impl BlockWriter {
 
     fn flush_compressed<W: Write>(&mut self, inner: &mut W) -> Result<BlockHeader> {
         // Encode the block
-        copy_encode(self.ubuf.as_slice(), &mut self.zbuf, self.level)?;
+        self.zstd.compress(&self.ubuf, &mut self.zbuf, self.level)?;
 
         // Build a block header (this is variably sized in the compressed case)
-        let header = BlockHeader::new(self.zbuf.len() as u64, self.starts.len() as u32);
+        let header = BlockHeader::new(
+            self.zbuf.len() as u64,
+            block_record_count(self.starts.len())?,
+        );
 
         // Write the block header and compressed block
         header.write_bytes(inner)?;
@@ -978,7 +2297,10 @@ impl BlockWriter {
 
     fn flush_uncompressed<W: Write>(&mut self, inner: &mut W) -> Result<BlockHeader> {
         // Build a block header (this is static in size in the uncompressed case)
-        let header = BlockHeader::new(self.block_size as u64, self.starts.len() as u32);
+        let header = BlockHeader::new(
+            self.block_size as u64,
+            block_record_count(self.starts.len())?,
+        );
 
         // Write the block header and uncompressed block
         header.write_bytes(inner)?;
@@ -993,8 +2315,20 @@ impl BlockWriter {
             return Ok(BlockHeader::empty());
         }
 
+        // Store the common prefix of the headers once (the flag summary is taken before)
+        if let Some(layout) = self.header_prefix {
+            prefix::encode_block(
+                layout,
+                &self.ubuf,
+                &self.starts,
+                self.block_size,
+                &mut self.pbuf,
+            );
+            std::mem::swap(&mut self.ubuf, &mut self.pbuf);
+        }
+
         // Finish out the block with padding
-        let bytes_to_next_start = self.block_size - self.pos;
+        let bytes_to_next_start = self.block_size - self.ubuf.len();
         self.ubuf.write_all(&self.padding[..bytes_to_next_start])?;
 
         // Flush the block (implemented differently based on compression)
@@ -1010,6 +2344,24 @@ impl BlockWriter {
         Ok(header)
     }
 
+    /// Returns the bitwise OR and AND of the flags of the records in the block
+    ///
+    /// Returns `None` if flags are not stored or the block is empty.
+    fn flag_summary(&self) -> Option<(u64, u64)> {
+        if !self.has_flags || self.starts.is_empty() {
+            return None;
+        }
+        Some(
+            self.starts
+                .iter()
+                .fold((0, u64::MAX), |(flag_or, flag_and), &start| {
+                    // The flag is the first field of each record
+                    let flag = LittleEndian::read_u64(&self.ubuf[start..start + 8]);
+                    (flag_or | flag, flag_and & flag)
+                }),
+        )
+    }
+
     fn clear(&mut self) {
         self.pos = 0;
         self.starts.clear();
@@ -1022,9 +2374,14 @@ impl BlockWriter {
     /// Because both block sizes should be equivalent the process should take
     /// at most two steps.
     ///
-    /// I.e. the bytes can either all fit directly into self.ubuf or an intermediate
-    /// flush step is required.
-    fn ingest<W: Write>(&mut self, other: &mut Self, inner: &mut W) -> Result<BlockHeader> {
+    /// I.e. the bytes can either all fit directly into self.ubuf, or the records of other
+    /// that fit are moved over, self is flushed, and the rest of other fills the now empty
+    /// block. Returns the header and flag summary of the flushed block.
+    fn ingest<W: Write>(
+        &mut self,
+        other: &mut Self,
+        inner: &mut W,
+    ) -> Result<(BlockHeader, Option<(u64, u64)>)> {
         if self.block_size != other.block_size {
             return Err(
                 WriteError::IncompatibleBlockSizes(self.block_size, other.block_size).into(),
@@ -1036,13 +2393,22 @@ impl BlockWriter {
         // Quick ingestion (take all without flush)
         if other.pos <= remaining {
             self.ingest_all(other)?;
-            Ok(BlockHeader::empty())
-        } else {
-            self.ingest_subset(other)?;
-            let header = self.flush(inner)?;
-            self.ingest_all(other)?;
-            Ok(header)
+            return Ok((BlockHeader::empty(), None));
         }
+
+        // Move the records that fit (possibly none), then flush the full block
+        self.ingest_subset(other)?;
+        let flag_summary = self.flag_summary();
+        let header = self.flush(inner)?;
+
+        // The rest of other is at most a block, so it fits into the empty block
+        if other.pos > self.block_size {
+            return Err(
+                WriteError::RecordSizeExceedsMaximumBlockSize(other.pos, self.block_size).into(),
+            );
+        }
+        self.ingest_all(other)?;
+        Ok((header, flag_summary))
     }
 
     /// Takes all bytes from the other into self
@@ -1074,19 +2440,26 @@ impl BlockWriter {
         Ok(())
     }
 
-    /// Takes as many bytes as possible from the other into self
+    /// Takes as many whole records as possible from the other into self
+    ///
+    /// A record fits if it *ends* within the remaining space of self, where a record ends
+    /// at the start of the next one (or at the end of the data for the last record).
+    /// Nothing is taken if not even the first record fits.
     ///
     /// Do not call this directly - always go through `ingest`
     fn ingest_subset(&mut self, other: &mut Self) -> Result<()> {
         let remaining = self.block_size - self.pos;
-        let (start_index, end_byte) = other
+        let n_records = other
             .starts
             .iter()
-            .enumerate()
-            .take_while(|(_idx, x)| **x <= remaining)
-            .last()
-            .map(|(idx, x)| (idx, *x))
-            .unwrap();
+            .skip(1)
+            .chain(std::iter::once(&other.pos))
+            .take_while(|&&end| end <= remaining)
+            .count();
+        if n_records == 0 {
+            return Ok(());
+        }
+        let end_byte = other.starts.get(n_records).copied().unwrap_or(other.pos);
 
         // Drain bounded bytes from other (clearing them in the process)
         self.ubuf
@@ -1095,7 +2468,7 @@ impl BlockWriter {
         // Take starts from other (shifting them in the process)
         other
             .starts
-            .drain(0..start_index)
+            .drain(0..n_records)
             .for_each(|start| self.starts.push(start + self.pos));
 
         // Left shift all remaining starts in other
@@ -1130,11 +2503,19 @@ pub struct Encoder {
 
     /// Random Number Generator
     rng: SmallRng,
+
+    /// Seed of the random number generator
+    seed: u64,
 }
 
 impl Encoder {
     /// Initialize a new encoder with the given policy.
     pub fn with_policy(bitsize: BitSize, policy: Policy) -> Self {
+        Self::with_policy_seed(bitsize, policy, RNG_SEED)
+    }
+
+    /// Initialize a new encoder with the given policy and random seed.
+    pub fn with_policy_seed(bitsize: BitSize, policy: Policy, seed: u64) -> Self {
         Self {
             bitsize,
             policy,
@@ -1142,7 +2523,8 @@ impl Encoder {
             xbuffer: Vec::default(),
             s_ibuf: Vec::default(),
             x_ibuf: Vec::default(),
-            rng: SmallRng::seed_from_u64(RNG_SEED),
+            rng: SmallRng::seed_from_u64(seed),
+            seed,
         }
     }
 
@@ -1152,16 +2534,14 @@ impl Encoder {
     pub fn encode_single(&mut self, primary: &[u8]) -> Result<Option<&[u64]>> {
         // Fill the buffer with the bit representation of the nucleotides
         self.clear();
-        if self.bitsize.encode(primary, &mut self.sbuffer).is_err() {
-            self.clear();
-            if self
-                .policy
-                .handle(primary, &mut self.s_ibuf, &mut self.rng)?
-            {
-                self.bitsize.encode(&self.s_ibuf, &mut self.sbuffer)?;
-            } else {
-                return Ok(None);
-            }
+        if !self.policy.encode(
+            self.bitsize,
+            primary,
+            &mut self.s_ibuf,
+            &mut self.sbuffer,
+            &mut self.rng,
+        )? {
+            return Ok(None);
         }
         Ok(Some(&self.sbuffer))
     }
@@ -1174,23 +2554,22 @@ impl Encoder {
         primary: &[u8],
         extended: &[u8],
     ) -> Result<Option<(&[u64], &[u64])>> {
+        // Each mate is corrected on its own, so a valid mate is encoded only once
         self.clear();
-        if self.bitsize.encode(primary, &mut self.sbuffer).is_err()
-            || self.bitsize.encode(extended, &mut self.xbuffer).is_err()
-        {
-            self.clear();
-            if self
-                .policy
-                .handle(primary, &mut self.s_ibuf, &mut self.rng)?
-                && self
-                    .policy
-                    .handle(extended, &mut self.x_ibuf, &mut self.rng)?
-            {
-                self.bitsize.encode(&self.s_ibuf, &mut self.sbuffer)?;
-                self.bitsize.encode(&self.x_ibuf, &mut self.xbuffer)?;
-            } else {
-                return Ok(None);
-            }
+        if !self.policy.encode(
+            self.bitsize,
+            primary,
+            &mut self.s_ibuf,
+            &mut self.sbuffer,
+            &mut self.rng,
+        )? || !self.policy.encode(
+            self.bitsize,
+            extended,
+            &mut self.x_ibuf,
+            &mut self.xbuffer,
+            &mut self.rng,
+        )? {
+            return Ok(None);
         }
         Ok(Some((&self.sbuffer, &self.xbuffer)))
     }
@@ -1604,4 +2983,978 @@ mod tests {
 
         Ok(())
     }
+    #[test]
+    fn test_into_mmap_reader() -> super::Result<()> {
+        let header = FileHeaderBuilder::new()
+            .block(1024)
+            .qual(true)
+            .compressed(true)
+            .build();
+        let mut writer = WriterBuilder::default().header(header).build(Vec::new())?;
+        let seqs: Vec<Vec<u8>> = (0..100).map(|i| b"ACGTT".repeat(1 + i % 9)).collect();
+        for seq in &seqs {
+            let qual = vec![b'I'; seq.len()];
+            let record = SequencingRecordBuilder::default()
+                .s_seq(seq)
+                .s_qual(&qual)
+                .build()?;
+            writer.push(record)?;
+        }
+
+        let mut reader = writer.into_mmap_reader()?;
+        assert_eq!(reader.num_records()?, seqs.len());
+        let mut block = reader.new_block();
+        let mut idx = 0;
+        while reader.read_block_into(&mut block)? {
+            for record in block.iter() {
+                assert_eq!(record.decode_s_alloc()?, seqs[idx]);
+                assert_eq!(record.squal(), vec![b'I'; seqs[idx].len()].as_slice());
+                idx += 1;
+            }
+        }
+        assert_eq!(idx, seqs.len());
+        Ok(())
+    }
+
+    #[test]
+    fn test_length_policy_truncate() -> super::Result<()> {
+        let header = FileHeaderBuilder::new().qual(true).headers(true).build();
+        let mut writer = WriterBuilder::default()
+            .header(header)
+            .min_length(10)
+            .max_length(30)
+            .on_oversize(OnOversize::Truncate(30))
+            .build(Vec::new())?;
+
+        let mut pushed = Vec::new();
+        for (name, len) in [(b"short", 5), (b"exact", 30), (b"long0", 45)] {
+            let seq = b"ACGT".repeat(12)[..len].to_vec();
+            let qual = vec![b'I'; len];
+            let record = SequencingRecordBuilder::default()
+                .s_seq(&seq)
+                .s_qual(&qual)
+                .s_header(name)
+                .build()?;
+            pushed.push(writer.push(record)?);
+        }
+        assert_eq!(pushed, vec![false, true, true]);
+        assert_eq!(
+            writer.write_stats(),
+            WriteStats {
+                skipped_short: 1,
+                skipped_long: 0,
+                truncated: 1,
+            }
+        );
+
+        let mut reader = writer.into_mmap_reader()?;
+        let mut block = reader.new_block();
+        let mut records = Vec::new();
+        while reader.read_block_into(&mut block)? {
+            for record in block.iter() {
+                records.push((
+                    record.sheader().to_vec(),
+                    record.decode_s_alloc()?,
+                    record.squal().to_vec(),
+                ));
+            }
+        }
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1].0, b"long0");
+        assert_eq!(records[1].1, b"ACGT".repeat(12)[..30].to_vec());
+        assert_eq!(records[1].2, vec![b'I'; 30]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_length_policy_skip_and_error() -> super::Result<()> {
+        let seq = b"ACGT".repeat(10);
+        let record = SequencingRecordBuilder::default().s_seq(&seq).build()?;
+
+        let mut writer = WriterBuilder::default()
+            .max_length(20)
+            .on_oversize(OnOversize::Skip)
+            .build(Vec::new())?;
+        assert!(!writer.push(record)?);
+        assert_eq!(writer.write_stats().skipped_long, 1);
+
+        let mut writer = WriterBuilder::default().max_length(20).build(Vec::new())?;
+        assert!(writer.push(record).is_err());
+
+        // Records exceeding the block size are oversized even without a maximum length
+        let header = FileHeaderBuilder::new().block(16).build();
+        let mut writer = WriterBuilder::default()
+            .header(header)
+            .on_oversize(OnOversize::Skip)
+            .build(Vec::new())?;
+        assert!(!writer.push(record)?);
+        assert_eq!(writer.write_stats().skipped_long, 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_length_policy_invalid_truncate_length() {
+        use crate::error::Error;
+
+        for (len, max_length) in [(0, None), (0, Some(20)), (21, Some(20))] {
+            let mut builder = WriterBuilder::default().on_oversize(OnOversize::Truncate(len));
+            if let Some(max) = max_length {
+                builder = builder.max_length(max);
+            }
+            assert!(matches!(
+                builder.build(Vec::new()),
+                Err(Error::WriteError(WriteError::InvalidTruncateLength { .. }))
+            ));
+        }
+        let builder = WriterBuilder::default()
+            .max_length(20)
+            .on_oversize(OnOversize::Truncate(20));
+        assert!(builder.build(Vec::new()).is_ok());
+    }
+
+    #[test]
+    fn test_sparse_index_format_version() -> super::Result<()> {
+        for (stride, format) in [(1, 1), (4, 2)] {
+            let mut writer = WriterBuilder::default()
+                .index_stride(stride)
+                .build(Vec::new())?;
+            let record = SequencingRecordBuilder::default().s_seq(b"ACGT").build()?;
+            writer.push(record)?;
+            let reader = writer.into_mmap_reader()?;
+            assert_eq!(reader.header().format, format);
+            assert_eq!(reader.num_records()?, 1);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_flag_summary_format_version() -> super::Result<()> {
+        // summaries are only stored, and the format bumped, if the header stores flags
+        for (flags, format) in [(false, 1), (true, 2)] {
+            let header = FileHeaderBuilder::new().flags(flags).build();
+            let mut writer = WriterBuilder::default()
+                .header(header)
+                .index_flag_summary(true)
+                .build(Vec::new())?;
+            let record = SequencingRecordBuilder::default()
+                .s_seq(b"ACGT")
+                .flag(3)
+                .build()?;
+            writer.push(record)?;
+            let reader = writer.into_mmap_reader()?;
+            assert_eq!(reader.header().format, format);
+            assert_eq!(reader.num_records()?, 1);
+            assert_eq!(reader.index()?.ranges()[0].flag_or().is_some(), flags);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_tiny_blocks_record_counts() -> super::Result<()> {
+        // 24-byte records (two lengths and one sequence word) fill a 64-byte block twice
+        let header = FileHeaderBuilder::new().block(64).compressed(false).build();
+        let mut writer = WriterBuilder::default().header(header).build(Vec::new())?;
+        for _ in 0..21 {
+            let record = SequencingRecordBuilder::default().s_seq(b"ACGT").build()?;
+            writer.push(record)?;
+        }
+
+        // A record larger than the block is a clean error
+        let seq = [b'A'; 256];
+        let record = SequencingRecordBuilder::default().s_seq(&seq).build()?;
+        assert!(matches!(
+            writer.push(record),
+            Err(crate::Error::WriteError(
+                WriteError::RecordSizeExceedsMaximumBlockSize(_, 64)
+            ))
+        ));
+
+        let reader = writer.into_mmap_reader()?;
+        assert_eq!(reader.num_records()?, 21);
+        let index = reader.index()?;
+        assert_eq!(index.n_blocks(), 11);
+        assert!(
+            index.ranges()[..10]
+                .iter()
+                .all(|range| range.block_records == 2)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_block_record_count_overflow() {
+        assert_eq!(block_record_count(7).unwrap(), 7);
+        assert_eq!(block_record_count(u32::MAX as usize).unwrap(), u32::MAX);
+        #[cfg(target_pointer_width = "64")]
+        assert!(matches!(
+            block_record_count(u32::MAX as usize + 1),
+            Err(crate::Error::WriteError(
+                WriteError::BlockRecordCountOverflow(n)
+            )) if n == u32::MAX as usize + 1
+        ));
+    }
+
+    #[test]
+    fn test_advance_counts_overflow() {
+        let (mut bytes, mut records) = (100, 10);
+        advance_counts(&mut bytes, &mut records, 64, 2).unwrap();
+        assert_eq!((bytes, records), (100 + 64 + SIZE_BLOCK_HEADER, 12));
+
+        let mut bytes = usize::MAX - 8;
+        assert!(matches!(
+            advance_counts(&mut bytes, &mut records, 64, 2),
+            Err(crate::Error::WriteError(WriteError::CountOverflow(
+                "bytes written"
+            )))
+        ));
+
+        let (mut bytes, mut records) = (0, usize::MAX);
+        assert!(matches!(
+            advance_counts(&mut bytes, &mut records, 64, 2),
+            Err(crate::Error::WriteError(WriteError::CountOverflow(
+                "records written"
+            )))
+        ));
+    }
+
+    #[test]
+    fn test_into_mmap_reader_all_fields() -> super::Result<()> {
+        let header = FileHeaderBuilder::new()
+            .block(2048)
+            .flags(true)
+            .qual(true)
+            .headers(true)
+            .paired(true)
+            .build();
+        let mut writer = WriterBuilder::default().header(header).build(Vec::new())?;
+        let seq = |i: usize, base: &[u8]| base.repeat(1 + i % 7);
+        for i in 0..100 {
+            let (s, x) = (seq(i, b"ACGTT"), seq(i + 3, b"GGCA"));
+            let (squal, xqual) = (vec![b'A' + (i % 40) as u8; s.len()], vec![b'#'; x.len()]);
+            let (sheader, xheader) = (format!("read_{i}/1"), format!("read_{i}/2"));
+            let record = SequencingRecordBuilder::default()
+                .s_seq(&s)
+                .s_qual(&squal)
+                .s_header(sheader.as_bytes())
+                .x_seq(&x)
+                .x_qual(&xqual)
+                .x_header(xheader.as_bytes())
+                .flag(i as u64 * 11)
+                .build()?;
+            writer.push(record)?;
+        }
+
+        let mut reader = writer.into_mmap_reader()?;
+        assert_eq!(reader.num_records()?, 100);
+        let mut block = reader.new_block();
+        let mut n_records = 0;
+        while reader.read_block_into(&mut block)? {
+            for record in block.iter() {
+                let i = record.index() as usize;
+                let (s, x) = (seq(i, b"ACGTT"), seq(i + 3, b"GGCA"));
+                assert_eq!(record.flag(), Some(i as u64 * 11));
+                assert_eq!(record.decode_s_alloc()?, s);
+                assert_eq!(record.decode_x_alloc()?, x);
+                assert_eq!(record.squal(), vec![b'A' + (i % 40) as u8; s.len()]);
+                assert_eq!(record.xqual(), vec![b'#'; x.len()]);
+                assert_eq!(record.sheader(), format!("read_{i}/1").as_bytes());
+                assert_eq!(record.xheader(), format!("read_{i}/2").as_bytes());
+                n_records += 1;
+            }
+        }
+        assert_eq!(n_records, 100);
+        Ok(())
+    }
+
+    #[test]
+    fn test_soft_mask_roundtrip() -> super::Result<()> {
+        use crate::vbq::apply_soft_mask;
+
+        let header = FileHeaderBuilder::new()
+            .block(2048)
+            .flags(true)
+            .qual(true)
+            .headers(true)
+            .paired(true)
+            .masked(true)
+            .build();
+        let mut writer = WriterBuilder::default().header(header).build(Vec::new())?;
+        let seq = |i: usize, base: &[u8]| {
+            let mut seq = base.repeat(1 + i % 7);
+            seq.iter_mut()
+                .skip(i % 3)
+                .step_by(2 + i % 5)
+                .for_each(u8::make_ascii_lowercase);
+            seq
+        };
+        for i in 0..100 {
+            let (s, x) = (seq(i, b"ACGTT"), seq(i + 3, b"GGCA"));
+            let (squal, xqual) = (vec![b'I'; s.len()], vec![b'#'; x.len()]);
+            let record = SequencingRecordBuilder::default()
+                .s_seq(&s)
+                .s_qual(&squal)
+                .s_header(b"s")
+                .x_seq(&x)
+                .x_qual(&xqual)
+                .x_header(b"x")
+                .flag(i as u64)
+                .build()?;
+            assert!(writer.push(record)?);
+        }
+
+        let mut reader = writer.into_mmap_reader()?;
+        assert!(reader.header().masked);
+        let mut block = reader.new_block();
+        let mut n_records = 0;
+        while reader.read_block_into(&mut block)? {
+            for record in block.iter() {
+                let i = record.index() as usize;
+                let (s, x) = (seq(i, b"ACGTT"), seq(i + 3, b"GGCA"));
+                assert_eq!(record.smask().map(<[u8]>::len), Some(s.len().div_ceil(8)));
+                assert_eq!(record.decode_s_alloc()?, s.to_ascii_uppercase());
+
+                let mut sseq = Vec::new();
+                record.decode_s_masked(&mut sseq)?;
+                assert_eq!(sseq, s);
+
+                let mut xseq = record.decode_x_alloc()?;
+                apply_soft_mask(&mut xseq, record.xmask().unwrap());
+                assert_eq!(xseq, x);
+
+                assert_eq!(record.squal(), vec![b'I'; s.len()]);
+                assert_eq!((record.sheader(), record.xheader()), (&b"s"[..], &b"x"[..]));
+                n_records += 1;
+            }
+        }
+        assert_eq!(n_records, 100);
+        Ok(())
+    }
+
+    #[test]
+    fn test_unmasked_file_has_no_soft_mask() -> super::Result<()> {
+        let mut writer = WriterBuilder::default().build(Vec::new())?;
+        let record = SequencingRecordBuilder::default().s_seq(b"ACGT").build()?;
+        writer.push(record)?;
+
+        let mut reader = writer.into_mmap_reader()?;
+        assert!(!reader.header().masked);
+        let mut block = reader.new_block();
+        assert!(reader.read_block_into(&mut block)?);
+        let record = block.iter().next().unwrap();
+        assert_eq!(record.smask(), None);
+        let mut seq = Vec::new();
+        record.decode_s_masked(&mut seq)?;
+        assert_eq!(seq, b"ACGT");
+        Ok(())
+    }
+
+    #[test]
+    fn test_estimated_records_preallocates_ranges() -> super::Result<()> {
+        // 24-byte records (lengths and a single word), so 42 records fill each block
+        let header = FileHeaderBuilder::new().block(1024).build();
+        let mut writer = WriterBuilder::default()
+            .header(header)
+            .estimated_records(42_000)
+            .mean_sequence_length(32)
+            .build(Vec::new())?;
+        let capacity = writer.ranges.capacity();
+        assert!(capacity >= 1000);
+
+        let seq = |i: usize| -> Vec<u8> { (0..32).map(|j| b"ACGT"[(i + j) % 4]).collect() };
+        for i in 0..42_000 {
+            let s = seq(i);
+            let record = SequencingRecordBuilder::default().s_seq(&s).build()?;
+            writer.push(record)?;
+            assert_eq!(writer.ranges.capacity(), capacity);
+        }
+        writer.finish()?;
+        assert_eq!(writer.ranges.len(), 1000);
+        assert_eq!(writer.ranges.capacity(), capacity);
+
+        let reader = writer.into_mmap_reader()?;
+        assert_eq!(reader.num_records()?, 42_000);
+        assert_eq!(reader.load_index()?.n_blocks(), 1000);
+        for (i, record) in reader.head(42_000)?.iter().enumerate().step_by(997) {
+            assert_eq!(record.decode_s_alloc()?, seq(i));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_header_shorthands() -> super::Result<()> {
+        let header = FileHeaderBuilder::new()
+            .flags(true)
+            .paired(true)
+            .qual(true)
+            .headers(true)
+            .build();
+        let mut writer = WriterBuilder::default().header(header).build(Vec::new())?;
+        assert!(writer.write_paired_quality_with_headers(
+            7,
+            b"r1/1",
+            b"ACGTACGT",
+            b"IIIIIIII",
+            b"r1/2",
+            b"TTTTGGGG",
+            b"########",
+        )?);
+
+        let mut reader = writer.into_mmap_reader()?;
+        let mut block = reader.new_block();
+        assert!(reader.read_block_into(&mut block)?);
+        let record = block.iter().next().unwrap();
+        assert_eq!(record.flag(), Some(7));
+        assert_eq!(
+            (record.sheader(), record.xheader()),
+            (&b"r1/1"[..], &b"r1/2"[..])
+        );
+        assert_eq!(record.decode_x_alloc()?, b"TTTTGGGG");
+        assert_eq!(record.xqual(), b"########");
+        Ok(())
+    }
+
+    #[test]
+    fn test_header_shorthands_check_configuration() -> super::Result<()> {
+        let mut writer = WriterBuilder::default()
+            .header(FileHeaderBuilder::new().build())
+            .build(Vec::new())?;
+        assert!(matches!(
+            writer.write_nucleotides_with_header(0, b"r1", b"ACGT"),
+            Err(crate::Error::WriteError(WriteError::HeaderFlagNotSet))
+        ));
+
+        let header = FileHeaderBuilder::new().headers(true).build();
+        let mut writer = WriterBuilder::default().header(header).build(Vec::new())?;
+        assert!(writer.write_nucleotides_with_header(0, b"r1", b"ACGT")?);
+        assert!(matches!(
+            writer.write_nucleotides_quality_with_header(0, b"r1", b"ACGT", b"IIII"),
+            Err(crate::Error::WriteError(WriteError::QualityFlagNotSet))
+        ));
+        assert!(matches!(
+            writer.write_paired_with_headers(0, b"r1", b"ACGT", b"r2", b"ACGT"),
+            Err(crate::Error::WriteError(WriteError::PairedFlagNotSet))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_strict_mode_rejects_extra_data() -> super::Result<()> {
+        let record = SequencingRecordBuilder::default()
+            .s_seq(b"ACGT")
+            .s_qual(b"IIII")
+            .s_header(b"r1")
+            .build()?;
+
+        // Extra quality scores and headers are ignored by default
+        let mut writer = WriterBuilder::default().build(Vec::new())?;
+        assert!(!writer.is_strict());
+        assert!(writer.push(record)?);
+
+        let header = FileHeaderBuilder::new().qual(true).build();
+        let mut writer = WriterBuilder::default()
+            .header(header)
+            .strict_mode(true)
+            .build(Vec::new())?;
+        assert!(writer.is_strict());
+        assert!(matches!(
+            writer.push(record),
+            Err(crate::Error::WriteError(WriteError::HeaderFlagNotSet))
+        ));
+
+        let paired = SequencingRecordBuilder::default()
+            .s_seq(b"ACGT")
+            .s_qual(b"IIII")
+            .x_seq(b"TTTT")
+            .x_qual(b"IIII")
+            .build()?;
+        assert!(matches!(
+            writer.push(paired),
+            Err(crate::Error::WriteError(WriteError::PairedFlagNotSet))
+        ));
+
+        let matching = SequencingRecordBuilder::default()
+            .s_seq(b"ACGT")
+            .s_qual(b"IIII")
+            .build()?;
+        assert!(writer.push(matching)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_zstd_long_matching_roundtrip() -> super::Result<()> {
+        use rand::Rng;
+
+        // Blocks of 4MB, twice the default window of level 3
+        let header = FileHeaderBuilder::new()
+            .block(4 << 20)
+            .compressed(true)
+            .build();
+        let mut writer = WriterBuilder::default()
+            .header(header)
+            .zstd_long_matching(24)
+            .zstd_workers(2)
+            .build(Vec::new())?;
+        assert_eq!(writer.zstd_long_matching(), Some(24));
+        assert_eq!(writer.zstd_workers(), 2);
+
+        // Random sequences repeated a few MB apart
+        let mut rng = SmallRng::seed_from_u64(RNG_SEED);
+        let unique: Vec<Vec<u8>> = (0..4_000)
+            .map(|_| (0..1000).map(|_| b"ACGT"[rng.random_range(0..4)]).collect())
+            .collect();
+        let n_records = 20_000;
+        for idx in 0..n_records {
+            let record = SequencingRecordBuilder::default()
+                .s_seq(&unique[idx % unique.len()])
+                .build()?;
+            assert!(writer.push(record)?);
+        }
+
+        let mut reader = writer.into_mmap_reader()?;
+        assert!(reader.load_index()?.n_blocks() > 1);
+        let mut block = reader.new_block();
+        let mut n_read = 0;
+        while reader.read_block_into(&mut block)? {
+            for record in block.iter() {
+                let idx = record.index() as usize;
+                assert_eq!(record.decode_s_alloc()?, unique[idx % unique.len()]);
+                n_read += 1;
+            }
+        }
+        assert_eq!(n_read, n_records);
+        Ok(())
+    }
+
+    #[test]
+    fn test_block_flush_observer() -> super::Result<()> {
+        for compressed in [false, true] {
+            let infos = Arc::new(Mutex::new(Vec::new()));
+            let observed = Arc::clone(&infos);
+            let header = FileHeaderBuilder::new()
+                .block(256)
+                .compressed(compressed)
+                .build();
+            let mut writer = WriterBuilder::default()
+                .header(header)
+                .on_block_flush(Box::new(move |info| observed.lock().unwrap().push(info)))
+                .build(Vec::new())?;
+
+            // 24 bytes per record, so 10 records per block
+            for idx in 0..25 {
+                let seq = ingest_sequence(idx, 1);
+                writer.push(SequencingRecordBuilder::default().s_seq(&seq).build()?)?;
+            }
+            assert_eq!(infos.lock().unwrap().len(), 2);
+            assert_eq!(writer.current_block_fill(), (5 * 24, 256));
+
+            // Flushing the partial block on demand, then again without effect
+            writer.flush_block()?;
+            writer.flush_block()?;
+            assert_eq!(writer.current_block_fill(), (0, 256));
+            assert_eq!(infos.lock().unwrap().len(), 3);
+
+            // Blocks of an ingested writer are reported by the ingesting writer
+            let mut other = WriterBuilder::default()
+                .header(header)
+                .headless(true)
+                .build(Vec::new())?;
+            for idx in 25..40 {
+                let seq = ingest_sequence(idx, 1);
+                other.push(SequencingRecordBuilder::default().s_seq(&seq).build()?)?;
+            }
+            writer.ingest(&mut other)?;
+            let reader = writer.into_mmap_reader()?;
+
+            let infos = infos.lock().unwrap();
+            let index = reader.load_index()?;
+            assert_eq!(infos.len(), index.n_blocks());
+            for (info, range) in infos.iter().zip(&index.ranges) {
+                assert_eq!(info.records, range.block_records);
+                assert_eq!(info.compressed_size, range.len);
+                assert_eq!(info.uncompressed_size, 256);
+                if !compressed {
+                    assert_eq!(info.compressed_size, info.uncompressed_size);
+                }
+            }
+            let last = infos.last().unwrap();
+            assert_eq!(last.total_blocks, infos.len());
+            assert_eq!(last.total_records, 40);
+            assert_eq!(reader.num_records()?, 40);
+        }
+        Ok(())
+    }
+
+    /// Returns the sequence of record `idx` with `words` 2-bit words (one per 32 bases)
+    ///
+    /// The first 16 bases spell out `idx` in base 4, so every record is unique.
+    fn ingest_sequence(idx: usize, words: usize) -> Vec<u8> {
+        (0..32 * words)
+            .map(|pos| match pos {
+                0..16 => b"ACGT"[(idx >> (2 * pos)) & 3],
+                _ => b"ACGT"[pos % 4],
+            })
+            .collect()
+    }
+
+    /// Writes records of the given word counts into a writer over the same 256 byte blocks
+    ///
+    /// Each record takes 16 bytes for its lengths plus 8 bytes per word.
+    fn ingest_writer(headless: bool, words: &[usize], first: usize) -> Result<Writer<Vec<u8>>> {
+        let header = FileHeaderBuilder::new()
+            .block(256)
+            .compressed(false)
+            .build();
+        let mut writer = WriterBuilder::default()
+            .header(header)
+            .headless(headless)
+            .build(Vec::new())?;
+        for (idx, &n) in words.iter().enumerate() {
+            let seq = ingest_sequence(first + idx, n);
+            assert!(writer.push(SequencingRecordBuilder::default().s_seq(&seq).build()?)?);
+        }
+        Ok(writer)
+    }
+
+    /// Ingests records of the given word counts into a writer and checks the file reads back
+    fn check_ingest(dest_words: &[usize], sources: &[&[usize]]) -> Result<()> {
+        let mut dest = ingest_writer(false, dest_words, 0)?;
+        let mut expected: Vec<Vec<u8>> = dest_words
+            .iter()
+            .enumerate()
+            .map(|(idx, &n)| ingest_sequence(idx, n))
+            .collect();
+        for words in sources {
+            let mut source = ingest_writer(true, words, expected.len())?;
+            let first = expected.len();
+            expected.extend(
+                words
+                    .iter()
+                    .enumerate()
+                    .map(|(idx, &n)| ingest_sequence(first + idx, n)),
+            );
+            dest.ingest(&mut source)?;
+        }
+        dest.finish()?;
+
+        let mut reader = crate::vbq::MmapReader::from_bytes(std::mem::take(dest.by_ref()))?;
+        assert_eq!(reader.num_records()?, expected.len());
+        let mut block = reader.new_block();
+        let mut observed = Vec::new();
+        while reader.read_block_into(&mut block)? {
+            for record in block.iter() {
+                observed.push(crate::BinseqRecord::decode_s_alloc(&record)?);
+            }
+        }
+
+        // Complete blocks of a source are written before the partial block of the
+        // destination, so records are compared regardless of their order
+        observed.sort_unstable();
+        expected.sort_unstable();
+        assert_eq!(observed, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_ingest_record_larger_than_remaining() -> Result<()> {
+        // 200 bytes in the destination leave 56 bytes, the 96 byte record does not fit
+        check_ingest(&[23], &[&[10]])?;
+        // Neither does a record filling a whole block
+        check_ingest(&[23], &[&[30]])
+    }
+
+    #[test]
+    fn test_ingest_record_equal_to_remaining() -> Result<()> {
+        // The first 56 byte record exactly fills the remaining space, the second does not fit
+        check_ingest(&[23], &[&[5, 3]])?;
+        // A full block ingested into an empty one
+        check_ingest(&[], &[&[30], &[30]])
+    }
+
+    #[test]
+    fn test_ingest_multiple_small_records() -> Result<()> {
+        // Two 24 byte records fit into the remaining 56 bytes
+        check_ingest(&[23], &[&[1; 6]])?;
+        // Sources spanning several blocks
+        check_ingest(&[1, 2, 3], &[&[1; 40], &[4; 12], &[2; 7]])
+    }
+
+    #[test]
+    fn test_ingest_random_record_sizes() -> Result<()> {
+        use rand::{Rng, rngs::StdRng};
+
+        let mut rng = StdRng::seed_from_u64(RNG_SEED);
+        for _ in 0..50 {
+            let dest: Vec<usize> = (0..rng.random_range(0..10))
+                .map(|_| rng.random_range(1..=30))
+                .collect();
+            let sources: Vec<Vec<usize>> = (0..rng.random_range(1..5))
+                .map(|_| {
+                    (0..rng.random_range(0..20))
+                        .map(|_| rng.random_range(1..=30))
+                        .collect()
+                })
+                .collect();
+            let sources: Vec<&[usize]> = sources.iter().map(Vec::as_slice).collect();
+            check_ingest(&dest, &sources)?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_block_raw() -> Result<()> {
+        // uncompressed records: slen, xlen and one word of 2-bit bases each
+        let raw_block = |words: &[u64], block_size: usize| -> Vec<u8> {
+            let mut bytes = Vec::new();
+            for &word in words {
+                for value in [4, 0, word] {
+                    bytes.extend_from_slice(&value.to_le_bytes());
+                }
+            }
+            bytes.resize(block_size, 0);
+            bytes
+        };
+        let header = FileHeaderBuilder::new().block(64).build();
+        let mut buffer = Vec::new();
+        {
+            let mut writer = WriterBuilder::default().header(header).build(&mut buffer)?;
+            writer.push(SequencingRecordBuilder::default().s_seq(b"GGGG").build()?)?;
+
+            // ACGT, TGCA | CCCC
+            let first = raw_block(&[0b1110_0100, 0b0001_1011], 64);
+            writer.write_block_raw(&first, BlockHeader::new(64, 2))?;
+            let second = raw_block(&[0b0101_0101], 64);
+            writer.write_block_raw(&second, BlockHeader::new(64, 1))?;
+
+            // sizes must match the block header and the block size of the file
+            assert!(matches!(
+                writer.write_block_raw(&second[..32], BlockHeader::new(64, 1)),
+                Err(crate::Error::WriteError(
+                    WriteError::RawBlockSizeMismatch { .. }
+                ))
+            ));
+            assert!(matches!(
+                writer.write_block_raw(&second[..32], BlockHeader::new(32, 1)),
+                Err(crate::Error::WriteError(
+                    WriteError::RawBlockSizeMismatch { .. }
+                ))
+            ));
+
+            writer.push(SequencingRecordBuilder::default().s_seq(b"TTTT").build()?)?;
+            writer.finish()?;
+        }
+
+        let mut reader = MmapReader::from_bytes(buffer)?;
+        assert!(reader.validate_index()?.is_valid());
+        assert_eq!(reader.num_records()?, 5);
+        let mut block = reader.new_block();
+        let mut seqs = Vec::new();
+        while reader.read_block_into(&mut block)? {
+            for record in block.iter() {
+                seqs.push(record.decode_s_alloc()?);
+            }
+        }
+        assert_eq!(seqs, [&b"GGGG"[..], b"ACGT", b"TGCA", b"CCCC", b"TTTT"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_resume_from_checkpoint() -> super::Result<()> {
+        let seqs: Vec<Vec<u8>> = (0..200)
+            .map(|i| (0..30 + i % 17).map(|j| b"ACGT"[(i * 5 + j) % 4]).collect())
+            .collect();
+        fn push_all<W: Write>(
+            writer: &mut Writer<W>,
+            seqs: &[Vec<u8>],
+            range: std::ops::Range<usize>,
+        ) -> super::Result<()> {
+            let quals = [b'I'; 64];
+            for i in range {
+                let record = SequencingRecordBuilder::default()
+                    .s_seq(&seqs[i])
+                    .s_qual(&quals[..seqs[i].len()])
+                    .s_header(b"read")
+                    .flag(i as u64)
+                    .build()?;
+                writer.push(record)?;
+            }
+            Ok(())
+        }
+
+        for compressed in [false, true] {
+            let header = FileHeaderBuilder::new()
+                .block(512)
+                .qual(true)
+                .headers(true)
+                .flags(true)
+                .compressed(compressed)
+                .build();
+            let builder = || {
+                WriterBuilder::default()
+                    .header(header)
+                    .index_flag_summary(true)
+            };
+
+            let mut expected = Vec::new();
+            let mut writer = builder().build(&mut expected)?;
+            push_all(&mut writer, &seqs, 0..200)?;
+            writer.finish()?;
+            drop(writer);
+
+            for split in [0, 37, 123, 200] {
+                let mut output = Vec::new();
+                let mut writer = builder().build(&mut output)?;
+                push_all(&mut writer, &seqs, 0..split)?;
+                let checkpoint = writer.suspend()?;
+                assert_eq!(checkpoint.bytes_written(), output.len() as u64);
+
+                let checkpoint = WriterCheckpoint::from_bytes(&checkpoint.to_bytes())?;
+                let mut rest = Vec::new();
+                let mut writer = builder().resume(checkpoint).build(&mut rest)?;
+                push_all(&mut writer, &seqs, split..200)?;
+                writer.finish()?;
+                drop(writer);
+
+                output.extend_from_slice(&rest);
+                assert_eq!(output, expected);
+                let reader = MmapReader::from_bytes(output)?;
+                assert_eq!(reader.num_records()?, 200);
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_resume_header_mismatch() -> super::Result<()> {
+        use crate::error::Error;
+
+        let header = FileHeaderBuilder::new().block(512).build();
+        let mut output = Vec::new();
+        let mut writer = WriterBuilder::default().header(header).build(&mut output)?;
+        writer.push(SequencingRecordBuilder::default().s_seq(b"ACGT").build()?)?;
+        let checkpoint = writer.suspend()?;
+
+        let other = FileHeaderBuilder::new().block(1024).build();
+        let result = Writer::resume(Vec::new(), other, Policy::default(), &checkpoint);
+        assert!(matches!(
+            result,
+            Err(Error::WriteError(WriteError::CheckpointHeaderMismatch))
+        ));
+        assert!(Writer::resume(Vec::new(), header, Policy::default(), &checkpoint).is_ok());
+        Ok(())
+    }
+
+    /// Headers of a run of reads, with pathological headers in between
+    fn run_headers(i: usize) -> (Vec<u8>, Vec<u8>) {
+        match i % 50 {
+            // Empty headers (read back as the record index)
+            7 => (Vec::new(), Vec::new()),
+            // Headers equal to the common prefix of their block
+            11 => (
+                b"A00123:8:H7K2LDSX3:".to_vec(),
+                b"A00123:8:H7K2LDSX3:".to_vec(),
+            ),
+            // Bytes that are not UTF-8, leaving no common prefix
+            23 => (vec![0xff, 0xfe, 0x00, b':', 0x80], vec![0xc3]),
+            _ => {
+                let id = format!(
+                    "A00123:8:H7K2LDSX3:{}:{}:{}",
+                    1 + i % 4,
+                    1101 + i % 7,
+                    i * 13
+                );
+                (
+                    format!("{id} 1:N:0").into_bytes(),
+                    format!("{id} 2:N:0").into_bytes(),
+                )
+            }
+        }
+    }
+
+    /// Writes paired records with `run_headers`, returning the output
+    fn write_run(header: FileHeader, n_records: usize) -> super::Result<Vec<u8>> {
+        let mut output = Vec::new();
+        let mut writer = WriterBuilder::default().header(header).build(&mut output)?;
+        for i in 0..n_records {
+            let (sheader, xheader) = run_headers(i);
+            let (s, x) = (b"ACgTT".repeat(1 + i % 7), b"GGCA".repeat(1 + i % 5));
+            let (squal, xqual) = (vec![b'F'; s.len()], vec![b'#'; x.len()]);
+            let record = SequencingRecordBuilder::default()
+                .s_seq(&s)
+                .s_qual(&squal)
+                .s_header(&sheader)
+                .x_seq(&x)
+                .x_qual(&xqual)
+                .x_header(&xheader)
+                .flag(i as u64)
+                .build()?;
+            assert!(writer.push(record)?);
+        }
+        writer.finish()?;
+        drop(writer);
+        Ok(output)
+    }
+
+    /// Reads all records of a file with their soft-masks
+    fn read_run(bytes: Vec<u8>) -> super::Result<Vec<(crate::OwnedRecord, Vec<u8>, Vec<u8>)>> {
+        let mut reader = MmapReader::from_bytes(bytes)?;
+        let mut block = reader.new_block();
+        let mut records = Vec::new();
+        while reader.read_block_into(&mut block)? {
+            for record in block.iter() {
+                let smask = record.smask().unwrap_or_default().to_vec();
+                let xmask = record.xmask().unwrap_or_default().to_vec();
+                records.push((crate::OwnedRecord::from(record), smask, xmask));
+            }
+        }
+        Ok(records)
+    }
+
+    #[test]
+    fn test_header_prefix_roundtrip() -> super::Result<()> {
+        let n_records = 500;
+        for compressed in [false, true] {
+            let header = FileHeaderBuilder::new()
+                .block(1024)
+                .flags(true)
+                .qual(true)
+                .paired(true)
+                .masked(true)
+                .headers(true)
+                .compressed(compressed)
+                .build();
+            let plain = read_run(write_run(header, n_records)?)?;
+            let prefixed = read_run(write_run(header.with_header_prefix(true), n_records)?)?;
+
+            // Records are identical to those written without prefixes
+            assert_eq!(prefixed.len(), n_records);
+            assert_eq!(prefixed, plain);
+            for (i, (record, _, _)) in prefixed.iter().enumerate() {
+                let (sheader, xheader) = run_headers(i);
+                if sheader.is_empty() {
+                    assert_eq!(record.sheader(), i.to_string().as_bytes());
+                } else {
+                    assert_eq!(record.sheader(), sheader.as_slice());
+                    assert_eq!(record.xheader(), xheader.as_slice());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_header_prefix_shrinks_blocks() -> super::Result<()> {
+        let header = FileHeaderBuilder::new()
+            .block(4096)
+            .paired(true)
+            .headers(true)
+            .build();
+        let n_records = 2000;
+        let plain = MmapReader::from_bytes(write_run(header, n_records)?)?;
+        let prefixed =
+            MmapReader::from_bytes(write_run(header.with_header_prefix(true), n_records)?)?;
+        assert!(prefixed.header().header_prefix);
+        assert_eq!(prefixed.num_records()?, n_records);
+        assert!(prefixed.load_index()?.n_blocks() < plain.load_index()?.n_blocks());
+        Ok(())
+    }
 }
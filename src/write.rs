@@ -132,8 +132,11 @@ impl Format {
 /// | `compression_level(n)` | ignored | ignored | applied |
 /// | `block_size(n)` | ignored | applied | applied |
 /// | `bitsize(b)` | applied | applied | ignored |
+/// | `masked(true)` | ignored | applied | ignored |
 /// | `slen(n)` | **required** | ignored | ignored |
 /// | `xlen(n)` | required if paired | ignored | ignored |
+/// | `infer_slen_from_first(true)` | applied | ignored | ignored |
+/// | `length_mismatch_policy(p)` | applied | ignored | ignored |
 /// | `policy(p)` | applied | applied | ignored |
 /// | `headless(true)` | applied | applied | applied |
 #[derive(Debug, Clone)]
@@ -149,8 +152,11 @@ pub struct BinseqWriterBuilder {
     policy: Option<Policy>,
     headless: bool,
     bitsize: Option<BitSize>,
+    masked: bool,
     pub(crate) slen: Option<u32>,
     pub(crate) xlen: Option<u32>,
+    infer_slen: bool,
+    length_mismatch_policy: Option<bq::LengthMismatchPolicy>,
 }
 
 impl BinseqWriterBuilder {
@@ -169,8 +175,11 @@ impl BinseqWriterBuilder {
             policy: None,
             headless: false,
             bitsize: None,
+            masked: false,
             slen: None,
             xlen: None,
+            infer_slen: false,
+            length_mismatch_policy: None,
         }
     }
 
@@ -244,6 +253,15 @@ impl BinseqWriterBuilder {
         self
     }
 
+    /// Set whether to preserve lowercase (soft-masked) bases (only applies to VBQ)
+    ///
+    /// See [`vbq::FileHeaderBuilder::masked`].
+    #[must_use]
+    pub fn masked(mut self, masked: bool) -> Self {
+        self.masked = masked;
+        self
+    }
+
     /// Set the primary sequence length (required for BQ, ignored for VBQ/CBQ)
     #[must_use]
     pub fn slen(mut self, len: u32) -> Self {
@@ -258,6 +276,28 @@ impl BinseqWriterBuilder {
         self
     }
 
+    /// Take the BQ sequence lengths from the first record instead of `slen`/`xlen`
+    ///
+    /// The BQ header is held back until the first record is pushed, so `slen` and `xlen`
+    /// are not required. This cannot be combined with `headless(true)`, and the writer
+    /// cannot create headless buffers or ingest records before its first record. See
+    /// [`bq::WriterBuilder::infer_lengths`]. Ignored for VBQ/CBQ.
+    #[must_use]
+    pub fn infer_slen_from_first(mut self, infer: bool) -> Self {
+        self.infer_slen = infer;
+        self
+    }
+
+    /// Set how BQ records with sequences of the wrong length are handled
+    ///
+    /// See [`bq::LengthMismatchPolicy`]; the counts are available from
+    /// [`bq::Writer::write_stats`]. Ignored for VBQ/CBQ.
+    #[must_use]
+    pub fn length_mismatch_policy(mut self, policy: bq::LengthMismatchPolicy) -> Self {
+        self.length_mismatch_policy = Some(policy);
+        self
+    }
+
     /// Sets the corresponding values for this builder given an existing BQ header
     #[must_use]
     pub fn from_bq_header(header: bq::FileHeader) -> Self {
@@ -265,6 +305,8 @@ impl BinseqWriterBuilder {
             format: Format::Bq,
             slen: Some(header.slen),
             xlen: (header.xlen > 0).then_some(header.xlen),
+            infer_slen: false,
+            length_mismatch_policy: None,
             bitsize: Some(header.bits),
             paired: header.is_paired(),
             flags: header.flags,
@@ -275,6 +317,7 @@ impl BinseqWriterBuilder {
             block_size: None,
             headless: false,
             policy: None,
+            masked: false,
         }
     }
 
@@ -285,6 +328,8 @@ impl BinseqWriterBuilder {
             format: Format::Vbq,
             slen: None,
             xlen: None,
+            infer_slen: false,
+            length_mismatch_policy: None,
             flags: header.flags,
             quality: header.qual,
             paired: header.paired,
@@ -295,6 +340,7 @@ impl BinseqWriterBuilder {
             policy: None,
             compression_level: None,
             headless: false,
+            masked: header.masked,
         }
     }
 
@@ -312,9 +358,12 @@ impl BinseqWriterBuilder {
             compression: false,
             slen: None,
             xlen: None,
+            infer_slen: false,
+            length_mismatch_policy: None,
             bitsize: None,
             policy: None,
             headless: false,
+            masked: false,
         }
     }
 
@@ -378,6 +427,9 @@ impl BinseqWriterBuilder {
     }
 
     fn build_bq<W: Write>(self, writer: W) -> Result<BinseqWriter<W>> {
+        if self.infer_slen {
+            return self.build_bq_inferred(writer);
+        }
         let slen = self.slen.ok_or(WriteError::MissingSequenceLength {
             exp_primary: true,
             exp_extended: self.paired,
@@ -409,6 +461,29 @@ impl BinseqWriterBuilder {
             .header(header)
             .policy(self.policy.unwrap_or_default())
             .headless(self.headless)
+            .length_mismatch_policy(self.length_mismatch_policy.unwrap_or_default())
+            .build(writer)?;
+
+        Ok(BinseqWriter::Bq(inner))
+    }
+
+    /// Builds a BQ writer whose sequence lengths are fixed by the first record
+    fn build_bq_inferred<W: Write>(self, writer: W) -> Result<BinseqWriter<W>> {
+        // The lengths are placeholders, a non-zero `xlen` only marks the file as paired
+        let mut header_builder = bq::FileHeaderBuilder::new()
+            .slen(1)
+            .xlen(u32::from(self.paired || self.xlen.is_some_and(|x| x > 0)))
+            .flags(self.flags);
+        if let Some(bitsize) = self.bitsize {
+            header_builder = header_builder.bitsize(bitsize);
+        }
+
+        let inner = bq::WriterBuilder::default()
+            .header(header_builder.build()?)
+            .policy(self.policy.unwrap_or_default())
+            .headless(self.headless)
+            .length_mismatch_policy(self.length_mismatch_policy.unwrap_or_default())
+            .infer_lengths(true)
             .build(writer)?;
 
         Ok(BinseqWriter::Bq(inner))
@@ -420,7 +495,8 @@ impl BinseqWriterBuilder {
             .qual(self.quality)
             .headers(self.headers)
             .flags(self.flags)
-            .compressed(self.compression);
+            .compressed(self.compression)
+            .masked(self.masked);
 
         if let Some(block_size) = self.block_size {
             header_builder = header_builder.block(block_size as u64);
@@ -628,19 +704,33 @@ impl<W: Write> BinseqWriter<W> {
     pub fn new_headless_buffer(&self) -> Result<BinseqWriter<Vec<u8>>> {
         match self {
             Self::Bq(w) => {
+                if w.is_header_pending() {
+                    return Err(WriteError::LengthsNotInferred(
+                        "cannot create headless buffers before the first record",
+                    )
+                    .into());
+                }
                 let inner = bq::WriterBuilder::default()
                     .header(w.header())
                     .policy(w.policy())
+                    .policy_seed(w.policy_seed())
                     .headless(true)
+                    .strict_mode(w.is_strict())
+                    .length_mismatch_policy(w.length_mismatch_policy())
                     .build(Vec::new())?;
                 Ok(BinseqWriter::Bq(inner))
             }
             Self::Vbq(w) => {
-                let inner = vbq::WriterBuilder::default()
+                let mut builder = vbq::WriterBuilder::default()
                     .header(w.header())
                     .policy(w.policy())
-                    .headless(true)
-                    .build(Vec::new())?;
+                    .policy_seed(w.policy_seed())
+                    .zstd_workers(w.zstd_workers())
+                    .headless(true);
+                if let Some(window_log) = w.zstd_long_matching() {
+                    builder = builder.zstd_long_matching(window_log);
+                }
+                let inner = builder.build(Vec::new())?;
                 Ok(BinseqWriter::Vbq(inner))
             }
             Self::Cbq(w) => {
@@ -651,10 +741,138 @@ impl<W: Write> BinseqWriter<W> {
     }
 }
 
+/// Format-agnostic writing of records
+///
+/// Implemented by the writers of every BINSEQ format and by [`BinseqWriter`], so that
+/// converters can hold a `Box<dyn RecordWriter>` and choose the output format at runtime.
+///
+/// Fields that a format cannot store (e.g. quality scores in BQ) are dropped, or rejected in
+/// strict mode (see [`bq::WriterBuilder::strict_mode`] and
+/// [`vbq::WriterBuilder::strict_mode`]). Sequence length requirements of the format still
+/// apply, so BQ writers reject sequences that do not match the lengths of the header.
+///
+/// # Examples
+///
+/// ```rust
+/// use binseq::{RecordWriter, bq, vbq};
+///
+/// # fn main() -> binseq::Result<()> {
+/// let mut output = Vec::new();
+/// let use_vbq = true;
+/// let mut writer: Box<dyn RecordWriter + '_> = if use_vbq {
+///     let header = vbq::FileHeaderBuilder::new().qual(true).build();
+///     Box::new(vbq::WriterBuilder::default().header(header).build(&mut output)?)
+/// } else {
+///     let header = bq::FileHeaderBuilder::new().slen(8).build()?;
+///     Box::new(bq::WriterBuilder::default().header(header).build(&mut output)?)
+/// };
+/// writer.write(None, None, b"ACGTACGT", Some(b"IIIIIIII".as_slice()))?;
+/// writer.finish()?;
+/// # Ok(())
+/// # }
+/// ```
+pub trait RecordWriter {
+    /// Writes a record
+    ///
+    /// Returns `Ok(false)` if the record was skipped by the invalid nucleotide policy.
+    fn push(&mut self, record: SequencingRecord) -> Result<bool>;
+
+    /// Writes a single-end record
+    ///
+    /// Shorthand for [`push`](Self::push) with a record built from its fields.
+    fn write(
+        &mut self,
+        flag: Option<u64>,
+        header: Option<&[u8]>,
+        seq: &[u8],
+        qual: Option<&[u8]>,
+    ) -> Result<bool> {
+        self.push(SequencingRecord::new(
+            seq, qual, header, None, None, None, flag,
+        ))
+    }
+
+    /// Writes a paired record
+    ///
+    /// Shorthand for [`push`](Self::push) with a record built from its fields.
+    #[allow(clippy::too_many_arguments)]
+    fn write_paired(
+        &mut self,
+        flag: Option<u64>,
+        s_header: Option<&[u8]>,
+        s_seq: &[u8],
+        s_qual: Option<&[u8]>,
+        x_header: Option<&[u8]>,
+        x_seq: &[u8],
+        x_qual: Option<&[u8]>,
+    ) -> Result<bool> {
+        self.push(SequencingRecord::new(
+            s_seq,
+            s_qual,
+            s_header,
+            Some(x_seq),
+            x_qual,
+            x_header,
+            flag,
+        ))
+    }
+
+    /// Writes any buffered data
+    ///
+    /// For VBQ and CBQ writers this also writes the embedded index. For BQ writers it is
+    /// equivalent to a flush.
+    fn finish(&mut self) -> Result<()>;
+}
+impl<W: Write> RecordWriter for bq::Writer<W> {
+    fn push(&mut self, record: SequencingRecord) -> Result<bool> {
+        bq::Writer::push(self, record)
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.flush()
+    }
+}
+impl<W: Write> RecordWriter for vbq::Writer<W> {
+    fn push(&mut self, record: SequencingRecord) -> Result<bool> {
+        vbq::Writer::push(self, record)
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        vbq::Writer::finish(self)
+    }
+}
+impl RecordWriter for vbq::ShardedWriter {
+    fn push(&mut self, record: SequencingRecord) -> Result<bool> {
+        vbq::ShardedWriter::push(self, record)
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        vbq::ShardedWriter::finish(self)
+    }
+}
+impl<W: Write> RecordWriter for cbq::ColumnarBlockWriter<W> {
+    fn push(&mut self, record: SequencingRecord) -> Result<bool> {
+        cbq::ColumnarBlockWriter::push(self, record)
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        cbq::ColumnarBlockWriter::finish(self)
+    }
+}
+impl<W: Write> RecordWriter for BinseqWriter<W> {
+    fn push(&mut self, record: SequencingRecord) -> Result<bool> {
+        BinseqWriter::push(self, record)
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        BinseqWriter::finish(self)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::SequencingRecordBuilder;
+    use crate::{BinseqRecord, Error, SequencingRecordBuilder};
     use std::io::Cursor;
 
     #[test]
@@ -1631,4 +1849,165 @@ mod tests {
         writer.finish()?;
         Ok(())
     }
+
+    /// Writes the same record stream through any writer
+    fn write_stream(writer: &mut dyn RecordWriter) -> Result<()> {
+        for (idx, seq) in [b"ACGTACGTACGT", b"TTTTGGGGCCCC", b"GATTACAGATTA"]
+            .iter()
+            .enumerate()
+        {
+            let header = format!("seq{idx}");
+            let written = writer.write(
+                Some(idx as u64),
+                Some(header.as_bytes()),
+                *seq,
+                Some(b"IIIIIIIIIIII".as_slice()),
+            )?;
+            assert!(written);
+        }
+        writer.finish()
+    }
+
+    #[test]
+    fn test_record_writer_interchangeable() -> Result<()> {
+        let (mut bq_bytes, mut vbq_bytes) = (Vec::new(), Vec::new());
+        {
+            let header = bq::FileHeaderBuilder::new().slen(12).flags(true).build()?;
+            let bq_writer = bq::WriterBuilder::default()
+                .header(header)
+                .build(&mut bq_bytes)?;
+            let header = vbq::FileHeaderBuilder::new()
+                .qual(true)
+                .headers(true)
+                .flags(true)
+                .build();
+            let vbq_writer = vbq::WriterBuilder::default()
+                .header(header)
+                .build(&mut vbq_bytes)?;
+            let writers: Vec<Box<dyn RecordWriter + '_>> =
+                vec![Box::new(bq_writer), Box::new(vbq_writer)];
+            for mut writer in writers {
+                write_stream(writer.as_mut())?;
+            }
+        }
+
+        let bq_reader = bq::MmapReader::from_bytes(bq_bytes)?;
+        let mut vbq_reader = vbq::MmapReader::from_bytes(vbq_bytes)?;
+        let mut block = vbq_reader.new_block();
+        assert!(vbq_reader.read_block_into(&mut block)?);
+        assert_eq!(bq_reader.num_records(), 3);
+        assert_eq!(block.n_records(), 3);
+        for (idx, vbq_record) in block.iter().enumerate() {
+            let bq_record = bq_reader.get(idx)?;
+            assert_eq!(bq_record.decode_s_alloc()?, vbq_record.decode_s_alloc()?);
+            assert_eq!(bq_record.flag(), Some(idx as u64));
+            assert_eq!(vbq_record.flag(), Some(idx as u64));
+            assert_eq!(vbq_record.sheader(), format!("seq{idx}").as_bytes());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_bq_record_writer_strict_mode() -> Result<()> {
+        let header = bq::FileHeaderBuilder::new().slen(4).build()?;
+        let mut writer = bq::WriterBuilder::default()
+            .header(header)
+            .strict_mode(true)
+            .build(Vec::new())?;
+        let writer: &mut dyn RecordWriter = &mut writer;
+
+        assert!(writer.write(None, None, b"ACGT", None)?);
+        assert!(matches!(
+            writer.write(None, None, b"ACGT", Some(b"IIII".as_slice())),
+            Err(Error::WriteError(WriteError::UnsupportedField("quality")))
+        ));
+        assert!(matches!(
+            writer.write(None, Some(b"seq".as_slice()), b"ACGT", None),
+            Err(Error::WriteError(WriteError::UnsupportedField("header")))
+        ));
+        assert!(matches!(
+            writer.write_paired(None, None, b"ACGT", None, None, b"ACGT", None),
+            Err(Error::WriteError(WriteError::PairedFlagNotSet))
+        ));
+        assert!(matches!(
+            writer.write(None, None, b"ACG", None),
+            Err(Error::WriteError(WriteError::SequenceTooShort { .. }))
+        ));
+        writer.finish()?;
+
+        // Rejected records leave no partial data behind
+        let bytes = {
+            let header = bq::FileHeaderBuilder::new().slen(4).flags(true).build()?;
+            let mut writer = bq::WriterBuilder::default()
+                .header(header)
+                .strict_mode(true)
+                .build(Vec::new())?;
+            assert!(RecordWriter::write(
+                &mut writer,
+                Some(1),
+                None,
+                b"ACGT",
+                None
+            )?);
+            assert!(RecordWriter::write(&mut writer, Some(2), None, b"ACGTA", None).is_err());
+            assert!(RecordWriter::write(
+                &mut writer,
+                Some(3),
+                None,
+                b"TTTT",
+                None
+            )?);
+            writer.into_inner()
+        };
+        let reader = bq::MmapReader::from_bytes(bytes)?;
+        assert_eq!(reader.num_records(), 2);
+        assert_eq!(reader.get(1)?.flag(), Some(3));
+        assert_eq!(reader.get(1)?.decode_s_alloc()?, b"TTTT");
+        Ok(())
+    }
+
+    #[test]
+    fn test_bq_infer_slen_from_first() -> Result<()> {
+        let mut writer = BinseqWriterBuilder::new(Format::Bq)
+            .infer_slen_from_first(true)
+            .length_mismatch_policy(bq::LengthMismatchPolicy::TruncatePad { pad_base: b'A' })
+            .build(Vec::new())?;
+        assert!(matches!(
+            writer.new_headless_buffer(),
+            Err(Error::WriteError(WriteError::LengthsNotInferred(_)))
+        ));
+        for seq in [&b"ACGTACGTAC"[..], b"ACGTAC", b"ACGTACGTACGTAC"] {
+            let record = SequencingRecordBuilder::default().s_seq(seq).build()?;
+            assert!(writer.push(record)?);
+        }
+        writer.finish()?;
+        let mut buffer = writer.new_headless_buffer()?;
+        let BinseqWriter::Bq(inner) = &writer else {
+            unreachable!()
+        };
+        assert_eq!(inner.write_stats().truncated, 1);
+        assert_eq!(inner.write_stats().padded, 1);
+        let record = SequencingRecordBuilder::default().s_seq(b"TT").build()?;
+        assert!(buffer.push(record)?);
+        writer.ingest(&mut buffer)?;
+
+        let BinseqWriter::Bq(inner) = writer else {
+            unreachable!()
+        };
+        let reader = bq::MmapReader::from_bytes(inner.into_inner())?;
+        assert_eq!(reader.header().slen, 10);
+        let sequences = (0..reader.num_records())
+            .map(|idx| reader.get(idx)?.decode_s_alloc())
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(
+            sequences,
+            [
+                &b"ACGTACGTAC"[..],
+                b"ACGTACAAAA",
+                b"ACGTACGTAC",
+                b"TTAAAAAAAA"
+            ]
+        );
+        Ok(())
+    }
 }
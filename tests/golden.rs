@@ -0,0 +1,122 @@
+//! Forward-compatibility checks against the files in `data/`
+//!
+//! The expected values were obtained by decoding the files independently of this crate.
+//! A change to any of them means files written by earlier releases no longer read back
+//! the same.
+
+use binseq::{BinseqRecord, BitSize, Result, bq, vbq};
+
+const BQ_PATH: &str = "./data/subset.bq";
+const VBQ_PATH: &str = "./data/subset.vbq";
+
+/// Counts of A, C, G and T over all primary sequences
+fn count_bases(counts: &mut [usize; 4], seq: &[u8]) {
+    for &base in seq {
+        match base {
+            b'A' => counts[0] += 1,
+            b'C' => counts[1] += 1,
+            b'G' => counts[2] += 1,
+            b'T' => counts[3] += 1,
+            _ => panic!("unexpected base {}", base as char),
+        }
+    }
+}
+
+#[test]
+fn test_golden_bq() -> Result<()> {
+    let reader = bq::MmapReader::new(BQ_PATH)?;
+    let header = reader.header();
+    assert_eq!(header.slen, 28);
+    assert_eq!(header.xlen, 90);
+    assert_eq!(header.bits, BitSize::Two);
+    assert!(header.flags);
+    assert_eq!(reader.num_records(), 24890);
+
+    let mut sbuf = Vec::new();
+    let mut xbuf = Vec::new();
+    let first = reader.get(0)?;
+    first.decode_pair(&mut sbuf, &mut xbuf)?;
+    assert_eq!(first.flag(), Some(0));
+    assert_eq!(sbuf, b"CGGTATTGTTAGCGCCGTCATTATCCAA");
+    assert_eq!(
+        xbuf,
+        b"ACGCGGTTAGCACGTACAAGTAGGCTCTTGCTATGCACTCTTGTGCTTAGCTCTGAAACTCGTGTCCTGTGGGCAAAGCCGGTCCTAGCA"
+    );
+
+    let last = reader.get(reader.num_records() - 1)?;
+    sbuf.clear();
+    xbuf.clear();
+    last.decode_pair(&mut sbuf, &mut xbuf)?;
+    assert_eq!(sbuf, b"CCGGCTATCCTCACTTAAAGGATTCGAG");
+    assert_eq!(
+        xbuf,
+        b"ACGCGGTTAGCACGTACCAACGGGAACTTGCTATGCACTCTTGTGCTTAGCTCTGAAACCCGAAGGCCTCAACCGCGCCCGGTCCTAGCA"
+    );
+
+    let mut counts = [0; 4];
+    for idx in 0..reader.num_records() {
+        sbuf.clear();
+        reader.get(idx)?.decode_s(&mut sbuf)?;
+        count_bases(&mut counts, &sbuf);
+    }
+    assert_eq!(counts, [181_311, 167_235, 149_906, 198_468]);
+    Ok(())
+}
+
+#[test]
+fn test_golden_vbq() -> Result<()> {
+    let mut reader = vbq::MmapReader::new(VBQ_PATH)?;
+    let header = reader.header();
+    assert_eq!(header.block, 131_072);
+    assert!(header.qual);
+    assert!(header.compressed);
+    assert!(header.paired);
+    assert!(header.headers);
+    assert!(!header.flags);
+    assert_eq!(header.bits, BitSize::Two);
+    assert_eq!(reader.num_records()?, 24893);
+    assert!(reader.validate_index()?.is_valid());
+
+    let mut block = reader.new_block();
+    let mut num_blocks = 0;
+    let mut num_records = 0;
+    let mut counts = [0; 4];
+    let mut qual_sum = 0u64;
+    let mut sbuf = Vec::new();
+    let mut xbuf = Vec::new();
+    while reader.read_block_into(&mut block)? {
+        num_blocks += 1;
+        for record in block.iter() {
+            sbuf.clear();
+            xbuf.clear();
+            record.decode_pair(&mut sbuf, &mut xbuf)?;
+            if num_records == 0 {
+                assert_eq!(sbuf, b"CGGTATTGTTAGCGCCGTCATTATCCAA");
+                assert_eq!(record.squal(), b"IIIIIIIIIIIIIIIIIIIIIIIIIIII");
+                assert_eq!(
+                    record.sheader(),
+                    b"LH00181:37:22GTKCLT4:1:1101:41752:1056 1:N:0:CGCGCACTTA+ANAATACAGG"
+                );
+                assert_eq!(
+                    record.xheader(),
+                    b"LH00181:37:22GTKCLT4:1:1101:41752:1056 2:N:0:CGCGCACTTA+ANAATACAGG"
+                );
+            }
+            count_bases(&mut counts, &sbuf);
+            qual_sum += record.squal().iter().map(|&q| u64::from(q)).sum::<u64>();
+            num_records += 1;
+        }
+    }
+    assert_eq!(num_blocks, 60);
+    assert_eq!(num_records, 24893);
+    assert_eq!(counts, [181_328, 167_246, 149_934, 198_496]);
+    assert_eq!(qual_sum, 50_637_702);
+
+    // last record
+    assert_eq!(sbuf, b"ACAACTTTCCCATAATCTTCTACTCATC");
+    assert_eq!(
+        xbuf,
+        b"ACGCGGTTAGCACGTACCAGTGAGTGGCTATGCTGTTTCCAGCTTAGCTCTTAAACCGAGTCTCGACGCTCAGGCCCGGTCCTAGCAAGA"
+    );
+    Ok(())
+}
@@ -0,0 +1,84 @@
+//! Write/read round trips over the combinations of BINSEQ format options
+
+use binseq::testing::{
+    DatasetConfig, LengthDistribution, random_dataset, read_bq, read_parallel, read_vbq, write_bq,
+    write_vbq,
+};
+use binseq::{BitSize, Result, vbq};
+
+const NUM_RECORDS: usize = 500;
+const NUM_THREADS: usize = 4;
+
+/// Every combination of the optional record fields
+fn configs(lengths: LengthDistribution) -> impl Iterator<Item = DatasetConfig> {
+    (0..16u8).map(move |bits| {
+        DatasetConfig::new(lengths)
+            .paired(bits & 1 != 0)
+            .quality(bits & 2 != 0)
+            .headers(bits & 4 != 0)
+            .flags(bits & 8 != 0)
+            .seed(u64::from(bits))
+    })
+}
+
+fn with_bitsize(config: DatasetConfig, bitsize: BitSize) -> DatasetConfig {
+    match bitsize {
+        BitSize::Two => config,
+        BitSize::Four => config.ambiguous(0.05),
+    }
+}
+
+#[test]
+fn test_bq_matrix() -> Result<()> {
+    for bitsize in [BitSize::Two, BitSize::Four] {
+        // BQ stores neither quality scores nor headers
+        for config in configs(LengthDistribution::Fixed(75)).filter(|c| !c.quality && !c.headers) {
+            let config = with_bitsize(config, bitsize);
+            let records = random_dataset(NUM_RECORDS, &config);
+            let bytes = write_bq(&records, config.bq_header(bitsize)?)?;
+
+            assert_eq!(read_bq(bytes.clone(), &config)?, records, "{config:?}");
+            assert_eq!(
+                read_parallel(bytes, &config, NUM_THREADS)?,
+                records,
+                "{config:?}"
+            );
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_vbq_matrix() -> Result<()> {
+    let lengths = LengthDistribution::Uniform { min: 1, max: 300 };
+    for bitsize in [BitSize::Two, BitSize::Four] {
+        for compressed in [false, true] {
+            for config in configs(lengths) {
+                let config = with_bitsize(config, bitsize);
+                let records = random_dataset(NUM_RECORDS, &config);
+                // small blocks so that the index spans several entries
+                let mut header = config.vbq_header(bitsize, compressed);
+                header.block = 16 * 1024;
+                let bytes = write_vbq(&records, header)?;
+
+                let reader = vbq::MmapReader::from_bytes(bytes.clone())?;
+                assert_eq!(reader.num_records()?, NUM_RECORDS, "{config:?}");
+                assert!(reader.validate_index()?.is_valid(), "{config:?}");
+
+                assert_eq!(read_vbq(bytes.clone(), &config)?, records, "{config:?}");
+                assert_eq!(
+                    read_parallel(bytes, &config, NUM_THREADS)?,
+                    records,
+                    "{config:?}"
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_bq_rejects_variable_lengths() {
+    let config = DatasetConfig::new(LengthDistribution::Uniform { min: 10, max: 20 });
+    assert!(config.bq_header(BitSize::Two).is_err());
+}